@@ -0,0 +1,29 @@
+//! Record the git commit being built, reported by `--version --json`
+//!
+//! `SHINKANSEN_GIT_SHA` overrides the detected commit, e.g. for builds from a
+//! source archive without `.git`.
+
+use std::path::Path;
+use std::process::Command;
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=SHINKANSEN_GIT_SHA");
+    // Only watch paths that exist; a missing one would rerun this on every build
+    for path in [".git/HEAD", ".git/refs/heads", ".git/packed-refs"] {
+        if Path::new(path).exists() {
+            println!("cargo:rerun-if-changed={}", path);
+        }
+    }
+
+    let sha = std::env::var("SHINKANSEN_GIT_SHA").ok().or_else(|| {
+        Command::new("git")
+            .args(["rev-parse", "--short=12", "HEAD"])
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+    });
+    if let Some(sha) = sha.filter(|sha| !sha.is_empty()) {
+        println!("cargo:rustc-env=SHINKANSEN_GIT_SHA={}", sha);
+    }
+}
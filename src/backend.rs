@@ -0,0 +1,402 @@
+//! Where rendered files are stored
+//!
+//! Every output goes through an [`OutputBackend`]. The command line uses
+//! [`default_backend`]: stdout for `-`, the local filesystem for paths, with
+//! the `cloud` feature an object store for `s3://bucket/prefix/` or
+//! `gs://bucket/prefix/` (uploaded through the `aws` or `gcloud` executable,
+//! so their usual credentials and configuration apply), and with the
+//! `clipboard` feature the system clipboard for `-o clipboard`. Embedders can pass
+//! their own backend to [`crate::processing::process_inputs_to`], e.g. to
+//! collect outputs in memory or in an [`ArchiveBackend`].
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::cli::Cli;
+use crate::error::{Result, ShinkansenError};
+use crate::fileio::{Compression, IoOptions};
+
+/// The path a backend receives for output destined for stdout
+pub const STDOUT_PATH: &str = "-";
+
+/// The `-o` value that copies the output to the system clipboard
+pub const CLIPBOARD_OUTPUT: &str = "clipboard";
+
+/// The path a backend receives for output destined for the clipboard
+pub const CLIPBOARD_PATH: &str = "clipboard:";
+
+/// What is known about an output besides its path and content
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputMeta {
+    /// The template the output was rendered from (`stdin` for standard input)
+    pub input: PathBuf,
+    /// The format the content is already compressed with, if any
+    pub compression: Option<Compression>,
+}
+
+/// Stores the content rendered for an output path
+///
+/// Paths are those the run resolved: files below `-o`, the input itself with
+/// `--in-place`, an object URL, or [`STDOUT_PATH`]. Outputs are written one at
+/// a time and in input order. Options that read outputs back (`--check`,
+/// `--diff`, stamps, managed blocks, staging) still look at the filesystem.
+pub trait OutputBackend {
+    /// Store `content` as `path`, replacing whatever is there
+    fn write(&self, path: &Path, content: &[u8], meta: &OutputMeta) -> Result<()>;
+}
+
+/// Writes outputs to local files, atomically, creating their directories
+pub struct FilesystemBackend {
+    io: IoOptions,
+}
+
+impl FilesystemBackend {
+    /// Write with the buffer size and page cache hint of `io`; content arrives compressed
+    pub fn new(io: IoOptions) -> Self {
+        FilesystemBackend {
+            io: IoOptions {
+                compression: None,
+                ..io
+            },
+        }
+    }
+}
+
+impl OutputBackend for FilesystemBackend {
+    fn write(&self, path: &Path, content: &[u8], _meta: &OutputMeta) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        crate::output::write_atomically(path, content, &self.io)
+    }
+}
+
+/// Writes every output to stdout, whatever its path
+pub struct StdoutBackend;
+
+impl OutputBackend for StdoutBackend {
+    fn write(&self, _path: &Path, content: &[u8], _meta: &OutputMeta) -> Result<()> {
+        let mut stdout = std::io::stdout().lock();
+        stdout.write_all(content)?;
+        stdout.flush()?;
+        Ok(())
+    }
+}
+
+/// Copies every output to the system clipboard as text, replacing what was there
+///
+/// Without a clipboard to reach, e.g. on a headless system without a display
+/// server, the output is written to stdout instead, with a warning on stderr.
+#[cfg(feature = "clipboard")]
+pub struct ClipboardBackend;
+
+#[cfg(feature = "clipboard")]
+impl OutputBackend for ClipboardBackend {
+    fn write(&self, path: &Path, content: &[u8], meta: &OutputMeta) -> Result<()> {
+        let text = std::str::from_utf8(content).map_err(|_| {
+            ShinkansenError::ValidationError(format!(
+                "The output of {:?} is not text and cannot be copied to the clipboard",
+                meta.input
+            ))
+        })?;
+        let copied = arboard::Clipboard::new().and_then(|mut clipboard| clipboard.set_text(text));
+        if let Err(err) = copied {
+            eprintln!(
+                "warning: No clipboard is available ({}); writing to stdout instead",
+                err
+            );
+            return StdoutBackend.write(path, content, meta);
+        }
+        Ok(())
+    }
+}
+
+/// Collects outputs into a tar archive in memory
+///
+/// Entries are regular files with mode 0644 and no timestamp, so the same
+/// outputs always give the same archive. Paths are stored with `/`
+/// separators and without a leading `/`.
+#[derive(Default)]
+pub struct ArchiveBackend {
+    tar: Mutex<Vec<u8>>,
+}
+
+impl ArchiveBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The finished archive
+    pub fn finish(self) -> Vec<u8> {
+        let mut tar = self
+            .tar
+            .into_inner()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        tar.extend_from_slice(&[0; 2 * TAR_BLOCK]);
+        tar
+    }
+}
+
+const TAR_BLOCK: usize = 512;
+
+impl OutputBackend for ArchiveBackend {
+    fn write(&self, path: &Path, content: &[u8], _meta: &OutputMeta) -> Result<()> {
+        let name = path
+            .components()
+            .filter_map(|component| match component {
+                std::path::Component::Normal(part) => Some(part.to_string_lossy()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("/");
+        let header = tar_header(&name, content.len())?;
+
+        let mut tar = self
+            .tar
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        tar.extend_from_slice(&header);
+        tar.extend_from_slice(content);
+        let padded = tar.len().next_multiple_of(TAR_BLOCK);
+        tar.resize(padded, 0);
+        Ok(())
+    }
+}
+
+/// A ustar header for a regular file; names over 100 bytes are split at a `/`
+fn tar_header(name: &str, size: usize) -> Result<[u8; TAR_BLOCK]> {
+    let too_long = || {
+        ShinkansenError::ValidationError(format!("{:?} is too long for a tar archive entry", name))
+    };
+    let (prefix, name) = if name.len() <= 100 {
+        ("", name)
+    } else {
+        let split = name
+            .char_indices()
+            .filter(|(index, c)| *c == '/' && *index <= 155 && name.len() - index - 1 <= 100)
+            .map(|(index, _)| index)
+            .next()
+            .ok_or_else(too_long)?;
+        (&name[..split], &name[split + 1..])
+    };
+    if name.is_empty() {
+        return Err(too_long());
+    }
+
+    let mut header = [0u8; TAR_BLOCK];
+    let mut field = |offset: usize, value: &[u8]| {
+        header[offset..offset + value.len()].copy_from_slice(value);
+    };
+    field(0, name.as_bytes());
+    field(100, b"0000644\0");
+    field(108, b"0000000\0");
+    field(116, b"0000000\0");
+    field(124, format!("{:011o}\0", size).as_bytes());
+    field(136, b"00000000000\0");
+    field(156, b"0");
+    field(257, b"ustar\0");
+    field(263, b"00");
+    field(345, prefix.as_bytes());
+
+    // The checksum is computed with its own field read as spaces
+    header[148..156].copy_from_slice(b"        ");
+    let checksum: u32 = header.iter().map(|byte| u32::from(*byte)).sum();
+    header[148..156].copy_from_slice(format!("{:06o}\0 ", checksum).as_bytes());
+    Ok(header)
+}
+
+/// An object store `-o` can name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectStore {
+    /// Amazon S3 (`s3://`), uploaded with `aws s3 cp`
+    S3,
+    /// Google Cloud Storage (`gs://`), uploaded with `gcloud storage cp`
+    Gcs,
+}
+
+impl ObjectStore {
+    /// The store an output URL points into, if it is not a local path
+    pub fn from_url(url: &str) -> Option<Self> {
+        if url.starts_with("s3://") {
+            Some(ObjectStore::S3)
+        } else if url.starts_with("gs://") {
+            Some(ObjectStore::Gcs)
+        } else {
+            None
+        }
+    }
+
+    /// The executable that uploads to the store
+    pub fn program(self) -> &'static str {
+        match self {
+            ObjectStore::S3 => "aws",
+            ObjectStore::Gcs => "gcloud",
+        }
+    }
+
+    /// Arguments that make [`ObjectStore::program`] copy stdin to the URL that follows
+    #[cfg(feature = "cloud")]
+    fn upload_args(self) -> &'static [&'static str] {
+        match self {
+            ObjectStore::S3 => &["s3", "cp", "-"],
+            ObjectStore::Gcs => &["storage", "cp", "-"],
+        }
+    }
+}
+
+/// The object store `-o` names, if any
+pub fn output_store(cli: &Cli) -> Option<ObjectStore> {
+    cli.output.as_deref().and_then(ObjectStore::from_url)
+}
+
+/// Uploads outputs to an object store, one object per file
+///
+/// Output paths are object URLs: the `-o` URL joined with each file's path.
+#[cfg(feature = "cloud")]
+pub struct ObjectStoreBackend;
+
+#[cfg(feature = "cloud")]
+impl OutputBackend for ObjectStoreBackend {
+    fn write(&self, path: &Path, content: &[u8], _meta: &OutputMeta) -> Result<()> {
+        use std::process::{Command, Stdio};
+
+        use crate::error::ContextExt;
+
+        // Joined on Windows with a backslash; object keys always use '/'
+        let url = path.to_string_lossy().replace('\\', "/");
+        let store = ObjectStore::from_url(&url).ok_or_else(|| {
+            ShinkansenError::ValidationError(format!(
+                "{:?} is not an s3:// or gs:// object URL",
+                path
+            ))
+        })?;
+        if url.split('/').any(|segment| segment == "..") {
+            return Err(ShinkansenError::SecurityError(format!(
+                "Invalid object URL {} - contains parent directory references",
+                url
+            )));
+        }
+
+        let program = store.program();
+        let mut child = Command::new(program)
+            .args(store.upload_args())
+            .arg(&url)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| {
+                ShinkansenError::FileSystemError(format!(
+                    "Failed to run {}: {}. Is it installed and on PATH?",
+                    program, e
+                ))
+            })?;
+        if let Some(mut pipe) = child.stdin.take() {
+            pipe.write_all(content)
+                .with_context(|| format!("Failed to write to {}", program))?;
+        }
+        let output = child
+            .wait_with_output()
+            .with_context(|| format!("Failed to run {}", program))?;
+        if !output.status.success() {
+            return Err(ShinkansenError::FileSystemError(format!(
+                "Failed to upload {}: {}",
+                url,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// The backend a command line writes with
+///
+/// [`STDOUT_PATH`] goes to stdout, [`CLIPBOARD_PATH`] to the clipboard (with
+/// the `clipboard` feature), object URLs to their store (with the `cloud`
+/// feature) and anything else to the filesystem.
+pub fn default_backend(cli: &Cli) -> Box<dyn OutputBackend> {
+    Box::new(DefaultBackend {
+        files: FilesystemBackend::new(IoOptions::from_cli(cli)),
+    })
+}
+
+struct DefaultBackend {
+    files: FilesystemBackend,
+}
+
+impl OutputBackend for DefaultBackend {
+    fn write(&self, path: &Path, content: &[u8], meta: &OutputMeta) -> Result<()> {
+        if path == Path::new(STDOUT_PATH) {
+            return StdoutBackend.write(path, content, meta);
+        }
+        #[cfg(feature = "clipboard")]
+        if path == Path::new(CLIPBOARD_PATH) {
+            return ClipboardBackend.write(path, content, meta);
+        }
+        #[cfg(feature = "cloud")]
+        if path.to_str().and_then(ObjectStore::from_url).is_some() {
+            return ObjectStoreBackend.write(path, content, meta);
+        }
+        self.files.write(path, content, meta)
+    }
+}
+
+/// Reject an object store `-o` this build or these options cannot write to
+///
+/// Objects are only ever uploaded, so options that read outputs back, keep
+/// them next to each other or replace them in place do not apply.
+pub fn validate_output_store(cli: &Cli) -> Result<()> {
+    let Some(store) = output_store(cli) else {
+        return Ok(());
+    };
+    if !cfg!(feature = "cloud") {
+        return Err(ShinkansenError::ValidationError(format!(
+            "{} outputs need shinkansen built with the cloud feature",
+            match store {
+                ObjectStore::S3 => "s3://",
+                ObjectStore::Gcs => "gs://",
+            }
+        )));
+    }
+    let local_only = [
+        (cli.check, "--check"),
+        (cli.diff, "--diff"),
+        (cli.check_stamps, "--check-stamps"),
+        (cli.managed_block, "--managed-block"),
+        (cli.emit_patch, "--emit-patch"),
+        (cli.manifest.is_some(), "--manifest"),
+        (cli.transactional, "--transactional"),
+        (cli.link.is_some(), "--link"),
+        (cli.no_clobber, "--no-clobber"),
+        (cli.interactive, "--interactive"),
+    ];
+    if let Some((_, flag)) = local_only.iter().find(|(used, _)| *used) {
+        return Err(ShinkansenError::ValidationError(format!(
+            "{} needs local output files and cannot be used with an object store -o",
+            flag
+        )));
+    }
+    Ok(())
+}
+
+/// Reject `-o clipboard` when this build or these options cannot copy to the clipboard
+///
+/// The clipboard holds text, so the output cannot be compressed.
+pub fn validate_clipboard_output(cli: &Cli) -> Result<()> {
+    if cli.output.as_deref() != Some(CLIPBOARD_OUTPUT) || cli.in_place {
+        return Ok(());
+    }
+    if !cfg!(feature = "clipboard") {
+        return Err(ShinkansenError::ValidationError(
+            "-o clipboard needs shinkansen built with the clipboard feature".to_string(),
+        )
+        .with_hint("use -o - and pipe the output to pbcopy, wl-copy or xclip"));
+    }
+    if cli.compress_output.is_some() {
+        return Err(ShinkansenError::ValidationError(
+            "--compress-output cannot be used with -o clipboard".to_string(),
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::cli::Cli;
+use crate::error::{ContextExt, Result, ShinkansenError};
+use crate::project::ProjectConfig;
+use crate::rendering::{RenderOptions, render_template_with_options};
+
+/// Percentiles reported by `shinkansen bench`
+const PERCENTILES: [f64; 4] = [50.0, 90.0, 99.0, 99.9];
+
+/// Latencies of repeated renders of one template (`shinkansen bench`)
+#[derive(Debug, Clone)]
+pub struct BenchReport {
+    pub name: String,
+    /// Render latencies, fastest first
+    pub latencies: Vec<Duration>,
+    /// Wall-clock time of all measured renders
+    pub total: Duration,
+}
+
+impl BenchReport {
+    /// Renders per second over the measured iterations
+    pub fn throughput(&self) -> f64 {
+        let seconds = self.total.as_secs_f64();
+        if seconds == 0.0 {
+            return 0.0;
+        }
+        self.latencies.len() as f64 / seconds
+    }
+
+    /// Latency below which `percentile` percent of renders completed (nearest rank)
+    pub fn percentile(&self, percentile: f64) -> Duration {
+        if self.latencies.is_empty() {
+            return Duration::ZERO;
+        }
+        let rank = (percentile / 100.0 * self.latencies.len() as f64).ceil() as usize;
+        self.latencies[rank.clamp(1, self.latencies.len()) - 1]
+    }
+
+    pub fn mean(&self) -> Duration {
+        if self.latencies.is_empty() {
+            return Duration::ZERO;
+        }
+        self.latencies.iter().sum::<Duration>() / self.latencies.len() as u32
+    }
+
+    /// Describe throughput and the latency distribution
+    ///
+    /// ```text
+    /// bench: deploy.yaml
+    ///   iterations  1000
+    ///   throughput  8123.4 renders/s
+    ///   mean        123.1µs
+    ///   min         101.2µs
+    ///   p50         118.9µs
+    ///   ...
+    /// ```
+    pub fn report(&self) -> String {
+        let mut rows = vec![
+            ("iterations".to_string(), self.latencies.len().to_string()),
+            (
+                "throughput".to_string(),
+                format!("{:.1} renders/s", self.throughput()),
+            ),
+            ("mean".to_string(), format!("{:.1?}", self.mean())),
+            (
+                "min".to_string(),
+                format!(
+                    "{:.1?}",
+                    self.latencies.first().copied().unwrap_or_default()
+                ),
+            ),
+        ];
+        for percentile in PERCENTILES {
+            rows.push((
+                format!("p{}", percentile),
+                format!("{:.1?}", self.percentile(percentile)),
+            ));
+        }
+        rows.push((
+            "max".to_string(),
+            format!("{:.1?}", self.latencies.last().copied().unwrap_or_default()),
+        ));
+
+        let mut output = format!("bench: {}\n", self.name);
+        for (label, value) in rows {
+            output.push_str(&format!("  {:<10}  {}\n", label, value));
+        }
+        output
+    }
+}
+
+/// Render a template `warmup` times unmeasured, then `iterations` times measured
+pub fn run_benchmark(
+    content: &str,
+    variables: &HashMap<String, minijinja::Value>,
+    name: &str,
+    options: &RenderOptions,
+    iterations: usize,
+    warmup: usize,
+) -> Result<BenchReport> {
+    if iterations == 0 {
+        return Err(ShinkansenError::ValidationError(
+            "Benchmark needs at least one iteration".to_string(),
+        ));
+    }
+
+    for _ in 0..warmup {
+        render_template_with_options(content, variables, name, options)?;
+    }
+
+    let mut latencies = Vec::with_capacity(iterations);
+    let start = Instant::now();
+    for _ in 0..iterations {
+        let render_start = Instant::now();
+        render_template_with_options(content, variables, name, options)?;
+        latencies.push(render_start.elapsed());
+    }
+    let total = start.elapsed();
+    latencies.sort();
+
+    Ok(BenchReport {
+        name: name.to_string(),
+        latencies,
+        total,
+    })
+}
+
+/// Benchmark a template file with the variables and rendering options of `cli`
+pub fn bench_template(
+    cli: &Cli,
+    variables: &HashMap<String, minijinja::Value>,
+    template: &Path,
+    iterations: usize,
+    warmup: usize,
+) -> Result<BenchReport> {
+    let content = std::fs::read_to_string(template)
+        .with_context(|| format!("Failed to read file: {:?}", template))?;
+    let options =
+        ProjectConfig::from_cli(cli)?.render_options_for(template, &RenderOptions::from_cli(cli));
+    run_benchmark(
+        &content,
+        variables,
+        &template.display().to_string(),
+        &options,
+        iterations,
+        warmup,
+    )
+}
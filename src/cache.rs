@@ -0,0 +1,216 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::cli::Cli;
+use crate::diagnostics::Diagnostics;
+use crate::error::{ContextExt, Result, ShinkansenError};
+
+/// On-disk cache for data fetched from remote sources
+///
+/// Caching is opt-in: without `--cache-ttl` or `--offline` every source is
+/// fetched on each run and nothing is written to disk. When enabled:
+/// - entries younger than the TTL are used without touching the network
+/// - a failed fetch falls back to a stale entry (with a warning) if one exists
+/// - `--offline` only ever reads the cache, regardless of entry age
+#[derive(Debug, Clone, Default)]
+pub struct Cache {
+    /// Cache directory; `None` disables caching
+    pub dir: Option<PathBuf>,
+    /// How long an entry is considered fresh
+    pub ttl: Option<Duration>,
+    /// Never fetch, serve everything from the cache
+    pub offline: bool,
+    /// Where stale-entry fallbacks are reported
+    pub diagnostics: Diagnostics,
+}
+
+/// A cached payload together with when and where it was fetched
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheEntry {
+    source: String,
+    fetched_at: u64,
+    data: serde_json::Value,
+}
+
+impl Cache {
+    /// Build the cache described by `--cache-ttl` and `--offline`
+    pub fn from_cli(cli: &Cli, diagnostics: &Diagnostics) -> Self {
+        let enabled = cli.cache_ttl.is_some() || cli.offline;
+        Cache {
+            dir: if enabled {
+                crate::platform::cache_dir()
+            } else {
+                None
+            },
+            ttl: cli.cache_ttl,
+            offline: cli.offline,
+            diagnostics: diagnostics.clone(),
+        }
+    }
+
+    /// Return the cached data for `source`, fetching and storing it when needed
+    ///
+    /// `source` identifies the request (kind plus URL/prefix) and is hashed to
+    /// form the file name.
+    pub fn get_or_fetch<F>(&self, source: &str, fetch: F) -> Result<serde_json::Value>
+    where
+        F: FnOnce() -> Result<serde_json::Value>,
+    {
+        let Some(path) = self.entry_path(source) else {
+            if self.offline {
+                return Err(ShinkansenError::DataSourceError(format!(
+                    "Cannot use {} in --offline mode: no cache directory is available",
+                    source
+                )));
+            }
+            return fetch();
+        };
+
+        let cached = read_entry(&path);
+
+        if self.offline {
+            return cached.map(|entry| entry.data).ok_or_else(|| {
+                ShinkansenError::DataSourceError(format!(
+                    "{} is not cached and --offline prevents fetching it",
+                    source
+                ))
+            });
+        }
+
+        if let (Some(entry), Some(ttl)) = (&cached, self.ttl)
+            && now().saturating_sub(entry.fetched_at) < ttl.as_secs()
+        {
+            return Ok(entry.data.clone());
+        }
+
+        match fetch() {
+            Ok(data) => {
+                write_entry(&path, source, &data)?;
+                Ok(data)
+            }
+            Err(err) => match cached {
+                Some(entry) => {
+                    self.diagnostics.warn(format!(
+                        "{}; using cached copy of {} from {} seconds ago",
+                        err,
+                        source,
+                        now().saturating_sub(entry.fetched_at)
+                    ));
+                    Ok(entry.data)
+                }
+                None => Err(err),
+            },
+        }
+    }
+
+    fn entry_path(&self, source: &str) -> Option<PathBuf> {
+        let digest = Sha256::digest(source.as_bytes());
+        self.dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{:x}.json", digest)))
+    }
+}
+
+/// Parse a duration such as `90`, `500ms`, `30s`, `5m`, `2h` or `1d` (plain numbers are seconds)
+pub fn parse_duration(value: &str) -> std::result::Result<Duration, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration '{}': expected e.g. 30s, 5m, 2h", value))?;
+    let millis_per_unit = match unit {
+        "ms" => 1,
+        "" | "s" => 1000,
+        "m" => 60 * 1000,
+        "h" => 60 * 60 * 1000,
+        "d" => 24 * 60 * 60 * 1000,
+        _ => {
+            return Err(format!(
+                "invalid duration unit '{}' in '{}': use ms, s, m, h or d",
+                unit, value
+            ));
+        }
+    };
+
+    Ok(Duration::from_millis(
+        number.saturating_mul(millis_per_unit),
+    ))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0)
+}
+
+/// Read a cache entry, treating unreadable or corrupt files as missing
+fn read_entry(path: &Path) -> Option<CacheEntry> {
+    let content = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+fn write_entry(path: &Path, source: &str, data: &serde_json::Value) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        create_private_dir(parent)
+            .with_context(|| format!("Failed to create cache directory {:?}", parent))?;
+    }
+
+    let entry = CacheEntry {
+        source: source.to_string(),
+        fetched_at: now(),
+        data: data.clone(),
+    };
+    let content = serde_json::to_string(&entry)?;
+
+    // Write to a temporary file first so concurrent runs never see a partial entry
+    let temp_path = path.with_extension(format!("json.{}.tmp", std::process::id()));
+    write_private_file(&temp_path, content.as_bytes())
+        .with_context(|| format!("Failed to write cache entry {:?}", temp_path))?;
+    std::fs::rename(&temp_path, path)
+        .with_context(|| format!("Failed to write cache entry {:?}", path))
+}
+
+/// Create the cache directory, accessible only to the current user on unix
+///
+/// Entries can hold secrets fetched from Consul, etcd or a remote config.
+#[cfg(unix)]
+fn create_private_dir(dir: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::DirBuilderExt;
+    std::fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)
+}
+
+#[cfg(not(unix))]
+fn create_private_dir(dir: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)
+}
+
+/// Write a cache entry readable only by the current user on unix
+#[cfg(unix)]
+fn write_private_file(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+    // A leftover temporary file would keep its old mode
+    let _ = std::fs::remove_file(path);
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)?
+        .write_all(content)
+}
+
+#[cfg(not(unix))]
+fn write_private_file(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    std::fs::write(path, content)
+}
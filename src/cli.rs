@@ -1,19 +1,37 @@
 use clap::{Parser, Subcommand};
 use clap_complete::Shell;
+use clap_complete::engine::ArgValueCompleter;
+use std::num::NonZeroUsize;
 use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::completion::{complete_config, complete_define, complete_profile};
+use crate::error::ErrorFormat;
+use crate::fileio::{Compression, IoHint};
+use crate::input::GitRef;
+use crate::output::ColorChoice;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 const DESCRIPTION: &str = env!("CARGO_PKG_DESCRIPTION");
 
 /// CLI arguments structure
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Default, Clone)]
 #[command(name = "shinkansen")]
-#[command(version = VERSION)]
+#[command(version = VERSION, disable_version_flag = true)]
 #[command(about = DESCRIPTION, long_about = None)]
 pub struct Cli {
     #[command(subcommand)]
     pub command: Option<Commands>,
 
+    /// Print version
+    #[arg(short = 'V', long)]
+    pub version: bool,
+
+    /// With --version, print the version, git commit, enabled features, config formats
+    /// and filter packs as JSON
+    #[arg(long, requires = "version")]
+    pub json: bool,
+
     /// Input files or directories to process (defaults to stdin if not specified)
     #[arg(value_name = "INPUT")]
     pub inputs: Vec<String>,
@@ -22,32 +40,539 @@ pub struct Cli {
     #[arg(short, long)]
     pub recursive: bool,
 
+    /// Skip files and directories matching a glob while walking input directories
+    /// Without a '/' it matches any name (node_modules, *.png), otherwise the relative path
+    #[arg(long = "exclude", value_name = "GLOB")]
+    pub exclude: Vec<String>,
+
+    /// Read the inputs from a commit, branch or tag of the git repository, not the working tree
+    /// REF:PATH renders that tree without INPUT arguments: --git-ref origin/main:templates/
+    #[arg(
+        long = "git-ref",
+        value_name = "REF[:PATH]",
+        value_parser = crate::input::parse_git_ref,
+        conflicts_with = "in_place"
+    )]
+    pub git_ref: Option<GitRef>,
+
     /// Output file or directory (use '-' for stdout)
+    /// 'clipboard' copies a single output to the system clipboard (requires the 'clipboard' feature)
     #[arg(short, long, value_name = "OUTPUT")]
     pub output: Option<String>,
 
+    /// Remove a template extension from file names in directory output (repeatable)
+    /// --strip-extension .j2 writes app.conf.j2 as app.conf
+    #[arg(long = "strip-extension", value_name = "EXT", value_parser = crate::output::parse_extension)]
+    pub strip_extensions: Vec<String>,
+
+    /// Replace an extension in directory output file names (repeatable)
+    /// --extension-map .yaml.j2=.yml writes deploy.yaml.j2 as deploy.yml
+    #[arg(
+        long = "extension-map",
+        value_name = "FROM=TO",
+        value_parser = crate::output::parse_extension_map
+    )]
+    pub extension_maps: Vec<(String, String)>,
+
+    /// Join the rendered inputs, in input order, into one output file or stdout
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "stamp",
+            "check_stamps",
+            "managed_block",
+            "strip_extensions",
+            "extension_maps",
+        ]
+    )]
+    pub concat: bool,
+
+    /// With --concat, a template rendered between consecutive fragments
+    /// `template` holds the path of the fragment that follows: --separator '# {{ template }}'
+    #[arg(long, value_name = "TEMPLATE", requires = "concat")]
+    pub separator: Option<String>,
+
+    /// YAML or JSON file mapping input paths to extra variables and optional outputs
+    /// List an input several times to render it once per entry: --per-file-vars sites.yaml
+    #[arg(
+        long = "per-file-vars",
+        value_name = "FILE",
+        conflicts_with_all = ["concat", "in_place"]
+    )]
+    pub per_file_vars: Option<PathBuf>,
+
+    /// Write each rendered input back over its source file
+    /// Binary files found in input directories are left untouched
+    #[arg(
+        long = "in-place",
+        conflicts_with_all = [
+            "output",
+            "concat",
+            "stamp",
+            "check_stamps",
+            "managed_block",
+            "strip_extensions",
+            "extension_maps",
+        ]
+    )]
+    pub in_place: bool,
+
+    /// With --in-place, keep each original next to it as FILE<SUFFIX>, e.g. --suffix .bak
+    #[arg(long, value_name = "SUFFIX", requires = "in_place")]
+    pub suffix: Option<String>,
+
     /// Template variables as key=value pairs
     /// Supports escaping special characters: \\ (backslash), \, (comma), \= (equals)
     /// Multiple variables can be specified in one flag separated by commas: -D "a=1,b=2"
-    #[arg(short = 'D', long = "define", value_name = "KEY=VALUE")]
+    /// A value of @PATH is read from a file, as JSON or YAML data when it parses: -D config=@payload.json
+    #[arg(
+        short = 'D',
+        long = "define",
+        value_name = "KEY=VALUE",
+        add = ArgValueCompleter::new(complete_define)
+    )]
     pub variables: Vec<String>,
 
-    /// Configuration file (JSON, YAML, or TOML) containing template variables
-    #[arg(short, long, value_name = "CONFIG")]
-    pub config: Option<PathBuf>,
+    /// Set a string variable without type detection, Helm-style (dotted keys nest)
+    /// Applied after -D: --set-string version=1.10 keeps "1.10" instead of 1.1
+    #[arg(
+        long = "set-string",
+        value_name = "KEY=VALUE",
+        add = ArgValueCompleter::new(complete_define)
+    )]
+    pub set_strings: Vec<String>,
+
+    /// Set a variable to a JSON value, Helm-style (dotted keys nest)
+    /// One assignment per flag: --set-json 'app.ports=[80, 443]'
+    #[arg(long = "set-json", value_name = "KEY=JSON")]
+    pub set_jsons: Vec<String>,
+
+    /// Set a variable to the contents of a file, Helm-style (dotted keys nest)
+    /// One assignment per flag: --set-file tls.cert=certs/server.pem
+    #[arg(long = "set-file", value_name = "KEY=PATH")]
+    pub set_files: Vec<String>,
+
+    /// Configuration file (JSON, YAML, TOML or CUE; Dhall and Jsonnet with their features) or http(s) URL containing template variables
+    /// Repeat to layer files; later files win and objects are deep-merged: -c base.yaml -c prod.yaml
+    #[arg(
+        short,
+        long,
+        value_name = "CONFIG",
+        add = ArgValueCompleter::new(complete_config)
+    )]
+    pub config: Vec<PathBuf>,
+
+    /// Read a JSON or YAML mapping of variables from stdin, above the config file in precedence
+    /// The template must then come from a file: kubectl get cm app -o json | shinkansen app.conf --vars-stdin
+    #[arg(long = "vars-stdin", conflicts_with = "interactive")]
+    pub vars_stdin: bool,
+
+    /// Profile selecting config sections such as profiles.prod and [vars.'profile:prod']
+    /// Sections for the current platform ([vars.linux], [vars.unix], ...) always apply: --profile prod
+    #[arg(long, value_name = "NAME", add = ArgValueCompleter::new(complete_profile))]
+    pub profile: Option<String>,
+
+    /// Render the config file as a MiniJinja template before parsing it
+    /// Its variables are the --env and -D values: -c 'config.yaml' --config-template -D env=prod
+    #[arg(long = "config-template", requires = "config")]
+    pub config_template: bool,
+
+    /// CUE schema used to validate the merged template variables
+    #[arg(long = "cue-schema", value_name = "SCHEMA")]
+    pub cue_schema: Option<PathBuf>,
+
+    /// Load a CSV or TSV file into a variable as a list of objects keyed by the header row,
+    /// or a .jsonl/.ndjson file as a list of its values: --data users=people.csv
+    #[arg(long = "data", value_name = "NAME=PATH")]
+    pub data_files: Vec<String>,
+
+    /// Fetch JSON/YAML from a URL into a variable, optionally filtered by JMESPath
+    /// Requires the 'http' feature: --data-url 'hosts=https://api/inventory#items[].name'
+    #[arg(long = "data-url", value_name = "NAME=URL[#JMESPATH]")]
+    pub data_urls: Vec<String>,
+
+    /// POST a GraphQL query, or @FILE holding one, to the --data-url of the same name and
+    /// expose the response's data: --graphql 'hosts={ hosts { name ip } }'
+    #[arg(long = "graphql", value_name = "NAME=QUERY")]
+    pub graphql_queries: Vec<String>,
+
+    /// Run a SQL query and expose the rows as a list of objects
+    /// Requires the 'sqlite' feature: --data-sql 'hosts=sqlite:inventory.db:SELECT host, ip FROM hosts'
+    #[arg(long = "data-sql", value_name = "NAME=sqlite:PATH:QUERY")]
+    pub data_sqls: Vec<String>,
+
+    /// Run an LDAP search (RFC 4516 URL) and expose the entries as a list of objects
+    /// Requires the 'ldap' feature: --data-ldap 'users=ldap://dc1/ou=people,dc=example,dc=com?(objectClass=person)'
+    #[arg(long = "data-ldap", value_name = "NAME=LDAP_URL")]
+    pub data_ldaps: Vec<String>,
+
+    /// Load a Consul KV subtree as nested variables (keys relative to the prefix)
+    /// Requires the 'http' feature: --consul-prefix app/config/
+    #[arg(long = "consul-prefix", value_name = "PREFIX")]
+    pub consul_prefixes: Vec<String>,
+
+    /// Consul HTTP address (defaults to $CONSUL_HTTP_ADDR, then http://127.0.0.1:8500)
+    #[arg(long = "consul-addr", value_name = "URL")]
+    pub consul_addr: Option<String>,
+
+    /// Load an etcd v3 key prefix as nested variables (keys relative to the prefix)
+    /// Requires the 'http' feature: --etcd-prefix /app/config/
+    #[arg(long = "etcd-prefix", value_name = "PREFIX")]
+    pub etcd_prefixes: Vec<String>,
+
+    /// etcd endpoint (defaults to $ETCDCTL_ENDPOINTS, then http://127.0.0.1:2379)
+    #[arg(long = "etcd-endpoint", value_name = "URL")]
+    pub etcd_endpoint: Option<String>,
+
+    /// Expose the data keys of a Kubernetes ConfigMap as variables (uses kubectl)
+    /// Prefix with VAR= to nest them instead: --k8s-configmap app=prod/app-config
+    #[arg(long = "k8s-configmap", value_name = "[VAR=][NS/]NAME")]
+    pub k8s_configmaps: Vec<String>,
+
+    /// Expose the decoded data keys of a Kubernetes Secret as variables (uses kubectl)
+    /// Prefix with VAR= to nest them instead: --k8s-secret db=prod/db-credentials
+    #[arg(long = "k8s-secret", value_name = "[VAR=][NS/]NAME")]
+    pub k8s_secrets: Vec<String>,
+
+    /// Fetch each named source (--data-*, --k8s-* with VAR=) only once a template reads it
+    /// Sources whose variable no rendered template uses are never contacted
+    #[arg(long = "lazy-sources")]
+    pub lazy_sources: bool,
+
+    /// Cache remote data (URLs, Consul, etcd) on disk and reuse it for this long
+    /// Accepts seconds or a unit suffix: --cache-ttl 10m (s, m, h, d)
+    #[arg(long = "cache-ttl", value_name = "DURATION", value_parser = crate::cache::parse_duration)]
+    pub cache_ttl: Option<Duration>,
+
+    /// Never contact remote sources; use cached data regardless of its age
+    #[arg(long)]
+    pub offline: bool,
+
+    /// Retry failed remote fetches this many times with exponential backoff
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    pub retries: u32,
+
+    /// Delay before the first retry, doubled for each further retry (default 500ms)
+    #[arg(long = "retry-delay", value_name = "DURATION", value_parser = crate::cache::parse_duration)]
+    pub retry_delay: Option<Duration>,
+
+    /// PEM bundle of CA certificates trusted for HTTPS and LDAPS (replaces the default roots)
+    #[arg(long = "ca-bundle", value_name = "PEM")]
+    pub ca_bundle: Option<PathBuf>,
+
+    /// Do not verify TLS certificates of remote sources (insecure, for testing only)
+    #[arg(long = "insecure-skip-verify")]
+    pub insecure_skip_verify: bool,
+
+    /// Give up on a single HTTP request (data sources, config URLs) after this long (default 30s)
+    #[arg(long = "http-timeout", value_name = "DURATION", value_parser = crate::cache::parse_duration)]
+    pub http_timeout: Option<Duration>,
+
+    /// Print how long each data source took to fetch (to stderr)
+    #[arg(long)]
+    pub timing: bool,
+
+    /// Print where rendering each template spent its time (to stderr), slowest first
+    /// Reports parse, render and postprocess time and the template instructions executed
+    #[arg(long = "profile-template")]
+    pub profile_template: bool,
+
+    /// Cancel and fail a template whose render runs longer than this (e.g. 5s, 500ms)
+    /// Set per template with max_render_time in a [[render]] profile of shinkansen.toml
+    #[arg(long = "max-render-time", value_name = "DURATION", value_parser = crate::cache::parse_duration)]
+    pub max_render_time: Option<Duration>,
+
+    /// Print which supplied variables each template read, and which no template used (to stderr)
+    /// Tracks top-level variables actually looked up; pair with --check to leave outputs alone
+    #[arg(long)]
+    pub coverage: bool,
+
+    /// Fail when the inputs (directories or unexpanded globs) contain no files
+    /// By default that is a warning and the run succeeds without rendering anything
+    #[arg(long = "fail-if-no-files")]
+    pub fail_if_no_files: bool,
+
+    /// Number of files to render in parallel (default: the number of CPUs)
+    /// Outputs, patches and errors still follow the input order
+    #[arg(short = 'j', long = "jobs", value_name = "N")]
+    pub jobs: Option<NonZeroUsize>,
+
+    /// Chunk size for reading templates and buffer size for writing outputs (default: 64K)
+    /// Larger values mean fewer round trips on network filesystems: --io-buffer 1M
+    #[arg(long = "io-buffer", value_name = "SIZE", value_parser = crate::fileio::parse_size)]
+    pub io_buffer: Option<usize>,
+
+    /// Page cache advice for templates and outputs: normal, sequential, or nocache (Linux only)
+    #[arg(long = "io-hint", value_enum, default_value_t, value_name = "HINT")]
+    pub io_hint: IoHint,
+
+    /// Compress generated files (and stdout) with gzip or zstd
+    /// Directory outputs gain a .gz or .zst extension; .gz and .zst inputs are always decompressed
+    #[arg(
+        long = "compress-output",
+        value_enum,
+        value_name = "FORMAT",
+        conflicts_with_all = [
+            "in_place",
+            "check",
+            "diff",
+            "check_stamps",
+            "managed_block",
+            "manifest",
+            "emit_patch",
+        ]
+    )]
+    pub compress_output: Option<Compression>,
+
+    /// Fail when the run produced warnings (skipped binary files, unset --env names, ...)
+    #[arg(long = "deny-warnings")]
+    pub deny_warnings: bool,
+
+    /// How to print errors: text (message, hint and docs link) or json
+    #[arg(
+        long = "error-format",
+        value_enum,
+        default_value_t,
+        value_name = "FORMAT"
+    )]
+    pub error_format: ErrorFormat,
+
+    /// Print which source supplied each variable and which ones it overrode (to stderr)
+    #[arg(long = "explain-vars")]
+    pub explain_vars: bool,
+
+    /// Load variables from a dotenv file of KEY=value lines, below --env in precedence
+    /// Repeat for several files; later files override earlier ones: --env-file .env --env-file .env.local
+    #[arg(long = "env-file", value_name = "FILE")]
+    pub env_files: Vec<PathBuf>,
 
     /// Load specific environment variables (comma-separated)
+    /// Suffix a name with :json to parse its value as JSON: --env 'HOST,CONFIG:json'
     #[arg(long, value_name = "VARS")]
     pub env: Option<String>,
+
+    /// Load every environment variable whose name starts with PREFIX: --env-prefix APP_
+    #[arg(
+        long = "env-prefix",
+        value_name = "PREFIX",
+        value_parser = clap::builder::NonEmptyStringValueParser::new()
+    )]
+    pub env_prefix: Option<String>,
+
+    /// With --env-prefix, drop the prefix and lowercase the rest: APP_DB_HOST becomes db_host
+    #[arg(long = "strip-env-prefix", requires = "env_prefix")]
+    pub strip_env_prefix: bool,
+
+    /// Expose the whole process environment as the `env` object: {{ env.HOME }}
+    /// Values are strings; top-level variables are not affected
+    #[arg(long = "env-all")]
+    pub env_all: bool,
+
+    /// Fail if any variable listed in --env is not set, reporting all missing names
+    #[arg(long = "require-env", requires = "env")]
+    pub require_env: bool,
+
+    /// Directory searched for templates named in {% include %}, {% extends %} and {% import %}
+    /// Repeat for several directories, searched in order: --template-dir partials --template-dir layouts
+    #[arg(long = "template-dir", value_name = "DIR")]
+    pub template_dirs: Vec<PathBuf>,
+
+    /// Let template functions (filehash, glob, listdir) read files in DIR, besides the template root
+    /// Repeat the flag for several directories: --allow-dir config --allow-dir /etc/ssl
+    #[arg(long = "allow-dir", value_name = "DIR")]
+    pub allow_dirs: Vec<PathBuf>,
+
+    /// Enable Kubernetes helper filters (b64encode, toYaml, k8s_label, k8s_name, ...)
+    #[arg(long)]
+    pub k8s: bool,
+
+    /// Enable the bcrypt, htpasswd and hmac filters
+    /// bcrypt hashes change on every render unless a salt is given: {{ pw | bcrypt(salt=host) }}
+    #[arg(long)]
+    pub allow_crypto: bool,
+
+    /// Validate each rendered file with a command before it is written ('{}' is the file path)
+    /// Prefix with an extension to limit it to matching outputs: --verify-cmd '.conf=nginx -t -c {}'
+    #[arg(long = "verify-cmd", value_name = "[.EXT=]CMD")]
+    pub verify_cmds: Vec<String>,
+
+    /// Pipe each rendered file through a shell command before it is verified and written
+    /// The command reads the rendered output on stdin: --postprocess 'prettier --parser yaml'
+    #[arg(long, value_name = "CMD")]
+    pub postprocess: Option<String>,
+
+    /// Print a unified diff of the changes to stdout instead of writing the output files
+    /// The patch applies with 'git apply': -o deploy/ --emit-patch > render.patch
+    #[arg(long = "emit-patch")]
+    pub emit_patch: bool,
+
+    /// Print a unified diff of each output file that changes before it is written
+    /// With --check nothing is written: the diff shows why the check fails
+    #[arg(long, conflicts_with_all = ["emit_patch", "check_stamps", "dry_run"])]
+    pub diff: bool,
+
+    /// Color --diff output: auto (when stdout is a terminal and NO_COLOR is unset), always, never
+    #[arg(long, value_enum, default_value_t, value_name = "WHEN")]
+    pub color: ColorChoice,
+
+    /// Render everything but write nothing; fail listing each output that is missing or
+    /// differs from what would be written (for CI)
+    #[arg(long, conflicts_with_all = ["emit_patch", "check_stamps", "transactional", "link"])]
+    pub check: bool,
+
+    /// After a successful run, write the SHA-256 of every output file to this JSON file
+    /// 'shinkansen verify FILE' later reports outputs that were changed or removed
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["check", "emit_patch", "check_stamps", "dry_run"]
+    )]
+    pub manifest: Option<PathBuf>,
+
+    /// Print which file each input would be written to, and which would be skipped,
+    /// without rendering, writing or running hooks
+    #[arg(long = "dry-run", conflicts_with_all = ["emit_patch", "check_stamps"])]
+    pub dry_run: bool,
+
+    /// Capture the templates read, merged variables (secrets redacted), options and outputs
+    /// of this run in a tar bundle (.tar, .tar.gz or .tar.zst) for --replay
+    #[arg(long, value_name = "BUNDLE", conflicts_with = "replay")]
+    pub record: Option<PathBuf>,
+
+    /// Render a --record bundle again from its templates and variables, without fetching or
+    /// writing anything, and report which outputs differ from the recording (see also --diff)
+    #[arg(long, value_name = "BUNDLE", conflicts_with = "inputs")]
+    pub replay: Option<PathBuf>,
+
+    /// Render a directory output into a staging copy and swap it into place only if every
+    /// file succeeds; a symlinked output directory is flipped to the new copy atomically
+    #[arg(long, conflicts_with_all = ["in_place", "emit_patch", "check_stamps", "concat"])]
+    pub transactional: bool,
+
+    /// Render into a new timestamped directory inside --output and, once every file
+    /// succeeds, atomically repoint this symlink at it: -o releases/ --link current
+    #[arg(
+        long,
+        value_name = "SYMLINK",
+        requires = "output",
+        conflicts_with_all = ["in_place", "emit_patch", "check_stamps", "concat"]
+    )]
+    pub link: Option<PathBuf>,
+
+    /// With --link, remove all but the newest N releases after switching
+    #[arg(long, value_name = "N", requires = "link")]
+    pub keep: Option<NonZeroUsize>,
+
+    /// Add a "generated by" comment header to each output, using the comment syntax of its extension
+    /// Placed after a leading #! or <?xml line; formats without comments (JSON) are left as is
+    #[arg(long)]
+    pub stamp: bool,
+
+    /// Text of the --stamp header; {template} becomes the template path
+    /// (default: 'GENERATED BY shinkansen from {template} — do not edit')
+    #[arg(long = "stamp-text", value_name = "TEXT")]
+    pub stamp_text: Option<String>,
+
+    /// Overwrite existing outputs regardless of --no-clobber, --interactive and missing stamps
+    /// Without it, --stamp treats outputs lacking their stamp as hand-maintained and refuses the run
+    #[arg(long)]
+    pub force: bool,
+
+    /// Refuse the run, before writing anything, if any output file already exists
+    #[arg(long = "no-clobber", conflicts_with_all = ["interactive", "in_place"])]
+    pub no_clobber: bool,
+
+    /// Ask on the terminal before overwriting each output file whose content would change
+    #[arg(short = 'i', long, conflicts_with = "in_place")]
+    pub interactive: bool,
+
+    /// Check that every output file exists and carries its stamp instead of rendering
+    #[arg(long = "check-stamps", conflicts_with_all = ["stamp", "emit_patch"])]
+    pub check_stamps: bool,
+
+    /// Only regenerate the marked region of existing output files, preserving the rest
+    /// The block is appended when the file has no markers yet (like Ansible's blockinfile)
+    #[arg(long = "managed-block")]
+    pub managed_block: bool,
+
+    /// Marker lines of the managed block; {mark} becomes BEGIN or END (default: '# {mark} shinkansen')
+    /// Use the comment syntax of the destination: --block-marker '<!-- {mark} shinkansen -->'
+    #[arg(
+        long = "block-marker",
+        value_name = "MARKER",
+        requires = "managed_block"
+    )]
+    pub block_marker: Option<String>,
+
+    /// Project config with per-file rendering profiles (default: nearest shinkansen.toml)
+    /// Searched for in the current directory and its parents: --project-config ci/shinkansen.toml
+    #[arg(
+        long = "project-config",
+        value_name = "PATH",
+        conflicts_with = "no_project_config"
+    )]
+    pub project_config: Option<PathBuf>,
+
+    /// Ignore any shinkansen.toml project config
+    #[arg(long = "no-project-config")]
+    pub no_project_config: bool,
 }
 
 /// Subcommands
-#[derive(Subcommand, Debug)]
+#[derive(Subcommand, Debug, Clone)]
 pub enum Commands {
     /// Generate shell completion scripts
     Completions {
         /// Shell to generate completions for
         #[arg(value_enum)]
         shell: Shell,
+
+        /// Print a script that asks shinkansen for completions as you type
+        /// Completes -D keys and --profile names from the -c config; source it on shell startup
+        #[arg(long)]
+        dynamic: bool,
+    },
+    /// Print every template variable with its type, source, default and description
+    /// Put variable options before the subcommand: shinkansen -c config.toml describe
+    Describe,
+    /// Show how the variables would change with other config files, a profile or -D values
+    /// Put the current options before the subcommand: shinkansen -c staging.yaml diff-vars -c prod.yaml
+    DiffVars {
+        /// Config files of the other side, in place of the -c files before the subcommand
+        #[arg(short, long, value_name = "CONFIG")]
+        config: Vec<PathBuf>,
+
+        /// Profile of the other side, in place of the --profile before the subcommand
+        #[arg(long, value_name = "NAME")]
+        profile: Option<String>,
+
+        /// Variables set on the other side, after the -D values before the subcommand
+        #[arg(short = 'D', long = "define", value_name = "KEY=VALUE")]
+        variables: Vec<String>,
+    },
+    /// Check config discovery, remote sources, the cache directory and helper tools
+    /// Put the options of the run to check before the subcommand: shinkansen -c vars.yaml doctor
+    Doctor,
+    /// Render a template repeatedly and report throughput and latency percentiles
+    /// Put variable options before the subcommand: shinkansen -c vars.yaml bench app.yaml -n 5000
+    Bench {
+        /// Template file to render
+        #[arg(value_name = "TEMPLATE")]
+        template: PathBuf,
+
+        /// Number of measured renders
+        #[arg(short = 'n', long, value_name = "N", default_value_t = 1000)]
+        iterations: usize,
+
+        /// Unmeasured renders run first to warm up caches
+        #[arg(long, value_name = "N", default_value_t = 10)]
+        warmup: usize,
+    },
+    /// Check that the files listed in a --manifest still match their recorded hashes
+    /// Needs neither templates nor variables; exits 1 when a file was modified or removed
+    Verify {
+        /// Manifest written by a run with --manifest
+        #[arg(value_name = "MANIFEST")]
+        manifest: PathBuf,
     },
 }
@@ -0,0 +1,170 @@
+//! Dynamic shell completion (`COMPLETE=bash shinkansen`, `completions --dynamic`)
+//!
+//! Besides flags, the dynamic completer offers `-D` keys and `--profile` names
+//! read from the config file given with `-c` earlier on the command line, and
+//! only config files for `-c` itself.
+
+use std::collections::BTreeMap;
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+
+use clap_complete::engine::{CompletionCandidate, PathCompleter, ValueCompleter};
+use clap_complete::env::{EnvCompleter, Shells};
+
+use crate::config::{
+    CONDITIONAL_VARS_KEY, ConfigFile, ConfigLoaderFactory, DERIVED_VARS_KEY, META_KEY, PROFILES_KEY,
+};
+use crate::error::{Result, ShinkansenError};
+
+/// Environment variable that switches the binary into completion mode
+pub const COMPLETE_VAR: &str = "COMPLETE";
+
+/// Write the script registering dynamic completions of `bin` for `shell`
+///
+/// The script calls back into `bin` with `COMPLETE` set on every completion,
+/// so it should be sourced on shell startup rather than saved.
+pub fn write_registration(shell: &str, bin: &str, buf: &mut dyn std::io::Write) -> Result<()> {
+    let shells = Shells::builtins();
+    let completer = shells.completer(shell).ok_or_else(|| {
+        ShinkansenError::ValidationError(format!(
+            "Dynamic completions are not available for {}: use one of {}",
+            shell,
+            shells.names().collect::<Vec<_>>().join(", ")
+        ))
+    })?;
+    EnvCompleter::write_registration(completer, COMPLETE_VAR, bin, bin, bin, buf)?;
+    Ok(())
+}
+
+/// Complete `-D` with `KEY=` for the variables of the `-c` config
+pub fn complete_define(current: &OsStr) -> Vec<CompletionCandidate> {
+    let Some(current) = current.to_str() else {
+        return Vec::new();
+    };
+    // Values are free-form
+    if current.contains('=') {
+        return Vec::new();
+    }
+
+    let Some(config) = config_argument(std::env::args_os()).and_then(|path| load(&path)) else {
+        return Vec::new();
+    };
+    variable_keys(&config)
+        .into_iter()
+        .filter(|(name, _)| name.starts_with(current))
+        .map(|(name, description)| {
+            CompletionCandidate::new(format!("{}=", name)).help(description.map(Into::into))
+        })
+        .collect()
+}
+
+/// Complete `--profile` with the `profile:NAME` sections of the `-c` config
+pub fn complete_profile(current: &OsStr) -> Vec<CompletionCandidate> {
+    let Some(current) = current.to_str() else {
+        return Vec::new();
+    };
+    let Some(config) = config_argument(std::env::args_os()).and_then(|path| load(&path)) else {
+        return Vec::new();
+    };
+    profile_names(&config)
+        .into_iter()
+        .filter(|name| name.starts_with(current))
+        .map(CompletionCandidate::new)
+        .collect()
+}
+
+/// Complete `-c` with directories and files of a supported config format
+pub fn complete_config(current: &OsStr) -> Vec<CompletionCandidate> {
+    PathCompleter::any()
+        .filter(|path| path.is_dir() || is_config_file(path))
+        .complete(current)
+}
+
+/// Variable names a config file defines or declares, with their `_meta` descriptions
+///
+/// Includes top-level variables, those of `[vars.*]` and `profiles.*` sections,
+/// `[derived]` names and `_meta` declarations.
+pub fn variable_keys(config: &ConfigFile) -> BTreeMap<String, Option<String>> {
+    let mut keys = BTreeMap::new();
+    for (name, value) in &config.variables {
+        let entries = value.as_object();
+        match (name.as_str(), entries) {
+            (CONDITIONAL_VARS_KEY | PROFILES_KEY, Some(sections)) => {
+                for section in sections.values().filter_map(serde_json::Value::as_object) {
+                    keys.extend(section.keys().map(|key| (key.clone(), None)));
+                }
+            }
+            (DERIVED_VARS_KEY, Some(derived)) => {
+                keys.extend(derived.keys().map(|key| (key.clone(), None)));
+            }
+            (META_KEY, Some(declarations)) => {
+                for (declared, declaration) in declarations {
+                    let description = declaration
+                        .get("description")
+                        .and_then(serde_json::Value::as_str)
+                        .map(str::to_string);
+                    keys.insert(declared.clone(), description);
+                }
+            }
+            _ => {
+                keys.entry(name.clone()).or_insert(None);
+            }
+        }
+    }
+    keys
+}
+
+/// Profiles selectable with `--profile`: the `[vars.'profile:NAME']` and `profiles.NAME` sections
+pub fn profile_names(config: &ConfigFile) -> Vec<String> {
+    let sections = |key| match config.variables.get(key) {
+        Some(serde_json::Value::Object(sections)) => sections.keys().collect(),
+        _ => Vec::new(),
+    };
+    let mut names: Vec<String> = sections(CONDITIONAL_VARS_KEY)
+        .into_iter()
+        .filter_map(|predicate| predicate.strip_prefix("profile:"))
+        .chain(sections(PROFILES_KEY).into_iter().map(String::as_str))
+        .map(str::to_string)
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// The value of the last `-c`/`--config` in a command line
+pub fn config_argument(args: impl IntoIterator<Item = OsString>) -> Option<PathBuf> {
+    let mut config = None;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let Some(arg) = arg.to_str() else {
+            continue;
+        };
+        if arg == "--" {
+            // `COMPLETE=bash shinkansen -- shinkansen ...`: the real command line follows
+            config = None;
+        } else if arg == "-c" || arg == "--config" {
+            config = args.next().map(PathBuf::from);
+        } else if let Some(value) = arg.strip_prefix("--config=") {
+            config = Some(PathBuf::from(value));
+        } else if let Some(value) = arg.strip_prefix("-c").filter(|value| !value.is_empty()) {
+            config = Some(PathBuf::from(value.strip_prefix('=').unwrap_or(value)));
+        }
+    }
+    config
+}
+
+fn is_config_file(path: &Path) -> bool {
+    path.is_file()
+        && path
+            .extension()
+            .and_then(OsStr::to_str)
+            .is_some_and(|extension| ConfigLoaderFactory::create_loader(extension).is_some())
+}
+
+/// Load a config for completion; errors only mean there is nothing to offer
+fn load(path: &Path) -> Option<ConfigFile> {
+    let extension = path.extension()?.to_str()?;
+    ConfigLoaderFactory::create_loader(extension)?
+        .load_config_file(path)
+        .ok()
+}
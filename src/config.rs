@@ -1,7 +1,8 @@
 use serde::Deserialize;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
 
-use crate::error::Result;
+use crate::error::{ContextExt, Result};
 
 /// Configuration file structure
 #[derive(Deserialize, Debug)]
@@ -10,11 +11,267 @@ pub struct ConfigFile {
     pub variables: HashMap<String, serde_json::Value>,
 }
 
+/// Table holding conditional variable sections such as `[vars.linux]`
+pub const CONDITIONAL_VARS_KEY: &str = "vars";
+
+/// Table of per-profile variables: `profiles.<name>` applies with `--profile <name>`
+pub const PROFILES_KEY: &str = "profiles";
+
+/// Table of variables computed from MiniJinja templates after all sources are merged
+pub const DERIVED_VARS_KEY: &str = "derived";
+
+/// Table documenting variables: `_meta.<name> = { description, type, default }`
+pub const META_KEY: &str = "_meta";
+
+/// Type names accepted in `_meta` declarations
+pub const VARIABLE_TYPES: &[&str] = &["string", "integer", "number", "boolean", "array", "object"];
+
+/// Operating systems accepted as `[vars.<os>]` predicates (values of `std::env::consts::OS`)
+const KNOWN_OPERATING_SYSTEMS: &[&str] = &[
+    "linux",
+    "macos",
+    "windows",
+    "freebsd",
+    "netbsd",
+    "openbsd",
+    "dragonfly",
+    "solaris",
+    "illumos",
+    "android",
+    "ios",
+];
+
+/// What conditional config sections are evaluated against
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigContext {
+    /// Operating system, as in `std::env::consts::OS`
+    pub os: String,
+    /// OS family, `unix` or `windows`
+    pub family: String,
+    /// Profile selected with `--profile`
+    pub profile: Option<String>,
+}
+
+impl ConfigContext {
+    /// Context for the running platform with the given profile selected
+    pub fn current(profile: Option<&str>) -> Self {
+        ConfigContext {
+            os: std::env::consts::OS.to_string(),
+            family: std::env::consts::FAMILY.to_string(),
+            profile: profile.map(str::to_string),
+        }
+    }
+
+    /// Check a section predicate: an OS name, `unix`/`windows`, or `profile:NAME`
+    pub fn matches(&self, predicate: &str) -> Result<bool> {
+        if let Some(profile) = predicate.strip_prefix("profile:") {
+            return Ok(self.profile.as_deref() == Some(profile));
+        }
+        if predicate == "unix" || predicate == "windows" {
+            return Ok(self.family == predicate || self.os == predicate);
+        }
+        if KNOWN_OPERATING_SYSTEMS.contains(&predicate) {
+            return Ok(self.os == predicate);
+        }
+        Err(crate::error::ShinkansenError::ConfigParseError(format!(
+            "Unknown condition '[{}.{}]': use an OS name ({}), unix or profile:NAME",
+            CONDITIONAL_VARS_KEY,
+            predicate,
+            KNOWN_OPERATING_SYSTEMS.join(", ")
+        )))
+    }
+}
+
+impl ConfigFile {
+    /// Merge the `[vars.<predicate>]` sections matching `context` over the top-level variables
+    ///
+    /// Matching sections are applied from least to most specific: OS family
+    /// (`unix`), then OS (`linux`), then profile (`profile:prod`). The selected
+    /// `profiles.<name>` table comes last and is deep-merged, so it only needs
+    /// the keys that differ. A `vars` or `profiles` value that is not a table of
+    /// tables is left alone as a regular variable.
+    pub fn resolve_conditionals(mut self, context: &ConfigContext) -> Result<Self> {
+        let profiles = self.take_sections(PROFILES_KEY);
+        let Some(sections) = self.take_sections(CONDITIONAL_VARS_KEY) else {
+            return Ok(self.apply_profile(profiles, context));
+        };
+
+        let mut matching = Vec::new();
+        for (predicate, section) in sections {
+            if context.matches(&predicate)? {
+                let rank = if predicate.starts_with("profile:") {
+                    2
+                } else if predicate == "unix" || predicate == "windows" {
+                    0
+                } else {
+                    1
+                };
+                matching.push((rank, section));
+            }
+        }
+        matching.sort_by_key(|(rank, _)| *rank);
+
+        for (_, section) in matching {
+            if let serde_json::Value::Object(section) = section {
+                self.variables.extend(section);
+            }
+        }
+
+        Ok(self.apply_profile(profiles, context))
+    }
+
+    fn apply_profile(
+        mut self,
+        profiles: Option<serde_json::Map<String, serde_json::Value>>,
+        context: &ConfigContext,
+    ) -> Self {
+        if let Some(mut profiles) = profiles
+            && let Some(profile) = context.profile.as_deref()
+            && let Some(serde_json::Value::Object(profile)) = profiles.remove(profile)
+        {
+            for (name, value) in profile {
+                merge_json(&mut self.variables, name, value);
+            }
+        }
+        self
+    }
+
+    /// Remove the `key` table when it is a table of tables
+    fn take_sections(&mut self, key: &str) -> Option<serde_json::Map<String, serde_json::Value>> {
+        match self.variables.get(key) {
+            Some(serde_json::Value::Object(sections))
+                if sections.values().all(serde_json::Value::is_object) =>
+            {
+                match self.variables.remove(key) {
+                    Some(serde_json::Value::Object(sections)) => Some(sections),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Set `name` to `value`, merging objects key by key into an existing object
+fn merge_json(
+    variables: &mut HashMap<String, serde_json::Value>,
+    name: String,
+    value: serde_json::Value,
+) {
+    match (variables.get_mut(&name), value) {
+        (Some(serde_json::Value::Object(existing)), serde_json::Value::Object(overlay)) => {
+            merge_json_object(existing, overlay);
+        }
+        (_, value) => {
+            variables.insert(name, value);
+        }
+    }
+}
+
+fn merge_json_object(
+    target: &mut serde_json::Map<String, serde_json::Value>,
+    overlay: serde_json::Map<String, serde_json::Value>,
+) {
+    for (key, value) in overlay {
+        match (target.get_mut(&key), value) {
+            (Some(serde_json::Value::Object(existing)), serde_json::Value::Object(overlay)) => {
+                merge_json_object(existing, overlay);
+            }
+            (_, value) => {
+                target.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Documentation declared for a variable in a `_meta` block
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct VariableMeta {
+    pub description: Option<String>,
+    #[serde(rename = "type")]
+    pub type_name: Option<String>,
+    /// Value used when no source defines the variable
+    pub default: Option<serde_json::Value>,
+    /// Replace the value with a placeholder in `--record` bundles
+    #[serde(default)]
+    pub secret: bool,
+}
+
+impl ConfigFile {
+    /// Remove the `_meta` block and return the declaration of each variable
+    pub fn take_meta(&mut self) -> Result<BTreeMap<String, VariableMeta>> {
+        let Some(meta) = self.variables.remove(META_KEY) else {
+            return Ok(BTreeMap::new());
+        };
+
+        let meta: BTreeMap<String, VariableMeta> = serde_json::from_value(meta).map_err(|e| {
+            crate::error::ShinkansenError::ConfigParseError(format!(
+                "Invalid {} block: {}",
+                META_KEY, e
+            ))
+        })?;
+
+        for (name, declaration) in &meta {
+            if let Some(type_name) = &declaration.type_name
+                && !VARIABLE_TYPES.contains(&type_name.as_str())
+            {
+                return Err(crate::error::ShinkansenError::ConfigParseError(format!(
+                    "Invalid type '{}' for {}.{}: use one of {}",
+                    type_name,
+                    META_KEY,
+                    name,
+                    VARIABLE_TYPES.join(", ")
+                )));
+            }
+        }
+
+        Ok(meta)
+    }
+
+    /// Remove the `[derived]` section and return its `(name, template)` pairs
+    ///
+    /// A `derived` value that is not a table of strings is left alone as a
+    /// regular variable.
+    pub fn take_derived(&mut self) -> Vec<(String, String)> {
+        let is_derived_section = matches!(
+            self.variables.get(DERIVED_VARS_KEY),
+            Some(serde_json::Value::Object(entries))
+                if entries.values().all(serde_json::Value::is_string)
+        );
+        if !is_derived_section {
+            return Vec::new();
+        }
+
+        match self.variables.remove(DERIVED_VARS_KEY) {
+            Some(serde_json::Value::Object(entries)) => entries
+                .into_iter()
+                .filter_map(|(name, template)| match template {
+                    serde_json::Value::String(template) => Some((name, template)),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
 /// Trait for loading configuration files in different formats
 pub trait ConfigLoader {
     /// Load configuration from file content
     fn load_config(&self, content: &str) -> Result<ConfigFile>;
 
+    /// Load configuration from a file on disk
+    ///
+    /// Defaults to reading the file and calling `load_config`. Loaders for
+    /// formats with relative imports override this so imports resolve against
+    /// the config file's location.
+    fn load_config_file(&self, path: &Path) -> Result<ConfigFile> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file: {:?}", path))?;
+        self.load_config(&content)
+    }
+
     /// Get the file extensions supported by this loader
     fn supported_extensions(&self) -> &[&'static str];
 }
@@ -24,8 +281,7 @@ pub struct JsonConfigLoader;
 
 impl ConfigLoader for JsonConfigLoader {
     fn load_config(&self, content: &str) -> Result<ConfigFile> {
-        serde_json::from_str(content)
-            .map_err(|e| crate::error::ShinkansenError::ConfigParseError(e.to_string()))
+        serde_json::from_str(content).map_err(crate::error::ShinkansenError::from)
     }
 
     fn supported_extensions(&self) -> &[&'static str] {
@@ -38,8 +294,7 @@ pub struct YamlConfigLoader;
 
 impl ConfigLoader for YamlConfigLoader {
     fn load_config(&self, content: &str) -> Result<ConfigFile> {
-        serde_yaml::from_str(content)
-            .map_err(|e| crate::error::ShinkansenError::ConfigParseError(e.to_string()))
+        serde_yaml::from_str(content).map_err(crate::error::ShinkansenError::from)
     }
 
     fn supported_extensions(&self) -> &[&'static str] {
@@ -52,8 +307,7 @@ pub struct TomlConfigLoader;
 
 impl ConfigLoader for TomlConfigLoader {
     fn load_config(&self, content: &str) -> Result<ConfigFile> {
-        toml::from_str(content)
-            .map_err(|e| crate::error::ShinkansenError::ConfigParseError(e.to_string()))
+        toml::from_str(content).map_err(crate::error::ShinkansenError::from)
     }
 
     fn supported_extensions(&self) -> &[&'static str] {
@@ -61,6 +315,218 @@ impl ConfigLoader for TomlConfigLoader {
     }
 }
 
+/// Dhall configuration loader
+///
+/// Evaluates the file in-process with `serde_dhall`, so imports and functions
+/// are fully resolved before the result is used as variables. Relative imports
+/// resolve against the config file's directory.
+#[cfg(feature = "dhall")]
+pub struct DhallConfigLoader;
+
+#[cfg(feature = "dhall")]
+impl DhallConfigLoader {
+    fn convert(value: serde_dhall::Result<serde_json::Value>) -> Result<ConfigFile> {
+        let value = value.map_err(|e| {
+            crate::error::ShinkansenError::ConfigParseError(format!("Invalid Dhall config: {}", e))
+        })?;
+        serde_json::from_value(value).map_err(crate::error::ShinkansenError::from)
+    }
+}
+
+#[cfg(feature = "dhall")]
+impl ConfigLoader for DhallConfigLoader {
+    fn load_config(&self, content: &str) -> Result<ConfigFile> {
+        Self::convert(serde_dhall::from_str(content).parse())
+    }
+
+    fn load_config_file(&self, path: &Path) -> Result<ConfigFile> {
+        Self::convert(serde_dhall::from_file(path).parse())
+    }
+
+    fn supported_extensions(&self) -> &[&'static str] {
+        &["dhall"]
+    }
+}
+
+/// CUE configuration loader
+///
+/// Evaluates the file with `cue export` and uses the exported data as variables.
+/// Requires the `cue` executable on `PATH`.
+pub struct CueConfigLoader;
+
+impl ConfigLoader for CueConfigLoader {
+    fn load_config(&self, content: &str) -> Result<ConfigFile> {
+        let mut command = external_tool(CUE_EXECUTABLES, CUE_HINT)?;
+        command.args(["export", "--out", "json", "-"]);
+        run_json_converter(command, Some(content))
+    }
+
+    fn load_config_file(&self, path: &Path) -> Result<ConfigFile> {
+        let mut command = external_tool(CUE_EXECUTABLES, CUE_HINT)?;
+        command.args(["export", "--out", "json"]).arg(path);
+        run_json_converter(command, None)
+    }
+
+    fn supported_extensions(&self) -> &[&'static str] {
+        &["cue"]
+    }
+}
+
+/// Executables able to evaluate CUE
+const CUE_EXECUTABLES: &[&str] = &["cue"];
+
+const CUE_HINT: &str = "install cue (https://cuelang.org/docs/introduction/installation/)";
+
+/// Jsonnet configuration loader
+///
+/// Evaluates the file in-process with jrsonnet, so computed configs with
+/// functions, the standard library and imports can be used directly as
+/// variables. Imports resolve against the importing file's directory.
+#[cfg(feature = "jsonnet")]
+pub struct JsonnetConfigLoader;
+
+#[cfg(feature = "jsonnet")]
+impl JsonnetConfigLoader {
+    fn evaluate(
+        evaluate: impl FnOnce(
+            &jrsonnet_evaluator::State,
+        ) -> jrsonnet_evaluator::Result<jrsonnet_evaluator::Val>,
+    ) -> Result<ConfigFile> {
+        use jrsonnet_evaluator::trace::PathResolver;
+
+        let invalid = |e: &dyn std::fmt::Display| {
+            crate::error::ShinkansenError::ConfigParseError(format!(
+                "Invalid Jsonnet config: {}",
+                e
+            ))
+        };
+        let mut builder = jrsonnet_evaluator::State::builder();
+        builder
+            .import_resolver(jrsonnet_evaluator::FileImportResolver::new(vec![]))
+            .context_initializer(jrsonnet_stdlib::ContextInitializer::new(
+                PathResolver::new_cwd_fallback(),
+            ));
+        let state = builder.build();
+        let _entered = state.enter();
+        let value = evaluate(&state).map_err(|e| invalid(&e))?;
+        // Fields are evaluated lazily, so errors in them surface while serializing
+        let value = serde_json::to_value(&value).map_err(|e| invalid(&e))?;
+        serde_json::from_value(value).map_err(crate::error::ShinkansenError::from)
+    }
+}
+
+#[cfg(feature = "jsonnet")]
+impl ConfigLoader for JsonnetConfigLoader {
+    fn load_config(&self, content: &str) -> Result<ConfigFile> {
+        Self::evaluate(|state| state.evaluate_snippet("<config>", content))
+    }
+
+    fn load_config_file(&self, path: &Path) -> Result<ConfigFile> {
+        Self::evaluate(|state| state.import(path))
+    }
+
+    fn supported_extensions(&self) -> &[&'static str] {
+        &["jsonnet"]
+    }
+}
+
+/// Validate the merged template context against a CUE schema with `cue vet`
+pub fn validate_with_cue_schema(
+    variables: &HashMap<String, minijinja::Value>,
+    schema_path: &Path,
+) -> Result<()> {
+    let temp_dir = tempfile::tempdir().with_context(|| "Failed to create temporary directory")?;
+    let data_path = temp_dir.path().join("context.json");
+    let data = serde_json::to_string(variables)?;
+    std::fs::write(&data_path, data)
+        .with_context(|| format!("Failed to write to {:?}", data_path))?;
+
+    let output = external_tool(CUE_EXECUTABLES, CUE_HINT)?
+        .arg("vet")
+        .arg(schema_path)
+        .arg(&data_path)
+        .output()
+        .map_err(|e| {
+            crate::error::ShinkansenError::ValidationError(format!("Failed to run cue: {}", e))
+        })?;
+
+    if !output.status.success() {
+        return Err(crate::error::ShinkansenError::ValidationError(format!(
+            "Variables do not satisfy CUE schema {:?}:\n{}",
+            schema_path,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(())
+}
+
+/// Command for the first of `names` found on `PATH`
+///
+/// Config formats with a Rust evaluator are loaded in-process, behind a cargo
+/// feature that pulls in the evaluator crate (`dhall`, `jsonnet`). Formats without one run
+/// their reference tool instead: those loaders are always compiled in, look the
+/// tool up here and are covered by `shinkansen doctor`, so a missing tool fails
+/// with the same hint wherever it is needed.
+fn external_tool(names: &[&str], hint: &str) -> Result<std::process::Command> {
+    let program = names
+        .iter()
+        .find(|name| crate::platform::find_executable(name).is_some())
+        .ok_or_else(|| {
+            crate::error::ShinkansenError::ConfigParseError(format!(
+                "No {} executable on PATH",
+                names.join(" or ")
+            ))
+            .with_hint(hint)
+        })?;
+    Ok(std::process::Command::new(program))
+}
+
+/// Run an external converter that prints JSON on stdout and parse its output
+fn run_json_converter(
+    mut command: std::process::Command,
+    stdin: Option<&str>,
+) -> Result<ConfigFile> {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let program = command.get_program().to_string_lossy().to_string();
+    let mut child = command
+        .stdin(if stdin.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| {
+            crate::error::ShinkansenError::ConfigParseError(format!(
+                "Failed to run {}: {}. Is it installed and on PATH?",
+                program, e
+            ))
+        })?;
+
+    if let (Some(content), Some(mut pipe)) = (stdin, child.stdin.take()) {
+        pipe.write_all(content.as_bytes())
+            .with_context(|| format!("Failed to write to {}", program))?;
+    }
+
+    let output = child
+        .wait_with_output()
+        .with_context(|| format!("Failed to run {}", program))?;
+
+    if !output.status.success() {
+        return Err(crate::error::ShinkansenError::ConfigParseError(format!(
+            "{} failed: {}",
+            program,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    serde_json::from_slice(&output.stdout).map_err(crate::error::ShinkansenError::from)
+}
+
 /// Config loader factory that creates appropriate loaders based on file extension
 pub struct ConfigLoaderFactory;
 
@@ -70,7 +536,112 @@ impl ConfigLoaderFactory {
             "json" => Some(Box::new(JsonConfigLoader)),
             "yaml" | "yml" => Some(Box::new(YamlConfigLoader)),
             "toml" => Some(Box::new(TomlConfigLoader)),
+            "cue" => Some(Box::new(CueConfigLoader)),
+            #[cfg(feature = "jsonnet")]
+            "jsonnet" => Some(Box::new(JsonnetConfigLoader)),
+            #[cfg(feature = "dhall")]
+            "dhall" => Some(Box::new(DhallConfigLoader)),
             _ => None,
         }
     }
+
+    /// File extensions supported by the loaders compiled into this build
+    pub fn supported_extensions() -> Vec<&'static str> {
+        let loaders: Vec<Box<dyn ConfigLoader>> = vec![
+            Box::new(JsonConfigLoader),
+            Box::new(YamlConfigLoader),
+            Box::new(TomlConfigLoader),
+            Box::new(CueConfigLoader),
+            #[cfg(feature = "jsonnet")]
+            Box::new(JsonnetConfigLoader),
+            #[cfg(feature = "dhall")]
+            Box::new(DhallConfigLoader),
+        ];
+
+        loaders
+            .iter()
+            .flat_map(|loader| loader.supported_extensions().iter().copied())
+            .collect()
+    }
+}
+
+/// Whether a `-c` value is an `http://` or `https://` URL rather than a file
+pub fn is_config_url(path: &Path) -> bool {
+    path.to_str()
+        .is_some_and(|path| path.starts_with("http://") || path.starts_with("https://"))
+}
+
+/// The loader extension for a config served with `content_type` from `url`
+///
+/// A JSON, YAML or TOML Content-Type decides; otherwise (`text/plain`,
+/// `application/octet-stream`, ...) the extension of the URL path does.
+pub fn remote_config_format(content_type: Option<&str>, url: &str) -> Option<String> {
+    let content_type = content_type.unwrap_or("").to_ascii_lowercase();
+    if content_type.contains("json") {
+        return Some("json".to_string());
+    }
+    if content_type.contains("yaml") {
+        return Some("yaml".to_string());
+    }
+    if content_type.contains("toml") {
+        return Some("toml".to_string());
+    }
+
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    let name = path.rsplit('/').next().unwrap_or(path);
+    let (_, extension) = name.rsplit_once('.')?;
+    let extension = extension.to_ascii_lowercase();
+    ConfigLoaderFactory::create_loader(&extension).map(|_| extension)
+}
+
+/// Download the config at `url`: its content and the extension of the loader for it
+///
+/// Fetches go through the cache, so `--cache-ttl` and `--offline` apply as
+/// they do to remote data sources.
+pub fn fetch_config(
+    url: &str,
+    network: &crate::network::NetworkOptions,
+    cache: &crate::cache::Cache,
+) -> Result<(String, String)> {
+    let fetched = cache.get_or_fetch(&format!("config:{}", url), || {
+        fetch_config_document(url, network)
+    })?;
+    match (fetched["content"].as_str(), fetched["format"].as_str()) {
+        (Some(content), Some(format)) => Ok((content.to_string(), format.to_string())),
+        _ => Err(crate::error::ShinkansenError::ConfigParseError(format!(
+            "Invalid cached copy of config {}",
+            url
+        ))),
+    }
+}
+
+#[cfg(feature = "http")]
+fn fetch_config_document(
+    url: &str,
+    network: &crate::network::NetworkOptions,
+) -> Result<serde_json::Value> {
+    let response = crate::http::Client::new(network)?.get(url)?;
+    let format = remote_config_format(response.content_type.as_deref(), url).ok_or_else(|| {
+        crate::error::ShinkansenError::ConfigParseError(format!(
+            "Cannot tell the format of config {}: it was served as {} and the URL has no supported extension",
+            url,
+            response.content_type.as_deref().unwrap_or("no Content-Type")
+        ))
+        .with_hint(format!(
+            "serve it as JSON, YAML or TOML, or end the URL in .{}",
+            ConfigLoaderFactory::supported_extensions().join(", .")
+        ))
+    })?;
+    Ok(serde_json::json!({"content": response.body, "format": format}))
+}
+
+#[cfg(not(feature = "http"))]
+fn fetch_config_document(
+    url: &str,
+    _network: &crate::network::NetworkOptions,
+) -> Result<serde_json::Value> {
+    Err(crate::error::ShinkansenError::ConfigParseError(format!(
+        "Cannot fetch config {}: shinkansen was built without the 'http' feature",
+        url
+    )))
 }
@@ -0,0 +1,145 @@
+//! The context templates are rendered against
+//!
+//! Variables are collected into a map up front, but a source may put a
+//! [`lazy`] value in the map instead of fetching its data: a placeholder that
+//! is resolved the first time something reads it, and at most once. Templates
+//! are rendered against a [`Context`], which resolves placeholders as they are
+//! looked up, so a source whose variable no rendered template reads is never
+//! fetched.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use minijinja::value::{Enumerator, Object};
+use minijinja::{Error, ErrorKind, Value};
+
+use crate::error::{Result, ShinkansenError};
+
+type Resolver = Box<dyn FnOnce() -> Result<Value> + Send>;
+
+/// A variable whose value is computed when it is first read
+pub struct LazyValue {
+    resolver: Mutex<Option<Resolver>>,
+    value: OnceLock<std::result::Result<Value, String>>,
+}
+
+impl LazyValue {
+    fn resolve(&self) -> Result<Value> {
+        self.value
+            .get_or_init(|| {
+                let resolver = self
+                    .resolver
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner())
+                    .take();
+                match resolver {
+                    Some(resolver) => resolver().map_err(|e| e.to_string()),
+                    // Only reachable if the resolver panicked on an earlier read
+                    None => Err("The value could not be resolved".to_string()),
+                }
+            })
+            .clone()
+            .map_err(ShinkansenError::DataSourceError)
+    }
+}
+
+impl fmt::Debug for LazyValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.value.get() {
+            Some(Ok(value)) => write!(f, "{:?}", value),
+            Some(Err(err)) => write!(f, "<failed: {}>", err),
+            None => f.write_str("<deferred>"),
+        }
+    }
+}
+
+impl Object for LazyValue {}
+
+/// A value computed by `resolver` the first time it is read
+pub fn lazy<F>(resolver: F) -> Value
+where
+    F: FnOnce() -> Result<Value> + Send + 'static,
+{
+    Value::from_object(LazyValue {
+        resolver: Mutex::new(Some(Box::new(resolver))),
+        value: OnceLock::new(),
+    })
+}
+
+/// Whether `value` is a [`lazy`] value, resolved or not
+pub fn is_lazy(value: &Value) -> bool {
+    value.downcast_object_ref::<LazyValue>().is_some()
+}
+
+/// Whether `value` can be read without resolving anything
+pub fn is_resolved(value: &Value) -> bool {
+    value
+        .downcast_object_ref::<LazyValue>()
+        .is_none_or(|lazy| lazy.value.get().is_some())
+}
+
+/// The value behind `value`, resolving it if it is [`lazy`]
+pub fn resolve(value: &Value) -> Result<Value> {
+    match value.downcast_object_ref::<LazyValue>() {
+        Some(lazy) => lazy.resolve(),
+        None => Ok(value.clone()),
+    }
+}
+
+/// A copy of `variables` with every [`lazy`] value resolved
+///
+/// For consumers that need all the data at once, such as schema validation.
+pub fn resolve_all(variables: &HashMap<String, Value>) -> Result<HashMap<String, Value>> {
+    variables
+        .iter()
+        .map(|(name, value)| Ok((name.clone(), resolve(value)?)))
+        .collect()
+}
+
+/// The value a template sees for `value`: resolved, or an invalid value that
+/// fails the render with the resolution error
+pub(crate) fn lookup(name: &str, value: &Value) -> Value {
+    resolve(value).unwrap_or_else(|err| {
+        Value::from(Error::new(
+            ErrorKind::InvalidOperation,
+            format!("Failed to resolve variable '{}': {}", name, err),
+        ))
+    })
+}
+
+/// The root object of a render, resolving [`lazy`] variables as they are looked up
+#[derive(Debug)]
+pub struct Context {
+    variables: HashMap<String, Value>,
+}
+
+impl Context {
+    /// A render context serving `variables`, used with [`Value::from_object`]
+    pub fn new(variables: &HashMap<String, Value>) -> Self {
+        Context {
+            variables: variables.clone(),
+        }
+    }
+}
+
+impl Object for Context {
+    fn get_value(self: &Arc<Self>, key: &Value) -> Option<Value> {
+        if let Err(err) = crate::rendering::check_deadline(None) {
+            return Some(Value::from(err));
+        }
+        let name = key.as_str()?;
+        self.variables.get(name).map(|value| lookup(name, value))
+    }
+
+    fn enumerate(self: &Arc<Self>) -> Enumerator {
+        let mut names: Vec<&String> = self.variables.keys().collect();
+        names.sort();
+        Enumerator::Values(
+            names
+                .into_iter()
+                .map(|name| Value::from(name.as_str()))
+                .collect(),
+        )
+    }
+}
@@ -0,0 +1,134 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::{Arc, Mutex};
+
+use minijinja::Value;
+use minijinja::value::{Enumerator, Object};
+
+/// Which supplied variables each template read (`--coverage`); clones share the records
+///
+/// Only top-level variables are tracked, and only those a render actually
+/// looked up, so a variable used in a branch that did not run counts as unused.
+#[derive(Debug, Clone, Default)]
+pub struct Coverage {
+    used: Arc<Mutex<BTreeMap<String, BTreeSet<String>>>>,
+}
+
+impl Coverage {
+    /// A render context serving `variables` and recording lookups for `template`
+    pub fn context(&self, template: &str, variables: &HashMap<String, Value>) -> Value {
+        self.lock().entry(template.to_string()).or_default();
+        Value::from_object(TrackedContext {
+            template: template.to_string(),
+            variables: variables.clone(),
+            coverage: self.clone(),
+        })
+    }
+
+    fn record(&self, template: &str, variable: &str) {
+        self.lock()
+            .entry(template.to_string())
+            .or_default()
+            .insert(variable.to_string());
+    }
+
+    /// The variables each rendered template read, by template name
+    pub fn used(&self) -> BTreeMap<String, BTreeSet<String>> {
+        self.lock().clone()
+    }
+
+    /// Describe, for every supplied variable, the templates that read it
+    ///
+    /// ```text
+    /// coverage: 3 of 4 variables used by 2 templates (profile prod)
+    /// coverage:   port         2/2  app.yaml, nginx.conf
+    /// coverage:   replicas     1/2  app.yaml
+    /// coverage:   legacy_flag  0/2  unused
+    /// ```
+    pub fn report<'a>(
+        &self,
+        supplied: impl IntoIterator<Item = &'a String>,
+        profile: Option<&str>,
+    ) -> String {
+        let used = self.used();
+        let templates = used.len();
+        let supplied: BTreeSet<&String> = supplied.into_iter().collect();
+        let readers = |variable: &str| -> Vec<&str> {
+            used.iter()
+                .filter(|(_, variables)| variables.contains(variable))
+                .map(|(template, _)| template.as_str())
+                .collect()
+        };
+
+        let used_count = supplied
+            .iter()
+            .filter(|variable| !readers(variable).is_empty())
+            .count();
+        let mut output = format!(
+            "coverage: {} of {} variables used by {} templates{}\n",
+            used_count,
+            supplied.len(),
+            templates,
+            profile.map_or_else(String::new, |profile| format!(" (profile {})", profile))
+        );
+        let width = supplied
+            .iter()
+            .map(|variable| variable.chars().count())
+            .max()
+            .unwrap_or(0);
+        for variable in &supplied {
+            let readers = readers(variable);
+            let detail = if readers.is_empty() {
+                "unused".to_string()
+            } else {
+                readers.join(", ")
+            };
+            output.push_str(&format!(
+                "coverage:   {:<width$}  {}/{}  {}\n",
+                variable,
+                readers.len(),
+                templates,
+                detail,
+                width = width
+            ));
+        }
+        output
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, BTreeSet<String>>> {
+        // A panic while recording cannot leave the map inconsistent
+        self.used
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// The root context of a render under `--coverage`
+#[derive(Debug)]
+struct TrackedContext {
+    template: String,
+    variables: HashMap<String, Value>,
+    coverage: Coverage,
+}
+
+impl Object for TrackedContext {
+    fn get_value(self: &Arc<Self>, key: &Value) -> Option<Value> {
+        if let Err(err) = crate::rendering::check_deadline(None) {
+            return Some(Value::from(err));
+        }
+        let name = key.as_str()?;
+        let value = self.variables.get(name)?;
+        self.coverage.record(&self.template, name);
+        Some(crate::context::lookup(name, value))
+    }
+
+    fn enumerate(self: &Arc<Self>) -> Enumerator {
+        let mut names: Vec<&String> = self.variables.keys().collect();
+        names.sort();
+        Enumerator::Values(
+            names
+                .into_iter()
+                .map(|name| Value::from(name.as_str()))
+                .collect(),
+        )
+    }
+}
@@ -0,0 +1,535 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::cache::Cache;
+use crate::cli::Cli;
+use crate::diagnostics::Diagnostics;
+use crate::error::{ContextExt, Result, ShinkansenError};
+use crate::fileio::IoOptions;
+use crate::network::NetworkOptions;
+use crate::sources::SourceTask;
+
+/// Prepare the named data sources for fetching
+///
+/// Each source is given as `NAME=SOURCE` and its data is exposed under `NAME`:
+/// - `--data NAME=PATH` reads a CSV or TSV file and exposes the rows as a list
+///   of objects keyed by the header row, or a JSON Lines file as the list of
+///   its values
+/// - `--data-url NAME=URL[#JMESPATH]` fetches a JSON/YAML/TOML document over
+///   HTTP(S), or the data of a `--graphql NAME=QUERY` query posted to it,
+///   optionally filtered by a JMESPath expression
+/// - `--data-sql NAME=sqlite:PATH:QUERY` runs a query and exposes the rows as a
+///   list of objects keyed by column name
+/// - `--data-ldap NAME=LDAP_URL` runs a directory search and exposes the entries
+///   as a list of objects with a `dn` key plus one key per attribute
+pub fn data_source_tasks(cli: &Cli, diagnostics: &Diagnostics) -> Result<Vec<SourceTask>> {
+    let cache = Cache::from_cli(cli, diagnostics);
+    let network = NetworkOptions::from_cli(cli);
+    let mut tasks = Vec::new();
+
+    for spec in &cli.data_files {
+        let (name, path) = parse_named_source(spec, "--data")?;
+        let (name, path) = (name.to_string(), PathBuf::from(path));
+        let io = IoOptions::from_cli(cli);
+
+        let task = SourceTask::new(format!("--data {}", spec), {
+            let name = name.clone();
+            move || Ok(vec![(name, read_data_file(&path, &io)?)])
+        });
+        tasks.push(task.provides(name));
+    }
+
+    let mut graphql_queries = graphql_queries(cli)?;
+    for spec in &cli.data_urls {
+        let (name, source) = parse_named_source(spec, "--data-url")?;
+        let (url, query) = match source.rsplit_once('#') {
+            Some((url, query)) => (url.to_string(), Some(query.to_string())),
+            None => (source.to_string(), None),
+        };
+        let graphql = graphql_queries.remove(name);
+        let name = name.to_string();
+        let (cache, network) = (cache.clone(), network.clone());
+
+        let task = SourceTask::new(format!("--data-url {}", spec), {
+            let name = name.clone();
+            move || {
+                let mut data = match graphql {
+                    Some(graphql) => cache
+                        .get_or_fetch(&format!("graphql:{}#{}", url, graphql), || {
+                            fetch_graphql_data(&url, &graphql, &network)
+                        })?,
+                    None => cache
+                        .get_or_fetch(&format!("url:{}", url), || fetch_url_data(&url, &network))?,
+                };
+                if let Some(query) = query {
+                    data = filter_url_data(&query, &data)?;
+                }
+                Ok(vec![(name, data)])
+            }
+        });
+        tasks.push(task.provides(name));
+    }
+    if let Some(name) = graphql_queries.keys().next() {
+        return Err(ShinkansenError::ValidationError(format!(
+            "--graphql {} has no --data-url {}=URL to send the query to",
+            name, name
+        )));
+    }
+
+    for spec in &cli.data_sqls {
+        let (name, source) = parse_named_source(spec, "--data-sql")?;
+        let (database, query) = parse_sql_source(source)?;
+        let (name, database, query) = (name.to_string(), database.to_string(), query.to_string());
+
+        let task = SourceTask::new(format!("--data-sql {}", spec), {
+            let name = name.clone();
+            move || Ok(vec![(name, query_sqlite(&database, &query)?)])
+        });
+        tasks.push(task.provides(name));
+    }
+
+    for spec in &cli.data_ldaps {
+        let (name, source) = parse_named_source(spec, "--data-ldap")?;
+        let query = LdapQuery::parse(source)?;
+        let name = name.to_string();
+        let network = network.clone();
+
+        let task = SourceTask::new(format!("--data-ldap {}", spec), {
+            let name = name.clone();
+            move || Ok(vec![(name, query_ldap(&query, &network)?)])
+        });
+        tasks.push(task.provides(name));
+    }
+
+    Ok(tasks)
+}
+
+/// The `--graphql NAME=QUERY` queries by name, with `@path` queries read from their files
+fn graphql_queries(cli: &Cli) -> Result<HashMap<String, String>> {
+    let mut queries = HashMap::new();
+    for spec in &cli.graphql_queries {
+        let (name, query) = parse_named_source(spec, "--graphql")?;
+        let query = match query.strip_prefix('@') {
+            Some(path) => std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read GraphQL query {:?}", path))?,
+            None => query.to_string(),
+        };
+        if queries.insert(name.to_string(), query).is_some() {
+            return Err(ShinkansenError::ValidationError(format!(
+                "--graphql {} is given more than once",
+                name
+            )));
+        }
+    }
+    Ok(queries)
+}
+
+/// Read a `--data` file: JSON Lines by extension, otherwise CSV or TSV
+pub fn read_data_file(path: &Path, io: &IoOptions) -> Result<serde_json::Value> {
+    if crate::jsonl::is_json_lines(path) {
+        crate::jsonl::read_json_lines(path, io)
+    } else {
+        crate::tabular::read_table(path, io)
+    }
+}
+
+/// Scope of an LDAP search
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LdapScope {
+    Base,
+    OneLevel,
+    Subtree,
+}
+
+/// An LDAP search parsed from an RFC 4516 URL
+///
+/// `ldap://HOST[:PORT]/BASE_DN?ATTRIBUTES?SCOPE?FILTER`. As a shorthand the
+/// filter may directly follow the base DN (`ldap://host/dc=example?(uid=*)`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct LdapQuery {
+    /// Server URL without the path and query (`ldaps://dc1.example.com:636`)
+    pub server: String,
+    pub base_dn: String,
+    /// Requested attributes; empty means all user attributes
+    pub attributes: Vec<String>,
+    pub scope: LdapScope,
+    pub filter: String,
+}
+
+impl LdapQuery {
+    pub fn parse(url: &str) -> Result<Self> {
+        let invalid = |reason: &str| {
+            ShinkansenError::VariableParseError(format!(
+                "Invalid --data-ldap URL '{}': {}",
+                url, reason
+            ))
+        };
+
+        let (scheme, rest) = url
+            .split_once("://")
+            .filter(|(scheme, _)| matches!(*scheme, "ldap" | "ldaps" | "ldapi"))
+            .ok_or_else(|| invalid("expected ldap://, ldaps:// or ldapi://"))?;
+
+        let (authority, path) = match rest.split_once('/') {
+            Some((authority, path)) => (authority, path),
+            None => (rest.split('?').next().unwrap_or(rest), ""),
+        };
+        let (base_dn, query) = path.split_once('?').unwrap_or((path, ""));
+
+        let (attributes, scope, filter) = if query.starts_with('(') {
+            ("", "", query)
+        } else {
+            let mut parts = query.splitn(4, '?');
+            (
+                parts.next().unwrap_or(""),
+                parts.next().unwrap_or(""),
+                parts.next().unwrap_or(""),
+            )
+        };
+
+        let scope = match scope {
+            "" | "sub" => LdapScope::Subtree,
+            "one" => LdapScope::OneLevel,
+            "base" => LdapScope::Base,
+            other => return Err(invalid(&format!("unknown scope '{}'", other))),
+        };
+        let filter = percent_decode(filter).map_err(|e| invalid(&e))?;
+
+        Ok(LdapQuery {
+            server: format!("{}://{}", scheme, authority),
+            base_dn: percent_decode(base_dn).map_err(|e| invalid(&e))?,
+            attributes: attributes
+                .split(',')
+                .map(str::trim)
+                .filter(|attr| !attr.is_empty())
+                .map(String::from)
+                .collect(),
+            scope,
+            filter: if filter.is_empty() {
+                "(objectClass=*)".to_string()
+            } else {
+                filter
+            },
+        })
+    }
+}
+
+/// Decode `%XX` escapes in an URL component
+fn percent_decode(value: &str) -> std::result::Result<String, String> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = value
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| "invalid percent escape".to_string())?;
+            decoded.push(hex);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).map_err(|_| "escaped text is not valid UTF-8".to_string())
+}
+
+/// Split a `sqlite:PATH:QUERY` source into the database path and query
+///
+/// Windows drive letters (`C:\db.sqlite`) are not mistaken for the separator.
+pub fn parse_sql_source(source: &str) -> Result<(&str, &str)> {
+    let invalid = || {
+        ShinkansenError::VariableParseError(format!(
+            "Invalid --data-sql source: '{}'. Use sqlite:PATH:QUERY",
+            source
+        ))
+    };
+
+    let rest = source.strip_prefix("sqlite:").ok_or_else(invalid)?;
+    let bytes = rest.as_bytes();
+    let has_drive_letter = bytes.len() > 2
+        && bytes[0].is_ascii_alphabetic()
+        && bytes[1] == b':'
+        && matches!(bytes[2], b'\\' | b'/');
+    let search_from = if has_drive_letter { 2 } else { 0 };
+
+    let separator = rest[search_from..]
+        .find(':')
+        .map(|i| i + search_from)
+        .ok_or_else(invalid)?;
+    let (path, query) = (&rest[..separator], rest[separator + 1..].trim());
+
+    if path.is_empty() || query.is_empty() {
+        return Err(invalid());
+    }
+
+    Ok((path, query))
+}
+
+/// Split a `NAME=SOURCE` data source specification
+pub fn parse_named_source<'a>(spec: &'a str, flag: &str) -> Result<(&'a str, &'a str)> {
+    match spec.split_once('=') {
+        Some((name, source)) if !name.trim().is_empty() && !source.trim().is_empty() => {
+            Ok((name.trim(), source.trim()))
+        }
+        _ => Err(ShinkansenError::VariableParseError(format!(
+            "Invalid {} value: '{}'. Use NAME=SOURCE",
+            flag, spec
+        ))),
+    }
+}
+
+/// Parse a structured document, choosing the format from a content type or file name
+///
+/// Falls back to trying JSON and then YAML when neither identifies the format.
+pub fn parse_document(
+    content: &str,
+    content_type: Option<&str>,
+    name: &str,
+) -> Result<serde_json::Value> {
+    let content_type = content_type.unwrap_or("").to_ascii_lowercase();
+    let extension = name
+        .split(['?', '#'])
+        .next()
+        .and_then(|path| path.rsplit_once('.'))
+        .map(|(_, ext)| ext.to_ascii_lowercase())
+        .unwrap_or_default();
+
+    if content_type.contains("json") || extension == "json" {
+        Ok(serde_json::from_str(content)?)
+    } else if content_type.contains("yaml") || matches!(extension.as_str(), "yaml" | "yml") {
+        Ok(serde_yaml::from_str(content)?)
+    } else if content_type.contains("toml") || extension == "toml" {
+        Ok(toml::from_str(content)?)
+    } else {
+        serde_json::from_str(content)
+            .or_else(|_| serde_yaml::from_str(content))
+            .map_err(|e| {
+                ShinkansenError::ConfigParseError(format!(
+                    "Could not parse data from {} as JSON or YAML: {}",
+                    name, e
+                ))
+            })
+    }
+}
+
+#[cfg(feature = "http")]
+fn fetch_url_data(url: &str, network: &NetworkOptions) -> Result<serde_json::Value> {
+    let response = crate::http::Client::new(network)?.get(url)?;
+    parse_document(&response.body, response.content_type.as_deref(), url)
+}
+
+#[cfg(not(feature = "http"))]
+fn fetch_url_data(url: &str, _network: &NetworkOptions) -> Result<serde_json::Value> {
+    Err(ShinkansenError::DataSourceError(format!(
+        "Cannot fetch {}: shinkansen was built without the 'http' feature",
+        url
+    )))
+}
+
+/// POST `{"query": QUERY}` to a GraphQL endpoint and return the response's `data`
+///
+/// A response listing `errors` fails, even when it also carries partial data.
+#[cfg(feature = "http")]
+fn fetch_graphql_data(
+    url: &str,
+    query: &str,
+    network: &NetworkOptions,
+) -> Result<serde_json::Value> {
+    let body = serde_json::json!({ "query": query }).to_string();
+    let response = crate::http::Client::new(network)?.post_json(url, &body)?;
+    let mut document: serde_json::Value = serde_json::from_str(&response.body).map_err(|e| {
+        ShinkansenError::DataSourceError(format!("Invalid GraphQL response from {}: {}", url, e))
+    })?;
+    if let Some(errors) = document.get("errors").and_then(|errors| errors.as_array())
+        && !errors.is_empty()
+    {
+        let messages: Vec<&str> = errors
+            .iter()
+            .map(|error| error["message"].as_str().unwrap_or("unknown error"))
+            .collect();
+        return Err(ShinkansenError::DataSourceError(format!(
+            "GraphQL query to {} failed: {}",
+            url,
+            messages.join("; ")
+        )));
+    }
+    Ok(document["data"].take())
+}
+
+#[cfg(not(feature = "http"))]
+fn fetch_graphql_data(
+    url: &str,
+    _query: &str,
+    network: &NetworkOptions,
+) -> Result<serde_json::Value> {
+    fetch_url_data(url, network)
+}
+
+#[cfg(feature = "http")]
+fn filter_url_data(query: &str, data: &serde_json::Value) -> Result<serde_json::Value> {
+    crate::jmespath::search(query, data)
+}
+
+// Never reached: fetching fails first without the 'http' feature
+#[cfg(not(feature = "http"))]
+fn filter_url_data(_query: &str, data: &serde_json::Value) -> Result<serde_json::Value> {
+    Ok(data.clone())
+}
+
+#[cfg(feature = "sqlite")]
+fn query_sqlite(database: &str, query: &str) -> Result<serde_json::Value> {
+    use base64::Engine;
+    use rusqlite::types::ValueRef;
+    use rusqlite::{Connection, OpenFlags};
+
+    let sql_error = |e: rusqlite::Error| {
+        ShinkansenError::DataSourceError(format!("SQLite query on {} failed: {}", database, e))
+    };
+
+    // Open read-only so a data source can never modify the database
+    let connection = Connection::open_with_flags(database, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(sql_error)?;
+    let mut statement = connection.prepare(query).map_err(sql_error)?;
+    let columns: Vec<String> = statement
+        .column_names()
+        .iter()
+        .map(|name| name.to_string())
+        .collect();
+
+    let mut rows = statement.query([]).map_err(sql_error)?;
+    let mut results = Vec::new();
+    while let Some(row) = rows.next().map_err(sql_error)? {
+        let mut object = serde_json::Map::new();
+        for (index, column) in columns.iter().enumerate() {
+            let value = match row.get_ref(index).map_err(sql_error)? {
+                ValueRef::Null => serde_json::Value::Null,
+                ValueRef::Integer(i) => serde_json::Value::from(i),
+                ValueRef::Real(f) => serde_json::Value::from(f),
+                ValueRef::Text(text) => {
+                    serde_json::Value::from(String::from_utf8_lossy(text).to_string())
+                }
+                ValueRef::Blob(blob) => {
+                    serde_json::Value::from(base64::engine::general_purpose::STANDARD.encode(blob))
+                }
+            };
+            object.insert(column.clone(), value);
+        }
+        results.push(serde_json::Value::Object(object));
+    }
+
+    Ok(serde_json::Value::Array(results))
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn query_sqlite(database: &str, _query: &str) -> Result<serde_json::Value> {
+    Err(ShinkansenError::DataSourceError(format!(
+        "Cannot query {}: shinkansen was built without the 'sqlite' feature",
+        database
+    )))
+}
+
+/// Environment variables holding the bind credentials for `--data-ldap`
+#[cfg(feature = "ldap")]
+const LDAP_BIND_DN_VAR: &str = "SHINKANSEN_LDAP_BIND_DN";
+#[cfg(feature = "ldap")]
+const LDAP_PASSWORD_VAR: &str = "SHINKANSEN_LDAP_PASSWORD";
+
+#[cfg(feature = "ldap")]
+fn query_ldap(query: &LdapQuery, network: &NetworkOptions) -> Result<serde_json::Value> {
+    let settings = ldap_settings(network)?;
+    crate::retry::retry(
+        &network.retry,
+        &format!("LDAP search on {}", query.server),
+        || search_ldap(query, settings.clone()),
+    )
+}
+
+/// Apply --ca-bundle and --insecure-skip-verify to LDAP connections
+#[cfg(feature = "ldap")]
+fn ldap_settings(network: &NetworkOptions) -> Result<ldap3::LdapConnSettings> {
+    let mut settings =
+        ldap3::LdapConnSettings::new().set_no_tls_verify(network.insecure_skip_verify);
+
+    if let Some(certificates) = network.ca_certificates()? {
+        let mut roots = rustls::RootCertStore::empty();
+        for der in certificates {
+            roots.add(&rustls::Certificate(der)).map_err(|e| {
+                ShinkansenError::ValidationError(format!("Invalid certificate in CA bundle: {}", e))
+            })?;
+        }
+        let config = rustls::ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        settings = settings.set_config(std::sync::Arc::new(config));
+    }
+
+    Ok(settings)
+}
+
+#[cfg(feature = "ldap")]
+fn search_ldap(query: &LdapQuery, settings: ldap3::LdapConnSettings) -> Result<serde_json::Value> {
+    use ldap3::{LdapConn, Scope, SearchEntry};
+
+    let ldap_error = |e: ldap3::LdapError| {
+        ShinkansenError::DataSourceError(format!("LDAP search on {} failed: {}", query.server, e))
+    };
+
+    let mut connection = LdapConn::with_settings(settings, &query.server).map_err(ldap_error)?;
+
+    // Credentials come from the environment so they never end up in shell history;
+    // without them the search is performed anonymously
+    if let Ok(bind_dn) = std::env::var(LDAP_BIND_DN_VAR) {
+        let password = std::env::var(LDAP_PASSWORD_VAR).unwrap_or_default();
+        connection
+            .simple_bind(&bind_dn, &password)
+            .and_then(|result| result.success())
+            .map_err(ldap_error)?;
+    }
+
+    let scope = match query.scope {
+        LdapScope::Base => Scope::Base,
+        LdapScope::OneLevel => Scope::OneLevel,
+        LdapScope::Subtree => Scope::Subtree,
+    };
+    let (entries, _) = connection
+        .search(
+            &query.base_dn,
+            scope,
+            &query.filter,
+            query.attributes.clone(),
+        )
+        .and_then(|result| result.success())
+        .map_err(ldap_error)?;
+
+    let results = entries
+        .into_iter()
+        .map(|entry| {
+            let entry = SearchEntry::construct(entry);
+            let mut object = serde_json::Map::new();
+            object.insert("dn".to_string(), serde_json::Value::from(entry.dn));
+            for (attribute, mut values) in entry.attrs {
+                let value = if values.len() == 1 {
+                    serde_json::Value::from(values.remove(0))
+                } else {
+                    serde_json::Value::from(values)
+                };
+                object.insert(attribute, value);
+            }
+            serde_json::Value::Object(object)
+        })
+        .collect();
+
+    let _ = connection.unbind();
+    Ok(serde_json::Value::Array(results))
+}
+
+#[cfg(not(feature = "ldap"))]
+fn query_ldap(query: &LdapQuery, _network: &NetworkOptions) -> Result<serde_json::Value> {
+    Err(ShinkansenError::DataSourceError(format!(
+        "Cannot search {}: shinkansen was built without the 'ldap' feature",
+        query.server
+    )))
+}
@@ -0,0 +1,89 @@
+use std::collections::BTreeSet;
+
+use minijinja::value::ValueKind;
+
+use crate::variables::VariableContext;
+
+/// Column headings of the `describe` table
+const HEADINGS: [&str; 5] = ["NAME", "TYPE", "SOURCE", "DEFAULT", "DESCRIPTION"];
+
+/// Render a table of every variable with its type, winning source, default and description
+///
+/// Variables declared in `_meta` but not defined by any source are listed as
+/// `undefined` so missing inputs stand out. A declared type that does not
+/// match the value is shown next to the actual one. Sources deferred with
+/// `--lazy-sources` are not fetched and their variables are shown as `deferred`.
+pub fn describe_variables(context: &VariableContext) -> String {
+    let names: BTreeSet<&String> = context.values.keys().chain(context.meta.keys()).collect();
+
+    let mut rows: Vec<[String; 5]> = Vec::new();
+    for name in names {
+        let meta = context.meta.get(name.as_str());
+        let declared = meta.and_then(|meta| meta.type_name.as_deref());
+        let actual = context.values.get(name.as_str()).map(type_name);
+
+        let type_column = match (actual, declared) {
+            (Some(actual), Some(declared)) if !type_matches(actual, declared) => {
+                format!("{} (expected {})", actual, declared)
+            }
+            (Some(actual), _) => actual.to_string(),
+            (None, Some(declared)) => format!("undefined ({})", declared),
+            (None, None) => "undefined".to_string(),
+        };
+        let source = context
+            .provenance
+            .winner(name)
+            .map(|source| source.to_string())
+            .unwrap_or_else(|| "-".to_string());
+        let default = meta
+            .and_then(|meta| meta.default.as_ref())
+            .map(|value| value.to_string())
+            .unwrap_or_else(|| "-".to_string());
+        let description = meta
+            .and_then(|meta| meta.description.clone())
+            .unwrap_or_default();
+
+        rows.push([name.clone(), type_column, source, default, description]);
+    }
+
+    let mut widths = HEADINGS.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let mut output = String::new();
+    let headings = HEADINGS.map(str::to_string);
+    for row in std::iter::once(&headings).chain(&rows) {
+        let line: Vec<String> = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        output.push_str(line.join("  ").trim_end());
+        output.push('\n');
+    }
+    output
+}
+
+/// Name of a value's type using the vocabulary of `_meta` declarations
+fn type_name(value: &minijinja::Value) -> &'static str {
+    if crate::context::is_lazy(value) {
+        return "deferred";
+    }
+    match value.kind() {
+        ValueKind::String => "string",
+        ValueKind::Bool => "boolean",
+        ValueKind::Number if value.is_integer() => "integer",
+        ValueKind::Number => "number",
+        ValueKind::Seq | ValueKind::Iterable => "array",
+        ValueKind::Map => "object",
+        ValueKind::None => "null",
+        _ => "other",
+    }
+}
+
+fn type_matches(actual: &str, declared: &str) -> bool {
+    actual == declared || actual == "deferred" || (declared == "number" && actual == "integer")
+}
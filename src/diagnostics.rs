@@ -0,0 +1,63 @@
+use std::sync::{Arc, Mutex};
+
+use crate::error::{Result, ShinkansenError};
+
+/// Non-fatal conditions noticed during a run, reported once it finishes
+///
+/// Skipped binary inputs, `--env` names that are not set, `-D` values whose
+/// type detection changed them and stale cache fallbacks end up here instead
+/// of failing the run. `--deny-warnings` turns any of them into an error.
+/// Clones share the same records.
+#[derive(Debug, Clone, Default)]
+pub struct Diagnostics {
+    warnings: Arc<Mutex<Vec<String>>>,
+}
+
+impl Diagnostics {
+    pub fn warn(&self, message: impl Into<String>) {
+        self.lock().push(message.into());
+    }
+
+    /// Recorded warnings, in the order they occurred
+    pub fn warnings(&self) -> Vec<String> {
+        self.lock().clone()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// Describe the warnings, one per line
+    ///
+    /// ```text
+    /// warning: Skipped binary file "templates/logo.png"
+    /// warning: --env names not set in the environment: API_TOKEN
+    /// ```
+    pub fn report(&self) -> String {
+        self.lock()
+            .iter()
+            .map(|warning| format!("warning: {}\n", warning))
+            .collect()
+    }
+
+    /// Print the warnings to stderr; with `deny` (`--deny-warnings`), fail if there were any
+    pub fn finish(&self, deny: bool) -> Result<()> {
+        let count = self.lock().len();
+        eprint!("{}", self.report());
+        if deny && count > 0 {
+            return Err(ShinkansenError::ValidationError(format!(
+                "{} warning{} treated as errors (--deny-warnings)",
+                count,
+                if count == 1 { "" } else { "s" }
+            )));
+        }
+        Ok(())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<String>> {
+        // A panic while recording cannot leave the list inconsistent
+        self.warnings
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
@@ -0,0 +1,175 @@
+//! Structural diff of the variables of two runs (`shinkansen diff-vars`)
+//!
+//! The options before the subcommand describe the current side, and the
+//! subcommand's `-c`, `--profile` and `-D` describe the other side. Both
+//! contexts are collected in full, with deferred sources fetched, and compared
+//! key by key so a change deep inside an object shows up under its own path.
+
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+use serde_json::Value;
+
+use crate::cli::Cli;
+use crate::error::Result;
+use crate::session::{REDACTED, is_secret, is_secret_name};
+use crate::variables::{VariableContext, collect_variable_context};
+
+/// How a variable or nested key differs between the two sides
+#[derive(Debug, Clone, PartialEq)]
+pub enum VarChange {
+    /// Only set on the other side
+    Added(Value),
+    /// Only set on the current side
+    Removed(Value),
+    /// Set on both sides to different values
+    Changed(Value, Value),
+}
+
+/// A difference at `path`, such as `app.replicas` or `hosts[2]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct VarDiff {
+    pub path: String,
+    pub change: VarChange,
+    /// Whether the values are secrets and reported as [`REDACTED`]
+    pub secret: bool,
+}
+
+/// The outcome of `diff-vars`
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct VarDiffReport {
+    pub diffs: Vec<VarDiff>,
+}
+
+impl VarDiffReport {
+    pub fn has_differences(&self) -> bool {
+        !self.diffs.is_empty()
+    }
+
+    /// One line per difference and a summary
+    ///
+    /// ```text
+    /// ~ app.replicas: 2 -> 4
+    /// + app.debug: true
+    /// - legacy: "x"
+    /// 3 difference(s)
+    /// ```
+    pub fn report(&self) -> String {
+        if self.diffs.is_empty() {
+            return "No differences\n".to_string();
+        }
+        let show = |value: &Value, secret: bool| {
+            if secret {
+                REDACTED.to_string()
+            } else {
+                value.to_string()
+            }
+        };
+        let mut output = String::new();
+        for diff in &self.diffs {
+            let line = match &diff.change {
+                VarChange::Added(value) => format!("+ {}: {}", diff.path, show(value, diff.secret)),
+                VarChange::Removed(value) => {
+                    format!("- {}: {}", diff.path, show(value, diff.secret))
+                }
+                VarChange::Changed(old, new) => format!(
+                    "~ {}: {} -> {}",
+                    diff.path,
+                    show(old, diff.secret),
+                    show(new, diff.secret)
+                ),
+            };
+            output.push_str(&line);
+            output.push('\n');
+        }
+        output.push_str(&format!("{} difference(s)\n", self.diffs.len()));
+        output
+    }
+}
+
+/// The invocation of the other side: `cli` with the config files and profile
+/// replaced and the `-D` values added, when given
+pub fn other_side(
+    cli: &Cli,
+    config: &[PathBuf],
+    profile: Option<&str>,
+    variables: &[String],
+) -> Cli {
+    let mut other = cli.clone();
+    other.command = None;
+    if !config.is_empty() {
+        other.config = config.to_vec();
+    }
+    if let Some(profile) = profile {
+        other.profile = Some(profile.to_string());
+    }
+    other.variables.extend(variables.iter().cloned());
+    other
+}
+
+/// Collect the variables of both invocations and compare them
+pub fn diff_runs(cli: &Cli, other: &Cli) -> Result<VarDiffReport> {
+    let current = collect_variable_context(cli)?;
+    let changed = collect_variable_context(other)?;
+    current.diagnostics.finish(cli.deny_warnings)?;
+    changed.diagnostics.finish(cli.deny_warnings)?;
+    diff_contexts(&current, &changed)
+}
+
+/// Compare two variable contexts, reporting secrets without their values
+pub fn diff_contexts(current: &VariableContext, other: &VariableContext) -> Result<VarDiffReport> {
+    let old = to_json(current)?;
+    let new = to_json(other)?;
+    let mut diffs = Vec::new();
+    let names: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    for name in names {
+        let secret = is_secret(current, name) || is_secret(other, name);
+        diff_values(name, old.get(name), new.get(name), secret, &mut diffs);
+    }
+    Ok(VarDiffReport { diffs })
+}
+
+fn to_json(context: &VariableContext) -> Result<serde_json::Map<String, Value>> {
+    crate::context::resolve_all(&context.values)?
+        .into_iter()
+        .map(|(name, value)| Ok((name, serde_json::to_value(value)?)))
+        .collect()
+}
+
+/// Compare two values at `path`, descending into objects and lists on both sides
+pub fn diff_values(
+    path: &str,
+    old: Option<&Value>,
+    new: Option<&Value>,
+    secret: bool,
+    diffs: &mut Vec<VarDiff>,
+) {
+    let mut push = |change| {
+        diffs.push(VarDiff {
+            path: path.to_string(),
+            change,
+            secret,
+        })
+    };
+    match (old, new) {
+        (None, None) => {}
+        (None, Some(new)) => push(VarChange::Added(new.clone())),
+        (Some(old), None) => push(VarChange::Removed(old.clone())),
+        (Some(old), Some(new)) if old == new => {}
+        (Some(Value::Object(old)), Some(Value::Object(new))) => {
+            let keys: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+            for key in keys {
+                let path = format!("{}.{}", path, key);
+                let secret = secret || is_secret_name(key);
+                diff_values(&path, old.get(key), new.get(key), secret, diffs);
+            }
+        }
+        (Some(Value::Array(old)), Some(Value::Array(new))) => {
+            for index in 0..old.len().max(new.len()) {
+                let path = format!("{}[{}]", path, index);
+                diff_values(&path, old.get(index), new.get(index), secret, diffs);
+            }
+        }
+        (Some(old), Some(new)) => push(VarChange::Changed(old.clone(), new.clone())),
+    }
+}
@@ -0,0 +1,456 @@
+use std::fmt;
+use std::path::Path;
+use std::time::Instant;
+
+use crate::backend::{ObjectStore, output_store};
+use crate::cache::Cache;
+use crate::cli::Cli;
+use crate::config::{ConfigLoaderFactory, fetch_config, is_config_url};
+use crate::data::data_source_tasks;
+use crate::diagnostics::Diagnostics;
+use crate::kv::kv_source_tasks;
+use crate::network::NetworkOptions;
+use crate::platform::{cache_dir, find_executable};
+use crate::project::{PROJECT_CONFIG_FILE, ProjectConfig, find_project_config};
+use crate::rendering::RenderOptions;
+
+/// External programs shinkansen runs or works alongside, with what they are for
+///
+/// A missing tool fails the check only when `needed_for` says the current
+/// invocation's options use it.
+const TOOLS: &[Tool] = &[
+    Tool {
+        names: &["cue"],
+        purpose: ".cue config files and --cue-schema",
+        needed_for: |cli| cli.cue_schema.is_some() || has_config_extension(cli, "cue"),
+    },
+    Tool {
+        names: &["kubectl"],
+        purpose: "--k8s-configmap and --k8s-secret",
+        needed_for: |cli| !cli.k8s_configmaps.is_empty() || !cli.k8s_secrets.is_empty(),
+    },
+    Tool {
+        names: &["git"],
+        purpose: "--git-ref inputs without the git feature, and applying --emit-patch output",
+        needed_for: |cli| (cli.git_ref.is_some() && !cfg!(feature = "git")) || cli.emit_patch,
+    },
+    Tool {
+        names: &["aws"],
+        purpose: "s3:// outputs with the cloud feature",
+        needed_for: |cli| output_store(cli) == Some(ObjectStore::S3),
+    },
+    Tool {
+        names: &["gcloud"],
+        purpose: "gs:// outputs with the cloud feature",
+        needed_for: |cli| output_store(cli) == Some(ObjectStore::Gcs),
+    },
+    Tool {
+        names: &["sops"],
+        purpose: "decrypting SOPS-encrypted config files before -c",
+        needed_for: |_| false,
+    },
+];
+
+struct Tool {
+    /// Executable names, in order of preference
+    names: &'static [&'static str],
+    purpose: &'static str,
+    needed_for: fn(&Cli) -> bool,
+}
+
+/// Outcome of a single `shinkansen doctor` check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Ok,
+    /// Nothing to check, or an optional piece is missing
+    Info,
+    /// Likely to cause trouble, but not for this invocation
+    Warn,
+    /// This invocation will fail
+    Fail,
+}
+
+impl fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            CheckStatus::Ok => "ok",
+            CheckStatus::Info => "info",
+            CheckStatus::Warn => "warn",
+            CheckStatus::Fail => "FAIL",
+        };
+        f.pad(label)
+    }
+}
+
+/// One line of the doctor report
+#[derive(Debug, Clone, PartialEq)]
+pub struct Check {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+    /// What to do about a warning or failure
+    pub hint: Option<String>,
+}
+
+impl Check {
+    fn new(name: impl Into<String>, status: CheckStatus, detail: impl Into<String>) -> Self {
+        Check {
+            name: name.into(),
+            status,
+            detail: detail.into(),
+            hint: None,
+        }
+    }
+
+    fn hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+}
+
+/// Results of `shinkansen doctor`
+#[derive(Debug, Clone, Default)]
+pub struct DoctorReport {
+    pub checks: Vec<Check>,
+}
+
+impl DoctorReport {
+    /// Whether any check failed
+    pub fn has_failures(&self) -> bool {
+        self.checks
+            .iter()
+            .any(|check| check.status == CheckStatus::Fail)
+    }
+
+    /// Describe every check, with hints under warnings and failures
+    ///
+    /// ```text
+    /// doctor: shinkansen 0.2.0 (linux/x86_64)
+    ///   ok    project config   /work/app/shinkansen.toml
+    ///   FAIL  source           --data-url users=https://api/users: connection refused
+    ///         hint: check the address and credentials, proxy settings (HTTPS_PROXY) and --ca-bundle
+    ///   info  tool sops        not found on PATH (decrypting SOPS-encrypted config files before -c)
+    /// ```
+    pub fn report(&self) -> String {
+        let width = self
+            .checks
+            .iter()
+            .map(|check| check.name.chars().count())
+            .max()
+            .unwrap_or(0);
+
+        let mut output = format!(
+            "doctor: shinkansen {} ({}/{})\n",
+            env!("CARGO_PKG_VERSION"),
+            std::env::consts::OS,
+            std::env::consts::ARCH
+        );
+        for check in &self.checks {
+            output.push_str(&format!(
+                "  {:<4}  {:<width$}  {}\n",
+                check.status,
+                check.name,
+                check.detail,
+                width = width
+            ));
+            if let Some(hint) = &check.hint {
+                output.push_str(&format!("        hint: {}\n", hint));
+            }
+        }
+        output
+    }
+}
+
+/// Check the environment an invocation with `cli`'s options would run in
+///
+/// Remote sources are fetched for real, bypassing the cache, so connectivity
+/// problems are not hidden by cached copies.
+pub fn run_doctor(cli: &Cli) -> DoctorReport {
+    let mut checks = vec![check_project_config(cli)];
+    if cli.config.is_empty() {
+        checks.push(Check::new(
+            "config file",
+            CheckStatus::Info,
+            "none given (-c)",
+        ));
+    }
+    checks.extend(cli.config.iter().map(|path| check_config_file(path, cli)));
+    checks.extend(check_sources(cli));
+    checks.push(check_cache_dir(cli));
+    checks.extend(TOOLS.iter().map(|tool| check_tool(tool, cli)));
+    DoctorReport { checks }
+}
+
+fn check_project_config(cli: &Cli) -> Check {
+    const NAME: &str = "project config";
+    if cli.no_project_config {
+        return Check::new(NAME, CheckStatus::Info, "disabled with --no-project-config");
+    }
+
+    let path = match &cli.project_config {
+        Some(path) => path.clone(),
+        None => match std::env::current_dir()
+            .ok()
+            .and_then(|cwd| find_project_config(&cwd))
+        {
+            Some(path) => path,
+            None => {
+                return Check::new(
+                    NAME,
+                    CheckStatus::Info,
+                    format!(
+                        "no {} in this directory or its parents",
+                        PROJECT_CONFIG_FILE
+                    ),
+                );
+            }
+        },
+    };
+
+    match ProjectConfig::load(&path) {
+        Ok(project) => match project.check_requirements(&RenderOptions::from_cli(cli)) {
+            Err(err) => Check::new(NAME, CheckStatus::Fail, err.to_string()),
+            Ok(()) => Check::new(
+                NAME,
+                CheckStatus::Ok,
+                format!(
+                    "{} ({} render rule{})",
+                    path.display(),
+                    project.render.len(),
+                    if project.render.len() == 1 { "" } else { "s" }
+                ),
+            ),
+        },
+        Err(err) => Check::new(NAME, CheckStatus::Fail, err.to_string())
+            .hint("fix the file, or skip it with --no-project-config"),
+    }
+}
+
+fn check_config_file(path: &Path, cli: &Cli) -> Check {
+    const NAME: &str = "config file";
+    if is_config_url(path) {
+        return check_config_url(path, cli);
+    }
+    if !path.is_file() {
+        return Check::new(
+            NAME,
+            CheckStatus::Fail,
+            format!("{} does not exist", path.display()),
+        );
+    }
+
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or("");
+    let Some(loader) = ConfigLoaderFactory::create_loader(extension) else {
+        return Check::new(
+            NAME,
+            CheckStatus::Fail,
+            format!("{} has an unsupported format", path.display()),
+        )
+        .hint(format!(
+            "supported formats are .{}",
+            ConfigLoaderFactory::supported_extensions().join(", .")
+        ));
+    };
+    if cli.config_template {
+        // Rendering needs the variables; loading is checked by a real run
+        return Check::new(
+            NAME,
+            CheckStatus::Info,
+            format!("{} (not loaded: --config-template)", path.display()),
+        );
+    }
+
+    match loader.load_config_file(path) {
+        Ok(config) => Check::new(
+            NAME,
+            CheckStatus::Ok,
+            format!(
+                "{} ({} top-level key{})",
+                path.display(),
+                config.variables.len(),
+                if config.variables.len() == 1 { "" } else { "s" }
+            ),
+        ),
+        Err(err) => Check::new(NAME, CheckStatus::Fail, err.to_string()),
+    }
+}
+
+/// Fetch a config URL past the cache, like the sources, and parse it
+fn check_config_url(path: &Path, cli: &Cli) -> Check {
+    const NAME: &str = "config file";
+    let url = path.to_string_lossy();
+    if cli.offline || cli.config_template {
+        let reason = if cli.offline {
+            "--offline"
+        } else {
+            "--config-template"
+        };
+        return Check::new(
+            NAME,
+            CheckStatus::Info,
+            format!("{} (not fetched: {})", url, reason),
+        );
+    }
+
+    let loaded = fetch_config(&url, &NetworkOptions::from_cli(cli), &Cache::default()).and_then(
+        |(content, format)| match ConfigLoaderFactory::create_loader(&format) {
+            Some(loader) => loader.load_config(&content),
+            None => Err(crate::error::ShinkansenError::ConfigParseError(format!(
+                "{} has an unsupported format",
+                url
+            ))),
+        },
+    );
+    match loaded {
+        Ok(config) => Check::new(
+            NAME,
+            CheckStatus::Ok,
+            format!(
+                "{} ({} top-level key{})",
+                url,
+                config.variables.len(),
+                if config.variables.len() == 1 { "" } else { "s" }
+            ),
+        ),
+        Err(err) => Check::new(NAME, CheckStatus::Fail, err.to_string()),
+    }
+}
+
+fn check_sources(cli: &Cli) -> Vec<Check> {
+    const NAME: &str = "source";
+    let mut live = cli.clone();
+    live.cache_ttl = None;
+    live.offline = false;
+
+    let diagnostics = Diagnostics::default();
+    let tasks = data_source_tasks(&live, &diagnostics).and_then(|mut tasks| {
+        tasks.extend(kv_source_tasks(&live, &diagnostics)?);
+        Ok(tasks)
+    });
+    let tasks = match tasks {
+        Ok(tasks) => tasks,
+        Err(err) => return vec![Check::new(NAME, CheckStatus::Fail, err.to_string())],
+    };
+    if tasks.is_empty() {
+        return vec![Check::new(
+            NAME,
+            CheckStatus::Info,
+            "no remote sources given (--data-url, --consul-prefix, ...)",
+        )];
+    }
+    if cli.offline {
+        return vec![Check::new(
+            NAME,
+            CheckStatus::Info,
+            format!("{} source(s) not fetched: --offline", tasks.len()),
+        )];
+    }
+
+    tasks
+        .into_iter()
+        .map(|task| {
+            let label = task.label.clone();
+            let start = Instant::now();
+            match task.fetch() {
+                Ok(variables) => Check::new(
+                    NAME,
+                    CheckStatus::Ok,
+                    format!(
+                        "{} ({} variable{}, {:.1?})",
+                        label,
+                        variables.len(),
+                        if variables.len() == 1 { "" } else { "s" },
+                        start.elapsed()
+                    ),
+                ),
+                Err(err) => Check::new(NAME, CheckStatus::Fail, format!("{}: {}", label, err))
+                    .hint(
+                        "check the address and credentials, proxy settings (HTTPS_PROXY) \
+                         and --ca-bundle",
+                    ),
+            }
+        })
+        .collect()
+}
+
+fn check_cache_dir(cli: &Cli) -> Check {
+    const NAME: &str = "cache directory";
+    let in_use = cli.cache_ttl.is_some() || cli.offline;
+    let Some(dir) = cache_dir() else {
+        let status = if in_use {
+            CheckStatus::Fail
+        } else {
+            CheckStatus::Warn
+        };
+        return Check::new(NAME, status, "no cache directory could be determined")
+            .hint("set SHINKANSEN_CACHE_DIR or HOME");
+    };
+
+    match probe_writable(&dir) {
+        Ok(entries) => Check::new(
+            NAME,
+            CheckStatus::Ok,
+            format!(
+                "{} (writable, {} entr{})",
+                dir.display(),
+                entries,
+                if entries == 1 { "y" } else { "ies" }
+            ),
+        ),
+        Err(err) => {
+            let status = if in_use {
+                CheckStatus::Fail
+            } else {
+                CheckStatus::Warn
+            };
+            Check::new(
+                NAME,
+                status,
+                format!("{} is not writable: {}", dir.display(), err),
+            )
+            .hint("fix its permissions or point SHINKANSEN_CACHE_DIR elsewhere")
+        }
+    }
+}
+
+/// Create the directory if needed, write and remove a file, and count the entries
+fn probe_writable(dir: &Path) -> std::io::Result<usize> {
+    std::fs::create_dir_all(dir)?;
+    let probe = dir.join(format!(".doctor-{}", std::process::id()));
+    std::fs::write(&probe, b"ok")?;
+    std::fs::remove_file(&probe)?;
+    Ok(std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+        .count())
+}
+
+fn check_tool(tool: &Tool, cli: &Cli) -> Check {
+    let name = format!("tool {}", tool.names[0]);
+    match tool.names.iter().find_map(|name| find_executable(name)) {
+        Some(path) => Check::new(name, CheckStatus::Ok, path.display().to_string()),
+        None if (tool.needed_for)(cli) => Check::new(
+            name,
+            CheckStatus::Fail,
+            format!("not found on PATH, needed for {}", tool.purpose),
+        )
+        .hint(format!(
+            "install {} or add it to PATH",
+            tool.names.join(" or ")
+        )),
+        None => Check::new(
+            name,
+            CheckStatus::Info,
+            format!("not found on PATH ({})", tool.purpose),
+        ),
+    }
+}
+
+fn has_config_extension(cli: &Cli, extension: &str) -> bool {
+    cli.config
+        .iter()
+        .any(|path| path.extension().and_then(|ext| ext.to_str()) == Some(extension))
+}
@@ -2,6 +2,11 @@ use std::error::Error;
 use std::fmt;
 use std::io;
 
+use clap::ValueEnum;
+
+/// Documentation linked from error hints
+pub const DOCS_URL: &str = "https://github.com/bbeardsley/shinkansen";
+
 /// Custom error types for Shinkansen
 #[derive(Debug)]
 pub enum ShinkansenError {
@@ -11,9 +16,15 @@ pub enum ShinkansenError {
     /// Template rendering errors
     TemplateError(String),
 
+    /// Template errors raised by MiniJinja, kept as the error source
+    Template(minijinja::Error),
+
     /// Configuration file parsing errors
     ConfigParseError(String),
 
+    /// Syntax errors from the JSON, YAML or TOML parsers, kept as the error source
+    ConfigSyntax(Box<dyn Error + Send + Sync>),
+
     /// Variable parsing errors
     VariableParseError(String),
 
@@ -28,6 +39,146 @@ pub enum ShinkansenError {
 
     /// Template context creation errors
     ContextError(String),
+
+    /// Data source errors (remote fetches, queries)
+    DataSourceError(String),
+
+    /// The run was stopped by SIGINT or SIGTERM before every input was processed
+    Interrupted(String),
+
+    /// An error with a description of what was being done when it occurred
+    ///
+    /// Displayed as `context: source`, so nested contexts read from the
+    /// outermost operation down to the root cause.
+    Context {
+        context: String,
+        source: Box<ShinkansenError>,
+    },
+
+    /// An error with advice on how to fix it, shown under the message
+    Hinted {
+        error: Box<ShinkansenError>,
+        hint: Option<String>,
+        /// Documentation page for the failure
+        docs_url: Option<String>,
+    },
+}
+
+/// How the binary prints errors (`--error-format`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ErrorFormat {
+    /// The message, followed by any hint and documentation link
+    #[default]
+    Text,
+    /// A JSON object with `error`, `hint` and `docs_url` fields
+    Json,
+}
+
+impl ShinkansenError {
+    /// Wrap the error with a description of the operation that failed
+    pub fn context(self, context: impl Into<String>) -> Self {
+        ShinkansenError::Context {
+            context: context.into(),
+            source: Box::new(self),
+        }
+    }
+
+    /// Attach advice on how to fix the error
+    pub fn with_hint(self, hint: impl Into<String>) -> Self {
+        match self {
+            ShinkansenError::Hinted {
+                error, docs_url, ..
+            } => ShinkansenError::Hinted {
+                error,
+                hint: Some(hint.into()),
+                docs_url,
+            },
+            error => ShinkansenError::Hinted {
+                error: Box::new(error),
+                hint: Some(hint.into()),
+                docs_url: None,
+            },
+        }
+    }
+
+    /// Link the README section with the given anchor
+    pub fn with_docs(self, anchor: &str) -> Self {
+        let docs_url = Some(format!("{}#{}", DOCS_URL, anchor));
+        match self {
+            ShinkansenError::Hinted { error, hint, .. } => ShinkansenError::Hinted {
+                error,
+                hint,
+                docs_url,
+            },
+            error => ShinkansenError::Hinted {
+                error: Box::new(error),
+                hint: None,
+                docs_url,
+            },
+        }
+    }
+
+    /// The innermost error, below any contexts and hints
+    pub fn root_cause(&self) -> &ShinkansenError {
+        match self {
+            ShinkansenError::Context { source, .. } => source.root_cause(),
+            ShinkansenError::Hinted { error, .. } => error.root_cause(),
+            other => other,
+        }
+    }
+
+    /// The outermost hint in the chain, if any
+    pub fn hint(&self) -> Option<&str> {
+        match self {
+            ShinkansenError::Hinted {
+                hint: Some(hint), ..
+            } => Some(hint),
+            ShinkansenError::Hinted { error, .. } => error.hint(),
+            ShinkansenError::Context { source, .. } => source.hint(),
+            _ => None,
+        }
+    }
+
+    /// The outermost documentation link in the chain, if any
+    pub fn docs_url(&self) -> Option<&str> {
+        match self {
+            ShinkansenError::Hinted {
+                docs_url: Some(docs_url),
+                ..
+            } => Some(docs_url),
+            ShinkansenError::Hinted { error, .. } => error.docs_url(),
+            ShinkansenError::Context { source, .. } => source.docs_url(),
+            _ => None,
+        }
+    }
+
+    /// Describe the error for the terminal or for tools
+    ///
+    /// ```text
+    /// Unsupported config file format: "values.ini"
+    ///   hint: supported formats are .json, .yaml, .yml, .toml
+    ///   docs: https://github.com/bbeardsley/shinkansen#using-configuration-files
+    /// ```
+    pub fn report(&self, format: ErrorFormat) -> String {
+        match format {
+            ErrorFormat::Text => {
+                let mut output = self.to_string();
+                if let Some(hint) = self.hint() {
+                    output.push_str(&format!("\n  hint: {}", hint));
+                }
+                if let Some(docs_url) = self.docs_url() {
+                    output.push_str(&format!("\n  docs: {}", docs_url));
+                }
+                output
+            }
+            ErrorFormat::Json => serde_json::json!({
+                "error": self.to_string(),
+                "hint": self.hint(),
+                "docs_url": self.docs_url(),
+            })
+            .to_string(),
+        }
+    }
 }
 
 impl fmt::Display for ShinkansenError {
@@ -35,12 +186,29 @@ impl fmt::Display for ShinkansenError {
         match self {
             ShinkansenError::IoError(err) => write!(f, "{}", err),
             ShinkansenError::TemplateError(msg) => write!(f, "{}", msg),
+            ShinkansenError::Template(err) => {
+                // Spell out the chain like anyhow does
+                write!(f, "MiniJinja error: {}", err)?;
+                let mut source = err.source();
+                let mut level = 1;
+                while let Some(src_err) = source {
+                    write!(f, "\nCaused by:\n    {}: {}", level, src_err)?;
+                    source = src_err.source();
+                    level += 1;
+                }
+                Ok(())
+            }
             ShinkansenError::ConfigParseError(msg) => write!(f, "{}", msg),
+            ShinkansenError::ConfigSyntax(err) => write!(f, "{}", err),
             ShinkansenError::VariableParseError(msg) => write!(f, "{}", msg),
             ShinkansenError::ValidationError(msg) => write!(f, "{}", msg),
             ShinkansenError::FileSystemError(msg) => write!(f, "{}", msg),
             ShinkansenError::SecurityError(msg) => write!(f, "{}", msg),
             ShinkansenError::ContextError(msg) => write!(f, "{}", msg),
+            ShinkansenError::DataSourceError(msg) => write!(f, "{}", msg),
+            ShinkansenError::Interrupted(msg) => write!(f, "{}", msg),
+            ShinkansenError::Context { context, source } => write!(f, "{}: {}", context, source),
+            ShinkansenError::Hinted { error, .. } => write!(f, "{}", error),
         }
     }
 }
@@ -49,6 +217,10 @@ impl std::error::Error for ShinkansenError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
             ShinkansenError::IoError(err) => Some(err),
+            ShinkansenError::Template(err) => Some(err),
+            ShinkansenError::ConfigSyntax(err) => Some(err.as_ref()),
+            ShinkansenError::Context { source, .. } => Some(source.as_ref()),
+            ShinkansenError::Hinted { error, .. } => error.source(),
             _ => None,
         }
     }
@@ -62,36 +234,41 @@ impl From<io::Error> for ShinkansenError {
 
 impl From<minijinja::Error> for ShinkansenError {
     fn from(err: minijinja::Error) -> Self {
-        // Build the full error chain like anyhow does
-        let mut full_message = format!("MiniJinja error: {}", err);
-        let mut source = err.source();
-        let mut level = 1;
-
-        while let Some(src_err) = source {
-            full_message.push_str(&format!("\nCaused by:\n    {}: {}", level, src_err));
-            source = src_err.source();
-            level += 1;
+        if err.kind() == minijinja::ErrorKind::UndefinedError {
+            ShinkansenError::Template(err)
+                .with_hint(
+                    "define the variable with -D name=value, --set-string, a config file (-c) \
+                     or --env, or give it a fallback with the default filter",
+                )
+                .with_docs("variable-not-found-error")
+        } else if err.kind() == minijinja::ErrorKind::TemplateNotFound {
+            ShinkansenError::Template(err)
+                .with_hint(
+                    "name the directory holding included, extended or imported templates \
+                     with --template-dir DIR",
+                )
+                .with_docs("includes-and-layouts")
+        } else {
+            ShinkansenError::Template(err)
         }
-
-        ShinkansenError::TemplateError(full_message)
     }
 }
 
 impl From<serde_json::Error> for ShinkansenError {
     fn from(err: serde_json::Error) -> Self {
-        ShinkansenError::ConfigParseError(err.to_string())
+        ShinkansenError::ConfigSyntax(Box::new(err))
     }
 }
 
 impl From<serde_yaml::Error> for ShinkansenError {
     fn from(err: serde_yaml::Error) -> Self {
-        ShinkansenError::ConfigParseError(err.to_string())
+        ShinkansenError::ConfigSyntax(Box::new(err))
     }
 }
 
 impl From<toml::de::Error> for ShinkansenError {
     fn from(err: toml::de::Error) -> Self {
-        ShinkansenError::ConfigParseError(err.to_string())
+        ShinkansenError::ConfigSyntax(Box::new(err))
     }
 }
 
@@ -99,6 +276,9 @@ impl From<toml::de::Error> for ShinkansenError {
 pub type Result<T> = std::result::Result<T, ShinkansenError>;
 
 /// Helper trait for adding context to errors
+///
+/// The original error is kept as the source of the returned
+/// [`ShinkansenError::Context`].
 pub trait ContextExt<T> {
     fn with_context<C, F>(self, context: C) -> Result<T>
     where
@@ -110,39 +290,30 @@ impl<T, E> ContextExt<T> for std::result::Result<T, E>
 where
     E: Into<ShinkansenError>,
 {
-    fn with_context<C, F>(self, _context: C) -> Result<T>
+    fn with_context<C, F>(self, context: C) -> Result<T>
     where
         C: FnOnce() -> F,
         F: Into<String>,
     {
-        self.map_err(|e| {
-            let error: ShinkansenError = e.into();
-            match error {
-                ShinkansenError::IoError(err) => {
-                    ShinkansenError::FileSystemError(format!("{}", err))
-                }
-                ShinkansenError::TemplateError(msg) => {
-                    ShinkansenError::TemplateError(msg.to_string())
-                }
-                ShinkansenError::ConfigParseError(msg) => {
-                    ShinkansenError::ConfigParseError(msg.to_string())
-                }
-                ShinkansenError::VariableParseError(msg) => {
-                    ShinkansenError::VariableParseError(msg.to_string())
-                }
-                ShinkansenError::ValidationError(msg) => {
-                    ShinkansenError::ValidationError(msg.to_string())
-                }
-                ShinkansenError::FileSystemError(msg) => {
-                    ShinkansenError::FileSystemError(msg.to_string())
-                }
-                ShinkansenError::SecurityError(msg) => {
-                    ShinkansenError::SecurityError(msg.to_string())
-                }
-                ShinkansenError::ContextError(msg) => {
-                    ShinkansenError::ContextError(msg.to_string())
-                }
-            }
-        })
+        self.map_err(|e| e.into().context(context()))
     }
 }
+
+/// Run `f`, turning a panic into an error so malformed input can never abort the process
+///
+/// Used around the parsers and the renderer, which see user-supplied strings
+/// (`-D` values, config files, templates). `context` names the operation in
+/// the resulting message.
+pub fn catch_panic<T>(context: &str, f: impl FnOnce() -> Result<T>) -> Result<T> {
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).unwrap_or_else(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string());
+        Err(ShinkansenError::ValidationError(format!(
+            "Internal error while {}: {}",
+            context, message
+        )))
+    })
+}
@@ -0,0 +1,257 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+
+use clap::ValueEnum;
+
+use crate::cli::Cli;
+
+/// Chunk size for reading templates and buffer size for writing outputs, unless `--io-buffer` is given
+pub const DEFAULT_IO_BUFFER: usize = 64 * 1024;
+
+/// Page cache advice for template and output files (`--io-hint`)
+///
+/// Only Linux acts on the hints; elsewhere they are accepted and ignored.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum IoHint {
+    /// Leave caching to the kernel
+    #[default]
+    Normal,
+    /// Files are read front to back, so the kernel may read further ahead
+    Sequential,
+    /// Drop files from the page cache once read or written, much like `O_DIRECT`
+    Nocache,
+}
+
+/// A compression format for inputs and outputs (`--compress-output`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// The format a `.gz` or `.zst` file name calls for
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "gz" => Some(Compression::Gzip),
+            "zst" => Some(Compression::Zstd),
+            _ => None,
+        }
+    }
+
+    /// The file name extension, with its dot
+    pub fn extension(self) -> &'static str {
+        match self {
+            Compression::Gzip => ".gz",
+            Compression::Zstd => ".zst",
+        }
+    }
+
+    /// Compress `content` at the format's default level
+    ///
+    /// The output depends only on `content`: gzip headers carry no timestamp.
+    pub fn compress(self, content: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            Compression::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(content)?;
+                encoder.finish()
+            }
+            Compression::Zstd => zstd::encode_all(content, 0),
+        }
+    }
+
+    pub fn decompress(self, content: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            Compression::Gzip => {
+                let mut decompressed = Vec::new();
+                flate2::read::MultiGzDecoder::new(content).read_to_end(&mut decompressed)?;
+                Ok(decompressed)
+            }
+            Compression::Zstd => zstd::decode_all(content),
+        }
+    }
+}
+
+/// How templates are read and outputs written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IoOptions {
+    /// Bytes read per call, and bytes buffered before each write
+    pub buffer_size: usize,
+    pub hint: IoHint,
+    /// Format written files are compressed with
+    pub compression: Option<Compression>,
+}
+
+impl Default for IoOptions {
+    fn default() -> Self {
+        IoOptions {
+            buffer_size: DEFAULT_IO_BUFFER,
+            hint: IoHint::Normal,
+            compression: None,
+        }
+    }
+}
+
+impl IoOptions {
+    pub fn from_cli(cli: &Cli) -> Self {
+        IoOptions {
+            buffer_size: cli.io_buffer.unwrap_or(DEFAULT_IO_BUFFER),
+            hint: cli.io_hint,
+            compression: cli.compress_output,
+        }
+    }
+}
+
+/// Read a whole file in `buffer_size` chunks, decompressing `.gz` and `.zst` files
+///
+/// The content is read straight into a buffer sized from the file's metadata,
+/// so large chunks mean few round trips on network filesystems.
+pub fn read_file(path: &Path, options: &IoOptions) -> std::io::Result<Vec<u8>> {
+    let content = read_raw(path, options)?;
+    match Compression::from_path(path) {
+        Some(compression) => compression.decompress(&content).map_err(|err| {
+            std::io::Error::new(
+                ErrorKind::InvalidData,
+                format!("not valid {:?} data: {}", compression, err),
+            )
+        }),
+        None => Ok(content),
+    }
+}
+
+/// Open a file to read front to back in `buffer_size` chunks, decompressing
+/// `.gz` and `.zst` files as they are read
+///
+/// Unlike [`read_file`], the content is never held in memory all at once.
+pub fn open_file(path: &Path, options: &IoOptions) -> std::io::Result<Box<dyn BufRead>> {
+    let file = File::open(path)?;
+    advise(&file, options.hint);
+    let reader: Box<dyn Read> = match Compression::from_path(path) {
+        Some(Compression::Gzip) => Box::new(flate2::read::MultiGzDecoder::new(file)),
+        Some(Compression::Zstd) => Box::new(zstd::stream::read::Decoder::new(file)?),
+        None => Box::new(file),
+    };
+    Ok(Box::new(BufReader::with_capacity(
+        options.buffer_size,
+        reader,
+    )))
+}
+
+/// The extension of a file name in lower case, looking past a `.gz` or `.zst` suffix
+///
+/// `users.CSV.gz` has the extension `csv`.
+pub fn content_extension(path: &Path) -> Option<String> {
+    let path = match Compression::from_path(path) {
+        Some(_) => Path::new(path.file_stem()?),
+        None => path,
+    };
+    Some(path.extension()?.to_string_lossy().to_ascii_lowercase())
+}
+
+fn read_raw(path: &Path, options: &IoOptions) -> std::io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    advise(&file, options.hint);
+    let expected = file.metadata().map_or(0, |meta| meta.len() as usize);
+    // One spare byte lets the end of a file of the expected size be seen without growing
+    let mut content = Vec::with_capacity(expected + 1);
+    loop {
+        let start = content.len();
+        let chunk = match expected.checked_sub(start) {
+            Some(0) => 1,
+            Some(remaining) => remaining.min(options.buffer_size),
+            None => options.buffer_size,
+        };
+        content.resize(start + chunk, 0);
+        match file.read(&mut content[start..]) {
+            Ok(0) => {
+                content.truncate(start);
+                break;
+            }
+            Ok(read) => content.truncate(start + read),
+            Err(err) if err.kind() == ErrorKind::Interrupted => content.truncate(start),
+            Err(err) => return Err(err),
+        }
+    }
+    if options.hint == IoHint::Nocache {
+        drop_cached(&file);
+    }
+    Ok(content)
+}
+
+/// Create or truncate `path` and write `content` through a `buffer_size` buffer
+///
+/// The content is compressed first when `compression` is set, whatever the file is named.
+pub fn write_file(path: &Path, content: &[u8], options: &IoOptions) -> std::io::Result<()> {
+    let compressed;
+    let content = match options.compression {
+        Some(compression) => {
+            compressed = compression.compress(content)?;
+            compressed.as_slice()
+        }
+        None => content,
+    };
+    let mut writer = BufWriter::with_capacity(options.buffer_size, File::create(path)?);
+    writer.write_all(content)?;
+    let file = writer.into_inner().map_err(|err| err.into_error())?;
+    if options.hint == IoHint::Nocache {
+        // Dirty pages cannot be dropped, so they are flushed first
+        file.sync_data()?;
+        drop_cached(&file);
+    }
+    Ok(())
+}
+
+/// Parse an `--io-buffer` size: bytes, or a number with a K, M, KiB or MiB suffix
+pub fn parse_size(value: &str) -> std::result::Result<usize, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+
+    let number: usize = number
+        .parse()
+        .map_err(|_| format!("invalid size '{}': expected e.g. 65536, 64K or 1M", value))?;
+    let multiplier = match unit.trim() {
+        "" | "B" => 1,
+        "K" | "k" | "KiB" => 1024,
+        "M" | "m" | "MiB" => 1024 * 1024,
+        _ => {
+            return Err(format!(
+                "invalid size unit '{}' in '{}': use K or M",
+                unit, value
+            ));
+        }
+    };
+    match number.checked_mul(multiplier) {
+        Some(size) if size > 0 => Ok(size),
+        _ => Err(format!("invalid size '{}': must be above zero", value)),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn advise(file: &File, hint: IoHint) {
+    use std::os::fd::AsRawFd;
+
+    if hint == IoHint::Sequential {
+        // SAFETY: the descriptor is open for the call; the advice may be ignored
+        unsafe { libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_SEQUENTIAL) };
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn drop_cached(file: &File) {
+    use std::os::fd::AsRawFd;
+
+    // SAFETY: the descriptor is open for the call; the advice may be ignored
+    unsafe { libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED) };
+}
+
+#[cfg(not(target_os = "linux"))]
+fn advise(_file: &File, _hint: IoHint) {}
+
+#[cfg(not(target_os = "linux"))]
+fn drop_cached(_file: &File) {}
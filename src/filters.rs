@@ -0,0 +1,946 @@
+use std::cmp::Ordering;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::Path;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use hmac::{Hmac, Mac};
+use icu_collator::{Collator, CollatorOptions, Numeric};
+use icu_locid::Locale;
+use ipnet::IpNet;
+use minijinja::value::{Kwargs, Rest};
+use minijinja::{Environment, Error, ErrorKind, Value};
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::stamp::comment_syntax;
+
+/// Maximum length of a Kubernetes label value
+const K8S_LABEL_MAX_LENGTH: usize = 63;
+
+/// Maximum length of a Kubernetes resource name (DNS-1123 subdomain)
+const K8S_NAME_MAX_LENGTH: usize = 253;
+
+/// Filter packs and the filters each one registers, for `--version --json`
+pub const FILTER_PACKS: &[(&str, &[&str])] = &[
+    ("crypto", CRYPTO_FILTERS),
+    ("json", JSON_FILTERS),
+    ("k8s", K8S_FILTERS),
+    ("net", NET_FILTERS),
+    ("sort", SORT_FILTERS),
+    ("text", TEXT_FILTERS),
+];
+
+/// Filters added by [`register_json`]
+pub const JSON_FILTERS: &[&str] = &["tojson_sorted", "canonical_json"];
+
+/// Largest integer an f64 holds exactly; integral floats up to it are written as integers
+const MAX_SAFE_INTEGER: f64 = 9_007_199_254_740_992.0;
+
+/// Filters added by [`register_net`]
+pub const NET_FILTERS: &[&str] = &["ipaddr", "nthhost", "ipsubnet"];
+
+/// Queries understood by the `ipaddr` filter
+const IPADDR_QUERIES: &[&str] = &[
+    "address",
+    "network",
+    "cidr",
+    "prefix",
+    "netmask",
+    "hostmask",
+    "broadcast",
+    "first_usable",
+    "last_usable",
+    "size",
+    "version",
+];
+
+/// Most subnets `ipsubnet` lists at once; pick one by index beyond that
+const MAX_SUBNETS: u128 = 65536;
+
+/// Filters added by [`register_sorting`]
+pub const SORT_FILTERS: &[&str] = &["natsort", "localesort"];
+
+/// Filters added by [`register_text`]
+pub const TEXT_FILTERS: &[&str] = &["nindent", "comment", "wordwrap", "truncate_chars", "center"];
+
+/// Language names the `comment` filter accepts besides file extensions
+const COMMENT_LANGUAGES: &[(&str, &str)] = &[
+    ("shell", "sh"),
+    ("python", "py"),
+    ("ruby", "rb"),
+    ("perl", "pl"),
+    ("make", "mk"),
+    ("terraform", "tf"),
+    ("rust", "rs"),
+    ("javascript", "js"),
+    ("typescript", "ts"),
+    ("kotlin", "kt"),
+    ("csharp", "cs"),
+    ("haskell", "hs"),
+    ("markdown", "md"),
+    ("clojure", "clj"),
+    ("lisp", "lisp"),
+    ("latex", "tex"),
+    ("erlang", "erl"),
+    ("batch", "bat"),
+];
+
+/// Filters added by [`register_crypto`]
+pub const CRYPTO_FILTERS: &[&str] = &["bcrypt", "htpasswd", "hmac"];
+
+/// Filters added by [`register_k8s`]
+pub const K8S_FILTERS: &[&str] = &[
+    "b64encode",
+    "b64decode",
+    "toYaml",
+    "yaml_documents",
+    "k8s_label",
+    "k8s_name",
+];
+
+/// Register the Kubernetes filter pack enabled with `--k8s`
+///
+/// Adds Helm-style helpers that make generating manifests practical:
+/// - `b64encode` / `b64decode` for Secret data
+/// - `toYaml` to serialize values as YAML
+/// - `yaml_documents` to join a list of objects into a multi-document YAML stream
+/// - `k8s_label` to sanitize a string into a valid label value
+/// - `k8s_name` to validate a resource name
+pub fn register_k8s(env: &mut Environment<'_>) {
+    env.add_filter("b64encode", b64encode);
+    env.add_filter("b64decode", b64decode);
+    env.add_filter("toYaml", to_yaml);
+    env.add_filter("yaml_documents", yaml_documents);
+    env.add_filter("k8s_label", k8s_label);
+    env.add_filter("k8s_name", k8s_name);
+}
+
+/// Register the human-friendly sorting filters, available in every template
+///
+/// - `natsort` orders embedded numbers by value, so `host2` comes before `host10`
+/// - `localesort("sv")` orders strings by a locale's collation rules (Unicode
+///   root collation without an argument); `numeric=true` also orders numbers by value
+///
+/// Both accept the `reverse` and `attribute` arguments of the builtin `sort`;
+/// `natsort` also accepts `case_sensitive`.
+pub fn register_sorting(env: &mut Environment<'_>) {
+    env.add_filter("natsort", natsort);
+    env.add_filter("localesort", localesort);
+}
+
+/// Register the password hash and HMAC filters enabled with `--allow-crypto`
+///
+/// - `bcrypt` hashes a password; the salt is random, so the output changes on
+///   every render unless `salt="..."` is given
+/// - `htpasswd("alice")` formats an Apache htpasswd line with a bcrypt hash
+/// - `hmac(key)` signs a message with HMAC-SHA256 (`algorithm`, `encoding` to change)
+///
+/// `bcrypt` and `htpasswd` take `cost` (default 12) and `salt`; the same
+/// password, cost and salt always give the same hash.
+pub fn register_crypto(env: &mut Environment<'_>) {
+    env.add_filter("bcrypt", bcrypt_filter);
+    env.add_filter("htpasswd", htpasswd);
+    env.add_filter("hmac", hmac_filter);
+}
+
+/// Make the crypto filters fail with a pointer to `--allow-crypto`
+pub fn register_crypto_disabled(env: &mut Environment<'_>) {
+    for name in CRYPTO_FILTERS {
+        env.add_filter(
+            *name,
+            move |_: &Value, _: Rest<Value>| -> Result<Value, Error> {
+                Err(Error::new(
+                    ErrorKind::InvalidOperation,
+                    format!(
+                        "the {} filter is disabled; pass --allow-crypto to use it",
+                        name
+                    ),
+                ))
+            },
+        );
+    }
+}
+
+/// Register the stable JSON serialization filters, available in every template
+///
+/// - `tojson_sorted` serializes like `tojson`, with object keys sorted;
+///   `indent=2` pretty-prints
+/// - `canonical_json` writes the compact canonical form (RFC 8785 style), e.g.
+///   for values that are hashed or compared byte for byte
+///
+/// Both write integral floats as integers (`1.0` becomes `1`), so the output
+/// only changes when the data does.
+pub fn register_json(env: &mut Environment<'_>) {
+    env.add_filter("tojson_sorted", tojson_sorted);
+    env.add_filter("canonical_json", canonical_json);
+}
+
+/// Register the IP address and CIDR filters, available in every template
+///
+/// - `ipaddr("network")` and other queries on an address or network
+/// - `nthhost(n)` for the n-th address of a network, counting from the end when negative
+/// - `ipsubnet(prefix)` to split a network into subnets, or `ipsubnet(prefix, n)` for one
+///
+/// Values are IPv4 or IPv6 addresses with an optional `/prefix`; a bare address
+/// is a single-address network.
+pub fn register_net(env: &mut Environment<'_>) {
+    env.add_filter("ipaddr", ipaddr);
+    env.add_filter("nthhost", nthhost);
+    env.add_filter("ipsubnet", ipsubnet);
+}
+
+/// Register the block-formatting filters, available in every template
+///
+/// - `nindent(n)` to splice blocks at a given indentation (pairs with the builtin `indent`)
+/// - `comment("python")` to turn text into comments of a language, e.g. for headers
+/// - `wordwrap(72)`, `truncate_chars(40)` and `center(80)` to lay out plain text
+///   in display columns
+pub fn register_text(env: &mut Environment<'_>) {
+    env.add_filter("nindent", nindent);
+    env.add_filter("comment", comment);
+    env.add_filter("wordwrap", wordwrap);
+    env.add_filter("truncate_chars", truncate_chars);
+    env.add_filter("center", center);
+}
+
+fn bcrypt_filter(password: &str, kwargs: Kwargs) -> Result<String, Error> {
+    let hash = bcrypt_hash(password, &kwargs)?;
+    kwargs.assert_all_used()?;
+    Ok(hash.format_for_version(bcrypt::Version::TwoB))
+}
+
+/// `user:hash`, with the `$2y$` bcrypt prefix Apache expects
+fn htpasswd(password: &str, user: &str, kwargs: Kwargs) -> Result<String, Error> {
+    if user.is_empty() || user.contains(':') {
+        return Err(Error::new(
+            ErrorKind::InvalidOperation,
+            format!(
+                "invalid htpasswd user '{}': must be non-empty without ':'",
+                user
+            ),
+        ));
+    }
+    let hash = bcrypt_hash(password, &kwargs)?;
+    kwargs.assert_all_used()?;
+    Ok(format!(
+        "{}:{}",
+        user,
+        hash.format_for_version(bcrypt::Version::TwoY)
+    ))
+}
+
+/// Hash with the `cost` and `salt` keyword arguments; a salt string is reduced
+/// to bcrypt's 16 bytes through SHA-256
+fn bcrypt_hash(password: &str, kwargs: &Kwargs) -> Result<bcrypt::HashParts, Error> {
+    let cost = kwargs
+        .get::<Option<u32>>("cost")?
+        .unwrap_or(bcrypt::DEFAULT_COST);
+    let hash = match kwargs.get::<Option<&str>>("salt")? {
+        Some(salt) => {
+            let mut bytes = [0u8; 16];
+            bytes.copy_from_slice(&Sha256::digest(salt.as_bytes())[..16]);
+            bcrypt::hash_with_salt(password, cost, bytes)
+        }
+        None => bcrypt::hash_with_result(password, cost),
+    };
+    hash.map_err(|e| Error::new(ErrorKind::InvalidOperation, format!("bcrypt failed: {}", e)))
+}
+
+fn hmac_filter(message: &str, key: &str, kwargs: Kwargs) -> Result<String, Error> {
+    let algorithm = kwargs.get::<Option<&str>>("algorithm")?.unwrap_or("sha256");
+    let encoding = kwargs.get::<Option<&str>>("encoding")?.unwrap_or("hex");
+    kwargs.assert_all_used()?;
+
+    let (key, message) = (key.as_bytes(), message.as_bytes());
+    let digest = match algorithm {
+        "sha256" => hmac_digest::<Hmac<Sha256>>(key, message),
+        "sha384" => hmac_digest::<Hmac<Sha384>>(key, message),
+        "sha512" => hmac_digest::<Hmac<Sha512>>(key, message),
+        _ => {
+            return Err(Error::new(
+                ErrorKind::InvalidOperation,
+                format!(
+                    "unknown hmac algorithm '{}' (known: sha256, sha384, sha512)",
+                    algorithm
+                ),
+            ));
+        }
+    }?;
+    match encoding {
+        "hex" => Ok(digest.iter().map(|byte| format!("{:02x}", byte)).collect()),
+        "base64" => Ok(BASE64.encode(digest)),
+        _ => Err(Error::new(
+            ErrorKind::InvalidOperation,
+            format!("unknown hmac encoding '{}' (known: hex, base64)", encoding),
+        )),
+    }
+}
+
+fn hmac_digest<M: Mac + hmac::digest::KeyInit>(
+    key: &[u8],
+    message: &[u8],
+) -> Result<Vec<u8>, Error> {
+    let mut mac = <M as hmac::digest::KeyInit>::new_from_slice(key).map_err(|e| {
+        Error::new(
+            ErrorKind::InvalidOperation,
+            format!("invalid hmac key: {}", e),
+        )
+    })?;
+    mac.update(message);
+    Ok(mac.finalize().into_bytes().to_vec())
+}
+
+fn b64encode(value: &str) -> String {
+    BASE64.encode(value.as_bytes())
+}
+
+fn b64decode(value: &str) -> Result<String, Error> {
+    let bytes = BASE64.decode(value.trim()).map_err(|e| {
+        Error::new(
+            ErrorKind::InvalidOperation,
+            format!("invalid base64: {}", e),
+        )
+    })?;
+    String::from_utf8(bytes).map_err(|e| {
+        Error::new(
+            ErrorKind::InvalidOperation,
+            format!("decoded base64 is not valid UTF-8: {}", e),
+        )
+    })
+}
+
+/// Indent every non-empty line by `width` spaces and prefix the result with a newline
+fn nindent(value: &str, width: usize) -> String {
+    let padding = " ".repeat(width);
+    let indented: Vec<String> = value
+        .lines()
+        .map(|line| {
+            if line.is_empty() {
+                String::new()
+            } else {
+                format!("{}{}", padding, line)
+            }
+        })
+        .collect();
+    format!("\n{}", indented.join("\n"))
+}
+
+/// Turn text into comments in the syntax of `language` (`shell` by default)
+///
+/// `language` is a name such as `python` or any file extension `--stamp`
+/// knows. Line comments prefix every line, leaving blank lines as a bare
+/// marker; block comments wrap the text, on separate lines when it spans several.
+fn comment(value: &str, language: Option<&str>) -> Result<String, Error> {
+    let language = language.unwrap_or("shell").to_ascii_lowercase();
+    let extension = COMMENT_LANGUAGES
+        .iter()
+        .find(|(name, _)| *name == language)
+        .map_or(language.as_str(), |(_, extension)| extension);
+    let (open, close) = comment_syntax(Path::new(&format!("comment.{}", extension)))
+        .ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidOperation,
+                format!(
+                    "unknown comment language '{}': use a name such as python or rust, or a file extension",
+                    language
+                ),
+            )
+        })?;
+
+    if close.is_empty() {
+        return Ok(value
+            .lines()
+            .map(|line| {
+                if line.trim().is_empty() {
+                    open.to_string()
+                } else {
+                    format!("{} {}", open, line)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n"));
+    }
+    if value.contains(close) {
+        return Err(Error::new(
+            ErrorKind::InvalidOperation,
+            format!("cannot comment text containing '{}'", close),
+        ));
+    }
+    let text = value.trim_end_matches('\n');
+    if text.contains('\n') {
+        Ok(format!("{}\n{}\n{}", open, text, close))
+    } else {
+        Ok(format!("{} {} {}", open, text, close))
+    }
+}
+
+/// Wrap each line of text at `width` display columns (79 by default)
+///
+/// Words are never split unless one is wider than `width` and
+/// `break_long_words` (default true) allows it. Lines are joined with
+/// `wrapstring`, a newline by default; blank lines are kept.
+fn wordwrap(value: &str, width: Option<usize>, kwargs: Kwargs) -> Result<String, Error> {
+    let width = positive_width("wordwrap", width.unwrap_or(79))?;
+    let break_long_words = kwargs
+        .get::<Option<bool>>("break_long_words")?
+        .unwrap_or(true);
+    let wrapstring = kwargs.get::<Option<&str>>("wrapstring")?.unwrap_or("\n");
+    kwargs.assert_all_used()?;
+
+    let mut lines = Vec::new();
+    for paragraph in value.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            let mut word = word.to_string();
+            if !line.is_empty() && line.width() + 1 + word.width() <= width {
+                line.push(' ');
+                line.push_str(&word);
+                continue;
+            }
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+            while break_long_words && word.width() > width {
+                let (mut head, mut tail) = split_at_width(&word, width);
+                if head.is_empty() {
+                    // A single character wider than the line still has to go somewhere
+                    let first = word.chars().next().map_or(0, char::len_utf8);
+                    (head, tail) = word.split_at(first);
+                }
+                lines.push(head.to_string());
+                word = tail.to_string();
+            }
+            line = word;
+        }
+        lines.push(line);
+    }
+    Ok(lines.join(wrapstring))
+}
+
+/// Shorten text to at most `length` display columns, ending in `end` (`...` by default)
+fn truncate_chars(value: &str, length: usize, end: Option<&str>) -> Result<String, Error> {
+    let end = end.unwrap_or("...");
+    if value.width() <= length {
+        return Ok(value.to_string());
+    }
+    let Some(room) = length.checked_sub(end.width()) else {
+        return Err(Error::new(
+            ErrorKind::InvalidOperation,
+            format!(
+                "truncate_chars length {} is shorter than the ending '{}'",
+                length, end
+            ),
+        ));
+    };
+    let (head, _) = split_at_width(value, room);
+    Ok(format!("{}{}", head.trim_end(), end))
+}
+
+/// Center each line of text in `width` display columns (80 by default)
+///
+/// Lines are padded on both sides with `fillchar`, a space by default; the
+/// extra column of an odd padding goes to the right. Wider lines are unchanged.
+fn center(value: &str, width: Option<usize>, fillchar: Option<&str>) -> Result<String, Error> {
+    let width = width.unwrap_or(80);
+    let fill = fillchar.unwrap_or(" ");
+    let mut chars = fill.chars();
+    let (Some(fill), None) = (chars.next(), chars.next()) else {
+        return Err(Error::new(
+            ErrorKind::InvalidOperation,
+            format!("center fillchar must be a single character, got '{}'", fill),
+        ));
+    };
+    if fill.width() != Some(1) {
+        return Err(Error::new(
+            ErrorKind::InvalidOperation,
+            format!("center fillchar '{}' must be one column wide", fill),
+        ));
+    }
+
+    Ok(value
+        .lines()
+        .map(|line| {
+            let padding = width.saturating_sub(line.width());
+            let left = fill.to_string().repeat(padding / 2);
+            let right = fill.to_string().repeat(padding - padding / 2);
+            format!("{}{}{}", left, line, right)
+        })
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+fn positive_width(filter: &str, width: usize) -> Result<usize, Error> {
+    if width == 0 {
+        return Err(Error::new(
+            ErrorKind::InvalidOperation,
+            format!("{} width must be at least 1", filter),
+        ));
+    }
+    Ok(width)
+}
+
+/// Split text after at most `width` display columns
+fn split_at_width(text: &str, width: usize) -> (&str, &str) {
+    let mut columns = 0;
+    for (index, c) in text.char_indices() {
+        columns += c.width().unwrap_or(0);
+        if columns > width {
+            return text.split_at(index);
+        }
+    }
+    (text, "")
+}
+
+/// JSON with sorted keys, escaped like `tojson` so it can be embedded in HTML and scripts
+fn tojson_sorted(value: &Value, indent: Option<usize>, kwargs: Kwargs) -> Result<Value, Error> {
+    let indent = match indent {
+        Some(indent) => Some(indent),
+        None => kwargs.get::<Option<usize>>("indent")?,
+    };
+    kwargs.assert_all_used()?;
+
+    let json = stable_json(value)?;
+    let serialized = match indent {
+        Some(indent) => {
+            let padding = b" ".repeat(indent);
+            let formatter = serde_json::ser::PrettyFormatter::with_indent(&padding);
+            let mut output = Vec::new();
+            let mut serializer = serde_json::Serializer::with_formatter(&mut output, formatter);
+            serde::Serialize::serialize(&json, &mut serializer).map_err(json_error)?;
+            String::from_utf8(output).map_err(|e| json_error(e.utf8_error()))?
+        }
+        None => serde_json::to_string(&json).map_err(json_error)?,
+    };
+    Ok(Value::from_safe_string(
+        serialized
+            .replace('<', "\\u003c")
+            .replace('>', "\\u003e")
+            .replace('&', "\\u0026")
+            .replace('\'', "\\u0027"),
+    ))
+}
+
+/// Compact JSON with sorted keys and normalized numbers, byte-identical for equal data
+fn canonical_json(value: &Value) -> Result<String, Error> {
+    serde_json::to_string(&stable_json(value)?).map_err(json_error)
+}
+
+/// A value as JSON with object keys sorted and integral floats turned into integers
+///
+/// Keys are ordered by UTF-16 code units, as RFC 8785 requires.
+fn stable_json(value: &Value) -> Result<serde_json::Value, Error> {
+    fn normalize(value: serde_json::Value) -> serde_json::Value {
+        match value {
+            serde_json::Value::Object(map) => {
+                let mut entries: Vec<_> = map.into_iter().collect();
+                entries.sort_by(|(a, _), (b, _)| a.encode_utf16().cmp(b.encode_utf16()));
+                serde_json::Value::Object(
+                    entries
+                        .into_iter()
+                        .map(|(key, value)| (key, normalize(value)))
+                        .collect(),
+                )
+            }
+            serde_json::Value::Array(items) => {
+                serde_json::Value::Array(items.into_iter().map(normalize).collect())
+            }
+            serde_json::Value::Number(number) => match number.as_f64() {
+                Some(float)
+                    if !number.is_i64()
+                        && !number.is_u64()
+                        && float.fract() == 0.0
+                        && float.abs() <= MAX_SAFE_INTEGER =>
+                {
+                    serde_json::Value::from(float as i64)
+                }
+                _ => serde_json::Value::Number(number),
+            },
+            other => other,
+        }
+    }
+
+    serde_json::to_value(value)
+        .map(normalize)
+        .map_err(json_error)
+}
+
+fn json_error(err: impl std::fmt::Display) -> Error {
+    Error::new(
+        ErrorKind::InvalidOperation,
+        format!("cannot serialize to JSON: {}", err),
+    )
+}
+
+/// Serialize a value as YAML without the trailing newline
+fn to_yaml(value: &Value) -> Result<String, Error> {
+    let yaml = serde_yaml::to_string(value).map_err(|e| {
+        Error::new(
+            ErrorKind::InvalidOperation,
+            format!("cannot serialize to YAML: {}", e),
+        )
+    })?;
+    Ok(yaml.trim_end_matches('\n').to_string())
+}
+
+/// Serialize each item of a sequence as its own YAML document separated by `---`
+fn yaml_documents(value: &Value) -> Result<String, Error> {
+    let documents = value
+        .try_iter()?
+        .map(|item| to_yaml(&item))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(documents
+        .iter()
+        .map(|doc| format!("---\n{}\n", doc))
+        .collect())
+}
+
+/// Sanitize a string into a valid Kubernetes label value
+///
+/// Invalid characters become `-`, the value is truncated to 63 characters and
+/// leading/trailing non-alphanumeric characters are trimmed.
+fn k8s_label(value: &str) -> String {
+    let sanitized: String = value
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '-'
+            }
+        })
+        .take(K8S_LABEL_MAX_LENGTH)
+        .collect();
+
+    sanitized
+        .trim_matches(|c: char| !c.is_ascii_alphanumeric())
+        .to_string()
+}
+
+/// Validate that a string is a valid Kubernetes resource name (DNS-1123 subdomain)
+fn k8s_name(value: &str) -> Result<String, Error> {
+    let invalid = |reason: &str| {
+        Err(Error::new(
+            ErrorKind::InvalidOperation,
+            format!("invalid Kubernetes resource name '{}': {}", value, reason),
+        ))
+    };
+
+    if value.is_empty() {
+        return invalid("must not be empty");
+    }
+    if value.len() > K8S_NAME_MAX_LENGTH {
+        return invalid("must be no more than 253 characters");
+    }
+    if !value
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '.')
+    {
+        return invalid("must consist of lower case alphanumeric characters, '-' or '.'");
+    }
+    let alphanumeric = |c: Option<char>| c.is_some_and(|c| c.is_ascii_alphanumeric());
+    if !alphanumeric(value.chars().next()) || !alphanumeric(value.chars().last()) {
+        return invalid("must start and end with an alphanumeric character");
+    }
+
+    Ok(value.to_string())
+}
+
+fn natsort(value: &Value, kwargs: Kwargs) -> Result<Value, Error> {
+    let case_sensitive = kwargs
+        .get::<Option<bool>>("case_sensitive")?
+        .unwrap_or(false);
+    sort_items(value, &kwargs, |a, b| match (a.as_str(), b.as_str()) {
+        (Some(a), Some(b)) => natural_cmp(a, b, case_sensitive),
+        _ => a.cmp(b),
+    })
+}
+
+fn localesort(value: &Value, locale: Option<&str>, kwargs: Kwargs) -> Result<Value, Error> {
+    let locale = match locale {
+        Some(locale) => locale.parse::<Locale>().map_err(|e| {
+            Error::new(
+                ErrorKind::InvalidOperation,
+                format!("invalid locale '{}': {}", locale, e),
+            )
+        })?,
+        None => Locale::UND,
+    };
+    let mut options = CollatorOptions::new();
+    if kwargs.get::<Option<bool>>("numeric")?.unwrap_or(false) {
+        options.numeric = Some(Numeric::On);
+    }
+    let collator = Collator::try_new(&(&locale).into(), options).map_err(|e| {
+        Error::new(
+            ErrorKind::InvalidOperation,
+            format!("no collation rules for locale '{}': {}", locale, e),
+        )
+    })?;
+
+    sort_items(value, &kwargs, |a, b| match (a.as_str(), b.as_str()) {
+        (Some(a), Some(b)) => collator.compare(a, b),
+        _ => a.cmp(b),
+    })
+}
+
+/// Stable-sort a sequence with `compare`, honouring `reverse` and `attribute`
+///
+/// Items lacking the attribute sort first; values of different kinds are
+/// ordered the way the builtin `sort` orders them.
+fn sort_items(
+    value: &Value,
+    kwargs: &Kwargs,
+    compare: impl Fn(&Value, &Value) -> Ordering,
+) -> Result<Value, Error> {
+    let reverse = kwargs.get::<Option<bool>>("reverse")?.unwrap_or(false);
+    let attribute = kwargs.get::<Option<&str>>("attribute")?;
+    let mut items = value
+        .try_iter()
+        .map_err(|e| {
+            Error::new(ErrorKind::InvalidOperation, "cannot convert value to list").with_source(e)
+        })?
+        .map(|item| {
+            let key = match attribute {
+                Some(path) => lookup_path(&item, path),
+                None => item.clone(),
+            };
+            (key, item)
+        })
+        .collect::<Vec<_>>();
+    kwargs.assert_all_used()?;
+
+    items.sort_by(|(a, _), (b, _)| {
+        let ordering = compare(a, b);
+        if reverse {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    });
+    Ok(Value::from(
+        items.into_iter().map(|(_, item)| item).collect::<Vec<_>>(),
+    ))
+}
+
+/// Look up a dotted path such as `meta.name` or `ports.0`, undefined if missing
+fn lookup_path(value: &Value, path: &str) -> Value {
+    path.split('.').fold(value.clone(), |current, segment| {
+        let item = match segment.parse::<usize>() {
+            Ok(index) => current.get_item_by_index(index),
+            Err(_) => current.get_attr(segment),
+        };
+        item.unwrap_or(Value::UNDEFINED)
+    })
+}
+
+/// Compare strings treating runs of ASCII digits as numbers
+///
+/// Equal numbers with different zero padding order the shorter one first
+/// (`1` < `01`) so the order stays total.
+fn natural_cmp(a: &str, b: &str, case_sensitive: bool) -> Ordering {
+    let (mut a, mut b) = (a, b);
+    let mut tie = Ordering::Equal;
+    loop {
+        let (Some(ca), Some(cb)) = (a.chars().next(), b.chars().next()) else {
+            return a.len().cmp(&b.len()).then(tie);
+        };
+
+        if ca.is_ascii_digit() && cb.is_ascii_digit() {
+            let (digits_a, rest_a) = split_digits(a);
+            let (digits_b, rest_b) = split_digits(b);
+            let (value_a, value_b) = (
+                digits_a.trim_start_matches('0'),
+                digits_b.trim_start_matches('0'),
+            );
+            let ordering = value_a
+                .len()
+                .cmp(&value_b.len())
+                .then_with(|| value_a.cmp(value_b));
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+            tie = tie.then(digits_a.len().cmp(&digits_b.len()));
+            (a, b) = (rest_a, rest_b);
+        } else {
+            let ordering = if case_sensitive {
+                ca.cmp(&cb)
+            } else {
+                let folded = ca.to_lowercase().cmp(cb.to_lowercase());
+                tie = tie.then(ca.cmp(&cb));
+                folded
+            };
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+            (a, b) = (&a[ca.len_utf8()..], &b[cb.len_utf8()..]);
+        }
+    }
+}
+
+fn split_digits(value: &str) -> (&str, &str) {
+    let end = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    value.split_at(end)
+}
+
+fn ipaddr(value: &str, query: Option<&str>) -> Result<Value, Error> {
+    let net = parse_net(value)?;
+    let Some(query) = query else {
+        return Ok(Value::from(value));
+    };
+    let usable = |first: bool| {
+        let last = last_offset(&net);
+        // /31 and /32 (RFC 3021) and all IPv6 networks have no reserved addresses
+        let reserved = matches!(net, IpNet::V4(_)) && last > 1;
+        let offset = match (first, reserved) {
+            (true, reserved) => u128::from(reserved),
+            (false, true) => last - 1,
+            (false, false) => last,
+        };
+        Value::from(nth_address(&net, offset).to_string())
+    };
+    Ok(match query {
+        "address" => Value::from(net.addr().to_string()),
+        "network" => Value::from(net.network().to_string()),
+        "cidr" => Value::from(net.trunc().to_string()),
+        "prefix" => Value::from(net.prefix_len()),
+        "netmask" => Value::from(net.netmask().to_string()),
+        "hostmask" => Value::from(net.hostmask().to_string()),
+        "broadcast" => match net {
+            IpNet::V4(net) => Value::from(net.broadcast().to_string()),
+            IpNet::V6(_) => {
+                return Err(Error::new(
+                    ErrorKind::InvalidOperation,
+                    format!("IPv6 network '{}' has no broadcast address", value),
+                ));
+            }
+        },
+        "first_usable" => usable(true),
+        "last_usable" => usable(false),
+        "size" => match last_offset(&net).checked_add(1) {
+            Some(size) => Value::from(size),
+            None => {
+                return Err(Error::new(
+                    ErrorKind::InvalidOperation,
+                    format!("'{}' has too many addresses to count", value),
+                ));
+            }
+        },
+        "version" => Value::from(match net {
+            IpNet::V4(_) => 4,
+            IpNet::V6(_) => 6,
+        }),
+        _ => {
+            return Err(Error::new(
+                ErrorKind::InvalidOperation,
+                format!(
+                    "unknown ipaddr query '{}' (known: {})",
+                    query,
+                    IPADDR_QUERIES.join(", ")
+                ),
+            ));
+        }
+    })
+}
+
+fn nthhost(value: &str, n: i64) -> Result<String, Error> {
+    let net = parse_net(value)?;
+    let last = last_offset(&net);
+    let offset = if n >= 0 {
+        Some(n as u128).filter(|offset| *offset <= last)
+    } else {
+        last.checked_sub(u128::from(n.unsigned_abs() - 1))
+    };
+    let offset = offset.ok_or_else(|| {
+        Error::new(
+            ErrorKind::InvalidOperation,
+            format!("'{}' has no address at index {}", net.trunc(), n),
+        )
+    })?;
+    Ok(nth_address(&net, offset).to_string())
+}
+
+fn ipsubnet(value: &str, prefix: u8, index: Option<i64>) -> Result<Value, Error> {
+    let net = parse_net(value)?.trunc();
+    if prefix < net.prefix_len() || prefix > net.max_prefix_len() {
+        return Err(Error::new(
+            ErrorKind::InvalidOperation,
+            format!(
+                "cannot split '{}' into /{} subnets: the prefix must be between {} and {}",
+                net,
+                prefix,
+                net.prefix_len(),
+                net.max_prefix_len()
+            ),
+        ));
+    }
+    let subnet_bits = u32::from(prefix - net.prefix_len());
+    let last = 1u128
+        .checked_shl(subnet_bits)
+        .map_or(u128::MAX, |count| count - 1);
+    let host_bits = u32::from(net.max_prefix_len() - prefix);
+    let subnet = |index: u128| {
+        let offset = index.checked_shl(host_bits).unwrap_or(0);
+        IpNet::new(nth_address(&net, offset), prefix)
+            .map(|subnet| subnet.to_string())
+            .unwrap_or_default()
+    };
+
+    match index {
+        Some(n) => {
+            let index = if n >= 0 {
+                Some(n as u128).filter(|index| *index <= last)
+            } else {
+                last.checked_sub(u128::from(n.unsigned_abs() - 1))
+            };
+            let index = index.ok_or_else(|| {
+                Error::new(
+                    ErrorKind::InvalidOperation,
+                    format!("'{}' has no /{} subnet at index {}", net, prefix, n),
+                )
+            })?;
+            Ok(Value::from(subnet(index)))
+        }
+        None if last >= MAX_SUBNETS => Err(Error::new(
+            ErrorKind::InvalidOperation,
+            format!(
+                "'{}' splits into more than {} /{} subnets; pick one with ipsubnet({}, index)",
+                net, MAX_SUBNETS, prefix, prefix
+            ),
+        )),
+        None => Ok(Value::from((0..=last).map(subnet).collect::<Vec<_>>())),
+    }
+}
+
+/// Parse `10.0.0.1/24` or a bare address, which becomes a single-address network
+fn parse_net(value: &str) -> Result<IpNet, Error> {
+    let value = value.trim();
+    value
+        .parse::<IpNet>()
+        .or_else(|_| value.parse::<IpAddr>().map(IpNet::from))
+        .map_err(|_| {
+            Error::new(
+                ErrorKind::InvalidOperation,
+                format!("invalid IP address or network '{}'", value),
+            )
+        })
+}
+
+/// Offset of the last address of a network from its first
+fn last_offset(net: &IpNet) -> u128 {
+    let host_bits = u32::from(net.max_prefix_len() - net.prefix_len());
+    1u128
+        .checked_shl(host_bits)
+        .map_or(u128::MAX, |size| size - 1)
+}
+
+/// The address `offset` addresses after the start of the network
+fn nth_address(net: &IpNet, offset: u128) -> IpAddr {
+    match net.network() {
+        IpAddr::V4(network) => IpAddr::V4(Ipv4Addr::from(
+            u32::from(network).wrapping_add(offset as u32),
+        )),
+        IpAddr::V6(network) => IpAddr::V6(Ipv6Addr::from(u128::from(network).wrapping_add(offset))),
+    }
+}
@@ -0,0 +1,65 @@
+//! Entry points for the fuzz targets in `fuzz/` (`fuzz` feature)
+//!
+//! Each function feeds arbitrary bytes to one of the parsers that see
+//! user-supplied strings. Errors are expected and ignored; a panic, hang or
+//! crash is a bug.
+
+use std::collections::HashMap;
+
+use crate::config::{
+    ConfigContext, ConfigLoader, JsonConfigLoader, TomlConfigLoader, YamlConfigLoader,
+};
+use crate::rendering::{RenderOptions, render_template_with_options};
+use crate::variables::collect_cli_variables;
+
+/// Parse the input as `-D` assignments, one flag per line
+pub fn define(data: &[u8]) {
+    let Ok(input) = std::str::from_utf8(data) else {
+        return;
+    };
+    let definitions: Vec<String> = input.lines().map(str::to_string).collect();
+    let _ = collect_cli_variables(&mut HashMap::new(), &definitions);
+}
+
+/// Parse the input as a config file; the first byte selects JSON, YAML or TOML
+pub fn config(data: &[u8]) {
+    let Some((selector, rest)) = data.split_first() else {
+        return;
+    };
+    let Ok(content) = std::str::from_utf8(rest) else {
+        return;
+    };
+    let loaded = match selector % 3 {
+        0 => JsonConfigLoader.load_config(content),
+        1 => YamlConfigLoader.load_config(content),
+        _ => TomlConfigLoader.load_config(content),
+    };
+    let Ok(config) = loaded else {
+        return;
+    };
+    if let Ok(mut config) = config.resolve_conditionals(&ConfigContext::current(Some("fuzz"))) {
+        config.take_derived();
+        let _ = config.take_meta();
+    }
+}
+
+/// Render the input as a template; a first line starting with `-D ` defines variables
+pub fn render(data: &[u8]) {
+    let Ok(input) = std::str::from_utf8(data) else {
+        return;
+    };
+    let (definitions, template) = match input.split_once('\n') {
+        Some((first, rest)) if first.starts_with("-D ") => (vec![first[3..].to_string()], rest),
+        _ => (Vec::new(), input),
+    };
+
+    let mut variables = HashMap::new();
+    if collect_cli_variables(&mut variables, &definitions).is_err() {
+        return;
+    }
+    let options = RenderOptions {
+        k8s: true,
+        ..Default::default()
+    };
+    let _ = render_template_with_options(template, &variables, "fuzz", &options);
+}
@@ -0,0 +1,191 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+use crate::diagnostics::Diagnostics;
+use crate::error::{ContextExt, Result, ShinkansenError};
+use crate::project::ProjectConfig;
+use crate::verify::{file_command, shell_command};
+
+/// Run the `[[hook]]` commands of `project` on written outputs, `jobs` files at a time
+///
+/// The hooks of one file run in config order and stop at its first failure;
+/// other files are unaffected. Returns a description of each failure, in the
+/// order of `outputs`, so failures can be collected across the whole run.
+pub fn run_post_write_hooks(
+    project: &ProjectConfig,
+    outputs: &[PathBuf],
+    jobs: usize,
+) -> Vec<String> {
+    if project.hooks.is_empty() {
+        return Vec::new();
+    }
+    crate::processing::parallel_map(outputs, jobs, |output| {
+        project
+            .hooks_for(output)
+            .into_iter()
+            .find_map(|command| run_hook(command, output).err())
+    })
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+/// Turn the hook failures of a run into one error listing all of them
+pub fn report_hook_failures(failures: &[String]) -> Result<()> {
+    if failures.is_empty() {
+        return Ok(());
+    }
+    Err(ShinkansenError::ValidationError(format!(
+        "{} post-write hook(s) failed:\n{}",
+        failures.len(),
+        failures.join("\n")
+    )))
+}
+
+/// Run one hook on `output`, describing the failure with the command's output
+fn run_hook(command: &str, output: &Path) -> std::result::Result<(), String> {
+    let result = file_command(command, output)
+        .output()
+        .map_err(|e| format!("{}: failed to run '{}': {}", output.display(), command, e))?;
+    if result.status.success() {
+        return Ok(());
+    }
+
+    let mut message = format!(
+        "{}: '{}' failed ({})",
+        output.display(),
+        command,
+        result.status
+    );
+    for stream in [&result.stdout, &result.stderr] {
+        let text = String::from_utf8_lossy(stream);
+        if !text.trim().is_empty() {
+            message.push('\n');
+            message.push_str(text.trim_end());
+        }
+    }
+    Err(message)
+}
+
+/// Prefix of the environment variables carrying the template variables to lifecycle hooks
+pub const HOOK_VAR_PREFIX: &str = "SHINKANSEN_VAR_";
+
+/// Environment variable holding the error message for `on_failure` hooks
+pub const HOOK_ERROR_VAR: &str = "SHINKANSEN_ERROR";
+
+/// Run `process` between the `[hooks]` lifecycle commands of `project`
+///
+/// `before` commands run first and stop the run at the first failure; `after`
+/// commands run once `process` succeeded. When anything fails, the
+/// `on_failure` commands run with the error in `SHINKANSEN_ERROR`; their own
+/// failures become warnings so the original error is the one reported.
+pub fn with_lifecycle_hooks(
+    project: &ProjectConfig,
+    variables: &HashMap<String, minijinja::Value>,
+    diagnostics: &Diagnostics,
+    process: impl FnOnce() -> Result<()>,
+) -> Result<()> {
+    let hooks = &project.lifecycle;
+    if hooks.before.is_empty() && hooks.after.is_empty() && hooks.on_failure.is_empty() {
+        return process();
+    }
+
+    let env = hook_environment(variables);
+    let result = run_lifecycle_commands("before", &hooks.before, &env)
+        .and_then(|_| process())
+        .and_then(|_| run_lifecycle_commands("after", &hooks.after, &env));
+    if let Err(err) = &result {
+        let mut env = env.clone();
+        env.push((HOOK_ERROR_VAR.to_string(), err.to_string()));
+        for command in &hooks.on_failure {
+            if let Err(hook_err) = run_lifecycle_command("on_failure", command, &env) {
+                diagnostics.warn(hook_err.to_string());
+            }
+        }
+    }
+    result
+}
+
+/// Template variables as environment variables, e.g. `SHINKANSEN_VAR_DB_HOST`
+///
+/// Names are upper-cased with other characters replaced by `_`. Maps are
+/// exported as JSON and also flattened into one variable per leaf; lists are
+/// exported as JSON and other values as their text.
+pub fn hook_environment(variables: &HashMap<String, minijinja::Value>) -> Vec<(String, String)> {
+    fn export(name: String, value: &minijinja::Value, env: &mut Vec<(String, String)>) {
+        use minijinja::value::ValueKind;
+
+        match value.kind() {
+            ValueKind::Map => {
+                if let Ok(keys) = value.try_iter() {
+                    for key in keys {
+                        if let Ok(item) = value.get_item(&key) {
+                            export(
+                                format!("{}_{}", name, env_name(&key.to_string())),
+                                &item,
+                                env,
+                            );
+                        }
+                    }
+                }
+                env.push((name, serde_json::to_string(value).unwrap_or_default()));
+            }
+            ValueKind::Seq => env.push((name, serde_json::to_string(value).unwrap_or_default())),
+            ValueKind::Undefined | ValueKind::None => env.push((name, String::new())),
+            _ => env.push((name, value.to_string())),
+        }
+    }
+
+    let mut env = Vec::new();
+    for (name, value) in variables {
+        // A deferred source that fails to fetch is exported as empty
+        let value = crate::context::resolve(value).unwrap_or_default();
+        export(
+            format!("{}{}", HOOK_VAR_PREFIX, env_name(name)),
+            &value,
+            &mut env,
+        );
+    }
+    env.sort();
+    env
+}
+
+fn env_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+fn run_lifecycle_commands(
+    stage: &str,
+    commands: &[String],
+    env: &[(String, String)],
+) -> Result<()> {
+    commands
+        .iter()
+        .try_for_each(|command| run_lifecycle_command(stage, command, env))
+}
+
+/// Run a lifecycle command with its output on stderr, keeping stdout for rendered output
+fn run_lifecycle_command(stage: &str, command: &str, env: &[(String, String)]) -> Result<()> {
+    let status = shell_command(command)
+        .envs(env.iter().map(|(name, value)| (name, value)))
+        .stdin(Stdio::null())
+        .stdout(Stdio::from(std::io::stderr()))
+        .status()
+        .with_context(|| format!("Failed to run {} hook: {}", stage, command))?;
+    if status.success() {
+        return Ok(());
+    }
+    Err(ShinkansenError::ValidationError(format!(
+        "{} hook '{}' failed ({})",
+        stage, command, status
+    )))
+}
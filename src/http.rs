@@ -0,0 +1,109 @@
+use std::time::Duration;
+
+use crate::error::{Result, ShinkansenError};
+use crate::network::NetworkOptions;
+use crate::retry::{Failure, RetryPolicy};
+
+/// Timeout applied to every remote request unless `--http-timeout` is given
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Body and content type of a successful HTTP response
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+    pub body: String,
+    pub content_type: Option<String>,
+}
+
+/// HTTP client shared by the remote data sources
+///
+/// Requests fail on non-success status codes and are retried according to
+/// the retry policy; client errors other than 408 and 429 are not retried.
+/// Proxies come from `HTTP_PROXY`/`HTTPS_PROXY`/`ALL_PROXY`, honoring `NO_PROXY`.
+pub struct Client {
+    agent: ureq::Agent,
+    retry: RetryPolicy,
+}
+
+impl Client {
+    pub fn new(network: &NetworkOptions) -> Result<Self> {
+        use ureq::tls::{Certificate, RootCerts, TlsConfig};
+
+        let mut tls = TlsConfig::builder().disable_verification(network.insecure_skip_verify);
+        if let Some(certificates) = network.ca_certificates()? {
+            let certificates: Vec<Certificate<'static>> = certificates
+                .into_iter()
+                .map(|der| Certificate::from_der(&der).to_owned())
+                .collect();
+            tls = tls.root_certs(RootCerts::new_with_certs(&certificates));
+        }
+
+        let agent = ureq::Agent::config_builder()
+            .timeout_global(Some(network.timeout.unwrap_or(REQUEST_TIMEOUT)))
+            .proxy(ureq::Proxy::try_from_env())
+            .tls_config(tls.build())
+            .build()
+            .into();
+
+        Ok(Client {
+            agent,
+            retry: network.retry.clone(),
+        })
+    }
+
+    /// Fetch a URL with GET
+    pub fn get(&self, url: &str) -> Result<HttpResponse> {
+        self.get_with_headers(url, &[])
+    }
+
+    /// Fetch a URL with GET and extra request headers (e.g. authentication tokens)
+    pub fn get_with_headers(&self, url: &str, headers: &[(&str, &str)]) -> Result<HttpResponse> {
+        self.retry.run(&format!("GET {}", url), || {
+            let mut request = self.agent.get(url);
+            for (name, value) in headers {
+                request = request.header(*name, *value);
+            }
+            read_response(url, request.call())
+        })
+    }
+
+    /// POST a JSON body to a URL
+    pub fn post_json(&self, url: &str, body: &str) -> Result<HttpResponse> {
+        self.retry.run(&format!("POST {}", url), || {
+            let response = self
+                .agent
+                .post(url)
+                .header("content-type", "application/json")
+                .send(body);
+            read_response(url, response)
+        })
+    }
+}
+
+fn read_response(
+    url: &str,
+    response: std::result::Result<ureq::http::Response<ureq::Body>, ureq::Error>,
+) -> std::result::Result<HttpResponse, Failure> {
+    let mut response = response.map_err(|e| {
+        let error = ShinkansenError::DataSourceError(format!("Failed to fetch {}: {}", url, e));
+        match e {
+            ureq::Error::StatusCode(status)
+                if (400..500).contains(&status) && status != 408 && status != 429 =>
+            {
+                Failure::Permanent(error)
+            }
+            _ => Failure::Transient(error),
+        }
+    })?;
+
+    let content_type = response
+        .headers()
+        .get("content-type")
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_string());
+
+    let body = response.body_mut().read_to_string().map_err(|e| {
+        ShinkansenError::DataSourceError(format!("Failed to read response from {}: {}", url, e))
+    })?;
+
+    Ok(HttpResponse { body, content_type })
+}
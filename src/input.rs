@@ -0,0 +1,726 @@
+//! Where templates come from
+//!
+//! Every template is listed and read through an [`InputSource`]. The command
+//! line uses [`default_source`]: stdin without inputs, the tree of a git ref
+//! with `--git-ref`, and otherwise local files and directories, `.tar`
+//! archives (also `.tar.gz` and `.tar.zst`) and, with the `http` feature,
+//! `http(s)://` URLs. Embedders can pass their own source to
+//! [`crate::processing::process_inputs_from`], e.g. templates kept in memory.
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+
+use crate::cache::Cache;
+use crate::cli::Cli;
+use crate::diagnostics::Diagnostics;
+use crate::error::{ContextExt, Result, ShinkansenError};
+use crate::fileio::{Compression, IoOptions, read_file};
+use crate::network::NetworkOptions;
+use crate::platform::normalize_path;
+use crate::processing::{Excludes, fragment_order, input_files, local_input_files, validate_path};
+
+/// Stream of input files to render, as they are discovered
+pub type InputFiles<'a> = Box<dyn Iterator<Item = Result<PathBuf>> + 'a>;
+
+/// The path the template read from standard input goes by
+pub const STDIN_PATH: &str = "stdin";
+
+/// Lists the templates of a run and reads their content
+///
+/// Listed paths decide where outputs go, the same way local paths do: below
+/// `-o`, a file keeps its path relative to the input it was found in. Files
+/// are read from several threads at once. Includes and `read_file` still
+/// resolve on the local filesystem.
+pub trait InputSource: Sync {
+    /// The files to render, in order
+    fn files(&self) -> Result<InputFiles<'_>>;
+
+    /// The content of a listed file (of [`STDIN_PATH`] when rendering stdin)
+    fn read(&self, path: &Path) -> Result<Vec<u8>>;
+}
+
+/// Local files, directories and globs, as named by the command line inputs
+pub struct FilesystemSource<'a> {
+    cli: &'a Cli,
+    io: IoOptions,
+}
+
+impl<'a> FilesystemSource<'a> {
+    pub fn new(cli: &'a Cli) -> Self {
+        FilesystemSource {
+            cli,
+            io: IoOptions::from_cli(cli),
+        }
+    }
+}
+
+impl InputSource for FilesystemSource<'_> {
+    fn files(&self) -> Result<InputFiles<'_>> {
+        input_files(self.cli)
+    }
+
+    fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        read_file(path, &self.io).with_context(|| format!("Failed to read file: {:?}", path))
+    }
+}
+
+/// The one template piped in on standard input
+pub struct StdinSource;
+
+impl InputSource for StdinSource {
+    fn files(&self) -> Result<InputFiles<'_>> {
+        Ok(Box::new(std::iter::once(Ok(PathBuf::from(STDIN_PATH)))))
+    }
+
+    fn read(&self, _path: &Path) -> Result<Vec<u8>> {
+        let mut content = Vec::new();
+        std::io::stdin()
+            .read_to_end(&mut content)
+            .with_context(|| "Failed to read from stdin")?;
+        Ok(content)
+    }
+}
+
+/// The regular files of a tar archive, held in memory
+///
+/// Files are listed as `ARCHIVE/MEMBER` in path order, so the archive acts
+/// like a directory input. Members named `.gz` or `.zst` are decompressed.
+pub struct ArchiveSource {
+    path: PathBuf,
+    members: BTreeMap<PathBuf, Vec<u8>>,
+}
+
+impl ArchiveSource {
+    /// Read the archive at `path`, decompressing a `.tar.gz` or `.tar.zst`
+    pub fn open(path: &Path, io: &IoOptions) -> Result<Self> {
+        let tar =
+            read_file(path, io).with_context(|| format!("Failed to read file: {:?}", path))?;
+        Self::new(normalize_path(path), &tar)
+    }
+
+    /// Parse `tar`, whose files are listed below `path`
+    pub fn new(path: impl Into<PathBuf>, tar: &[u8]) -> Result<Self> {
+        let path = path.into();
+        let members = read_tar(tar).map_err(|message| {
+            ShinkansenError::FileSystemError(format!(
+                "Invalid tar archive {}: {}",
+                path.display(),
+                message
+            ))
+        })?;
+        let mut archive = ArchiveSource {
+            path,
+            members: BTreeMap::new(),
+        };
+        for (name, content) in members {
+            let member = Path::new(&name);
+            if member.has_root() {
+                return Err(ShinkansenError::SecurityError(format!(
+                    "Invalid member {:?} in {} - absolute paths are not allowed",
+                    name,
+                    archive.path.display()
+                )));
+            }
+            validate_path(member).with_context(|| {
+                format!("Invalid member {:?} in {}", name, archive.path.display())
+            })?;
+            // `./app.conf`, as tar writes it, is listed as `app.conf`
+            let member: PathBuf = member
+                .components()
+                .filter(|component| matches!(component, Component::Normal(_)))
+                .collect();
+            archive.members.insert(member, content);
+        }
+        Ok(archive)
+    }
+
+    /// The path the archive's files are listed below
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl InputSource for ArchiveSource {
+    fn files(&self) -> Result<InputFiles<'_>> {
+        Ok(Box::new(
+            self.members.keys().map(|member| Ok(self.path.join(member))),
+        ))
+    }
+
+    fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        let content = path
+            .strip_prefix(&self.path)
+            .ok()
+            .and_then(|member| self.members.get(member))
+            .ok_or_else(|| {
+                ShinkansenError::FileSystemError(format!(
+                    "{:?} is not a file in {}",
+                    path,
+                    self.path.display()
+                ))
+            })?;
+        decompress(path, content.clone())
+    }
+}
+
+const TAR_BLOCK: usize = 512;
+
+/// The regular files of a tar archive, as (name, content) in archive order
+///
+/// Reads ustar archives with pax (`path`) and GNU long name extensions;
+/// directories, links and other entries are skipped.
+fn read_tar(tar: &[u8]) -> std::result::Result<Vec<(String, Vec<u8>)>, String> {
+    let text = |field: &[u8]| {
+        let end = field
+            .iter()
+            .position(|byte| *byte == 0)
+            .unwrap_or(field.len());
+        String::from_utf8_lossy(&field[..end]).into_owned()
+    };
+    let mut members = Vec::new();
+    let mut long_name = None;
+    let mut offset = 0;
+    while offset + TAR_BLOCK <= tar.len() {
+        let header = &tar[offset..offset + TAR_BLOCK];
+        if header.iter().all(|byte| *byte == 0) {
+            break;
+        }
+        let size_field = text(&header[124..136]);
+        let size = usize::from_str_radix(size_field.trim(), 8)
+            .map_err(|_| format!("bad size {:?} at offset {}", size_field, offset))?;
+        let start = offset + TAR_BLOCK;
+        let content = tar
+            .get(start..start + size)
+            .ok_or_else(|| format!("truncated entry at offset {}", offset))?;
+        offset = start + size.next_multiple_of(TAR_BLOCK);
+
+        match header[156] {
+            b'0' | b'\0' | b'7' => {
+                let name = match long_name.take() {
+                    Some(name) => name,
+                    None if &header[257..262] == b"ustar" && header[345] != 0 => {
+                        format!("{}/{}", text(&header[345..500]), text(&header[..100]))
+                    }
+                    None => text(&header[..100]),
+                };
+                members.push((name, content.to_vec()));
+            }
+            b'L' => long_name = Some(text(content)),
+            b'x' => long_name = pax_path(content).or(long_name),
+            _ => long_name = None,
+        }
+    }
+    Ok(members)
+}
+
+/// The `path` record of a pax extended header: `LEN path=VALUE\n`
+fn pax_path(records: &[u8]) -> Option<String> {
+    let mut rest = records;
+    while !rest.is_empty() {
+        let space = rest.iter().position(|byte| *byte == b' ')?;
+        let len: usize = std::str::from_utf8(&rest[..space]).ok()?.parse().ok()?;
+        let record = rest.get(space + 1..len)?;
+        if let Some(value) = record.strip_prefix(b"path=") {
+            let value = value.strip_suffix(b"\n").unwrap_or(value);
+            return Some(String::from_utf8_lossy(value).into_owned());
+        }
+        rest = &rest[len..];
+    }
+    None
+}
+
+/// Whether an input names a tar archive to render the files of
+pub fn is_archive_input(input: &str) -> bool {
+    [".tar", ".tar.gz", ".tar.zst"]
+        .iter()
+        .any(|extension| input.ends_with(extension))
+        && Path::new(input).is_file()
+}
+
+/// A `--git-ref` value: `REF`, or `REF:PATH` to render the tree at `PATH`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitRef {
+    /// A commit, branch or tag, e.g. `origin/main`
+    pub reference: String,
+    /// The directory or file of the tree to render, from the repository root
+    pub path: Option<String>,
+}
+
+/// Parse a `--git-ref` value; refs cannot contain `:`, so the first one ends the ref
+pub fn parse_git_ref(value: &str) -> std::result::Result<GitRef, String> {
+    let (reference, path) = match value.split_once(':') {
+        Some((reference, path)) => (reference, Some(path.trim_end_matches('/').to_string())),
+        None => (value, None),
+    };
+    if reference.is_empty() {
+        return Err(format!(
+            "invalid git ref '{}': expected REF or REF:PATH, e.g. origin/main:templates/",
+            value
+        ));
+    }
+    Ok(GitRef {
+        reference: reference.to_string(),
+        path,
+    })
+}
+
+/// The files of a commit, branch or tag, read without a checkout
+///
+/// Inputs are paths in the ref's tree from the repository root, as in
+/// `git show REF:PATH`; an empty path is the whole tree. The repository is
+/// found like git finds it (`GIT_DIR`, or the current directory and its
+/// parents), so a bare repository works. With the `git` feature trees are
+/// read in-process with libgit2, otherwise through the `git` executable.
+pub struct GitSource {
+    reference: String,
+    inputs: Vec<String>,
+    recursive: bool,
+}
+
+impl GitSource {
+    /// The files of `inputs` at `reference`; directories only list their own files unless `recursive`
+    pub fn new(reference: impl Into<String>, inputs: Vec<String>, recursive: bool) -> Self {
+        GitSource {
+            reference: reference.into(),
+            inputs,
+            recursive,
+        }
+    }
+
+    /// The files of one input: the file itself, or the files of a directory
+    fn input_files(&self, input: &str) -> Result<Vec<PathBuf>> {
+        let input = input.trim_end_matches('/');
+        let input = if input == "." { "" } else { input };
+        let files = self.tree_files(input)?.ok_or_else(|| {
+            ShinkansenError::FileSystemError(format!(
+                "Input does not exist in {}: {:?}",
+                self.reference, input
+            ))
+        })?;
+        Ok(files
+            .into_iter()
+            .map(|path| normalize_path(Path::new(&path)))
+            .collect())
+    }
+
+    /// The blob paths at or below `path`, or `None` if the tree has no such entry
+    #[cfg(feature = "git")]
+    fn tree_files(&self, path: &str) -> Result<Option<Vec<String>>> {
+        use git2::{ObjectType, TreeWalkMode, TreeWalkResult};
+
+        let repo = open_repository()?;
+        let root = self.tree(&repo)?;
+        let tree = if path.is_empty() {
+            root
+        } else {
+            let Ok(entry) = root.get_path(Path::new(path)) else {
+                return Ok(None);
+            };
+            match entry.kind() {
+                Some(ObjectType::Blob) => return Ok(Some(vec![path.to_string()])),
+                Some(ObjectType::Tree) => repo.find_tree(entry.id()).map_err(git_error)?,
+                _ => return Ok(Some(Vec::new())),
+            }
+        };
+
+        let prefix = if path.is_empty() {
+            String::new()
+        } else {
+            format!("{}/", path)
+        };
+        let mut files = Vec::new();
+        if self.recursive {
+            tree.walk(TreeWalkMode::PreOrder, |dir, entry| {
+                if entry.kind() == Some(ObjectType::Blob)
+                    && let Some(name) = entry.name()
+                {
+                    files.push(format!("{}{}{}", prefix, dir, name));
+                }
+                TreeWalkResult::Ok
+            })
+            .map_err(git_error)?;
+        } else {
+            files.extend(
+                tree.iter()
+                    .filter(|entry| entry.kind() == Some(ObjectType::Blob))
+                    .filter_map(|entry| entry.name().map(|name| format!("{}{}", prefix, name))),
+            );
+        }
+        Ok(Some(files))
+    }
+
+    #[cfg(feature = "git")]
+    fn tree<'r>(&self, repo: &'r git2::Repository) -> Result<git2::Tree<'r>> {
+        repo.revparse_single(&self.reference)
+            .and_then(|object| object.peel_to_tree())
+            .map_err(|e| {
+                ShinkansenError::FileSystemError(format!(
+                    "Cannot read git ref {}: {}",
+                    self.reference,
+                    e.message()
+                ))
+            })
+    }
+
+    #[cfg(feature = "git")]
+    fn blob(&self, path: &Path) -> Result<Vec<u8>> {
+        let repo = open_repository()?;
+        let blob = self
+            .tree(&repo)?
+            .get_path(path)
+            .and_then(|entry| entry.to_object(&repo))
+            .and_then(|object| object.peel_to_blob())
+            .map_err(|e| {
+                ShinkansenError::FileSystemError(format!(
+                    "Failed to read {:?} from {}: {}",
+                    path,
+                    self.reference,
+                    e.message()
+                ))
+            })?;
+        Ok(blob.content().to_vec())
+    }
+
+    /// The blob paths at or below `path`, or `None` if the tree has no such entry
+    #[cfg(not(feature = "git"))]
+    fn tree_files(&self, path: &str) -> Result<Option<Vec<String>>> {
+        let entries = if path.is_empty() {
+            self.ls_tree(".", self.recursive)?
+        } else {
+            match self.ls_tree(path, false)?.as_slice() {
+                [] => return Ok(None),
+                [(kind, _)] if kind == "tree" => {
+                    self.ls_tree(&format!("{}/", path), self.recursive)?
+                }
+                entries => entries.to_vec(),
+            }
+        };
+        Ok(Some(
+            entries
+                .into_iter()
+                .filter(|(kind, _)| kind == "blob")
+                .map(|(_, path)| path)
+                .collect(),
+        ))
+    }
+
+    /// The entries `git ls-tree` lists for `pathspec`, as (type, path)
+    #[cfg(not(feature = "git"))]
+    fn ls_tree(&self, pathspec: &str, recursive: bool) -> Result<Vec<(String, String)>> {
+        let mut args = vec!["ls-tree", "-z", "--full-tree"];
+        if recursive {
+            args.push("-r");
+        }
+        args.extend([self.reference.as_str(), "--", pathspec]);
+        let listing = git(&args)?;
+        Ok(listing
+            .split(|byte| *byte == 0)
+            .filter_map(|entry| {
+                let entry = std::str::from_utf8(entry).ok()?;
+                let (info, path) = entry.split_once('\t')?;
+                let kind = info.split(' ').nth(1)?;
+                Some((kind.to_string(), path.to_string()))
+            })
+            .collect())
+    }
+
+    #[cfg(not(feature = "git"))]
+    fn blob(&self, path: &Path) -> Result<Vec<u8>> {
+        let path_in_tree = path.to_string_lossy().replace('\\', "/");
+        git(&[
+            "cat-file",
+            "blob",
+            &format!("{}:{}", self.reference, path_in_tree),
+        ])
+    }
+}
+
+impl InputSource for GitSource {
+    fn files(&self) -> Result<InputFiles<'_>> {
+        let mut files = Vec::new();
+        for input in &self.inputs {
+            files.extend(self.input_files(input)?);
+        }
+        Ok(Box::new(files.into_iter().map(Ok)))
+    }
+
+    fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        decompress(path, self.blob(path)?)
+    }
+}
+
+#[cfg(feature = "git")]
+fn open_repository() -> Result<git2::Repository> {
+    git2::Repository::open_from_env().map_err(|e| {
+        ShinkansenError::FileSystemError(format!("Cannot open git repository: {}", e.message()))
+    })
+}
+
+#[cfg(feature = "git")]
+fn git_error(e: git2::Error) -> ShinkansenError {
+    ShinkansenError::FileSystemError(format!("Failed to read git tree: {}", e.message()))
+}
+
+/// Run `git` and return its stdout
+#[cfg(not(feature = "git"))]
+fn git(args: &[&str]) -> Result<Vec<u8>> {
+    let output = std::process::Command::new("git")
+        .args(args)
+        .output()
+        .map_err(|e| {
+            ShinkansenError::FileSystemError(format!(
+                "Failed to run git: {}. Is it installed and on PATH?",
+                e
+            ))
+        })?;
+    if !output.status.success() {
+        return Err(ShinkansenError::FileSystemError(format!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(output.stdout)
+}
+
+/// Whether an input is a template to download
+pub fn is_remote_input(input: &str) -> bool {
+    input.starts_with("http://") || input.starts_with("https://")
+}
+
+/// Templates downloaded from `http(s)://` URLs, listed as the URL itself
+///
+/// Downloads go through the cache, so `--cache-ttl` and `--offline` apply as
+/// they do to remote data sources.
+pub struct RemoteSource {
+    urls: Vec<String>,
+    network: NetworkOptions,
+    cache: Cache,
+}
+
+impl RemoteSource {
+    pub fn new(urls: Vec<String>, network: NetworkOptions, cache: Cache) -> Self {
+        RemoteSource {
+            urls,
+            network,
+            cache,
+        }
+    }
+}
+
+impl InputSource for RemoteSource {
+    fn files(&self) -> Result<InputFiles<'_>> {
+        Ok(Box::new(self.urls.iter().map(|url| Ok(PathBuf::from(url)))))
+    }
+
+    fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        let url = path.to_string_lossy();
+        let fetched = self.cache.get_or_fetch(&format!("template:{}", url), || {
+            fetch_template(&url, &self.network)
+        })?;
+        match fetched.as_str() {
+            Some(content) => Ok(content.as_bytes().to_vec()),
+            None => Err(ShinkansenError::DataSourceError(format!(
+                "Invalid cached copy of template {}",
+                url
+            ))),
+        }
+    }
+}
+
+#[cfg(feature = "http")]
+fn fetch_template(url: &str, network: &NetworkOptions) -> Result<serde_json::Value> {
+    let response = crate::http::Client::new(network)?.get(url)?;
+    Ok(serde_json::Value::String(response.body))
+}
+
+#[cfg(not(feature = "http"))]
+fn fetch_template(url: &str, _network: &NetworkOptions) -> Result<serde_json::Value> {
+    Err(ShinkansenError::DataSourceError(format!(
+        "Cannot fetch template {}: shinkansen was built without the 'http' feature",
+        url
+    )))
+}
+
+/// Decompress content whose name ends in `.gz` or `.zst`, like local files
+fn decompress(path: &Path, content: Vec<u8>) -> Result<Vec<u8>> {
+    match Compression::from_path(path) {
+        Some(compression) => compression
+            .decompress(&content)
+            .with_context(|| format!("Failed to read file: {:?}", path)),
+        None => Ok(content),
+    }
+}
+
+/// The source a command line reads its templates from
+///
+/// Stdin when there are no inputs (or `-`), the tree of `--git-ref` when one
+/// is given, and otherwise each input by its kind: a URL, an archive, or
+/// local files. Archives are read up front. `--exclude` applies to archive
+/// and git listings as it does to directories.
+pub fn default_source<'a>(
+    cli: &'a Cli,
+    diagnostics: &Diagnostics,
+) -> Result<Box<dyn InputSource + 'a>> {
+    if reads_stdin(cli) {
+        return Ok(Box::new(StdinSource));
+    }
+    let inputs = cli_inputs(cli).into_owned();
+    let io = IoOptions::from_cli(cli);
+    let mut archives = Vec::new();
+    for input in inputs.iter().filter(|input| is_archive_input(input)) {
+        archives.push(ArchiveSource::open(Path::new(input), &io)?);
+    }
+    Ok(Box::new(DefaultSource {
+        cli,
+        files: FilesystemSource::new(cli),
+        archives,
+        git: cli
+            .git_ref
+            .as_ref()
+            .map(|git_ref| GitSource::new(&git_ref.reference, inputs.clone(), cli.recursive)),
+        remote: RemoteSource::new(
+            inputs
+                .iter()
+                .filter(|input| is_remote_input(input))
+                .cloned()
+                .collect(),
+            NetworkOptions::from_cli(cli),
+            Cache::from_cli(cli, diagnostics),
+        ),
+        excludes: Excludes::from_cli(cli)?,
+        inputs,
+    }))
+}
+
+struct DefaultSource<'a> {
+    cli: &'a Cli,
+    files: FilesystemSource<'a>,
+    archives: Vec<ArchiveSource>,
+    git: Option<GitSource>,
+    remote: RemoteSource,
+    excludes: Excludes,
+    inputs: Vec<String>,
+}
+
+impl DefaultSource<'_> {
+    /// Drop the files `--exclude` matches below `root`
+    fn exclude<'s>(&'s self, files: InputFiles<'s>, root: &'s Path) -> InputFiles<'s> {
+        Box::new(files.filter(move |file| {
+            match file {
+                Ok(file) => !file
+                    .strip_prefix(root)
+                    .is_ok_and(|relative| self.excludes.excludes_file(relative)),
+                Err(_) => true,
+            }
+        }))
+    }
+}
+
+impl InputSource for DefaultSource<'_> {
+    fn files(&self) -> Result<InputFiles<'_>> {
+        if let Some(git) = &self.git {
+            let mut streams = Vec::new();
+            for input in &self.inputs {
+                let files = Box::new(git.input_files(input)?.into_iter().map(Ok));
+                let files = self.exclude(files, Path::new(input.as_str()));
+                streams.push(fragment_order(files, self.cli)?);
+            }
+            return Ok(Box::new(streams.into_iter().flatten()));
+        }
+
+        let mut streams = Vec::new();
+        for input in &self.inputs {
+            if is_remote_input(input) {
+                streams.push(Box::new(std::iter::once(Ok(PathBuf::from(input)))) as InputFiles);
+            } else if let Some(archive) = self
+                .archives
+                .iter()
+                .find(|archive| archive.path() == normalize_path(Path::new(input)))
+            {
+                let files = self.exclude(archive.files()?, archive.path());
+                streams.push(fragment_order(files, self.cli)?);
+            } else {
+                streams.push(local_input_files(self.cli, input, &self.excludes)?);
+            }
+        }
+        Ok(Box::new(streams.into_iter().flatten()))
+    }
+
+    fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        if let Some(git) = &self.git {
+            return git.read(path);
+        }
+        if path.to_str().is_some_and(is_remote_input) {
+            return self.remote.read(path);
+        }
+        match self
+            .archives
+            .iter()
+            .find(|archive| path.starts_with(archive.path()))
+        {
+            Some(archive) => archive.read(path),
+            None => self.files.read(path),
+        }
+    }
+}
+
+/// The inputs a command line names: its INPUT arguments, or the `PATH` of `--git-ref REF:PATH`
+pub fn cli_inputs(cli: &Cli) -> Cow<'_, [String]> {
+    match cli
+        .git_ref
+        .as_ref()
+        .and_then(|git_ref| git_ref.path.as_ref())
+    {
+        Some(path) if cli.inputs.is_empty() => Cow::Owned(vec![path.clone()]),
+        _ => Cow::Borrowed(&cli.inputs),
+    }
+}
+
+/// Whether a command line renders the template piped in on stdin: no inputs, or `-`
+pub fn reads_stdin(cli: &Cli) -> bool {
+    let inputs = cli_inputs(cli);
+    inputs.is_empty() || (inputs.len() == 1 && inputs[0] == "-")
+}
+
+/// Reject inputs these options cannot read or write back
+pub fn validate_input_sources(cli: &Cli) -> Result<()> {
+    if let Some(git_ref) = &cli.git_ref {
+        if git_ref.path.is_some() && !cli.inputs.is_empty() {
+            return Err(ShinkansenError::ValidationError(format!(
+                "--git-ref {}:{} names the inputs itself; drop the INPUT arguments or the :PATH",
+                git_ref.reference,
+                git_ref.path.as_deref().unwrap_or_default()
+            )));
+        }
+        if reads_stdin(cli) || cli.inputs.iter().any(|input| input == "-") {
+            return Err(ShinkansenError::ValidationError(
+                "--git-ref needs input paths or REF:PATH; stdin is not read from git".to_string(),
+            ));
+        }
+        if let Some(input) = cli
+            .inputs
+            .iter()
+            .find(|input| crate::platform::is_glob_input(input))
+        {
+            return Err(ShinkansenError::ValidationError(format!(
+                "--git-ref inputs are paths in the tree, and {:?} is a glob",
+                input
+            )));
+        }
+    }
+    if cli.in_place
+        && let Some(input) = cli
+            .inputs
+            .iter()
+            .find(|input| is_remote_input(input) || is_archive_input(input))
+    {
+        return Err(ShinkansenError::ValidationError(format!(
+            "--in-place needs local input files and cannot write back to {}",
+            input
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,52 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+
+use signal_hook::consts::{SIGINT, SIGTERM};
+
+use crate::error::{ContextExt, Result, ShinkansenError};
+
+/// Exit status of a run stopped by a signal, as shells report ctrl-c
+pub const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+/// Set once SIGINT or SIGTERM asks the run to stop
+fn stop_flag() -> &'static Arc<AtomicBool> {
+    static STOP: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+    STOP.get_or_init(Default::default)
+}
+
+/// Stop gracefully on the first SIGINT or SIGTERM (ctrl-c), and at once on the second
+///
+/// After the first signal no further file starts rendering. Files already
+/// rendering finish, and outputs are replaced whole, so an interrupted run
+/// leaves every output either untouched or completely written.
+pub fn install_handlers() -> Result<()> {
+    for signal in [SIGINT, SIGTERM] {
+        // Registered first, so it only sees the flag set by an earlier signal
+        signal_hook::flag::register_conditional_shutdown(
+            signal,
+            INTERRUPTED_EXIT_CODE,
+            Arc::clone(stop_flag()),
+        )
+        .and_then(|_| signal_hook::flag::register(signal, Arc::clone(stop_flag())))
+        .with_context(|| "Failed to install signal handlers")?;
+    }
+    Ok(())
+}
+
+/// Ask the run to stop, as a signal does
+pub fn request_stop() {
+    stop_flag().store(true, Ordering::SeqCst);
+}
+
+/// Whether the run was asked to stop
+pub fn stop_requested() -> bool {
+    stop_flag().load(Ordering::SeqCst)
+}
+
+/// The error ending an interrupted run, saying how far it got
+pub fn interrupted_error(written: usize) -> ShinkansenError {
+    ShinkansenError::Interrupted(format!(
+        "Interrupted: {} output file(s) written; the remaining inputs were not rendered",
+        written
+    ))
+}
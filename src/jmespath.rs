@@ -0,0 +1,23 @@
+//! JMESPath queries filtering structured data sources (`--data-url NAME=URL#EXPR`)
+//!
+//! Expressions are compiled and evaluated by the `jmespath` crate, which
+//! implements the whole specification, including expression references and
+//! the functions taking them (`sort_by(hosts, &name)`, `map(&ip, hosts)`).
+
+use serde_json::Value;
+
+use crate::error::{Result, ShinkansenError};
+
+/// Evaluate a JMESPath expression against JSON data
+pub fn search(expression: &str, data: &Value) -> Result<Value> {
+    let invalid = |e: ::jmespath::JmespathError| {
+        ShinkansenError::VariableParseError(format!(
+            "Invalid JMESPath expression '{}': {} at column {}",
+            expression, e.reason, e.column
+        ))
+    };
+    let result = ::jmespath::compile(expression)
+        .and_then(|compiled| compiled.search(data))
+        .map_err(invalid)?;
+    Ok(serde_json::to_value(&*result)?)
+}
@@ -0,0 +1,57 @@
+//! JSON Lines data files (`--data NAME=events.jsonl`)
+//!
+//! Each non-blank line holds one JSON value, and the file is exposed as the
+//! list of those values. The file is read a line at a time, so only the parsed
+//! values are held in memory, never the whole text.
+
+use std::io::BufRead;
+use std::path::Path;
+
+use crate::error::{ContextExt, Result, ShinkansenError};
+use crate::fileio::IoOptions;
+
+/// Whether `path` names a JSON Lines file: `.jsonl` or `.ndjson`, also compressed
+pub fn is_json_lines(path: &Path) -> bool {
+    matches!(
+        crate::fileio::content_extension(path).as_deref(),
+        Some("jsonl" | "ndjson")
+    )
+}
+
+/// Read a JSON Lines file into a list of values
+pub fn read_json_lines(path: &Path, io: &IoOptions) -> Result<serde_json::Value> {
+    let reader = crate::fileio::open_file(path, io)
+        .with_context(|| format!("Failed to read data file {:?}", path))?;
+    parse_json_lines(reader)
+        .map(serde_json::Value::Array)
+        .map_err(|e| {
+            ShinkansenError::DataSourceError(format!("Invalid data file {:?}: {}", path, e))
+        })
+}
+
+/// Parse one JSON value per line, skipping blank lines
+///
+/// A line that cannot be read or is not valid JSON is an error naming its line number.
+pub fn parse_json_lines(
+    mut reader: impl BufRead,
+) -> std::result::Result<Vec<serde_json::Value>, String> {
+    let mut values = Vec::new();
+    let mut line = String::new();
+    let mut number = 0;
+    loop {
+        line.clear();
+        number += 1;
+        let read = reader
+            .read_line(&mut line)
+            .map_err(|e| format!("line {}: {}", number, e))?;
+        if read == 0 {
+            return Ok(values);
+        }
+        let text = line.trim_start_matches('\u{feff}').trim();
+        if text.is_empty() {
+            continue;
+        }
+        let value = serde_json::from_str(text).map_err(|e| format!("line {}: {}", number, e))?;
+        values.push(value);
+    }
+}
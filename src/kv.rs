@@ -0,0 +1,436 @@
+use crate::cache::Cache;
+use crate::cli::Cli;
+use crate::diagnostics::Diagnostics;
+use crate::error::{Result, ShinkansenError};
+use crate::network::NetworkOptions;
+use crate::retry::{Failure, RetryPolicy};
+use crate::sources::{SourceTask, SourceVariables};
+
+/// Consul agent used when neither --consul-addr nor CONSUL_HTTP_ADDR is set
+pub const DEFAULT_CONSUL_ADDR: &str = "http://127.0.0.1:8500";
+
+/// etcd endpoint used when neither --etcd-endpoint nor ETCDCTL_ENDPOINTS is set
+pub const DEFAULT_ETCD_ENDPOINT: &str = "http://127.0.0.1:2379";
+
+/// Prepare the Consul/etcd subtrees and Kubernetes ConfigMaps/Secrets for fetching
+///
+/// Consul and etcd keys are taken relative to the prefix and split on `/`, so
+/// with `--consul-prefix app/config/` the key `app/config/db/host` becomes
+/// `{{ db.host }}`. ConfigMap and Secret data keys become variables directly.
+/// Values are exposed as strings.
+pub fn kv_source_tasks(cli: &Cli, diagnostics: &Diagnostics) -> Result<Vec<SourceTask>> {
+    let cache = Cache::from_cli(cli, diagnostics);
+    let network = NetworkOptions::from_cli(cli);
+    let mut tasks = Vec::new();
+
+    if !cli.consul_prefixes.is_empty() {
+        let address = resolve_address(cli.consul_addr.as_deref(), "CONSUL_HTTP_ADDR")
+            .unwrap_or_else(|| DEFAULT_CONSUL_ADDR.to_string());
+        for prefix in &cli.consul_prefixes {
+            let (address, prefix) = (address.clone(), prefix.clone());
+            let (cache, network) = (cache.clone(), network.clone());
+
+            tasks.push(SourceTask::new(
+                format!("--consul-prefix {}", prefix),
+                move || {
+                    let tree = cache
+                        .get_or_fetch(&format!("consul:{}/{}", address, prefix), || {
+                            build_tree(&prefix, fetch_consul(&address, &prefix, &network)?)
+                        })?;
+                    Ok(tree_variables(tree))
+                },
+            ));
+        }
+    }
+
+    if !cli.etcd_prefixes.is_empty() {
+        let endpoint = resolve_address(cli.etcd_endpoint.as_deref(), "ETCDCTL_ENDPOINTS")
+            .unwrap_or_else(|| DEFAULT_ETCD_ENDPOINT.to_string());
+        for prefix in &cli.etcd_prefixes {
+            let (endpoint, prefix) = (endpoint.clone(), prefix.clone());
+            let (cache, network) = (cache.clone(), network.clone());
+
+            tasks.push(SourceTask::new(
+                format!("--etcd-prefix {}", prefix),
+                move || {
+                    let tree = cache
+                        .get_or_fetch(&format!("etcd:{}/{}", endpoint, prefix), || {
+                            build_tree(&prefix, fetch_etcd(&endpoint, &prefix, &network)?)
+                        })?;
+                    Ok(tree_variables(tree))
+                },
+            ));
+        }
+    }
+
+    for (flag, kind, specs) in [
+        ("--k8s-configmap", "configmap", &cli.k8s_configmaps),
+        ("--k8s-secret", "secret", &cli.k8s_secrets),
+    ] {
+        for spec in specs {
+            let source = KubernetesSource::parse(spec, flag)?;
+            let network = network.clone();
+            let variable = source.variable.clone();
+
+            let task = SourceTask::new(format!("{} {}", flag, spec), move || {
+                let data = source.fetch(kind, &network.retry)?;
+                Ok(source.variables(data))
+            });
+            // Without VAR= the data keys, unknown until fetched, become the variables
+            tasks.push(match variable {
+                Some(variable) => task.provides(variable),
+                None => task,
+            });
+        }
+    }
+
+    Ok(tasks)
+}
+
+/// A ConfigMap or Secret reference of the form `[VAR=][NAMESPACE/]NAME`
+///
+/// Without a namespace the current kubeconfig context's namespace is used.
+/// With `VAR=` the data keys are nested under `VAR` instead of being exposed
+/// as top-level variables, which helps with keys such as `app.properties`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KubernetesSource {
+    pub variable: Option<String>,
+    pub namespace: Option<String>,
+    pub name: String,
+}
+
+impl KubernetesSource {
+    pub fn parse(spec: &str, flag: &str) -> Result<Self> {
+        let invalid = || {
+            ShinkansenError::VariableParseError(format!(
+                "Invalid {} value: '{}'. Use [VAR=][NAMESPACE/]NAME",
+                flag, spec
+            ))
+        };
+
+        let (variable, reference) = match spec.split_once('=') {
+            Some((variable, reference)) => (Some(variable.trim()), reference.trim()),
+            None => (None, spec.trim()),
+        };
+        let (namespace, name) = match reference.split_once('/') {
+            Some((namespace, name)) => (Some(namespace), name),
+            None => (None, reference),
+        };
+
+        if variable.is_some_and(str::is_empty)
+            || namespace.is_some_and(str::is_empty)
+            || name.is_empty()
+            || name.contains('/')
+        {
+            return Err(invalid());
+        }
+
+        Ok(KubernetesSource {
+            variable: variable.map(String::from),
+            namespace: namespace.map(String::from),
+            name: name.to_string(),
+        })
+    }
+
+    /// Read the object's data with kubectl, decoding Secret values
+    ///
+    /// Missing objects and permission errors are not retried.
+    fn fetch(
+        &self,
+        kind: &str,
+        retry: &RetryPolicy,
+    ) -> Result<serde_json::Map<String, serde_json::Value>> {
+        let mut command = std::process::Command::new("kubectl");
+        command.args(["get", kind, &self.name, "--output", "json"]);
+        if let Some(namespace) = &self.namespace {
+            command.args(["--namespace", namespace]);
+        }
+
+        let output = retry.run(&format!("kubectl get {} {}", kind, self.name), || {
+            let output = command.output().map_err(|e| {
+                Failure::Permanent(ShinkansenError::DataSourceError(format!(
+                    "Failed to run kubectl: {}. Is it installed and on PATH?",
+                    e
+                )))
+            })?;
+            if output.status.success() {
+                return Ok(output);
+            }
+
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let error = ShinkansenError::DataSourceError(format!(
+                "kubectl get {} {} failed: {}",
+                kind,
+                self.name,
+                stderr.trim()
+            ));
+            if stderr.contains("(NotFound)") || stderr.contains("(Forbidden)") {
+                Err(Failure::Permanent(error))
+            } else {
+                Err(Failure::Transient(error))
+            }
+        })?;
+
+        let object: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+        kubernetes_data(&object, kind == "secret")
+    }
+
+    fn variables(&self, data: serde_json::Map<String, serde_json::Value>) -> SourceVariables {
+        match &self.variable {
+            Some(variable) => vec![(variable.clone(), serde_json::Value::Object(data))],
+            None => data.into_iter().collect(),
+        }
+    }
+}
+
+/// Extract the `data` of a ConfigMap or Secret object as string values
+///
+/// Secret values (and ConfigMap `binaryData`) are base64 encoded by the API;
+/// Secret values are decoded, binary ConfigMap data is kept encoded.
+pub fn kubernetes_data(
+    object: &serde_json::Value,
+    decode: bool,
+) -> Result<serde_json::Map<String, serde_json::Value>> {
+    use base64::Engine;
+    use base64::engine::general_purpose::STANDARD as BASE64;
+
+    let mut data = serde_json::Map::new();
+    for section in ["binaryData", "data"] {
+        let Some(entries) = object.get(section).and_then(|v| v.as_object()) else {
+            continue;
+        };
+        for (key, value) in entries {
+            let text = value.as_str().unwrap_or_default();
+            let text = if decode && section == "data" {
+                let bytes = BASE64.decode(text).map_err(|e| {
+                    ShinkansenError::DataSourceError(format!(
+                        "Invalid base64 in Secret key '{}': {}",
+                        key, e
+                    ))
+                })?;
+                String::from_utf8_lossy(&bytes).into_owned()
+            } else {
+                text.to_string()
+            };
+            data.insert(key.clone(), serde_json::Value::String(text));
+        }
+    }
+
+    Ok(data)
+}
+
+/// Build a nested object from key/value pairs found under a prefix
+///
+/// Directory placeholders (keys ending in `/`) are skipped. A key that is both
+/// a value and the parent of other keys is reported as an error.
+pub fn build_tree(prefix: &str, pairs: Vec<(String, String)>) -> Result<serde_json::Value> {
+    let mut root = serde_json::Map::new();
+
+    for (key, value) in pairs {
+        if key.ends_with('/') {
+            continue;
+        }
+
+        let relative = key.strip_prefix(prefix).unwrap_or(&key);
+        let segments: Vec<&str> = relative.split('/').filter(|s| !s.is_empty()).collect();
+        let Some((last, parents)) = segments.split_last() else {
+            continue;
+        };
+
+        let conflict = || {
+            ShinkansenError::DataSourceError(format!(
+                "Key '{}' conflicts with another key under prefix '{}'",
+                key, prefix
+            ))
+        };
+
+        let mut current = &mut root;
+        for segment in parents {
+            current = current
+                .entry(segment.to_string())
+                .or_insert_with(|| serde_json::Value::Object(serde_json::Map::new()))
+                .as_object_mut()
+                .ok_or_else(conflict)?;
+        }
+
+        if current
+            .get(*last)
+            .is_some_and(|existing| existing.is_object())
+        {
+            return Err(conflict());
+        }
+        current.insert(last.to_string(), serde_json::Value::String(value));
+    }
+
+    Ok(serde_json::Value::Object(root))
+}
+
+/// Compute the etcd range end that selects every key starting with `prefix`
+///
+/// Mirrors `clientv3.GetPrefixRangeEnd`: the last byte that is not 0xff is
+/// incremented and everything after it dropped; an all-0xff prefix selects
+/// every key (`\0`).
+pub fn prefix_range_end(prefix: &[u8]) -> Vec<u8> {
+    let mut end = prefix.to_vec();
+    while let Some(last) = end.pop() {
+        if last < 0xff {
+            end.push(last + 1);
+            return end;
+        }
+    }
+    vec![0]
+}
+
+fn tree_variables(tree: serde_json::Value) -> SourceVariables {
+    match tree {
+        serde_json::Value::Object(map) => map.into_iter().collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Pick the address from the flag, falling back to an environment variable
+///
+/// Bare `host:port` values (as accepted by the consul and etcdctl CLIs) get an
+/// `http://` scheme, and only the first of a comma-separated endpoint list is used.
+fn resolve_address(flag: Option<&str>, env_var: &str) -> Option<String> {
+    let value = flag
+        .map(|value| value.to_string())
+        .or_else(|| std::env::var(env_var).ok())?;
+    let first = value.split(',').next().unwrap_or("").trim();
+    if first.is_empty() {
+        return None;
+    }
+
+    let address = if first.contains("://") {
+        first.to_string()
+    } else {
+        format!("http://{}", first)
+    };
+    Some(address.trim_end_matches('/').to_string())
+}
+
+#[cfg(feature = "http")]
+fn fetch_consul(
+    address: &str,
+    prefix: &str,
+    network: &NetworkOptions,
+) -> Result<Vec<(String, String)>> {
+    use base64::Engine;
+    use base64::engine::general_purpose::STANDARD as BASE64;
+
+    #[derive(serde::Deserialize)]
+    struct ConsulEntry {
+        #[serde(rename = "Key")]
+        key: String,
+        #[serde(rename = "Value")]
+        value: Option<String>,
+    }
+
+    let url = format!("{}/v1/kv/{}?recurse=true", address, prefix);
+    let token = std::env::var("CONSUL_HTTP_TOKEN").ok();
+    let headers: Vec<(&str, &str)> = token
+        .as_deref()
+        .map(|token| ("X-Consul-Token", token))
+        .into_iter()
+        .collect();
+    let response = crate::http::Client::new(network)?.get_with_headers(&url, &headers)?;
+
+    let entries: Vec<ConsulEntry> = serde_json::from_str(&response.body)?;
+    entries
+        .into_iter()
+        .map(|entry| {
+            let value = match entry.value {
+                Some(encoded) => decode_value(&BASE64.decode(encoded).map_err(|e| {
+                    ShinkansenError::DataSourceError(format!(
+                        "Invalid value for Consul key '{}': {}",
+                        entry.key, e
+                    ))
+                })?),
+                None => String::new(),
+            };
+            Ok((entry.key, value))
+        })
+        .collect()
+}
+
+#[cfg(feature = "http")]
+fn fetch_etcd(
+    endpoint: &str,
+    prefix: &str,
+    network: &NetworkOptions,
+) -> Result<Vec<(String, String)>> {
+    use base64::Engine;
+    use base64::engine::general_purpose::STANDARD as BASE64;
+
+    #[derive(serde::Deserialize)]
+    struct RangeResponse {
+        #[serde(default)]
+        kvs: Vec<KeyValue>,
+    }
+
+    #[derive(serde::Deserialize)]
+    struct KeyValue {
+        key: String,
+        #[serde(default)]
+        value: String,
+    }
+
+    // The v3 JSON gateway expects base64-encoded keys
+    let key = if prefix.is_empty() {
+        vec![0]
+    } else {
+        prefix.as_bytes().to_vec()
+    };
+    let request = serde_json::json!({
+        "key": BASE64.encode(&key),
+        "range_end": BASE64.encode(prefix_range_end(prefix.as_bytes())),
+    });
+    let url = format!("{}/v3/kv/range", endpoint);
+    let response = crate::http::Client::new(network)?.post_json(&url, &request.to_string())?;
+
+    let range: RangeResponse = serde_json::from_str(&response.body)?;
+    range
+        .kvs
+        .into_iter()
+        .map(|kv| {
+            let invalid = |e: base64::DecodeError| {
+                ShinkansenError::DataSourceError(format!(
+                    "Invalid etcd response from {}: {}",
+                    url, e
+                ))
+            };
+            let key = decode_value(&BASE64.decode(&kv.key).map_err(invalid)?);
+            let value = decode_value(&BASE64.decode(&kv.value).map_err(invalid)?);
+            Ok((key, value))
+        })
+        .collect()
+}
+
+#[cfg(feature = "http")]
+fn decode_value(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+#[cfg(not(feature = "http"))]
+fn fetch_consul(
+    address: &str,
+    _prefix: &str,
+    _network: &NetworkOptions,
+) -> Result<Vec<(String, String)>> {
+    Err(ShinkansenError::DataSourceError(format!(
+        "Cannot read from Consul at {}: shinkansen was built without the 'http' feature",
+        address
+    )))
+}
+
+#[cfg(not(feature = "http"))]
+fn fetch_etcd(
+    endpoint: &str,
+    _prefix: &str,
+    _network: &NetworkOptions,
+) -> Result<Vec<(String, String)>> {
+    Err(ShinkansenError::DataSourceError(format!(
+        "Cannot read from etcd at {}: shinkansen was built without the 'http' feature",
+        endpoint
+    )))
+}
@@ -1,10 +1,53 @@
 //! Shinkansen library - CLI file preprocessor using MiniJinja templates
 
+pub mod backend;
+pub mod bench;
+pub mod cache;
 pub mod cli;
+pub mod completion;
 pub mod config;
+pub mod context;
+pub mod coverage;
+pub mod data;
+pub mod describe;
+pub mod diagnostics;
+pub mod diff_vars;
+pub mod doctor;
 pub mod error;
+pub mod fileio;
+pub mod filters;
+#[cfg(feature = "fuzz")]
+pub mod fuzz;
+pub mod hooks;
+#[cfg(feature = "http")]
+pub mod http;
+pub mod input;
+pub mod interrupt;
+#[cfg(feature = "http")]
+pub mod jmespath;
+pub mod jsonl;
+pub mod kv;
+pub mod managed;
+pub mod manifest;
+pub mod network;
 pub mod output;
+pub mod patch;
+pub mod per_file;
 pub mod platform;
 pub mod processing;
+pub mod profiling;
+pub mod project;
+pub mod provenance;
 pub mod rendering;
+pub mod retry;
+pub mod run;
+pub mod sandbox;
+pub mod session;
+pub mod sources;
+pub mod stamp;
+pub mod tabular;
+pub mod transaction;
 pub mod variables;
+pub mod verify;
+pub mod version;
+pub mod x509;
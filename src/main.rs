@@ -1,50 +1,221 @@
+pub mod backend;
+pub mod bench;
+pub mod cache;
 pub mod cli;
+pub mod completion;
 pub mod config;
+pub mod context;
+pub mod coverage;
+pub mod data;
+pub mod describe;
+pub mod diagnostics;
+pub mod diff_vars;
+pub mod doctor;
 pub mod error;
+pub mod fileio;
+pub mod filters;
+#[cfg(feature = "fuzz")]
+pub mod fuzz;
+pub mod hooks;
+#[cfg(feature = "http")]
+pub mod http;
+pub mod input;
+pub mod interrupt;
+#[cfg(feature = "http")]
+pub mod jmespath;
+pub mod jsonl;
+pub mod kv;
+pub mod managed;
+pub mod manifest;
+pub mod network;
 pub mod output;
+pub mod patch;
+pub mod per_file;
 pub mod platform;
 pub mod processing;
+pub mod profiling;
+pub mod project;
+pub mod provenance;
 pub mod rendering;
+pub mod retry;
+pub mod run;
+pub mod sandbox;
+pub mod session;
+pub mod sources;
+pub mod stamp;
+pub mod tabular;
+pub mod transaction;
 pub mod variables;
+pub mod verify;
+pub mod version;
+pub mod x509;
 
 use clap::{CommandFactory, Parser};
-use clap_complete::generate;
+use clap_complete::{CompleteEnv, generate};
 use cli::{Cli, Commands};
 use std::io;
 
 fn main() {
+    // Answer the shell when called back by dynamic completions (COMPLETE=bash shinkansen)
+    CompleteEnv::with_factory(Cli::command)
+        .var(completion::COMPLETE_VAR)
+        .complete();
+
     let cli = Cli::parse();
 
+    if cli.version {
+        let info = version::VersionInfo::current();
+        println!(
+            "{}",
+            if cli.json {
+                info.to_json()
+            } else {
+                info.text()
+            }
+        );
+        return;
+    }
+
     // Handle subcommands
-    if let Some(command) = cli.command {
+    if let Some(command) = &cli.command {
         match command {
-            Commands::Completions { shell } => {
+            Commands::Completions { shell, dynamic } => {
                 let mut cmd = Cli::command();
                 let name = cmd.get_name().to_string();
-                generate(shell, &mut cmd, name, &mut io::stdout());
+                if *dynamic {
+                    if let Err(err) =
+                        completion::write_registration(&shell.to_string(), &name, &mut io::stdout())
+                    {
+                        exit_with_error(&cli, err);
+                    }
+                } else {
+                    generate(*shell, &mut cmd, name, &mut io::stdout());
+                }
+                return;
+            }
+            Commands::Describe => {
+                match variables::collect_variable_context(&cli) {
+                    Ok(context) => {
+                        print!("{}", describe::describe_variables(&context));
+                        if let Err(err) = context.diagnostics.finish(cli.deny_warnings) {
+                            exit_with_error(&cli, err);
+                        }
+                    }
+                    Err(err) => exit_with_error(&cli, err),
+                }
+                return;
+            }
+            Commands::DiffVars {
+                config,
+                profile,
+                variables,
+            } => {
+                let other = diff_vars::other_side(&cli, config, profile.as_deref(), variables);
+                match diff_vars::diff_runs(&cli, &other) {
+                    Ok(report) => {
+                        print!("{}", report.report());
+                        if report.has_differences() {
+                            std::process::exit(1);
+                        }
+                    }
+                    Err(err) => exit_with_error(&cli, err),
+                }
+                return;
+            }
+            Commands::Doctor => {
+                let report = doctor::run_doctor(&cli);
+                print!("{}", report.report());
+                if report.has_failures() {
+                    std::process::exit(1);
+                }
+                return;
+            }
+            Commands::Bench {
+                template,
+                iterations,
+                warmup,
+            } => {
+                let report = variables::collect_variable_context(&cli).and_then(|context| {
+                    let report = bench::bench_template(
+                        &cli,
+                        &context.values,
+                        template,
+                        *iterations,
+                        *warmup,
+                    );
+                    context.diagnostics.finish(cli.deny_warnings)?;
+                    report
+                });
+                match report {
+                    Ok(report) => print!("{}", report.report()),
+                    Err(err) => exit_with_error(&cli, err),
+                }
                 return;
             }
+            Commands::Verify { manifest } => {
+                match manifest::verify_manifest(manifest) {
+                    Ok(report) => {
+                        print!("{}", report.report());
+                        if report.has_drift() {
+                            std::process::exit(1);
+                        }
+                    }
+                    Err(err) => exit_with_error(&cli, err),
+                }
+                return;
+            }
+        }
+    }
+
+    // Reproduce a recorded run instead of rendering the inputs
+    if let Some(bundle) = &cli.replay {
+        match session::replay(bundle, &cli) {
+            Ok(report) => {
+                print!("{}", report.report());
+                if report.has_differences() {
+                    std::process::exit(1);
+                }
+            }
+            Err(err) => exit_with_error(&cli, err),
         }
+        return;
     }
 
     // Validate input/output combinations
     if let Err(err) = processing::validate_args(&cli) {
-        eprintln!("{}", err);
-        std::process::exit(1);
+        exit_with_error(&cli, err);
     }
 
     // Collect template variables with proper precedence
-    let variables = match variables::collect_variables(&cli) {
-        Ok(vars) => vars,
-        Err(err) => {
-            eprintln!("{}", err);
-            std::process::exit(1);
+    let context = match variables::collect_variable_context(&cli) {
+        Ok(context) => context,
+        Err(err) => exit_with_error(&cli, err),
+    };
+
+    // Finish the files in flight on ctrl-c instead of leaving partial outputs
+    if let Err(err) = interrupt::install_handlers() {
+        exit_with_error(&cli, err);
+    }
+
+    // Process inputs, then report warnings even if processing failed
+    let processed = match &cli.record {
+        Some(bundle) => {
+            let args: Vec<String> = std::env::args().collect();
+            session::record(&cli, &context, &args, bundle)
         }
+        None => processing::process_inputs(&cli, &context.values, &context.diagnostics),
     };
+    let finished = context.diagnostics.finish(cli.deny_warnings);
+    if let Err(err) = processed.and(finished) {
+        exit_with_error(&cli, err);
+    }
+}
 
-    // Process inputs
-    if let Err(err) = processing::process_inputs(&cli, &variables) {
-        eprintln!("{}", err);
-        std::process::exit(1);
+/// Print the error in the format chosen with `--error-format` and exit
+fn exit_with_error(cli: &Cli, err: error::ShinkansenError) -> ! {
+    eprintln!("{}", err.report(cli.error_format));
+    if matches!(err.root_cause(), error::ShinkansenError::Interrupted(_)) {
+        std::process::exit(interrupt::INTERRUPTED_EXIT_CODE);
     }
+    std::process::exit(1);
 }
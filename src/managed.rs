@@ -0,0 +1,121 @@
+use std::path::Path;
+
+use crate::cli::Cli;
+use crate::error::{ContextExt, Result, ShinkansenError};
+
+/// Marker used when `--block-marker` is not given
+pub const DEFAULT_MARKER: &str = "# {mark} shinkansen";
+
+/// Placeholder replaced with `BEGIN`/`END` in a marker
+const MARK_PLACEHOLDER: &str = "{mark}";
+
+/// A region of a destination file owned by shinkansen (`--managed-block`)
+///
+/// Only the lines between the begin and end markers are regenerated; the
+/// rest of the file is preserved, like Ansible's `blockinfile`:
+///
+/// ```text
+/// # hand-written settings
+/// # BEGIN shinkansen
+/// ...rendered output...
+/// # END shinkansen
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ManagedBlock {
+    pub begin: String,
+    pub end: String,
+}
+
+impl ManagedBlock {
+    /// Build the markers from a template containing `{mark}`
+    pub fn new(marker: &str) -> Result<Self> {
+        if !marker.contains(MARK_PLACEHOLDER) {
+            return Err(ShinkansenError::ValidationError(format!(
+                "Invalid block marker '{}': it must contain {}",
+                marker, MARK_PLACEHOLDER
+            )));
+        }
+        if marker.contains('\n') {
+            return Err(ShinkansenError::ValidationError(format!(
+                "Invalid block marker '{}': it must be a single line",
+                marker.escape_debug()
+            )));
+        }
+
+        Ok(ManagedBlock {
+            begin: marker.replace(MARK_PLACEHOLDER, "BEGIN"),
+            end: marker.replace(MARK_PLACEHOLDER, "END"),
+        })
+    }
+
+    /// The managed block selected by `--managed-block`/`--block-marker`, if enabled
+    pub fn from_cli(cli: &Cli) -> Result<Option<Self>> {
+        if !cli.managed_block {
+            return Ok(None);
+        }
+        Self::new(cli.block_marker.as_deref().unwrap_or(DEFAULT_MARKER)).map(Some)
+    }
+
+    /// Replace the managed region of `existing` with `content`
+    ///
+    /// Without an existing file, or when the file has no markers yet, the
+    /// block is appended. `name` identifies the file in error messages.
+    pub fn merge(&self, existing: Option<&str>, content: &str, name: &str) -> Result<String> {
+        let existing = existing.unwrap_or_default();
+        let lines: Vec<&str> = existing.split_inclusive('\n').collect();
+        let find = |marker: &str| -> Vec<usize> {
+            lines
+                .iter()
+                .enumerate()
+                .filter(|(_, line)| line.trim() == marker)
+                .map(|(index, _)| index)
+                .collect()
+        };
+
+        let mut merged = String::with_capacity(existing.len() + content.len());
+        match (find(&self.begin).as_slice(), find(&self.end).as_slice()) {
+            ([], []) => {
+                merged.push_str(existing);
+                if !merged.is_empty() && !merged.ends_with('\n') {
+                    merged.push('\n');
+                }
+                merged.push_str(&self.begin);
+                merged.push('\n');
+                push_block_content(&mut merged, content);
+                merged.push_str(&self.end);
+                merged.push('\n');
+            }
+            ([begin], [end]) if begin < end => {
+                merged.push_str(&lines[..=*begin].concat());
+                push_block_content(&mut merged, content);
+                merged.push_str(&lines[*end..].concat());
+            }
+            _ => {
+                return Err(ShinkansenError::ValidationError(format!(
+                    "Expected one '{}' line followed by one '{}' line in {}",
+                    self.begin, self.end, name
+                )));
+            }
+        }
+        Ok(merged)
+    }
+
+    /// Merge `content` into the managed block of the file at `path`
+    pub fn merge_file(&self, path: &Path, content: &str) -> Result<String> {
+        let existing = match std::fs::read_to_string(path) {
+            Ok(existing) => Some(existing),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read file: {:?}", path));
+            }
+        };
+        self.merge(existing.as_deref(), content, &path.display().to_string())
+    }
+}
+
+fn push_block_content(output: &mut String, content: &str) {
+    output.push_str(content);
+    if !content.is_empty() && !content.ends_with('\n') {
+        output.push('\n');
+    }
+}
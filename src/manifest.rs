@@ -0,0 +1,157 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::error::{ContextExt, Result, ShinkansenError};
+
+/// The files a run wrote with their SHA-256 (`--manifest`), checked by `shinkansen verify`
+///
+/// Paths are relative to the manifest's directory when the file lies below it,
+/// so the manifest can travel with the files it describes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub generator: String,
+    /// Path to lowercase hex SHA-256 of the content written
+    pub sha256: BTreeMap<String, String>,
+}
+
+impl Manifest {
+    /// A manifest to be saved at `manifest_path` for `outputs` (path and hash)
+    pub fn from_outputs(manifest_path: &Path, outputs: &[(PathBuf, String)]) -> Result<Self> {
+        let base = absolute(manifest_path)?
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        let mut sha256 = BTreeMap::new();
+        for (path, hash) in outputs {
+            let path = absolute(path)?;
+            let key = match path.strip_prefix(&base) {
+                Ok(relative) => slash_path(relative),
+                Err(_) => path.to_string_lossy().into_owned(),
+            };
+            sha256.insert(key, hash.clone());
+        }
+        Ok(Manifest {
+            generator: format!("shinkansen {}", env!("CARGO_PKG_VERSION")),
+            sha256,
+        })
+    }
+
+    pub fn read(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read manifest {:?}", path))?;
+        serde_json::from_str(&content).map_err(|e| {
+            ShinkansenError::ConfigParseError(format!("Invalid manifest {:?}: {}", path, e))
+        })
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| {
+            ShinkansenError::ConfigParseError(format!("Failed to serialize manifest: {}", e))
+        })?;
+        crate::output::write_to_file(path, &format!("{}\n", json))
+    }
+}
+
+/// Lowercase hex SHA-256 of `content`, as recorded in a manifest
+pub fn content_hash(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+/// How a file differs from its manifest entry
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Drift {
+    Modified,
+    Missing,
+}
+
+impl std::fmt::Display for Drift {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Drift::Modified => write!(f, "modified"),
+            Drift::Missing => write!(f, "missing"),
+        }
+    }
+}
+
+/// The outcome of `shinkansen verify`
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifyReport {
+    pub manifest: PathBuf,
+    /// Number of files listed in the manifest
+    pub checked: usize,
+    pub drifted: Vec<(String, Drift)>,
+}
+
+impl VerifyReport {
+    pub fn has_drift(&self) -> bool {
+        !self.drifted.is_empty()
+    }
+
+    /// One line per drifted file and a summary
+    ///
+    /// ```text
+    ///   modified  deploy/app.yaml
+    ///   missing   deploy/db.yaml
+    /// 2 of 5 file(s) differ from deploy/manifest.json
+    /// ```
+    pub fn report(&self) -> String {
+        let mut output = String::new();
+        for (path, drift) in &self.drifted {
+            output.push_str(&format!("  {:<8}  {}\n", drift, path));
+        }
+        if self.has_drift() {
+            output.push_str(&format!(
+                "{} of {} file(s) differ from {}\n",
+                self.drifted.len(),
+                self.checked,
+                self.manifest.display()
+            ));
+        } else {
+            output.push_str(&format!(
+                "{} file(s) match {}\n",
+                self.checked,
+                self.manifest.display()
+            ));
+        }
+        output
+    }
+}
+
+/// Compare the files listed in the manifest at `path` with their recorded hashes
+pub fn verify_manifest(path: &Path) -> Result<VerifyReport> {
+    let manifest = Manifest::read(path)?;
+    let base = path.parent().unwrap_or(Path::new(""));
+    let mut drifted = Vec::new();
+    for (file, expected) in &manifest.sha256 {
+        let resolved = base.join(file);
+        match std::fs::read(&resolved) {
+            Ok(content) if content_hash(&content) == *expected => {}
+            Ok(_) => drifted.push((resolved.display().to_string(), Drift::Modified)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                drifted.push((resolved.display().to_string(), Drift::Missing));
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read file: {:?}", resolved));
+            }
+        }
+    }
+    Ok(VerifyReport {
+        manifest: path.to_path_buf(),
+        checked: manifest.sha256.len(),
+        drifted,
+    })
+}
+
+fn absolute(path: &Path) -> Result<PathBuf> {
+    std::path::absolute(path).with_context(|| format!("Failed to resolve {:?}", path))
+}
+
+fn slash_path(path: &Path) -> String {
+    path.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
@@ -0,0 +1,86 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+
+use crate::cli::Cli;
+use crate::error::{ContextExt, Result, ShinkansenError};
+use crate::retry::RetryPolicy;
+
+/// Settings shared by every network-backed source
+///
+/// Proxies are taken from the standard `HTTP_PROXY`/`HTTPS_PROXY`/`ALL_PROXY`
+/// and `NO_PROXY` environment variables by the HTTP client itself.
+#[derive(Debug, Clone, Default)]
+pub struct NetworkOptions {
+    pub retry: RetryPolicy,
+    /// PEM file whose certificates replace the default trusted roots
+    pub ca_bundle: Option<PathBuf>,
+    /// Accept any server certificate (testing only)
+    pub insecure_skip_verify: bool,
+    /// How long a single HTTP request may take, instead of 30 seconds
+    pub timeout: Option<Duration>,
+}
+
+impl NetworkOptions {
+    /// Build the options described by `--retries`, `--ca-bundle`, `--insecure-skip-verify`
+    /// and `--http-timeout`
+    pub fn from_cli(cli: &Cli) -> Self {
+        NetworkOptions {
+            retry: RetryPolicy::from_cli(cli),
+            ca_bundle: cli.ca_bundle.clone(),
+            insecure_skip_verify: cli.insecure_skip_verify,
+            timeout: cli.http_timeout,
+        }
+    }
+
+    /// Load the DER-encoded certificates of the CA bundle, if one was given
+    pub fn ca_certificates(&self) -> Result<Option<Vec<Vec<u8>>>> {
+        let Some(path) = &self.ca_bundle else {
+            return Ok(None);
+        };
+
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read CA bundle {:?}", path))?;
+        let certificates = parse_pem_certificates(&content)?;
+        if certificates.is_empty() {
+            return Err(ShinkansenError::ValidationError(format!(
+                "CA bundle {} contains no certificates",
+                path.display()
+            )));
+        }
+
+        Ok(Some(certificates))
+    }
+}
+
+/// Extract the DER bytes of every `CERTIFICATE` block in a PEM document
+///
+/// Other blocks (keys, CRLs) and text between blocks are ignored.
+pub fn parse_pem_certificates(pem: &str) -> Result<Vec<Vec<u8>>> {
+    const BEGIN: &str = "-----BEGIN CERTIFICATE-----";
+    const END: &str = "-----END CERTIFICATE-----";
+
+    let mut certificates = Vec::new();
+    let mut rest = pem;
+    while let Some(start) = rest.find(BEGIN) {
+        let body_start = start + BEGIN.len();
+        let end = rest[body_start..].find(END).ok_or_else(|| {
+            ShinkansenError::ValidationError("Unterminated certificate in CA bundle".to_string())
+        })? + body_start;
+
+        let body: String = rest[body_start..end]
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect();
+        let der = BASE64.decode(body).map_err(|e| {
+            ShinkansenError::ValidationError(format!("Invalid certificate in CA bundle: {}", e))
+        })?;
+        certificates.push(der);
+
+        rest = &rest[end + END.len()..];
+    }
+
+    Ok(certificates)
+}
@@ -1,8 +1,19 @@
+use std::io::{IsTerminal, Write};
 use std::path::{Path, PathBuf};
 
+use clap::ValueEnum;
+
+use crate::backend::{
+    CLIPBOARD_OUTPUT, CLIPBOARD_PATH, ObjectStore, OutputBackend, OutputMeta, STDOUT_PATH,
+    default_backend,
+};
 use crate::cli::Cli;
 use crate::error::{ContextExt, Result};
-use crate::platform::{get_max_path_length, normalize_path};
+use crate::fileio::{Compression, IoOptions, write_file};
+use crate::input::cli_inputs;
+use crate::platform::{
+    glob_input_base, max_path_length_for, normalize_path, validate_platform_path,
+};
 
 /// Validate a path for security issues
 fn validate_path(path: &Path) -> Result<()> {
@@ -19,10 +30,11 @@ fn validate_path(path: &Path) -> Result<()> {
     }
 
     // Check path length and validity using platform-specific limit
-    if normalized_path.as_os_str().len() > get_max_path_length() {
+    let max_length = max_path_length_for(&normalized_path);
+    if normalized_path.as_os_str().len() > max_length {
         return Err(crate::error::ShinkansenError::ValidationError(format!(
             "Path too long (max {} characters)",
-            get_max_path_length()
+            max_length
         )));
     }
 
@@ -37,33 +49,65 @@ fn validate_path(path: &Path) -> Result<()> {
         ));
     }
 
+    // Check platform naming rules (reserved names, UNC paths, ... on Windows)
+    validate_platform_path(path)?;
+
     Ok(())
 }
 
 /// Output destination enum to represent different output types
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum OutputDestination {
     /// Output to stdout
     Stdout,
+    /// Output to the system clipboard (`-o clipboard`)
+    Clipboard,
     /// Output to a single file
     SingleFile(PathBuf),
     /// Output to a directory (for multiple files)
     Directory(PathBuf),
+    /// Output over each input file (`--in-place`), backing it up with the suffix if any
+    InPlace(Option<String>),
 }
 
 /// Determine the appropriate output destination based on CLI arguments
 pub fn determine_output_destination(cli: &Cli, single_file: bool) -> Result<OutputDestination> {
+    if cli.in_place {
+        return Ok(OutputDestination::InPlace(cli.suffix.clone()));
+    }
     if let Some(output_str) = &cli.output {
         if output_str == "-" {
             return Ok(OutputDestination::Stdout);
         }
+        // The clipboard holds one text, so several files would replace each other
+        if output_str == CLIPBOARD_OUTPUT {
+            if !single_file {
+                return Err(crate::error::ShinkansenError::ValidationError(
+                    "-o clipboard takes a single file; use --concat to copy several as one"
+                        .to_string(),
+                ));
+            }
+            return Ok(OutputDestination::Clipboard);
+        }
 
         let output = PathBuf::from(output_str);
 
+        // Object URLs name no local path; a trailing '/' makes one a prefix
+        if ObjectStore::from_url(output_str).is_some() {
+            return Ok(
+                if single_file && !output_str.ends_with('/') && cli.link.is_none() {
+                    OutputDestination::SingleFile(output)
+                } else {
+                    OutputDestination::Directory(output)
+                },
+            );
+        }
+
         // Validate output path for security
         validate_path(&output)?;
 
-        if single_file && !output.is_dir() {
+        // Releases for --link are always directories
+        if single_file && !output.is_dir() && cli.link.is_none() {
             Ok(OutputDestination::SingleFile(output))
         } else {
             Ok(OutputDestination::Directory(output))
@@ -84,41 +128,108 @@ pub fn write_to_output(
     content: &str,
     cli: &Cli,
 ) -> Result<()> {
-    match destination {
-        OutputDestination::Stdout => {
-            print!("{}", content);
-            Ok(())
+    write_to_backend(
+        &*default_backend(cli),
+        destination,
+        input_file,
+        content,
+        cli,
+    )
+}
+
+/// Write content to the appropriate output destination through `backend`
+///
+/// Content is compressed here, with `--compress-output` or, in place, with
+/// the input's own format, so backends receive the final bytes.
+pub fn write_to_backend(
+    backend: &dyn OutputBackend,
+    destination: &OutputDestination,
+    input_file: &Path,
+    content: &str,
+    cli: &Cli,
+) -> Result<()> {
+    let (path, compression) = match destination {
+        OutputDestination::Stdout => (PathBuf::from(STDOUT_PATH), cli.compress_output),
+        OutputDestination::Clipboard => (PathBuf::from(CLIPBOARD_PATH), None),
+        OutputDestination::SingleFile(output_path) => (output_path.clone(), cli.compress_output),
+        OutputDestination::Directory(output_dir) => {
+            // Preserve directory structure if input was a single directory
+            let output_file = determine_output_file_path(output_dir, input_file, cli);
+            (output_file, cli.compress_output)
         }
-        OutputDestination::SingleFile(output_path) => {
-            if let Some(parent) = output_path.parent() {
-                std::fs::create_dir_all(parent)?;
+        OutputDestination::InPlace(suffix) => {
+            if let Some(suffix) = suffix {
+                let backup = backup_path(input_file, suffix);
+                std::fs::copy(input_file, &backup).with_context(|| {
+                    format!("Failed to back up {:?} to {:?}", input_file, backup)
+                })?;
             }
-            std::fs::write(output_path, content)
-                .with_context(|| format!("Failed to write to {:?}", output_path))
+            // A compressed input stays compressed
+            (input_file.to_path_buf(), Compression::from_path(input_file))
         }
-        OutputDestination::Directory(output_dir) => {
-            // Create output directory if it doesn't exist
-            std::fs::create_dir_all(output_dir)?;
+    };
 
-            // Preserve directory structure if input was a single directory
-            let output_file = determine_output_file_path(output_dir, input_file, cli);
+    let compressed;
+    let bytes = match compression {
+        Some(compression) => {
+            compressed = compression.compress(content.as_bytes())?;
+            compressed.as_slice()
+        }
+        None => content.as_bytes(),
+    };
+    let meta = OutputMeta {
+        input: input_file.to_path_buf(),
+        compression,
+    };
+    backend.write(&path, bytes, &meta)
+}
 
-            if let Some(parent) = output_file.parent() {
-                std::fs::create_dir_all(parent)?;
-            }
+/// Where `--suffix` keeps the original of an input rendered in place
+pub fn backup_path(input_file: &Path, suffix: &str) -> PathBuf {
+    let mut name = input_file.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    input_file.with_file_name(name)
+}
 
-            std::fs::write(&output_file, content)
-                .with_context(|| format!("Failed to write to {:?}", output_file))
+/// Resolve the file path an input will be written to, if the destination is a file
+pub fn resolve_output_path(
+    destination: &OutputDestination,
+    input_file: &Path,
+    cli: &Cli,
+) -> Option<PathBuf> {
+    match destination {
+        OutputDestination::Stdout | OutputDestination::Clipboard => None,
+        OutputDestination::SingleFile(output_path) => Some(output_path.clone()),
+        OutputDestination::Directory(output_dir) => {
+            Some(determine_output_file_path(output_dir, input_file, cli))
         }
+        OutputDestination::InPlace(_) => Some(input_file.to_path_buf()),
     }
 }
 
 /// Determine the output file path for directory output
+///
+/// A compressed input loses its `.gz` or `.zst` before the extension rules
+/// apply, and `--compress-output` adds its own extension after them.
 fn determine_output_file_path(output_dir: &Path, input_file: &Path, cli: &Cli) -> PathBuf {
-    let input_path_strs: Vec<PathBuf> = cli.inputs.iter().map(PathBuf::from).collect();
+    let mut path = output_path_in_dir(output_dir, input_file, cli);
+    if Compression::from_path(&path).is_some() {
+        path.set_extension("");
+    }
+    let mut path = map_extension(path, cli).into_os_string();
+    if let Some(compression) = cli.compress_output {
+        path.push(compression.extension());
+    }
+    PathBuf::from(path)
+}
+
+fn output_path_in_dir(output_dir: &Path, input_file: &Path, cli: &Cli) -> PathBuf {
+    let inputs = cli_inputs(cli);
+    let input_path_strs: Vec<PathBuf> = inputs.iter().map(PathBuf::from).collect();
 
+    // A file below its input came from a directory, an archive or a git tree
     if input_path_strs.len() == 1
-        && input_path_strs[0].is_dir()
+        && input_file != input_path_strs[0]
         && input_file.starts_with(&input_path_strs[0])
     {
         // Preserve directory structure
@@ -126,12 +237,239 @@ fn determine_output_file_path(output_dir: &Path, input_file: &Path, cli: &Cli) -
             .strip_prefix(&input_path_strs[0])
             .unwrap_or(input_file);
         output_dir.join(relative)
+    } else if let [input] = &*inputs
+        && let Some(base) = glob_input_base(input)
+        && let Ok(relative) = input_file.strip_prefix(&base)
+    {
+        // A glob keeps the structure below its literal base directory
+        output_dir.join(relative)
     } else {
         // Just use the filename
         output_dir.join(input_file.file_name().unwrap_or_default())
     }
 }
 
+/// Rename an output for `--strip-extension` and `--extension-map`
+///
+/// The longest matching source extension wins, so `--extension-map
+/// .yaml.j2=.yml` takes precedence over `--strip-extension .j2`. A name that
+/// is nothing but the extension is left alone.
+fn map_extension(path: PathBuf, cli: &Cli) -> PathBuf {
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return path;
+    };
+    let rule = cli
+        .strip_extensions
+        .iter()
+        .map(|from| (from.as_str(), ""))
+        .chain(
+            cli.extension_maps
+                .iter()
+                .map(|(from, to)| (from.as_str(), to.as_str())),
+        )
+        .filter(|(from, _)| name.len() > from.len() && name.ends_with(from))
+        .max_by_key(|(from, _)| from.len());
+    match rule {
+        Some((from, to)) => {
+            let renamed = format!("{}{}", &name[..name.len() - from.len()], to);
+            path.with_file_name(renamed)
+        }
+        None => path,
+    }
+}
+
+/// Parse an extension given to `--strip-extension`; the leading dot is optional
+pub fn parse_extension(value: &str) -> std::result::Result<String, String> {
+    let extension = value.trim().trim_start_matches('.');
+    if extension.is_empty() || extension.contains(['/', '\\']) {
+        return Err(format!("invalid extension '{}'", value));
+    }
+    Ok(format!(".{}", extension))
+}
+
+/// Parse a `FROM=TO` pair given to `--extension-map`, e.g. `.yaml.j2=.yml`
+///
+/// `TO` may be empty to strip `FROM`.
+pub fn parse_extension_map(value: &str) -> std::result::Result<(String, String), String> {
+    let (from, to) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected FROM=TO, e.g. .tpl=.txt, got '{}'", value))?;
+    let to = match to.trim() {
+        "" => String::new(),
+        to => parse_extension(to)?,
+    };
+    Ok((parse_extension(from)?, to))
+}
+
+/// What happens to output files that already exist
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ClobberPolicy {
+    /// Replace them (the default, and `--force`)
+    #[default]
+    Overwrite,
+    /// Refuse the run before anything is written (`--no-clobber`)
+    Refuse,
+    /// Ask before replacing each one (`--interactive`)
+    Ask,
+}
+
+impl ClobberPolicy {
+    pub fn from_cli(cli: &Cli) -> Self {
+        if cli.force {
+            ClobberPolicy::Overwrite
+        } else if cli.no_clobber {
+            ClobberPolicy::Refuse
+        } else if cli.interactive {
+            ClobberPolicy::Ask
+        } else {
+            ClobberPolicy::Overwrite
+        }
+    }
+}
+
+/// Fail listing every output in `outputs` that already exists (`--no-clobber`)
+pub fn refuse_clobber(outputs: impl IntoIterator<Item = PathBuf>) -> Result<()> {
+    let existing: Vec<String> = outputs
+        .into_iter()
+        .filter(|path| path.exists())
+        .map(|path| path.display().to_string())
+        .collect();
+    if existing.is_empty() {
+        return Ok(());
+    }
+    Err(crate::error::ShinkansenError::ValidationError(format!(
+        "Refusing to overwrite existing files (--no-clobber; use --force): {}",
+        existing.join(", ")
+    )))
+}
+
+/// Asks before each existing output is replaced (`--interactive`)
+///
+/// Answers are `y`es, `n`o (keep the file), `a`ll (stop asking) and `q`uit
+/// (fail the run); anything else, including end of input, means no.
+pub struct OverwritePrompt {
+    input: Box<dyn std::io::BufRead>,
+    output: Box<dyn std::io::Write>,
+    all: bool,
+}
+
+impl OverwritePrompt {
+    pub fn new(input: Box<dyn std::io::BufRead>, output: Box<dyn std::io::Write>) -> Self {
+        OverwritePrompt {
+            input,
+            output,
+            all: false,
+        }
+    }
+
+    /// Prompt on stderr and read answers from stdin
+    pub fn stdio() -> Self {
+        OverwritePrompt::new(
+            Box::new(std::io::BufReader::new(std::io::stdin())),
+            Box::new(std::io::stderr()),
+        )
+    }
+
+    /// Whether `content` may be written to `path`; files that hold it already are not asked about
+    pub fn confirm(&mut self, path: &Path, content: &str) -> Result<bool> {
+        if self.all {
+            return Ok(true);
+        }
+        match std::fs::read(path) {
+            Ok(existing) if existing != content.as_bytes() => {}
+            _ => return Ok(true),
+        }
+
+        write!(self.output, "overwrite {}? [y/n/a/q] ", path.display())?;
+        self.output.flush()?;
+        let mut answer = String::new();
+        self.input.read_line(&mut answer)?;
+        match answer.trim().to_ascii_lowercase().as_str() {
+            "y" | "yes" => Ok(true),
+            "a" | "all" => {
+                self.all = true;
+                Ok(true)
+            }
+            "q" | "quit" => Err(crate::error::ShinkansenError::ValidationError(format!(
+                "Stopped at {:?}: not overwritten",
+                path
+            ))),
+            _ => Ok(false),
+        }
+    }
+}
+
+/// When `--diff` output is colored (`--color`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ColorChoice {
+    /// Color when stdout is a terminal and `NO_COLOR` is unset or empty
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Whether output written to stdout should be colored
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+                    && std::io::stdout().is_terminal()
+            }
+        }
+    }
+}
+
+/// Unified diff of the change writing `content` to `path` would make (`--diff`)
+///
+/// Empty when the file already holds `content`; a missing file shows as new.
+pub fn diff_for_file(path: &Path, content: &str, color: bool) -> Result<String> {
+    let diff = crate::patch::patch_for_file(path, content)?;
+    Ok(if color { colorize_diff(&diff) } else { diff })
+}
+
+/// Color a unified diff with ANSI escapes: file headers bold, hunk headers cyan,
+/// removed lines red and added lines green
+pub fn colorize_diff(diff: &str) -> String {
+    const BOLD: &str = "\x1b[1m";
+    const CYAN: &str = "\x1b[36m";
+    const RED: &str = "\x1b[31m";
+    const GREEN: &str = "\x1b[32m";
+    const RESET: &str = "\x1b[0m";
+
+    let mut colored = String::with_capacity(diff.len());
+    // `---`/`+++` are only headers before a file's first hunk
+    let mut in_header = false;
+    for line in diff.split_inclusive('\n') {
+        let (text, newline) = match line.strip_suffix('\n') {
+            Some(text) => (text, "\n"),
+            None => (line, ""),
+        };
+        if text.starts_with("diff --git ") {
+            in_header = true;
+        } else if text.starts_with("@@") {
+            in_header = false;
+        }
+        let color = if in_header {
+            BOLD
+        } else if text.starts_with("@@") {
+            CYAN
+        } else if text.starts_with('-') {
+            RED
+        } else if text.starts_with('+') {
+            GREEN
+        } else {
+            colored.push_str(line);
+            continue;
+        };
+        colored.push_str(&format!("{}{}{}{}", color, text, RESET, newline));
+    }
+    colored
+}
+
 /// Write content directly to stdout
 pub fn write_to_stdout(content: &str) {
     print!("{}", content);
@@ -145,5 +483,32 @@ pub fn write_to_file(path: &Path, content: &str) -> Result<()> {
     if let Some(parent) = path.parent() {
         std::fs::create_dir_all(parent)?;
     }
-    std::fs::write(path, content).with_context(|| format!("Failed to write to {:?}", path))
+    write_atomically(path, content.as_bytes(), &IoOptions::default())
+}
+
+/// Replace `path` with `content` through a temporary file in the same directory
+///
+/// Readers, and an interrupted run, see either the old file or the complete
+/// new one. The existing file's permissions are kept, and a symlinked output
+/// is written through to its target.
+pub(crate) fn write_atomically(path: &Path, content: &[u8], io: &IoOptions) -> Result<()> {
+    let target = match std::fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_symlink() => {
+            std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+        }
+        _ => path.to_path_buf(),
+    };
+    let name = target.file_name().unwrap_or_default().to_string_lossy();
+    let temp = target.with_file_name(format!(".{}.{}.tmp", name, std::process::id()));
+
+    let written = write_file(&temp, content, io).and_then(|_| {
+        if let Ok(meta) = std::fs::metadata(&target) {
+            std::fs::set_permissions(&temp, meta.permissions())?;
+        }
+        std::fs::rename(&temp, &target)
+    });
+    if written.is_err() {
+        let _ = std::fs::remove_file(&temp);
+    }
+    written.with_context(|| format!("Failed to write to {:?}", path))
 }
@@ -0,0 +1,55 @@
+use std::path::Path;
+
+use similar::TextDiff;
+
+use crate::error::{ContextExt, Result};
+
+/// Lines of unchanged context around each hunk, as with `diff -u`
+const CONTEXT_LINES: usize = 3;
+
+/// Unified diff turning `old` into `new` for the file at `path` (`--emit-patch`)
+///
+/// The patch uses git's `a/`/`b/` prefixes so it applies with `git apply` or
+/// `patch -p1`; a missing `old` produces a new-file patch. Returns an empty
+/// string when nothing changes.
+pub fn file_patch(path: &Path, old: Option<&str>, new: &str) -> String {
+    if old == Some(new) {
+        return String::new();
+    }
+
+    let path = patch_path(path);
+    let mut patch = format!("diff --git a/{path} b/{path}\n");
+    let old_header = match old {
+        Some(_) => format!("a/{}", path),
+        None => {
+            patch.push_str("new file mode 100644\n");
+            "/dev/null".to_string()
+        }
+    };
+
+    let diff = TextDiff::from_lines(old.unwrap_or_default(), new);
+    patch.push_str(
+        &diff
+            .unified_diff()
+            .context_radius(CONTEXT_LINES)
+            .header(&old_header, &format!("b/{}", path))
+            .to_string(),
+    );
+    patch
+}
+
+/// Patch for writing `new` to `path`, compared with the file currently there
+pub fn patch_for_file(path: &Path, new: &str) -> Result<String> {
+    let old = match std::fs::read_to_string(path) {
+        Ok(old) => Some(old),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e).with_context(|| format!("Failed to read file: {:?}", path)),
+    };
+    Ok(file_patch(path, old.as_deref(), new))
+}
+
+/// Path as it appears in patch headers: `/`-separated, without a leading `./`
+fn patch_path(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    path.strip_prefix("./").unwrap_or(&path).to_string()
+}
@@ -0,0 +1,168 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::error::{ContextExt, Result, ShinkansenError};
+use crate::platform::normalize_path;
+use crate::processing::validate_path;
+use crate::variables::{json_to_minijinja_value, merge_variable};
+
+/// Extra variables, and optionally their own output, for renders of particular input files
+///
+/// An input listed once is rendered with its variables on top of the run's.
+/// An input listed several times is rendered once per entry, so every entry
+/// then needs its own output. Inputs are matched against the files the run
+/// finds, as paths relative to the working directory; outputs are relative
+/// paths, below the `-o` directory when there is one. Loaded from `--per-file-vars`, or
+/// built by library callers with [`PerFileVars::add`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PerFileVars {
+    renders: BTreeMap<PathBuf, Vec<PerFileRender>>,
+}
+
+/// One render of a listed input
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PerFileRender {
+    /// Where this render is written instead of the input's usual output
+    pub output: Option<PathBuf>,
+    /// Variables overriding the run's for this render; objects are deep-merged
+    pub vars: HashMap<String, minijinja::Value>,
+}
+
+impl PerFileRender {
+    /// The run's `variables` with this render's on top
+    pub fn variables(
+        &self,
+        variables: &HashMap<String, minijinja::Value>,
+    ) -> HashMap<String, minijinja::Value> {
+        let mut merged = variables.clone();
+        for (name, value) in &self.vars {
+            merge_variable(&mut merged, name.clone(), value.clone());
+        }
+        merged
+    }
+}
+
+/// An input's entry in a `--per-file-vars` file: one render or a list of them
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawEntries {
+    One(RawRender),
+    Many(Vec<RawRender>),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawRender {
+    output: Option<PathBuf>,
+    #[serde(default)]
+    vars: serde_json::Map<String, serde_json::Value>,
+}
+
+impl PerFileVars {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Render `input` with `render`, in addition to any renders already added for it
+    pub fn add(&mut self, input: impl AsRef<Path>, render: PerFileRender) -> Result<()> {
+        let input = normalize_path(input.as_ref());
+        if let Some(output) = &render.output {
+            // Joining an absolute path to the `-o` directory would replace it
+            if output.has_root()
+                || output
+                    .components()
+                    .any(|component| matches!(component, Component::Prefix(_)))
+            {
+                return Err(ShinkansenError::SecurityError(format!(
+                    "Invalid output {:?} for {:?} - must be a relative path",
+                    output, input
+                )));
+            }
+            validate_path(output)
+                .with_context(|| format!("Invalid output {:?} for {:?}", output, input))?;
+        }
+        let renders = self.renders.entry(input.clone()).or_default();
+        if !renders.is_empty() {
+            let mut outputs = HashSet::new();
+            for listed in renders.iter().chain(std::iter::once(&render)) {
+                let Some(output) = &listed.output else {
+                    return Err(ShinkansenError::ValidationError(format!(
+                        "{:?} is rendered {} times, so every render needs an output",
+                        input,
+                        renders.len() + 1
+                    )));
+                };
+                if !outputs.insert(output) {
+                    return Err(ShinkansenError::ValidationError(format!(
+                        "{:?} is rendered to {:?} more than once",
+                        input, output
+                    )));
+                }
+            }
+        }
+        renders.push(render);
+        Ok(())
+    }
+
+    /// Read a YAML or JSON mapping of input paths to `{output, vars}`, or to a list of them
+    ///
+    /// ```yaml
+    /// templates/app.yaml:
+    ///   vars: {replicas: 3}
+    /// templates/vhost.conf:
+    ///   - output: sites/a.conf
+    ///     vars: {server_name: a.example.com}
+    ///   - output: sites/b.conf
+    ///     vars: {server_name: b.example.com}
+    /// ```
+    pub fn read(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read per-file variables {:?}", path))?;
+        let entries: BTreeMap<String, RawEntries> =
+            serde_yaml::from_str(&content).map_err(|e| {
+                ShinkansenError::ConfigParseError(format!(
+                    "Invalid per-file variables {:?}: {}",
+                    path, e
+                ))
+            })?;
+
+        let mut per_file = PerFileVars::new();
+        for (input, entries) in entries {
+            let renders = match entries {
+                RawEntries::One(render) => vec![render],
+                RawEntries::Many(renders) => renders,
+            };
+            for render in renders {
+                let vars = render
+                    .vars
+                    .into_iter()
+                    .map(|(name, value)| (name, json_to_minijinja_value(value)))
+                    .collect();
+                per_file.add(
+                    &input,
+                    PerFileRender {
+                        output: render.output,
+                        vars,
+                    },
+                )?;
+            }
+        }
+        Ok(per_file)
+    }
+
+    /// The renders listed for `input`, if it is listed
+    pub fn renders(&self, input: &Path) -> Option<&[PerFileRender]> {
+        self.renders.get(&normalize_path(input)).map(Vec::as_slice)
+    }
+
+    /// Every listed input
+    pub fn inputs(&self) -> impl Iterator<Item = &Path> {
+        self.renders.keys().map(PathBuf::as_path)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.renders.is_empty()
+    }
+}
@@ -3,6 +3,20 @@
 
 use std::path::{Path, PathBuf};
 
+use crate::error::{Result, ShinkansenError};
+
+/// Device names Windows reserves in every directory, with or without an extension
+const WINDOWS_RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Characters Windows does not allow in file names
+const WINDOWS_INVALID_CHARS: &[char] = &['<', '>', ':', '"', '|', '?', '*'];
+
+/// Longest path Windows accepts with the `\\?\` extended-length prefix
+const WINDOWS_EXTENDED_MAX_PATH: usize = 32767;
+
 /// Platform-specific configuration
 #[derive(Debug, Clone)]
 pub struct PlatformConfig {
@@ -45,12 +59,16 @@ pub fn get_platform_config() -> PlatformConfig {
     }
 }
 
-/// Normalize a path to use forward slashes consistently
 /// Normalize a path to use forward slashes consistently
 /// This is a more robust version that can be used across the codebase
+/// Windows extended-length paths (`\\?\C:\...`) are returned unchanged, since
+/// Windows does not treat `/` as a separator in them.
 pub fn normalize_path<P: AsRef<Path>>(path: P) -> PathBuf {
-    let path_str = path.as_ref().to_string_lossy().replace('\\', "/");
-    PathBuf::from(path_str)
+    let path_str = path.as_ref().to_string_lossy();
+    if is_windows_verbatim_path(&path_str) {
+        return path.as_ref().to_path_buf();
+    }
+    PathBuf::from(path_str.replace('\\', "/"))
 }
 
 /// Get the appropriate line ending for the current platform
@@ -67,3 +85,208 @@ pub fn is_case_sensitive() -> bool {
 pub fn get_max_path_length() -> usize {
     get_platform_config().max_path_length
 }
+
+/// Maximum length allowed for a specific path
+///
+/// Like [`get_max_path_length`], except that Windows extended-length paths
+/// (`\\?\` prefix) may be up to 32767 characters long.
+pub fn max_path_length_for(path: &Path) -> usize {
+    if cfg!(windows) && is_windows_verbatim_path(&path.to_string_lossy()) {
+        WINDOWS_EXTENDED_MAX_PATH
+    } else {
+        get_max_path_length()
+    }
+}
+
+/// Apply the naming rules of the current platform to a path
+///
+/// On Windows this runs [`validate_windows_path`]; other platforms accept any
+/// name that passed the generic checks.
+pub fn validate_platform_path(path: &Path) -> Result<()> {
+    #[cfg(windows)]
+    {
+        validate_windows_path(&path.to_string_lossy())
+    }
+
+    #[cfg(not(windows))]
+    {
+        let _ = path;
+        Ok(())
+    }
+}
+
+/// Check a path against Windows naming rules
+///
+/// Accepts drive-letter (`C:\x`), relative and UNC (`\\server\share\x`) paths
+/// with either separator, and rejects:
+/// - reserved device names such as `CON` or `NUL.txt` in any component
+/// - components ending in a dot or a space
+/// - `< > : " | ? *` and control characters (except the drive letter colon)
+/// - device namespace paths (`\\.\`) and UNC paths without a share
+///
+/// Extended-length paths (`\\?\`) are passed to Windows verbatim and are not
+/// checked. This is plain string logic so it can be tested on every platform.
+pub fn validate_windows_path(path: &str) -> Result<()> {
+    let invalid = |reason: String| {
+        ShinkansenError::ValidationError(format!("Invalid Windows path {:?}: {}", path, reason))
+    };
+
+    if is_windows_verbatim_path(path) {
+        return Ok(());
+    }
+
+    let unified = path.replace('\\', "/");
+    let rest = if unified.starts_with("//./") {
+        return Err(invalid(
+            "device namespace paths are not supported".to_string(),
+        ));
+    } else if let Some(unc) = unified.strip_prefix("//") {
+        // \\server\share\rest: the server and share are required
+        let mut parts = unc.splitn(3, '/');
+        let server = parts.next().unwrap_or("");
+        let share = parts.next().unwrap_or("");
+        if server.is_empty() || share.is_empty() {
+            return Err(invalid("UNC paths need a server and a share".to_string()));
+        }
+        parts.next().unwrap_or("").to_string()
+    } else {
+        let mut chars = unified.chars();
+        match (chars.next(), chars.next()) {
+            (Some(drive), Some(':')) if drive.is_ascii_alphabetic() => chars.collect(),
+            _ => unified.clone(),
+        }
+    };
+
+    for component in rest.split('/') {
+        if component.is_empty() || component == "." || component == ".." {
+            continue;
+        }
+
+        if let Some(c) = component
+            .chars()
+            .find(|c| WINDOWS_INVALID_CHARS.contains(c) || c.is_control())
+        {
+            return Err(invalid(format!(
+                "{:?} contains the invalid character {:?}",
+                component, c
+            )));
+        }
+
+        if component.ends_with('.') || component.ends_with(' ') {
+            return Err(invalid(format!(
+                "{:?} ends with a dot or a space",
+                component
+            )));
+        }
+
+        let stem = component.split('.').next().unwrap_or(component).trim_end();
+        if WINDOWS_RESERVED_NAMES
+            .iter()
+            .any(|reserved| reserved.eq_ignore_ascii_case(stem))
+        {
+            return Err(invalid(format!(
+                "{:?} is a reserved device name",
+                component
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether an input is a glob pattern (`*`, `?`, `[...]` or `{a,b}`) rather than a path
+///
+/// An existing file or directory is always a path, even if its name contains
+/// wildcard characters.
+pub fn is_glob_input(input: &str) -> bool {
+    input.contains(['*', '?', '[', '{']) && !Path::new(input).exists()
+}
+
+/// An input glob with `/` separators
+///
+/// Windows shells leave patterns to the program and use `\` as the separator,
+/// so it is accepted there; elsewhere `\` escapes the next character.
+pub fn input_glob_pattern(input: &str) -> String {
+    if cfg!(windows) {
+        input.replace('\\', "/")
+    } else {
+        input.to_string()
+    }
+}
+
+/// Directory an input glob is expanded from, e.g. `templates` for
+/// `templates/**/*.yaml.j2`
+///
+/// Empty when the pattern starts with a wildcard, `None` when `input` is not a glob.
+pub fn glob_input_base(input: &str) -> Option<PathBuf> {
+    is_glob_input(input).then(|| {
+        let (base, _) = split_glob_base(&input_glob_pattern(input));
+        normalize_path(base)
+    })
+}
+
+/// Split a glob into its literal leading directories and the wildcard remainder
+pub fn split_glob_base(pattern: &str) -> (String, String) {
+    let components: Vec<&str> = pattern.split('/').collect();
+    let literal = components
+        .iter()
+        .take(components.len() - 1)
+        .take_while(|component| !component.contains(['*', '?', '[', '{']))
+        .count();
+    (
+        components[..literal].join("/"),
+        components[literal..].join("/"),
+    )
+}
+
+/// Whether a path uses the Windows extended-length prefix (`\\?\`)
+fn is_windows_verbatim_path(path: &str) -> bool {
+    path.starts_with("\\\\?\\")
+}
+
+/// Find an executable on `PATH`, returning its full path if present
+pub fn find_executable(name: &str) -> Option<PathBuf> {
+    let path_var = std::env::var_os("PATH")?;
+
+    std::env::split_paths(&path_var).find_map(|dir| {
+        let candidate = dir.join(name);
+        if candidate.is_file() {
+            return Some(candidate);
+        }
+
+        #[cfg(windows)]
+        {
+            let candidate = dir.join(format!("{}.exe", name));
+            if candidate.is_file() {
+                return Some(candidate);
+            }
+        }
+
+        None
+    })
+}
+
+/// Get the directory used to cache remote data between runs
+///
+/// `SHINKANSEN_CACHE_DIR` takes precedence; otherwise the platform's user cache
+/// directory is used (`%LOCALAPPDATA%` on Windows, `~/Library/Caches` on macOS,
+/// `$XDG_CACHE_HOME` or `~/.cache` elsewhere).
+pub fn cache_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("SHINKANSEN_CACHE_DIR").filter(|dir| !dir.is_empty()) {
+        return Some(PathBuf::from(dir));
+    }
+
+    #[cfg(windows)]
+    let base = std::env::var_os("LOCALAPPDATA").map(PathBuf::from);
+
+    #[cfg(target_os = "macos")]
+    let base = std::env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Caches"));
+
+    #[cfg(not(any(windows, target_os = "macos")))]
+    let base = std::env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")));
+
+    base.map(|dir| dir.join("shinkansen"))
+}
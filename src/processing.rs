@@ -1,15 +1,39 @@
-use std::collections::HashMap;
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use std::collections::{HashMap, HashSet};
+use std::num::NonZeroUsize;
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use walkdir::WalkDir;
 
+use crate::backend::{CLIPBOARD_OUTPUT, OutputBackend, default_backend};
 use crate::cli::Cli;
+use crate::diagnostics::Diagnostics;
 use crate::error::{ContextExt, Result};
-use crate::output::{determine_output_destination, write_to_output};
-use crate::platform::{get_max_path_length, normalize_path};
+use crate::hooks::{report_hook_failures, run_post_write_hooks, with_lifecycle_hooks};
+use crate::input::{InputFiles, InputSource, STDIN_PATH, cli_inputs, default_source, reads_stdin};
+use crate::interrupt::{interrupted_error, stop_requested};
+use crate::managed::ManagedBlock;
+use crate::manifest::{Manifest, content_hash};
+use crate::output::{
+    ClobberPolicy, OutputDestination, OverwritePrompt, backup_path, determine_output_destination,
+    diff_for_file, refuse_clobber, resolve_output_path, write_to_backend, write_to_stdout,
+};
+use crate::per_file::{PerFileRender, PerFileVars};
+use crate::platform::{
+    input_glob_pattern, is_glob_input, max_path_length_for, normalize_path, split_glob_base,
+    validate_platform_path,
+};
+use crate::project::ProjectConfig;
+use crate::rendering::RenderOptions;
+use crate::sandbox::template_root;
+use crate::stamp::{StampOptions, report_unstamped};
+use crate::transaction::{StagedDirectory, check_link, next_release};
+use crate::verify::{VerifyCommand, parse_verify_commands, verify_rendered};
 use std::path::Component;
 
 /// Validate a path for security issues
-fn validate_path(path: &Path) -> Result<()> {
+pub(crate) fn validate_path(path: &Path) -> Result<()> {
     // Prevent path traversal attacks
     if path
         .components()
@@ -21,10 +45,11 @@ fn validate_path(path: &Path) -> Result<()> {
     }
 
     // Check path length and validity using platform-specific limit
-    if path.as_os_str().len() > get_max_path_length() {
+    let max_length = max_path_length_for(path);
+    if path.as_os_str().len() > max_length {
         return Err(crate::error::ShinkansenError::ValidationError(format!(
             "Path too long (max {} characters)",
-            get_max_path_length()
+            max_length
         )));
     }
 
@@ -39,13 +64,16 @@ fn validate_path(path: &Path) -> Result<()> {
         ));
     }
 
+    // Check platform naming rules (reserved names, UNC paths, ... on Windows)
+    validate_platform_path(path)?;
+
     Ok(())
 }
 
 /// Validate input/output combinations
 pub fn validate_args(cli: &Cli) -> Result<()> {
     // Check if stdin is being used (either explicitly with "-" or implicitly with no inputs)
-    let using_stdin = cli.inputs.is_empty() || (cli.inputs.len() == 1 && cli.inputs[0] == "-");
+    let using_stdin = reads_stdin(cli);
 
     // Check if multiple inputs include stdin
     if cli.inputs.len() > 1 && cli.inputs.iter().any(|i| i == "-") {
@@ -56,7 +84,10 @@ pub fn validate_args(cli: &Cli) -> Result<()> {
 
     // Check if stdin is used with directory output (which is invalid)
     if using_stdin
-        && cli.output.as_ref().is_some_and(|o| o != "-")
+        && cli
+            .output
+            .as_ref()
+            .is_some_and(|o| o != "-" && o != CLIPBOARD_OUTPUT)
         && let Some(output) = &cli.output
     {
         let output_path = PathBuf::from(output);
@@ -73,19 +104,71 @@ pub fn validate_args(cli: &Cli) -> Result<()> {
         }
     }
 
+    if cli.interactive && using_stdin {
+        return Err(crate::error::ShinkansenError::ValidationError(
+            "--interactive reads its answers from stdin, so the template cannot come from stdin"
+                .to_string(),
+        ));
+    }
+
+    if cli.vars_stdin && using_stdin {
+        return Err(crate::error::ShinkansenError::ValidationError(
+            "--vars-stdin reads variables from stdin, so the template cannot come from stdin"
+                .to_string(),
+        ));
+    }
+
+    if cli.in_place {
+        if using_stdin {
+            return Err(crate::error::ShinkansenError::ValidationError(
+                "--in-place requires input files; stdin has nowhere to be written back".to_string(),
+            ));
+        }
+        if let Some(suffix) = &cli.suffix
+            && (suffix.is_empty() || suffix.contains(['/', '\\']))
+        {
+            return Err(crate::error::ShinkansenError::ValidationError(format!(
+                "Invalid --suffix {:?}: it must be a non-empty file name suffix",
+                suffix
+            )));
+        }
+    }
+
     // Check output combinations
-    let single_input = using_stdin || cli.inputs.len() == 1;
+    let single_input = using_stdin || cli_inputs(cli).len() == 1;
     let using_stdout = cli.output.as_ref().is_some_and(|o| o == "-");
+    let using_clipboard = !cli.in_place && cli.output.as_deref() == Some(CLIPBOARD_OUTPUT);
+
+    if cli.concat
+        && let Some(output) = cli
+            .output
+            .as_ref()
+            .filter(|output| *output != "-" && *output != CLIPBOARD_OUTPUT)
+        && Path::new(output).is_dir()
+    {
+        return Err(crate::error::ShinkansenError::ValidationError(format!(
+            "--concat writes a single file, but {:?} is a directory",
+            output
+        )));
+    }
 
-    if !single_input && using_stdout {
+    if !single_input && using_stdout && !cli.concat {
         return Err(crate::error::ShinkansenError::ValidationError(
             "Cannot use '-' (stdout) with multiple inputs".to_string(),
         ));
     }
 
+    if !single_input && using_clipboard && !cli.concat {
+        return Err(crate::error::ShinkansenError::ValidationError(
+            "-o clipboard takes a single file; use --concat to copy several as one".to_string(),
+        ));
+    }
+
     if !single_input
+        && !cli.concat
         && cli.output.is_some()
         && !using_stdout
+        && !using_clipboard
         && let Some(output) = &cli.output
     {
         let output_path = PathBuf::from(output);
@@ -101,64 +184,373 @@ pub fn validate_args(cli: &Cli) -> Result<()> {
     }
 
     // For multiple inputs, output must be specified
-    if !single_input && cli.output.is_none() {
+    if !single_input && cli.output.is_none() && !cli.in_place && !cli.concat {
         return Err(crate::error::ShinkansenError::ValidationError(
             "Multiple inputs require --output directory".to_string(),
         ));
     }
 
-    Ok(())
+    if let Some(dir) = cli.template_dirs.iter().find(|dir| !dir.is_dir()) {
+        return Err(crate::error::ShinkansenError::ValidationError(format!(
+            "Template directory does not exist: {:?}",
+            dir
+        )));
+    }
+
+    // Managed blocks are merged into existing files, so there must be one
+    let writes_files = cli.in_place || (cli.output.is_some() && !using_stdout && !using_clipboard);
+    if cli.managed_block && !writes_files {
+        return Err(crate::error::ShinkansenError::ValidationError(
+            "--managed-block requires a file or directory output".to_string(),
+        ));
+    }
+
+    // Patches describe changes to output files, so there must be some
+    if cli.emit_patch && !writes_files {
+        return Err(crate::error::ShinkansenError::ValidationError(
+            "--emit-patch requires a file or directory output".to_string(),
+        ));
+    }
+    if cli.manifest.is_some() && !writes_files {
+        return Err(crate::error::ShinkansenError::ValidationError(
+            "--manifest requires a file or directory output".to_string(),
+        ));
+    }
+    if cli.diff && !writes_files {
+        return Err(crate::error::ShinkansenError::ValidationError(
+            "--diff requires a file or directory output".to_string(),
+        ));
+    }
+    if cli.check && !writes_files {
+        return Err(crate::error::ShinkansenError::ValidationError(
+            "--check requires a file or directory output".to_string(),
+        ));
+    }
+
+    if let Some(link) = &cli.link {
+        if using_stdout || using_clipboard {
+            return Err(crate::error::ShinkansenError::ValidationError(
+                "--link requires an output directory for the releases".to_string(),
+            ));
+        }
+        check_link(link)?;
+    }
+
+    if cli.transactional && !writes_files {
+        return Err(crate::error::ShinkansenError::ValidationError(
+            "--transactional requires a file or directory output".to_string(),
+        ));
+    }
+
+    // Stamps are checked in the files a run would write
+    if cli.check_stamps && !writes_files {
+        return Err(crate::error::ShinkansenError::ValidationError(
+            "--check-stamps requires a file or directory output".to_string(),
+        ));
+    }
+
+    crate::input::validate_input_sources(cli)?;
+    crate::backend::validate_output_store(cli)?;
+    crate::backend::validate_clipboard_output(cli)
 }
 
-/// Process all inputs
-pub fn process_inputs(cli: &Cli, variables: &HashMap<String, minijinja::Value>) -> Result<()> {
-    // Check if we're reading from stdin (either explicitly with "-" or implicitly with no inputs)
-    let using_stdin = cli.inputs.is_empty() || (cli.inputs.len() == 1 && cli.inputs[0] == "-");
+/// Process all inputs, recording non-fatal conditions in `diagnostics`
+pub fn process_inputs(
+    cli: &Cli,
+    variables: &HashMap<String, minijinja::Value>,
+    diagnostics: &Diagnostics,
+) -> Result<()> {
+    let per_file = match &cli.per_file_vars {
+        Some(path) => PerFileVars::read(path)?,
+        None => PerFileVars::new(),
+    };
+    process_inputs_with(cli, variables, &per_file, diagnostics)
+}
 
-    if using_stdin {
-        process_stdin(cli, variables)?;
-    } else {
-        process_files(cli, variables)?;
+/// Process all inputs like [`process_inputs`], with variables for particular input files
+pub fn process_inputs_with(
+    cli: &Cli,
+    variables: &HashMap<String, minijinja::Value>,
+    per_file: &PerFileVars,
+    diagnostics: &Diagnostics,
+) -> Result<()> {
+    let backend = default_backend(cli);
+    process_inputs_to(cli, variables, per_file, &*backend, diagnostics)
+}
+
+/// Process all inputs like [`process_inputs_with`], storing outputs through `backend`
+pub fn process_inputs_to(
+    cli: &Cli,
+    variables: &HashMap<String, minijinja::Value>,
+    per_file: &PerFileVars,
+    backend: &dyn OutputBackend,
+    diagnostics: &Diagnostics,
+) -> Result<()> {
+    let source = default_source(cli, diagnostics)?;
+    process_inputs_from(cli, variables, per_file, &*source, backend, diagnostics)
+}
+
+/// Process all inputs like [`process_inputs_to`], listing and reading templates through `source`
+///
+/// Without inputs (or with `-`) the one template is read as
+/// [`crate::input::STDIN_PATH`]; otherwise every file `source` lists is rendered.
+pub fn process_inputs_from(
+    cli: &Cli,
+    variables: &HashMap<String, minijinja::Value>,
+    per_file: &PerFileVars,
+    source: &dyn InputSource,
+    backend: &dyn OutputBackend,
+    diagnostics: &Diagnostics,
+) -> Result<()> {
+    // Check if we're reading from stdin (either explicitly with "-" or implicitly with no inputs)
+    let using_stdin = reads_stdin(cli);
+    if using_stdin && !per_file.is_empty() {
+        return Err(crate::error::ShinkansenError::ValidationError(
+            "Per-file variables are matched against input files, so the template cannot come from stdin"
+                .to_string(),
+        ));
     }
+    let project = ProjectConfig::from_cli(cli)?;
+    project.check_requirements(&RenderOptions::from_cli(cli))?;
 
-    Ok(())
+    let process = || {
+        if using_stdin {
+            process_stdin(cli, &project, variables, source, backend)
+        } else {
+            process_files(
+                cli,
+                &project,
+                variables,
+                per_file,
+                source,
+                backend,
+                diagnostics,
+            )
+        }
+    };
+    if cli.dry_run {
+        // Hooks may change files, which a dry run must not
+        return process();
+    }
+    with_lifecycle_hooks(&project, variables, diagnostics, process)
 }
 
-fn process_stdin(cli: &Cli, variables: &HashMap<String, minijinja::Value>) -> Result<()> {
-    use std::io::{self, Read};
+fn process_stdin(
+    cli: &Cli,
+    project: &ProjectConfig,
+    variables: &HashMap<String, minijinja::Value>,
+    source: &dyn InputSource,
+    backend: &dyn OutputBackend,
+) -> Result<()> {
+    if cli.dry_run {
+        let output_destination = determine_output_destination(cli, true)?;
+        let stdin = RenderJob::file(PathBuf::from("stdin"));
+        return print_plan(&output_destination, [Ok(stdin)], true, source, cli);
+    }
 
-    let mut content = String::new();
-    io::stdin()
-        .read_to_string(&mut content)
+    let content = String::from_utf8(source.read(Path::new(STDIN_PATH))?)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
         .with_context(|| "Failed to read from stdin")?;
 
+    // Determine output destination
+    let output_destination = determine_output_destination(cli, true)?;
+    let stamp = StampOptions::from_cli(cli);
+
+    if let Some(stamp) = stamp.as_ref().filter(|_| cli.check_stamps) {
+        let unstamped = check_stamp(stamp, &output_destination, Path::new("stdin"), cli)?;
+        return report_unstamped(unstamped.as_slice());
+    }
+    refuse_unowned_outputs(
+        stamp.as_ref(),
+        &output_destination,
+        [Ok(RenderJob::file(PathBuf::from("stdin")))],
+        cli,
+    )?;
+    if ClobberPolicy::from_cli(cli) == ClobberPolicy::Refuse {
+        refuse_clobber(resolve_output_path(
+            &output_destination,
+            Path::new("stdin"),
+            cli,
+        ))?;
+    }
+
+    // Rendering profiles can only match stdin through the file it is written to
+    let base_options = RenderOptions::from_cli(cli);
+    let render_options = match resolve_output_path(&output_destination, Path::new("stdin"), cli) {
+        Some(output_path) => project.render_options_for(&output_path, &base_options),
+        None => base_options,
+    };
+
     // Use a more descriptive template name for better error reporting
     let template_name = "<stdin>";
-    let result = crate::rendering::render_template(&content, variables, template_name)?;
+    let result = crate::rendering::render_template_with_options(
+        &content,
+        variables,
+        template_name,
+        &render_options,
+    )?;
+    let result = stamp_output(
+        stamp.as_ref(),
+        &output_destination,
+        Path::new("stdin"),
+        result,
+        cli,
+    );
+    let result = merge_managed_block(
+        ManagedBlock::from_cli(cli)?.as_ref(),
+        &output_destination,
+        Path::new("stdin"),
+        result,
+        cli,
+    )?;
 
-    // Determine output destination
-    let output_destination = determine_output_destination(cli, true)?;
+    // Validate the rendered output before anything is written
+    let verify_commands = parse_verify_commands(&cli.verify_cmds)?;
+    verify_output(
+        &verify_commands,
+        &output_destination,
+        Path::new("stdin"),
+        &result,
+        cli,
+    )?;
+
+    if let Some(profiler) = &render_options.profiler {
+        eprint!("{}", profiler.report());
+    }
+    if let Some(coverage) = &render_options.coverage {
+        eprint!(
+            "{}",
+            coverage.report(variables.keys(), cli.profile.as_deref())
+        );
+    }
 
-    // Write to the appropriate output
-    write_to_output(&output_destination, Path::new("stdin"), &result, cli)?;
+    // Write to the appropriate output, or describe the change
+    if cli.diff {
+        print_diff(&output_destination, Path::new("stdin"), &result, cli)?;
+    }
+    if cli.check {
+        let stale = resolve_output_path(&output_destination, Path::new("stdin"), cli)
+            .map(|output_path| stale_output(&output_path, &result))
+            .transpose()?
+            .flatten();
+        report_stale(stale.as_slice())?;
+    } else if cli.emit_patch {
+        write_to_stdout(&output_patch(
+            &output_destination,
+            Path::new("stdin"),
+            &result,
+            cli,
+        )?);
+    } else {
+        write_to_backend(
+            backend,
+            &output_destination,
+            Path::new("stdin"),
+            &result,
+            cli,
+        )?;
+        let written: Vec<PathBuf> =
+            resolve_output_path(&output_destination, Path::new("stdin"), cli)
+                .into_iter()
+                .collect();
+        if let Some(manifest) = &cli.manifest {
+            let hash = content_hash(result.as_bytes());
+            let outputs: Vec<_> = written
+                .iter()
+                .map(|path| (path.clone(), hash.clone()))
+                .collect();
+            Manifest::from_outputs(manifest, &outputs)?.write(manifest)?;
+        }
+        report_hook_failures(&run_post_write_hooks(project, &written, 1))?;
+    }
 
     Ok(())
 }
 
-fn process_files(cli: &Cli, variables: &HashMap<String, minijinja::Value>) -> Result<()> {
-    let input_files = collect_input_files(cli)?;
+fn process_files(
+    cli: &Cli,
+    project: &ProjectConfig,
+    variables: &HashMap<String, minijinja::Value>,
+    per_file: &PerFileVars,
+    source: &dyn InputSource,
+    backend: &dyn OutputBackend,
+    diagnostics: &Diagnostics,
+) -> Result<()> {
+    for pattern in cli
+        .inputs
+        .iter()
+        .filter(|input| skips_unmatched_glob(input, cli))
+    {
+        diagnostics.warn(format!("No files match {:?}", pattern));
+    }
 
-    if input_files.is_empty() {
-        return Err(crate::error::ShinkansenError::FileSystemError(
-            "No files found to process".to_string(),
-        ));
+    let mut files = render_jobs(source, per_file)?.peekable();
+    let Some(first) = files.next().transpose()? else {
+        // Optional pipeline stages may legitimately have nothing to render
+        if cli.fail_if_no_files {
+            return Err(crate::error::ShinkansenError::FileSystemError(
+                "No files found to process".to_string(),
+            ));
+        }
+        diagnostics.warn("No files found to process");
+        return Ok(());
+    };
+
+    // Only the next file is needed to tell a single input from many
+    let single_file = files.peek().is_none();
+    let single_output = single_file || cli.concat;
+    let output_destination = match determine_output_destination(cli, single_output)? {
+        OutputDestination::Directory(releases) if cli.link.is_some() => {
+            OutputDestination::Directory(next_release(&releases))
+        }
+        output_destination => output_destination,
+    };
+    if cli.dry_run {
+        let files = std::iter::once(Ok(first)).chain(files);
+        return print_plan(&output_destination, files, single_file, source, cli);
+    }
+    let base_options = RenderOptions::from_cli(cli);
+    let verify_commands = parse_verify_commands(&cli.verify_cmds)?;
+    let managed_block = ManagedBlock::from_cli(cli)?;
+    let mut patch = String::new();
+    let stamp = StampOptions::from_cli(cli);
+    let mut unstamped = Vec::new();
+    let mut stale = Vec::new();
+    if refuses_unowned_outputs(cli) {
+        // A separate walk, so the check does not hold the whole tree in memory
+        let jobs = render_jobs(source, per_file)?;
+        refuse_unowned_outputs(stamp.as_ref(), &output_destination, jobs, cli)?;
+    }
+    if ClobberPolicy::from_cli(cli) == ClobberPolicy::Refuse {
+        let mut outputs = Vec::new();
+        for job in render_jobs(source, per_file)? {
+            let job = job?;
+            let destination = job.destination(&output_destination);
+            outputs.extend(resolve_output_path(&destination, &job.input, cli));
+        }
+        refuse_clobber(outputs)?;
     }
 
-    let single_file = input_files.len() == 1;
-    let output_destination = determine_output_destination(cli, single_file)?;
+    let jobs = cli.jobs.map_or_else(default_jobs, NonZeroUsize::get);
+    let inputs = cli_inputs(cli);
+    let render_file = |job: &RenderJob| -> Result<RenderedFile> {
+        if stop_requested() {
+            return Ok(RenderedFile::Cancelled);
+        }
+        let input_file = job.input.as_path();
+        let output_destination = job.destination(&output_destination);
+        if let Some(stamp) = stamp.as_ref().filter(|_| cli.check_stamps) {
+            let unstamped = check_stamp(stamp, &output_destination, input_file, cli)?;
+            return Ok(RenderedFile::Checked(unstamped));
+        }
 
-    for input_file in &input_files {
-        let content = std::fs::read_to_string(input_file)
+        let bytes = source.read(input_file)?;
+        // Images and archives in a template tree are skipped; a lone input must be text
+        if !single_file && is_binary(&bytes) {
+            return Ok(RenderedFile::Binary);
+        }
+        let content = String::from_utf8(bytes)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
             .with_context(|| format!("Failed to read file: {:?}", input_file))?;
 
         let template_name = input_file
@@ -166,65 +558,857 @@ fn process_files(cli: &Cli, variables: &HashMap<String, minijinja::Value>) -> Re
             .map(|s| s.to_string())
             .unwrap_or_else(|| format!("file_{}", input_file.display()));
 
-        let result = crate::rendering::render_template(&content, variables, &template_name)?;
+        let extended;
+        let variables = if cli.concat {
+            extended = fragment_variables(variables, input_file);
+            &extended
+        } else if let Some(render) = job.render {
+            extended = render.variables(variables);
+            &extended
+        } else {
+            variables
+        };
+        let result = crate::rendering::render_template_with_options(
+            &content,
+            variables,
+            &template_name,
+            &RenderOptions {
+                files: base_options
+                    .files
+                    .as_ref()
+                    .map(|files| files.with_root(template_root(&inputs, input_file))),
+                ..project.render_options_for(input_file, &base_options)
+            },
+        )?;
+        let result = stamp_output(stamp.as_ref(), &output_destination, input_file, result, cli);
+        let result = merge_managed_block(
+            managed_block.as_ref(),
+            &output_destination,
+            input_file,
+            result,
+            cli,
+        )?;
+
+        // Concatenated fragments are verified once they are joined
+        if !cli.concat {
+            verify_output(
+                &verify_commands,
+                &output_destination,
+                input_file,
+                &result,
+                cli,
+            )?;
+        }
+        Ok(RenderedFile::Rendered(result))
+    };
+
+    // A transactional run writes into a staging copy, dropped on any error, and
+    // runs the post-write hooks once the copy is in place
+    let staged = match (&output_destination, &cli.link) {
+        (OutputDestination::Directory(release), Some(link)) => {
+            Some(StagedDirectory::release(release, link, cli.keep)?)
+        }
+        (OutputDestination::Directory(dir), None) if cli.transactional => {
+            Some(StagedDirectory::begin(dir)?)
+        }
+        _ => None,
+    };
+    let write_destination = match &staged {
+        Some(staged) => OutputDestination::Directory(staged.path().to_path_buf()),
+        None => output_destination.clone(),
+    };
+    let mut staged_outputs = Vec::new();
+    let mut recorded = Vec::new();
+    let mut concatenated: Option<(PathBuf, String)> = None;
+    let mut prompt =
+        (ClobberPolicy::from_cli(cli) == ClobberPolicy::Ask).then(OverwritePrompt::stdio);
+
+    // Files render in bounded batches; results are handled in input order, so
+    // output, warnings and the first error match a sequential run
+    // An interrupt stops the run between files; see crate::interrupt
+    let mut hook_failures = Vec::new();
+    let mut written_total = 0;
+    let mut listed_inputs: HashSet<&Path> = per_file.inputs().collect();
+    let mut files = std::iter::once(Ok(first)).chain(files);
+    loop {
+        if stop_requested() {
+            return Err(interrupted_error(written_total));
+        }
+        let batch: Vec<Result<RenderJob>> = files.by_ref().take(jobs * FILES_PER_JOB).collect();
+        if batch.is_empty() {
+            break;
+        }
+        let rendered = parallel_map(&batch, jobs, |job| match job {
+            Ok(job) => Some(render_file(job)),
+            Err(_) => None,
+        });
+
+        let mut written = Vec::new();
+        for (job, rendered) in batch.into_iter().zip(rendered) {
+            let job = job?;
+            let input_file = &job.input;
+            listed_inputs.remove(input_file.as_path());
+            let output_destination = job.destination(&output_destination);
+            let write_destination = job.destination(&write_destination);
+            match rendered.expect("rendered every listed file")? {
+                RenderedFile::Cancelled => return Err(interrupted_error(written_total)),
+                RenderedFile::Checked(output) => unstamped.extend(output),
+                RenderedFile::Binary => {
+                    diagnostics.warn(format!("Skipped binary file {:?}", input_file));
+                }
+                RenderedFile::Rendered(result) if cli.concat => match &mut concatenated {
+                    Some((_, joined)) => {
+                        // Rendering drops a template's final newline, so pieces get one back
+                        end_line(joined);
+                        if let Some(separator) = &cli.separator {
+                            joined.push_str(&render_separator(
+                                separator,
+                                variables,
+                                input_file,
+                                &base_options,
+                            )?);
+                            end_line(joined);
+                        }
+                        joined.push_str(&result);
+                    }
+                    None => concatenated = Some((input_file.clone(), result)),
+                },
+                RenderedFile::Rendered(result) if cli.check => {
+                    if cli.diff {
+                        print_diff(&output_destination, input_file, &result, cli)?;
+                    }
+                    if let Some(output_path) =
+                        resolve_output_path(&output_destination, input_file, cli)
+                    {
+                        stale.extend(stale_output(&output_path, &result)?);
+                    }
+                }
+                RenderedFile::Rendered(result) if cli.emit_patch => {
+                    patch.push_str(&output_patch(
+                        &output_destination,
+                        input_file,
+                        &result,
+                        cli,
+                    )?);
+                }
+                RenderedFile::Rendered(result) => {
+                    if cli.diff {
+                        print_diff(&output_destination, input_file, &result, cli)?;
+                    }
+                    let output_path = resolve_output_path(&output_destination, input_file, cli);
+                    if !confirm_overwrite(&mut prompt, output_path.as_deref(), &result)? {
+                        continue;
+                    }
+                    write_to_backend(backend, &write_destination, input_file, &result, cli)?;
+                    written_total += 1;
+                    if cli.manifest.is_some() {
+                        let hash = content_hash(result.as_bytes());
+                        recorded.extend(output_path.clone().map(|path| (path, hash)));
+                    }
+                    written.extend(output_path);
+                }
+            }
+        }
+        if staged.is_some() {
+            staged_outputs.extend(written);
+        } else {
+            hook_failures.extend(run_post_write_hooks(project, &written, jobs));
+        }
+    }
+    if let Some((first_input, joined)) = &concatenated {
+        // The joined output goes through the same steps as a single rendered file
+        verify_output(
+            &verify_commands,
+            &output_destination,
+            first_input,
+            joined,
+            cli,
+        )?;
+        if cli.diff {
+            print_diff(&output_destination, first_input, joined, cli)?;
+        }
+        let output_path = resolve_output_path(&output_destination, first_input, cli);
+        if cli.check {
+            if let Some(output_path) = &output_path {
+                stale.extend(stale_output(output_path, joined)?);
+            }
+        } else if cli.emit_patch {
+            patch.push_str(&output_patch(
+                &output_destination,
+                first_input,
+                joined,
+                cli,
+            )?);
+        } else if confirm_overwrite(&mut prompt, output_path.as_deref(), joined)? {
+            write_to_backend(backend, &output_destination, first_input, joined, cli)?;
+            if cli.manifest.is_some() {
+                let hash = content_hash(joined.as_bytes());
+                recorded.extend(output_path.clone().map(|path| (path, hash)));
+            }
+            let written: Vec<PathBuf> = output_path.into_iter().collect();
+            hook_failures.extend(run_post_write_hooks(project, &written, jobs));
+        }
+    }
+    for input in listed_inputs {
+        diagnostics.warn(format!(
+            "Per-file variables for {:?} were not used: it is not among the inputs",
+            input
+        ));
+    }
+    if let Some(staged) = staged {
+        staged.commit()?;
+        hook_failures.extend(run_post_write_hooks(project, &staged_outputs, jobs));
+    }
+    if let Some(manifest) = &cli.manifest {
+        Manifest::from_outputs(manifest, &recorded)?.write(manifest)?;
+    }
+
+    // Coverage describes the renders, so it is reported even when a check fails
+    if let Some(coverage) = &base_options.coverage {
+        eprint!(
+            "{}",
+            coverage.report(variables.keys(), cli.profile.as_deref())
+        );
+    }
 
-        write_to_output(&output_destination, input_file, &result, cli)?;
+    if cli.check_stamps {
+        return report_unstamped(&unstamped);
+    }
+    if cli.check {
+        report_stale(&stale)?;
     }
 
-    Ok(())
+    if let Some(profiler) = &base_options.profiler {
+        eprint!("{}", profiler.report());
+    }
+
+    // Print the patch once every file has rendered and verified
+    if cli.emit_patch {
+        write_to_stdout(&patch);
+    }
+
+    report_hook_failures(&hook_failures)
 }
 
-/// Collect all input files from the provided inputs
-fn collect_input_files(cli: &Cli) -> Result<Vec<PathBuf>> {
-    let mut input_files = Vec::new();
+/// Files each `--jobs` worker is handed per batch; bounds the rendered output held in memory
+const FILES_PER_JOB: usize = 8;
+
+/// What happened to one input file, before anything is written
+enum RenderedFile {
+    /// `--check-stamps`: the output lacking its stamp, if any
+    Checked(Option<String>),
+    /// Skipped as binary
+    Binary,
+    /// Rendered, stamped, merged and verified content
+    Rendered(String),
+    /// Not started because the run was interrupted
+    Cancelled,
+}
 
-    for input_str in &cli.inputs {
-        let input = PathBuf::from(input_str);
-        let normalized_input = normalize_path(&input);
+fn default_jobs() -> usize {
+    std::thread::available_parallelism().map_or(1, NonZeroUsize::get)
+}
 
-        // Validate input path for security
-        validate_path(&normalized_input)?;
+/// Apply `f` to every item on up to `jobs` threads, returning the results in order
+pub(crate) fn parallel_map<T: Sync, R: Send>(
+    items: &[T],
+    jobs: usize,
+    f: impl Fn(&T) -> R + Sync,
+) -> Vec<R> {
+    let workers = jobs.min(items.len());
+    if workers <= 1 {
+        return items.iter().map(f).collect();
+    }
 
-        if input.is_file() {
-            input_files.push(normalized_input);
-        } else if input.is_dir() {
-            if cli.recursive {
-                for entry in WalkDir::new(&input).into_iter().filter_map(|e| e.ok()) {
-                    if entry.file_type().is_file() {
-                        let normalized_path = normalize_path(entry.path());
-                        input_files.push(normalized_path);
-                    }
+    let next = AtomicUsize::new(0);
+    let results: Vec<Mutex<Option<R>>> = items.iter().map(|_| Mutex::new(None)).collect();
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(item) = items.get(index) else {
+                        break;
+                    };
+                    let result = f(item);
+                    *results[index].lock().unwrap_or_else(|e| e.into_inner()) = Some(result);
                 }
+            });
+        }
+    });
+    results
+        .into_iter()
+        .map(|result| {
+            result
+                .into_inner()
+                .unwrap_or_else(|e| e.into_inner())
+                .expect("every item was mapped")
+        })
+        .collect()
+}
+
+/// Add a final newline to non-empty text that lacks one
+fn end_line(text: &mut String) {
+    if !text.is_empty() && !text.ends_with('\n') {
+        text.push('\n');
+    }
+}
+
+/// `--separator`: the text put before the fragment rendered from `next_input`
+fn render_separator(
+    separator: &str,
+    variables: &HashMap<String, minijinja::Value>,
+    next_input: &Path,
+    options: &RenderOptions,
+) -> Result<String> {
+    let mut variables = variables.clone();
+    variables.insert(
+        "template".to_string(),
+        minijinja::Value::from(next_input.display().to_string()),
+    );
+    crate::rendering::render_template_with_options(separator, &variables, "<separator>", options)
+}
+
+/// `--interactive`: whether `content` may replace the file at `output_path`
+fn confirm_overwrite(
+    prompt: &mut Option<OverwritePrompt>,
+    output_path: Option<&Path>,
+    content: &str,
+) -> Result<bool> {
+    match (prompt, output_path) {
+        (Some(prompt), Some(output_path)) => prompt.confirm(output_path, content),
+        _ => Ok(true),
+    }
+}
+
+/// `--diff`: print the change `result` makes to the output of `input_file`
+fn print_diff(
+    destination: &OutputDestination,
+    input_file: &Path,
+    result: &str,
+    cli: &Cli,
+) -> Result<()> {
+    if let Some(output_path) = resolve_output_path(destination, input_file, cli) {
+        write_to_stdout(&diff_for_file(&output_path, result, cli.color.enabled())?);
+    }
+    Ok(())
+}
+
+/// `--check`: describe `output_path` if it does not hold `content`
+fn stale_output(output_path: &Path, content: &str) -> Result<Option<String>> {
+    match std::fs::read(output_path) {
+        Ok(existing) if existing == content.as_bytes() => Ok(None),
+        Ok(_) => Ok(Some(format!("{} (changed)", output_path.display()))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            Ok(Some(format!("{} (missing)", output_path.display())))
+        }
+        Err(e) => Err(e).with_context(|| format!("Failed to read file: {:?}", output_path)),
+    }
+}
+
+/// Fail listing every output in `stale`, if any (`--check`)
+fn report_stale(stale: &[String]) -> Result<()> {
+    if stale.is_empty() {
+        return Ok(());
+    }
+    Err(crate::error::ShinkansenError::ValidationError(format!(
+        "{} generated file(s) out of date:\n  {}",
+        stale.len(),
+        stale.join("\n  ")
+    )))
+}
+
+/// `--dry-run`: print `input -> output` for every input, or why it would be skipped
+fn print_plan<'a>(
+    destination: &OutputDestination,
+    jobs: impl IntoIterator<Item = Result<RenderJob<'a>>>,
+    single_file: bool,
+    source: &dyn InputSource,
+    cli: &Cli,
+) -> Result<()> {
+    for job in jobs {
+        let job = job?;
+        let destination = &job.destination(destination);
+        let input_file = job.input;
+        if !single_file {
+            let bytes = source.read(&input_file)?;
+            if is_binary(&bytes) {
+                println!("{} (skipped: binary)", input_file.display());
+                continue;
+            }
+        }
+        let output = match resolve_output_path(destination, &input_file, cli) {
+            Some(output_path) => output_path.display().to_string(),
+            None if *destination == OutputDestination::Clipboard => CLIPBOARD_OUTPUT.to_string(),
+            None => "stdout".to_string(),
+        };
+        match destination {
+            OutputDestination::InPlace(Some(suffix)) => println!(
+                "{} -> {} (backup {})",
+                input_file.display(),
+                output,
+                backup_path(&input_file, suffix).display()
+            ),
+            _ => println!("{} -> {}", input_file.display(), output),
+        }
+    }
+    Ok(())
+}
+
+/// Content that is not UTF-8 text or contains NUL bytes
+fn is_binary(bytes: &[u8]) -> bool {
+    bytes.contains(&0) || std::str::from_utf8(bytes).is_err()
+}
+
+/// Add the generated-file header to rendered content when `--stamp` is given
+fn stamp_output(
+    stamp: Option<&StampOptions>,
+    destination: &OutputDestination,
+    input_file: &Path,
+    content: String,
+    cli: &Cli,
+) -> String {
+    match stamp {
+        Some(stamp) if cli.stamp => {
+            // The comment syntax follows the destination, or the input name on stdout
+            let output_path = resolve_output_path(destination, input_file, cli)
+                .unwrap_or_else(|| input_file.to_path_buf());
+            stamp.apply(input_file, &output_path, &content)
+        }
+        _ => content,
+    }
+}
+
+/// Describe the output of `input_file` if it is missing or lacks its stamp
+fn check_stamp(
+    stamp: &StampOptions,
+    destination: &OutputDestination,
+    input_file: &Path,
+    cli: &Cli,
+) -> Result<Option<String>> {
+    let Some(output_path) = resolve_output_path(destination, input_file, cli) else {
+        return Ok(None);
+    };
+    match std::fs::read_to_string(&output_path) {
+        Ok(content) if stamp.is_stamped(input_file, &output_path, &content) => Ok(None),
+        Ok(_) => Ok(Some(output_path.display().to_string())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            Ok(Some(format!("{} (missing)", output_path.display())))
+        }
+        Err(e) => Err(e).with_context(|| format!("Failed to read file: {:?}", output_path)),
+    }
+}
+
+/// Refuse to overwrite existing outputs shinkansen did not generate
+///
+/// With `--stamp`, an existing output without its stamp is treated as
+/// hand-maintained; `--force` takes it over. Every such file is checked
+/// before anything is written. Managed blocks are co-owned by design and
+/// are not checked.
+fn refuse_unowned_outputs<'a>(
+    stamp: Option<&StampOptions>,
+    destination: &OutputDestination,
+    jobs: impl IntoIterator<Item = Result<RenderJob<'a>>>,
+    cli: &Cli,
+) -> Result<()> {
+    let Some(stamp) = stamp.filter(|_| refuses_unowned_outputs(cli)) else {
+        return Ok(());
+    };
+
+    let mut unowned = Vec::new();
+    for job in jobs {
+        let job = job?;
+        let input_file = &job.input;
+        let destination = &job.destination(destination);
+        let Some(output_path) = resolve_output_path(destination, input_file, cli) else {
+            continue;
+        };
+        match std::fs::read_to_string(&output_path) {
+            Ok(content) if !stamp.is_stamped(input_file, &output_path, &content) => {
+                unowned.push(output_path.display().to_string());
+            }
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read file: {:?}", output_path));
+            }
+        }
+    }
+
+    if unowned.is_empty() {
+        return Ok(());
+    }
+    Err(crate::error::ShinkansenError::SecurityError(format!(
+        "Refusing to overwrite files not generated by shinkansen (use --force): {}",
+        unowned.join(", ")
+    )))
+}
+
+/// Whether existing outputs must carry their stamp before being overwritten
+fn refuses_unowned_outputs(cli: &Cli) -> bool {
+    cli.stamp && !cli.force && !cli.managed_block
+}
+
+/// Merge rendered content into the managed block of its destination file, if enabled
+///
+/// Verify commands then see the whole file as it will be written.
+fn merge_managed_block(
+    managed_block: Option<&ManagedBlock>,
+    destination: &OutputDestination,
+    input_file: &Path,
+    content: String,
+    cli: &Cli,
+) -> Result<String> {
+    match (
+        managed_block,
+        resolve_output_path(destination, input_file, cli),
+    ) {
+        (Some(managed_block), Some(output_path)) => {
+            managed_block.merge_file(&output_path, &content)
+        }
+        _ => Ok(content),
+    }
+}
+
+/// Patch turning the current destination file into the rendered content
+fn output_patch(
+    destination: &OutputDestination,
+    input_file: &Path,
+    content: &str,
+    cli: &Cli,
+) -> Result<String> {
+    match resolve_output_path(destination, input_file, cli) {
+        Some(output_path) => crate::patch::patch_for_file(&output_path, content),
+        None => Ok(String::new()),
+    }
+}
+
+/// Run verify commands against rendered content destined for the given output
+fn verify_output(
+    commands: &[VerifyCommand],
+    destination: &OutputDestination,
+    input_file: &Path,
+    content: &str,
+    cli: &Cli,
+) -> Result<()> {
+    if commands.is_empty() {
+        return Ok(());
+    }
+
+    // Match extensions against the destination when writing to a file,
+    // otherwise fall back to the input name (stdout output)
+    let output_path = resolve_output_path(destination, input_file, cli)
+        .unwrap_or_else(|| input_file.to_path_buf());
+
+    verify_rendered(commands, &output_path, content)
+}
+
+/// Order the files of one directory or glob input as `conf.d`-style fragments (`--concat`)
+///
+/// Files sort by name, with a leading number compared numerically, so
+/// `9-base.cfg` comes before `10-tls.cfg`; unnumbered names follow the
+/// numbered ones. Other runs keep the walk order.
+pub(crate) fn fragment_order<'a>(files: InputFiles<'a>, cli: &Cli) -> Result<InputFiles<'a>> {
+    if !cli.concat {
+        return Ok(files);
+    }
+    let mut files = files.collect::<Result<Vec<_>>>()?;
+    files.sort_by_cached_key(|path| {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        (
+            fragment_prefix(&name).unwrap_or(u64::MAX),
+            name.into_owned(),
+            path.clone(),
+        )
+    });
+    Ok(Box::new(files.into_iter().map(Ok)))
+}
+
+/// The number a fragment name starts with: 10 for `10-tls.cfg.j2`
+fn fragment_prefix(name: &str) -> Option<u64> {
+    let digits = name.len() - name.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    name[..digits].parse().ok()
+}
+
+/// The variables of a `--concat` fragment: the run's, plus `fragment`
+fn fragment_variables(
+    variables: &HashMap<String, minijinja::Value>,
+    input_file: &Path,
+) -> HashMap<String, minijinja::Value> {
+    let name = input_file
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned();
+    let fragment = minijinja::context! {
+        name => name,
+        path => input_file.display().to_string(),
+        prefix => fragment_prefix(&name),
+    };
+    let mut variables = variables.clone();
+    variables.insert("fragment".to_string(), fragment);
+    variables
+}
+
+/// One render of an input file; `--per-file-vars` can render a file several times
+struct RenderJob<'a> {
+    input: PathBuf,
+    render: Option<&'a PerFileRender>,
+}
+
+impl RenderJob<'_> {
+    fn file(input: PathBuf) -> Self {
+        RenderJob {
+            input,
+            render: None,
+        }
+    }
+
+    /// The render's own output if it has one, below the `-o` directory if there is one
+    fn destination(&self, destination: &OutputDestination) -> OutputDestination {
+        match (
+            self.render.and_then(|render| render.output.as_ref()),
+            destination,
+        ) {
+            (None, _) => destination.clone(),
+            (Some(output), OutputDestination::Directory(dir)) => {
+                OutputDestination::SingleFile(dir.join(output))
+            }
+            (Some(output), _) => OutputDestination::SingleFile(output.clone()),
+        }
+    }
+}
+
+/// Stream of renders: each input file once, or once per `--per-file-vars` entry
+type RenderJobs<'a> = Box<dyn Iterator<Item = Result<RenderJob<'a>>> + 'a>;
+
+fn render_jobs<'a>(
+    source: &'a dyn InputSource,
+    per_file: &'a PerFileVars,
+) -> Result<RenderJobs<'a>> {
+    let jobs = source.files()?.flat_map(move |input| match input {
+        Ok(input) => match per_file.renders(&input) {
+            Some(renders) => renders
+                .iter()
+                .map(|render| {
+                    Ok(RenderJob {
+                        input: input.clone(),
+                        render: Some(render),
+                    })
+                })
+                .collect(),
+            None => vec![Ok(RenderJob::file(input))],
+        },
+        Err(err) => vec![Err(err)],
+    });
+    Ok(Box::new(jobs))
+}
+
+/// Whether `input` is a glob pattern that matches nothing and is skipped
+///
+/// Such a pattern is treated like an empty directory, unless
+/// `--fail-if-no-files` is given.
+fn skips_unmatched_glob(input: &str, cli: &Cli) -> bool {
+    !cli.fail_if_no_files
+        && is_glob_input(input)
+        && Excludes::from_cli(cli)
+            .and_then(|excludes| glob_input_files(input, excludes))
+            .is_ok_and(|mut files| files.next().is_none())
+}
+
+/// `--exclude` globs, checked against paths relative to their input directory
+///
+/// Patterns without a `/` match the name of any file or directory, so
+/// `node_modules` skips that directory at every depth and `*.png` skips images;
+/// patterns with a `/` match the whole relative path. Excluded directories are
+/// not descended into.
+#[derive(Clone)]
+pub(crate) struct Excludes {
+    names: GlobSet,
+    paths: GlobSet,
+}
+
+impl Excludes {
+    pub(crate) fn from_cli(cli: &Cli) -> Result<Self> {
+        let mut names = GlobSetBuilder::new();
+        let mut paths = GlobSetBuilder::new();
+        for pattern in &cli.exclude {
+            let glob = GlobBuilder::new(pattern)
+                .literal_separator(true)
+                .build()
+                .map_err(|e| {
+                    crate::error::ShinkansenError::ValidationError(format!(
+                        "Invalid exclude pattern {:?}: {}",
+                        pattern, e
+                    ))
+                })?;
+            if pattern.contains('/') {
+                paths.add(glob);
             } else {
-                // Non-recursive: only direct children
-                if let Ok(entries) = std::fs::read_dir(&input) {
-                    for entry in entries.filter_map(|e| e.ok()) {
-                        match entry.file_type() {
-                            Ok(file_type) if file_type.is_file() => {
-                                let normalized_path = normalize_path(entry.path());
-                                input_files.push(normalized_path);
-                            }
-                            Ok(_) => {}
-                            Err(err) => {
-                                return Err(crate::error::ShinkansenError::FileSystemError(
-                                    format!(
-                                        "Failed to get file type for {:?}: {}",
-                                        entry.path(),
-                                        err
-                                    ),
-                                ));
-                            }
-                        }
-                    }
-                }
+                names.add(glob);
             }
-        } else {
+        }
+
+        let build = |builder: GlobSetBuilder| {
+            builder.build().map_err(|e| {
+                crate::error::ShinkansenError::ValidationError(format!(
+                    "Invalid exclude patterns: {}",
+                    e
+                ))
+            })
+        };
+        Ok(Excludes {
+            names: build(names)?,
+            paths: build(paths)?,
+        })
+    }
+
+    /// Whether the entry at `relative` (below its input directory) is excluded
+    fn is_excluded(&self, relative: &Path) -> bool {
+        let name_match = relative
+            .file_name()
+            .is_some_and(|name| self.names.is_match(name));
+        name_match || (!relative.as_os_str().is_empty() && self.paths.is_match(relative))
+    }
+
+    /// Whether the file at `relative` is excluded, or lies in an excluded directory
+    ///
+    /// For listings that are not walked, such as archives and git trees.
+    pub(crate) fn excludes_file(&self, relative: &Path) -> bool {
+        relative
+            .ancestors()
+            .filter(|ancestor| !ancestor.as_os_str().is_empty())
+            .any(|ancestor| self.is_excluded(ancestor))
+    }
+
+    /// Whether a walked entry is kept; the walk root itself always is
+    fn keeps(&self, root: &Path, entry: &walkdir::DirEntry) -> bool {
+        entry.depth() == 0
+            || entry
+                .path()
+                .strip_prefix(root)
+                .is_ok_and(|relative| !self.is_excluded(relative))
+    }
+}
+
+/// Files matching an input glob such as `templates/**/*.yaml.j2`, in path order
+///
+/// Patterns are expanded here rather than by the shell, so they work quoted
+/// and on Windows. `*` and `?` stay within a directory and `**` crosses
+/// directories, whether or not `-r` is given.
+fn glob_input_files(input: &str, excludes: Excludes) -> Result<InputFiles<'static>> {
+    let (base, rest) = split_glob_base(&input_glob_pattern(input));
+    let matcher = GlobBuilder::new(&rest)
+        .literal_separator(true)
+        .build()
+        .map_err(|e| {
+            crate::error::ShinkansenError::ValidationError(format!(
+                "Invalid glob pattern {:?}: {}",
+                input, e
+            ))
+        })?
+        .compile_matcher();
+    let base = normalize_path(base);
+    validate_path(&base)?;
+
+    let root = if base.as_os_str().is_empty() {
+        PathBuf::from(".")
+    } else {
+        base.clone()
+    };
+    let mut walk = WalkDir::new(&root).sort_by_file_name();
+    if !rest.contains("**") {
+        walk = walk.max_depth(rest.split('/').count());
+    }
+    let walk_root = root.clone();
+    let files = walk
+        .into_iter()
+        .filter_entry(move |entry| excludes.keeps(&walk_root, entry))
+        .filter_map(|e| e.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(move |entry| {
+            let relative = entry.path().strip_prefix(&root).ok()?;
+            matcher
+                .is_match(relative)
+                .then(|| Ok(normalize_path(base.join(relative))))
+        });
+    Ok(Box::new(files))
+}
+
+/// Walk the inputs lazily so large trees start rendering immediately
+///
+/// Every input is validated and checked for existence up front; the files in
+/// directories are only listed as the stream is consumed, keeping memory flat
+/// regardless of the tree size.
+pub(crate) fn input_files(cli: &Cli) -> Result<InputFiles<'_>> {
+    let excludes = Excludes::from_cli(cli)?;
+    let mut streams = Vec::new();
+    for input in &cli.inputs {
+        streams.push(local_input_files(cli, input, &excludes)?);
+    }
+    Ok(Box::new(streams.into_iter().flatten()))
+}
+
+/// The files of one local input: a file, a directory or a glob
+pub(crate) fn local_input_files<'a>(
+    cli: &'a Cli,
+    input_str: &str,
+    excludes: &Excludes,
+) -> Result<InputFiles<'a>> {
+    if is_glob_input(input_str) {
+        let mut files = glob_input_files(input_str, excludes.clone())?.peekable();
+        if files.peek().is_none() && cli.fail_if_no_files {
             return Err(crate::error::ShinkansenError::FileSystemError(format!(
-                "Input does not exist: {:?}",
-                normalized_input
+                "No files match {:?}",
+                input_str
             )));
         }
+        return fragment_order(Box::new(files), cli);
     }
 
-    Ok(input_files)
+    let input = PathBuf::from(input_str);
+    let normalized_input = normalize_path(&input);
+
+    // Validate input path for security
+    validate_path(&normalized_input)?;
+
+    if input.is_file() {
+        Ok(Box::new(std::iter::once(Ok(normalized_input))))
+    } else if input.is_dir() {
+        if cli.recursive {
+            let excludes = excludes.clone();
+            let root = input.clone();
+            let files = WalkDir::new(&input)
+                .into_iter()
+                .filter_entry(move |entry| excludes.keeps(&root, entry))
+                .filter_map(|e| e.ok())
+                .filter(|entry| entry.file_type().is_file())
+                .map(|entry| Ok(normalize_path(entry.path())));
+            fragment_order(Box::new(files), cli)
+        } else {
+            // Non-recursive: only direct children
+            let Ok(entries) = std::fs::read_dir(&input) else {
+                return Ok(Box::new(std::iter::empty()));
+            };
+            let excludes = excludes.clone();
+            let files = entries.filter_map(|e| e.ok()).filter_map(move |entry| {
+                if excludes.is_excluded(Path::new(&entry.file_name())) {
+                    return None;
+                }
+                match entry.file_type() {
+                    Ok(file_type) if file_type.is_file() => Some(Ok(normalize_path(entry.path()))),
+                    Ok(_) => None,
+                    Err(err) => Some(Err(crate::error::ShinkansenError::FileSystemError(
+                        format!("Failed to get file type for {:?}: {}", entry.path(), err),
+                    ))),
+                }
+            });
+            fragment_order(Box::new(files), cli)
+        }
+    } else {
+        Err(crate::error::ShinkansenError::FileSystemError(format!(
+            "Input does not exist: {:?}",
+            normalized_input
+        )))
+    }
 }
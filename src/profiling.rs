@@ -0,0 +1,79 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Where rendering one template spent its time (`--profile-template`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct TemplateProfile {
+    pub name: String,
+    /// Parsing and compiling the template
+    pub parse: Duration,
+    /// Evaluating the template, including loops, filters and macros
+    pub render: Duration,
+    /// Running the `--postprocess` command, if any
+    pub postprocess: Duration,
+    /// MiniJinja VM instructions executed while rendering
+    pub instructions: u64,
+}
+
+impl TemplateProfile {
+    pub fn total(&self) -> Duration {
+        self.parse + self.render + self.postprocess
+    }
+}
+
+/// Collects template profiles across a run; clones share the same records
+#[derive(Debug, Clone, Default)]
+pub struct Profiler {
+    profiles: Arc<Mutex<Vec<TemplateProfile>>>,
+}
+
+impl Profiler {
+    pub fn record(&self, profile: TemplateProfile) {
+        self.lock().push(profile);
+    }
+
+    /// Recorded profiles, slowest first
+    pub fn profiles(&self) -> Vec<TemplateProfile> {
+        let mut profiles = self.lock().clone();
+        profiles.sort_by(|a, b| b.total().cmp(&a.total()).then(a.name.cmp(&b.name)));
+        profiles
+    }
+
+    /// Describe the recorded profiles, slowest template first
+    ///
+    /// ```text
+    /// profile:   12.4ms  parse 0.3ms  render 12.1ms  postprocess 0ns  1843 instructions  deploy.yaml
+    /// profile:   12.9ms  all templates (2)
+    /// ```
+    pub fn report(&self) -> String {
+        let profiles = self.profiles();
+        let mut output = String::new();
+        for profile in &profiles {
+            output.push_str(&format!(
+                "profile: {:>8.1?}  parse {:.1?}  render {:.1?}  postprocess {:.1?}  {} instructions  {}\n",
+                profile.total(),
+                profile.parse,
+                profile.render,
+                profile.postprocess,
+                profile.instructions,
+                profile.name
+            ));
+        }
+        if !profiles.is_empty() {
+            let total: Duration = profiles.iter().map(TemplateProfile::total).sum();
+            output.push_str(&format!(
+                "profile: {:>8.1?}  all templates ({})\n",
+                total,
+                profiles.len()
+            ));
+        }
+        output
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<TemplateProfile>> {
+        // A panic while recording cannot leave the list inconsistent
+        self.profiles
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
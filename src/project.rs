@@ -0,0 +1,455 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::Deserialize;
+
+use crate::cli::Cli;
+use crate::error::{ContextExt, Result, ShinkansenError, catch_panic};
+use crate::filters::FILTER_PACKS;
+use crate::rendering::{Delimiters, RenderOptions};
+use crate::version::VersionReq;
+
+/// File name of the project config discovered from the working directory
+pub const PROJECT_CONFIG_FILE: &str = "shinkansen.toml";
+
+/// Project-wide settings read from `shinkansen.toml`
+///
+/// ```toml
+/// requires_shinkansen = ">=0.2"
+/// requires_filter_packs = ["k8s"]
+///
+/// [[render]]
+/// match = "*.ps1"
+/// delimiters = { block = ["<%", "%>"], variable = ["<<", ">>"], comment = ["<#", "#>"] }
+///
+/// [[render]]
+/// match = ["*.yaml", "*.yml"]
+/// trim_blocks = true
+/// postprocess = "yq --prettyPrint"
+///
+/// [[render]]
+/// match = "reports/*.html"
+/// max_render_time = "30s"
+///
+/// [[hook]]
+/// match = "*.service"
+/// run = "systemd-analyze verify {}"
+///
+/// [hooks]
+/// before = ["git pull --ff-only"]
+/// after = ["systemctl daemon-reload"]
+/// ```
+#[derive(Debug, Default)]
+pub struct ProjectConfig {
+    /// Directory containing the config; `/` patterns are relative to it
+    pub root: PathBuf,
+    pub render: Vec<RenderRule>,
+    pub hooks: Vec<HookRule>,
+    pub lifecycle: LifecycleHooks,
+    pub requirements: Requirements,
+}
+
+/// What the templates of a project need from the running shinkansen
+#[derive(Debug, Default)]
+pub struct Requirements {
+    /// `requires_shinkansen`
+    pub version: Option<VersionReq>,
+    /// `requires_filter_packs`, names from [`FILTER_PACKS`]
+    pub filter_packs: Vec<String>,
+}
+
+/// Shell commands run around a whole run (`[hooks]`), each list in order
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LifecycleHooks {
+    /// Before anything is rendered; a failure aborts the run
+    #[serde(default)]
+    pub before: Vec<String>,
+    /// After every output was written
+    #[serde(default)]
+    pub after: Vec<String>,
+    /// When the run fails, including in `before` or `after`
+    #[serde(default)]
+    pub on_failure: Vec<String>,
+}
+
+/// Rendering options applied to files matching a set of globs
+#[derive(Debug)]
+pub struct RenderRule {
+    pub patterns: Vec<String>,
+    matcher: Matcher,
+    pub profile: RenderProfile,
+}
+
+/// Command run after writing outputs matching a set of globs (`[[hook]]`)
+#[derive(Debug)]
+pub struct HookRule {
+    pub patterns: Vec<String>,
+    matcher: Matcher,
+    /// Shell command, with `{}` standing in for the written file
+    pub run: String,
+}
+
+/// Overrides of a `[[render]]` entry; unset fields keep the current value
+#[derive(Debug, Default, Clone)]
+pub struct RenderProfile {
+    pub delimiters: Option<DelimiterConfig>,
+    pub autoescape: Option<AutoEscape>,
+    pub trim_blocks: Option<bool>,
+    pub lstrip_blocks: Option<bool>,
+    pub keep_trailing_newline: Option<bool>,
+    pub postprocess: Option<String>,
+    pub max_render_time: Option<Duration>,
+}
+
+/// Delimiter pairs of a profile; missing pairs keep the MiniJinja defaults
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DelimiterConfig {
+    pub block: Option<(String, String)>,
+    pub variable: Option<(String, String)>,
+    pub comment: Option<(String, String)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AutoEscape {
+    None,
+    Html,
+}
+
+#[derive(Debug)]
+struct Matcher {
+    /// Patterns without a `/`, matched against the file name
+    names: GlobSet,
+    /// Patterns with a `/`, matched against the path relative to the project root
+    paths: GlobSet,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawProjectConfig {
+    #[serde(default)]
+    render: Vec<RawRenderRule>,
+    #[serde(default)]
+    hook: Vec<RawHookRule>,
+    #[serde(default)]
+    hooks: LifecycleHooks,
+    requires_shinkansen: Option<String>,
+    #[serde(default)]
+    requires_filter_packs: Vec<String>,
+}
+
+// `deny_unknown_fields` does not work with `flatten`, so the profile fields are repeated here
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawRenderRule {
+    #[serde(rename = "match")]
+    patterns: Patterns,
+    delimiters: Option<DelimiterConfig>,
+    autoescape: Option<AutoEscape>,
+    trim_blocks: Option<bool>,
+    lstrip_blocks: Option<bool>,
+    keep_trailing_newline: Option<bool>,
+    postprocess: Option<String>,
+    max_render_time: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawHookRule {
+    #[serde(rename = "match")]
+    patterns: Patterns,
+    run: String,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Patterns {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Patterns {
+    fn into_vec(self) -> Vec<String> {
+        match self {
+            Patterns::One(pattern) => vec![pattern],
+            Patterns::Many(patterns) => patterns,
+        }
+    }
+}
+
+impl ProjectConfig {
+    /// Load the project config selected by `--project-config`/`--no-project-config`
+    ///
+    /// Without either flag the nearest `shinkansen.toml` in the working
+    /// directory or its parents is used; having none is not an error.
+    pub fn from_cli(cli: &Cli) -> Result<Self> {
+        match Self::path_from_cli(cli)? {
+            Some(path) => Self::load(&path),
+            None => Ok(ProjectConfig::default()),
+        }
+    }
+
+    /// The project config file `cli` uses, if any
+    pub fn path_from_cli(cli: &Cli) -> Result<Option<PathBuf>> {
+        if cli.no_project_config {
+            return Ok(None);
+        }
+        if let Some(path) = &cli.project_config {
+            return Ok(Some(path.clone()));
+        }
+        let cwd =
+            std::env::current_dir().with_context(|| "Failed to determine the current directory")?;
+        Ok(find_project_config(&cwd))
+    }
+
+    /// Load a project config file
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read project config: {:?}", path))?;
+        let root = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        Self::parse(&content, root).with_context(|| path.display().to_string())
+    }
+
+    /// Parse project config content whose relative patterns resolve against `root`
+    pub fn parse(content: &str, root: &Path) -> Result<Self> {
+        let raw: RawProjectConfig = catch_panic("parsing the project config", || {
+            toml::from_str(content).map_err(ShinkansenError::from)
+        })?;
+
+        let render = raw
+            .render
+            .into_iter()
+            .map(|rule| {
+                let patterns = rule.patterns.into_vec();
+                let max_render_time = rule
+                    .max_render_time
+                    .map(|value| {
+                        crate::cache::parse_duration(&value).map_err(|e| {
+                            ShinkansenError::ConfigParseError(format!(
+                                "Invalid max_render_time: {}",
+                                e
+                            ))
+                        })
+                    })
+                    .transpose()?;
+                Ok(RenderRule {
+                    matcher: Matcher::new(&patterns, "render")?,
+                    patterns,
+                    profile: RenderProfile {
+                        delimiters: rule.delimiters,
+                        autoescape: rule.autoescape,
+                        trim_blocks: rule.trim_blocks,
+                        lstrip_blocks: rule.lstrip_blocks,
+                        keep_trailing_newline: rule.keep_trailing_newline,
+                        postprocess: rule.postprocess,
+                        max_render_time,
+                    },
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        let hooks = raw
+            .hook
+            .into_iter()
+            .map(|rule| {
+                if rule.run.trim().is_empty() {
+                    return Err(ShinkansenError::ConfigParseError(
+                        "Hook commands must not be empty".to_string(),
+                    ));
+                }
+                let patterns = rule.patterns.into_vec();
+                Ok(HookRule {
+                    matcher: Matcher::new(&patterns, "hook")?,
+                    patterns,
+                    run: rule.run,
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        let version = raw
+            .requires_shinkansen
+            .map(|requirement| {
+                VersionReq::parse(&requirement).map_err(|e| {
+                    ShinkansenError::ConfigParseError(format!(
+                        "Invalid requires_shinkansen '{}': {}",
+                        requirement, e
+                    ))
+                })
+            })
+            .transpose()?;
+
+        Ok(ProjectConfig {
+            root: root.to_path_buf(),
+            render,
+            hooks,
+            lifecycle: raw.hooks,
+            requirements: Requirements {
+                version,
+                filter_packs: raw.requires_filter_packs,
+            },
+        })
+    }
+
+    /// Fail before rendering when this build or the run's options cannot
+    /// satisfy the project's `requires_shinkansen` and `requires_filter_packs`
+    pub fn check_requirements(&self, options: &RenderOptions) -> Result<()> {
+        let version = env!("CARGO_PKG_VERSION");
+        if let Some(requirement) = &self.requirements.version
+            && !requirement.matches(version)
+        {
+            return Err(ShinkansenError::ValidationError(format!(
+                "This project requires shinkansen {}, but this is shinkansen {}",
+                requirement, version
+            ))
+            .with_hint("install a shinkansen version that satisfies requires_shinkansen"));
+        }
+
+        for pack in &self.requirements.filter_packs {
+            let flag = match pack.as_str() {
+                "k8s" if !options.k8s => Some("--k8s"),
+                "crypto" if !options.crypto => Some("--allow-crypto"),
+                _ => None,
+            };
+            if !FILTER_PACKS.iter().any(|(name, _)| name == pack) {
+                return Err(ShinkansenError::ValidationError(format!(
+                    "This project requires the '{}' filter pack, which shinkansen {} does not have",
+                    pack, version
+                ))
+                .with_hint(format!(
+                    "available filter packs are {}",
+                    FILTER_PACKS
+                        .iter()
+                        .map(|(name, _)| *name)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )));
+            }
+            if let Some(flag) = flag {
+                return Err(ShinkansenError::ValidationError(format!(
+                    "This project requires the '{}' filter pack, which is only enabled with {}",
+                    pack, flag
+                ))
+                .with_hint(format!("pass {}", flag)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Rendering options for a file: `base` with every matching profile applied in order
+    pub fn render_options_for(&self, path: &Path, base: &RenderOptions) -> RenderOptions {
+        let relative = self.relative_path(path);
+        let mut options = base.clone();
+        for rule in &self.render {
+            if rule.matcher.is_match(path, &relative) {
+                rule.profile.apply(&mut options);
+            }
+        }
+        options
+    }
+
+    /// Hook commands to run after writing `path`, in config order
+    pub fn hooks_for(&self, path: &Path) -> Vec<&str> {
+        let relative = self.relative_path(path);
+        self.hooks
+            .iter()
+            .filter(|hook| hook.matcher.is_match(path, &relative))
+            .map(|hook| hook.run.as_str())
+            .collect()
+    }
+
+    fn relative_path(&self, path: &Path) -> PathBuf {
+        let absolute = |p: &Path| std::path::absolute(p).unwrap_or_else(|_| p.to_path_buf());
+        let (path_abs, root_abs) = (absolute(path), absolute(&self.root));
+        path_abs
+            .strip_prefix(&root_abs)
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|_| path.to_path_buf())
+    }
+}
+
+impl RenderProfile {
+    /// Apply the fields set in this profile on top of `options`
+    pub fn apply(&self, options: &mut RenderOptions) {
+        if let Some(delimiters) = &self.delimiters {
+            let mut merged = options.delimiters.clone().unwrap_or_default();
+            if let Some(block) = &delimiters.block {
+                merged.block = block.clone();
+            }
+            if let Some(variable) = &delimiters.variable {
+                merged.variable = variable.clone();
+            }
+            if let Some(comment) = &delimiters.comment {
+                merged.comment = comment.clone();
+            }
+            options.delimiters = Some(merged).filter(|d| *d != Delimiters::default());
+        }
+        if let Some(autoescape) = self.autoescape {
+            options.autoescape_html = autoescape == AutoEscape::Html;
+        }
+        if let Some(trim_blocks) = self.trim_blocks {
+            options.trim_blocks = trim_blocks;
+        }
+        if let Some(lstrip_blocks) = self.lstrip_blocks {
+            options.lstrip_blocks = lstrip_blocks;
+        }
+        if let Some(keep_trailing_newline) = self.keep_trailing_newline {
+            options.keep_trailing_newline = keep_trailing_newline;
+        }
+        if let Some(postprocess) = &self.postprocess {
+            options.postprocess = Some(postprocess.clone()).filter(|cmd| !cmd.is_empty());
+        }
+        if let Some(max_render_time) = self.max_render_time {
+            options.max_render_time = Some(max_render_time);
+        }
+    }
+}
+
+impl Matcher {
+    fn new(patterns: &[String], section: &str) -> Result<Self> {
+        let mut names = GlobSetBuilder::new();
+        let mut paths = GlobSetBuilder::new();
+        for pattern in patterns {
+            let glob = Glob::new(pattern).map_err(|e| {
+                ShinkansenError::ConfigParseError(format!(
+                    "Invalid {} pattern '{}': {}",
+                    section, pattern, e
+                ))
+            })?;
+            if pattern.contains('/') {
+                paths.add(glob);
+            } else {
+                names.add(glob);
+            }
+        }
+
+        let build = |builder: GlobSetBuilder| {
+            builder.build().map_err(|e| {
+                ShinkansenError::ConfigParseError(format!("Invalid {} patterns: {}", section, e))
+            })
+        };
+        Ok(Matcher {
+            names: build(names)?,
+            paths: build(paths)?,
+        })
+    }
+
+    fn is_match(&self, path: &Path, relative: &Path) -> bool {
+        let name_match = path
+            .file_name()
+            .is_some_and(|name| self.names.is_match(name));
+        name_match || self.paths.is_match(relative)
+    }
+}
+
+/// Find the nearest project config in `dir` or one of its parents
+pub fn find_project_config(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|ancestor| ancestor.join(PROJECT_CONFIG_FILE))
+        .find(|candidate| candidate.is_file())
+}
@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+
+/// Where the value of a template variable came from
+#[derive(Debug, Clone, PartialEq)]
+pub enum VariableSource {
+    /// Dotenv file given with --env-file
+    Dotenv(PathBuf),
+    /// Environment variable loaded with --env
+    Env(String),
+    /// The whole process environment, exposed as `env` by --env-all
+    Environment,
+    /// Config file given with -c
+    Config(PathBuf),
+    /// JSON or YAML document read with --vars-stdin
+    Stdin,
+    /// Named data source or key/value store, by its flag and spec
+    Source(String),
+    /// Command-line assignment: the flag (-D, --set-json, ...), the key and
+    /// its 1-based position among that flag's assignments
+    Cli {
+        flag: &'static str,
+        key: String,
+        index: usize,
+    },
+    /// `default` declared in the `_meta` block of a config file
+    Default(PathBuf),
+    /// `[derived]` template of a config file
+    Derived(PathBuf),
+}
+
+impl fmt::Display for VariableSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VariableSource::Dotenv(path) => write!(f, "env file {}", path.display()),
+            VariableSource::Env(name) => write!(f, "env ${}", name),
+            VariableSource::Environment => write!(f, "--env-all"),
+            VariableSource::Config(path) => write!(f, "config {}", path.display()),
+            VariableSource::Stdin => write!(f, "stdin"),
+            VariableSource::Source(label) => write!(f, "{}", label),
+            VariableSource::Cli { flag, key, index } => {
+                write!(f, "{} {} (#{})", flag, key, index)
+            }
+            VariableSource::Default(path) => write!(f, "default in {}", path.display()),
+            VariableSource::Derived(path) => write!(f, "derived in {}", path.display()),
+        }
+    }
+}
+
+/// Every source that set each variable, in the order they were applied
+///
+/// The last source recorded for a name is the one whose value is used.
+#[derive(Debug, Clone, Default)]
+pub struct Provenance {
+    sources: HashMap<String, Vec<VariableSource>>,
+}
+
+impl Provenance {
+    /// Record that `source` set the variable `name`
+    pub fn record(&mut self, name: impl Into<String>, source: VariableSource) {
+        self.sources.entry(name.into()).or_default().push(source);
+    }
+
+    /// All sources that set `name`, lowest precedence first
+    pub fn sources(&self, name: &str) -> &[VariableSource] {
+        self.sources.get(name).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// The source whose value won for `name`
+    pub fn winner(&self, name: &str) -> Option<&VariableSource> {
+        self.sources(name).last()
+    }
+
+    /// Describe, for every variable, the winning source and the ones it overrode (`--explain-vars`)
+    ///
+    /// ```text
+    /// region: -D region (#1), overriding config prod.yaml, env $REGION
+    /// ```
+    pub fn explain(&self) -> String {
+        let mut names: Vec<&String> = self.sources.keys().collect();
+        names.sort();
+
+        let mut output = String::new();
+        for name in names {
+            let sources = &self.sources[name];
+            let Some((winner, overridden)) = sources.split_last() else {
+                continue;
+            };
+            output.push_str(&format!("{}: {}", name, winner));
+            if !overridden.is_empty() {
+                let overridden: Vec<String> =
+                    overridden.iter().rev().map(ToString::to_string).collect();
+                output.push_str(&format!(", overriding {}", overridden.join(", ")));
+            }
+            output.push('\n');
+        }
+        output
+    }
+}
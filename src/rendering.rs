@@ -1,7 +1,87 @@
-use std::collections::HashMap;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, mpsc};
+use std::time::{Duration, Instant};
 
-use crate::error::{ContextExt, Result};
-use minijinja::Environment;
+use crate::cli::Cli;
+use crate::context::Context;
+use crate::coverage::Coverage;
+use crate::error::{ContextExt, Result, catch_panic};
+use crate::profiling::{Profiler, TemplateProfile};
+use crate::sandbox::FileAccess;
+use minijinja::value::{Kwargs, ValueKind};
+use minijinja::{Environment, Error, ErrorKind, State, Value};
+
+/// Options controlling how templates are rendered
+///
+/// Everything except `k8s`, `crypto`, `files` and `template_dirs` can also be
+/// set per file by the `[[render]]` profiles of the project config.
+#[derive(Debug, Clone, Default)]
+pub struct RenderOptions {
+    /// Register the Kubernetes filter pack (`--k8s`)
+    pub k8s: bool,
+    /// Register the password hash and HMAC filters (`--allow-crypto`)
+    pub crypto: bool,
+    /// Custom template delimiters; `None` keeps `{% %}`, `{{ }}` and `{# #}`
+    pub delimiters: Option<Delimiters>,
+    /// HTML-escape variable output
+    pub autoescape_html: bool,
+    /// Remove the first newline after a block tag
+    pub trim_blocks: bool,
+    /// Strip leading whitespace from the start of a line up to a block tag
+    pub lstrip_blocks: bool,
+    /// Keep the trailing newline at the end of the template
+    pub keep_trailing_newline: bool,
+    /// Shell command the rendered output is piped through (`--postprocess`)
+    pub postprocess: Option<String>,
+    /// Records where each render spends its time (`--profile-template`)
+    pub profiler: Option<Profiler>,
+    /// Records which variables each render reads (`--coverage`)
+    pub coverage: Option<Coverage>,
+    /// Directories file functions may read; `None` leaves them unregistered
+    pub files: Option<FileAccess>,
+    /// Search path for `include`, `extends` and `import` (`--template-dir`)
+    pub template_dirs: Vec<PathBuf>,
+    /// Cancel a render still running after this long (`--max-render-time`)
+    pub max_render_time: Option<Duration>,
+}
+
+/// Template delimiter pairs (start, end)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Delimiters {
+    pub block: (String, String),
+    pub variable: (String, String),
+    pub comment: (String, String),
+}
+
+impl Default for Delimiters {
+    fn default() -> Self {
+        Delimiters {
+            block: ("{%".to_string(), "%}".to_string()),
+            variable: ("{{".to_string(), "}}".to_string()),
+            comment: ("{#".to_string(), "#}".to_string()),
+        }
+    }
+}
+
+impl RenderOptions {
+    /// Build rendering options from CLI arguments
+    pub fn from_cli(cli: &Cli) -> Self {
+        RenderOptions {
+            k8s: cli.k8s,
+            crypto: cli.allow_crypto,
+            postprocess: cli.postprocess.clone(),
+            profiler: cli.profile_template.then(Profiler::default),
+            coverage: cli.coverage.then(Coverage::default),
+            files: Some(FileAccess::new(".", &cli.allow_dirs)),
+            template_dirs: cli.template_dirs.clone(),
+            max_render_time: cli.max_render_time,
+            ..Default::default()
+        }
+    }
+}
 
 /// Validate template content for security and size constraints
 fn validate_template_content(content: &str) -> Result<()> {
@@ -16,28 +96,357 @@ fn validate_template_content(content: &str) -> Result<()> {
     Ok(())
 }
 
+/// Create a MiniJinja environment configured for the given options
+fn create_environment<'source>(options: &RenderOptions) -> Result<Environment<'source>> {
+    let mut env = Environment::new();
+
+    // Configure MiniJinja to treat missing variables as errors
+    env.set_undefined_behavior(minijinja::UndefinedBehavior::Strict);
+
+    env.set_trim_blocks(options.trim_blocks);
+    env.set_lstrip_blocks(options.lstrip_blocks);
+    env.set_keep_trailing_newline(options.keep_trailing_newline);
+
+    if options.autoescape_html {
+        env.set_auto_escape_callback(|_| minijinja::AutoEscape::Html);
+    }
+
+    if let Some(delimiters) = &options.delimiters {
+        let syntax = minijinja::syntax::SyntaxConfig::builder()
+            .block_delimiters(delimiters.block.0.clone(), delimiters.block.1.clone())
+            .variable_delimiters(delimiters.variable.0.clone(), delimiters.variable.1.clone())
+            .comment_delimiters(delimiters.comment.0.clone(), delimiters.comment.1.clone())
+            .build()
+            .map_err(|e| {
+                crate::error::ShinkansenError::ValidationError(format!(
+                    "Invalid template delimiters: {}",
+                    e
+                ))
+            })?;
+        env.set_syntax(syntax);
+    }
+
+    // Render booleans as `true`/`false` so generated config files stay valid
+    // regardless of how the MiniJinja version formats them by default
+    env.set_formatter(|out, state, value| {
+        check_deadline(Some(state))?;
+        if value.kind() == ValueKind::Bool {
+            out.write_str(if value.is_true() { "true" } else { "false" })?;
+            Ok(())
+        } else {
+            minijinja::escape_formatter(out, state, value)
+        }
+    });
+
+    crate::filters::register_json(&mut env);
+    crate::filters::register_net(&mut env);
+    crate::filters::register_sorting(&mut env);
+    crate::filters::register_text(&mut env);
+    crate::x509::register_x509(&mut env);
+    if options.k8s {
+        crate::filters::register_k8s(&mut env);
+    }
+    if options.crypto {
+        crate::filters::register_crypto(&mut env);
+    } else {
+        crate::filters::register_crypto_disabled(&mut env);
+    }
+    if !options.template_dirs.is_empty() {
+        let dirs = options.template_dirs.clone();
+        env.set_loader(move |name| crate::sandbox::load_template(&dirs, name));
+    }
+    if let Some(files) = &options.files {
+        crate::sandbox::register_file_functions(&mut env, files);
+    }
+
+    env.add_function("render", render_function);
+
+    // Variables named `run` shadow this global
+    env.add_global(
+        crate::run::RUN_VARIABLE,
+        crate::run::RunContext::current().to_value(),
+    );
+
+    Ok(env)
+}
+
 /// Render a template with the given variables
 pub fn render_template(
     content: &str,
-    variables: &HashMap<String, minijinja::Value>,
+    variables: &HashMap<String, Value>,
     name: &str,
+) -> Result<String> {
+    render_template_with_options(content, variables, name, &RenderOptions::default())
+}
+
+/// Render a template with the given variables and rendering options
+pub fn render_template_with_options(
+    content: &str,
+    variables: &HashMap<String, Value>,
+    name: &str,
+    options: &RenderOptions,
 ) -> Result<String> {
     // Validate template content before processing
     validate_template_content(content)?;
 
-    let mut env = Environment::new();
+    catch_panic(&format!("rendering {}", name), || {
+        render_unchecked(content, variables, name, options)
+    })
+}
 
-    // Configure MiniJinja to treat missing variables as errors
-    env.set_undefined_behavior(minijinja::UndefinedBehavior::Strict);
+fn render_unchecked(
+    content: &str,
+    variables: &HashMap<String, Value>,
+    name: &str,
+    options: &RenderOptions,
+) -> Result<String> {
+    let mut env = create_environment(options)?;
+    if options.profiler.is_some() || options.max_render_time.is_some() {
+        // Practically unlimited fuel, only so the VM counts the instructions it
+        // executes; the remaining level is a signed counter of the platform's width
+        env.set_fuel(Some(isize::MAX as u64));
+    }
 
+    let parse_start = Instant::now();
     env.add_template(name, content)
         .with_context(|| format!("Failed to parse template: {}", name))?;
 
     let template = env
         .get_template(name)
         .with_context(|| format!("Failed to get template: {}", name))?;
+    let parse = parse_start.elapsed();
+
+    let render_start = Instant::now();
+    let deadline = options.max_render_time.map(Deadline::start).transpose()?;
+    let captured = match &options.coverage {
+        Some(coverage) => template.render_captured(coverage.context(name, variables)),
+        None => template.render_captured(Value::from_object(Context::new(variables))),
+    };
+    if let (Err(_), Some(deadline)) = (&captured, &deadline)
+        && deadline.expired()
+    {
+        return Err(deadline.error(name));
+    }
+    drop(deadline);
+    let captured = captured.with_context(|| format!("Failed to render template: {}", name))?;
+    let render = render_start.elapsed();
+    let instructions = captured
+        .state()
+        .fuel_levels()
+        .map_or(0, |(consumed, _)| consumed);
+    let rendered = captured.into_output();
+
+    let postprocess_start = Instant::now();
+    let rendered = match &options.postprocess {
+        Some(command) => postprocess(&rendered, command, name)?,
+        None => rendered,
+    };
+
+    if let Some(profiler) = &options.profiler {
+        profiler.record(TemplateProfile {
+            name: name.to_string(),
+            parse,
+            render,
+            postprocess: postprocess_start.elapsed(),
+            instructions,
+        });
+    }
+
+    Ok(rendered)
+}
+
+thread_local! {
+    /// The time limit of the render running on this thread, if it has one
+    static DEADLINE: RefCell<Option<Arc<DeadlineState>>> = const { RefCell::new(None) };
+}
+
+#[derive(Default)]
+struct DeadlineState {
+    /// Set by the watchdog once the time is up
+    expired: AtomicBool,
+    /// Instructions the render had executed when it noticed
+    instructions: AtomicU64,
+}
+
+/// A time limit on the render running on this thread
+///
+/// MiniJinja cannot be stopped from outside a render, so a watchdog thread
+/// flags the render once the limit has passed, and the render fails at its
+/// next checkpoint: writing a value or looking up a variable. Fuel tracking
+/// counts the instructions it executed until then. Dropping the deadline
+/// stops the watchdog.
+struct Deadline {
+    limit: Duration,
+    state: Arc<DeadlineState>,
+    previous: Option<Arc<DeadlineState>>,
+    _done: mpsc::Sender<()>,
+}
+
+impl Deadline {
+    fn start(limit: Duration) -> Result<Self> {
+        let state = Arc::new(DeadlineState::default());
+        let (done, finished) = mpsc::channel::<()>();
+        let watched = state.clone();
+        std::thread::Builder::new()
+            .name("render-watchdog".to_string())
+            .spawn(move || {
+                // The sender is dropped, and the wait ends early, when the render finishes
+                if let Err(mpsc::RecvTimeoutError::Timeout) = finished.recv_timeout(limit) {
+                    watched.expired.store(true, Ordering::Relaxed);
+                }
+            })
+            .with_context(|| "Failed to start the render watchdog")?;
+        let previous = DEADLINE.replace(Some(state.clone()));
+        Ok(Deadline {
+            limit,
+            state,
+            previous,
+            _done: done,
+        })
+    }
+
+    fn expired(&self) -> bool {
+        self.state.expired.load(Ordering::Relaxed)
+    }
+
+    fn error(&self, name: &str) -> crate::error::ShinkansenError {
+        let instructions = self.state.instructions.load(Ordering::Relaxed);
+        let after = if instructions > 0 {
+            format!(" after {} instructions", instructions)
+        } else {
+            String::new()
+        };
+        crate::error::ShinkansenError::TemplateError(format!(
+            "Rendering {} took longer than {:?} and was cancelled{}",
+            name, self.limit, after
+        ))
+        .with_hint("raise --max-render-time, or max_render_time in a [[render]] profile")
+    }
+}
+
+impl Drop for Deadline {
+    fn drop(&mut self) {
+        DEADLINE.set(self.previous.take());
+    }
+}
+
+/// Fail if the render running on this thread has run out of time
+///
+/// Called from the output formatter and from variable lookups in the render context.
+pub(crate) fn check_deadline(state: Option<&State>) -> std::result::Result<(), Error> {
+    DEADLINE.with_borrow(|deadline| match deadline {
+        Some(deadline) if deadline.expired.load(Ordering::Relaxed) => {
+            if let Some((consumed, _)) = state.and_then(State::fuel_levels) {
+                deadline.instructions.store(consumed, Ordering::Relaxed);
+            }
+            Err(Error::new(
+                ErrorKind::InvalidOperation,
+                "the render ran out of time",
+            ))
+        }
+        _ => Ok(()),
+    })
+}
+
+/// Deepest nesting of `render()` calls, so a template rendering itself fails instead of overflowing
+const MAX_RENDER_DEPTH: usize = 32;
+
+thread_local! {
+    static RENDER_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// `render(name, vars, **kwargs)`: render another template and embed the result
+///
+/// The template is found like `include` (`--template-dir`) and sees the
+/// variables of the caller, overridden by the `vars` mapping if given and then
+/// by any keyword arguments. The caller's variables are left untouched.
+fn render_function(
+    state: &State,
+    name: &str,
+    vars: Option<Value>,
+    kwargs: Kwargs,
+) -> std::result::Result<Value, Error> {
+    let template = state.get_template(name)?;
+    let mut context: BTreeMap<String, Value> = state
+        .known_variables()
+        .iter()
+        .filter_map(|variable| Some((variable.to_string(), state.lookup(variable)?)))
+        .collect();
+    if let Some(vars) = vars {
+        if vars.kind() != ValueKind::Map {
+            return Err(Error::new(
+                ErrorKind::InvalidOperation,
+                format!(
+                    "render() expects a mapping of variables, got {}",
+                    vars.kind()
+                ),
+            ));
+        }
+        for key in vars.try_iter()? {
+            let value = vars.get_item(&key)?;
+            context.insert(key.to_string(), value);
+        }
+    }
+    for key in kwargs.args() {
+        context.insert(key.to_string(), kwargs.get::<Value>(key)?);
+    }
+
+    let depth = RENDER_DEPTH.get();
+    if depth >= MAX_RENDER_DEPTH {
+        return Err(Error::new(
+            ErrorKind::InvalidOperation,
+            format!(
+                "render() nested more than {} deep at '{}'",
+                MAX_RENDER_DEPTH, name
+            ),
+        ));
+    }
+    RENDER_DEPTH.set(depth + 1);
+    let rendered = template.render(context);
+    RENDER_DEPTH.set(depth);
+    Ok(Value::from_safe_string(rendered?))
+}
+
+/// Pipe rendered output through a shell command and return what it prints
+fn postprocess(content: &str, command: &str, name: &str) -> Result<String> {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let mut child = crate::verify::shell_command(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run postprocess command: {}", command))?;
+
+    // Feed stdin from a separate thread so large outputs cannot deadlock on full pipes
+    let mut stdin = child.stdin.take();
+    let input = content.to_string();
+    let writer = std::thread::spawn(move || {
+        if let Some(stdin) = stdin.as_mut() {
+            let _ = stdin.write_all(input.as_bytes());
+        }
+    });
+
+    let output = child
+        .wait_with_output()
+        .with_context(|| format!("Failed to run postprocess command: {}", command))?;
+    let _ = writer.join();
+
+    if !output.status.success() {
+        return Err(crate::error::ShinkansenError::ValidationError(format!(
+            "Postprocess command '{}' failed for {} ({}): {}",
+            command,
+            name,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
 
-    template
-        .render(variables)
-        .with_context(|| format!("Failed to render template: {}", name))
+    String::from_utf8(output.stdout).map_err(|_| {
+        crate::error::ShinkansenError::ValidationError(format!(
+            "Postprocess command '{}' produced invalid UTF-8 for {}",
+            command, name
+        ))
+    })
 }
@@ -0,0 +1,125 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+use crate::cli::Cli;
+use crate::error::{Result, ShinkansenError};
+
+/// Delay before the first retry when --retry-delay is not given
+pub const DEFAULT_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Upper bound for the delay between two attempts
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Retry policy for network-backed sources
+///
+/// Failed attempts are retried with exponential backoff (`delay`, `2 * delay`,
+/// `4 * delay`, ... capped at 30s) and equal jitter, so concurrent runs do not
+/// hammer a recovering service in lockstep.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Number of retries after the first attempt
+    pub retries: u32,
+    /// Base delay before the first retry
+    pub delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            retries: 0,
+            delay: DEFAULT_RETRY_DELAY,
+        }
+    }
+}
+
+/// Outcome of a failed attempt
+#[derive(Debug)]
+pub enum Failure {
+    /// Worth retrying (timeouts, connection errors, 5xx responses, ...)
+    Transient(ShinkansenError),
+    /// Retrying cannot help (404, invalid credentials, parse errors, ...)
+    Permanent(ShinkansenError),
+}
+
+impl From<ShinkansenError> for Failure {
+    fn from(error: ShinkansenError) -> Self {
+        Failure::Transient(error)
+    }
+}
+
+impl RetryPolicy {
+    /// Build the policy described by `--retries` and `--retry-delay`
+    pub fn from_cli(cli: &Cli) -> Self {
+        RetryPolicy {
+            retries: cli.retries,
+            delay: cli.retry_delay.unwrap_or(DEFAULT_RETRY_DELAY),
+        }
+    }
+
+    /// Run an operation, retrying transient failures
+    ///
+    /// When every attempt fails the error lists each attempt's failure so the
+    /// cause is visible even if it changed between attempts.
+    pub fn run<T, F>(&self, source: &str, mut operation: F) -> Result<T>
+    where
+        F: FnMut() -> std::result::Result<T, Failure>,
+    {
+        let mut failures = Vec::new();
+
+        for attempt in 0..=self.retries {
+            if attempt > 0 {
+                std::thread::sleep(self.backoff(attempt));
+            }
+
+            match operation() {
+                Ok(value) => return Ok(value),
+                Err(Failure::Permanent(error)) if failures.is_empty() => return Err(error),
+                Err(Failure::Permanent(error)) => {
+                    failures.push(error);
+                    break;
+                }
+                Err(Failure::Transient(error)) => failures.push(error),
+            }
+        }
+
+        if failures.len() == 1 {
+            return Err(failures.remove(0));
+        }
+
+        let mut message = format!("{} failed after {} attempts:", source, failures.len());
+        for (index, error) in failures.iter().enumerate() {
+            message.push_str(&format!("\n  attempt {}: {}", index + 1, error));
+        }
+        Err(ShinkansenError::DataSourceError(message))
+    }
+
+    /// Delay before the given retry (1-based), with equal jitter applied
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let exponential = self
+            .delay
+            .saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)))
+            .min(MAX_RETRY_DELAY);
+        let half = exponential / 2;
+        let jitter_millis = half.as_millis() as u64;
+        let jitter = if jitter_millis == 0 {
+            Duration::ZERO
+        } else {
+            Duration::from_millis(random() % (jitter_millis + 1))
+        };
+        half + jitter
+    }
+}
+
+/// Retry an operation whose failures are all considered transient
+pub fn retry<T, F>(policy: &RetryPolicy, source: &str, mut operation: F) -> Result<T>
+where
+    F: FnMut() -> Result<T>,
+{
+    policy.run(source, || operation().map_err(Failure::from))
+}
+
+/// A per-call random number, good enough for jitter without an RNG dependency
+fn random() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
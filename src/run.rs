@@ -0,0 +1,156 @@
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use minijinja::Value;
+use minijinja::value::ValueKind;
+use sha2::{Digest, Sha256};
+
+/// Name of the global holding the run context in templates
+pub const RUN_VARIABLE: &str = "run";
+
+/// Environment variable fixing `run.started_at` for reproducible builds
+const SOURCE_DATE_EPOCH: &str = "SOURCE_DATE_EPOCH";
+
+/// Hex digits kept for `run.id`
+const RUN_ID_LENGTH: usize = 16;
+
+/// Facts about the current invocation, shared by every file it renders (`{{ run.id }}`)
+///
+/// Fields can be pinned for deterministic output with variables such as
+/// `-D run.id=fixed`; `started_at` also honors `SOURCE_DATE_EPOCH`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunContext {
+    /// Random identifier of this run
+    pub id: String,
+    /// Start of the run as an RFC 3339 UTC timestamp
+    pub started_at: String,
+    /// Start of the run in seconds since the Unix epoch
+    pub started_at_epoch: u64,
+    pub user: String,
+    pub hostname: String,
+}
+
+impl RunContext {
+    /// The context of this process, created on first use
+    pub fn current() -> &'static RunContext {
+        static CURRENT: OnceLock<RunContext> = OnceLock::new();
+        CURRENT.get_or_init(RunContext::detect)
+    }
+
+    fn detect() -> Self {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let started_at = std::env::var(SOURCE_DATE_EPOCH)
+            .ok()
+            .and_then(|epoch| epoch.trim().parse::<u64>().ok())
+            .unwrap_or(now.as_secs());
+        let hostname = hostname();
+
+        let mut hasher = Sha256::new();
+        hasher.update(now.as_nanos().to_le_bytes());
+        hasher.update(std::process::id().to_le_bytes());
+        hasher.update(hostname.as_bytes());
+        let digest = hasher.finalize();
+        let id: String = digest.iter().map(|byte| format!("{:02x}", byte)).collect();
+
+        RunContext {
+            id: id[..RUN_ID_LENGTH].to_string(),
+            started_at: format_timestamp(started_at),
+            started_at_epoch: started_at,
+            user: user(),
+            hostname,
+        }
+    }
+
+    /// The context as a template value
+    pub fn to_value(&self) -> Value {
+        Value::from(self.fields())
+    }
+
+    /// Fill the fields missing from a partially overridden `run` map
+    ///
+    /// Values that are not maps are returned unchanged.
+    pub fn complete(&self, run: &Value) -> Value {
+        if run.kind() != ValueKind::Map {
+            return run.clone();
+        }
+        let mut fields = self.fields();
+        if let Ok(keys) = run.try_iter() {
+            for key in keys {
+                if let (Some(name), Ok(value)) = (key.as_str(), run.get_item(&key)) {
+                    fields.insert(name.to_string(), value);
+                }
+            }
+        }
+        Value::from(fields)
+    }
+
+    fn fields(&self) -> BTreeMap<String, Value> {
+        BTreeMap::from([
+            ("id".to_string(), Value::from(self.id.clone())),
+            (
+                "started_at".to_string(),
+                Value::from(self.started_at.clone()),
+            ),
+            ("user".to_string(), Value::from(self.user.clone())),
+            ("hostname".to_string(), Value::from(self.hostname.clone())),
+        ])
+    }
+}
+
+fn user() -> String {
+    ["USER", "USERNAME", "LOGNAME"]
+        .iter()
+        .find_map(|name| std::env::var(name).ok().filter(|value| !value.is_empty()))
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+fn hostname() -> String {
+    let from_env = ["COMPUTERNAME", "HOSTNAME"]
+        .iter()
+        .find_map(|name| std::env::var(name).ok());
+    let from_file = || {
+        ["/proc/sys/kernel/hostname", "/etc/hostname"]
+            .iter()
+            .find_map(|path| std::fs::read_to_string(path).ok())
+    };
+    from_env
+        .or_else(from_file)
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Format seconds since the Unix epoch as `YYYY-MM-DDTHH:MM:SSZ`
+pub fn format_timestamp(seconds: u64) -> String {
+    let days = (seconds / 86_400) as i64;
+    let time = seconds % 86_400;
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3_600,
+        time % 3_600 / 60,
+        time % 60
+    )
+}
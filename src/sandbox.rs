@@ -0,0 +1,219 @@
+use std::path::{Component, Path, PathBuf};
+
+use globset::GlobBuilder;
+use minijinja::{Environment, Error, ErrorKind};
+use sha2::{Digest, Sha256};
+
+use crate::platform::{glob_input_base, normalize_path, split_glob_base};
+
+/// Directories template functions such as `filehash` and `glob` may read
+///
+/// Relative paths are resolved against `root`, the template root: the input
+/// directory a template was found in, the directory of a single input file, or
+/// the working directory for stdin. `root` and every `--allow-dir` directory
+/// are readable; symlinks are resolved before the check, so they cannot be used
+/// to escape.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileAccess {
+    pub root: PathBuf,
+    /// Further readable directories (`--allow-dir`)
+    pub allowed: Vec<PathBuf>,
+}
+
+impl FileAccess {
+    pub fn new(root: impl Into<PathBuf>, allowed: &[PathBuf]) -> Self {
+        FileAccess {
+            root: root.into(),
+            allowed: allowed.to_vec(),
+        }
+    }
+
+    /// The same allowed directories with a different template root
+    pub fn with_root(&self, root: impl Into<PathBuf>) -> Self {
+        FileAccess {
+            root: root.into(),
+            allowed: self.allowed.clone(),
+        }
+    }
+
+    /// Resolve `path` and make sure it lies inside an allowed directory
+    pub fn resolve(&self, path: &str) -> Result<PathBuf, Error> {
+        let resolved = self.root.join(path).canonicalize().map_err(|e| {
+            Error::new(
+                ErrorKind::InvalidOperation,
+                format!("cannot read '{}': {}", path, e),
+            )
+        })?;
+        let inside = std::iter::once(&self.root)
+            .chain(&self.allowed)
+            .filter_map(|dir| dir.canonicalize().ok())
+            .any(|dir| resolved.starts_with(dir));
+        if !inside {
+            return Err(Error::new(
+                ErrorKind::InvalidOperation,
+                format!(
+                    "'{}' is outside the template root and --allow-dir directories",
+                    path
+                ),
+            ));
+        }
+        Ok(resolved)
+    }
+}
+
+/// Register the functions that read files through `access`
+///
+/// - `filehash("config/app.conf")` returns the hex sha256 of a file's contents
+/// - `glob("static/**/*.css")` lists the matching files, sorted
+/// - `listdir("static")` lists the names in a directory, sorted
+pub fn register_file_functions(env: &mut Environment<'_>, access: &FileAccess) {
+    let hash_access = access.clone();
+    env.add_function("filehash", move |path: &str| filehash(&hash_access, path));
+    let glob_access = access.clone();
+    env.add_function("glob", move |pattern: &str| glob(&glob_access, pattern));
+    let list_access = access.clone();
+    env.add_function("listdir", move |path: Option<&str>| {
+        listdir(&list_access, path.unwrap_or("."))
+    });
+}
+
+fn filehash(access: &FileAccess, path: &str) -> Result<String, Error> {
+    let resolved = access.resolve(path)?;
+    let mut file = std::fs::File::open(&resolved).map_err(|e| read_error(path, e))?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher).map_err(|e| read_error(path, e))?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Files matching `pattern`, as paths relative to the template root with `/` separators
+///
+/// `*` and `?` stay within a path component; `**` crosses directories. The
+/// directory before the first wildcard must be readable; the files found are
+/// not read, and symlinked directories are not followed.
+fn glob(access: &FileAccess, pattern: &str) -> Result<Vec<String>, Error> {
+    let (base, rest) = split_glob_base(pattern);
+    let matcher = GlobBuilder::new(&rest)
+        .literal_separator(true)
+        .build()
+        .map_err(|e| {
+            Error::new(
+                ErrorKind::InvalidOperation,
+                format!("invalid glob '{}': {}", pattern, e),
+            )
+        })?
+        .compile_matcher();
+    let dir = access.resolve(if base.is_empty() { "." } else { &base })?;
+
+    let mut files: Vec<String> = walkdir::WalkDir::new(&dir)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| {
+            let relative = entry.path().strip_prefix(&dir).ok()?.to_path_buf();
+            matcher.is_match(&relative).then(|| {
+                let relative = slash_path(&relative);
+                if base.is_empty() {
+                    relative
+                } else {
+                    format!("{}/{}", base, relative)
+                }
+            })
+        })
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Names of the entries of a directory, sorted
+fn listdir(access: &FileAccess, path: &str) -> Result<Vec<String>, Error> {
+    let dir = access.resolve(path)?;
+    let entries = std::fs::read_dir(&dir).map_err(|e| read_error(path, e))?;
+    let mut names = entries
+        .map(|entry| entry.map(|entry| entry.file_name().to_string_lossy().into_owned()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| read_error(path, e))?;
+    names.sort();
+    Ok(names)
+}
+
+fn slash_path(path: &Path) -> String {
+    path.components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn read_error(path: &str, err: std::io::Error) -> Error {
+    Error::new(
+        ErrorKind::InvalidOperation,
+        format!("cannot read '{}': {}", path, err),
+    )
+}
+
+/// Find template `name` (from `include`, `extends` or `import`) in the first
+/// of `dirs` that has it (`--template-dir`)
+///
+/// Names are `/`-separated paths relative to a search directory; `..` and
+/// absolute names are refused, as are files reached through a symlink leading
+/// out of the directory. `Ok(None)` lets MiniJinja report the missing template.
+pub fn load_template(dirs: &[PathBuf], name: &str) -> Result<Option<String>, Error> {
+    let segments: Vec<&str> = name.split('/').filter(|s| !s.is_empty()).collect();
+    if name.starts_with('/')
+        || segments
+            .iter()
+            .any(|segment| *segment == ".." || segment.contains('\\') || segment.contains(':'))
+    {
+        return Err(Error::new(
+            ErrorKind::InvalidOperation,
+            format!(
+                "template name '{}' must be a relative path inside --template-dir",
+                name
+            ),
+        ));
+    }
+
+    for dir in dirs {
+        let candidate = segments
+            .iter()
+            .fold(dir.clone(), |path, segment| path.join(segment));
+        if !candidate.is_file() {
+            continue;
+        }
+        let (Ok(resolved), Ok(root)) = (candidate.canonicalize(), dir.canonicalize()) else {
+            continue;
+        };
+        if !resolved.starts_with(&root) {
+            return Err(Error::new(
+                ErrorKind::InvalidOperation,
+                format!(
+                    "template '{}' in {} links outside the template directory",
+                    name,
+                    dir.display()
+                ),
+            ));
+        }
+        return std::fs::read_to_string(&resolved)
+            .map(Some)
+            .map_err(|e| read_error(name, e));
+    }
+    Ok(None)
+}
+
+/// The template root for `input`: the input directory or glob base containing it,
+/// or its parent
+pub fn template_root(inputs: &[String], input: &Path) -> PathBuf {
+    inputs
+        .iter()
+        .filter_map(|dir| match glob_input_base(dir) {
+            Some(base) => Some(base),
+            None => Some(normalize_path(Path::new(dir))).filter(|dir| dir.is_dir()),
+        })
+        .filter(|dir| input.starts_with(dir))
+        .max_by_key(|dir| dir.components().count())
+        .or_else(|| input.parent().map(Path::to_path_buf))
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or_else(|| PathBuf::from("."))
+}
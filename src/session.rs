@@ -0,0 +1,785 @@
+//! Recording a run into a bundle and replaying it (`--record`, `--replay`)
+//!
+//! A bundle is a tar archive (optionally `.tar.gz` or `.tar.zst`) holding:
+//! - `session.json`: the command line, the shinkansen version, the recorded
+//!   input and output paths, the redacted variables and any error
+//! - `context.json`: the merged variables, including the `run` context, with
+//!   secrets replaced by [`REDACTED`]
+//! - `inputs/`: every template the run read, and `outputs/`: every output it
+//!   wrote, with the values of secrets replaced
+//! - `shinkansen.toml` and `per-file.yaml`: the project config without its
+//!   hooks and the redacted per-file variables, when the run used them
+//!
+//! Replaying renders the recorded templates with the recorded variables and
+//! options and compares the outputs with the recorded ones. No source is
+//! fetched and nothing is written.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+
+use clap::Parser;
+use serde::{Deserialize, Serialize};
+
+use crate::backend::{ArchiveBackend, OutputBackend, OutputMeta, default_backend};
+use crate::cli::Cli;
+use crate::diagnostics::Diagnostics;
+use crate::error::{ContextExt, Result, ShinkansenError};
+use crate::fileio::{Compression, IoOptions};
+use crate::input::{ArchiveSource, InputFiles, InputSource, STDIN_PATH, default_source};
+use crate::per_file::PerFileVars;
+use crate::processing::process_inputs_from;
+use crate::project::ProjectConfig;
+use crate::provenance::VariableSource;
+use crate::run::{RUN_VARIABLE, RunContext};
+use crate::variables::{
+    VariableContext, json_to_minijinja_value, split_assignment, split_unescaped,
+};
+
+/// The value recorded in place of a secret
+pub const REDACTED: &str = "<redacted>";
+
+/// Parts of variable and key names that mark a value as a secret
+const SECRET_NAMES: [&str; 8] = [
+    "password",
+    "passwd",
+    "secret",
+    "token",
+    "api_key",
+    "apikey",
+    "private_key",
+    "credential",
+];
+
+const SESSION_FILE: &str = "session.json";
+const CONTEXT_FILE: &str = "context.json";
+const PROJECT_CONFIG_FILE: &str = "shinkansen.toml";
+const PER_FILE_VARS_FILE: &str = "per-file.yaml";
+
+/// The description of a recorded run (`session.json`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    /// Version of shinkansen that made the recording
+    pub version: String,
+    /// The command line, without `--record`
+    pub args: Vec<String>,
+    /// Templates read, in order, with their bundle members
+    pub inputs: Vec<RecordedFile>,
+    /// Outputs written, in order, with their bundle members
+    pub outputs: Vec<RecordedFile>,
+    /// Variables and keys whose values were replaced by [`REDACTED`]
+    pub redacted: Vec<String>,
+    /// The error the run failed with, if it did
+    pub error: Option<String>,
+}
+
+/// A file of the run and where its content is stored in the bundle
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedFile {
+    pub path: String,
+    pub member: String,
+}
+
+/// Process the inputs like [`crate::processing::process_inputs`] and record the run in `bundle`
+///
+/// `args` is the command line the run was started with. The bundle is
+/// written even when processing fails, so failing runs can be replayed too.
+pub fn record(cli: &Cli, context: &VariableContext, args: &[String], bundle: &Path) -> Result<()> {
+    let per_file = match &cli.per_file_vars {
+        Some(path) => PerFileVars::read(path)?,
+        None => PerFileVars::new(),
+    };
+    let source = RecordingSource::new(default_source(cli, &context.diagnostics)?);
+    let backend = RecordingBackend::new(default_backend(cli));
+    let result = process_inputs_from(
+        cli,
+        &context.values,
+        &per_file,
+        &source,
+        &backend,
+        &context.diagnostics,
+    );
+
+    let archive = ArchiveBackend::new();
+    let mut add = |member: &str, content: &[u8]| {
+        let meta = OutputMeta {
+            input: PathBuf::from(member),
+            compression: None,
+        };
+        archive.write(Path::new(member), content, &meta)
+    };
+
+    let mut redactions = Redactions::default();
+    let variables = recorded_context(context, &mut redactions)?;
+    add(
+        CONTEXT_FILE,
+        serde_json::to_string_pretty(&variables)?.as_bytes(),
+    )?;
+    if let Some(path) = &cli.per_file_vars {
+        let content = recorded_per_file_vars(path, context, &mut redactions)?;
+        add(PER_FILE_VARS_FILE, &content)?;
+    }
+    if let Some(path) = ProjectConfig::path_from_cli(cli)? {
+        let content = std::fs::read(&path)
+            .with_context(|| format!("Failed to read project config: {:?}", path))?;
+        add(
+            PROJECT_CONFIG_FILE,
+            &redactions.scrub(without_hooks(&content)?.as_bytes()),
+        )?;
+    }
+    let inputs = add_files("inputs", source.into_files(), &mut add)?;
+    let outputs = backend
+        .into_files()
+        .into_iter()
+        .map(|(path, content, compression)| {
+            Ok((path, redactions.scrub_output(&content, compression)?))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let outputs = add_files("outputs", outputs, &mut add)?;
+
+    let session = Session {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        args: recorded_args(args, context),
+        inputs,
+        outputs,
+        error: result.as_ref().err().map(|err| {
+            String::from_utf8_lossy(&redactions.scrub(err.to_string().as_bytes())).into_owned()
+        }),
+        redacted: redactions.paths,
+    };
+    add(
+        SESSION_FILE,
+        serde_json::to_string_pretty(&session)?.as_bytes(),
+    )?;
+
+    let io = IoOptions {
+        compression: Compression::from_path(bundle),
+        ..IoOptions::default()
+    };
+    let written = crate::fileio::write_file(bundle, &archive.finish(), &io)
+        .with_context(|| format!("Failed to write session bundle {:?}", bundle));
+    result.and(written)
+}
+
+/// Options whose values are `KEY=VALUE` assignments, redacted like the context
+const ASSIGNMENT_OPTIONS: [&str; 5] =
+    ["-D", "--define", "--set-string", "--set-json", "--set-file"];
+
+/// The command line without `--record BUNDLE`, with secret assignments redacted
+fn recorded_args(args: &[String], context: &VariableContext) -> Vec<String> {
+    let mut recorded = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--record" {
+            args.next();
+        } else if arg.starts_with("--record=") {
+            continue;
+        } else if ASSIGNMENT_OPTIONS.contains(&arg.as_str()) {
+            recorded.push(arg.clone());
+            if let Some(value) = args.next() {
+                recorded.push(redact_assignments(value, context));
+            }
+        } else if let Some((option, value)) = arg.split_once('=')
+            && option.starts_with("--")
+            && ASSIGNMENT_OPTIONS.contains(&option)
+        {
+            recorded.push(format!("{}={}", option, redact_assignments(value, context)));
+        } else if let Some(value) = arg.strip_prefix("-D")
+            && !value.is_empty()
+        {
+            recorded.push(format!("-D{}", redact_assignments(value, context)));
+        } else {
+            recorded.push(arg.clone());
+        }
+    }
+    recorded
+}
+
+/// Replace the values of secret variables in comma-separated `KEY=VALUE` assignments
+fn redact_assignments(value: &str, context: &VariableContext) -> String {
+    split_unescaped(value, ',')
+        .into_iter()
+        .map(
+            |assignment| match split_assignment(assignment, "KEY=VALUE") {
+                Ok((key, _)) if is_secret_key(context, key) => format!("{}={}", key, REDACTED),
+                _ => assignment.to_string(),
+            },
+        )
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Whether a dotted key such as `db.password` sets a secret
+fn is_secret_key(context: &VariableContext, key: &str) -> bool {
+    let name = key.split(['.', '[']).next().unwrap_or(key);
+    is_secret(context, name) || is_secret_name(key)
+}
+
+/// Store `files` below `directory` in the bundle and list where each went
+fn add_files(
+    directory: &str,
+    files: Vec<(PathBuf, Vec<u8>)>,
+    add: &mut impl FnMut(&str, &[u8]) -> Result<()>,
+) -> Result<Vec<RecordedFile>> {
+    let mut recorded = Vec::new();
+    for (index, (path, content)) in files.into_iter().enumerate() {
+        // Numbered, so paths that normalize alike (`/a`, `a`, `../a`) stay apart
+        let name: Vec<String> = path
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(part) => Some(part.to_string_lossy().into_owned()),
+                _ => None,
+            })
+            .collect();
+        let member = format!("{}/{}/{}", directory, index, name.join("/"));
+        add(&member, &content)?;
+        recorded.push(RecordedFile {
+            path: path.to_string_lossy().into_owned(),
+            member,
+        });
+    }
+    Ok(recorded)
+}
+
+/// The variables to record, with the `run` context pinned and secrets redacted
+fn recorded_context(
+    context: &VariableContext,
+    redactions: &mut Redactions,
+) -> Result<serde_json::Map<String, serde_json::Value>> {
+    // Deferred sources the run never read are left out rather than fetched now
+    let resolved: HashMap<String, minijinja::Value> = context
+        .values
+        .iter()
+        .filter(|(_, value)| crate::context::is_resolved(value))
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect();
+    let mut values = crate::context::resolve_all(&resolved)?;
+    let run = match values.get(RUN_VARIABLE) {
+        Some(run) => RunContext::current().complete(run),
+        None => RunContext::current().to_value(),
+    };
+    values.insert(RUN_VARIABLE.to_string(), run);
+
+    let mut variables = serde_json::Map::new();
+    let names: BTreeMap<&String, &minijinja::Value> = values.iter().collect();
+    for (name, value) in names {
+        let value = serde_json::to_value(value)?;
+        let value = if is_secret(context, name) {
+            redactions.replace(name.clone(), value)
+        } else {
+            redactions.redact(value, name)
+        };
+        variables.insert(name.clone(), value);
+    }
+    Ok(variables)
+}
+
+/// The `--per-file-vars` file to record, as JSON with secret variables and keys redacted
+fn recorded_per_file_vars(
+    path: &Path,
+    context: &VariableContext,
+    redactions: &mut Redactions,
+) -> Result<Vec<u8>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read per-file variables {:?}", path))?;
+    let mut entries: BTreeMap<String, serde_json::Value> =
+        serde_yaml::from_str(&content).map_err(|e| {
+            ShinkansenError::ConfigParseError(format!(
+                "Invalid per-file variables {:?}: {}",
+                path, e
+            ))
+        })?;
+    for (input, entry) in &mut entries {
+        let renders = match entry {
+            serde_json::Value::Array(renders) => renders.iter_mut().collect(),
+            render => vec![render],
+        };
+        for render in renders {
+            let Some(serde_json::Value::Object(vars)) = render.get_mut("vars") else {
+                continue;
+            };
+            for (name, value) in vars.iter_mut() {
+                let path = format!("{}:{}", input, name);
+                *value = if is_secret(context, name) {
+                    redactions.replace(path, value.take())
+                } else {
+                    redactions.redact(value.take(), &path)
+                };
+            }
+        }
+    }
+    Ok(serde_json::to_vec_pretty(&entries)?)
+}
+
+/// Whether the whole variable `name` is a secret: declared with `secret = true`
+/// in `_meta`, read from a Kubernetes Secret or named like one
+pub(crate) fn is_secret(context: &VariableContext, name: &str) -> bool {
+    let declared = context.meta.get(name).is_some_and(|meta| meta.secret);
+    let from_secret = matches!(
+        context.provenance.winner(name),
+        Some(VariableSource::Source(label)) if label.starts_with("--k8s-secret")
+    );
+    declared || from_secret || is_secret_name(name)
+}
+
+/// Whether a variable or key name looks like it holds a secret
+pub(crate) fn is_secret_name(name: &str) -> bool {
+    let name = name.to_lowercase().replace('-', "_");
+    SECRET_NAMES.iter().any(|secret| name.contains(secret))
+}
+
+/// The secrets replaced while recording a run
+#[derive(Default)]
+struct Redactions {
+    /// Variables and keys whose values were replaced
+    paths: Vec<String>,
+    /// The replaced values, also scrubbed from the outputs stored in the bundle
+    values: Vec<String>,
+}
+
+impl Redactions {
+    /// Record the secret `value` at `path` and return what replaces it
+    fn replace(&mut self, path: String, value: serde_json::Value) -> serde_json::Value {
+        self.paths.push(path);
+        self.add_values(value);
+        serde_json::Value::from(REDACTED)
+    }
+
+    fn add_values(&mut self, value: serde_json::Value) {
+        match value {
+            serde_json::Value::String(value) if !value.is_empty() => self.values.push(value),
+            serde_json::Value::Number(number) => self.values.push(number.to_string()),
+            serde_json::Value::Array(items) => {
+                items.into_iter().for_each(|item| self.add_values(item))
+            }
+            serde_json::Value::Object(map) => map
+                .into_iter()
+                .for_each(|(_, value)| self.add_values(value)),
+            _ => {}
+        }
+    }
+
+    /// Replace the values of secret-looking keys anywhere inside `value`
+    fn redact(&mut self, value: serde_json::Value, path: &str) -> serde_json::Value {
+        match value {
+            serde_json::Value::Object(map) => map
+                .into_iter()
+                .map(|(key, value)| {
+                    let path = format!("{}.{}", path, key);
+                    let value = if is_secret_name(&key) {
+                        self.replace(path, value)
+                    } else {
+                        self.redact(value, &path)
+                    };
+                    (key, value)
+                })
+                .collect(),
+            serde_json::Value::Array(items) => items
+                .into_iter()
+                .enumerate()
+                .map(|(index, item)| self.redact(item, &format!("{}[{}]", path, index)))
+                .collect(),
+            value => value,
+        }
+    }
+
+    /// `content` with every replaced value in it replaced by [`REDACTED`] too
+    ///
+    /// Values a template transformed (encoded, hashed, ...) are not recognized.
+    fn scrub(&self, content: &[u8]) -> Vec<u8> {
+        let mut values: Vec<&[u8]> = self.values.iter().map(String::as_bytes).collect();
+        // Longer values first, so a secret containing another is replaced whole
+        values.sort_by_key(|value| std::cmp::Reverse(value.len()));
+        values.dedup();
+        let mut content = content.to_vec();
+        for value in values {
+            let mut scrubbed = Vec::with_capacity(content.len());
+            let mut rest = content.as_slice();
+            while let Some(index) = rest.windows(value.len()).position(|window| window == value) {
+                scrubbed.extend_from_slice(&rest[..index]);
+                scrubbed.extend_from_slice(REDACTED.as_bytes());
+                rest = &rest[index + value.len()..];
+            }
+            scrubbed.extend_from_slice(rest);
+            content = scrubbed;
+        }
+        content
+    }
+
+    /// Scrub an output written `compression`-compressed, keeping it compressed
+    fn scrub_output(&self, content: &[u8], compression: Option<Compression>) -> Result<Vec<u8>> {
+        let Some(compression) = compression else {
+            return Ok(self.scrub(content));
+        };
+        let content = compression
+            .decompress(content)
+            .with_context(|| "Failed to decompress an output to record")?;
+        compression
+            .compress(&self.scrub(&content))
+            .with_context(|| "Failed to compress an output to record")
+    }
+}
+
+/// Lists and reads templates through another source, keeping what was read
+struct RecordingSource<'a> {
+    inner: Box<dyn InputSource + 'a>,
+    read: Mutex<Vec<(PathBuf, Vec<u8>)>>,
+}
+
+impl<'a> RecordingSource<'a> {
+    fn new(inner: Box<dyn InputSource + 'a>) -> Self {
+        RecordingSource {
+            inner,
+            read: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn into_files(self) -> Vec<(PathBuf, Vec<u8>)> {
+        self.read
+            .into_inner()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl InputSource for RecordingSource<'_> {
+    fn files(&self) -> Result<InputFiles<'_>> {
+        self.inner.files()
+    }
+
+    fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        let content = self.inner.read(path)?;
+        let mut read = self
+            .read
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        // Listing a plan and rendering read the same template twice
+        if !read.iter().any(|(recorded, _)| recorded == path) {
+            read.push((path.to_path_buf(), content.clone()));
+        }
+        Ok(content)
+    }
+}
+
+/// An output path, its content and the compression the content is in
+type WrittenOutput = (PathBuf, Vec<u8>, Option<Compression>);
+
+/// Stores outputs through another backend, keeping what was written
+struct RecordingBackend {
+    inner: Box<dyn OutputBackend>,
+    written: Mutex<Vec<WrittenOutput>>,
+}
+
+impl RecordingBackend {
+    fn new(inner: Box<dyn OutputBackend>) -> Self {
+        RecordingBackend {
+            inner,
+            written: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn into_files(self) -> Vec<WrittenOutput> {
+        self.written
+            .into_inner()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl OutputBackend for RecordingBackend {
+    fn write(&self, path: &Path, content: &[u8], meta: &OutputMeta) -> Result<()> {
+        self.inner.write(path, content, meta)?;
+        self.written
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push((path.to_path_buf(), content.to_vec(), meta.compression));
+        Ok(())
+    }
+}
+
+/// How a replayed output compares with the recorded one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayOutcome {
+    Same,
+    Changed,
+    /// Recorded but not produced by the replay
+    Missing,
+    /// Produced by the replay but not recorded
+    New,
+}
+
+impl std::fmt::Display for ReplayOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplayOutcome::Same => write!(f, "same"),
+            ReplayOutcome::Changed => write!(f, "changed"),
+            ReplayOutcome::Missing => write!(f, "missing"),
+            ReplayOutcome::New => write!(f, "new"),
+        }
+    }
+}
+
+/// The outcome of `--replay`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayReport {
+    pub session: Session,
+    /// Every recorded or replayed output, in recorded order
+    pub outputs: Vec<(String, ReplayOutcome)>,
+    /// The error the replay failed with, if it did
+    pub error: Option<String>,
+    /// Unified diffs of changed text outputs, when asked for with `--diff`
+    pub diffs: Vec<String>,
+}
+
+impl ReplayReport {
+    /// Whether the replay did not reproduce the recorded outputs and error
+    pub fn has_differences(&self) -> bool {
+        self.error != self.session.error
+            || self
+                .outputs
+                .iter()
+                .any(|(_, outcome)| *outcome != ReplayOutcome::Same)
+    }
+
+    /// One line per differing output, any errors and a summary
+    ///
+    /// ```text
+    ///   changed  out/app.conf
+    ///   missing  out/old.conf
+    /// redacted: db.password
+    /// 1 of 3 output(s) match the recording of shinkansen 0.2.0 templates/ -r -o out/
+    /// ```
+    pub fn report(&self) -> String {
+        let mut output = String::new();
+        for diff in &self.diffs {
+            output.push_str(diff);
+        }
+        for (path, outcome) in &self.outputs {
+            if *outcome != ReplayOutcome::Same {
+                output.push_str(&format!("  {:<7}  {}\n", outcome, path));
+            }
+        }
+        if !self.session.redacted.is_empty() {
+            output.push_str(&format!("redacted: {}\n", self.session.redacted.join(", ")));
+        }
+        if let Some(error) = &self.session.error {
+            output.push_str(&format!("recorded error: {}\n", error));
+        }
+        if let Some(error) = &self.error {
+            output.push_str(&format!("replay error: {}\n", error));
+        }
+        let same = self
+            .outputs
+            .iter()
+            .filter(|(_, outcome)| *outcome == ReplayOutcome::Same)
+            .count();
+        output.push_str(&format!(
+            "{} of {} output(s) match the recording of shinkansen {} {}\n",
+            same,
+            self.outputs.len(),
+            self.session.version,
+            self.session
+                .args
+                .iter()
+                .skip(1)
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(" ")
+        ));
+        output
+    }
+}
+
+/// Re-render the run recorded in `bundle` and compare its outputs with the recorded ones
+///
+/// Options that read or change files besides the outputs (`--check`,
+/// `--in-place` backups, manifests, releases, hooks, ...) are turned off.
+/// With `cli.diff`, the report includes diffs of changed outputs.
+pub fn replay(bundle: &Path, cli: &Cli) -> Result<ReplayReport> {
+    let archive = ArchiveSource::open(bundle, &IoOptions::default())?;
+    let member = |name: &str| archive.read(&archive.path().join(name));
+    let session: Session = serde_json::from_slice(&member(SESSION_FILE)?).map_err(|e| {
+        ShinkansenError::ConfigParseError(format!(
+            "Invalid {} in {:?}: {}",
+            SESSION_FILE, bundle, e
+        ))
+    })?;
+    let context: serde_json::Map<String, serde_json::Value> =
+        serde_json::from_slice(&member(CONTEXT_FILE)?).map_err(|e| {
+            ShinkansenError::ConfigParseError(format!(
+                "Invalid {} in {:?}: {}",
+                CONTEXT_FILE, bundle, e
+            ))
+        })?;
+    let variables: HashMap<String, minijinja::Value> = context
+        .into_iter()
+        .map(|(name, value)| (name, json_to_minijinja_value(value)))
+        .collect();
+
+    let mut recorded = Cli::try_parse_from(&session.args).map_err(|e| {
+        ShinkansenError::ValidationError(format!(
+            "The recorded command line cannot be replayed by this version: {}",
+            e.to_string().trim()
+        ))
+    })?;
+    disable_side_effects(&mut recorded);
+
+    // The project config and per-file variables are read from files
+    let temp_dir = tempfile::tempdir().with_context(|| "Failed to create temporary directory")?;
+    match member(PROJECT_CONFIG_FILE) {
+        Ok(content) => {
+            let path = temp_dir.path().join(PROJECT_CONFIG_FILE);
+            std::fs::write(&path, without_hooks(&content)?)
+                .with_context(|| format!("Failed to write to {:?}", path))?;
+            recorded.project_config = Some(path);
+            recorded.no_project_config = false;
+        }
+        Err(_) => recorded.no_project_config = true,
+    }
+    let per_file = match member(PER_FILE_VARS_FILE) {
+        Ok(content) => {
+            let path = temp_dir.path().join(PER_FILE_VARS_FILE);
+            std::fs::write(&path, content)
+                .with_context(|| format!("Failed to write to {:?}", path))?;
+            PerFileVars::read(&path)?
+        }
+        Err(_) => PerFileVars::new(),
+    };
+
+    let source = SessionSource {
+        archive: &archive,
+        inputs: &session.inputs,
+    };
+    let backend = MemoryBackend::default();
+    let error = process_inputs_from(
+        &recorded,
+        &variables,
+        &per_file,
+        &source,
+        &backend,
+        &Diagnostics::default(),
+    )
+    .err()
+    .map(|err| err.to_string());
+
+    let mut replayed: Vec<(String, Vec<u8>)> = backend
+        .written
+        .into_inner()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .into_iter()
+        .map(|(path, content)| (path.to_string_lossy().into_owned(), content))
+        .collect();
+    let mut outputs = Vec::new();
+    let mut diffs = Vec::new();
+    for file in &session.outputs {
+        let expected = member(&file.member)?;
+        let outcome = match replayed.iter().position(|(path, _)| *path == file.path) {
+            Some(index) => {
+                let (path, content) = replayed.remove(index);
+                if content == expected {
+                    ReplayOutcome::Same
+                } else {
+                    if cli.diff
+                        && let (Ok(old), Ok(new)) = (
+                            std::str::from_utf8(&expected),
+                            std::str::from_utf8(&content),
+                        )
+                    {
+                        diffs.push(crate::patch::file_patch(Path::new(&path), Some(old), new));
+                    }
+                    ReplayOutcome::Changed
+                }
+            }
+            None => ReplayOutcome::Missing,
+        };
+        outputs.push((file.path.clone(), outcome));
+    }
+    outputs.extend(
+        replayed
+            .into_iter()
+            .map(|(path, _)| (path, ReplayOutcome::New)),
+    );
+
+    Ok(ReplayReport {
+        session,
+        outputs,
+        error,
+        diffs,
+    })
+}
+
+/// Turn off the options of a recorded command line that touch files other than outputs
+fn disable_side_effects(cli: &mut Cli) {
+    cli.check = false;
+    cli.diff = false;
+    cli.emit_patch = false;
+    cli.manifest = None;
+    cli.transactional = false;
+    cli.link = None;
+    cli.keep = None;
+    cli.managed_block = false;
+    cli.check_stamps = false;
+    cli.no_clobber = false;
+    cli.interactive = false;
+    cli.suffix = None;
+    cli.verify_cmds.clear();
+    cli.timing = false;
+    cli.profile_template = false;
+    cli.coverage = false;
+}
+
+/// A project config without its `[[hook]]` and `[hooks]` commands
+fn without_hooks(content: &[u8]) -> Result<String> {
+    let content = String::from_utf8_lossy(content);
+    let mut config: toml::Table = toml::from_str(&content)?;
+    config.remove("hook");
+    config.remove("hooks");
+    toml::to_string(&config).map_err(|e| {
+        ShinkansenError::ConfigParseError(format!("Failed to write the project config: {}", e))
+    })
+}
+
+/// The templates of a recording, listed and read from its bundle
+struct SessionSource<'a> {
+    archive: &'a ArchiveSource,
+    inputs: &'a [RecordedFile],
+}
+
+impl InputSource for SessionSource<'_> {
+    fn files(&self) -> Result<InputFiles<'_>> {
+        Ok(Box::new(
+            self.inputs
+                .iter()
+                .filter(|input| input.path != STDIN_PATH)
+                .map(|input| Ok(PathBuf::from(&input.path))),
+        ))
+    }
+
+    fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        let input = self
+            .inputs
+            .iter()
+            .find(|input| Path::new(&input.path) == path)
+            .ok_or_else(|| {
+                ShinkansenError::FileSystemError(format!(
+                    "{:?} was not read by the recorded run",
+                    path
+                ))
+            })?;
+        self.archive.read(&self.archive.path().join(&input.member))
+    }
+}
+
+/// Keeps replayed outputs in memory
+#[derive(Default)]
+struct MemoryBackend {
+    written: Mutex<Vec<(PathBuf, Vec<u8>)>>,
+}
+
+impl OutputBackend for MemoryBackend {
+    fn write(&self, path: &Path, content: &[u8], _meta: &OutputMeta) -> Result<()> {
+        self.written
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push((path.to_path_buf(), content.to_vec()));
+        Ok(())
+    }
+}
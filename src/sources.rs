@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::error::Result;
+use crate::variables::{json_to_minijinja_value, merge_variable};
+
+/// Variables produced by a source, in the order they are applied
+pub type SourceVariables = Vec<(String, serde_json::Value)>;
+
+/// A data source waiting to be fetched
+///
+/// Sources are parsed up front (so invalid flags fail before any fetch) and
+/// fetched later, possibly on another thread. A source that sets a single
+/// variable known up front can be deferred until a template reads it.
+pub struct SourceTask {
+    /// Human readable description used in timing output
+    pub label: String,
+    fetch: Box<dyn FnOnce() -> Result<SourceVariables> + Send>,
+    provides: Option<String>,
+    deferred: bool,
+}
+
+impl SourceTask {
+    pub fn new<F>(label: impl Into<String>, fetch: F) -> Self
+    where
+        F: FnOnce() -> Result<SourceVariables> + Send + 'static,
+    {
+        SourceTask {
+            label: label.into(),
+            fetch: Box::new(fetch),
+            provides: None,
+            deferred: false,
+        }
+    }
+
+    /// Declare that the source sets exactly the variable `name`
+    pub fn provides(mut self, name: impl Into<String>) -> Self {
+        self.provides = Some(name.into());
+        self
+    }
+
+    /// Fetch the source only once its variable is read (see [`crate::context::lazy`])
+    ///
+    /// Has no effect on a source that has not declared its variable with
+    /// [`SourceTask::provides`], since any of its variables could be read.
+    pub fn defer(mut self) -> Self {
+        self.deferred = self.provides.is_some();
+        self
+    }
+
+    /// Fetch the source, returning its variables
+    pub fn fetch(self) -> Result<SourceVariables> {
+        (self.fetch)()
+    }
+}
+
+/// A fetched source: how long it took and which variables it set
+#[derive(Debug, Clone)]
+pub struct FetchedSource {
+    pub label: String,
+    pub elapsed: Duration,
+    pub variables: Vec<String>,
+    /// Whether the source was deferred instead of fetched, see [`SourceTask::defer`]
+    pub deferred: bool,
+}
+
+/// Fetch every source and apply its variables in declaration order
+///
+/// With the `async` feature the sources are fetched concurrently; the
+/// variables are still applied in order, so precedence between sources never
+/// depends on which one finishes first. The first failing source (in
+/// declaration order) is reported.
+///
+/// Deferred sources are not fetched: their variable is set to a lazy value
+/// that fetches the source when first read, and errors surface then.
+pub fn collect_source_variables(
+    variables: &mut HashMap<String, minijinja::Value>,
+    tasks: Vec<SourceTask>,
+) -> Result<Vec<FetchedSource>> {
+    // Deferred tasks keep their place in the order; eager ones are fetched first
+    let mut eager = Vec::new();
+    let mut order = Vec::new();
+    for task in tasks {
+        if task.deferred {
+            order.push(Some(task));
+        } else {
+            eager.push(task);
+            order.push(None);
+        }
+    }
+    let mut results = fetch_all(eager).into_iter();
+    let mut fetched = Vec::new();
+
+    for slot in order {
+        match slot {
+            Some(task) => {
+                let label = task.label.clone();
+                let name = task.provides.clone().unwrap_or_default();
+                merge_variable(variables, name.clone(), deferred_value(task, name.clone()));
+                fetched.push(FetchedSource {
+                    label,
+                    elapsed: Duration::ZERO,
+                    variables: vec![name],
+                    deferred: true,
+                });
+            }
+            None => {
+                // Serial fetching stops at the first failure, returned before the results run out
+                let Some((label, result, elapsed)) = results.next() else {
+                    break;
+                };
+                let mut names = Vec::new();
+                for (name, value) in result? {
+                    merge_variable(variables, name.clone(), json_to_minijinja_value(value));
+                    names.push(name);
+                }
+                fetched.push(FetchedSource {
+                    label,
+                    elapsed,
+                    variables: names,
+                    deferred: false,
+                });
+            }
+        }
+    }
+
+    Ok(fetched)
+}
+
+/// The variable `name` of a deferred source, fetched when first read
+fn deferred_value(task: SourceTask, name: String) -> minijinja::Value {
+    crate::context::lazy(move || {
+        let value = task
+            .fetch()?
+            .into_iter()
+            .rev()
+            .find(|(variable, _)| *variable == name)
+            .map(|(_, value)| json_to_minijinja_value(value));
+        Ok(value.unwrap_or_default())
+    })
+}
+
+/// Print per-source timings to stderr for `--timing`
+pub fn print_timings(timings: &[FetchedSource], total: Duration) {
+    for timing in timings {
+        if timing.deferred {
+            eprintln!("timing: {:>8}  {}", "deferred", timing.label);
+        } else {
+            eprintln!("timing: {:>8.1?}  {}", timing.elapsed, timing.label);
+        }
+    }
+    if !timings.is_empty() {
+        let mode = if cfg!(feature = "async") {
+            "concurrent"
+        } else {
+            "serial"
+        };
+        eprintln!("timing: {:>8.1?}  all sources ({})", total, mode);
+    }
+}
+
+type FetchResult = (String, Result<SourceVariables>, Duration);
+
+fn fetch_one(task: SourceTask) -> FetchResult {
+    let start = Instant::now();
+    let label = task.label.clone();
+    let result = task.fetch();
+    (label, result, start.elapsed())
+}
+
+#[cfg(not(feature = "async"))]
+fn fetch_all(tasks: Vec<SourceTask>) -> Vec<FetchResult> {
+    let mut results = Vec::new();
+    for task in tasks {
+        let result = fetch_one(task);
+        let failed = result.1.is_err();
+        results.push(result);
+        // Later sources cannot change the outcome once one has failed
+        if failed {
+            break;
+        }
+    }
+    results
+}
+
+#[cfg(feature = "async")]
+fn fetch_all(tasks: Vec<SourceTask>) -> Vec<FetchResult> {
+    if tasks.len() < 2 {
+        return tasks.into_iter().map(fetch_one).collect();
+    }
+
+    let runtime = match tokio::runtime::Builder::new_current_thread().build() {
+        Ok(runtime) => runtime,
+        // Fall back to fetching serially rather than failing the render
+        Err(_) => return tasks.into_iter().map(fetch_one).collect(),
+    };
+
+    // The sources use blocking clients, so each fetch runs on the blocking pool
+    runtime.block_on(async {
+        let handles: Vec<_> = tasks
+            .into_iter()
+            .map(|task| {
+                let label = task.label.clone();
+                (label, tokio::task::spawn_blocking(move || fetch_one(task)))
+            })
+            .collect();
+
+        let mut results = Vec::new();
+        for (label, handle) in handles {
+            let result = handle.await.unwrap_or_else(|e| {
+                let error = crate::error::ShinkansenError::DataSourceError(format!(
+                    "Fetching {} failed: {}",
+                    label, e
+                ));
+                (label, Err(error), Duration::ZERO)
+            });
+            results.push(result);
+        }
+        results
+    })
+}
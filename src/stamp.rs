@@ -0,0 +1,204 @@
+use std::path::Path;
+
+use crate::cli::Cli;
+use crate::error::{Result, ShinkansenError};
+
+/// Header text used when `--stamp-text` is not given
+pub const DEFAULT_STAMP_TEXT: &str = "GENERATED BY shinkansen from {template} — do not edit";
+
+/// Placeholder replaced with the template path in the stamp text
+const TEMPLATE_PLACEHOLDER: &str = "{template}";
+
+/// Comment delimiters of a file format (start, end)
+pub type CommentSyntax = (&'static str, &'static str);
+
+/// Comment syntax by file extension; formats without comments (JSON) are left out
+const COMMENT_SYNTAX: &[(&[&str], CommentSyntax)] = &[
+    (
+        &[
+            "sh",
+            "bash",
+            "zsh",
+            "fish",
+            "py",
+            "rb",
+            "pl",
+            "r",
+            "yaml",
+            "yml",
+            "toml",
+            "conf",
+            "cfg",
+            "properties",
+            "env",
+            "tf",
+            "hcl",
+            "nix",
+            "ps1",
+            "psm1",
+            "mk",
+            "cmake",
+            "dockerfile",
+        ],
+        ("#", ""),
+    ),
+    (
+        &[
+            "rs",
+            "go",
+            "js",
+            "mjs",
+            "ts",
+            "jsx",
+            "tsx",
+            "c",
+            "h",
+            "cc",
+            "cpp",
+            "hpp",
+            "java",
+            "kt",
+            "kts",
+            "swift",
+            "cs",
+            "scala",
+            "dart",
+            "proto",
+            "jsonnet",
+            "libsonnet",
+            "groovy",
+            "gradle",
+            "php",
+        ],
+        ("//", ""),
+    ),
+    (&["sql", "lua", "hs", "dhall", "cue"], ("--", "")),
+    (
+        &[
+            "html", "htm", "xml", "xhtml", "svg", "md", "vue", "plist", "xaml", "csproj",
+        ],
+        ("<!--", "-->"),
+    ),
+    (&["css", "scss", "less"], ("/*", "*/")),
+    (&["ini", "asm", "el", "clj", "lisp"], (";", "")),
+    (&["tex", "erl"], ("%", "")),
+    (&["bat", "cmd"], ("REM", "")),
+    (&["vim"], ("\"", "")),
+    (&["vb", "vbs"], ("'", "")),
+];
+
+/// File names without an extension that have a known comment syntax
+const COMMENT_SYNTAX_BY_NAME: &[(&str, CommentSyntax)] = &[
+    (".env", ("#", "")),
+    (".gitignore", ("#", "")),
+    ("Dockerfile", ("#", "")),
+    ("Containerfile", ("#", "")),
+    ("Makefile", ("#", "")),
+    ("Gemfile", ("#", "")),
+    ("Vagrantfile", ("#", "")),
+    ("Jenkinsfile", ("//", "")),
+];
+
+/// "Generated file" headers for rendered outputs (`--stamp`, `--check-stamps`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct StampOptions {
+    /// Header text, with `{template}` standing in for the template path
+    pub text: String,
+}
+
+impl StampOptions {
+    /// Stamp options when `--stamp` or `--check-stamps` is given
+    pub fn from_cli(cli: &Cli) -> Option<Self> {
+        (cli.stamp || cli.check_stamps).then(|| StampOptions {
+            text: cli
+                .stamp_text
+                .clone()
+                .unwrap_or_else(|| DEFAULT_STAMP_TEXT.to_string()),
+        })
+    }
+
+    /// The header line for a file rendered from `template` to `output`
+    ///
+    /// `None` when the output format has no known comment syntax.
+    pub fn header(&self, template: &Path, output: &Path) -> Option<String> {
+        let (start, end) = comment_syntax(output)?;
+        let template = template.to_string_lossy().replace('\\', "/");
+        let text = self.text.replace(TEMPLATE_PLACEHOLDER, &template);
+        Some(if end.is_empty() {
+            format!("{} {}", start, text)
+        } else {
+            format!("{} {} {}", start, text, end)
+        })
+    }
+
+    /// Insert the header into rendered content
+    ///
+    /// The header goes after a leading `#!` interpreter line or `<?xml`
+    /// declaration, which must stay first. Content whose format has no known
+    /// comment syntax is returned unchanged.
+    pub fn apply(&self, template: &Path, output: &Path, content: &str) -> String {
+        let Some(header) = self.header(template, output) else {
+            return content.to_string();
+        };
+
+        let split = if content.starts_with("#!") || content.starts_with("<?xml") {
+            content.find('\n').map(|newline| newline + 1)
+        } else {
+            Some(0)
+        };
+        match split {
+            Some(split) => {
+                let line_ending = if content.contains("\r\n") {
+                    "\r\n"
+                } else {
+                    "\n"
+                };
+                format!(
+                    "{}{}{}{}",
+                    &content[..split],
+                    header,
+                    line_ending,
+                    &content[split..]
+                )
+            }
+            // A single interpreter/declaration line without a newline
+            None => format!("{}\n{}\n", content, header),
+        }
+    }
+
+    /// Check that `content` of `output` carries the header for `template`
+    pub fn is_stamped(&self, template: &Path, output: &Path, content: &str) -> bool {
+        match self.header(template, output) {
+            Some(header) => content.lines().any(|line| line.trim() == header),
+            None => true,
+        }
+    }
+}
+
+/// Comment delimiters for a file, from its name or extension
+pub fn comment_syntax(path: &Path) -> Option<CommentSyntax> {
+    let file_name = path.file_name()?.to_str()?;
+    if let Some((_, syntax)) = COMMENT_SYNTAX_BY_NAME
+        .iter()
+        .find(|(name, _)| *name == file_name)
+    {
+        return Some(*syntax);
+    }
+
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    COMMENT_SYNTAX
+        .iter()
+        .find(|(extensions, _)| extensions.contains(&extension.as_str()))
+        .map(|(_, syntax)| *syntax)
+}
+
+/// Fail with every output in `unstamped`, if any (`--check-stamps`)
+pub fn report_unstamped(unstamped: &[String]) -> Result<()> {
+    if unstamped.is_empty() {
+        return Ok(());
+    }
+    Err(ShinkansenError::ValidationError(format!(
+        "Generated files missing their stamp: {}",
+        unstamped.join(", ")
+    )))
+}
@@ -0,0 +1,194 @@
+//! Delimited text data files (CSV, TSV) for `--data NAME=PATH`
+//!
+//! The first row names the columns and every later row becomes an object
+//! keyed by them, so `users.csv` with `name,email` is exposed as
+//! `[{"name": ..., "email": ...}, ...]`. Fields follow RFC 4180: a field in
+//! double quotes may contain the delimiter, line breaks and `""` for a quote.
+//! Values stay strings, since the format has no types.
+
+use std::path::Path;
+
+use crate::error::{ContextExt, Result, ShinkansenError};
+use crate::fileio::IoOptions;
+
+/// Delimiters recognized when the file name does not tell
+const CANDIDATES: [char; 4] = [',', '\t', ';', '|'];
+
+/// Read a CSV or TSV file into a list of row objects
+pub fn read_table(path: &Path, io: &IoOptions) -> Result<serde_json::Value> {
+    let content = crate::fileio::read_file(path, io)
+        .with_context(|| format!("Failed to read data file {:?}", path))?;
+    let content = String::from_utf8(content).map_err(|_| {
+        ShinkansenError::DataSourceError(format!("Data file {:?} is not valid UTF-8", path))
+    })?;
+    let delimiter = delimiter_for(path).unwrap_or_else(|| detect_delimiter(&content));
+    parse_table(&content, delimiter).map_err(|e| {
+        ShinkansenError::DataSourceError(format!("Invalid data file {:?}: {}", path, e))
+    })
+}
+
+/// The delimiter implied by the extension: `.tsv`/`.tab` or `.csv`, also compressed
+pub fn delimiter_for(path: &Path) -> Option<char> {
+    match crate::fileio::content_extension(path)?.as_str() {
+        "tsv" | "tab" => Some('\t'),
+        "csv" => Some(','),
+        _ => None,
+    }
+}
+
+/// The candidate delimiter occurring most often outside quotes in the header row
+///
+/// Ties go to the earlier candidate, so a single-column file is read as CSV.
+pub fn detect_delimiter(content: &str) -> char {
+    let mut counts = [0usize; CANDIDATES.len()];
+    let mut quoted = false;
+    for c in content.trim_start_matches('\u{feff}').chars() {
+        match c {
+            '"' => quoted = !quoted,
+            '\n' | '\r' if !quoted => break,
+            c if !quoted => {
+                if let Some(index) = CANDIDATES.iter().position(|candidate| *candidate == c) {
+                    counts[index] += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    let (index, _) = counts
+        .iter()
+        .enumerate()
+        .fold((0, 0), |best, (index, count)| {
+            if *count > best.1 {
+                (index, *count)
+            } else {
+                best
+            }
+        });
+    CANDIDATES[index]
+}
+
+/// Parse delimited text into a list of objects keyed by the header row
+///
+/// Blank lines are skipped. A row with a different number of fields than the
+/// header, or a header with an empty or repeated name, is an error.
+pub fn parse_table(
+    content: &str,
+    delimiter: char,
+) -> std::result::Result<serde_json::Value, String> {
+    let mut rows = parse_rows(content.trim_start_matches('\u{feff}'), delimiter)?.into_iter();
+    let Some((_, header)) = rows.next() else {
+        return Ok(serde_json::Value::Array(Vec::new()));
+    };
+    for (index, name) in header.iter().enumerate() {
+        if name.is_empty() {
+            return Err(format!(
+                "column {} of the header row has no name",
+                index + 1
+            ));
+        }
+        if header[..index].contains(name) {
+            return Err(format!("the header row names column '{}' twice", name));
+        }
+    }
+
+    rows.map(|(line, fields)| {
+        if fields.len() != header.len() {
+            return Err(format!(
+                "line {} has {} field(s), but the header row has {}",
+                line,
+                fields.len(),
+                header.len()
+            ));
+        }
+        Ok(header
+            .iter()
+            .cloned()
+            .zip(fields.into_iter().map(serde_json::Value::from))
+            .collect::<serde_json::Map<_, _>>()
+            .into())
+    })
+    .collect::<std::result::Result<Vec<_>, _>>()
+    .map(serde_json::Value::Array)
+}
+
+/// Split `content` into rows of fields, each with the line it starts on
+fn parse_rows(
+    content: &str,
+    delimiter: char,
+) -> std::result::Result<Vec<(usize, Vec<String>)>, String> {
+    let mut rows = Vec::new();
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    // Whether the current field was quoted, so `""` is kept as an empty field
+    let mut was_quoted = false;
+    let (mut line, mut row_line) = (1, 1);
+    let mut chars = content.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if field.is_empty() && !was_quoted => {
+                was_quoted = true;
+                let start = line;
+                loop {
+                    match chars.next() {
+                        Some('"') if chars.peek() == Some(&'"') => {
+                            chars.next();
+                            field.push('"');
+                        }
+                        Some('"') => break,
+                        Some(c) => {
+                            if c == '\n' {
+                                line += 1;
+                            }
+                            field.push(c);
+                        }
+                        None => {
+                            return Err(format!(
+                                "the quoted field on line {} is not closed",
+                                start
+                            ));
+                        }
+                    }
+                }
+                match chars.peek() {
+                    Some(&next) if next != delimiter && next != '\n' && next != '\r' => {
+                        return Err(format!(
+                            "unexpected '{}' after the quoted field on line {}",
+                            next, line
+                        ));
+                    }
+                    _ => {}
+                }
+            }
+            c if c == delimiter => {
+                fields.push(std::mem::take(&mut field));
+                was_quoted = false;
+            }
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' | '\r' => {
+                end_row(&mut rows, &mut fields, &mut field, was_quoted, row_line);
+                was_quoted = false;
+                line += 1;
+                row_line = line;
+            }
+            c => field.push(c),
+        }
+    }
+    end_row(&mut rows, &mut fields, &mut field, was_quoted, row_line);
+    Ok(rows)
+}
+
+fn end_row(
+    rows: &mut Vec<(usize, Vec<String>)>,
+    fields: &mut Vec<String>,
+    field: &mut String,
+    was_quoted: bool,
+    line: usize,
+) {
+    if fields.is_empty() && field.is_empty() && !was_quoted {
+        // A blank line
+        return;
+    }
+    fields.push(std::mem::take(field));
+    rows.push((line, std::mem::take(fields)));
+}
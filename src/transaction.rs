@@ -0,0 +1,311 @@
+use std::num::NonZeroUsize;
+use std::path::{Component, Path, PathBuf};
+
+use walkdir::WalkDir;
+
+use crate::error::{ContextExt, Result};
+use crate::run::{RunContext, format_timestamp};
+
+/// A directory output assembled next to its target and swapped in at once (`--transactional`)
+///
+/// The staging directory starts as a copy of the target, so files the run does
+/// not render survive the swap. When the target is a symlink to a directory
+/// the staging directory becomes a new sibling release and the link is flipped
+/// atomically; the previous release is left for the caller to remove.
+/// Otherwise the old directory is renamed aside, the staged one renamed into
+/// place and the old one deleted. A release (`--link`) is staged empty and
+/// becomes the link's new target. Dropping an uncommitted stage removes it.
+#[derive(Debug)]
+pub struct StagedDirectory {
+    target: PathBuf,
+    staging: PathBuf,
+    swap: Swap,
+    committed: bool,
+}
+
+/// How a staged directory replaces its target
+#[derive(Debug)]
+enum Swap {
+    /// Rename the old directory aside and the staged one into place
+    Rename,
+    /// The target is a symlink, repointed at the staged directory
+    FlipTarget,
+    /// The target is a new release; `link` is repointed at it (`--link`, `--keep`)
+    Release {
+        link: PathBuf,
+        keep: Option<NonZeroUsize>,
+    },
+}
+
+impl StagedDirectory {
+    /// Stage a copy of `target`'s current contents next to it
+    pub fn begin(target: &Path) -> Result<Self> {
+        // Without a trailing separator, so a symlinked target is seen as the link
+        let target: PathBuf = target.components().collect();
+        let name = target
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        let link = cfg!(unix) && target.is_symlink();
+        let swap = if link { Swap::FlipTarget } else { Swap::Rename };
+        let staging = if link {
+            let started = RunContext::current().started_at_epoch;
+            target.with_file_name(format!("{}.{}.{}", name, started, std::process::id()))
+        } else {
+            target.with_file_name(format!(".{}.staging-{}", name, std::process::id()))
+        };
+
+        let staged = StagedDirectory::create(target.clone(), staging, swap)?;
+        if target.is_dir() {
+            copy_tree(&target, &staged.staging)?;
+        }
+        Ok(staged)
+    }
+
+    /// Stage the new, empty release `release`, which `link` will point to
+    ///
+    /// Once it is in place, all but the newest `keep` releases in its parent
+    /// directory are removed.
+    pub fn release(release: &Path, link: &Path, keep: Option<NonZeroUsize>) -> Result<Self> {
+        let name = release.file_name().unwrap_or_default().to_string_lossy();
+        let staging = release.with_file_name(format!(".{}.staging-{}", name, std::process::id()));
+        let swap = Swap::Release {
+            link: link.components().collect(),
+            keep,
+        };
+        StagedDirectory::create(release.to_path_buf(), staging, swap)
+    }
+
+    fn create(target: PathBuf, staging: PathBuf, swap: Swap) -> Result<Self> {
+        let staged = StagedDirectory {
+            target,
+            staging,
+            swap,
+            committed: false,
+        };
+        std::fs::create_dir_all(&staged.staging)
+            .with_context(|| format!("Failed to create staging directory {:?}", staged.staging))?;
+        Ok(staged)
+    }
+
+    /// The directory outputs are written to until the commit
+    pub fn path(&self) -> &Path {
+        &self.staging
+    }
+
+    /// Swap the staged directory into place
+    pub fn commit(mut self) -> Result<()> {
+        if let Swap::FlipTarget = self.swap {
+            let release = self.staging.file_name().unwrap_or_default();
+            flip_link(&self.target, Path::new(release))?;
+        } else if let Swap::Release { link, keep } = &self.swap {
+            std::fs::rename(&self.staging, &self.target)
+                .with_context(|| format!("Failed to move staged output to {:?}", self.target))?;
+            self.committed = true;
+            flip_link(link, &link_value(link, &self.target)?)?;
+            if let Some(keep) = keep {
+                prune_releases(&self.target, keep.get())?;
+            }
+            return Ok(());
+        } else if self.target.exists() {
+            let name = self
+                .target
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy();
+            let old = self
+                .target
+                .with_file_name(format!(".{}.old-{}", name, std::process::id()));
+            std::fs::rename(&self.target, &old)
+                .with_context(|| format!("Failed to move {:?} aside", self.target))?;
+            if let Err(err) = std::fs::rename(&self.staging, &self.target) {
+                let _ = std::fs::rename(&old, &self.target);
+                return Err(err)
+                    .with_context(|| format!("Failed to move staged output to {:?}", self.target));
+            }
+            std::fs::remove_dir_all(&old)
+                .with_context(|| format!("Failed to remove previous output {:?}", old))?;
+        } else {
+            std::fs::rename(&self.staging, &self.target)
+                .with_context(|| format!("Failed to move staged output to {:?}", self.target))?;
+        }
+        self.committed = true;
+        Ok(())
+    }
+}
+
+impl Drop for StagedDirectory {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = std::fs::remove_dir_all(&self.staging);
+        }
+    }
+}
+
+/// Copy a directory tree, keeping symlinks as links where the platform allows
+fn copy_tree(from: &Path, to: &Path) -> Result<()> {
+    for entry in WalkDir::new(from).min_depth(1) {
+        let entry = entry.map_err(std::io::Error::from)?;
+        let relative = entry.path().strip_prefix(from).unwrap_or(entry.path());
+        let destination = to.join(relative);
+        let copied = if entry.file_type().is_dir() {
+            std::fs::create_dir_all(&destination)
+        } else if entry.file_type().is_symlink() {
+            copy_link(entry.path(), &destination)
+        } else {
+            std::fs::copy(entry.path(), &destination).map(|_| ())
+        };
+        copied.with_context(|| format!("Failed to stage {:?}", entry.path()))?;
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn copy_link(link: &Path, destination: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(std::fs::read_link(link)?, destination)
+}
+
+#[cfg(not(unix))]
+fn copy_link(link: &Path, destination: &Path) -> std::io::Result<()> {
+    if link.is_dir() {
+        std::fs::create_dir_all(destination)
+    } else {
+        std::fs::copy(link, destination).map(|_| ())
+    }
+}
+
+/// The directory for a new release in `releases`, named after the run's start time
+///
+/// Names sort chronologically, e.g. `20261016T093000Z`; a second run within
+/// the same second gets `20261016T093000Z.2`.
+pub fn next_release(releases: &Path) -> PathBuf {
+    let stamp: String = format_timestamp(RunContext::current().started_at_epoch)
+        .chars()
+        .filter(|c| !matches!(c, '-' | ':'))
+        .collect();
+    let mut release = releases.join(&stamp);
+    let mut attempt = 1;
+    while release.exists() {
+        attempt += 1;
+        release = releases.join(format!("{}.{}", stamp, attempt));
+    }
+    release
+}
+
+/// Make sure `--link` names a symlink or nothing, so it is never replaced by accident
+pub fn check_link(link: &Path) -> Result<()> {
+    let link: PathBuf = link.components().collect();
+    match std::fs::symlink_metadata(&link) {
+        Ok(meta) if !meta.file_type().is_symlink() => {
+            Err(crate::error::ShinkansenError::ValidationError(format!(
+                "--link {:?} exists and is not a symlink",
+                link
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// What to store in `link` to reach `release`: a path relative to the link's directory
+fn link_value(link: &Path, release: &Path) -> Result<PathBuf> {
+    let link_dir = match link.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let base = link_dir
+        .canonicalize()
+        .with_context(|| format!("Failed to resolve the directory of --link {:?}", link))?;
+    let release = release
+        .canonicalize()
+        .with_context(|| format!("Failed to resolve release {:?}", release))?;
+    let common = base
+        .components()
+        .zip(release.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut value: PathBuf = base
+        .components()
+        .skip(common)
+        .map(|_| Component::ParentDir)
+        .collect();
+    value.extend(release.components().skip(common));
+    Ok(value)
+}
+
+/// Remove all but the newest `keep` releases next to `current`, never `current` itself
+///
+/// Only directories named like [`next_release`] names them count as releases;
+/// anything else next to them, and symlinks, are left alone.
+fn prune_releases(current: &Path, keep: usize) -> Result<()> {
+    let Some(releases) = current.parent() else {
+        return Ok(());
+    };
+    let entries = std::fs::read_dir(releases)
+        .with_context(|| format!("Failed to list releases in {:?}", releases))?;
+    let mut names = Vec::new();
+    for entry in entries {
+        let entry = entry.with_context(|| format!("Failed to list releases in {:?}", releases))?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let is_dir = entry.file_type().is_ok_and(|file_type| file_type.is_dir());
+        if let Some(order) = release_order(&name)
+            && is_dir
+        {
+            names.push((order, name));
+        }
+    }
+    // Newest first; a same-second suffix sorts after its base name
+    names.sort();
+    names.reverse();
+    for (_, name) in names.into_iter().skip(keep) {
+        let old = releases.join(&name);
+        if old != current {
+            std::fs::remove_dir_all(&old)
+                .with_context(|| format!("Failed to remove old release {:?}", old))?;
+        }
+    }
+    Ok(())
+}
+
+/// Where a release named `20261016T093000Z` or `20261016T093000Z.2` sorts, if `name` is one
+fn release_order(name: &str) -> Option<(String, u64)> {
+    let (stamp, attempt) = match name.split_once('.') {
+        Some((stamp, attempt))
+            if !attempt.is_empty() && attempt.bytes().all(|b| b.is_ascii_digit()) =>
+        {
+            (stamp, attempt.parse().ok()?)
+        }
+        Some(_) => return None,
+        None => (name, 1),
+    };
+    let bytes = stamp.as_bytes();
+    let is_stamp = bytes.len() == 16
+        && bytes[8] == b'T'
+        && bytes[15] == b'Z'
+        && bytes[..8]
+            .iter()
+            .chain(&bytes[9..15])
+            .all(u8::is_ascii_digit);
+    is_stamp.then(|| (stamp.to_string(), attempt))
+}
+
+/// Point the symlink `link` at `value` by renaming a new link over it
+#[cfg(unix)]
+fn flip_link(link: &Path, value: &Path) -> Result<()> {
+    let name = link.file_name().unwrap_or_default().to_string_lossy();
+    let temp = link.with_file_name(format!(".{}.link-{}", name, std::process::id()));
+    std::os::unix::fs::symlink(value, &temp)
+        .and_then(|_| std::fs::rename(&temp, link))
+        .inspect_err(|_| {
+            let _ = std::fs::remove_file(&temp);
+        })
+        .with_context(|| format!("Failed to point {:?} at {:?}", link, value))
+}
+
+#[cfg(not(unix))]
+fn flip_link(link: &Path, _value: &Path) -> Result<()> {
+    Err(crate::error::ShinkansenError::ValidationError(format!(
+        "Cannot switch the symlink {:?}: output symlinks are only supported on unix",
+        link
+    )))
+}
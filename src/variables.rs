@@ -1,57 +1,359 @@
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
 
+use crate::cache::Cache;
 use crate::cli::Cli;
-use crate::config::ConfigLoaderFactory;
-use crate::error::{ContextExt, Result};
+use crate::config::{
+    ConfigContext, ConfigLoaderFactory, VariableMeta, fetch_config, is_config_url,
+    validate_with_cue_schema,
+};
+use crate::data::data_source_tasks;
+use crate::diagnostics::Diagnostics;
+use crate::error::{ContextExt, Result, catch_panic};
+use crate::kv::kv_source_tasks;
+use crate::network::NetworkOptions;
+use crate::provenance::{Provenance, VariableSource};
+use crate::run::{RUN_VARIABLE, RunContext};
+use crate::sources::{SourceTask, collect_source_variables, print_timings};
+
+/// Largest array index accepted in a variable key such as `servers[3]`
+pub const MAX_ARRAY_INDEX: usize = 65_535;
+
+/// Merged template variables together with where each one came from
+#[derive(Debug, Default)]
+pub struct VariableContext {
+    pub values: HashMap<String, minijinja::Value>,
+    pub provenance: Provenance,
+    /// Declarations from the config file's `_meta` block
+    pub meta: BTreeMap<String, VariableMeta>,
+    /// Warnings raised while collecting, such as unset --env names
+    pub diagnostics: Diagnostics,
+}
 
 /// Collect all template variables with proper precedence
 ///
 /// This function loads variables from multiple sources in order of precedence:
-/// 1. Environment variables (lowest precedence) - only if specified via --env flag
-/// 2. Config file variables - if a config file is specified via -c/--config flag
-/// 3. Command-line variables (highest precedence) - specified via -D flag
+/// 1. Dotenv files (lowest precedence) - given with --env-file, in order
+/// 2. Environment variables - only if specified via --env-prefix or --env, and
+///    the whole environment as the `env` object with --env-all
+/// 3. Config file variables - from each -c/--config file in order, later files
+///    deep-merged over earlier ones, including the `[vars.<predicate>]` sections
+///    matching the platform and --profile,
+///    then a JSON or YAML document read from stdin with --vars-stdin
+/// 4. Data source variables - named sources such as --data, --data-url, --data-sql and --data-ldap
+/// 5. Key/value store variables - Consul/etcd subtrees and Kubernetes ConfigMaps/Secrets
+/// 6. Command-line variables (highest precedence) - specified via -D, then
+///    --set-string, --set-json and --set-file
 ///
 /// Later sources override earlier ones for variables with the same name.
+/// Finally, `_meta` defaults and then the config file's `[derived]` templates
+/// fill in names no source defined.
 pub fn collect_variables(cli: &Cli) -> Result<HashMap<String, minijinja::Value>> {
-    let mut variables = HashMap::new();
+    collect_variable_context(cli).map(|context| context.values)
+}
+
+/// Collect all template variables like [`collect_variables`], recording their provenance
+pub fn collect_variable_context(cli: &Cli) -> Result<VariableContext> {
+    let mut context = VariableContext::default();
+    let variables = &mut context.values;
+    let provenance = &mut context.provenance;
+    let diagnostics = &context.diagnostics;
+
+    // 1. Load dotenv files (lowest precedence), later files overriding earlier ones
+    for path in &cli.env_files {
+        let source = DotenvSource::read(path)?;
+        for name in source.apply(variables, diagnostics) {
+            provenance.record(name, VariableSource::Dotenv(path.clone()));
+        }
+    }
 
-    // 1. Load environment variables (lowest precedence) - only if specified
+    // 2. Load environment variables - only if specified, by prefix and then by name
+    if let Some(prefix) = &cli.env_prefix {
+        for (name, variable) in load_prefixed_env_variables(variables, prefix, cli, diagnostics) {
+            provenance.record(name, VariableSource::Env(variable));
+        }
+    }
     if cli.env.is_some() {
-        collect_env_variables(&mut variables, cli)?;
+        for name in load_env_variables(variables, cli, diagnostics)? {
+            provenance.record(name.clone(), VariableSource::Env(name));
+        }
     }
 
-    // 2. Load config file variables
-    if let Some(config_path) = &cli.config {
-        collect_config_variables(&mut variables, config_path)?;
+    // With --env-all the whole environment is exposed as the `env` object
+    if cli.env_all {
+        merge_variable(variables, ENV_VARIABLE.to_string(), environment_object());
+        provenance.record(ENV_VARIABLE, VariableSource::Environment);
     }
 
-    // 3. Load command-line variables (highest precedence)
-    collect_cli_variables(&mut variables, &cli.variables)?;
+    // 3. Load config file variables; later files are deep-merged over earlier ones
+    // and their `_meta` declarations and `[derived]` templates replace same-named ones
+    let mut derived: Vec<(String, String, PathBuf)> = Vec::new();
+    let mut declared_in = HashMap::new();
+    let (network, cache) = (
+        NetworkOptions::from_cli(cli),
+        Cache::from_cli(cli, diagnostics),
+    );
+    for config_path in &cli.config {
+        let config_context = ConfigContext::current(cli.profile.as_deref());
+        let template_context = if cli.config_template {
+            // The config is rendered with what is known before it: the environment,
+            // the earlier config files and -D values
+            let mut template_context = variables.clone();
+            // Coercion warnings are raised when -D is applied for real below
+            load_cli_variables(
+                &mut template_context,
+                &cli.variables,
+                &Diagnostics::default(),
+            )?;
+            Some(template_context)
+        } else {
+            None
+        };
+        let remote = fetch_if_remote(config_path, &network, &cache)?;
+        let loaded = load_config_file(
+            variables,
+            config_path,
+            remote,
+            &config_context,
+            template_context.as_ref(),
+        )?;
+        for name in loaded.names {
+            provenance.record(name, VariableSource::Config(config_path.clone()));
+        }
+        for (name, template) in loaded.derived {
+            derived.retain(|(earlier, _, _)| *earlier != name);
+            derived.push((name, template, config_path.clone()));
+        }
+        for (name, declaration) in loaded.meta {
+            declared_in.insert(name.clone(), config_path.clone());
+            context.meta.insert(name, declaration);
+        }
+    }
+
+    // Then a JSON or YAML document piped in with --vars-stdin
+    if cli.vars_stdin {
+        use std::io::Read;
+
+        let mut content = String::new();
+        std::io::stdin()
+            .read_to_string(&mut content)
+            .with_context(|| "Failed to read variables from stdin")?;
+        for name in load_document_variables(variables, &content)? {
+            provenance.record(name, VariableSource::Stdin);
+        }
+    }
+
+    // 4. and 5. Fetch named data sources and key/value store subtrees; with the
+    // 'async' feature they are fetched concurrently but still applied in this order
+    let mut tasks = data_source_tasks(cli, diagnostics)?;
+    tasks.extend(kv_source_tasks(cli, diagnostics)?);
+    if cli.lazy_sources {
+        tasks = tasks.into_iter().map(SourceTask::defer).collect();
+    }
+    let start = Instant::now();
+    let fetched = collect_source_variables(variables, tasks)?;
+    if cli.timing {
+        print_timings(&fetched, start.elapsed());
+    }
+    for source in fetched {
+        for name in source.variables {
+            provenance.record(name, VariableSource::Source(source.label.clone()));
+        }
+    }
 
-    Ok(variables)
+    // 6. Load command-line variables (highest precedence): -D, then the Helm-style --set-* flags
+    let assignments = [
+        (
+            "-D",
+            load_cli_variables(variables, &cli.variables, diagnostics)?,
+        ),
+        (
+            "--set-string",
+            load_set_variables(variables, &cli.set_strings, SetKind::String)?,
+        ),
+        (
+            "--set-json",
+            load_set_variables(variables, &cli.set_jsons, SetKind::Json)?,
+        ),
+        (
+            "--set-file",
+            load_set_variables(variables, &cli.set_files, SetKind::File)?,
+        ),
+    ];
+    for (flag, names) in assignments {
+        for (index, (name, key)) in names.into_iter().enumerate() {
+            provenance.record(
+                name,
+                VariableSource::Cli {
+                    flag,
+                    key,
+                    index: index + 1,
+                },
+            );
+        }
+    }
+
+    // A partially pinned `run` (e.g. -D run.id=fixed) keeps the generated fields it does not set
+    if let Some(run) = variables.get(RUN_VARIABLE) {
+        let run = RunContext::current().complete(run);
+        variables.insert(RUN_VARIABLE.to_string(), run);
+    }
+
+    // Fill in declared defaults, then compute derived variables from the merged context
+    for (name, declaration) in &context.meta {
+        if let Some(default) = &declaration.default
+            && !variables.contains_key(name)
+        {
+            variables.insert(name.clone(), json_to_minijinja_value(default.clone()));
+            provenance.record(
+                name.clone(),
+                VariableSource::Default(declared_in[name].clone()),
+            );
+        }
+    }
+
+    let before: HashSet<String> = variables.keys().cloned().collect();
+    let templates: Vec<(String, String)> = derived
+        .iter()
+        .map(|(name, template, _)| (name.clone(), template.clone()))
+        .collect();
+    apply_derived_variables(variables, &templates)?;
+    for (name, _, config_path) in derived {
+        if !before.contains(&name) {
+            provenance.record(name, VariableSource::Derived(config_path));
+        }
+    }
+
+    // Validate the merged context against a schema if one was given; this
+    // needs every value, so deferred sources are fetched for it
+    if let Some(schema_path) = &cli.cue_schema {
+        validate_with_cue_schema(&crate::context::resolve_all(variables)?, schema_path)?;
+    }
+
+    if cli.explain_vars {
+        eprint!("{}", provenance.explain());
+    }
+
+    Ok(context)
 }
 
 /// Collect variables from environment variables
 ///
 /// Only loads variables that are explicitly listed in the --env flag
-/// Variables that don't exist in the environment are silently ignored
+/// Variables that don't exist in the environment are silently ignored, unless
+/// --require-env is given
+/// Names suffixed with `:json` (e.g. `--env CONFIG:json`) must hold valid JSON
 pub fn collect_env_variables(
     variables: &mut HashMap<String, minijinja::Value>,
     cli: &Cli,
 ) -> Result<()> {
-    load_env_variables(variables, cli)
+    load_env_variables(variables, cli, &Diagnostics::default()).map(|_| ())
 }
 
 /// Collect variables from a configuration file
 ///
-/// Supports JSON, YAML, YML, and TOML file formats
+/// Supports JSON, YAML, YML, TOML and CUE file formats (plus Dhall and Jsonnet with the `dhall` and `jsonnet` features)
 /// Returns an error if the file format is unsupported or the file cannot be read
+/// Conditional sections are evaluated for the running platform with no profile selected
 pub fn collect_config_variables(
     variables: &mut HashMap<String, minijinja::Value>,
-    config_path: &PathBuf,
+    config_path: &Path,
+) -> Result<()> {
+    collect_config_variables_for(variables, config_path, &ConfigContext::current(None))
+}
+
+/// Collect variables from a configuration file, evaluating conditional sections against `context`
+///
+/// Derived variables are computed right away from the variables collected so far.
+pub fn collect_config_variables_for(
+    variables: &mut HashMap<String, minijinja::Value>,
+    config_path: &Path,
+    context: &ConfigContext,
 ) -> Result<()> {
-    load_config_file(variables, config_path)
+    let remote = fetch_if_remote(config_path, &NetworkOptions::default(), &Cache::default())?;
+    let loaded = load_config_file(variables, config_path, remote, context, None)?;
+    apply_derived_variables(variables, &loaded.derived)
+}
+
+/// Collect variables from a JSON or YAML mapping, as read by `--vars-stdin`
+pub fn collect_stdin_variables(
+    variables: &mut HashMap<String, minijinja::Value>,
+    content: &str,
+) -> Result<()> {
+    load_document_variables(variables, content).map(|_| ())
+}
+
+/// Merge the top-level keys of a JSON or YAML mapping, returning their names
+fn load_document_variables(
+    variables: &mut HashMap<String, minijinja::Value>,
+    content: &str,
+) -> Result<Vec<String>> {
+    // JSON is tried first for its clearer errors; YAML accepts the rest
+    let document = match serde_json::from_str::<serde_json::Value>(content) {
+        Ok(document) => document,
+        Err(_) => serde_yaml::from_str::<serde_json::Value>(content).map_err(|e| {
+            crate::error::ShinkansenError::ConfigParseError(format!(
+                "Variables on stdin are neither JSON nor YAML: {}",
+                e
+            ))
+        })?,
+    };
+    let serde_json::Value::Object(document) = document else {
+        return Err(crate::error::ShinkansenError::ConfigParseError(
+            "Variables on stdin must be a mapping of names to values".to_string(),
+        ));
+    };
+
+    let mut names = Vec::new();
+    for (key, value) in document {
+        merge_variable(variables, key.clone(), json_to_minijinja_value(value));
+        names.push(key);
+    }
+    Ok(names)
+}
+
+/// Render `[derived]` templates against the variables and add the results as strings
+///
+/// Derived variables may refer to each other in any order. Names that are
+/// already defined are left untouched, so e.g. `-D image=...` overrides a
+/// derived `image`.
+pub fn apply_derived_variables(
+    variables: &mut HashMap<String, minijinja::Value>,
+    derived: &[(String, String)],
+) -> Result<()> {
+    let mut pending: Vec<&(String, String)> = derived
+        .iter()
+        .filter(|(name, _)| !variables.contains_key(name))
+        .collect();
+
+    // Render whatever can be rendered until a pass makes no progress
+    while !pending.is_empty() {
+        let mut failures = Vec::new();
+        let mut progress = false;
+        for entry in pending {
+            let (name, template) = entry;
+            let template_name = format!("derived.{}", name);
+            match crate::rendering::render_template(template, variables, &template_name) {
+                Ok(value) => {
+                    variables.insert(name.clone(), minijinja::Value::from(value));
+                    progress = true;
+                }
+                Err(err) => failures.push((entry, err)),
+            }
+        }
+
+        if !progress {
+            let (_, err) = failures.remove(0);
+            return Err(crate::error::ShinkansenError::VariableParseError(format!(
+                "Failed to compute derived variables: {}",
+                err
+            )));
+        }
+        pending = failures.into_iter().map(|(entry, _)| entry).collect();
+    }
+
+    Ok(())
 }
 
 /// Collect variables from command-line arguments
@@ -64,55 +366,375 @@ pub fn collect_cli_variables(
     variables: &mut HashMap<String, minijinja::Value>,
     cli_vars: &[String],
 ) -> Result<()> {
-    load_cli_variables(variables, cli_vars)
+    load_cli_variables(variables, cli_vars, &Diagnostics::default()).map(|_| ())
 }
 
-fn load_env_variables(variables: &mut HashMap<String, minijinja::Value>, cli: &Cli) -> Result<()> {
+/// Load the --env variables, returning the names that were set
+///
+/// Unset names are a warning, or an error with --require-env.
+fn load_env_variables(
+    variables: &mut HashMap<String, minijinja::Value>,
+    cli: &Cli,
+    diagnostics: &Diagnostics,
+) -> Result<Vec<String>> {
+    let mut names = Vec::new();
     if let Some(env_vars) = &cli.env {
         let var_names: Vec<&str> = env_vars.split(',').map(|s| s.trim()).collect();
+        let mut missing = Vec::new();
+
+        for var_spec in var_names {
+            // A ':json' suffix parses the raw value as JSON instead of guessing its type
+            let (var_name, json) = match var_spec.strip_suffix(":json") {
+                Some(name) => (name, true),
+                None => (var_spec, false),
+            };
 
-        for var_name in var_names {
             if let Ok(value) = std::env::var(var_name) {
-                // Unescape the value first, then convert to appropriate type
-                let unescaped_value = unescape_value(&value);
-                let minijinja_value = string_to_minijinja_value(&unescaped_value);
-                variables.insert(var_name.to_string(), minijinja_value);
+                let minijinja_value = if json {
+                    let json_value = serde_json::from_str(&value).map_err(|e| {
+                        crate::error::ShinkansenError::VariableParseError(format!(
+                            "Environment variable {} is not valid JSON: {}",
+                            var_name, e
+                        ))
+                    })?;
+                    json_to_minijinja_value(json_value)
+                } else {
+                    // Unescape the value first, then convert to appropriate type
+                    let unescaped_value = unescape_value(&value);
+                    let converted = string_to_minijinja_value(&unescaped_value);
+                    warn_if_coerced(
+                        diagnostics,
+                        &format!("--env {}", var_name),
+                        &unescaped_value,
+                        &converted,
+                    );
+                    converted
+                };
+                merge_variable(variables, var_name.to_string(), minijinja_value);
+                names.push(var_name.to_string());
+            } else {
+                missing.push(var_name);
             }
         }
+
+        // With --require-env, report every unset variable at once
+        if cli.require_env && !missing.is_empty() {
+            return Err(crate::error::ShinkansenError::VariableParseError(format!(
+                "Missing required environment variables: {}",
+                missing.join(", ")
+            )));
+        }
+        if !missing.is_empty() {
+            diagnostics.warn(format!(
+                "--env names not set in the environment: {}",
+                missing.join(", ")
+            ));
+        }
     }
 
-    Ok(())
+    Ok(names)
 }
 
-fn load_config_file(
+/// The variable holding the process environment under `--env-all`
+pub const ENV_VARIABLE: &str = "env";
+
+/// Every environment variable with a UTF-8 name and value, as strings
+fn environment_object() -> minijinja::Value {
+    let environment: BTreeMap<String, String> = std::env::vars_os()
+        .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)))
+        .collect();
+    minijinja::Value::from_serialize(&environment)
+}
+
+/// Assignments read from a dotenv file (`--env-file`)
+///
+/// Each line is `KEY=value`, optionally prefixed with `export`. Blank lines and
+/// lines starting with `#` are skipped, as is a ` #` comment after an unquoted
+/// value. Double-quoted values understand `\n`, `\t`, `\r`, `\"`, `\\` and `\$`;
+/// single- and backtick-quoted values are taken literally. Quoted values may
+/// span several lines. `${VAR}` references are not expanded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DotenvSource {
+    pub path: PathBuf,
+    /// Names and raw values, in file order
+    pub entries: Vec<(String, String)>,
+}
+
+impl DotenvSource {
+    pub fn read(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read env file {:?}", path))?;
+        DotenvSource::parse(path, &content)
+    }
+
+    pub fn parse(path: &Path, content: &str) -> Result<Self> {
+        let invalid = |line: usize, reason: &str| {
+            crate::error::ShinkansenError::VariableParseError(format!(
+                "{}:{}: {}",
+                path.display(),
+                line,
+                reason
+            ))
+        };
+
+        let mut entries = Vec::new();
+        let mut lines = content.lines().enumerate();
+        while let Some((index, line)) = lines.next() {
+            let number = index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let line = line
+                .strip_prefix("export")
+                .filter(|rest| rest.starts_with(char::is_whitespace))
+                .map_or(line, str::trim_start);
+            let (name, value) = line
+                .split_once('=')
+                .ok_or_else(|| invalid(number, "expected KEY=value"))?;
+            let name = name.trim();
+            let valid_name = name
+                .chars()
+                .next()
+                .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'));
+            if !valid_name {
+                return Err(invalid(
+                    number,
+                    &format!("invalid variable name '{}'", name),
+                ));
+            }
+
+            let value = value.trim_start();
+            let value = match value.chars().next() {
+                Some(quote @ ('"' | '\'' | '`')) => {
+                    // Gather lines until the closing quote
+                    let mut text = value[1..].to_string();
+                    let (value, rest) = loop {
+                        if let Some(closed) = close_quote(&text, quote) {
+                            break closed;
+                        }
+                        let Some((_, next)) = lines.next() else {
+                            return Err(invalid(number, &format!("unterminated {} quote", quote)));
+                        };
+                        text.push('\n');
+                        text.push_str(next);
+                    };
+                    let rest = rest.trim();
+                    if !rest.is_empty() && !rest.starts_with('#') {
+                        return Err(invalid(
+                            number,
+                            &format!("unexpected text after the quoted value of {}", name),
+                        ));
+                    }
+                    value
+                }
+                _ => {
+                    let end = value
+                        .char_indices()
+                        .find(|&(i, c)| c == '#' && value[..i].ends_with(char::is_whitespace))
+                        .map_or(value.len(), |(i, _)| i);
+                    value[..end].trim_end().to_string()
+                }
+            };
+            entries.push((name.to_string(), value));
+        }
+
+        Ok(DotenvSource {
+            path: path.to_path_buf(),
+            entries,
+        })
+    }
+
+    /// Merge the values into `variables` with the same type detection as `--env`
+    ///
+    /// Returns the names that were set, in file order.
+    fn apply(
+        &self,
+        variables: &mut HashMap<String, minijinja::Value>,
+        diagnostics: &Diagnostics,
+    ) -> Vec<String> {
+        let mut names = Vec::new();
+        for (name, raw) in &self.entries {
+            let value = string_to_minijinja_value(raw);
+            warn_if_coerced(
+                diagnostics,
+                &format!("--env-file {} {}", self.path.display(), name),
+                raw,
+                &value,
+            );
+            merge_variable(variables, name.clone(), value);
+            names.push(name.clone());
+        }
+        names
+    }
+}
+
+/// Split `text` after an opening `quote` into the unquoted value and what follows
+/// the closing quote, or `None` if it has not been closed yet
+fn close_quote(text: &str, quote: char) -> Option<(String, &str)> {
+    let mut value = String::new();
+    let mut chars = text.char_indices();
+    while let Some((i, c)) = chars.next() {
+        if c == quote {
+            return Some((value, &text[i + 1..]));
+        }
+        if c == '\\' && quote == '"' {
+            match chars.next() {
+                Some((_, 'n')) => value.push('\n'),
+                Some((_, 't')) => value.push('\t'),
+                Some((_, 'r')) => value.push('\r'),
+                Some((_, escaped @ ('"' | '\\' | '$'))) => value.push(escaped),
+                Some((_, other)) => {
+                    value.push('\\');
+                    value.push(other);
+                }
+                None => value.push('\\'),
+            }
+        } else {
+            value.push(c);
+        }
+    }
+    None
+}
+
+/// Load every environment variable whose name starts with `prefix` (`--env-prefix`)
+///
+/// With `--strip-env-prefix` the prefix is removed and the rest lowercased, so
+/// `APP_DB_HOST` becomes `db_host`. Values get the same type detection as
+/// `--env`. Returns the template names with the environment names they came from.
+fn load_prefixed_env_variables(
     variables: &mut HashMap<String, minijinja::Value>,
-    config_path: &PathBuf,
-) -> Result<()> {
-    let content = std::fs::read_to_string(config_path)
-        .with_context(|| format!("Failed to read config file: {:?}", config_path))?;
+    prefix: &str,
+    cli: &Cli,
+    diagnostics: &Diagnostics,
+) -> Vec<(String, String)> {
+    let mut matching: Vec<(String, String)> = std::env::vars_os()
+        .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)))
+        .filter(|(name, _)| name.starts_with(prefix))
+        .collect();
+    matching.sort();
 
-    let ext = config_path
-        .extension()
-        .and_then(|s| s.to_str())
-        .unwrap_or("");
+    let mut names = Vec::new();
+    for (variable, value) in matching {
+        let name = if cli.strip_env_prefix {
+            variable[prefix.len()..].to_lowercase()
+        } else {
+            variable.clone()
+        };
+        if name.is_empty() {
+            continue;
+        }
+        let unescaped_value = unescape_value(&value);
+        let converted = string_to_minijinja_value(&unescaped_value);
+        warn_if_coerced(
+            diagnostics,
+            &format!("--env-prefix {}", variable),
+            &unescaped_value,
+            &converted,
+        );
+        merge_variable(variables, name.clone(), converted);
+        names.push((name, variable));
+    }
+    names
+}
+
+/// What loading a config file produced besides its variables
+struct LoadedConfig {
+    /// Names of the variables that were set
+    names: Vec<String>,
+    derived: Vec<(String, String)>,
+    meta: BTreeMap<String, VariableMeta>,
+}
+
+/// Download `config_path` if it is a URL: its content and loader extension
+fn fetch_if_remote(
+    config_path: &Path,
+    network: &NetworkOptions,
+    cache: &Cache,
+) -> Result<Option<(String, String)>> {
+    if !is_config_url(config_path) {
+        return Ok(None);
+    }
+    fetch_config(&config_path.to_string_lossy(), network, cache).map(Some)
+}
+
+/// Load a config file, or the `remote` content downloaded for a config URL, into `variables`
+///
+/// With a `template_context` (--config-template) the file is first rendered as
+/// a MiniJinja template and the result is parsed as content, so relative
+/// imports of CUE/Jsonnet/Dhall configs resolve from the working directory.
+fn load_config_file(
+    variables: &mut HashMap<String, minijinja::Value>,
+    config_path: &Path,
+    remote: Option<(String, String)>,
+    context: &ConfigContext,
+    template_context: Option<&HashMap<String, minijinja::Value>>,
+) -> Result<LoadedConfig> {
+    let ext = match &remote {
+        Some((_, format)) => format.as_str(),
+        None => config_path
+            .extension()
+            .and_then(|s| s.to_str())
+            .unwrap_or(""),
+    };
 
     let loader = ConfigLoaderFactory::create_loader(ext).ok_or_else(|| {
-        crate::error::ShinkansenError::ConfigParseError(
-            "Unsupported config file format. Use .json, .yaml, .yml, or .toml".to_string(),
-        )
+        let supported: Vec<String> = ConfigLoaderFactory::supported_extensions()
+            .iter()
+            .map(|ext| format!(".{}", ext))
+            .collect();
+        crate::error::ShinkansenError::ConfigParseError(format!(
+            "Unsupported config file format: {:?}",
+            config_path
+        ))
+        .with_hint(format!("supported formats are {}", supported.join(", ")))
+        .with_docs("using-configuration-files")
     })?;
 
-    let config = loader.load_config(&content)?;
+    let config = match (template_context, remote) {
+        (Some(template_context), remote) => {
+            let content = match remote {
+                Some((content, _)) => content,
+                None => std::fs::read_to_string(config_path)
+                    .with_context(|| format!("Failed to read config file: {:?}", config_path))?,
+            };
+            let rendered = crate::rendering::render_template(
+                &content,
+                template_context,
+                &config_path.to_string_lossy(),
+            )?;
+            catch_panic("parsing the config file", || loader.load_config(&rendered))?
+        }
+        (None, Some((content, _))) => {
+            catch_panic("parsing the config file", || loader.load_config(&content))?
+        }
+        (None, None) => catch_panic("parsing the config file", || {
+            loader.load_config_file(config_path)
+        })?,
+    };
+    let mut config = config.resolve_conditionals(context)?;
+    let derived = config.take_derived();
+    let meta = config.take_meta()?;
 
     // Convert serde_json::Value to minijinja::Value
+    let mut names = Vec::new();
     for (key, value) in config.variables {
-        variables.insert(key, json_to_minijinja_value(value));
+        merge_variable(variables, key.clone(), json_to_minijinja_value(value));
+        names.push(key);
     }
 
-    Ok(())
+    Ok(LoadedConfig {
+        names,
+        derived,
+        meta,
+    })
 }
 
-fn json_to_minijinja_value(value: serde_json::Value) -> minijinja::Value {
+/// Convert a JSON value into the equivalent MiniJinja value
+pub(crate) fn json_to_minijinja_value(value: serde_json::Value) -> minijinja::Value {
     match value {
         serde_json::Value::Null => minijinja::Value::from(()),
         serde_json::Value::Bool(b) => minijinja::Value::from(b),
@@ -145,120 +767,467 @@ fn json_to_minijinja_value(value: serde_json::Value) -> minijinja::Value {
     }
 }
 
+/// Load -D variables, returning `(top-level name, key)` for each one that was set
 fn load_cli_variables(
     variables: &mut HashMap<String, minijinja::Value>,
     cli_vars: &[String],
-) -> Result<()> {
+    diagnostics: &Diagnostics,
+) -> Result<Vec<(String, String)>> {
+    catch_panic("parsing -D variables", || {
+        parse_cli_variables(variables, cli_vars, diagnostics)
+    })
+}
+
+fn parse_cli_variables(
+    variables: &mut HashMap<String, minijinja::Value>,
+    cli_vars: &[String],
+    diagnostics: &Diagnostics,
+) -> Result<Vec<(String, String)>> {
+    let mut names = Vec::new();
     for var in cli_vars {
         // Split the argument on unescaped commas to handle multiple variables in one -D flag
-        let var_parts = split_unescaped(var, ',');
-
-        for single_var in var_parts {
-            // Find the first unescaped '=' character
-            let mut key_end = 0;
-            let chars = single_var.chars().peekable();
-            let mut in_escape = false;
-
-            for ch in chars {
-                if in_escape {
-                    // Skip the escaped character
-                    in_escape = false;
-                } else if ch == '\\' {
-                    // Start escape sequence
-                    in_escape = true;
-                } else if ch == '=' {
-                    // Found unescaped '=' - this is the separator
-                    break;
+        for single_var in split_unescaped(var, ',') {
+            let (key, value_with_escapes) = split_assignment(single_var, "KEY=VALUE")?;
+
+            // `@path` reads the value from a file; `\@` keeps a literal `@`
+            let converted = match value_with_escapes.strip_prefix('@') {
+                Some(path) => file_value(key, &unescape_value(path))?,
+                None => {
+                    // Unescape the value, then convert to appropriate type (number, bool, or string)
+                    let value = unescape_value(value_with_escapes);
+                    let converted = string_to_minijinja_value(&value);
+                    warn_if_coerced(diagnostics, &format!("-D {}", key), &value, &converted);
+                    converted
                 }
-                key_end += ch.len_utf8();
-            }
+            };
+            let name = insert_variable(variables, key, converted)?;
+            names.push((name, key.to_string()));
+        }
+    }
 
-            if key_end == 0 || key_end >= single_var.len() {
-                return Err(crate::error::ShinkansenError::VariableParseError(format!(
-                    "Invalid variable format: '{}'. Use KEY=VALUE",
-                    single_var
-                )));
+    Ok(names)
+}
+
+/// The value of `-D key=@path`: the file's JSON or YAML data, or else its text
+///
+/// `.json` files must hold JSON and `.yaml`/`.yml` files YAML. Any other file
+/// is used as structured data if it is valid JSON, and as a string otherwise.
+fn file_value(key: &str, path: &str) -> Result<minijinja::Value> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read -D {} from {:?}", key, path))?;
+    let invalid = |format: &str, e: &dyn std::fmt::Display| {
+        crate::error::ShinkansenError::VariableParseError(format!(
+            "Invalid {} in {:?} for -D {}: {}",
+            format, path, key, e
+        ))
+    };
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_ascii_lowercase);
+    let value = match extension.as_deref() {
+        Some("json") => serde_json::from_str(&content).map_err(|e| invalid("JSON", &e))?,
+        Some("yaml" | "yml") => serde_yaml::from_str(&content).map_err(|e| invalid("YAML", &e))?,
+        _ => match serde_json::from_str(&content) {
+            Ok(value) => value,
+            Err(_) => return Ok(minijinja::Value::from(content)),
+        },
+    };
+    Ok(json_to_minijinja_value(value))
+}
+
+/// How the value of a Helm-style `--set-*` flag is interpreted
+#[derive(Debug, Clone, Copy)]
+enum SetKind {
+    /// `--set-string`: always a string, no type detection
+    String,
+    /// `--set-json`: parsed as JSON
+    Json,
+    /// `--set-file`: the contents of the named file
+    File,
+}
+
+/// Load `--set-string`/`--set-json`/`--set-file` values, returning `(top-level name, key)`
+///
+/// Like -D, `--set-string` accepts several comma-separated assignments; the
+/// JSON and file variants take exactly one per flag.
+fn load_set_variables(
+    variables: &mut HashMap<String, minijinja::Value>,
+    values: &[String],
+    kind: SetKind,
+) -> Result<Vec<(String, String)>> {
+    catch_panic("parsing --set variables", || {
+        parse_set_variables(variables, values, kind)
+    })
+}
+
+fn parse_set_variables(
+    variables: &mut HashMap<String, minijinja::Value>,
+    values: &[String],
+    kind: SetKind,
+) -> Result<Vec<(String, String)>> {
+    let mut names = Vec::new();
+    for arg in values {
+        let assignments = match kind {
+            SetKind::String => split_unescaped(arg, ','),
+            SetKind::Json | SetKind::File => vec![arg.as_str()],
+        };
+
+        for assignment in assignments {
+            let (key, raw) = match kind {
+                SetKind::String => split_assignment(assignment, "KEY=VALUE")?,
+                SetKind::Json => split_assignment(assignment, "KEY=JSON")?,
+                SetKind::File => split_assignment(assignment, "KEY=PATH")?,
+            };
+
+            let value = match kind {
+                SetKind::String => minijinja::Value::from(unescape_value(raw)),
+                SetKind::Json => {
+                    let json = serde_json::from_str(raw).map_err(|e| {
+                        crate::error::ShinkansenError::VariableParseError(format!(
+                            "Invalid JSON for --set-json {}: {}",
+                            key, e
+                        ))
+                    })?;
+                    json_to_minijinja_value(json)
+                }
+                SetKind::File => {
+                    let content = std::fs::read_to_string(raw).with_context(|| {
+                        format!("Failed to read --set-file {} from {:?}", key, raw)
+                    })?;
+                    minijinja::Value::from(content)
+                }
+            };
+
+            let name = insert_variable(variables, key, value)?;
+            names.push((name, key.to_string()));
+        }
+    }
+
+    Ok(names)
+}
+
+/// Split `KEY=VALUE` on the first unescaped `=` outside a quoted key segment
+pub(crate) fn split_assignment<'a>(
+    assignment: &'a str,
+    expected: &str,
+) -> Result<(&'a str, &'a str)> {
+    let mut key_end = 0;
+    let mut in_escape = false;
+    let mut quote: Option<char> = None;
+    let mut previous: Option<char> = None;
+
+    for ch in assignment.chars() {
+        let at_segment_start = matches!(previous, None | Some('.' | '['));
+        previous = Some(ch);
+        if let Some(open) = quote {
+            // Everything up to the closing quote belongs to the key
+            if ch == open {
+                quote = None;
             }
+        } else if in_escape {
+            // Skip the escaped character
+            in_escape = false;
+        } else if ch == '\\' {
+            // Start escape sequence
+            in_escape = true;
+        } else if at_segment_start && (ch == '"' || ch == '\'') {
+            // A quote only opens a name at the start of a key segment
+            quote = Some(ch);
+        } else if ch == '=' {
+            // Found unescaped '=' - this is the separator
+            break;
+        }
+        key_end += ch.len_utf8();
+    }
 
-            let key = &single_var[..key_end];
-            let value_with_escapes = &single_var[key_end + 1..]; // Skip the '='
+    if key_end == 0 || key_end >= assignment.len() {
+        return Err(crate::error::ShinkansenError::VariableParseError(format!(
+            "Invalid variable format: '{}'. Use {}",
+            assignment, expected
+        )));
+    }
 
-            // Unescape the value
-            let value = unescape_value(value_with_escapes);
+    Ok((&assignment[..key_end], &assignment[key_end + 1..]))
+}
 
-            // Convert to appropriate type (number, bool, or string)
-            let minijinja_value = string_to_minijinja_value(&value);
+/// One step of a variable key path such as `servers[0].port`
+#[derive(Debug, Clone, PartialEq)]
+enum KeySegment {
+    /// Object field (`.name`)
+    Field(String),
+    /// Existing or next array element (`[0]`)
+    Index(usize),
+    /// New element appended to an array (`[+]`)
+    Append,
+}
 
-            // Handle nested keys (e.g., "foo.bar" becomes {"foo": {"bar": value}})
-            if key.contains('.') {
-                insert_nested_variable(variables, key, minijinja_value);
-            } else {
-                variables.insert(key.to_string(), minijinja_value);
+/// Parse a key like `app.servers[0].port` or `hosts[+]` into its segments
+///
+/// A segment wrapped in double or single quotes is taken literally, so
+/// `a."dotted.key"` and `'weird key=1'` name a single field.
+fn parse_key_path(key: &str) -> Result<Vec<KeySegment>> {
+    let invalid = |reason: &str| {
+        crate::error::ShinkansenError::VariableParseError(format!(
+            "Invalid variable key '{}': {}",
+            key, reason
+        ))
+    };
+
+    let mut segments = Vec::new();
+    let mut field = String::new();
+    // Whether `field` is a complete quoted name (which may be empty)
+    let mut quoted = false;
+    let mut after_index = false;
+    let mut chars = key.chars();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '"' | '\'' if field.is_empty() && !quoted && !after_index => {
+                let mut closed = false;
+                for c in chars.by_ref() {
+                    if c == ch {
+                        closed = true;
+                        break;
+                    }
+                    field.push(c);
+                }
+                if !closed {
+                    return Err(invalid("unterminated quote"));
+                }
+                quoted = true;
+            }
+            '.' => {
+                if !field.is_empty() || quoted {
+                    segments.push(KeySegment::Field(std::mem::take(&mut field)));
+                } else if !after_index {
+                    return Err(invalid("empty key segment"));
+                }
+                quoted = false;
+                after_index = false;
             }
+            '[' => {
+                if !field.is_empty() || quoted {
+                    segments.push(KeySegment::Field(std::mem::take(&mut field)));
+                } else if segments.is_empty() {
+                    return Err(invalid("a key must start with a name"));
+                }
+                quoted = false;
+
+                let mut index = String::new();
+                let mut closed = false;
+                for c in chars.by_ref() {
+                    if c == ']' {
+                        closed = true;
+                        break;
+                    }
+                    index.push(c);
+                }
+                let segment = match index.as_str() {
+                    _ if !closed => return Err(invalid("missing ']'")),
+                    "+" => KeySegment::Append,
+                    digits => {
+                        let index: usize = digits
+                            .parse()
+                            .map_err(|_| invalid("use [N] for an index or [+] to append"))?;
+                        // Arrays are padded up to the index, so bound the allocation
+                        if index > MAX_ARRAY_INDEX {
+                            return Err(invalid(&format!(
+                                "index {} is larger than {}",
+                                index, MAX_ARRAY_INDEX
+                            )));
+                        }
+                        KeySegment::Index(index)
+                    }
+                };
+                segments.push(segment);
+                after_index = true;
+            }
+            _ if after_index => return Err(invalid("expected '.' or '[' after ']'")),
+            _ if quoted => return Err(invalid("expected '.' or '[' after a quoted name")),
+            _ => field.push(ch),
         }
     }
 
-    Ok(())
+    if !field.is_empty() || quoted {
+        segments.push(KeySegment::Field(field));
+    } else if !after_index {
+        return Err(invalid("empty key segment"));
+    }
+
+    Ok(segments)
+}
+
+/// Insert a variable, treating dotted keys (e.g. "foo.bar") and indices
+/// (e.g. "servers[0].port", "hosts[+]") as nested paths
+///
+/// Returns the name of the top-level variable that was set.
+fn insert_variable(
+    variables: &mut HashMap<String, minijinja::Value>,
+    key: &str,
+    value: minijinja::Value,
+) -> Result<String> {
+    let segments = parse_key_path(key)?;
+    let Some((KeySegment::Field(name), rest)) = segments.split_first() else {
+        // parse_key_path guarantees a leading field
+        return Err(crate::error::ShinkansenError::VariableParseError(format!(
+            "Invalid variable key '{}'",
+            key
+        )));
+    };
+
+    let existing = variables.remove(name);
+    variables.insert(name.clone(), merge_nested_value(existing, rest, value));
+    Ok(name.clone())
 }
 
-/// Insert a variable with a dotted key path into a nested structure
-/// For example, "foo.bar.baz" with value 5 becomes {"foo": {"bar": {"baz": 5}}}
-fn insert_nested_variable(
+/// Set variable `name` to `value`, deep-merging objects into an existing object
+pub(crate) fn merge_variable(
     variables: &mut HashMap<String, minijinja::Value>,
-    key_path: &str,
+    name: String,
     value: minijinja::Value,
 ) {
-    let parts: Vec<&str> = key_path.split('.').collect();
+    let existing = variables.remove(&name);
+    variables.insert(name, deep_merge(existing, value));
+}
 
-    if parts.len() == 1 {
-        // No nesting needed
-        variables.insert(parts[0].to_string(), value);
-        return;
+/// Merge `incoming` over `existing`: two objects combine their keys
+/// recursively, anything else (scalars, arrays, null) replaces what was there
+pub(crate) fn deep_merge(
+    existing: Option<minijinja::Value>,
+    incoming: minijinja::Value,
+) -> minijinja::Value {
+    use crate::context::{is_lazy, lazy, resolve};
+
+    let is_map = |value: &minijinja::Value| value.kind() == minijinja::value::ValueKind::Map;
+    match existing {
+        // Whether a lazy value is an object is only known once it is resolved
+        Some(existing) if is_lazy(&existing) || is_lazy(&incoming) => {
+            lazy(move || Ok(deep_merge(Some(resolve(&existing)?), resolve(&incoming)?)))
+        }
+        Some(existing) if is_map(&existing) && is_map(&incoming) => {
+            let mut map = object_entries(existing);
+            for (key, value) in object_entries(incoming) {
+                let merged = deep_merge(map.remove(&key), value);
+                map.insert(key, merged);
+            }
+            minijinja::Value::from(map)
+        }
+        _ => incoming,
     }
+}
 
-    // For now, we'll use a simpler approach that doesn't merge objects
-    // This is a known limitation that could be improved in the future
-    let nested_value = build_nested_object(&parts, 0, value);
-    variables.insert(parts[0].to_string(), nested_value);
+/// Set `value` at `path` inside `existing`, keeping everything else on the way
+///
+/// Objects keep their other keys and arrays their other elements, and an
+/// object value is merged into an object already at `path`. Indexing past the
+/// end of an array pads it with nulls, like Helm. Anything on the path of the
+/// wrong kind is replaced by an empty object or array.
+fn merge_nested_value(
+    existing: Option<minijinja::Value>,
+    path: &[KeySegment],
+    value: minijinja::Value,
+) -> minijinja::Value {
+    let existing = match existing {
+        Some(existing) if crate::context::is_lazy(&existing) => {
+            let path = path.to_vec();
+            return crate::context::lazy(move || {
+                let existing = crate::context::resolve(&existing)?;
+                Ok(merge_nested_value(Some(existing), &path, value))
+            });
+        }
+        existing => existing,
+    };
+    let Some((first, rest)) = path.split_first() else {
+        return deep_merge(existing, value);
+    };
+
+    match first {
+        KeySegment::Field(name) => {
+            let mut map = existing.map(object_entries).unwrap_or_default();
+            let child = map.remove(name);
+            map.insert(name.clone(), merge_nested_value(child, rest, value));
+            minijinja::Value::from(map)
+        }
+        KeySegment::Index(index) => {
+            let mut items = existing.map(sequence_items).unwrap_or_default();
+            if items.len() <= *index {
+                items.resize(*index + 1, minijinja::Value::from(()));
+            }
+            let child = std::mem::replace(&mut items[*index], minijinja::Value::from(()));
+            let child = (!child.is_none()).then_some(child);
+            items[*index] = merge_nested_value(child, rest, value);
+            minijinja::Value::from(items)
+        }
+        KeySegment::Append => {
+            let mut items = existing.map(sequence_items).unwrap_or_default();
+            items.push(merge_nested_value(None, rest, value));
+            minijinja::Value::from(items)
+        }
+    }
 }
 
-/// Recursively build a nested object structure
-fn build_nested_object(parts: &[&str], index: usize, value: minijinja::Value) -> minijinja::Value {
-    if index == parts.len() - 1 {
-        // Base case: we've reached the final part, return the value
-        value
-    } else {
-        // Create an object with the next part as key and recursively build the rest
-        let next_part = parts[index + 1];
+/// The elements of an array value; empty for any other kind of value
+fn sequence_items(value: minijinja::Value) -> Vec<minijinja::Value> {
+    if value.kind() != minijinja::value::ValueKind::Seq {
+        return Vec::new();
+    }
 
-        let nested_value = build_nested_object(parts, index + 1, value);
-        let mut map = std::collections::HashMap::new();
-        map.insert(next_part.to_string(), nested_value);
+    value
+        .try_iter()
+        .map(|items| items.collect())
+        .unwrap_or_default()
+}
 
-        minijinja::Value::from(map)
+/// The entries of an object value; empty for any other kind of value
+fn object_entries(value: minijinja::Value) -> HashMap<String, minijinja::Value> {
+    if value.kind() != minijinja::value::ValueKind::Map {
+        return HashMap::new();
     }
+
+    value
+        .try_iter()
+        .map(|keys| {
+            keys.filter_map(|key| {
+                let item = value.get_item(&key).ok()?;
+                Some((key.as_str().map(str::to_string)?, item))
+            })
+            .collect()
+        })
+        .unwrap_or_default()
 }
 
 /// Split a string on unescaped occurrences of a delimiter character
-/// Handles JSON-like structures (arrays and objects) by not splitting on commas inside brackets/braces
-fn split_unescaped(s: &str, delimiter: char) -> Vec<&str> {
+/// Handles JSON-like structures (arrays and objects) by not splitting on commas inside brackets/braces,
+/// and quoted key names (before the `=` of each part) by not splitting inside the quotes
+pub(crate) fn split_unescaped(s: &str, delimiter: char) -> Vec<&str> {
     let mut result = Vec::new();
     let mut current_start = 0;
-    let chars: Vec<char> = s.chars().collect();
     let mut in_escape = false;
     let mut in_brackets: i32 = 0; // Track nesting level for []
     let mut in_braces: i32 = 0; // Track nesting level for {}
-    let mut pos = 0;
+    let mut in_key = true; // Before the first unquoted '=' of the current part
+    let mut quote: Option<char> = None;
+    let mut previous: Option<char> = None;
 
-    while pos < chars.len() {
-        let ch = chars[pos];
-
-        if in_escape {
+    for (pos, ch) in s.char_indices() {
+        let at_segment_start = matches!(previous, None | Some('.' | '['));
+        previous = Some(ch);
+        if let Some(open) = quote {
+            // Quotes only group characters inside a key name
+            if ch == open {
+                quote = None;
+            }
+        } else if in_escape {
             // Skip the escaped character
             in_escape = false;
         } else if ch == '\\' {
             // Start escape sequence
             in_escape = true;
+        } else if in_key && at_segment_start && (ch == '"' || ch == '\'') {
+            quote = Some(ch);
+        } else if in_key && ch == '=' {
+            in_key = false;
         } else if ch == '[' {
             in_brackets += 1;
         } else if ch == ']' {
@@ -271,9 +1240,9 @@ fn split_unescaped(s: &str, delimiter: char) -> Vec<&str> {
             // Found unescaped delimiter outside of brackets/braces - split here
             result.push(&s[current_start..pos]);
             current_start = pos + ch.len_utf8();
+            in_key = true;
+            previous = None;
         }
-
-        pos += ch.len_utf8();
     }
 
     // Add the remaining part
@@ -288,7 +1257,7 @@ fn split_unescaped(s: &str, delimiter: char) -> Vec<&str> {
 }
 
 /// Unescape special characters in variable values
-/// Supports: \\ (backslash), \, (comma), \= (equals), \[ (left bracket), \] (right bracket), \{ (left brace), \} (right brace)
+/// Supports: \\ (backslash), \, (comma), \= (equals), \[ (left bracket), \] (right bracket), \{ (left brace), \} (right brace), \@ (at sign)
 fn unescape_value(value: &str) -> String {
     let mut result = String::new();
     let mut chars = value.chars().peekable();
@@ -305,6 +1274,7 @@ fn unescape_value(value: &str) -> String {
                     ']' => result.push(']'),   // Escaped right bracket
                     '{' => result.push('{'),   // Escaped left brace
                     '}' => result.push('}'),   // Escaped right brace
+                    '@' => result.push('@'),   // Escaped at sign (a literal leading @ in -D)
                     _ => {
                         // Unknown escape sequence - keep both characters
                         result.push('\\');
@@ -323,6 +1293,19 @@ fn unescape_value(value: &str) -> String {
     result
 }
 
+/// Warn when type detection turned `raw` into a number that reads differently
+///
+/// `version=1.10` becomes `1.1` and `mode=0755` becomes `755`, which is rarely
+/// what was meant.
+fn warn_if_coerced(diagnostics: &Diagnostics, origin: &str, raw: &str, value: &minijinja::Value) {
+    if value.kind() == minijinja::value::ValueKind::Number && value.to_string() != raw {
+        diagnostics.warn(format!(
+            "{}: {:?} was read as the number {}; use --set-string to keep it as text",
+            origin, raw, value
+        ));
+    }
+}
+
 /// Convert a string value to the appropriate minijinja::Value type
 /// Attempts to parse as JSON first (for arrays/objects), then number, then boolean, falls back to string
 fn string_to_minijinja_value(value: &str) -> minijinja::Value {
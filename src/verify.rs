@@ -0,0 +1,188 @@
+use std::path::Path;
+use std::process::Command;
+
+use crate::error::{ContextExt, Result, ShinkansenError};
+
+/// Placeholder replaced with the path of the rendered file in a verify command
+const PATH_PLACEHOLDER: &str = "{}";
+
+/// A command used to validate rendered output before it is written
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifyCommand {
+    /// Only verify outputs with this extension (without the leading dot)
+    pub extension: Option<String>,
+    /// Shell command to run, with `{}` standing in for the rendered file
+    pub command: String,
+}
+
+impl VerifyCommand {
+    /// Parse a `--verify-cmd` value of the form `[.EXT=]CMD`
+    ///
+    /// The extension prefix is only recognized when the value starts with `.`
+    /// and the first `=` comes before any whitespace, so commands that contain
+    /// `=` in their arguments are left intact.
+    pub fn parse(spec: &str) -> Result<Self> {
+        let spec = spec.trim();
+
+        let (extension, command) = match spec.split_once('=') {
+            Some((ext, command))
+                if ext.starts_with('.') && ext.len() > 1 && !ext.contains(char::is_whitespace) =>
+            {
+                (Some(ext[1..].to_string()), command.trim())
+            }
+            _ => (None, spec),
+        };
+
+        if command.is_empty() {
+            return Err(ShinkansenError::ValidationError(format!(
+                "Invalid verify command: '{}'. Use [.EXT=]CMD",
+                spec
+            )));
+        }
+
+        Ok(VerifyCommand {
+            extension,
+            command: command.to_string(),
+        })
+    }
+
+    /// Check whether this command applies to the given output path
+    pub fn applies_to(&self, path: &Path) -> bool {
+        match &self.extension {
+            Some(ext) => path
+                .extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| e == ext),
+            None => true,
+        }
+    }
+
+    /// Run the command against a file, failing on a non-zero exit status
+    fn run(&self, file: &Path) -> Result<()> {
+        let output = file_command(&self.command, file)
+            .output()
+            .with_context(|| format!("Failed to run verify command: {}", self.command))?;
+
+        if output.status.success() {
+            return Ok(());
+        }
+
+        let mut message = format!(
+            "Verify command '{}' failed ({})",
+            self.command, output.status
+        );
+        for stream in [&output.stdout, &output.stderr] {
+            let text = String::from_utf8_lossy(stream);
+            if !text.trim().is_empty() {
+                message.push('\n');
+                message.push_str(text.trim_end());
+            }
+        }
+
+        Err(ShinkansenError::ValidationError(message))
+    }
+}
+
+/// Parse all `--verify-cmd` values
+pub fn parse_verify_commands(specs: &[String]) -> Result<Vec<VerifyCommand>> {
+    specs
+        .iter()
+        .map(|spec| VerifyCommand::parse(spec))
+        .collect()
+}
+
+/// Verify rendered content by running every applicable command against a temporary copy
+///
+/// The temporary copy is named `output` with the output's extension, so tools
+/// that infer the file type from it behave as they would on the real
+/// destination, while nothing else from the output path reaches the command.
+pub fn verify_rendered(
+    commands: &[VerifyCommand],
+    output_path: &Path,
+    content: &str,
+) -> Result<()> {
+    let applicable: Vec<&VerifyCommand> = commands
+        .iter()
+        .filter(|cmd| cmd.applies_to(output_path))
+        .collect();
+
+    if applicable.is_empty() {
+        return Ok(());
+    }
+
+    let temp_dir = tempfile::tempdir().with_context(|| "Failed to create temporary directory")?;
+    let file_name = match output_path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext)
+            if ext
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') =>
+        {
+            format!("output.{}", ext)
+        }
+        _ => "output".to_string(),
+    };
+    let temp_file = temp_dir.path().join(file_name);
+    std::fs::write(&temp_file, content)
+        .with_context(|| format!("Failed to write to {:?}", temp_file))?;
+
+    for command in applicable {
+        command.run(&temp_file).map_err(|err| match err {
+            ShinkansenError::ValidationError(msg) => ShinkansenError::ValidationError(format!(
+                "Verification failed for {}: {}",
+                output_path.display(),
+                msg
+            )),
+            other => other,
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Build a shell command running `command` on `file`
+///
+/// `{}` stands for the file, which is appended when there is no `{}`. On unix
+/// the path reaches the shell as `"$1"` instead of being spliced into the
+/// command line, so spaces or `$(...)` in a file name are never interpreted.
+pub(crate) fn file_command(command: &str, file: &Path) -> Command {
+    #[cfg(windows)]
+    {
+        shell_command(&command_line_for(
+            command,
+            &format!("\"{}\"", file.display()),
+        ))
+    }
+
+    #[cfg(not(windows))]
+    {
+        let mut shell = shell_command(&command_line_for(command, "\"$1\""));
+        shell.arg("sh").arg(file);
+        shell
+    }
+}
+
+/// Substitute `file` for `{}` in a command, or append it when there is no `{}`
+fn command_line_for(command: &str, file: &str) -> String {
+    if command.contains(PATH_PLACEHOLDER) {
+        command.replace(PATH_PLACEHOLDER, file)
+    } else {
+        format!("{} {}", command, file)
+    }
+}
+
+/// Build a command that runs a command line through the platform shell
+pub(crate) fn shell_command(command_line: &str) -> Command {
+    #[cfg(windows)]
+    {
+        let mut command = Command::new("cmd");
+        command.args(["/C", command_line]);
+        command
+    }
+
+    #[cfg(not(windows))]
+    {
+        let mut command = Command::new("sh");
+        command.args(["-c", command_line]);
+        command
+    }
+}
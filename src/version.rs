@@ -0,0 +1,189 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::config::ConfigLoaderFactory;
+use crate::filters::FILTER_PACKS;
+
+/// Cargo features and whether this build has them
+const FEATURES: &[(&str, bool)] = &[
+    ("dhall", cfg!(feature = "dhall")),
+    ("jsonnet", cfg!(feature = "jsonnet")),
+    ("http", cfg!(feature = "http")),
+    ("sqlite", cfg!(feature = "sqlite")),
+    ("ldap", cfg!(feature = "ldap")),
+    ("cloud", cfg!(feature = "cloud")),
+    ("git", cfg!(feature = "git")),
+    ("async", cfg!(feature = "async")),
+    ("clipboard", cfg!(feature = "clipboard")),
+    ("fuzz", cfg!(feature = "fuzz")),
+];
+
+/// What this build of shinkansen can do (`--version --json`)
+///
+/// ```json
+/// {"version":"0.2.0","git_sha":"1a2b3c4d5e6f","features":["http"],
+///  "config_formats":["json","yaml","yml","toml","cue"],
+///  "filter_packs":{"k8s":["b64encode","b64decode","toYaml",...],...}}
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct VersionInfo {
+    pub version: &'static str,
+    /// Commit the binary was built from, when known
+    pub git_sha: Option<&'static str>,
+    /// Enabled cargo features
+    pub features: Vec<&'static str>,
+    /// Config file extensions accepted by `-c`
+    pub config_formats: Vec<&'static str>,
+    /// Filter packs and the filters each one adds
+    pub filter_packs: BTreeMap<&'static str, Vec<&'static str>>,
+}
+
+impl VersionInfo {
+    pub fn current() -> Self {
+        VersionInfo {
+            version: env!("CARGO_PKG_VERSION"),
+            git_sha: option_env!("SHINKANSEN_GIT_SHA"),
+            features: FEATURES
+                .iter()
+                .filter(|(_, enabled)| *enabled)
+                .map(|(name, _)| *name)
+                .collect(),
+            config_formats: ConfigLoaderFactory::supported_extensions(),
+            filter_packs: FILTER_PACKS
+                .iter()
+                .map(|(pack, filters)| (*pack, filters.to_vec()))
+                .collect(),
+        }
+    }
+
+    /// `shinkansen 0.2.0 (1a2b3c4d5e6f)`, as printed by `--version`
+    pub fn text(&self) -> String {
+        match self.git_sha {
+            Some(sha) => format!("shinkansen {} ({})", self.version, sha),
+            None => format!("shinkansen {}", self.version),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+/// A requirement on the shinkansen version, such as `>=0.5` or `>=0.2, <0.4`
+///
+/// Every comma-separated comparator must hold. As in Cargo, `^1.2` and a bare
+/// `1.2` allow changes that keep the leftmost non-zero component, `~1.2`
+/// allows patch changes, and `=0.2` matches any `0.2.x`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VersionReq {
+    text: String,
+    /// Comparators, each a bound and the version it compares with
+    comparators: Vec<(Bound, [u64; 3])>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Bound {
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+}
+
+impl VersionReq {
+    pub fn parse(text: &str) -> std::result::Result<Self, String> {
+        let mut comparators = Vec::new();
+        for part in text.split(',') {
+            let part = part.trim();
+            let (operator, version) = match part.find(|c: char| c.is_ascii_digit()) {
+                Some(index) => part.split_at(index),
+                None => return Err(format!("'{}' has no version", part)),
+            };
+            let (version, parts) = parse_version(version)
+                .ok_or_else(|| format!("'{}' is not a version such as 1.2.3", version))?;
+            match operator.trim() {
+                ">" => comparators.push((Bound::Greater, version)),
+                ">=" => comparators.push((Bound::GreaterOrEqual, version)),
+                "<" => comparators.push((Bound::Less, version)),
+                "<=" => comparators.push((Bound::LessOrEqual, version)),
+                "=" if parts == 3 => {
+                    comparators.push((Bound::GreaterOrEqual, version));
+                    comparators.push((Bound::LessOrEqual, version));
+                }
+                "=" | "~" => {
+                    comparators.push((Bound::GreaterOrEqual, version));
+                    comparators.push((Bound::Less, tilde_limit(version, parts)));
+                }
+                "" | "^" => {
+                    comparators.push((Bound::GreaterOrEqual, version));
+                    comparators.push((Bound::Less, caret_limit(version, parts)));
+                }
+                other => {
+                    return Err(format!(
+                        "unknown operator '{}': use >, >=, <, <=, =, ~ or ^",
+                        other
+                    ));
+                }
+            }
+        }
+        Ok(VersionReq {
+            text: text.trim().to_string(),
+            comparators,
+        })
+    }
+
+    /// Whether `version` satisfies every comparator; pre-release and build
+    /// suffixes (`-beta`, `+abc`) are ignored
+    pub fn matches(&self, version: &str) -> bool {
+        let Some((version, _)) = parse_version(version.split(['-', '+']).next().unwrap_or(""))
+        else {
+            return false;
+        };
+        self.comparators.iter().all(|(bound, other)| match bound {
+            Bound::Greater => version > *other,
+            Bound::GreaterOrEqual => version >= *other,
+            Bound::Less => version < *other,
+            Bound::LessOrEqual => version <= *other,
+        })
+    }
+}
+
+impl std::fmt::Display for VersionReq {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+/// Parse `1`, `1.2` or `1.2.3`, with missing components as 0, and count the given ones
+fn parse_version(text: &str) -> Option<([u64; 3], usize)> {
+    let mut version = [0; 3];
+    let mut parts = 0;
+    for part in text.trim().split('.') {
+        if parts == 3 || part.is_empty() || !part.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        version[parts] = part.parse().ok()?;
+        parts += 1;
+    }
+    Some((version, parts))
+}
+
+/// The first version `~version` excludes
+fn tilde_limit([major, minor, _]: [u64; 3], parts: usize) -> [u64; 3] {
+    if parts == 1 {
+        [major + 1, 0, 0]
+    } else {
+        [major, minor + 1, 0]
+    }
+}
+
+/// The first version `^version` excludes
+fn caret_limit([major, minor, patch]: [u64; 3], parts: usize) -> [u64; 3] {
+    if major > 0 || parts == 1 {
+        [major + 1, 0, 0]
+    } else if minor > 0 || parts == 2 {
+        [0, minor + 1, 0]
+    } else {
+        [0, 0, patch + 1]
+    }
+}
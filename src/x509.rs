@@ -0,0 +1,113 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use minijinja::{Environment, Error, ErrorKind, Value};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use x509_parser::extensions::GeneralName;
+use x509_parser::pem::Pem;
+
+use crate::run::{RunContext, format_timestamp};
+
+/// What `x509_info` reports about a certificate
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CertificateInfo {
+    /// Distinguished name, e.g. `CN=web.example.com, O=Acme`
+    pub subject: String,
+    pub common_name: Option<String>,
+    pub issuer: String,
+    /// Subject alternative names: DNS names, IP addresses, emails and URIs
+    pub sans: Vec<String>,
+    /// Validity period as RFC 3339 UTC timestamps
+    pub not_before: String,
+    pub not_after: String,
+    /// Whole days from the start of the run until expiry; negative once expired
+    pub days_remaining: i64,
+    pub expired: bool,
+    /// Serial number in lowercase hex
+    pub serial: String,
+    pub is_ca: bool,
+    /// SHA-256 of the DER encoding in lowercase hex
+    pub fingerprint_sha256: String,
+}
+
+/// Register `x509_info`, usable as a function and as a filter
+///
+/// ```jinja
+/// {% set cert = x509_info(tls.cert) %}
+/// # {{ cert.common_name }} expires {{ cert.not_after }} ({{ cert.days_remaining }} days)
+/// ```
+pub fn register_x509(env: &mut Environment<'_>) {
+    env.add_function("x509_info", x509_info);
+    env.add_filter("x509_info", x509_info);
+}
+
+fn x509_info(pem: &str) -> Result<Value, Error> {
+    let info = certificate_info(pem, RunContext::current().started_at_epoch)?;
+    Ok(Value::from_serialize(&info))
+}
+
+/// Describe the first certificate of a PEM bundle, with `days_remaining` counted from `now`
+pub fn certificate_info(pem: &str, now: u64) -> Result<CertificateInfo, Error> {
+    let invalid = |reason: String| Error::new(ErrorKind::InvalidOperation, reason);
+    let pem = Pem::iter_from_buffer(pem.as_bytes())
+        .filter_map(|pem| pem.ok())
+        .find(|pem| pem.label == "CERTIFICATE")
+        .ok_or_else(|| invalid("no PEM certificate found".to_string()))?;
+    let cert = pem
+        .parse_x509()
+        .map_err(|e| invalid(format!("invalid certificate: {}", e)))?;
+
+    let sans = match cert.subject_alternative_name() {
+        Ok(Some(extension)) => extension
+            .value
+            .general_names
+            .iter()
+            .filter_map(general_name)
+            .collect(),
+        Ok(None) => Vec::new(),
+        Err(e) => return Err(invalid(format!("invalid subjectAltName: {}", e))),
+    };
+    let not_before = cert.validity().not_before.timestamp();
+    let not_after = cert.validity().not_after.timestamp();
+    let remaining = not_after - now as i64;
+
+    Ok(CertificateInfo {
+        subject: cert.subject().to_string(),
+        common_name: cert
+            .subject()
+            .iter_common_name()
+            .next()
+            .and_then(|cn| cn.as_str().ok())
+            .map(str::to_string),
+        issuer: cert.issuer().to_string(),
+        sans,
+        not_before: format_timestamp(not_before.max(0) as u64),
+        not_after: format_timestamp(not_after.max(0) as u64),
+        days_remaining: remaining.div_euclid(86_400),
+        expired: remaining < 0,
+        serial: hex(cert.raw_serial()),
+        is_ca: cert.is_ca(),
+        fingerprint_sha256: hex(&Sha256::digest(&pem.contents)),
+    })
+}
+
+fn general_name(name: &GeneralName<'_>) -> Option<String> {
+    match name {
+        GeneralName::DNSName(name) | GeneralName::RFC822Name(name) | GeneralName::URI(name) => {
+            Some(name.to_string())
+        }
+        GeneralName::IPAddress(bytes) => {
+            let address = match bytes.len() {
+                4 => IpAddr::V4(Ipv4Addr::from(<[u8; 4]>::try_from(*bytes).ok()?)),
+                16 => IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(*bytes).ok()?)),
+                _ => return None,
+            };
+            Some(address.to_string())
+        }
+        _ => None,
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
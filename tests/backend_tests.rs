@@ -0,0 +1,348 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::Parser;
+
+use shinkansen_lib::backend::{
+    ArchiveBackend, CLIPBOARD_PATH, ObjectStore, OutputBackend, OutputMeta, output_store,
+    validate_output_store,
+};
+use shinkansen_lib::cli::Cli;
+use shinkansen_lib::error::Result;
+use shinkansen_lib::fileio::Compression;
+use shinkansen_lib::output::{OutputDestination, determine_output_destination};
+use shinkansen_lib::per_file::PerFileVars;
+use shinkansen_lib::processing::{process_inputs_to, validate_args};
+
+/// Keeps outputs in memory instead of writing them anywhere
+#[derive(Default)]
+struct MemoryBackend {
+    files: RefCell<Vec<(PathBuf, Vec<u8>, OutputMeta)>>,
+}
+
+impl OutputBackend for MemoryBackend {
+    fn write(&self, path: &Path, content: &[u8], meta: &OutputMeta) -> Result<()> {
+        self.files
+            .borrow_mut()
+            .push((path.to_path_buf(), content.to_vec(), meta.clone()));
+        Ok(())
+    }
+}
+
+#[test]
+fn test_outputs_go_to_a_custom_backend() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let input_dir = temp_dir.path().join("templates");
+    fs::create_dir_all(input_dir.join("nginx")).unwrap();
+    fs::write(input_dir.join("app.conf"), "app={{ name }}").unwrap();
+    fs::write(input_dir.join("nginx/site.conf"), "site={{ name }}").unwrap();
+    let variables = HashMap::from([("name".to_string(), minijinja::Value::from("web"))]);
+
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        input_dir.to_str().unwrap(),
+        "-r",
+        "-o",
+        "out/",
+        "--compress-output",
+        "gzip",
+    ])
+    .unwrap();
+    let backend = MemoryBackend::default();
+    process_inputs_to(
+        &cli,
+        &variables,
+        &PerFileVars::new(),
+        &backend,
+        &Default::default(),
+    )
+    .unwrap();
+
+    let mut files = backend.files.into_inner();
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+    let paths: Vec<&Path> = files.iter().map(|(path, _, _)| path.as_path()).collect();
+    assert_eq!(
+        paths,
+        [
+            Path::new("out/app.conf.gz"),
+            Path::new("out/nginx/site.conf.gz")
+        ]
+    );
+    let (_, content, meta) = &files[0];
+    assert_eq!(
+        Compression::Gzip.decompress(content).unwrap(),
+        b"app=web".to_vec()
+    );
+    assert_eq!(meta.input, input_dir.join("app.conf"));
+    assert_eq!(meta.compression, Some(Compression::Gzip));
+    assert!(!Path::new("out").exists());
+}
+
+#[test]
+fn test_archive_backend_writes_a_tar() {
+    let meta = OutputMeta {
+        input: PathBuf::from("app.conf"),
+        compression: None,
+    };
+    let archive = ArchiveBackend::new();
+    archive
+        .write(Path::new("/out/app.conf"), b"app=web", &meta)
+        .unwrap();
+    let long = format!("{}/{}", "d".repeat(60), "f".repeat(90));
+    archive.write(Path::new(&long), b"", &meta).unwrap();
+    let err = archive
+        .write(Path::new(&"x".repeat(120)), b"", &meta)
+        .unwrap_err();
+    assert!(err.to_string().contains("too long"), "{}", err);
+    let tar = archive.finish();
+
+    // Header, one block of content, a second header, then the two end blocks
+    assert_eq!(tar.len(), 5 * 512);
+    assert_eq!(&tar[..12], b"out/app.conf");
+    assert_eq!(&tar[124..136], b"00000000007\0");
+    assert_eq!(&tar[257..263], b"ustar\0");
+    assert_eq!(&tar[512..519], b"app=web");
+    let checksum: u32 = tar[..512]
+        .iter()
+        .enumerate()
+        .map(|(i, byte)| {
+            if (148..156).contains(&i) {
+                32
+            } else {
+                u32::from(*byte)
+            }
+        })
+        .sum();
+    let stored = std::str::from_utf8(&tar[148..154]).unwrap();
+    assert_eq!(u32::from_str_radix(stored, 8).unwrap(), checksum);
+    assert_eq!(&tar[1024..1114], "f".repeat(90).as_bytes());
+    assert_eq!(&tar[1024 + 345..1024 + 405], "d".repeat(60).as_bytes());
+    assert!(tar[1536..].iter().all(|byte| *byte == 0));
+}
+
+#[test]
+fn test_object_store_urls() {
+    assert_eq!(
+        ObjectStore::from_url("s3://bucket/prefix/"),
+        Some(ObjectStore::S3)
+    );
+    assert_eq!(ObjectStore::from_url("gs://bucket"), Some(ObjectStore::Gcs));
+    assert_eq!(ObjectStore::from_url("out/s3://x"), None);
+    assert_eq!(ObjectStore::from_url("-"), None);
+    assert_eq!(ObjectStore::S3.program(), "aws");
+    assert_eq!(ObjectStore::Gcs.program(), "gcloud");
+
+    let cli = Cli::try_parse_from(["shinkansen", "t.conf", "-o", "out/"]).unwrap();
+    assert_eq!(output_store(&cli), None);
+    validate_output_store(&cli).unwrap();
+}
+
+#[test]
+fn test_object_store_destinations() {
+    let destination = |output: &str, single_file: bool| {
+        let cli = Cli::try_parse_from(["shinkansen", "t.conf", "-o", output]).unwrap();
+        determine_output_destination(&cli, single_file).unwrap()
+    };
+    assert_eq!(
+        destination("s3://bucket/app.conf", true),
+        OutputDestination::SingleFile(PathBuf::from("s3://bucket/app.conf"))
+    );
+    assert_eq!(
+        destination("s3://bucket/prefix/", true),
+        OutputDestination::Directory(PathBuf::from("s3://bucket/prefix/"))
+    );
+    assert_eq!(
+        destination("gs://bucket/prefix", false),
+        OutputDestination::Directory(PathBuf::from("gs://bucket/prefix"))
+    );
+}
+
+#[test]
+fn test_object_store_output_validation() {
+    let cli = Cli::try_parse_from(["shinkansen", "t.conf", "-o", "s3://bucket/prefix/"]).unwrap();
+    let checked = Cli::try_parse_from([
+        "shinkansen",
+        "t.conf",
+        "-o",
+        "s3://bucket/prefix/",
+        "--check",
+    ])
+    .unwrap();
+
+    if cfg!(feature = "cloud") {
+        validate_args(&cli).unwrap();
+        let err = validate_args(&checked).unwrap_err();
+        assert!(
+            err.to_string().contains("--check needs local output files"),
+            "{}",
+            err
+        );
+    } else {
+        let err = validate_args(&cli).unwrap_err();
+        assert!(err.to_string().contains("cloud feature"), "{}", err);
+    }
+}
+
+#[test]
+fn test_clipboard_output() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let conf_d = temp_dir.path().join("conf.d");
+    fs::create_dir_all(&conf_d).unwrap();
+    fs::write(conf_d.join("10-app.conf"), "app={{ name }}\n").unwrap();
+    fs::write(conf_d.join("20-db.conf"), "db={{ name }}\n").unwrap();
+    let variables = HashMap::from([("name".to_string(), minijinja::Value::from("web"))]);
+    let parse = |args: &[&str]| {
+        let mut all = vec!["shinkansen", conf_d.to_str().unwrap(), "-o", "clipboard"];
+        all.extend(args);
+        Cli::try_parse_from(all).unwrap()
+    };
+
+    let cli = parse(&["--concat"]);
+    assert_eq!(
+        determine_output_destination(&cli, true).unwrap(),
+        OutputDestination::Clipboard
+    );
+    let backend = MemoryBackend::default();
+    process_inputs_to(
+        &cli,
+        &variables,
+        &PerFileVars::new(),
+        &backend,
+        &Default::default(),
+    )
+    .unwrap();
+    let files = backend.files.into_inner();
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].0, Path::new(CLIPBOARD_PATH));
+    assert_eq!(files[0].1, b"app=web\ndb=web");
+    assert!(!Path::new("clipboard").exists());
+
+    let err = determine_output_destination(&parse(&[]), false).unwrap_err();
+    assert!(err.to_string().contains("single file"), "{}", err);
+    let two_inputs =
+        Cli::try_parse_from(["shinkansen", "a.conf", "b.conf", "-o", "clipboard"]).unwrap();
+    let err = validate_args(&two_inputs).unwrap_err();
+    assert!(err.to_string().contains("single file"), "{}", err);
+    let err = validate_args(&parse(&["--concat", "--check"])).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("requires a file or directory output"),
+        "{}",
+        err
+    );
+    if cfg!(feature = "clipboard") {
+        validate_args(&cli).unwrap();
+        let err = validate_args(&parse(&["--concat", "--compress-output", "gzip"])).unwrap_err();
+        assert!(err.to_string().contains("--compress-output"), "{}", err);
+    } else {
+        let err = validate_args(&cli).unwrap_err();
+        assert!(err.to_string().contains("clipboard feature"), "{}", err);
+    }
+}
+
+#[cfg(all(target_os = "linux", feature = "clipboard"))]
+#[test]
+fn test_clipboard_falls_back_to_stdout_without_a_display() {
+    use shinkansen_lib::backend::ClipboardBackend;
+
+    // With a display server this would replace the clipboard of whoever runs the tests
+    if std::env::var_os("DISPLAY").is_some() || std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        return;
+    }
+    let meta = OutputMeta {
+        input: PathBuf::from("app.conf"),
+        compression: None,
+    };
+    let path = Path::new(CLIPBOARD_PATH);
+    ClipboardBackend.write(path, b"app=web", &meta).unwrap();
+    let err = ClipboardBackend
+        .write(path, &[0xff, 0xfe], &meta)
+        .unwrap_err();
+    assert!(err.to_string().contains("not text"), "{}", err);
+}
+
+#[cfg(all(unix, feature = "cloud"))]
+#[test]
+fn test_outputs_are_uploaded_with_the_store_cli() {
+    use std::os::unix::fs::PermissionsExt;
+
+    use shinkansen_lib::processing::process_inputs;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let bin = temp_dir.path().join("bin");
+    let uploads = bin.join("uploads");
+    fs::create_dir_all(&uploads).unwrap();
+    // Stand-ins for `aws s3 cp - URL` and `gcloud storage cp - URL`, saving stdin under the URL
+    for (program, command) in [("aws", "s3"), ("gcloud", "storage")] {
+        let script = bin.join(program);
+        fs::write(
+            &script,
+            format!(
+                "#!/bin/sh\n\
+                 [ \"$1 $2 $3\" = \"{} cp -\" ] || exit 2\n\
+                 case \"$4\" in *denied*) echo 'access denied' >&2; exit 1;; esac\n\
+                 cat > \"$(dirname \"$0\")/uploads/$(printf %s \"$4\" | tr '/:' '__')\"\n",
+                command
+            ),
+        )
+        .unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+    let path = std::env::var("PATH").unwrap_or_default();
+    // TODO: Audit that the environment access only happens in single-threaded code.
+    unsafe { std::env::set_var("PATH", format!("{}:{}", bin.display(), path)) };
+
+    let input_dir = temp_dir.path().join("templates");
+    fs::create_dir_all(input_dir.join("nginx")).unwrap();
+    fs::write(input_dir.join("app.conf"), "app={{ name }}").unwrap();
+    fs::write(input_dir.join("nginx/site.conf"), "site={{ name }}").unwrap();
+    let variables = HashMap::from([("name".to_string(), minijinja::Value::from("web"))]);
+
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        input_dir.to_str().unwrap(),
+        "-r",
+        "-o",
+        "s3://bucket/prefix/",
+    ])
+    .unwrap();
+    validate_args(&cli).unwrap();
+    process_inputs(&cli, &variables, &Default::default()).unwrap();
+    let uploaded = |url: &str| fs::read_to_string(uploads.join(url.replace(['/', ':'], "_")));
+    assert_eq!(uploaded("s3://bucket/prefix/app.conf").unwrap(), "app=web");
+    assert_eq!(
+        uploaded("s3://bucket/prefix/nginx/site.conf").unwrap(),
+        "site=web"
+    );
+
+    let single = input_dir.join("app.conf");
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        single.to_str().unwrap(),
+        "-o",
+        "gs://bucket/app.conf",
+    ])
+    .unwrap();
+    process_inputs(&cli, &variables, &Default::default()).unwrap();
+    assert_eq!(uploaded("gs://bucket/app.conf").unwrap(), "app=web");
+
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        single.to_str().unwrap(),
+        "-o",
+        "s3://denied/app.conf",
+    ])
+    .unwrap();
+    let err = process_inputs(&cli, &variables, &Default::default()).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("Failed to upload s3://denied/app.conf: access denied"),
+        "{}",
+        err
+    );
+
+    // TODO: Audit that the environment access only happens in single-threaded code.
+    unsafe { std::env::set_var("PATH", path) };
+}
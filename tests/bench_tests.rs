@@ -0,0 +1,92 @@
+use clap::Parser;
+use shinkansen_lib::bench::{BenchReport, run_benchmark};
+use shinkansen_lib::cli::{Cli, Commands};
+use shinkansen_lib::rendering::RenderOptions;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+fn report(latencies_ms: &[u64]) -> BenchReport {
+    BenchReport {
+        name: "t".to_string(),
+        latencies: latencies_ms
+            .iter()
+            .map(|ms| Duration::from_millis(*ms))
+            .collect(),
+        total: Duration::from_millis(latencies_ms.iter().sum()),
+    }
+}
+
+#[test]
+fn test_percentiles_use_nearest_rank() {
+    let report = report(&(1..=100).collect::<Vec<_>>());
+    assert_eq!(report.percentile(50.0), Duration::from_millis(50));
+    assert_eq!(report.percentile(90.0), Duration::from_millis(90));
+    assert_eq!(report.percentile(99.9), Duration::from_millis(100));
+    assert_eq!(report.percentile(0.0), Duration::from_millis(1));
+    assert_eq!(report.mean(), Duration::from_micros(50_500));
+}
+
+#[test]
+fn test_throughput() {
+    let report = report(&[250, 250, 250, 250]);
+    assert!((report.throughput() - 4.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_run_benchmark_counts_measured_iterations() {
+    let mut variables = HashMap::new();
+    variables.insert("name".to_string(), minijinja::Value::from("bench"));
+
+    let report = run_benchmark(
+        "{% for i in range(10) %}{{ name|upper }}{% endfor %}",
+        &variables,
+        "loop",
+        &RenderOptions::default(),
+        25,
+        5,
+    )
+    .unwrap();
+    assert_eq!(report.latencies.len(), 25);
+    assert!(report.latencies.windows(2).all(|pair| pair[0] <= pair[1]));
+
+    let text = report.report();
+    assert!(text.starts_with("bench: loop\n"));
+    assert!(text.contains("  iterations  25\n"));
+    assert!(text.contains("  p99 "));
+}
+
+#[test]
+fn test_run_benchmark_reports_template_errors() {
+    let err = run_benchmark(
+        "{{ missing }}",
+        &HashMap::new(),
+        "t",
+        &RenderOptions::default(),
+        3,
+        0,
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("t"));
+
+    assert!(run_benchmark("x", &HashMap::new(), "t", &RenderOptions::default(), 0, 0).is_err());
+}
+
+#[test]
+fn test_bench_subcommand_parsing() {
+    let cli =
+        Cli::try_parse_from(["shinkansen", "-D", "a=1", "bench", "app.yaml", "-n", "50"]).unwrap();
+    assert_eq!(cli.variables, vec!["a=1"]);
+    match cli.command {
+        Some(Commands::Bench {
+            template,
+            iterations,
+            warmup,
+        }) => {
+            assert_eq!(template, PathBuf::from("app.yaml"));
+            assert_eq!(iterations, 50);
+            assert_eq!(warmup, 10);
+        }
+        other => panic!("unexpected command: {:?}", other),
+    }
+}
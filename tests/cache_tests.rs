@@ -0,0 +1,157 @@
+use clap::Parser;
+use serde_json::json;
+use shinkansen_lib::cache::{Cache, parse_duration};
+use shinkansen_lib::cli::Cli;
+use shinkansen_lib::error::ShinkansenError;
+use std::cell::Cell;
+use std::time::Duration;
+use tempfile::tempdir;
+
+fn cache_in(dir: &std::path::Path, ttl: Option<Duration>, offline: bool) -> Cache {
+    Cache {
+        dir: Some(dir.to_path_buf()),
+        ttl,
+        offline,
+        ..Default::default()
+    }
+}
+
+fn unreachable() -> Result<serde_json::Value, ShinkansenError> {
+    Err(ShinkansenError::DataSourceError(
+        "connection refused".to_string(),
+    ))
+}
+
+#[test]
+fn test_parse_duration() {
+    assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+    assert_eq!(parse_duration("250ms"), Ok(Duration::from_millis(250)));
+    assert_eq!(parse_duration("30s"), Ok(Duration::from_secs(30)));
+    assert_eq!(parse_duration("5m"), Ok(Duration::from_secs(300)));
+    assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(7200)));
+    assert_eq!(parse_duration("1d"), Ok(Duration::from_secs(86400)));
+    assert!(parse_duration("").is_err());
+    assert!(parse_duration("5w").is_err());
+    assert!(parse_duration("m").is_err());
+}
+
+#[test]
+fn test_cache_ttl_flag() {
+    let cli = Cli::try_parse_from(["shinkansen", "--cache-ttl", "10m"]).unwrap();
+    assert_eq!(cli.cache_ttl, Some(Duration::from_secs(600)));
+    assert!(Cli::try_parse_from(["shinkansen", "--cache-ttl", "soon"]).is_err());
+}
+
+#[test]
+fn test_disabled_cache_always_fetches() {
+    let cache = Cache::default();
+    let calls = Cell::new(0);
+    for _ in 0..2 {
+        let data = cache
+            .get_or_fetch("url:https://example.com", || {
+                calls.set(calls.get() + 1);
+                Ok(json!({"n": 1}))
+            })
+            .unwrap();
+        assert_eq!(data, json!({"n": 1}));
+    }
+    assert_eq!(calls.get(), 2);
+}
+
+#[test]
+fn test_fresh_entry_skips_fetch() {
+    let temp_dir = tempdir().unwrap();
+    let cache = cache_in(temp_dir.path(), Some(Duration::from_secs(3600)), false);
+
+    cache
+        .get_or_fetch("url:https://example.com", || Ok(json!("first")))
+        .unwrap();
+    let data = cache
+        .get_or_fetch("url:https://example.com", || Ok(json!("second")))
+        .unwrap();
+    assert_eq!(data, json!("first"));
+
+    // Different sources never share an entry
+    let data = cache
+        .get_or_fetch("url:https://example.org", || Ok(json!("other")))
+        .unwrap();
+    assert_eq!(data, json!("other"));
+}
+
+#[test]
+fn test_expired_entry_is_refreshed() {
+    let temp_dir = tempdir().unwrap();
+    let cache = cache_in(temp_dir.path(), Some(Duration::ZERO), false);
+
+    cache
+        .get_or_fetch("consul:app/", || Ok(json!("old")))
+        .unwrap();
+    let data = cache
+        .get_or_fetch("consul:app/", || Ok(json!("new")))
+        .unwrap();
+    assert_eq!(data, json!("new"));
+}
+
+#[test]
+fn test_failed_fetch_falls_back_to_stale_entry() {
+    let temp_dir = tempdir().unwrap();
+    let cache = cache_in(temp_dir.path(), Some(Duration::ZERO), false);
+
+    cache
+        .get_or_fetch("url:https://flaky", || Ok(json!("cached")))
+        .unwrap();
+    let data = cache
+        .get_or_fetch("url:https://flaky", unreachable)
+        .unwrap();
+    assert_eq!(data, json!("cached"));
+    let warnings = cache.diagnostics.warnings();
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].contains("using cached copy of url:https://flaky"));
+
+    let result = cache.get_or_fetch("url:https://never-fetched", unreachable);
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_offline_uses_cache_only() {
+    let temp_dir = tempdir().unwrap();
+    let online = cache_in(temp_dir.path(), Some(Duration::ZERO), false);
+    online
+        .get_or_fetch("url:https://example.com", || Ok(json!("cached")))
+        .unwrap();
+
+    let offline = cache_in(temp_dir.path(), None, true);
+    let data = offline
+        .get_or_fetch("url:https://example.com", || {
+            panic!("fetched while offline")
+        })
+        .unwrap();
+    assert_eq!(data, json!("cached"));
+
+    let result = offline.get_or_fetch("url:https://example.org", || {
+        panic!("fetched while offline")
+    });
+    assert!(result.unwrap_err().to_string().contains("--offline"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_cache_entries_are_private() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp_dir = tempdir().unwrap();
+    let dir = temp_dir.path().join("shinkansen");
+    let cache = cache_in(&dir, Some(Duration::from_secs(3600)), false);
+    cache
+        .get_or_fetch("consul:http://127.0.0.1:8500/app", || {
+            Ok(json!({"db_password": "hunter2"}))
+        })
+        .unwrap();
+
+    let mode =
+        |path: &std::path::Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+    assert_eq!(mode(&dir), 0o700);
+    let entries: Vec<_> = std::fs::read_dir(&dir).unwrap().collect();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(mode(&entries[0].as_ref().unwrap().path()), 0o600);
+}
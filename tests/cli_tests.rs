@@ -11,7 +11,7 @@ fn test_cli_parsing() {
     assert!(!cli.recursive);
     assert!(cli.output.is_none());
     assert!(cli.variables.is_empty());
-    assert!(cli.config.is_none());
+    assert!(cli.config.is_empty());
     assert!(cli.env.is_none());
 }
 
@@ -36,7 +36,7 @@ fn test_cli_with_all_options() {
     assert!(cli.recursive);
     assert_eq!(cli.output, Some("output.txt".to_string()));
     assert_eq!(cli.variables, vec!["key=value"]);
-    assert!(cli.config.is_some());
+    assert!(!cli.config.is_empty());
     assert_eq!(cli.env, Some("VAR1,VAR2".to_string()));
 }
 
@@ -0,0 +1,138 @@
+use clap::Parser;
+use shinkansen_lib::cli::{Cli, Commands};
+use shinkansen_lib::completion::{
+    complete_config, config_argument, profile_names, variable_keys, write_registration,
+};
+use shinkansen_lib::config::{ConfigLoader, TomlConfigLoader, YamlConfigLoader};
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::path::PathBuf;
+use tempfile::tempdir;
+
+const CONFIG: &str = r#"
+name = "web"
+port = 8080
+
+[vars.linux]
+shell = "bash"
+
+[vars.'profile:prod']
+replicas = 3
+
+[vars.'profile:staging']
+debug = true
+
+[derived]
+url = "http://{{ name }}:{{ port }}"
+
+[_meta.tag]
+description = "Image tag"
+
+[_meta.name]
+description = "Application name"
+"#;
+
+fn args(args: &[&str]) -> Vec<OsString> {
+    args.iter().map(OsString::from).collect()
+}
+
+#[test]
+fn test_variable_keys() {
+    let config = TomlConfigLoader.load_config(CONFIG).unwrap();
+    let keys = variable_keys(&config);
+    assert_eq!(
+        keys.keys().collect::<Vec<_>>(),
+        vec!["debug", "name", "port", "replicas", "shell", "tag", "url"]
+    );
+    assert_eq!(keys["name"].as_deref(), Some("Application name"));
+    assert_eq!(keys["tag"].as_deref(), Some("Image tag"));
+    assert_eq!(keys["port"], None);
+}
+
+#[test]
+fn test_profile_names() {
+    let config = TomlConfigLoader.load_config(CONFIG).unwrap();
+    assert_eq!(profile_names(&config), vec!["prod", "staging"]);
+
+    let config = YamlConfigLoader
+        .load_config(
+            "profiles:\n  dev: {debug: true}\n  prod: {replicas: 5}\nvars:\n  profile:prod: {}\n",
+        )
+        .unwrap();
+    assert_eq!(profile_names(&config), vec!["dev", "prod"]);
+    assert!(variable_keys(&config).contains_key("replicas"));
+
+    let config = TomlConfigLoader.load_config("name = \"web\"").unwrap();
+    assert!(profile_names(&config).is_empty());
+}
+
+#[test]
+fn test_config_argument() {
+    assert_eq!(
+        config_argument(args(&["shinkansen", "-c", "a.yaml", "-D", ""])),
+        Some(PathBuf::from("a.yaml"))
+    );
+    assert_eq!(
+        config_argument(args(&["shinkansen", "--config=b.toml", "-D"])),
+        Some(PathBuf::from("b.toml"))
+    );
+    assert_eq!(
+        config_argument(args(&["shinkansen", "-cc.json"])),
+        Some(PathBuf::from("c.json"))
+    );
+    assert_eq!(config_argument(args(&["shinkansen", "-D", "a=1"])), None);
+}
+
+#[test]
+fn test_config_argument_after_completion_separator() {
+    // COMPLETE=bash shinkansen -- shinkansen -c values.yaml --profile
+    assert_eq!(
+        config_argument(args(&[
+            "shinkansen",
+            "--",
+            "shinkansen",
+            "--config",
+            "values.yaml",
+            "--profile"
+        ])),
+        Some(PathBuf::from("values.yaml"))
+    );
+}
+
+#[test]
+fn test_complete_config_offers_config_files() {
+    let temp_dir = tempdir().unwrap();
+    fs::write(temp_dir.path().join("values.yaml"), "a: 1").unwrap();
+    fs::write(temp_dir.path().join("notes.txt"), "").unwrap();
+    let prefix = format!("{}/", temp_dir.path().display());
+
+    let candidates: Vec<String> = complete_config(OsStr::new(&prefix))
+        .iter()
+        .map(|candidate| candidate.get_value().to_string_lossy().into_owned())
+        .collect();
+    assert_eq!(candidates, vec![format!("{}values.yaml", prefix)]);
+}
+
+#[test]
+fn test_write_registration() {
+    let mut script = Vec::new();
+    write_registration("bash", "shinkansen", &mut script).unwrap();
+    let script = String::from_utf8(script).unwrap();
+    assert!(script.contains("COMPLETE=\"bash\""));
+    assert!(script.contains("complete -o nospace -o bashdefault"));
+
+    let err = write_registration("tcsh", "shinkansen", &mut Vec::new()).unwrap_err();
+    assert!(
+        err.to_string()
+            .starts_with("Dynamic completions are not available for tcsh")
+    );
+}
+
+#[test]
+fn test_completions_dynamic_flag() {
+    let cli = Cli::try_parse_from(["shinkansen", "completions", "zsh", "--dynamic"]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Some(Commands::Completions { dynamic: true, .. })
+    ));
+}
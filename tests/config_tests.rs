@@ -1,5 +1,11 @@
+use std::collections::HashMap;
+
+#[cfg(feature = "http")]
+mod common;
+
 use shinkansen_lib::config::{
-    ConfigLoader, ConfigLoaderFactory, JsonConfigLoader, TomlConfigLoader, YamlConfigLoader,
+    ConfigContext, ConfigLoader, ConfigLoaderFactory, JsonConfigLoader, TomlConfigLoader,
+    YamlConfigLoader, is_config_url, remote_config_format, validate_with_cue_schema,
 };
 
 #[test]
@@ -122,3 +128,403 @@ fn test_invalid_toml_config() {
     let result = loader.load_config(invalid_toml);
     assert!(result.is_err());
 }
+
+#[test]
+fn test_supported_extensions() {
+    let extensions = ConfigLoaderFactory::supported_extensions();
+    for ext in ["json", "yaml", "yml", "toml", "cue"] {
+        assert!(extensions.contains(&ext));
+    }
+    assert_eq!(extensions.contains(&"dhall"), cfg!(feature = "dhall"));
+    assert_eq!(extensions.contains(&"jsonnet"), cfg!(feature = "jsonnet"));
+}
+
+#[test]
+fn test_load_config_file_reads_from_disk() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().join("config.json");
+    std::fs::write(&path, r#"{"name": "from-file"}"#).unwrap();
+
+    let config = JsonConfigLoader.load_config_file(&path).unwrap();
+    assert_eq!(config.variables.get("name").unwrap(), "from-file");
+}
+
+#[cfg(feature = "dhall")]
+#[test]
+fn test_dhall_config_loader_registered() {
+    let loader = ConfigLoaderFactory::create_loader("dhall").unwrap();
+    assert_eq!(loader.supported_extensions(), &["dhall"]);
+}
+
+#[cfg(feature = "dhall")]
+#[test]
+fn test_dhall_config_evaluates_in_process() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        temp_dir.path().join("ports.dhall"),
+        "{ http = 80, https = 443 }",
+    )
+    .unwrap();
+    let path = temp_dir.path().join("config.dhall");
+    std::fs::write(
+        &path,
+        r#"let greet = \(name : Text) -> "hello ${name}" in { greeting = greet "world", ports = ./ports.dhall }"#,
+    )
+    .unwrap();
+
+    let loader = ConfigLoaderFactory::create_loader("dhall").unwrap();
+    let config = loader.load_config_file(&path).unwrap();
+    assert_eq!(config.variables["greeting"], "hello world");
+    assert_eq!(config.variables["ports"]["https"], 443);
+
+    let error = loader.load_config("{ broken = ").unwrap_err();
+    assert!(error.to_string().contains("Invalid Dhall config"));
+}
+
+#[test]
+fn test_cue_config_loader_registered() {
+    let loader = ConfigLoaderFactory::create_loader("cue").unwrap();
+    assert_eq!(loader.supported_extensions(), &["cue"]);
+    assert!(ConfigLoaderFactory::supported_extensions().contains(&"cue"));
+}
+
+#[test]
+fn test_cue_needs_the_cue_executable() {
+    let empty = tempfile::tempdir().unwrap();
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    // TODO: Audit that the environment access only happens in single-threaded code.
+    unsafe { std::env::set_var("PATH", empty.path()) };
+
+    let loader = ConfigLoaderFactory::create_loader("cue").unwrap();
+    let load_error = loader.load_config("name: \"app\"").unwrap_err();
+    let schema = empty.path().join("schema.cue");
+    let vet_error = validate_with_cue_schema(&HashMap::new(), &schema).unwrap_err();
+
+    // TODO: Audit that the environment access only happens in single-threaded code.
+    unsafe { std::env::set_var("PATH", path) };
+    for error in [load_error, vet_error] {
+        assert!(
+            error.to_string().contains("No cue executable on PATH"),
+            "{}",
+            error
+        );
+        assert!(error.hint().unwrap().starts_with("install cue"));
+    }
+}
+
+#[cfg(feature = "jsonnet")]
+#[test]
+fn test_jsonnet_config_loader_registered() {
+    let loader = ConfigLoaderFactory::create_loader("jsonnet").unwrap();
+    assert_eq!(loader.supported_extensions(), &["jsonnet"]);
+}
+
+#[cfg(feature = "jsonnet")]
+#[test]
+fn test_jsonnet_config_evaluates_in_process() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        temp_dir.path().join("lib.libsonnet"),
+        "{ port(n):: 8000 + n }",
+    )
+    .unwrap();
+    let path = temp_dir.path().join("config.jsonnet");
+    std::fs::write(
+        &path,
+        "local lib = import 'lib.libsonnet'; { name: std.asciiUpper('web'), port: lib.port(80) }",
+    )
+    .unwrap();
+
+    let loader = ConfigLoaderFactory::create_loader("jsonnet").unwrap();
+    let config = loader.load_config_file(&path).unwrap();
+    assert_eq!(config.variables["name"], "WEB");
+    assert_eq!(config.variables["port"], 8080);
+
+    let config = loader
+        .load_config("{ tags: [x * 2 for x in [1, 2]] }")
+        .unwrap();
+    assert_eq!(config.variables["tags"], serde_json::json!([2, 4]));
+}
+
+#[cfg(feature = "jsonnet")]
+#[test]
+fn test_jsonnet_errors_fail_the_config() {
+    let loader = ConfigLoaderFactory::create_loader("jsonnet").unwrap();
+    for content in ["{ broken: ", "{ lazy: error 'boom' }"] {
+        let error = loader.load_config(content).unwrap_err();
+        assert!(
+            error.to_string().contains("Invalid Jsonnet config"),
+            "{}",
+            error
+        );
+    }
+}
+
+#[cfg(not(feature = "jsonnet"))]
+#[test]
+fn test_jsonnet_needs_the_feature() {
+    assert!(ConfigLoaderFactory::create_loader("jsonnet").is_none());
+}
+
+fn linux_context(profile: Option<&str>) -> ConfigContext {
+    ConfigContext {
+        os: "linux".to_string(),
+        family: "unix".to_string(),
+        profile: profile.map(str::to_string),
+    }
+}
+
+const CONDITIONAL_TOML: &str = r#"
+shell = "sh"
+log_level = "info"
+separator = "/"
+
+[vars.windows]
+shell = "powershell"
+separator = "\\"
+
+[vars.unix]
+shell = "bash"
+
+[vars.linux]
+shell = "zsh"
+service_manager = "systemd"
+
+[vars.'profile:prod']
+log_level = "warn"
+"#;
+
+#[test]
+fn test_conditional_sections_match_platform() {
+    let config = TomlConfigLoader
+        .load_config(CONDITIONAL_TOML)
+        .unwrap()
+        .resolve_conditionals(&linux_context(None))
+        .unwrap();
+
+    // The OS section is more specific than the family section
+    assert_eq!(config.variables.get("shell").unwrap(), "zsh");
+    assert_eq!(config.variables.get("service_manager").unwrap(), "systemd");
+    assert_eq!(config.variables.get("separator").unwrap(), "/");
+    assert_eq!(config.variables.get("log_level").unwrap(), "info");
+    assert!(!config.variables.contains_key("vars"));
+}
+
+#[test]
+fn test_conditional_sections_match_windows() {
+    let context = ConfigContext {
+        os: "windows".to_string(),
+        family: "windows".to_string(),
+        profile: None,
+    };
+    let config = TomlConfigLoader
+        .load_config(CONDITIONAL_TOML)
+        .unwrap()
+        .resolve_conditionals(&context)
+        .unwrap();
+
+    assert_eq!(config.variables.get("shell").unwrap(), "powershell");
+    assert_eq!(config.variables.get("separator").unwrap(), "\\");
+    assert!(!config.variables.contains_key("service_manager"));
+}
+
+#[test]
+fn test_conditional_sections_match_profile() {
+    let config = TomlConfigLoader
+        .load_config(CONDITIONAL_TOML)
+        .unwrap()
+        .resolve_conditionals(&linux_context(Some("prod")))
+        .unwrap();
+    assert_eq!(config.variables.get("log_level").unwrap(), "warn");
+
+    let config = TomlConfigLoader
+        .load_config(CONDITIONAL_TOML)
+        .unwrap()
+        .resolve_conditionals(&linux_context(Some("staging")))
+        .unwrap();
+    assert_eq!(config.variables.get("log_level").unwrap(), "info");
+}
+
+#[test]
+fn test_conditional_sections_in_yaml() {
+    let yaml_content = "
+name: app
+vars:
+  linux:
+    name: app-linux
+  profile:dev:
+    debug: true
+";
+    let config = YamlConfigLoader
+        .load_config(yaml_content)
+        .unwrap()
+        .resolve_conditionals(&linux_context(Some("dev")))
+        .unwrap();
+    assert_eq!(config.variables.get("name").unwrap(), "app-linux");
+    assert_eq!(config.variables.get("debug").unwrap(), true);
+}
+
+#[test]
+fn test_profiles_section_applies_after_conditional_sections() {
+    let yaml_content = "
+shell: sh
+replicas: 1
+vars:
+  linux:
+    shell: bash
+  profile:prod:
+    replicas: 2
+profiles:
+  prod:
+    replicas: 5
+    resources: {cpu: 2}
+";
+    let config = YamlConfigLoader
+        .load_config(yaml_content)
+        .unwrap()
+        .resolve_conditionals(&linux_context(Some("prod")))
+        .unwrap();
+    assert_eq!(config.variables.get("shell").unwrap(), "bash");
+    assert_eq!(config.variables.get("replicas").unwrap(), 5);
+    assert_eq!(config.variables.get("resources").unwrap()["cpu"], 2);
+    assert!(!config.variables.contains_key("profiles"));
+
+    // A profiles value that is not a table of tables stays a regular variable
+    let config = YamlConfigLoader
+        .load_config("profiles: [a, b]\n")
+        .unwrap()
+        .resolve_conditionals(&linux_context(Some("a")))
+        .unwrap();
+    assert!(config.variables.get("profiles").unwrap().is_array());
+}
+
+#[test]
+fn test_unknown_condition_is_rejected() {
+    let result = TomlConfigLoader
+        .load_config("[vars.linx]\nname = \"x\"\n")
+        .unwrap()
+        .resolve_conditionals(&linux_context(None));
+    assert!(result.is_err());
+    assert!(
+        result
+            .unwrap_err()
+            .to_string()
+            .contains("Unknown condition '[vars.linx]'")
+    );
+}
+
+#[test]
+fn test_plain_vars_variable_is_kept() {
+    let config = JsonConfigLoader
+        .load_config(r#"{"vars": {"a": 1, "b": 2}}"#)
+        .unwrap()
+        .resolve_conditionals(&linux_context(None))
+        .unwrap();
+    assert_eq!(
+        config.variables.get("vars").unwrap(),
+        &serde_json::json!({"a": 1, "b": 2})
+    );
+}
+
+#[test]
+fn test_current_context() {
+    let context = ConfigContext::current(Some("prod"));
+    assert_eq!(context.os, std::env::consts::OS);
+    assert_eq!(context.family, std::env::consts::FAMILY);
+    assert!(context.matches("profile:prod").unwrap());
+    assert!(context.matches(std::env::consts::FAMILY).unwrap());
+    assert!(!context.matches("profile:dev").unwrap());
+}
+
+#[test]
+fn test_remote_config_format() {
+    assert!(is_config_url("https://configs.internal/app.json".as_ref()));
+    assert!(is_config_url("http://localhost:8080/app".as_ref()));
+    assert!(!is_config_url("configs/https.json".as_ref()));
+
+    let format = |content_type, url| remote_config_format(content_type, url);
+    assert_eq!(
+        format(Some("application/json; charset=utf-8"), "https://h/app"),
+        Some("json".to_string())
+    );
+    assert_eq!(
+        format(Some("application/yaml"), "https://h/app.json"),
+        Some("yaml".to_string())
+    );
+    assert_eq!(
+        format(Some("text/plain"), "https://h/app.TOML?ref=main#top"),
+        Some("toml".to_string())
+    );
+    assert_eq!(format(None, "https://h/app.cue"), Some("cue".to_string()));
+    assert_eq!(format(Some("text/plain"), "https://h/app"), None);
+    assert_eq!(
+        format(Some("text/plain"), "https://h.example/app.txt"),
+        None
+    );
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn test_config_from_url() {
+    use clap::Parser;
+    use shinkansen_lib::cli::Cli;
+    use shinkansen_lib::variables::collect_variable_context;
+
+    let json = format!(
+        "{}/app",
+        common::serve_once(
+            r#"{"db": {"host": "db.internal", "port": 5432}}"#,
+            "application/json"
+        )
+    );
+    let yaml = format!(
+        "{}/prod.yaml",
+        common::serve_once("db:\n  host: db.prod\nregion: {{ region }}\n", "text/plain")
+    );
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        "-c",
+        &json,
+        "-c",
+        &yaml,
+        "--config-template",
+        "-D",
+        "region=eu-west-1",
+        "--http-timeout",
+        "5s",
+    ])
+    .unwrap();
+    let context = collect_variable_context(&cli).unwrap();
+    let db = context.values.get("db").unwrap();
+    assert_eq!(db.get_attr("host").unwrap().as_str(), Some("db.prod"));
+    assert_eq!(db.get_attr("port").unwrap().as_i64(), Some(5432));
+    assert_eq!(
+        context.values.get("region").unwrap().as_str(),
+        Some("eu-west-1")
+    );
+    assert_eq!(
+        context.provenance.winner("db").unwrap().to_string(),
+        format!("config {}", yaml)
+    );
+
+    let unknown = format!("{}/app", common::serve_once("a: 1", "text/plain"));
+    let cli = Cli::try_parse_from(["shinkansen", "-c", &unknown]).unwrap();
+    let err = collect_variable_context(&cli).unwrap_err();
+    assert!(
+        err.to_string().contains("Cannot tell the format of config"),
+        "{}",
+        err
+    );
+}
+
+#[cfg(not(feature = "http"))]
+#[test]
+fn test_config_url_requires_http_feature() {
+    use clap::Parser;
+    use shinkansen_lib::cli::Cli;
+    use shinkansen_lib::variables::collect_variables;
+
+    let cli = Cli::try_parse_from(["shinkansen", "-c", "http://127.0.0.1:1/app.json"]).unwrap();
+    let err = collect_variables(&cli).unwrap_err();
+    assert!(err.to_string().contains("'http' feature"), "{}", err);
+}
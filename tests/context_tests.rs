@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use clap::Parser;
+use minijinja::Value;
+use serde_json::json;
+
+use shinkansen_lib::cli::Cli;
+use shinkansen_lib::context::{is_lazy, lazy, resolve, resolve_all};
+use shinkansen_lib::describe::describe_variables;
+use shinkansen_lib::error::ShinkansenError;
+use shinkansen_lib::rendering::render_template;
+use shinkansen_lib::sources::{SourceTask, collect_source_variables};
+use shinkansen_lib::variables::collect_variable_context;
+
+/// A lazy value counting how often it is resolved
+fn counted(fetches: &Arc<AtomicUsize>) -> Value {
+    let fetches = fetches.clone();
+    lazy(move || {
+        fetches.fetch_add(1, Ordering::SeqCst);
+        Ok(Value::from_serialize(json!({"host": "db1", "port": 5432})))
+    })
+}
+
+#[test]
+fn test_lazy_values_resolve_only_when_read() {
+    let fetches = Arc::new(AtomicUsize::new(0));
+    let variables = HashMap::from([
+        ("name".to_string(), Value::from("web")),
+        ("db".to_string(), counted(&fetches)),
+    ]);
+
+    let rendered = render_template("app={{ name }}", &variables, "app.conf").unwrap();
+    assert_eq!(rendered, "app=web");
+    assert_eq!(fetches.load(Ordering::SeqCst), 0);
+
+    for _ in 0..2 {
+        let rendered =
+            render_template("db={{ db.host }}:{{ db.port }}", &variables, "db.conf").unwrap();
+        assert_eq!(rendered, "db=db1:5432");
+    }
+    assert_eq!(fetches.load(Ordering::SeqCst), 1);
+
+    assert!(is_lazy(&variables["db"]));
+    assert!(!is_lazy(&variables["name"]));
+    let resolved = resolve_all(&variables).unwrap();
+    assert_eq!(resolved["db"].get_attr("port").unwrap(), Value::from(5432));
+}
+
+#[test]
+fn test_lazy_value_errors_fail_the_render() {
+    let variables = HashMap::from([(
+        "inventory".to_string(),
+        lazy(|| {
+            Err(ShinkansenError::DataSourceError(
+                "inventory service unavailable".to_string(),
+            ))
+        }),
+    )]);
+
+    assert!(render_template("static", &variables, "static.conf").is_ok());
+    let err = render_template("{{ inventory.hosts }}", &variables, "hosts.conf").unwrap_err();
+    let message = err.to_string();
+    assert!(
+        message.contains("inventory service unavailable"),
+        "{}",
+        message
+    );
+    assert!(resolve(&variables["inventory"]).is_err());
+}
+
+#[test]
+fn test_deferred_sources_keep_their_precedence() {
+    let fetches = Arc::new(AtomicUsize::new(0));
+    let counter = fetches.clone();
+    let tasks = vec![
+        SourceTask::new("first", move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Ok(vec![(
+                "db".to_string(),
+                json!({"host": "db1", "port": 5432}),
+            )])
+        })
+        .provides("db")
+        .defer(),
+        SourceTask::new("second", || {
+            Ok(vec![("db".to_string(), json!({"port": 6432}))])
+        }),
+        // Without a declared variable a source cannot be deferred
+        SourceTask::new("third", || Ok(vec![("region".to_string(), json!("eu"))])).defer(),
+    ];
+    let mut variables = HashMap::new();
+    let fetched = collect_source_variables(&mut variables, tasks).unwrap();
+
+    let deferred: Vec<bool> = fetched.iter().map(|source| source.deferred).collect();
+    assert_eq!(deferred, [true, false, false]);
+    assert_eq!(fetched[0].variables, ["db"]);
+    assert_eq!(fetches.load(Ordering::SeqCst), 0);
+
+    let rendered = render_template(
+        "{{ region }} {{ db.host }}:{{ db.port }}",
+        &variables,
+        "db.conf",
+    )
+    .unwrap();
+    assert_eq!(rendered, "eu db1:6432");
+    assert_eq!(fetches.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_lazy_sources_flag() {
+    // Nothing listens on port 1, so reading the variable fails
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        "--lazy-sources",
+        "--data-url",
+        "hosts=http://127.0.0.1:1/hosts.json",
+        "-D",
+        "hosts.extra=true",
+        "-D",
+        "name=web",
+    ])
+    .unwrap();
+    let context = collect_variable_context(&cli).unwrap();
+
+    let rendered = render_template("app={{ name }}", &context.values, "app.conf").unwrap();
+    assert_eq!(rendered, "app=web");
+    assert!(render_template("{{ hosts }}", &context.values, "hosts.conf").is_err());
+
+    let described = describe_variables(&context);
+    let row = described
+        .lines()
+        .find(|line| line.starts_with("hosts"))
+        .unwrap();
+    assert!(row.contains("deferred"), "{}", row);
+
+    let eager = Cli::try_parse_from([
+        "shinkansen",
+        "--data-url",
+        "hosts=http://127.0.0.1:1/hosts.json",
+    ])
+    .unwrap();
+    assert!(collect_variable_context(&eager).is_err());
+}
@@ -0,0 +1,81 @@
+use minijinja::Value;
+use shinkansen_lib::coverage::Coverage;
+use shinkansen_lib::rendering::{RenderOptions, render_template_with_options};
+use std::collections::{BTreeSet, HashMap};
+
+fn variables() -> HashMap<String, Value> {
+    HashMap::from([
+        ("port".to_string(), Value::from(8080)),
+        ("debug".to_string(), Value::from(false)),
+        ("name".to_string(), Value::from("web")),
+        ("legacy".to_string(), Value::from(1)),
+    ])
+}
+
+#[test]
+fn test_render_records_variables_read() {
+    let coverage = Coverage::default();
+    let options = RenderOptions {
+        coverage: Some(coverage.clone()),
+        ..Default::default()
+    };
+
+    let app = render_template_with_options(
+        "{% for i in range(2) %}{{ port }}{% endfor %}",
+        &variables(),
+        "app.conf",
+        &options,
+    )
+    .unwrap();
+    // `name` sits in a branch that does not run, so it is not read
+    render_template_with_options(
+        "{% if debug %}{{ name }}{% endif %}{{ port }}",
+        &variables(),
+        "web.conf",
+        &options,
+    )
+    .unwrap();
+    render_template_with_options("static", &variables(), "static.txt", &options).unwrap();
+
+    assert_eq!(app, "80808080");
+    let used = coverage.used();
+    assert_eq!(used["app.conf"], BTreeSet::from(["port".to_string()]));
+    assert_eq!(
+        used["web.conf"],
+        BTreeSet::from(["debug".to_string(), "port".to_string()])
+    );
+    assert!(used["static.txt"].is_empty());
+}
+
+#[test]
+fn test_coverage_report() {
+    let coverage = Coverage::default();
+    let options = RenderOptions {
+        coverage: Some(coverage.clone()),
+        ..Default::default()
+    };
+    render_template_with_options("{{ port }}", &variables(), "app.conf", &options).unwrap();
+    render_template_with_options("{{ port }}{{ name }}", &variables(), "web.conf", &options)
+        .unwrap();
+
+    let variables = variables();
+    assert_eq!(
+        coverage.report(variables.keys(), Some("prod")),
+        "coverage: 2 of 4 variables used by 2 templates (profile prod)\n\
+         coverage:   debug   0/2  unused\n\
+         coverage:   legacy  0/2  unused\n\
+         coverage:   name    1/2  web.conf\n\
+         coverage:   port    2/2  app.conf, web.conf\n"
+    );
+}
+
+#[test]
+fn test_tracked_context_still_iterates() {
+    let options = RenderOptions {
+        coverage: Some(Coverage::default()),
+        ..Default::default()
+    };
+    let result =
+        render_template_with_options("{{ name|upper }}", &variables(), "t", &options).unwrap();
+    assert_eq!(result, "WEB");
+}
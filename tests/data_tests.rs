@@ -0,0 +1,282 @@
+#[cfg(feature = "http")]
+mod common;
+
+use clap::Parser;
+use serde_json::json;
+use shinkansen_lib::cli::Cli;
+use shinkansen_lib::data::{
+    LdapQuery, LdapScope, parse_document, parse_named_source, parse_sql_source,
+};
+use shinkansen_lib::variables::collect_variables;
+
+#[test]
+fn test_parse_named_source() {
+    assert_eq!(
+        parse_named_source("hosts=https://example.com/api", "--data-url").unwrap(),
+        ("hosts", "https://example.com/api")
+    );
+    assert!(parse_named_source("missing-name", "--data-url").is_err());
+    assert!(parse_named_source("=https://example.com", "--data-url").is_err());
+    assert!(parse_named_source("name=", "--data-url").is_err());
+}
+
+#[test]
+fn test_parse_document_by_content_type() {
+    let value = parse_document("{\"a\": 1}", Some("application/json"), "https://x/api").unwrap();
+    assert_eq!(value, json!({"a": 1}));
+
+    let value = parse_document("a: 1", Some("application/yaml"), "https://x/api").unwrap();
+    assert_eq!(value, json!({"a": 1}));
+}
+
+#[test]
+fn test_parse_document_by_extension() {
+    let value = parse_document("a = 1", None, "https://x/config.toml?ref=main").unwrap();
+    assert_eq!(value, json!({"a": 1}));
+}
+
+#[test]
+fn test_parse_document_fallback() {
+    let value = parse_document("[1, 2]", Some("text/plain"), "https://x/api").unwrap();
+    assert_eq!(value, json!([1, 2]));
+
+    let value = parse_document("a: [1, 2]", None, "https://x/api").unwrap();
+    assert_eq!(value, json!({"a": [1, 2]}));
+}
+
+#[test]
+fn test_parse_sql_source() {
+    assert_eq!(
+        parse_sql_source("sqlite:inventory.db:SELECT host, ip FROM hosts").unwrap(),
+        ("inventory.db", "SELECT host, ip FROM hosts")
+    );
+    assert_eq!(
+        parse_sql_source("sqlite:C:\\data\\cmdb.db:SELECT 1").unwrap(),
+        ("C:\\data\\cmdb.db", "SELECT 1")
+    );
+    assert_eq!(
+        parse_sql_source("sqlite:db.sqlite:SELECT * FROM t WHERE a = 'x:y'").unwrap(),
+        ("db.sqlite", "SELECT * FROM t WHERE a = 'x:y'")
+    );
+    assert!(parse_sql_source("postgres:db:SELECT 1").is_err());
+    assert!(parse_sql_source("sqlite:db.sqlite").is_err());
+    assert!(parse_sql_source("sqlite:db.sqlite:").is_err());
+}
+
+#[cfg(not(feature = "sqlite"))]
+#[test]
+fn test_data_sql_requires_sqlite_feature() {
+    let args = vec!["shinkansen", "--data-sql", "hosts=sqlite:cmdb.db:SELECT 1"];
+    let cli = Cli::try_parse_from(args).unwrap();
+    let result = collect_variables(&cli);
+
+    assert!(result.is_err());
+    assert!(result.unwrap_err().to_string().contains("'sqlite' feature"));
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn test_data_sql_rows_as_objects() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let database = temp_dir.path().join("cmdb.db");
+    let connection = rusqlite::Connection::open(&database).unwrap();
+    connection
+        .execute_batch(
+            "CREATE TABLE hosts (host TEXT, ip TEXT, cpus INTEGER);
+             INSERT INTO hosts VALUES ('web1', '10.0.0.1', 2), ('db1', '10.0.1.1', 8);",
+        )
+        .unwrap();
+    drop(connection);
+
+    let spec = format!(
+        "hosts=sqlite:{}:SELECT host, cpus FROM hosts ORDER BY host",
+        database.display()
+    );
+    let cli = Cli::try_parse_from(vec!["shinkansen", "--data-sql", &spec]).unwrap();
+    let variables = collect_variables(&cli).unwrap();
+
+    let hosts = variables.get("hosts").unwrap();
+    assert_eq!(hosts.len(), Some(2));
+    let first = hosts.get_item_by_index(0).unwrap();
+    assert_eq!(first.get_attr("host").unwrap().as_str(), Some("db1"));
+    assert_eq!(first.get_attr("cpus").unwrap().as_i64(), Some(8));
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn test_data_sql_missing_database() {
+    let spec = "hosts=sqlite:/nonexistent/cmdb.db:SELECT 1";
+    let cli = Cli::try_parse_from(vec!["shinkansen", "--data-sql", spec]).unwrap();
+    let result = collect_variables(&cli);
+
+    assert!(result.is_err());
+    assert!(result.unwrap_err().to_string().contains("SQLite query"));
+}
+
+#[cfg(not(feature = "http"))]
+#[test]
+fn test_data_url_requires_http_feature() {
+    let args = vec!["shinkansen", "--data-url", "hosts=http://127.0.0.1:1/hosts"];
+    let cli = Cli::try_parse_from(args).unwrap();
+    let result = collect_variables(&cli);
+
+    assert!(result.is_err());
+    assert!(result.unwrap_err().to_string().contains("'http' feature"));
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn test_data_url_with_jmespath() {
+    let base = common::serve_once(
+        r#"{"items": [{"name": "web1", "up": true}, {"name": "web2", "up": false}]}"#,
+        "application/json",
+    );
+    let spec = format!("hosts={}/inventory#items[?up].name", base);
+    let cli = Cli::try_parse_from(vec!["shinkansen", "--data-url", &spec]).unwrap();
+    let variables = collect_variables(&cli).unwrap();
+
+    let hosts = variables.get("hosts").unwrap();
+    assert_eq!(hosts.len(), Some(1));
+    assert_eq!(hosts.get_item_by_index(0).unwrap().as_str(), Some("web1"));
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn test_data_url_with_graphql_query() {
+    let (base, request) = common::serve_request(
+        r#"{"data": {"hosts": [{"name": "web1"}, {"name": "db1"}]}}"#,
+        "application/json",
+    );
+    let temp_dir = tempfile::tempdir().unwrap();
+    let query = temp_dir.path().join("hosts.graphql");
+    std::fs::write(&query, "{ hosts { name } }").unwrap();
+    let spec = format!("hosts={}/graphql#hosts[].name", base);
+    let graphql = format!("hosts=@{}", query.display());
+    let args = vec!["shinkansen", "--data-url", &spec, "--graphql", &graphql];
+    let cli = Cli::try_parse_from(args).unwrap();
+    let variables = collect_variables(&cli).unwrap();
+
+    let hosts = variables.get("hosts").unwrap();
+    assert_eq!(hosts.len(), Some(2));
+    assert_eq!(hosts.get_item_by_index(1).unwrap().as_str(), Some("db1"));
+    let request = request.recv().unwrap();
+    assert!(request.starts_with("POST /graphql "), "{}", request);
+    assert!(
+        request.ends_with(r#"{"query":"{ hosts { name } }"}"#),
+        "{}",
+        request
+    );
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn test_graphql_errors_fail_the_source() {
+    let base = common::serve_once(
+        r#"{"data": null, "errors": [{"message": "Cannot query field \"nope\""}]}"#,
+        "application/json",
+    );
+    let spec = format!("hosts={}/graphql", base);
+    let args = vec![
+        "shinkansen",
+        "--data-url",
+        &spec,
+        "--graphql",
+        "hosts={ nope }",
+    ];
+    let cli = Cli::try_parse_from(args).unwrap();
+    let err = collect_variables(&cli).unwrap_err();
+    assert!(
+        format!("{:#}", err).contains("Cannot query field \"nope\""),
+        "{:#}",
+        err
+    );
+}
+
+#[test]
+fn test_graphql_needs_a_data_url() {
+    let args = vec!["shinkansen", "--graphql", "hosts={ hosts { name } }"];
+    let cli = Cli::try_parse_from(args).unwrap();
+    let err = collect_variables(&cli).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("--graphql hosts has no --data-url"),
+        "{}",
+        err
+    );
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn test_data_url_overridden_by_cli_define() {
+    let base = common::serve_once("region: us-east-1", "application/yaml");
+    let spec = format!("site={}/site.yaml", base);
+    let args = vec!["shinkansen", "--data-url", &spec, "-D", "site=local"];
+    let cli = Cli::try_parse_from(args).unwrap();
+    let variables = collect_variables(&cli).unwrap();
+
+    assert_eq!(variables.get("site").unwrap().as_str(), Some("local"));
+}
+
+#[test]
+fn test_parse_ldap_query() {
+    let query = LdapQuery::parse(
+        "ldaps://dc1.example.com:636/ou=people,dc=example,dc=com?uid,mail?one?(objectClass=person)",
+    )
+    .unwrap();
+    assert_eq!(query.server, "ldaps://dc1.example.com:636");
+    assert_eq!(query.base_dn, "ou=people,dc=example,dc=com");
+    assert_eq!(query.attributes, vec!["uid", "mail"]);
+    assert_eq!(query.scope, LdapScope::OneLevel);
+    assert_eq!(query.filter, "(objectClass=person)");
+}
+
+#[test]
+fn test_parse_ldap_query_filter_shorthand() {
+    let query =
+        LdapQuery::parse("ldap://dc1/dc=example,dc=com?(%26(objectClass=person)(uid=a*))").unwrap();
+    assert_eq!(query.server, "ldap://dc1");
+    assert!(query.attributes.is_empty());
+    assert_eq!(query.scope, LdapScope::Subtree);
+    assert_eq!(query.filter, "(&(objectClass=person)(uid=a*))");
+
+    let defaults = LdapQuery::parse("ldap://dc1").unwrap();
+    assert_eq!(defaults.base_dn, "");
+    assert_eq!(defaults.filter, "(objectClass=*)");
+}
+
+#[test]
+fn test_parse_ldap_query_invalid() {
+    assert!(LdapQuery::parse("http://dc1/dc=example").is_err());
+    assert!(LdapQuery::parse("ldap://dc1/dc=example?cn?deep?(cn=*)").is_err());
+    assert!(LdapQuery::parse("ldap://dc1/dc=example?cn?sub?(cn=%zz)").is_err());
+}
+
+#[cfg(not(feature = "ldap"))]
+#[test]
+fn test_data_ldap_requires_ldap_feature() {
+    let args = vec![
+        "shinkansen",
+        "--data-ldap",
+        "users=ldap://dc1/dc=example?(uid=*)",
+    ];
+    let cli = Cli::try_parse_from(args).unwrap();
+    let result = collect_variables(&cli);
+
+    assert!(result.is_err());
+    assert!(result.unwrap_err().to_string().contains("'ldap' feature"));
+}
+
+#[cfg(feature = "ldap")]
+#[test]
+fn test_data_ldap_unreachable_server() {
+    let args = vec![
+        "shinkansen",
+        "--data-ldap",
+        "users=ldap://127.0.0.1:1/dc=example?(uid=*)",
+    ];
+    let cli = Cli::try_parse_from(args).unwrap();
+    let result = collect_variables(&cli);
+
+    assert!(result.is_err());
+    assert!(result.unwrap_err().to_string().contains("LDAP search"));
+}
@@ -0,0 +1,178 @@
+use clap::Parser;
+use shinkansen_lib::cli::{Cli, Commands};
+use shinkansen_lib::describe::describe_variables;
+use shinkansen_lib::provenance::VariableSource;
+use shinkansen_lib::variables::{collect_variable_context, collect_variables};
+use std::fs;
+use tempfile::tempdir;
+
+const CONFIG: &str = r#"
+replicas = 2
+name = "web"
+
+[_meta.replicas]
+description = "Number of pods"
+type = "integer"
+default = 1
+
+[_meta.tag]
+description = "Image tag"
+type = "string"
+
+[_meta.region]
+type = "string"
+default = "us-east-1"
+
+[_meta.name]
+type = "integer"
+"#;
+
+fn context_for(extra_args: &[&str]) -> shinkansen_lib::variables::VariableContext {
+    let temp_dir = tempdir().unwrap();
+    let config_path = temp_dir.path().join("config.toml");
+    fs::write(&config_path, CONFIG).unwrap();
+
+    let mut args = vec!["shinkansen", "-c", config_path.to_str().unwrap()];
+    args.extend_from_slice(extra_args);
+    let cli = Cli::try_parse_from(args).unwrap();
+    collect_variable_context(&cli).unwrap()
+}
+
+#[test]
+fn test_meta_block_is_not_a_variable() {
+    let context = context_for(&[]);
+    assert!(!context.values.contains_key("_meta"));
+    assert_eq!(context.meta.len(), 4);
+    assert_eq!(
+        context.meta["replicas"].description.as_deref(),
+        Some("Number of pods")
+    );
+}
+
+#[test]
+fn test_meta_defaults_fill_missing_variables() {
+    let context = context_for(&[]);
+    assert_eq!(
+        context.values.get("region").unwrap().as_str(),
+        Some("us-east-1")
+    );
+    assert_eq!(context.values.get("replicas").unwrap().as_i64(), Some(2));
+    assert!(matches!(
+        context.provenance.winner("region"),
+        Some(VariableSource::Default(_))
+    ));
+
+    let context = context_for(&["-D", "region=eu-west-1"]);
+    assert_eq!(
+        context.values.get("region").unwrap().as_str(),
+        Some("eu-west-1")
+    );
+    assert_eq!(
+        context.provenance.winner("region"),
+        Some(&VariableSource::Cli {
+            flag: "-D",
+            key: "region".to_string(),
+            index: 1
+        })
+    );
+}
+
+#[test]
+fn test_provenance_lists_every_source_in_order() {
+    let context = context_for(&["-D", "replicas=5,settings.debug=true"]);
+    let sources = context.provenance.sources("replicas");
+    assert_eq!(sources.len(), 2);
+    assert!(matches!(sources[0], VariableSource::Config(_)));
+    assert_eq!(
+        sources[1],
+        VariableSource::Cli {
+            flag: "-D",
+            key: "replicas".to_string(),
+            index: 1
+        }
+    );
+
+    // Nested -D keys are attributed to their top-level variable
+    assert_eq!(
+        context.provenance.winner("settings"),
+        Some(&VariableSource::Cli {
+            flag: "-D",
+            key: "settings.debug".to_string(),
+            index: 2
+        })
+    );
+}
+
+#[test]
+fn test_describe_table() {
+    let table = describe_variables(&context_for(&["-D", "extra=1"]));
+    let lines: Vec<&str> = table.lines().collect();
+
+    assert!(lines[0].starts_with("NAME"));
+    assert!(lines[0].contains("DESCRIPTION"));
+    // Rows are sorted by name
+    let names: Vec<&str> = lines[1..]
+        .iter()
+        .map(|line| line.split_whitespace().next().unwrap())
+        .collect();
+    assert_eq!(names, vec!["extra", "name", "region", "replicas", "tag"]);
+
+    let row = |name: &str| {
+        *lines
+            .iter()
+            .find(|line| line.starts_with(&format!("{} ", name)))
+            .unwrap()
+    };
+    assert!(row("extra").contains("-D extra"));
+    assert!(row("name").contains("string (expected integer)"));
+    assert!(row("region").contains("\"us-east-1\""));
+    assert!(row("replicas").contains("Number of pods"));
+    assert!(row("tag").contains("undefined (string)"));
+}
+
+#[test]
+fn test_invalid_meta_type_is_rejected() {
+    let temp_dir = tempdir().unwrap();
+    let config_path = temp_dir.path().join("config.yaml");
+    fs::write(&config_path, "_meta:\n  port:\n    type: int\n").unwrap();
+
+    let cli = Cli::try_parse_from(["shinkansen", "-c", config_path.to_str().unwrap()]).unwrap();
+    let err = collect_variables(&cli).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("Invalid type 'int' for _meta.port")
+    );
+}
+
+#[test]
+fn test_describe_subcommand_parses_after_options() {
+    let cli = Cli::try_parse_from(["shinkansen", "-D", "a=1", "describe"]).unwrap();
+    assert!(matches!(cli.command, Some(Commands::Describe)));
+    assert_eq!(cli.variables, vec!["a=1"]);
+}
+
+#[test]
+fn test_explain_lists_winner_and_overridden_sources() {
+    let context = context_for(&["-D", "name=api", "-D", "name=worker,extra=1"]);
+    let explanation = context.provenance.explain();
+    let lines: Vec<&str> = explanation.lines().collect();
+
+    assert!(lines.contains(&"extra: -D extra (#3)"));
+    let name = lines
+        .iter()
+        .find(|line| line.starts_with("name: "))
+        .unwrap();
+    assert!(name.starts_with("name: -D name (#2), overriding -D name (#1), config "));
+    assert!(name.ends_with("config.toml"));
+
+    // Sorted by variable name
+    let mut sorted = lines.clone();
+    sorted.sort();
+    assert_eq!(lines, sorted);
+}
+
+#[test]
+fn test_explain_vars_flag() {
+    let cli = Cli::try_parse_from(["shinkansen", "--explain-vars"]).unwrap();
+    assert!(cli.explain_vars);
+}
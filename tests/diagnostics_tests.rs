@@ -0,0 +1,131 @@
+use clap::Parser;
+use shinkansen_lib::cli::Cli;
+use shinkansen_lib::diagnostics::Diagnostics;
+use shinkansen_lib::processing::process_inputs;
+use shinkansen_lib::variables::collect_variable_context;
+use std::collections::HashMap;
+use std::fs;
+use tempfile::tempdir;
+
+#[test]
+fn test_report_lists_warnings_in_order() {
+    let diagnostics = Diagnostics::default();
+    assert!(diagnostics.is_empty());
+    diagnostics.warn("first");
+    diagnostics.clone().warn("second");
+    assert_eq!(diagnostics.warnings(), vec!["first", "second"]);
+    assert_eq!(diagnostics.report(), "warning: first\nwarning: second\n");
+}
+
+#[test]
+fn test_finish_with_deny_warnings() {
+    let diagnostics = Diagnostics::default();
+    assert!(diagnostics.finish(true).is_ok());
+
+    diagnostics.warn("something odd");
+    assert!(diagnostics.finish(false).is_ok());
+    let err = diagnostics.finish(true).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "1 warning treated as errors (--deny-warnings)"
+    );
+}
+
+#[test]
+fn test_deny_warnings_flag() {
+    let cli = Cli::try_parse_from(["shinkansen", "--deny-warnings"]).unwrap();
+    assert!(cli.deny_warnings);
+}
+
+#[test]
+fn test_binary_file_in_directory_is_skipped() {
+    let temp_dir = tempdir().unwrap();
+    let input_dir = temp_dir.path().join("templates");
+    let output_dir = temp_dir.path().join("out");
+    fs::create_dir_all(&input_dir).unwrap();
+    fs::write(input_dir.join("app.txt"), "{{ 1 + 1 }}").unwrap();
+    fs::write(
+        input_dir.join("logo.png"),
+        [0x89, b'P', b'N', b'G', 0x00, 0xff],
+    )
+    .unwrap();
+
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        input_dir.to_str().unwrap(),
+        "-o",
+        output_dir.to_str().unwrap(),
+    ])
+    .unwrap();
+    let diagnostics = Diagnostics::default();
+    process_inputs(&cli, &HashMap::new(), &diagnostics).unwrap();
+
+    assert_eq!(fs::read_to_string(output_dir.join("app.txt")).unwrap(), "2");
+    assert!(!output_dir.join("logo.png").exists());
+    let warnings = diagnostics.warnings();
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].starts_with("Skipped binary file"));
+    assert!(warnings[0].contains("logo.png"));
+}
+
+#[test]
+fn test_single_binary_file_is_an_error() {
+    let temp_dir = tempdir().unwrap();
+    let input = temp_dir.path().join("data.bin");
+    fs::write(&input, [0xff, 0xfe, 0x00]).unwrap();
+
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        input.to_str().unwrap(),
+        "-o",
+        temp_dir.path().join("out.bin").to_str().unwrap(),
+    ])
+    .unwrap();
+    let diagnostics = Diagnostics::default();
+    let err = process_inputs(&cli, &HashMap::new(), &diagnostics).unwrap_err();
+    assert!(err.to_string().starts_with("Failed to read file"));
+    assert!(diagnostics.is_empty());
+}
+
+#[test]
+fn test_unset_env_name_is_a_warning() {
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        "--env",
+        "SHINKANSEN_DIAGNOSTICS_UNSET_A,SHINKANSEN_DIAGNOSTICS_UNSET_B",
+    ])
+    .unwrap();
+    let context = collect_variable_context(&cli).unwrap();
+    assert_eq!(
+        context.diagnostics.warnings(),
+        vec![
+            "--env names not set in the environment: \
+             SHINKANSEN_DIAGNOSTICS_UNSET_A, SHINKANSEN_DIAGNOSTICS_UNSET_B"
+        ]
+    );
+}
+
+#[test]
+fn test_surprising_number_coercion_is_a_warning() {
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        "-D",
+        "version=1.10,mode=0755,replicas=3,ratio=1.5,name=web",
+    ])
+    .unwrap();
+    let context = collect_variable_context(&cli).unwrap();
+    let warnings = context.diagnostics.warnings();
+    assert_eq!(warnings.len(), 2);
+    assert_eq!(
+        warnings[0],
+        "-D version: \"1.10\" was read as the number 1.1; use --set-string to keep it as text"
+    );
+    assert!(warnings[1].starts_with("-D mode: \"0755\" was read as the number 755"));
+}
+
+#[test]
+fn test_set_string_does_not_warn() {
+    let cli = Cli::try_parse_from(["shinkansen", "--set-string", "version=1.10"]).unwrap();
+    let context = collect_variable_context(&cli).unwrap();
+    assert!(context.diagnostics.is_empty());
+}
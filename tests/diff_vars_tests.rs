@@ -0,0 +1,122 @@
+use std::fs;
+
+use clap::Parser;
+use serde_json::json;
+
+use shinkansen_lib::cli::{Cli, Commands};
+use shinkansen_lib::diff_vars::{VarChange, diff_runs, diff_values, other_side};
+
+#[test]
+fn test_diff_values_by_path() {
+    let mut diffs = Vec::new();
+    let old = json!({"image": {"repo": "web", "tag": "1"}, "ports": [80], "debug": true});
+    let new = json!({"image": {"repo": "web", "tag": "2"}, "ports": [80, 443], "replicas": 3});
+    diff_values("app", Some(&old), Some(&new), false, &mut diffs);
+
+    let changes: Vec<(&str, &VarChange)> = diffs
+        .iter()
+        .map(|diff| (diff.path.as_str(), &diff.change))
+        .collect();
+    assert_eq!(
+        changes,
+        [
+            ("app.debug", &VarChange::Removed(json!(true))),
+            ("app.image.tag", &VarChange::Changed(json!("1"), json!("2"))),
+            ("app.ports[1]", &VarChange::Added(json!(443))),
+            ("app.replicas", &VarChange::Added(json!(3))),
+        ]
+    );
+
+    // A value that changes type is reported as a whole
+    let mut diffs = Vec::new();
+    diff_values(
+        "a",
+        Some(&json!({"b": 1})),
+        Some(&json!([1])),
+        false,
+        &mut diffs,
+    );
+    assert_eq!(diffs.len(), 1);
+    assert_eq!(
+        diffs[0].change,
+        VarChange::Changed(json!({"b": 1}), json!([1]))
+    );
+}
+
+#[test]
+fn test_diff_vars_between_config_sets() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let staging = temp_dir.path().join("staging.yaml");
+    fs::write(
+        &staging,
+        "app:\n  replicas: 1\n  db:\n    host: db-staging\n    password: s3cret\nlegacy: true\n",
+    )
+    .unwrap();
+    let prod = temp_dir.path().join("prod.yaml");
+    fs::write(
+        &prod,
+        "app:\n  replicas: 4\n  db:\n    host: db-prod\n    password: hunter2\n",
+    )
+    .unwrap();
+
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        "-c",
+        staging.to_str().unwrap(),
+        "-D",
+        "region=eu",
+        "diff-vars",
+        "-c",
+        prod.to_str().unwrap(),
+        "-D",
+        "region=us",
+    ])
+    .unwrap();
+    let Some(Commands::DiffVars {
+        config,
+        profile,
+        variables,
+    }) = &cli.command
+    else {
+        panic!("{:?}", cli.command);
+    };
+    let other = other_side(&cli, config, profile.as_deref(), variables);
+    assert_eq!(other.config, [prod]);
+    assert_eq!(other.variables, ["region=eu", "region=us"]);
+
+    let report = diff_runs(&cli, &other).unwrap();
+    assert!(report.has_differences());
+    assert_eq!(
+        report.report(),
+        "~ app.db.host: \"db-staging\" -> \"db-prod\"\n\
+         ~ app.db.password: <redacted> -> <redacted>\n\
+         ~ app.replicas: 1 -> 4\n\
+         - legacy: true\n\
+         ~ region: \"eu\" -> \"us\"\n\
+         5 difference(s)\n"
+    );
+
+    let same = other_side(&cli, &[], None, &[]);
+    let report = diff_runs(&cli, &same).unwrap();
+    assert!(!report.has_differences());
+    assert_eq!(report.report(), "No differences\n");
+}
+
+#[test]
+fn test_diff_vars_between_profiles() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let config = temp_dir.path().join("config.toml");
+    fs::write(
+        &config,
+        "replicas = 1\n\n[profiles.prod]\nreplicas = 3\n\n[_meta.signing_key]\nsecret = true\ndefault = \"dev\"\n",
+    )
+    .unwrap();
+
+    let cli = Cli::try_parse_from(["shinkansen", "-c", config.to_str().unwrap()]).unwrap();
+    let other = other_side(&cli, &[], Some("prod"), &["signing_key=abc".to_string()]);
+    let report = diff_runs(&cli, &other).unwrap();
+    assert_eq!(
+        report.report(),
+        "~ replicas: 1 -> 3\n~ signing_key: <redacted> -> <redacted>\n2 difference(s)\n"
+    );
+}
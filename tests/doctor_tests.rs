@@ -0,0 +1,135 @@
+use clap::Parser;
+use shinkansen_lib::cli::{Cli, Commands};
+use shinkansen_lib::doctor::{Check, CheckStatus, DoctorReport, run_doctor};
+use std::fs;
+use tempfile::tempdir;
+
+fn check<'a>(report: &'a DoctorReport, name: &str) -> &'a Check {
+    report
+        .checks
+        .iter()
+        .find(|check| check.name == name)
+        .unwrap()
+}
+
+#[test]
+fn test_doctor_subcommand() {
+    let cli = Cli::try_parse_from(["shinkansen", "-c", "vars.yaml", "doctor"]).unwrap();
+    assert!(matches!(cli.command, Some(Commands::Doctor)));
+}
+
+#[test]
+fn test_config_file_loads() {
+    let temp_dir = tempdir().unwrap();
+    let config = temp_dir.path().join("vars.yaml");
+    fs::write(&config, "name: web\nport: 80\n").unwrap();
+
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        "--no-project-config",
+        "-c",
+        config.to_str().unwrap(),
+    ])
+    .unwrap();
+    let report = run_doctor(&cli);
+    let config_check = check(&report, "config file");
+    assert_eq!(config_check.status, CheckStatus::Ok);
+    assert!(config_check.detail.ends_with("(2 top-level keys)"));
+    assert_eq!(check(&report, "project config").status, CheckStatus::Info);
+    assert_eq!(check(&report, "source").status, CheckStatus::Info);
+}
+
+#[test]
+fn test_missing_and_broken_config_files_fail() {
+    let temp_dir = tempdir().unwrap();
+    let missing = temp_dir.path().join("missing.yaml");
+    let cli = Cli::try_parse_from(["shinkansen", "-c", missing.to_str().unwrap()]).unwrap();
+    let report = run_doctor(&cli);
+    assert_eq!(check(&report, "config file").status, CheckStatus::Fail);
+    assert!(report.has_failures());
+
+    let broken = temp_dir.path().join("broken.json");
+    fs::write(&broken, "{ not json").unwrap();
+    let cli = Cli::try_parse_from(["shinkansen", "-c", broken.to_str().unwrap()]).unwrap();
+    assert_eq!(
+        check(&run_doctor(&cli), "config file").status,
+        CheckStatus::Fail
+    );
+
+    let unsupported = temp_dir.path().join("vars.ini");
+    fs::write(&unsupported, "a = 1").unwrap();
+    let cli = Cli::try_parse_from(["shinkansen", "-c", unsupported.to_str().unwrap()]).unwrap();
+    let report = run_doctor(&cli);
+    let config_check = check(&report, "config file");
+    assert_eq!(config_check.status, CheckStatus::Fail);
+    assert!(config_check.hint.as_ref().unwrap().contains(".toml"));
+}
+
+#[test]
+fn test_broken_project_config_fails() {
+    let temp_dir = tempdir().unwrap();
+    let project = temp_dir.path().join("shinkansen.toml");
+    fs::write(&project, "[[render]]\nunknown = true\n").unwrap();
+
+    let cli =
+        Cli::try_parse_from(["shinkansen", "--project-config", project.to_str().unwrap()]).unwrap();
+    let report = run_doctor(&cli);
+    let project_check = check(&report, "project config");
+    assert_eq!(project_check.status, CheckStatus::Fail);
+    assert!(project_check.hint.is_some());
+}
+
+#[test]
+fn test_unreachable_source_fails() {
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        "--data-url",
+        "users=http://127.0.0.1:9/users.json",
+    ])
+    .unwrap();
+    let report = run_doctor(&cli);
+    let source = check(&report, "source");
+    assert_eq!(source.status, CheckStatus::Fail);
+    assert!(
+        source
+            .detail
+            .starts_with("--data-url users=http://127.0.0.1:9/users.json: ")
+    );
+}
+
+#[test]
+fn test_optional_tools_never_fail() {
+    let cli = Cli::try_parse_from(["shinkansen", "--no-project-config"]).unwrap();
+    let report = run_doctor(&cli);
+    assert!(!report.has_failures());
+    assert_ne!(check(&report, "tool sops").status, CheckStatus::Fail);
+}
+
+#[test]
+fn test_report_layout() {
+    let report = DoctorReport {
+        checks: vec![
+            Check {
+                name: "config file".to_string(),
+                status: CheckStatus::Ok,
+                detail: "vars.yaml (2 top-level keys)".to_string(),
+                hint: None,
+            },
+            Check {
+                name: "tool cue".to_string(),
+                status: CheckStatus::Fail,
+                detail: "not found on PATH".to_string(),
+                hint: Some("install cue".to_string()),
+            },
+        ],
+    };
+    let output = report.report();
+    let lines: Vec<&str> = output.lines().collect();
+    assert!(lines[0].starts_with("doctor: shinkansen "));
+    assert_eq!(
+        lines[1],
+        "  ok    config file  vars.yaml (2 top-level keys)"
+    );
+    assert_eq!(lines[2], "  FAIL  tool cue     not found on PATH");
+    assert_eq!(lines[3], "        hint: install cue");
+}
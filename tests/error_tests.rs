@@ -0,0 +1,161 @@
+use clap::Parser;
+use shinkansen_lib::cli::Cli;
+use shinkansen_lib::config::{ConfigLoader, JsonConfigLoader, YamlConfigLoader};
+use shinkansen_lib::error::{ContextExt, ErrorFormat, Result, ShinkansenError};
+use shinkansen_lib::rendering::render_template;
+use shinkansen_lib::variables::collect_variables;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+
+#[test]
+fn test_with_context_keeps_message() {
+    let result: Result<()> = Err(ShinkansenError::ValidationError("bad value".to_string()));
+    let err = result
+        .with_context(|| "Failed to load settings")
+        .unwrap_err();
+    assert_eq!(err.to_string(), "Failed to load settings: bad value");
+}
+
+#[test]
+fn test_with_context_nests() {
+    let result: Result<()> = Err(ShinkansenError::ValidationError("bad value".to_string()));
+    let err = result
+        .with_context(|| "inner")
+        .with_context(|| "outer")
+        .unwrap_err();
+    assert_eq!(err.to_string(), "outer: inner: bad value");
+    assert!(matches!(
+        err.root_cause(),
+        ShinkansenError::ValidationError(message) if message == "bad value"
+    ));
+}
+
+#[test]
+fn test_with_context_chains_io_source() {
+    let err = JsonConfigLoader
+        .load_config_file(Path::new("/nonexistent/shinkansen/config.json"))
+        .unwrap_err();
+    assert!(
+        err.to_string()
+            .starts_with("Failed to read config file: \"/nonexistent/shinkansen/config.json\": ")
+    );
+    assert!(matches!(err.root_cause(), ShinkansenError::IoError(_)));
+
+    let source = err.source().unwrap();
+    let io_error = source.source().unwrap();
+    assert!(io_error.downcast_ref::<std::io::Error>().is_some());
+}
+
+#[test]
+fn test_config_syntax_error_keeps_source() {
+    let err = YamlConfigLoader.load_config("a: [1, 2").unwrap_err();
+    assert!(matches!(err, ShinkansenError::ConfigSyntax(_)));
+    assert!(
+        err.source()
+            .unwrap()
+            .downcast_ref::<serde_yaml::Error>()
+            .is_some()
+    );
+}
+
+#[test]
+fn test_template_error_keeps_source() {
+    let err = render_template("{{ missing(", &HashMap::new(), "broken.txt").unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("Failed to parse template: broken.txt: ")
+    );
+    assert!(err.to_string().contains("MiniJinja error: "));
+
+    let template_error = err
+        .source()
+        .unwrap()
+        .downcast_ref::<ShinkansenError>()
+        .unwrap();
+    assert!(matches!(template_error, ShinkansenError::Template(_)));
+    assert!(
+        template_error
+            .source()
+            .unwrap()
+            .downcast_ref::<minijinja::Error>()
+            .is_some()
+    );
+}
+
+#[test]
+fn test_report_text_shows_hint_and_docs() {
+    let err = ShinkansenError::ValidationError("bad value".to_string())
+        .with_hint("use a good value")
+        .with_docs("variable-precedence")
+        .context("Failed to load settings");
+    assert_eq!(err.to_string(), "Failed to load settings: bad value");
+    assert_eq!(
+        err.report(ErrorFormat::Text),
+        "Failed to load settings: bad value\n  \
+         hint: use a good value\n  \
+         docs: https://github.com/bbeardsley/shinkansen#variable-precedence"
+    );
+}
+
+#[test]
+fn test_report_json_includes_hint_and_docs() {
+    let err =
+        ShinkansenError::ValidationError("bad value".to_string()).with_hint("use a good value");
+    let report: serde_json::Value = serde_json::from_str(&err.report(ErrorFormat::Json)).unwrap();
+    assert_eq!(
+        report,
+        serde_json::json!({
+            "error": "bad value",
+            "hint": "use a good value",
+            "docs_url": null,
+        })
+    );
+}
+
+#[test]
+fn test_report_without_hint_is_message() {
+    let err = ShinkansenError::ValidationError("bad value".to_string());
+    assert_eq!(err.report(ErrorFormat::Text), "bad value");
+    assert_eq!(err.hint(), None);
+    assert_eq!(err.docs_url(), None);
+}
+
+#[test]
+fn test_undefined_variable_hint() {
+    let err = render_template("{{ missing.field }}", &HashMap::new(), "app.txt").unwrap_err();
+    assert!(err.hint().unwrap().contains("-D name=value"));
+    assert_eq!(
+        err.docs_url(),
+        Some("https://github.com/bbeardsley/shinkansen#variable-not-found-error")
+    );
+}
+
+#[test]
+fn test_unsupported_config_format_hint() {
+    let dir = tempdir().unwrap();
+    let config = dir.path().join("values.ini");
+    fs::write(&config, "name = web").unwrap();
+    let cli = Cli::try_parse_from(["shinkansen", "-c", config.to_str().unwrap()]).unwrap();
+
+    let err = collect_variables(&cli).unwrap_err();
+    assert!(err.to_string().contains("Unsupported config file format"));
+    let hint = err.hint().unwrap();
+    assert!(hint.contains(".json"));
+    assert!(hint.contains(".toml"));
+    assert!(
+        err.docs_url()
+            .unwrap()
+            .ends_with("#using-configuration-files")
+    );
+}
+
+#[test]
+fn test_error_format_flag() {
+    let cli = Cli::try_parse_from(["shinkansen", "--error-format", "json"]).unwrap();
+    assert_eq!(cli.error_format, ErrorFormat::Json);
+    let cli = Cli::try_parse_from(["shinkansen"]).unwrap();
+    assert_eq!(cli.error_format, ErrorFormat::Text);
+}
@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+
+use clap::Parser;
+use tempfile::tempdir;
+
+use shinkansen_lib::cli::Cli;
+use shinkansen_lib::fileio::{
+    Compression, DEFAULT_IO_BUFFER, IoHint, IoOptions, content_extension, open_file, parse_size,
+    read_file, write_file,
+};
+use shinkansen_lib::processing::process_inputs;
+
+#[test]
+fn test_parse_size() {
+    assert_eq!(parse_size("65536"), Ok(65536));
+    assert_eq!(parse_size("64K"), Ok(64 * 1024));
+    assert_eq!(parse_size("64KiB"), Ok(64 * 1024));
+    assert_eq!(parse_size("2M"), Ok(2 * 1024 * 1024));
+    for invalid in ["", "0", "0K", "12G", "K", "-1"] {
+        assert!(parse_size(invalid).is_err(), "{}", invalid);
+    }
+}
+
+#[test]
+fn test_read_and_write_in_chunks() {
+    let temp_dir = tempdir().unwrap();
+    let path = temp_dir.path().join("data.txt");
+    let content: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+
+    for (buffer_size, hint) in [
+        (1, IoHint::Normal),
+        (7, IoHint::Sequential),
+        (4096, IoHint::Nocache),
+        (DEFAULT_IO_BUFFER, IoHint::Normal),
+    ] {
+        let options = IoOptions {
+            buffer_size,
+            hint,
+            compression: None,
+        };
+        write_file(&path, &content, &options).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), content);
+        assert_eq!(read_file(&path, &options).unwrap(), content);
+    }
+
+    let empty = temp_dir.path().join("empty.txt");
+    fs::write(&empty, "").unwrap();
+    assert!(read_file(&empty, &IoOptions::default()).unwrap().is_empty());
+    assert!(read_file(&temp_dir.path().join("missing"), &IoOptions::default()).is_err());
+}
+
+#[test]
+fn test_io_flags_render_the_same_output() {
+    let temp_dir = tempdir().unwrap();
+    let input = temp_dir.path().join("app.conf");
+    let output = temp_dir.path().join("app.out.conf");
+    fs::write(&input, "name={{ 'x' * 3 }}\nend\n").unwrap();
+
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        input.to_str().unwrap(),
+        "-o",
+        output.to_str().unwrap(),
+        "--io-buffer",
+        "3",
+        "--io-hint",
+        "nocache",
+    ])
+    .unwrap();
+    assert_eq!(IoOptions::from_cli(&cli).buffer_size, 3);
+    process_inputs(&cli, &HashMap::new(), &Default::default()).unwrap();
+    assert_eq!(fs::read_to_string(&output).unwrap(), "name=xxx\nend");
+}
+
+#[test]
+fn test_compressed_files_round_trip() {
+    let temp_dir = tempdir().unwrap();
+    let content = "line\n".repeat(1000);
+
+    for compression in [Compression::Gzip, Compression::Zstd] {
+        let path = temp_dir
+            .path()
+            .join(format!("data.txt{}", compression.extension()));
+        assert_eq!(Compression::from_path(&path), Some(compression));
+        let options = IoOptions {
+            compression: Some(compression),
+            ..IoOptions::default()
+        };
+        write_file(&path, content.as_bytes(), &options).unwrap();
+        let raw = fs::read(&path).unwrap();
+        assert!(raw.len() < content.len());
+        assert_eq!(raw, compression.compress(content.as_bytes()).unwrap());
+        assert_eq!(
+            read_file(&path, &IoOptions::default()).unwrap(),
+            content.as_bytes()
+        );
+        let mut streamed = String::new();
+        open_file(&path, &IoOptions::default())
+            .unwrap()
+            .read_to_string(&mut streamed)
+            .unwrap();
+        assert_eq!(streamed, content);
+        assert_eq!(content_extension(&path).as_deref(), Some("txt"));
+    }
+
+    let corrupt = temp_dir.path().join("corrupt.gz");
+    fs::write(&corrupt, "not gzip").unwrap();
+    assert!(read_file(&corrupt, &IoOptions::default()).is_err());
+    assert_eq!(Compression::from_path("plain.txt".as_ref()), None);
+    assert_eq!(
+        content_extension("Users.CSV".as_ref()).as_deref(),
+        Some("csv")
+    );
+    assert_eq!(content_extension("archive.gz".as_ref()), None);
+}
+
+#[test]
+fn test_compressed_inputs_and_outputs() {
+    let temp_dir = tempdir().unwrap();
+    let input_dir = temp_dir.path().join("templates");
+    fs::create_dir_all(&input_dir).unwrap();
+    fs::write(
+        input_dir.join("app.conf.j2.gz"),
+        Compression::Gzip.compress(b"app={{ name }}").unwrap(),
+    )
+    .unwrap();
+    fs::write(
+        input_dir.join("log.conf.j2.zst"),
+        Compression::Zstd.compress(b"log={{ name }}").unwrap(),
+    )
+    .unwrap();
+    let variables = HashMap::from([("name".to_string(), minijinja::Value::from("web"))]);
+
+    let plain = temp_dir.path().join("plain");
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        input_dir.to_str().unwrap(),
+        "-o",
+        plain.to_str().unwrap(),
+        "--strip-extension",
+        ".j2",
+    ])
+    .unwrap();
+    process_inputs(&cli, &variables, &Default::default()).unwrap();
+    assert_eq!(
+        fs::read_to_string(plain.join("app.conf")).unwrap(),
+        "app=web"
+    );
+    assert_eq!(
+        fs::read_to_string(plain.join("log.conf")).unwrap(),
+        "log=web"
+    );
+
+    let compressed = temp_dir.path().join("compressed");
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        input_dir.to_str().unwrap(),
+        "-o",
+        compressed.to_str().unwrap(),
+        "--strip-extension",
+        ".j2",
+        "--compress-output",
+        "zstd",
+    ])
+    .unwrap();
+    process_inputs(&cli, &variables, &Default::default()).unwrap();
+    let read = |name: &str| read_file(&compressed.join(name), &IoOptions::default()).unwrap();
+    assert_eq!(read("app.conf.zst"), b"app=web");
+    assert_eq!(read("log.conf.zst"), b"log=web");
+
+    assert!(
+        Cli::try_parse_from(["shinkansen", "t.j2", "--compress-output", "zstd", "--check"])
+            .is_err()
+    );
+    assert!(Cli::try_parse_from(["shinkansen", "t.j2", "--compress-output", "brotli"]).is_err());
+}
@@ -0,0 +1,530 @@
+use shinkansen_lib::rendering::{RenderOptions, render_template, render_template_with_options};
+use std::collections::HashMap;
+
+fn render_k8s(template: &str, variables: &HashMap<String, minijinja::Value>) -> String {
+    let options = RenderOptions {
+        k8s: true,
+        ..Default::default()
+    };
+    render_template_with_options(template, variables, "test", &options).unwrap()
+}
+
+#[test]
+fn test_k8s_filters_disabled_by_default() {
+    let result = render_template("{{ 'secret' | b64encode }}", &HashMap::new(), "test");
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_b64encode_and_b64decode() {
+    let variables = HashMap::new();
+    assert_eq!(
+        render_k8s("{{ 'hunter2' | b64encode }}", &variables),
+        "aHVudGVyMg=="
+    );
+    assert_eq!(
+        render_k8s("{{ 'aHVudGVyMg==' | b64decode }}", &variables),
+        "hunter2"
+    );
+}
+
+#[test]
+fn test_nindent() {
+    let mut variables = HashMap::new();
+    variables.insert("block".to_string(), "a: 1\nb: 2".into());
+
+    let result = render_k8s("data:{{ block | nindent(2) }}", &variables);
+    assert_eq!(result, "data:\n  a: 1\n  b: 2");
+}
+
+#[test]
+fn test_to_yaml() {
+    let mut labels = HashMap::new();
+    labels.insert("app".to_string(), minijinja::Value::from("web"));
+    let mut variables = HashMap::new();
+    variables.insert("labels".to_string(), minijinja::Value::from(labels));
+
+    let result = render_k8s("{{ labels | toYaml }}", &variables);
+    assert_eq!(result, "app: web");
+}
+
+#[test]
+fn test_yaml_documents() {
+    let first: HashMap<String, minijinja::Value> = [("kind".to_string(), "Service".into())]
+        .into_iter()
+        .collect();
+    let second: HashMap<String, minijinja::Value> = [("kind".to_string(), "Deployment".into())]
+        .into_iter()
+        .collect();
+    let mut variables = HashMap::new();
+    variables.insert(
+        "manifests".to_string(),
+        minijinja::Value::from(vec![
+            minijinja::Value::from(first),
+            minijinja::Value::from(second),
+        ]),
+    );
+
+    let result = render_k8s("{{ manifests | yaml_documents }}", &variables);
+    assert_eq!(result, "---\nkind: Service\n---\nkind: Deployment\n");
+}
+
+#[test]
+fn test_k8s_label_sanitization() {
+    let variables = HashMap::new();
+    assert_eq!(
+        render_k8s("{{ 'feature/My Branch!' | k8s_label }}", &variables),
+        "feature-My-Branch"
+    );
+
+    let long = "a".repeat(100);
+    let result = render_k8s(&format!("{{{{ '{}' | k8s_label }}}}", long), &variables);
+    assert_eq!(result.len(), 63);
+}
+
+#[test]
+fn test_k8s_name_validation() {
+    let variables = HashMap::new();
+    assert_eq!(
+        render_k8s("{{ 'web-frontend.v1' | k8s_name }}", &variables),
+        "web-frontend.v1"
+    );
+
+    let options = RenderOptions {
+        k8s: true,
+        ..Default::default()
+    };
+    let result = render_template_with_options(
+        "{{ 'Web_Frontend' | k8s_name }}",
+        &variables,
+        "test",
+        &options,
+    );
+    assert!(result.is_err());
+    assert!(
+        result
+            .unwrap_err()
+            .to_string()
+            .contains("invalid Kubernetes resource name")
+    );
+}
+
+#[test]
+fn test_booleans_render_lowercase() {
+    let mut variables = HashMap::new();
+    variables.insert("enabled".to_string(), true.into());
+    variables.insert("debug".to_string(), false.into());
+
+    let result = render_template("{{ enabled }} {{ debug }}", &variables, "test").unwrap();
+    assert_eq!(result, "true false");
+}
+
+fn render(template: &str) -> String {
+    render_template(template, &HashMap::new(), "test").unwrap()
+}
+
+#[test]
+fn test_natsort_orders_numbers_by_value() {
+    assert_eq!(
+        render("{{ ['host10', 'host2', 'host1', 'db3'] | natsort | join(',') }}"),
+        "db3,host1,host2,host10"
+    );
+}
+
+#[test]
+fn test_natsort_case_and_padding() {
+    assert_eq!(
+        render("{{ ['b', 'A', 'a', 'B'] | natsort | join(',') }}"),
+        "A,a,B,b"
+    );
+    assert_eq!(
+        render("{{ ['b', 'A', 'a', 'B'] | natsort(case_sensitive=true) | join(',') }}"),
+        "A,B,a,b"
+    );
+    assert_eq!(
+        render("{{ ['n02', 'n2', 'n1', 'n10'] | natsort | join(',') }}"),
+        "n1,n2,n02,n10"
+    );
+}
+
+#[test]
+fn test_natsort_reverse_and_attribute() {
+    let mut variables = HashMap::new();
+    variables.insert(
+        "hosts".to_string(),
+        minijinja::Value::from_serialize(serde_json::json!([
+            {"meta": {"name": "web10"}},
+            {"meta": {"name": "web9"}},
+            {"meta": {"name": "web100"}},
+        ])),
+    );
+    let result = render_template(
+        "{{ hosts | natsort(attribute='meta.name', reverse=true) | map(attribute='meta.name') | join(',') }}",
+        &variables,
+        "test",
+    )
+    .unwrap();
+    assert_eq!(result, "web100,web10,web9");
+}
+
+#[test]
+fn test_localesort_uses_collation_rules() {
+    assert_eq!(
+        render("{{ ['zebra', 'Äpfel', 'apple'] | localesort | join(',') }}"),
+        "Äpfel,apple,zebra"
+    );
+    assert_eq!(
+        render("{{ ['zebra', 'Äpfel', 'apple'] | localesort('sv') | join(',') }}"),
+        "apple,zebra,Äpfel"
+    );
+}
+
+#[test]
+fn test_localesort_numeric() {
+    assert_eq!(
+        render("{{ ['v10', 'v9'] | localesort | join(',') }}"),
+        "v10,v9"
+    );
+    assert_eq!(
+        render("{{ ['v10', 'v9'] | localesort(numeric=true) | join(',') }}"),
+        "v9,v10"
+    );
+}
+
+#[test]
+fn test_localesort_rejects_invalid_locale() {
+    let err =
+        render_template("{{ ['a'] | localesort('!!') }}", &HashMap::new(), "test").unwrap_err();
+    assert!(err.to_string().contains("invalid locale '!!'"));
+}
+
+#[test]
+fn test_nindent_without_k8s() {
+    assert_eq!(
+        render("x:{{ 'a: 1\nb: 2' | nindent(2) }}"),
+        "x:\n  a: 1\n  b: 2"
+    );
+}
+
+#[test]
+fn test_comment_line_styles() {
+    assert_eq!(
+        render("{{ 'Copyright Acme\n\nMIT' | comment }}"),
+        "# Copyright Acme\n#\n# MIT"
+    );
+    assert_eq!(
+        render("{{ 'generated' | comment('rust') }}"),
+        "// generated"
+    );
+    assert_eq!(render("{{ 'generated' | comment('SQL') }}"), "-- generated");
+}
+
+#[test]
+fn test_comment_block_styles() {
+    assert_eq!(
+        render("{{ 'generated' | comment('html') }}"),
+        "<!-- generated -->"
+    );
+    assert_eq!(
+        render("{{ 'line 1\nline 2\n' | comment('css') }}"),
+        "/*\nline 1\nline 2\n*/"
+    );
+}
+
+#[test]
+fn test_comment_rejects_closing_delimiter() {
+    let err =
+        render_template("{{ 'a */ b' | comment('css') }}", &HashMap::new(), "test").unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("cannot comment text containing '*/'")
+    );
+}
+
+#[test]
+fn test_comment_unknown_language() {
+    let err =
+        render_template("{{ 'a' | comment('klingon') }}", &HashMap::new(), "test").unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("unknown comment language 'klingon'")
+    );
+}
+
+#[test]
+fn test_ipaddr_queries() {
+    let query = |q: &str| render(&format!("{{{{ '10.1.2.3/24' | ipaddr('{}') }}}}", q));
+    assert_eq!(query("address"), "10.1.2.3");
+    assert_eq!(query("network"), "10.1.2.0");
+    assert_eq!(query("cidr"), "10.1.2.0/24");
+    assert_eq!(query("prefix"), "24");
+    assert_eq!(query("netmask"), "255.255.255.0");
+    assert_eq!(query("hostmask"), "0.0.0.255");
+    assert_eq!(query("broadcast"), "10.1.2.255");
+    assert_eq!(query("first_usable"), "10.1.2.1");
+    assert_eq!(query("last_usable"), "10.1.2.254");
+    assert_eq!(query("size"), "256");
+    assert_eq!(query("version"), "4");
+}
+
+#[test]
+fn test_ipaddr_small_and_ipv6_networks() {
+    assert_eq!(
+        render(
+            "{{ '10.0.0.0/31' | ipaddr('first_usable') }} {{ '10.0.0.0/31' | ipaddr('last_usable') }}"
+        ),
+        "10.0.0.0 10.0.0.1"
+    );
+    assert_eq!(render("{{ '192.168.1.1' | ipaddr('prefix') }}"), "32");
+    assert_eq!(
+        render(
+            "{{ '2001:db8::5/64' | ipaddr('network') }} {{ '2001:db8::5/64' | ipaddr('version') }}"
+        ),
+        "2001:db8:: 6"
+    );
+    assert!(
+        render_template(
+            "{{ '2001:db8::/64' | ipaddr('broadcast') }}",
+            &HashMap::new(),
+            "test"
+        )
+        .is_err()
+    );
+}
+
+#[test]
+fn test_ipaddr_rejects_invalid_input() {
+    let err = render_template("{{ '10.0.0.300' | ipaddr }}", &HashMap::new(), "test").unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("invalid IP address or network '10.0.0.300'")
+    );
+    let err =
+        render_template("{{ '10.0.0.1' | ipaddr('mtu') }}", &HashMap::new(), "test").unwrap_err();
+    assert!(err.to_string().contains("unknown ipaddr query 'mtu'"));
+}
+
+#[test]
+fn test_nthhost() {
+    assert_eq!(render("{{ '10.0.0.0/24' | nthhost(5) }}"), "10.0.0.5");
+    assert_eq!(render("{{ '10.0.0.7/24' | nthhost(0) }}"), "10.0.0.0");
+    assert_eq!(render("{{ '10.0.0.0/24' | nthhost(-2) }}"), "10.0.0.254");
+    assert_eq!(
+        render("{{ '2001:db8::/64' | nthhost(16) }}"),
+        "2001:db8::10"
+    );
+    let err =
+        render_template("{{ '10.0.0.0/30' | nthhost(4) }}", &HashMap::new(), "test").unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("'10.0.0.0/30' has no address at index 4")
+    );
+}
+
+#[test]
+fn test_ipsubnet() {
+    assert_eq!(
+        render("{{ '10.0.0.0/24' | ipsubnet(26) | join(',') }}"),
+        "10.0.0.0/26,10.0.0.64/26,10.0.0.128/26,10.0.0.192/26"
+    );
+    assert_eq!(
+        render("{{ '10.0.0.0/16' | ipsubnet(24, 3) }}"),
+        "10.0.3.0/24"
+    );
+    assert_eq!(
+        render("{{ '10.0.0.0/16' | ipsubnet(24, -1) }}"),
+        "10.0.255.0/24"
+    );
+    assert_eq!(
+        render("{{ '10.0.0.0/8' | ipsubnet(32, 257) }}"),
+        "10.0.1.1/32"
+    );
+}
+
+#[test]
+fn test_ipsubnet_limits() {
+    let err = render_template(
+        "{{ '10.0.0.0/24' | ipsubnet(16) }}",
+        &HashMap::new(),
+        "test",
+    )
+    .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("the prefix must be between 24 and 32")
+    );
+    let err =
+        render_template("{{ '10.0.0.0/8' | ipsubnet(32) }}", &HashMap::new(), "test").unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("pick one with ipsubnet(32, index)")
+    );
+}
+
+fn render_crypto(template: &str) -> shinkansen_lib::error::Result<String> {
+    let options = RenderOptions {
+        crypto: true,
+        ..Default::default()
+    };
+    render_template_with_options(template, &HashMap::new(), "test", &options)
+}
+
+#[test]
+fn test_crypto_filters_disabled_by_default() {
+    let err = render_template("{{ 'pw' | bcrypt }}", &HashMap::new(), "test").unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("the bcrypt filter is disabled; pass --allow-crypto to use it")
+    );
+}
+
+#[test]
+fn test_bcrypt_verifies_and_salt_is_deterministic() {
+    let hash = render_crypto("{{ 'hunter2' | bcrypt(cost=4) }}").unwrap();
+    assert!(hash.starts_with("$2b$04$"));
+    assert!(bcrypt::verify("hunter2", &hash).unwrap());
+
+    let salted = "{{ 'hunter2' | bcrypt(cost=4, salt='web-1') }}";
+    assert_eq!(
+        render_crypto(salted).unwrap(),
+        render_crypto(salted).unwrap()
+    );
+    assert_ne!(
+        render_crypto(salted).unwrap(),
+        render_crypto("{{ 'hunter2' | bcrypt(cost=4, salt='web-2') }}").unwrap()
+    );
+}
+
+#[test]
+fn test_bcrypt_rejects_invalid_cost() {
+    let err = render_crypto("{{ 'pw' | bcrypt(cost=2) }}").unwrap_err();
+    assert!(err.to_string().contains("bcrypt failed"));
+}
+
+#[test]
+fn test_htpasswd() {
+    let line = render_crypto("{{ 'hunter2' | htpasswd('alice', cost=4) }}").unwrap();
+    let (user, hash) = line.split_once(':').unwrap();
+    assert_eq!(user, "alice");
+    assert!(hash.starts_with("$2y$04$"));
+    assert!(bcrypt::verify("hunter2", hash).unwrap());
+
+    let err = render_crypto("{{ 'pw' | htpasswd('a:b', cost=4) }}").unwrap_err();
+    assert!(err.to_string().contains("invalid htpasswd user 'a:b'"));
+}
+
+#[test]
+fn test_hmac() {
+    // RFC 4231 test case 2
+    assert_eq!(
+        render_crypto("{{ 'what do ya want for nothing?' | hmac('Jefe') }}").unwrap(),
+        "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+    );
+    assert_eq!(
+        render_crypto("{{ 'msg' | hmac('key', encoding='base64') }}").unwrap(),
+        "LZPLwb4We8sWN6SiPL/wGnh48MUO6DOVTqUiG7G4xig="
+    );
+    let err = render_crypto("{{ 'msg' | hmac('key', algorithm='md5') }}").unwrap_err();
+    assert!(err.to_string().contains("unknown hmac algorithm 'md5'"));
+}
+
+#[test]
+fn test_comment_accepts_file_extensions() {
+    assert_eq!(render("{{ 'a' | comment('tf') }}"), "# a");
+    assert_eq!(render("{{ 'a' | comment('proto') }}"), "// a");
+    assert_eq!(render("{{ 'a' | comment('vbs') }}"), "' a");
+}
+
+#[test]
+fn test_wordwrap() {
+    assert_eq!(
+        render("{{ 'the quick brown fox jumps over the lazy dog' | wordwrap(15) }}"),
+        "the quick brown\nfox jumps over\nthe lazy dog"
+    );
+    assert_eq!(
+        render("{{ 'one two\n\nthree' | wordwrap(5, wrapstring='|') }}"),
+        "one|two||three"
+    );
+}
+
+#[test]
+fn test_wordwrap_long_words() {
+    assert_eq!(
+        render("{{ 'see abcdefghij' | wordwrap(4) }}"),
+        "see\nabcd\nefgh\nij"
+    );
+    assert_eq!(
+        render("{{ 'see abcdefghij' | wordwrap(4, break_long_words=false) }}"),
+        "see\nabcdefghij"
+    );
+    // Wide characters take two columns
+    assert_eq!(render("{{ '日本語です' | wordwrap(4) }}"), "日本\n語で\nす");
+}
+
+#[test]
+fn test_truncate_chars() {
+    assert_eq!(render("{{ 'short' | truncate_chars(10) }}"), "short");
+    assert_eq!(
+        render("{{ 'Welcome to the build server' | truncate_chars(14) }}"),
+        "Welcome to..."
+    );
+    assert_eq!(
+        render("{{ 'Welcome to the build server' | truncate_chars(9, '…') }}"),
+        "Welcome…"
+    );
+    let err = render_template(
+        "{{ 'Welcome' | truncate_chars(2) }}",
+        &HashMap::new(),
+        "test",
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("shorter than the ending '...'"));
+}
+
+#[test]
+fn test_center() {
+    assert_eq!(render("[{{ 'MOTD' | center(9) }}]"), "[  MOTD   ]");
+    assert_eq!(
+        render("{{ 'ab\nabcd' | center(6, '=') }}"),
+        "==ab==\n=abcd="
+    );
+    assert_eq!(render("{{ 'too wide' | center(4) }}"), "too wide");
+    let err = render_template("{{ 'x' | center(5, '--') }}", &HashMap::new(), "test").unwrap_err();
+    assert!(err.to_string().contains("single character"));
+}
+
+#[test]
+fn test_tojson_sorted() {
+    let mut variables = HashMap::new();
+    variables.insert(
+        "config".to_string(),
+        minijinja::Value::from_serialize(serde_json::json!({
+            "zeta": [3.0, 1.5],
+            "alpha": {"b": true, "a": null},
+            "html": "<b>'&'</b>"
+        })),
+    );
+    assert_eq!(
+        render_template("{{ config | tojson_sorted }}", &variables, "test").unwrap(),
+        r#"{"alpha":{"a":null,"b":true},"html":"\u003cb\u003e\u0027\u0026\u0027\u003c/b\u003e","zeta":[3,1.5]}"#
+    );
+    assert_eq!(
+        render_template(
+            "{{ config.alpha | tojson_sorted(indent=2) }}",
+            &variables,
+            "test"
+        )
+        .unwrap(),
+        "{\n  \"a\": null,\n  \"b\": true\n}"
+    );
+}
+
+#[test]
+fn test_canonical_json_is_stable() {
+    let first =
+        render("{{ {'b': 1.0, 'a': [2, 0.5], 'c': {'y': 'é', 'x': -0.0}} | canonical_json }}");
+    let second =
+        render("{{ {'c': {'x': 0, 'y': 'é'}, 'a': [2.0, 0.5], 'b': 1} | canonical_json }}");
+    assert_eq!(first, r#"{"a":[2,0.5],"b":1,"c":{"x":0,"y":"é"}}"#);
+    assert_eq!(first, second);
+}
@@ -0,0 +1,107 @@
+use clap::Parser;
+use shinkansen_lib::cli::Cli;
+use shinkansen_lib::error::{Result, ShinkansenError, catch_panic};
+use shinkansen_lib::variables::collect_variables;
+
+/// Inputs that have tripped up key, escape and template parsers before
+#[cfg(feature = "fuzz")]
+const TRICKY_INPUTS: &[&str] = &[
+    "",
+    "=",
+    "==",
+    "\\",
+    "a=\\",
+    ",,,",
+    "a[",
+    "a[]=1",
+    "a[+]=1,a[+]=2",
+    "a[18446744073709551616]=1",
+    "a.b.c[0][1][2].d=x",
+    "'a.b'=1",
+    "\"unterminated=1",
+    "a.=1",
+    ".a=1",
+    "é=ü,ß\\,=\\=",
+    "{{",
+    "{% for %}",
+    "{{ a|nindent(-1) }}",
+    "{{ 'x' * 99999999999 }}",
+    "{% macro m() %}{{ m() }}{% endmacro %}{{ m() }}",
+    "\u{0}\u{7f}\u{feff}",
+];
+
+/// Small deterministic generator so failures are reproducible
+#[cfg(feature = "fuzz")]
+struct Lcg(u64);
+
+#[cfg(feature = "fuzz")]
+impl Lcg {
+    fn next_byte(&mut self) -> u8 {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (self.0 >> 56) as u8
+    }
+
+    fn bytes(&mut self, len: usize, alphabet: &[u8]) -> Vec<u8> {
+        (0..len)
+            .map(|_| alphabet[self.next_byte() as usize % alphabet.len()])
+            .collect()
+    }
+}
+
+#[cfg(feature = "fuzz")]
+const ALPHABET: &[u8] = b"ab01=,.[]+'\"\\{}%#|: \n-_";
+
+#[test]
+fn test_catch_panic_converts_panics_to_errors() {
+    let result: Result<()> = catch_panic("testing", || panic!("boom"));
+    match result {
+        Err(ShinkansenError::ValidationError(message)) => {
+            assert_eq!(message, "Internal error while testing: boom")
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    let value = catch_panic("testing", || Ok(42)).unwrap();
+    assert_eq!(value, 42);
+}
+
+#[test]
+fn test_large_array_index_is_rejected() {
+    let cli = Cli::try_parse_from(["shinkansen", "-D", "servers[4000000000]=web"]).unwrap();
+    let err = collect_variables(&cli).unwrap_err();
+    assert!(err.to_string().contains("larger than 65535"));
+}
+
+#[cfg(feature = "fuzz")]
+#[test]
+fn test_fuzz_entry_points_survive_tricky_inputs() {
+    use shinkansen_lib::fuzz;
+
+    for input in TRICKY_INPUTS {
+        fuzz::define(input.as_bytes());
+        fuzz::render(input.as_bytes());
+        fuzz::render(format!("-D {}\n{{{{ a }}}}", input).as_bytes());
+        for selector in 0..3u8 {
+            let mut data = vec![selector];
+            data.extend_from_slice(input.as_bytes());
+            fuzz::config(&data);
+        }
+    }
+}
+
+#[cfg(feature = "fuzz")]
+#[test]
+fn test_fuzz_entry_points_survive_random_inputs() {
+    use shinkansen_lib::fuzz;
+
+    let mut rng = Lcg(0x5348_494e_4b41_4e53);
+    for round in 0..2000 {
+        let data = rng.bytes(round % 64, ALPHABET);
+        fuzz::define(&data);
+        fuzz::config(&data);
+        fuzz::render(&data);
+    }
+}
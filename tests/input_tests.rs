@@ -0,0 +1,285 @@
+#[cfg(feature = "http")]
+mod common;
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::Parser;
+use minijinja::Value;
+use tempfile::tempdir;
+
+use shinkansen_lib::backend::{ArchiveBackend, OutputBackend, OutputMeta};
+use shinkansen_lib::cli::Cli;
+use shinkansen_lib::error::{Result, ShinkansenError};
+use shinkansen_lib::fileio::Compression;
+use shinkansen_lib::input::{
+    ArchiveSource, GitRef, InputFiles, InputSource, is_remote_input, parse_git_ref,
+};
+use shinkansen_lib::per_file::PerFileVars;
+use shinkansen_lib::processing::{process_inputs, process_inputs_from, validate_args};
+
+/// A tar archive holding `files`, written with [`ArchiveBackend`]
+fn tar(files: &[(&str, &str)]) -> Vec<u8> {
+    let archive = ArchiveBackend::new();
+    let meta = OutputMeta {
+        input: PathBuf::from("test"),
+        compression: None,
+    };
+    for (path, content) in files {
+        archive
+            .write(Path::new(path), content.as_bytes(), &meta)
+            .unwrap();
+    }
+    archive.finish()
+}
+
+fn variables() -> HashMap<String, Value> {
+    HashMap::from([("name".to_string(), Value::from("web"))])
+}
+
+#[test]
+fn test_archive_inputs_render_like_a_directory() {
+    let temp_dir = tempdir().unwrap();
+    let bundle = tar(&[
+        ("app.conf", "app={{ name }}"),
+        ("nginx/site.conf", "site={{ name }}"),
+        ("node_modules/skip.js", "{{ broken"),
+    ]);
+    let archive = temp_dir.path().join("bundle.tar.gz");
+    fs::write(&archive, Compression::Gzip.compress(&bundle).unwrap()).unwrap();
+    let output_dir = temp_dir.path().join("out");
+
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        archive.to_str().unwrap(),
+        "-o",
+        output_dir.to_str().unwrap(),
+        "--exclude",
+        "node_modules",
+    ])
+    .unwrap();
+    validate_args(&cli).unwrap();
+    process_inputs(&cli, &variables(), &Default::default()).unwrap();
+
+    let read = |path: &str| fs::read_to_string(output_dir.join(path)).unwrap();
+    assert_eq!(read("app.conf"), "app=web");
+    assert_eq!(read("nginx/site.conf"), "site=web");
+    assert!(!output_dir.join("node_modules").exists());
+
+    let in_place =
+        Cli::try_parse_from(["shinkansen", archive.to_str().unwrap(), "--in-place"]).unwrap();
+    let err = validate_args(&in_place).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("--in-place needs local input files")
+    );
+}
+
+#[test]
+fn test_archive_source_rejects_bad_archives() {
+    let mut escape = tar(&[("ok.conf", "ok")]);
+    // Rename the member to ../x.conf and fix up the header checksum
+    escape[..9].copy_from_slice(b"../x.conf");
+    let checksum: u32 = escape[..512]
+        .iter()
+        .enumerate()
+        .map(|(i, byte)| {
+            if (148..156).contains(&i) {
+                32
+            } else {
+                u32::from(*byte)
+            }
+        })
+        .sum();
+    escape[148..156].copy_from_slice(format!("{:06o}\0 ", checksum).as_bytes());
+    let err = ArchiveSource::new("bundle.tar", &escape).err().unwrap();
+    assert!(err.to_string().contains("parent directory"), "{}", err);
+
+    let truncated = &tar(&[("app.conf", "app")])[..514];
+    let err = ArchiveSource::new("bundle.tar", truncated).err().unwrap();
+    assert!(err.to_string().contains("Invalid tar archive"), "{}", err);
+
+    let source = ArchiveSource::new("bundle.tar", &tar(&[("a.conf", "a")])).unwrap();
+    let files: Vec<PathBuf> = source.files().unwrap().map(|file| file.unwrap()).collect();
+    assert_eq!(files, [PathBuf::from("bundle.tar/a.conf")]);
+    assert_eq!(source.read(Path::new("bundle.tar/a.conf")).unwrap(), b"a");
+    assert!(source.read(Path::new("bundle.tar/b.conf")).is_err());
+}
+
+/// Templates held in memory
+struct MemorySource(Vec<(&'static str, &'static str)>);
+
+impl InputSource for MemorySource {
+    fn files(&self) -> Result<InputFiles<'_>> {
+        Ok(Box::new(
+            self.0.iter().map(|(path, _)| Ok(PathBuf::from(path))),
+        ))
+    }
+
+    fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        self.0
+            .iter()
+            .find(|(name, _)| Path::new(name) == path)
+            .map(|(_, content)| content.as_bytes().to_vec())
+            .ok_or_else(|| ShinkansenError::FileSystemError(format!("{:?} not found", path)))
+    }
+}
+
+#[test]
+fn test_templates_come_from_a_custom_source() {
+    let temp_dir = tempdir().unwrap();
+    let output_dir = temp_dir.path().join("out");
+    let source = MemorySource(vec![
+        ("site/app.conf", "app={{ name }}"),
+        ("site/conf.d/db.conf", "db={{ name }}"),
+    ]);
+
+    let cli =
+        Cli::try_parse_from(["shinkansen", "site", "-o", output_dir.to_str().unwrap()]).unwrap();
+    let backend = shinkansen_lib::backend::default_backend(&cli);
+    process_inputs_from(
+        &cli,
+        &variables(),
+        &PerFileVars::new(),
+        &source,
+        &*backend,
+        &Default::default(),
+    )
+    .unwrap();
+
+    let read = |path: &str| fs::read_to_string(output_dir.join(path)).unwrap();
+    assert_eq!(read("app.conf"), "app=web");
+    assert_eq!(read("conf.d/db.conf"), "db=web");
+}
+
+#[test]
+fn test_git_ref_inputs() {
+    use std::process::Command;
+
+    if Command::new("git").arg("--version").output().is_err() {
+        return;
+    }
+    let temp_dir = tempdir().unwrap();
+    let work = temp_dir.path().join("work");
+    fs::create_dir_all(work.join("templates/nginx")).unwrap();
+    fs::write(work.join("templates/app.conf"), "app={{ name }}").unwrap();
+    fs::write(work.join("templates/nginx/site.conf"), "site={{ name }}").unwrap();
+    let git = |args: &[&str]| {
+        let status = Command::new("git")
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .current_dir(&work)
+            .env_remove("GIT_DIR")
+            .output()
+            .unwrap();
+        assert!(status.status.success(), "{:?}", status);
+    };
+    git(&["init", "-q"]);
+    git(&["add", "."]);
+    git(&["commit", "-qm", "templates"]);
+    git(&["tag", "v1"]);
+    // Only the committed version is rendered
+    fs::write(work.join("templates/app.conf"), "changed").unwrap();
+
+    let output_dir = temp_dir.path().join("out");
+    fs::create_dir_all(&output_dir).unwrap();
+    // TODO: Audit that the environment access only happens in single-threaded code.
+    unsafe { std::env::set_var("GIT_DIR", work.join(".git")) };
+    let run = |extra: &[&str]| {
+        let mut args = vec![
+            "shinkansen",
+            "templates/",
+            "--git-ref",
+            "v1",
+            "-o",
+            output_dir.to_str().unwrap(),
+        ];
+        args.extend(extra);
+        let cli = Cli::try_parse_from(args).unwrap();
+        validate_args(&cli)?;
+        process_inputs(&cli, &variables(), &Default::default())
+    };
+    run(&[]).unwrap();
+    assert_eq!(
+        fs::read_to_string(output_dir.join("app.conf")).unwrap(),
+        "app=web"
+    );
+    assert!(!output_dir.join("nginx").exists());
+    run(&["-r"]).unwrap();
+    assert_eq!(
+        fs::read_to_string(output_dir.join("nginx/site.conf")).unwrap(),
+        "site=web"
+    );
+
+    // REF:PATH names the tree itself
+    let tree_output = temp_dir.path().join("tree");
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        "-r",
+        "--git-ref",
+        "v1:templates/",
+        "-o",
+        tree_output.to_str().unwrap(),
+    ])
+    .unwrap();
+    validate_args(&cli).unwrap();
+    process_inputs(&cli, &variables(), &Default::default()).unwrap();
+    assert_eq!(
+        fs::read_to_string(tree_output.join("nginx/site.conf")).unwrap(),
+        "site=web"
+    );
+
+    let cli = Cli::try_parse_from(["shinkansen", "missing", "--git-ref", "v1"]).unwrap();
+    let err = process_inputs(&cli, &variables(), &Default::default()).unwrap_err();
+    assert!(err.to_string().contains("does not exist in v1"), "{}", err);
+    // TODO: Audit that the environment access only happens in single-threaded code.
+    unsafe { std::env::remove_var("GIT_DIR") };
+
+    let glob = Cli::try_parse_from(["shinkansen", "templates/*.conf", "--git-ref", "v1"]).unwrap();
+    assert!(validate_args(&glob).is_err());
+    let stdin = Cli::try_parse_from(["shinkansen", "--git-ref", "v1"]).unwrap();
+    assert!(validate_args(&stdin).is_err());
+    let both = Cli::try_parse_from(["shinkansen", "other/", "--git-ref", "v1:templates"]).unwrap();
+    let err = validate_args(&both).unwrap_err();
+    assert!(
+        err.to_string().contains("names the inputs itself"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_parse_git_ref() {
+    assert_eq!(
+        parse_git_ref("origin/main:templates/").unwrap(),
+        GitRef {
+            reference: "origin/main".to_string(),
+            path: Some("templates".to_string()),
+        }
+    );
+    assert_eq!(parse_git_ref("v1.4").unwrap().path, None);
+    assert_eq!(parse_git_ref("HEAD:").unwrap().path, Some(String::new()));
+    assert!(parse_git_ref(":templates").is_err());
+}
+
+#[test]
+fn test_remote_inputs() {
+    assert!(is_remote_input("https://example.com/app.conf"));
+    assert!(!is_remote_input("templates/app.conf"));
+
+    #[cfg(feature = "http")]
+    {
+        let temp_dir = tempdir().unwrap();
+        let output = temp_dir.path().join("app.conf");
+        let url = format!(
+            "{}/templates/app.conf",
+            common::serve_once("app={{ name }}", "text/plain")
+        );
+        let cli =
+            Cli::try_parse_from(["shinkansen", &url, "-o", output.to_str().unwrap()]).unwrap();
+        validate_args(&cli).unwrap();
+        process_inputs(&cli, &variables(), &Default::default()).unwrap();
+        assert_eq!(fs::read_to_string(&output).unwrap(), "app=web");
+    }
+}
@@ -24,7 +24,7 @@ fn test_path_normalization_in_processing() {
 
     let cli = Cli::try_parse_from(args).unwrap();
     let variables = shinkansen_lib::variables::collect_variables(&cli).unwrap();
-    let result = shinkansen_lib::processing::process_inputs(&cli, &variables);
+    let result = shinkansen_lib::processing::process_inputs(&cli, &variables, &Default::default());
 
     // Should succeed
     assert!(result.is_ok());
@@ -57,7 +57,8 @@ fn test_platform_specific_path_validation() {
         let variables = shinkansen_lib::variables::collect_variables(&cli).unwrap();
 
         // This should fail during processing when it tries to collect input files
-        let result = shinkansen_lib::processing::process_inputs(&cli, &variables);
+        let result =
+            shinkansen_lib::processing::process_inputs(&cli, &variables, &Default::default());
 
         // Should fail with path too long error
         assert!(result.is_err());
@@ -83,7 +84,7 @@ fn test_cross_platform_path_handling() {
 
     let cli = Cli::try_parse_from(args).unwrap();
     let variables = shinkansen_lib::variables::collect_variables(&cli).unwrap();
-    let result = shinkansen_lib::processing::process_inputs(&cli, &variables);
+    let result = shinkansen_lib::processing::process_inputs(&cli, &variables, &Default::default());
 
     assert!(result.is_ok());
 }
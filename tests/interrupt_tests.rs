@@ -0,0 +1,40 @@
+use std::collections::HashMap;
+use std::fs;
+
+use clap::Parser;
+use tempfile::tempdir;
+
+use shinkansen_lib::cli::Cli;
+use shinkansen_lib::interrupt::request_stop;
+use shinkansen_lib::processing::process_inputs;
+
+// The stop flag is process-wide, so this file holds a single test
+
+#[test]
+fn test_stop_request_renders_no_further_files() {
+    let temp_dir = tempdir().unwrap();
+    let input_dir = temp_dir.path().join("templates");
+    let output_dir = temp_dir.path().join("output");
+    fs::create_dir_all(&input_dir).unwrap();
+    fs::write(input_dir.join("a.txt"), "a").unwrap();
+    fs::write(input_dir.join("b.txt"), "b").unwrap();
+
+    request_stop();
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        input_dir.to_str().unwrap(),
+        "-o",
+        output_dir.to_str().unwrap(),
+    ])
+    .unwrap();
+    let err = process_inputs(&cli, &HashMap::new(), &Default::default()).unwrap_err();
+
+    assert!(
+        err.to_string()
+            .starts_with("Interrupted: 0 output file(s) written"),
+        "{}",
+        err
+    );
+    assert!(!output_dir.join("a.txt").exists());
+    assert!(!output_dir.join("b.txt").exists());
+}
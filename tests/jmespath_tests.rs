@@ -0,0 +1,166 @@
+#![cfg(feature = "http")]
+
+use serde_json::{Value, json};
+use shinkansen_lib::jmespath::search;
+
+fn inventory() -> Value {
+    json!({
+        "region": "us-east-1",
+        "hosts": [
+            {"name": "web1", "ip": "10.0.0.1", "role": "web", "cpus": 2, "tags": ["a", "b"]},
+            {"name": "web2", "ip": "10.0.0.2", "role": "web", "cpus": 4, "tags": ["c"]},
+            {"name": "db1", "ip": "10.0.1.1", "role": "db", "cpus": 8, "tags": []}
+        ],
+        "owners": {"web": "alice", "db": "bob"}
+    })
+}
+
+#[test]
+fn test_field_and_subexpression() {
+    let data = json!({"a": {"b": {"c": 1}}});
+    assert_eq!(search("a.b.c", &data).unwrap(), json!(1));
+    assert_eq!(search("a.missing.c", &data).unwrap(), Value::Null);
+    assert_eq!(search("\"a\".b", &data).unwrap(), json!({"c": 1}));
+}
+
+#[test]
+fn test_index_and_slice() {
+    let data = json!({"items": [0, 1, 2, 3, 4]});
+    assert_eq!(search("items[1]", &data).unwrap(), json!(1));
+    assert_eq!(search("items[-1]", &data).unwrap(), json!(4));
+    assert_eq!(search("items[10]", &data).unwrap(), Value::Null);
+    assert_eq!(search("items[1:3]", &data).unwrap(), json!([1, 2]));
+    assert_eq!(search("items[::2]", &data).unwrap(), json!([0, 2, 4]));
+    assert_eq!(
+        search("items[::-1]", &data).unwrap(),
+        json!([4, 3, 2, 1, 0])
+    );
+}
+
+#[test]
+fn test_list_projection() {
+    let data = inventory();
+    assert_eq!(
+        search("hosts[*].name", &data).unwrap(),
+        json!(["web1", "web2", "db1"])
+    );
+    assert_eq!(
+        search("hosts[].ip", &data).unwrap(),
+        json!(["10.0.0.1", "10.0.0.2", "10.0.1.1"])
+    );
+}
+
+#[test]
+fn test_object_projection() {
+    let data = inventory();
+    let mut owners: Vec<String> =
+        serde_json::from_value(search("owners.*", &data).unwrap()).unwrap();
+    owners.sort();
+    assert_eq!(owners, vec!["alice", "bob"]);
+}
+
+#[test]
+fn test_flatten() {
+    let data = inventory();
+    assert_eq!(
+        search("hosts[].tags[]", &data).unwrap(),
+        json!(["a", "b", "c"])
+    );
+}
+
+#[test]
+fn test_filter_projection() {
+    let data = inventory();
+    assert_eq!(
+        search("hosts[?role == 'web'].name", &data).unwrap(),
+        json!(["web1", "web2"])
+    );
+    assert_eq!(
+        search("hosts[?cpus > `2` && role == 'web'].name", &data).unwrap(),
+        json!(["web2"])
+    );
+    assert_eq!(
+        search("hosts[?!(role == 'web')].name", &data).unwrap(),
+        json!(["db1"])
+    );
+}
+
+#[test]
+fn test_multi_select() {
+    let data = inventory();
+    assert_eq!(
+        search("hosts[0].[name, ip]", &data).unwrap(),
+        json!(["web1", "10.0.0.1"])
+    );
+    assert_eq!(
+        search("hosts[?role == 'db'].{host: name, addr: ip}", &data).unwrap(),
+        json!([{"host": "db1", "addr": "10.0.1.1"}])
+    );
+}
+
+#[test]
+fn test_pipe_and_functions() {
+    let data = inventory();
+    assert_eq!(search("hosts[*].name | [0]", &data).unwrap(), json!("web1"));
+    assert_eq!(search("length(hosts)", &data).unwrap(), json!(3));
+    assert_eq!(
+        search("sort(hosts[*].name)", &data).unwrap(),
+        json!(["db1", "web1", "web2"])
+    );
+    assert_eq!(
+        search("join(', ', hosts[*].name)", &data).unwrap(),
+        json!("web1, web2, db1")
+    );
+    assert_eq!(
+        search("hosts[?contains(tags, 'c')].name", &data).unwrap(),
+        json!(["web2"])
+    );
+    assert_eq!(
+        search("hosts[?starts_with(name, 'db')].name", &data).unwrap(),
+        json!(["db1"])
+    );
+}
+
+#[test]
+fn test_or_and_literals() {
+    let data = json!({"a": null, "b": "fallback"});
+    assert_eq!(search("a || b", &data).unwrap(), json!("fallback"));
+    assert_eq!(search("`[1, 2]`", &data).unwrap(), json!([1, 2]));
+    assert_eq!(search("'raw string'", &data).unwrap(), json!("raw string"));
+}
+
+#[test]
+fn test_expression_references() {
+    let data = inventory();
+    assert_eq!(
+        search("sort_by(hosts, &cpus)[-1].name", &data).unwrap(),
+        json!("db1")
+    );
+    assert_eq!(
+        search("sort_by(hosts, &name)[*].name", &data).unwrap(),
+        json!(["db1", "web1", "web2"])
+    );
+    assert_eq!(
+        search("max_by(hosts, &cpus).name", &data).unwrap(),
+        json!("db1")
+    );
+    assert_eq!(
+        search("map(&ip, hosts[?role == 'web'])", &data).unwrap(),
+        json!(["10.0.0.1", "10.0.0.2"])
+    );
+}
+
+#[test]
+fn test_invalid_expressions() {
+    let data = inventory();
+    for expression in [
+        "hosts[",
+        "hosts[?role ==",
+        "unknown_fn(hosts)",
+        "a.",
+        "'unterminated",
+    ] {
+        let result = search(expression, &data);
+        assert!(result.is_err(), "expected error for {}", expression);
+    }
+}
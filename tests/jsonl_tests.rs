@@ -0,0 +1,87 @@
+use std::fs;
+use std::path::Path;
+
+use clap::Parser;
+use serde_json::json;
+
+use shinkansen_lib::cli::Cli;
+use shinkansen_lib::fileio::Compression;
+use shinkansen_lib::jsonl::{is_json_lines, parse_json_lines};
+use shinkansen_lib::rendering::render_template;
+use shinkansen_lib::variables::collect_variables;
+
+#[test]
+fn test_parse_json_lines() {
+    let content = "\u{feff}{\"event\": \"start\", \"ms\": 0}\r\n\n  [1, 2]  \n\"done\"";
+    assert_eq!(
+        parse_json_lines(content.as_bytes()).unwrap(),
+        [
+            json!({"event": "start", "ms": 0}),
+            json!([1, 2]),
+            json!("done")
+        ]
+    );
+    assert!(parse_json_lines("".as_bytes()).unwrap().is_empty());
+
+    let err = parse_json_lines("{}\n\n{\"a\": \n".as_bytes()).unwrap_err();
+    assert!(err.starts_with("line 3: "), "{}", err);
+    // One value per line
+    let err = parse_json_lines("{} {}\n".as_bytes()).unwrap_err();
+    assert!(err.starts_with("line 1: "), "{}", err);
+}
+
+#[test]
+fn test_is_json_lines() {
+    assert!(is_json_lines(Path::new("events.jsonl")));
+    assert!(is_json_lines(Path::new("events.NDJSON")));
+    assert!(is_json_lines(Path::new("events.jsonl.zst")));
+    assert!(!is_json_lines(Path::new("events.json")));
+    assert!(!is_json_lines(Path::new("events.csv")));
+}
+
+#[test]
+fn test_json_lines_data_files() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let events: String = (0..1000)
+        .map(|i| {
+            format!(
+                "{{\"id\": {}, \"level\": \"{}\"}}\n",
+                i,
+                ["info", "error"][i % 2]
+            )
+        })
+        .collect();
+    let path = temp_dir.path().join("events.jsonl.gz");
+    fs::write(
+        &path,
+        Compression::Gzip.compress(events.as_bytes()).unwrap(),
+    )
+    .unwrap();
+
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        "--data",
+        &format!("events={}", path.display()),
+    ])
+    .unwrap();
+    let variables = collect_variables(&cli).unwrap();
+    let rendered = render_template(
+        "{{ events | length }} {{ events | selectattr('level', 'eq', 'error') | list | length }} {{ events[999].id }}",
+        &variables,
+        "report.txt",
+    )
+    .unwrap();
+    assert_eq!(rendered, "1000 500 999");
+
+    let broken = temp_dir.path().join("broken.ndjson");
+    fs::write(&broken, "{\"id\": 1}\nnot json\n").unwrap();
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        "--data",
+        &format!("events={}", broken.display()),
+    ])
+    .unwrap();
+    let err = collect_variables(&cli).unwrap_err().to_string();
+    assert!(err.contains("Invalid data file"), "{}", err);
+    assert!(err.contains("line 2: "), "{}", err);
+}
@@ -0,0 +1,143 @@
+#[cfg(feature = "http")]
+mod common;
+
+use clap::Parser;
+use serde_json::json;
+use shinkansen_lib::cli::Cli;
+use shinkansen_lib::kv::{KubernetesSource, build_tree, kubernetes_data, prefix_range_end};
+use shinkansen_lib::variables::collect_variables;
+
+fn pairs(items: &[(&str, &str)]) -> Vec<(String, String)> {
+    items
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+#[test]
+fn test_build_tree_nests_keys_relative_to_prefix() {
+    let tree = build_tree(
+        "app/config/",
+        pairs(&[
+            ("app/config/", ""),
+            ("app/config/db/host", "db.internal"),
+            ("app/config/db/port", "5432"),
+            ("app/config/name", "web"),
+        ]),
+    )
+    .unwrap();
+
+    assert_eq!(
+        tree,
+        json!({"db": {"host": "db.internal", "port": "5432"}, "name": "web"})
+    );
+}
+
+#[test]
+fn test_build_tree_conflicting_keys() {
+    let result = build_tree("app/", pairs(&[("app/db/host", "a"), ("app/db", "b")]));
+    assert!(result.is_err());
+
+    let result = build_tree("app/", pairs(&[("app/db", "b"), ("app/db/host", "a")]));
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_prefix_range_end() {
+    assert_eq!(prefix_range_end(b"/app/"), b"/app0".to_vec());
+    assert_eq!(prefix_range_end(b"a\xff"), b"b".to_vec());
+    assert_eq!(prefix_range_end(b"\xff"), vec![0]);
+    assert_eq!(prefix_range_end(b""), vec![0]);
+}
+
+#[test]
+fn test_parse_kubernetes_source() {
+    let source = KubernetesSource::parse("prod/app-config", "--k8s-configmap").unwrap();
+    assert_eq!(source.variable, None);
+    assert_eq!(source.namespace.as_deref(), Some("prod"));
+    assert_eq!(source.name, "app-config");
+
+    let source = KubernetesSource::parse("db=db-credentials", "--k8s-secret").unwrap();
+    assert_eq!(source.variable.as_deref(), Some("db"));
+    assert_eq!(source.namespace, None);
+    assert_eq!(source.name, "db-credentials");
+
+    assert!(KubernetesSource::parse("prod/", "--k8s-secret").is_err());
+    assert!(KubernetesSource::parse("=prod/app", "--k8s-secret").is_err());
+    assert!(KubernetesSource::parse("a/b/c", "--k8s-secret").is_err());
+}
+
+#[test]
+fn test_kubernetes_data_decodes_secrets() {
+    let secret = json!({"kind": "Secret", "data": {"password": "aHVudGVyMg=="}});
+    let data = kubernetes_data(&secret, true).unwrap();
+    assert_eq!(data.get("password"), Some(&json!("hunter2")));
+
+    let configmap = json!({
+        "kind": "ConfigMap",
+        "data": {"log_level": "debug"},
+        "binaryData": {"logo.png": "iVBORw0K"}
+    });
+    let data = kubernetes_data(&configmap, false).unwrap();
+    assert_eq!(data.get("log_level"), Some(&json!("debug")));
+    assert_eq!(data.get("logo.png"), Some(&json!("iVBORw0K")));
+
+    let invalid = json!({"data": {"password": "not base64!"}});
+    assert!(kubernetes_data(&invalid, true).is_err());
+}
+
+#[cfg(not(feature = "http"))]
+#[test]
+fn test_consul_prefix_requires_http_feature() {
+    let args = vec!["shinkansen", "--consul-prefix", "app/config/"];
+    let cli = Cli::try_parse_from(args).unwrap();
+    let result = collect_variables(&cli);
+
+    assert!(result.is_err());
+    assert!(result.unwrap_err().to_string().contains("'http' feature"));
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn test_consul_prefix_loads_nested_variables() {
+    // Values are base64 encoded by the Consul KV API; folders have a null value
+    let address = common::serve_once(
+        r#"[{"Key": "app/config/", "Value": null},
+            {"Key": "app/config/db/host", "Value": "ZGIuaW50ZXJuYWw="}]"#,
+        "application/json",
+    );
+    let args = vec![
+        "shinkansen",
+        "--consul-addr",
+        &address,
+        "--consul-prefix",
+        "app/config/",
+    ];
+    let cli = Cli::try_parse_from(args).unwrap();
+    let variables = collect_variables(&cli).unwrap();
+
+    let db = variables.get("db").unwrap();
+    assert_eq!(db.get_attr("host").unwrap().as_str(), Some("db.internal"));
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn test_etcd_prefix_loads_nested_variables() {
+    // {"/app/log/level": "debug"} as returned by the v3 JSON gateway
+    let endpoint = common::serve_once(
+        r#"{"kvs": [{"key": "L2FwcC9sb2cvbGV2ZWw=", "value": "ZGVidWc="}], "count": "1"}"#,
+        "application/json",
+    );
+    let args = vec![
+        "shinkansen",
+        "--etcd-endpoint",
+        &endpoint,
+        "--etcd-prefix",
+        "/app/",
+    ];
+    let cli = Cli::try_parse_from(args).unwrap();
+    let variables = collect_variables(&cli).unwrap();
+
+    let log = variables.get("log").unwrap();
+    assert_eq!(log.get_attr("level").unwrap().as_str(), Some("debug"));
+}
@@ -0,0 +1,116 @@
+use clap::Parser;
+use shinkansen_lib::cli::Cli;
+use shinkansen_lib::managed::ManagedBlock;
+use shinkansen_lib::processing::{process_inputs, validate_args};
+use shinkansen_lib::variables::collect_variables;
+use std::fs;
+use tempfile::tempdir;
+
+fn block() -> ManagedBlock {
+    ManagedBlock::new("# {mark} shinkansen").unwrap()
+}
+
+#[test]
+fn test_merge_replaces_only_the_marked_region() {
+    let existing =
+        "user = alice\n# BEGIN shinkansen\nold = 1\nstale = 2\n# END shinkansen\ncolor = blue\n";
+    let merged = block()
+        .merge(Some(existing), "new = 3", "app.conf")
+        .unwrap();
+    assert_eq!(
+        merged,
+        "user = alice\n# BEGIN shinkansen\nnew = 3\n# END shinkansen\ncolor = blue\n"
+    );
+}
+
+#[test]
+fn test_merge_keeps_indented_markers() {
+    let existing = "hosts:\n  # BEGIN shinkansen\n  - old\n  # END shinkansen\n";
+    let merged = block()
+        .merge(Some(existing), "  - a\n  - b\n", "hosts.yaml")
+        .unwrap();
+    assert_eq!(
+        merged,
+        "hosts:\n  # BEGIN shinkansen\n  - a\n  - b\n  # END shinkansen\n"
+    );
+}
+
+#[test]
+fn test_merge_appends_block_without_markers() {
+    let merged = block()
+        .merge(Some("user = alice"), "a = 1\n", "app.conf")
+        .unwrap();
+    assert_eq!(
+        merged,
+        "user = alice\n# BEGIN shinkansen\na = 1\n# END shinkansen\n"
+    );
+
+    let merged = block().merge(None, "a = 1", "app.conf").unwrap();
+    assert_eq!(merged, "# BEGIN shinkansen\na = 1\n# END shinkansen\n");
+}
+
+#[test]
+fn test_merge_rejects_unbalanced_markers() {
+    let missing_end = "# BEGIN shinkansen\nold\n";
+    let reversed = "# END shinkansen\nold\n# BEGIN shinkansen\n";
+    let duplicated = "# BEGIN shinkansen\n# END shinkansen\n# BEGIN shinkansen\n# END shinkansen\n";
+
+    for existing in [missing_end, reversed, duplicated] {
+        let err = block()
+            .merge(Some(existing), "new", "app.conf")
+            .unwrap_err();
+        assert!(err.to_string().contains("app.conf"));
+    }
+}
+
+#[test]
+fn test_custom_marker() {
+    let block = ManagedBlock::new("<!-- {mark} generated -->").unwrap();
+    assert_eq!(block.begin, "<!-- BEGIN generated -->");
+    assert_eq!(block.end, "<!-- END generated -->");
+
+    assert!(ManagedBlock::new("# shinkansen").is_err());
+}
+
+#[test]
+fn test_managed_block_flag_preserves_file() {
+    let temp_dir = tempdir().unwrap();
+    let template = temp_dir.path().join("hosts.tmpl");
+    let output = temp_dir.path().join("hosts");
+    fs::write(&template, "10.0.0.1 {{ name }}\n").unwrap();
+    fs::write(
+        &output,
+        "127.0.0.1 localhost\n# BEGIN shinkansen\n10.0.0.1 old\n# END shinkansen\n::1 localhost\n",
+    )
+    .unwrap();
+
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        template.to_str().unwrap(),
+        "-o",
+        output.to_str().unwrap(),
+        "-D",
+        "name=web",
+        "--managed-block",
+    ])
+    .unwrap();
+    validate_args(&cli).unwrap();
+    let variables = collect_variables(&cli).unwrap();
+    process_inputs(&cli, &variables, &Default::default()).unwrap();
+
+    assert_eq!(
+        fs::read_to_string(&output).unwrap(),
+        "127.0.0.1 localhost\n# BEGIN shinkansen\n10.0.0.1 web\n# END shinkansen\n::1 localhost\n"
+    );
+}
+
+#[test]
+fn test_managed_block_requires_file_output() {
+    let cli = Cli::try_parse_from(["shinkansen", "in.txt", "--managed-block"]).unwrap();
+    assert!(validate_args(&cli).is_err());
+
+    let cli = Cli::try_parse_from(["shinkansen", "in.txt", "-o", "-", "--managed-block"]).unwrap();
+    assert!(validate_args(&cli).is_err());
+
+    assert!(Cli::try_parse_from(["shinkansen", "--block-marker", "// {mark}"]).is_err());
+}
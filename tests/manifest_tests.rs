@@ -0,0 +1,68 @@
+use std::collections::HashMap;
+use std::fs;
+
+use clap::Parser;
+use tempfile::tempdir;
+
+use shinkansen_lib::cli::Cli;
+use shinkansen_lib::manifest::{Drift, Manifest, verify_manifest};
+use shinkansen_lib::processing::{process_inputs, validate_args};
+
+#[test]
+fn test_manifest_records_outputs_and_verify_reports_drift() {
+    let temp_dir = tempdir().unwrap();
+    let input_dir = temp_dir.path().join("templates");
+    let output_dir = temp_dir.path().join("deploy");
+    let manifest_path = output_dir.join("manifest.json");
+    fs::create_dir_all(input_dir.join("db")).unwrap();
+    fs::write(input_dir.join("app.yaml"), "replicas: {{ 1 + 2 }}").unwrap();
+    fs::write(input_dir.join("db").join("db.yaml"), "size: 10Gi").unwrap();
+    fs::write(input_dir.join("extra.yaml"), "extra").unwrap();
+
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        input_dir.to_str().unwrap(),
+        "-r",
+        "-o",
+        output_dir.to_str().unwrap(),
+        "--manifest",
+        manifest_path.to_str().unwrap(),
+    ])
+    .unwrap();
+    validate_args(&cli).unwrap();
+    process_inputs(&cli, &HashMap::new(), &Default::default()).unwrap();
+
+    let manifest = Manifest::read(&manifest_path).unwrap();
+    let files: Vec<&str> = manifest.sha256.keys().map(String::as_str).collect();
+    assert_eq!(files, ["app.yaml", "db/db.yaml", "extra.yaml"]);
+
+    let report = verify_manifest(&manifest_path).unwrap();
+    assert!(!report.has_drift());
+    assert_eq!(report.checked, 3);
+    assert!(report.report().contains("3 file(s) match"));
+
+    fs::write(output_dir.join("app.yaml"), "replicas: 30").unwrap();
+    fs::remove_file(output_dir.join("extra.yaml")).unwrap();
+    let report = verify_manifest(&manifest_path).unwrap();
+    let drifts: Vec<Drift> = report.drifted.iter().map(|(_, drift)| *drift).collect();
+    assert_eq!(drifts, [Drift::Modified, Drift::Missing]);
+    assert!(report.drifted[0].0.ends_with("app.yaml"));
+    assert!(report.report().contains("2 of 3 file(s) differ from"));
+}
+
+#[test]
+fn test_manifest_requires_file_output() {
+    let cli = Cli::try_parse_from(["shinkansen", "a.txt", "--manifest", "m.json"]).unwrap();
+    let err = validate_args(&cli).unwrap_err();
+    assert!(err.to_string().contains("--manifest requires"));
+}
+
+#[test]
+fn test_verify_rejects_invalid_manifest() {
+    let temp_dir = tempdir().unwrap();
+    let manifest_path = temp_dir.path().join("manifest.json");
+    fs::write(&manifest_path, "not json").unwrap();
+
+    let err = verify_manifest(&manifest_path).unwrap_err();
+    assert!(err.to_string().contains("Invalid manifest"), "{}", err);
+}
@@ -2,7 +2,10 @@ use std::path::PathBuf;
 use tempfile::tempdir;
 
 use shinkansen_lib::cli::Cli;
-use shinkansen_lib::output::{OutputDestination, determine_output_destination, write_to_output};
+use shinkansen_lib::output::{
+    ColorChoice, OutputDestination, OverwritePrompt, colorize_diff, determine_output_destination,
+    diff_for_file, resolve_output_path, write_to_output,
+};
 
 #[test]
 fn test_determine_output_destination_stdout() {
@@ -12,8 +15,9 @@ fn test_determine_output_destination_stdout() {
         output: Some("-".to_string()),
         recursive: false,
         variables: vec![],
-        config: None,
+        config: Vec::new(),
         env: None,
+        ..Default::default()
     };
 
     let result = determine_output_destination(&cli, true).unwrap();
@@ -28,8 +32,9 @@ fn test_determine_output_destination_single_file() {
         output: Some("output.txt".to_string()),
         recursive: false,
         variables: vec![],
-        config: None,
+        config: Vec::new(),
         env: None,
+        ..Default::default()
     };
 
     let result = determine_output_destination(&cli, true).unwrap();
@@ -37,7 +42,7 @@ fn test_determine_output_destination_single_file() {
         OutputDestination::SingleFile(path) => {
             assert_eq!(path, PathBuf::from("output.txt"));
         }
-        _ => assert!(false, "Expected SingleFile output destination"),
+        _ => panic!("Expected SingleFile output destination"),
     }
 }
 
@@ -49,8 +54,9 @@ fn test_determine_output_destination_directory() {
         output: Some("output_dir".to_string()),
         recursive: false,
         variables: vec![],
-        config: None,
+        config: Vec::new(),
         env: None,
+        ..Default::default()
     };
 
     let result = determine_output_destination(&cli, false).unwrap();
@@ -58,7 +64,7 @@ fn test_determine_output_destination_directory() {
         OutputDestination::Directory(path) => {
             assert_eq!(path, PathBuf::from("output_dir"));
         }
-        _ => assert!(false, "Expected Directory output destination"),
+        _ => panic!("Expected Directory output destination"),
     }
 }
 
@@ -70,8 +76,9 @@ fn test_determine_output_destination_default_stdout() {
         output: None,
         recursive: false,
         variables: vec![],
-        config: None,
+        config: Vec::new(),
         env: None,
+        ..Default::default()
     };
 
     let result = determine_output_destination(&cli, true).unwrap();
@@ -86,8 +93,9 @@ fn test_determine_output_destination_error() {
         output: None,
         recursive: false,
         variables: vec![],
-        config: None,
+        config: Vec::new(),
         env: None,
+        ..Default::default()
     };
 
     let result = determine_output_destination(&cli, false);
@@ -106,8 +114,9 @@ fn test_write_to_stdout() {
         output: Some("-".to_string()),
         recursive: false,
         variables: vec![],
-        config: None,
+        config: Vec::new(),
         env: None,
+        ..Default::default()
     };
 
     // This should not panic and should write to stdout
@@ -130,8 +139,9 @@ fn test_write_to_single_file() {
         output: Some(output_path.to_str().unwrap().to_string()),
         recursive: false,
         variables: vec![],
-        config: None,
+        config: Vec::new(),
         env: None,
+        ..Default::default()
     };
 
     let result = write_to_output(&destination, &input_path, content, &cli);
@@ -157,8 +167,9 @@ fn test_write_to_directory() {
         output: Some(output_dir.to_str().unwrap().to_string()),
         recursive: false,
         variables: vec![],
-        config: None,
+        config: Vec::new(),
         env: None,
+        ..Default::default()
     };
 
     let result = write_to_output(&destination, &input_path, content, &cli);
@@ -169,3 +180,146 @@ fn test_write_to_directory() {
     let written_content = std::fs::read_to_string(output_file).unwrap();
     assert_eq!(written_content, content);
 }
+
+#[cfg(unix)]
+#[test]
+fn test_overwrite_keeps_permissions_and_leaves_no_temp_file() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp_dir = tempdir().unwrap();
+    let output_path = temp_dir.path().join("run.sh");
+    std::fs::write(&output_path, "old").unwrap();
+    std::fs::set_permissions(&output_path, std::fs::Permissions::from_mode(0o750)).unwrap();
+
+    let destination = OutputDestination::SingleFile(output_path.clone());
+    let cli = Cli::default();
+    write_to_output(&destination, &PathBuf::from("run.sh"), "new", &cli).unwrap();
+
+    assert_eq!(std::fs::read_to_string(&output_path).unwrap(), "new");
+    let mode = std::fs::metadata(&output_path)
+        .unwrap()
+        .permissions()
+        .mode();
+    assert_eq!(mode & 0o777, 0o750);
+    let names: Vec<_> = std::fs::read_dir(temp_dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    assert_eq!(names, ["run.sh"]);
+}
+
+#[test]
+fn test_strip_and_map_extensions() {
+    use clap::Parser;
+
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        "a.txt",
+        "b.txt",
+        "-o",
+        "out",
+        "--strip-extension",
+        "j2",
+        "--strip-extension",
+        ".tmpl",
+        "--extension-map",
+        ".yaml.j2=.yml",
+        "--extension-map",
+        ".tpl=",
+    ])
+    .unwrap();
+    let destination = OutputDestination::Directory(PathBuf::from("out"));
+    let output = |input: &str| resolve_output_path(&destination, &PathBuf::from(input), &cli);
+
+    assert_eq!(output("app.conf.j2"), Some(PathBuf::from("out/app.conf")));
+    assert_eq!(output("motd.tmpl"), Some(PathBuf::from("out/motd")));
+    assert_eq!(
+        output("deploy.yaml.j2"),
+        Some(PathBuf::from("out/deploy.yml"))
+    );
+    assert_eq!(output("Makefile.tpl"), Some(PathBuf::from("out/Makefile")));
+    assert_eq!(output("notes.txt"), Some(PathBuf::from("out/notes.txt")));
+    // A name that is only the extension is kept
+    assert_eq!(output(".j2"), Some(PathBuf::from("out/.j2")));
+
+    // Explicit output files are used as given
+    let single = OutputDestination::SingleFile(PathBuf::from("result.j2"));
+    assert_eq!(
+        resolve_output_path(&single, &PathBuf::from("app.j2"), &cli),
+        Some(PathBuf::from("result.j2"))
+    );
+}
+
+#[test]
+fn test_invalid_extension_flags() {
+    use clap::Parser;
+
+    assert!(Cli::try_parse_from(["shinkansen", "--strip-extension", "."]).is_err());
+    assert!(Cli::try_parse_from(["shinkansen", "--extension-map", ".j2"]).is_err());
+    assert!(Cli::try_parse_from(["shinkansen", "--extension-map", "=.txt"]).is_err());
+}
+
+#[test]
+fn test_diff_for_file() {
+    let temp_dir = tempdir().unwrap();
+    let path = temp_dir.path().join("app.conf");
+    std::fs::write(&path, "port=80\nhost=a\n").unwrap();
+
+    let diff = diff_for_file(&path, "port=8080\nhost=a\n", false).unwrap();
+    assert!(diff.contains("-port=80\n+port=8080\n"), "{}", diff);
+    assert!(!diff.contains('\x1b'));
+    assert_eq!(
+        diff_for_file(&path, "port=80\nhost=a\n", false).unwrap(),
+        ""
+    );
+
+    let created = diff_for_file(&temp_dir.path().join("new.conf"), "x\n", false).unwrap();
+    assert!(created.contains("--- /dev/null"), "{}", created);
+}
+
+#[test]
+fn test_colorize_diff() {
+    let diff = "diff --git a/f b/f\n--- a/f\n+++ b/f\n@@ -1 +1 @@\n--- old\n+new\n same\n";
+    assert_eq!(
+        colorize_diff(diff),
+        "\x1b[1mdiff --git a/f b/f\x1b[0m\n\x1b[1m--- a/f\x1b[0m\n\x1b[1m+++ b/f\x1b[0m\n\
+         \x1b[36m@@ -1 +1 @@\x1b[0m\n\x1b[31m--- old\x1b[0m\n\x1b[32m+new\x1b[0m\n same\n"
+    );
+    assert!(ColorChoice::Always.enabled());
+    assert!(!ColorChoice::Never.enabled());
+}
+
+#[test]
+fn test_overwrite_prompt() {
+    let temp_dir = tempdir().unwrap();
+    let path = temp_dir.path().join("app.conf");
+    std::fs::write(&path, "old").unwrap();
+
+    let ask = |answers: &str| {
+        OverwritePrompt::new(
+            Box::new(std::io::Cursor::new(answers.to_string())),
+            Box::new(std::io::sink()),
+        )
+    };
+
+    // Unchanged and missing files are not asked about
+    let mut prompt = ask("");
+    assert!(prompt.confirm(&path, "old").unwrap());
+    assert!(
+        prompt
+            .confirm(&temp_dir.path().join("new.conf"), "x")
+            .unwrap()
+    );
+    assert!(!prompt.confirm(&path, "new").unwrap());
+
+    let mut prompt = ask("y\nn\n");
+    assert!(prompt.confirm(&path, "new").unwrap());
+    assert!(!prompt.confirm(&path, "new").unwrap());
+
+    let mut prompt = ask("a\n");
+    assert!(prompt.confirm(&path, "new").unwrap());
+    assert!(prompt.confirm(&path, "newer").unwrap());
+
+    let err = ask("q\n").confirm(&path, "new").unwrap_err();
+    assert!(err.to_string().contains("not overwritten"));
+}
@@ -0,0 +1,83 @@
+use clap::Parser;
+use shinkansen_lib::cli::Cli;
+use shinkansen_lib::patch::file_patch;
+use shinkansen_lib::processing::{process_inputs, validate_args};
+use shinkansen_lib::variables::collect_variables;
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+
+#[test]
+fn test_patch_for_changed_file() {
+    let patch = file_patch(
+        Path::new("./deploy/app.yaml"),
+        Some("name: web\nreplicas: 1\n"),
+        "name: web\nreplicas: 3\n",
+    );
+    assert_eq!(
+        patch,
+        "diff --git a/deploy/app.yaml b/deploy/app.yaml\n\
+         --- a/deploy/app.yaml\n\
+         +++ b/deploy/app.yaml\n\
+         @@ -1,2 +1,2 @@\n \
+         name: web\n\
+         -replicas: 1\n\
+         +replicas: 3\n"
+    );
+}
+
+#[test]
+fn test_patch_for_new_file() {
+    let patch = file_patch(Path::new("out/motd"), None, "hello\n");
+    assert_eq!(
+        patch,
+        "diff --git a/out/motd b/out/motd\n\
+         new file mode 100644\n\
+         --- /dev/null\n\
+         +++ b/out/motd\n\
+         @@ -0,0 +1 @@\n\
+         +hello\n"
+    );
+}
+
+#[test]
+fn test_patch_for_unchanged_file_is_empty() {
+    assert_eq!(file_patch(Path::new("a.txt"), Some("same\n"), "same\n"), "");
+}
+
+#[test]
+fn test_patch_marks_missing_trailing_newline() {
+    let patch = file_patch(Path::new("a.txt"), Some("old\n"), "new");
+    assert!(patch.contains("+new\n\\ No newline at end of file\n"));
+}
+
+#[test]
+fn test_emit_patch_leaves_outputs_untouched() {
+    let temp_dir = tempdir().unwrap();
+    let template = temp_dir.path().join("motd.tmpl");
+    let output = temp_dir.path().join("motd");
+    fs::write(&template, "Welcome to {{ host }}\n").unwrap();
+    fs::write(&output, "Welcome\n").unwrap();
+
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        template.to_str().unwrap(),
+        "-o",
+        output.to_str().unwrap(),
+        "-D",
+        "host=web1",
+        "--emit-patch",
+    ])
+    .unwrap();
+    validate_args(&cli).unwrap();
+    let variables = collect_variables(&cli).unwrap();
+    process_inputs(&cli, &variables, &Default::default()).unwrap();
+
+    assert_eq!(fs::read_to_string(&output).unwrap(), "Welcome\n");
+}
+
+#[test]
+fn test_emit_patch_requires_file_output() {
+    let cli = Cli::try_parse_from(["shinkansen", "in.txt", "--emit-patch"]).unwrap();
+    assert!(validate_args(&cli).is_err());
+}
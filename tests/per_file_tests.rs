@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+use std::fs;
+
+use clap::Parser;
+use minijinja::Value;
+use tempfile::tempdir;
+
+use shinkansen_lib::cli::Cli;
+use shinkansen_lib::per_file::{PerFileRender, PerFileVars};
+use shinkansen_lib::processing::{process_inputs, process_inputs_with, validate_args};
+
+#[test]
+fn test_per_file_vars_render_a_template_once_per_entry() {
+    let temp_dir = tempdir().unwrap();
+    let input_dir = temp_dir.path().join("templates");
+    let output_dir = temp_dir.path().join("out");
+    fs::create_dir_all(&input_dir).unwrap();
+    let vhost = input_dir.join("vhost.conf");
+    let app = input_dir.join("app.yaml");
+    fs::write(&vhost, "server_name {{ server_name }}; # {{ owner }}").unwrap();
+    fs::write(&app, "replicas: {{ app.replicas }}, image: {{ app.image }}").unwrap();
+    fs::write(input_dir.join("plain.txt"), "{{ owner }}").unwrap();
+
+    let vars_file = temp_dir.path().join("renders.yaml");
+    fs::write(
+        &vars_file,
+        format!(
+            "{}:\n  vars: {{app: {{replicas: 3}}}}\n\
+             {}:\n  - output: sites/a.conf\n    vars: {{server_name: a.example.com}}\n  \
+             - output: sites/b.conf\n    vars: {{server_name: b.example.com, owner: ops}}\n",
+            app.display(),
+            vhost.display()
+        ),
+    )
+    .unwrap();
+
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        input_dir.to_str().unwrap(),
+        "-o",
+        output_dir.to_str().unwrap(),
+        "--per-file-vars",
+        vars_file.to_str().unwrap(),
+    ])
+    .unwrap();
+    validate_args(&cli).unwrap();
+    let variables = HashMap::from([
+        ("owner".to_string(), Value::from("web")),
+        (
+            "app".to_string(),
+            Value::from_serialize(serde_json::json!({"replicas": 1, "image": "nginx"})),
+        ),
+    ]);
+    process_inputs(&cli, &variables, &Default::default()).unwrap();
+
+    let read = |path: &str| fs::read_to_string(output_dir.join(path)).unwrap();
+    assert_eq!(read("sites/a.conf"), "server_name a.example.com; # web");
+    assert_eq!(read("sites/b.conf"), "server_name b.example.com; # ops");
+    assert_eq!(read("app.yaml"), "replicas: 3, image: nginx");
+    assert_eq!(read("plain.txt"), "web");
+    assert!(!output_dir.join("vhost.conf").exists());
+}
+
+#[test]
+fn test_per_file_vars_from_the_library() {
+    let temp_dir = tempdir().unwrap();
+    let input = temp_dir.path().join("greeting.txt");
+    let output = temp_dir.path().join("greeting.out");
+    fs::write(&input, "Hello, {{ name }}!").unwrap();
+
+    let mut per_file = PerFileVars::new();
+    per_file
+        .add(
+            &input,
+            PerFileRender {
+                output: Some("greeting.out".into()),
+                vars: HashMap::from([("name".to_string(), Value::from("library"))]),
+            },
+        )
+        .unwrap();
+    let output_dir = format!("{}/", temp_dir.path().display());
+    let cli =
+        Cli::try_parse_from(["shinkansen", input.to_str().unwrap(), "-o", &output_dir]).unwrap();
+    process_inputs_with(&cli, &HashMap::new(), &per_file, &Default::default()).unwrap();
+    assert_eq!(fs::read_to_string(&output).unwrap(), "Hello, library!");
+
+    let stdin = Cli::try_parse_from(["shinkansen", "-"]).unwrap();
+    let err =
+        process_inputs_with(&stdin, &HashMap::new(), &per_file, &Default::default()).unwrap_err();
+    assert!(
+        err.to_string().contains("cannot come from stdin"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_per_file_vars_need_distinct_outputs() {
+    let render = |output: Option<&str>| PerFileRender {
+        output: output.map(Into::into),
+        vars: HashMap::new(),
+    };
+
+    let mut per_file = PerFileVars::new();
+    per_file.add("t.conf", render(None)).unwrap();
+    let err = per_file.add("t.conf", render(Some("a.conf"))).unwrap_err();
+    assert!(err.to_string().contains("every render needs an output"));
+
+    let mut per_file = PerFileVars::new();
+    per_file.add("t.conf", render(Some("a.conf"))).unwrap();
+    let err = per_file.add("t.conf", render(Some("a.conf"))).unwrap_err();
+    assert!(err.to_string().contains("more than once"));
+    assert_eq!(per_file.renders("t.conf".as_ref()).unwrap().len(), 1);
+
+    let err = per_file
+        .add("u.conf", render(Some("../escape.conf")))
+        .unwrap_err();
+    assert!(
+        format!("{:#}", err).contains("parent directory"),
+        "{:#}",
+        err
+    );
+    let absolute = if cfg!(windows) {
+        "C:\\app.conf"
+    } else {
+        "/etc/app.conf"
+    };
+    let err = per_file.add("u.conf", render(Some(absolute))).unwrap_err();
+    assert!(
+        err.to_string().contains("must be a relative path"),
+        "{}",
+        err
+    );
+    assert!(per_file.renders("u.conf".as_ref()).is_none());
+
+    let temp_dir = tempdir().unwrap();
+    let vars_file = temp_dir.path().join("renders.yaml");
+    fs::write(&vars_file, "t.conf:\n  vars: {a: 1}\n  extra: true\n").unwrap();
+    assert!(PerFileVars::read(&vars_file).is_err());
+}
@@ -1,6 +1,10 @@
 use clap::Parser;
 use shinkansen_lib::cli::Cli;
-use shinkansen_lib::processing::validate_args;
+use shinkansen_lib::diagnostics::Diagnostics;
+use shinkansen_lib::processing::{process_inputs, validate_args};
+use std::collections::HashMap;
+use std::fs;
+use tempfile::tempdir;
 
 #[test]
 fn test_validate_no_input_defaults_to_stdin() {
@@ -160,3 +164,706 @@ fn test_validate_valid_single_file_with_output() {
 
     assert!(result.is_ok());
 }
+
+#[test]
+fn test_process_recursive_directory_tree() {
+    let temp_dir = tempdir().unwrap();
+    let input_dir = temp_dir.path().join("templates");
+    let output_dir = temp_dir.path().join("out");
+    for i in 0..50 {
+        let dir = input_dir.join(format!("d{}", i % 5));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(format!("f{}.txt", i)), "n={{ 6 * 7 }}").unwrap();
+    }
+
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        input_dir.to_str().unwrap(),
+        "-r",
+        "-o",
+        output_dir.to_str().unwrap(),
+    ])
+    .unwrap();
+    process_inputs(&cli, &HashMap::new(), &Default::default()).unwrap();
+
+    for i in 0..50 {
+        let output = output_dir
+            .join(format!("d{}", i % 5))
+            .join(format!("f{}.txt", i));
+        assert_eq!(fs::read_to_string(output).unwrap(), "n=42");
+    }
+}
+
+#[test]
+fn test_process_empty_directory() {
+    let temp_dir = tempdir().unwrap();
+    let input_dir = temp_dir.path().join("empty");
+    fs::create_dir_all(input_dir.join("nested")).unwrap();
+
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        input_dir.to_str().unwrap(),
+        "-o",
+        temp_dir.path().join("out").to_str().unwrap(),
+    ])
+    .unwrap();
+    let diagnostics = Diagnostics::default();
+    process_inputs(&cli, &HashMap::new(), &diagnostics).unwrap();
+    assert_eq!(diagnostics.warnings(), vec!["No files found to process"]);
+    assert!(!temp_dir.path().join("out").exists());
+}
+
+#[test]
+fn test_process_empty_directory_with_fail_if_no_files() {
+    let temp_dir = tempdir().unwrap();
+    let input_dir = temp_dir.path().join("empty");
+    fs::create_dir_all(&input_dir).unwrap();
+
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        input_dir.to_str().unwrap(),
+        "-o",
+        temp_dir.path().join("out").to_str().unwrap(),
+        "--fail-if-no-files",
+    ])
+    .unwrap();
+    let err = process_inputs(&cli, &HashMap::new(), &Default::default()).unwrap_err();
+    assert_eq!(err.to_string(), "No files found to process");
+}
+
+#[test]
+fn test_unmatched_glob_is_skipped() {
+    let temp_dir = tempdir().unwrap();
+    let template = temp_dir.path().join("app.txt");
+    fs::write(&template, "{{ 'rendered' }}").unwrap();
+    let pattern = temp_dir.path().join("*.tmpl");
+    let output_dir = temp_dir.path().join("out");
+    fs::create_dir_all(&output_dir).unwrap();
+
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        template.to_str().unwrap(),
+        pattern.to_str().unwrap(),
+        "-o",
+        output_dir.to_str().unwrap(),
+    ])
+    .unwrap();
+    let diagnostics = Diagnostics::default();
+    process_inputs(&cli, &HashMap::new(), &diagnostics).unwrap();
+    assert_eq!(
+        fs::read_to_string(output_dir.join("app.txt")).unwrap(),
+        "rendered"
+    );
+    assert_eq!(
+        diagnostics.warnings(),
+        vec![format!("No files match {:?}", pattern.to_str().unwrap())]
+    );
+}
+
+#[test]
+fn test_unmatched_glob_with_fail_if_no_files() {
+    let temp_dir = tempdir().unwrap();
+    let pattern = temp_dir.path().join("*.tmpl");
+
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        pattern.to_str().unwrap(),
+        "-o",
+        "-",
+        "--fail-if-no-files",
+    ])
+    .unwrap();
+    let err = process_inputs(&cli, &HashMap::new(), &Default::default()).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!("No files match {:?}", pattern.to_str().unwrap())
+    );
+}
+
+#[test]
+fn test_missing_plain_input_is_still_an_error() {
+    let cli =
+        Cli::try_parse_from(["shinkansen", "/nonexistent/shinkansen/app.txt", "-o", "-"]).unwrap();
+    let err = process_inputs(&cli, &HashMap::new(), &Default::default()).unwrap_err();
+    assert!(err.to_string().starts_with("Input does not exist"));
+}
+
+#[test]
+fn test_process_single_file_in_directory_goes_to_output_file() {
+    let temp_dir = tempdir().unwrap();
+    let input_dir = temp_dir.path().join("one");
+    fs::create_dir_all(&input_dir).unwrap();
+    fs::write(input_dir.join("only.txt"), "{{ 'single' }}").unwrap();
+    let output = temp_dir.path().join("result.txt");
+
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        input_dir.to_str().unwrap(),
+        "-o",
+        output.to_str().unwrap(),
+    ])
+    .unwrap();
+    process_inputs(&cli, &HashMap::new(), &Default::default()).unwrap();
+    assert_eq!(fs::read_to_string(output).unwrap(), "single");
+}
+
+#[test]
+fn test_filehash_resolves_against_input_directory() {
+    let dir = tempdir().unwrap();
+    let templates = dir.path().join("templates");
+    fs::create_dir_all(templates.join("deploy")).unwrap();
+    fs::create_dir(dir.path().join("config")).unwrap();
+    fs::write(templates.join("app.conf"), "hello\n").unwrap();
+    fs::write(dir.path().join("config/db.conf"), "hello\n").unwrap();
+    fs::write(
+        templates.join("deploy/pod.yaml"),
+        "app: {{ filehash('app.conf') }}\ndb: {{ filehash('../config/db.conf') }}",
+    )
+    .unwrap();
+    let output = dir.path().join("out");
+
+    let mut args = vec![
+        "shinkansen".to_string(),
+        "-r".to_string(),
+        templates.to_str().unwrap().to_string(),
+        "-o".to_string(),
+        output.to_str().unwrap().to_string(),
+    ];
+    let cli = Cli::try_parse_from(&args).unwrap();
+    let err = process_inputs(&cli, &HashMap::new(), &Diagnostics::default()).unwrap_err();
+    assert!(err.to_string().contains("is outside the template root"));
+
+    args.extend([
+        "--allow-dir".to_string(),
+        dir.path().join("config").to_str().unwrap().to_string(),
+    ]);
+    let cli = Cli::try_parse_from(&args).unwrap();
+    process_inputs(&cli, &HashMap::new(), &Diagnostics::default()).unwrap();
+    let hash = "5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03";
+    assert_eq!(
+        fs::read_to_string(output.join("deploy/pod.yaml")).unwrap(),
+        format!("app: {}\ndb: {}", hash, hash)
+    );
+}
+
+#[test]
+fn test_validate_missing_template_dir() {
+    let cli =
+        Cli::try_parse_from(["shinkansen", "--template-dir", "/nonexistent/partials"]).unwrap();
+    let err = validate_args(&cli).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("Template directory does not exist: \"/nonexistent/partials\"")
+    );
+}
+
+#[test]
+fn test_parallel_rendering_keeps_input_order() {
+    let temp_dir = tempdir().unwrap();
+    let output_dir = temp_dir.path().join("out");
+    let mut args = vec!["shinkansen".to_string()];
+    for i in 0..40 {
+        let path = temp_dir.path().join(format!("f{:02}.txt", i));
+        if i % 9 == 4 {
+            fs::write(&path, [0u8, 1, 2]).unwrap();
+        } else {
+            fs::write(&path, format!("n={{{{ {} * 2 }}}}", i)).unwrap();
+        }
+        args.push(path.to_str().unwrap().to_string());
+    }
+    args.extend(["-o", output_dir.to_str().unwrap(), "-j", "8"].map(String::from));
+
+    let cli = Cli::try_parse_from(&args).unwrap();
+    let diagnostics = Diagnostics::default();
+    process_inputs(&cli, &HashMap::new(), &diagnostics).unwrap();
+
+    for i in (0..40).filter(|i| i % 9 != 4) {
+        let output = output_dir.join(format!("f{:02}.txt", i));
+        assert_eq!(fs::read_to_string(output).unwrap(), format!("n={}", i * 2));
+    }
+    let skipped: Vec<String> = [4, 13, 22, 31]
+        .iter()
+        .map(|i| {
+            let path = temp_dir.path().join(format!("f{:02}.txt", i));
+            format!("Skipped binary file {:?}", path)
+        })
+        .collect();
+    assert_eq!(diagnostics.warnings(), skipped);
+}
+
+#[test]
+fn test_parallel_rendering_reports_first_error_in_input_order() {
+    let temp_dir = tempdir().unwrap();
+    let output_dir = temp_dir.path().join("out");
+    let mut args = vec!["shinkansen".to_string()];
+    for i in 0..40 {
+        let path = temp_dir.path().join(format!("f{:02}.txt", i));
+        let content = if i == 7 || i == 30 {
+            "{{ broken("
+        } else {
+            "ok"
+        };
+        fs::write(&path, content).unwrap();
+        args.push(path.to_str().unwrap().to_string());
+    }
+    args.extend(["-o", output_dir.to_str().unwrap(), "--jobs", "8"].map(String::from));
+
+    let cli = Cli::try_parse_from(&args).unwrap();
+    let err = process_inputs(&cli, &HashMap::new(), &Diagnostics::default()).unwrap_err();
+    assert!(err.to_string().contains("f07.txt"), "{}", err);
+
+    // Files before the failing one are written, later ones are not
+    assert!(output_dir.join("f06.txt").exists());
+    assert!(!output_dir.join("f08.txt").exists());
+}
+
+#[test]
+fn test_jobs_must_be_positive() {
+    assert!(Cli::try_parse_from(["shinkansen", "-j", "0"]).is_err());
+    let cli = Cli::try_parse_from(["shinkansen", "--jobs", "3"]).unwrap();
+    assert_eq!(cli.jobs.map(|jobs| jobs.get()), Some(3));
+}
+
+#[test]
+fn test_glob_input_expands_recursively() {
+    let temp_dir = tempdir().unwrap();
+    let templates = temp_dir.path().join("templates");
+    fs::create_dir_all(templates.join("apps/api")).unwrap();
+    fs::write(templates.join("base.yaml.j2"), "base: {{ 1 + 1 }}").unwrap();
+    fs::write(
+        templates.join("apps/api/deploy.yaml.j2"),
+        "api: {{ 2 + 2 }}",
+    )
+    .unwrap();
+    fs::write(templates.join("apps/README.md"), "{{ skipped }}").unwrap();
+    let output_dir = temp_dir.path().join("out");
+
+    let pattern = format!("{}/**/*.yaml.j2", templates.to_str().unwrap());
+    let cli =
+        Cli::try_parse_from(["shinkansen", &pattern, "-o", output_dir.to_str().unwrap()]).unwrap();
+    process_inputs(&cli, &HashMap::new(), &Diagnostics::default()).unwrap();
+
+    // The structure below the literal part of the pattern is kept
+    assert_eq!(
+        fs::read_to_string(output_dir.join("base.yaml.j2")).unwrap(),
+        "base: 2"
+    );
+    assert_eq!(
+        fs::read_to_string(output_dir.join("apps/api/deploy.yaml.j2")).unwrap(),
+        "api: 4"
+    );
+    assert!(!output_dir.join("apps/README.md").exists());
+}
+
+#[test]
+fn test_glob_input_star_stays_in_directory() {
+    let temp_dir = tempdir().unwrap();
+    fs::create_dir_all(temp_dir.path().join("nested")).unwrap();
+    fs::write(temp_dir.path().join("a.tmpl"), "a").unwrap();
+    fs::write(temp_dir.path().join("b.tmpl"), "b").unwrap();
+    fs::write(temp_dir.path().join("nested/c.tmpl"), "c").unwrap();
+    let output_dir = temp_dir.path().join("out");
+
+    let pattern = temp_dir.path().join("*.{tmpl,txt}");
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        pattern.to_str().unwrap(),
+        "-o",
+        output_dir.to_str().unwrap(),
+    ])
+    .unwrap();
+    process_inputs(&cli, &HashMap::new(), &Diagnostics::default()).unwrap();
+
+    assert_eq!(fs::read_to_string(output_dir.join("a.tmpl")).unwrap(), "a");
+    assert_eq!(fs::read_to_string(output_dir.join("b.tmpl")).unwrap(), "b");
+    assert!(!output_dir.join("nested").exists());
+}
+
+#[test]
+fn test_invalid_glob_input() {
+    let cli = Cli::try_parse_from(["shinkansen", "templates/[a-", "-o", "-"]).unwrap();
+    let err = process_inputs(&cli, &HashMap::new(), &Default::default()).unwrap_err();
+    assert!(
+        err.to_string()
+            .starts_with("Invalid glob pattern \"templates/[a-\"")
+    );
+}
+
+#[test]
+fn test_exclude_skips_names_and_paths() {
+    let temp_dir = tempdir().unwrap();
+    let input_dir = temp_dir.path().join("site");
+    for dir in ["node_modules/pkg", ".git", "assets", "docs/drafts"] {
+        fs::create_dir_all(input_dir.join(dir)).unwrap();
+    }
+    for file in [
+        "index.html",
+        "node_modules/pkg/index.js",
+        ".git/HEAD",
+        "assets/logo.png",
+        "assets/app.css",
+        "docs/guide.md",
+        "docs/drafts/wip.md",
+    ] {
+        fs::write(input_dir.join(file), "{{ 'x' }}").unwrap();
+    }
+    let output_dir = temp_dir.path().join("out");
+
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        input_dir.to_str().unwrap(),
+        "-r",
+        "-o",
+        output_dir.to_str().unwrap(),
+        "--exclude",
+        "node_modules",
+        "--exclude",
+        ".git",
+        "--exclude",
+        "*.png",
+        "--exclude",
+        "docs/drafts/**",
+    ])
+    .unwrap();
+    process_inputs(&cli, &HashMap::new(), &Diagnostics::default()).unwrap();
+
+    let mut written: Vec<String> = walkdir::WalkDir::new(&output_dir)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .map(|entry| {
+            let relative = entry.path().strip_prefix(&output_dir).unwrap();
+            relative.to_str().unwrap().replace('\\', "/")
+        })
+        .collect();
+    written.sort();
+    assert_eq!(written, ["assets/app.css", "docs/guide.md", "index.html"]);
+}
+
+#[test]
+fn test_exclude_applies_to_direct_children_and_globs() {
+    let temp_dir = tempdir().unwrap();
+    fs::write(temp_dir.path().join("app.conf"), "app").unwrap();
+    fs::write(temp_dir.path().join("db.conf"), "db").unwrap();
+    fs::write(temp_dir.path().join("app.conf.bak"), "backup").unwrap();
+    let output_dir = temp_dir.path().join("out");
+
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        temp_dir.path().to_str().unwrap(),
+        "-o",
+        output_dir.to_str().unwrap(),
+        "--exclude",
+        "*.bak",
+    ])
+    .unwrap();
+    process_inputs(&cli, &HashMap::new(), &Diagnostics::default()).unwrap();
+    assert!(output_dir.join("app.conf").exists());
+    assert!(output_dir.join("db.conf").exists());
+    assert!(!output_dir.join("app.conf.bak").exists());
+
+    // A glob whose only matches are excluded matches nothing
+    let pattern = temp_dir.path().join("*.bak");
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        pattern.to_str().unwrap(),
+        "-o",
+        "-",
+        "--exclude",
+        "*.bak",
+        "--fail-if-no-files",
+    ])
+    .unwrap();
+    let err = process_inputs(&cli, &HashMap::new(), &Default::default()).unwrap_err();
+    assert!(err.to_string().starts_with("No files match"));
+}
+
+#[test]
+fn test_invalid_exclude_pattern() {
+    let temp_dir = tempdir().unwrap();
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        temp_dir.path().to_str().unwrap(),
+        "-o",
+        "-",
+        "--exclude",
+        "[a-",
+    ])
+    .unwrap();
+    let err = process_inputs(&cli, &HashMap::new(), &Default::default()).unwrap_err();
+    assert!(
+        err.to_string()
+            .starts_with("Invalid exclude pattern \"[a-\"")
+    );
+}
+
+#[test]
+fn test_validate_in_place() {
+    let cli = Cli::try_parse_from(["shinkansen", "a.txt", "b.txt", "--in-place"]).unwrap();
+    assert!(validate_args(&cli).is_ok());
+
+    let cli = Cli::try_parse_from(["shinkansen", "--in-place"]).unwrap();
+    let err = validate_args(&cli).unwrap_err();
+    assert!(err.to_string().contains("--in-place requires input files"));
+
+    let cli = Cli::try_parse_from(["shinkansen", "a.txt", "--in-place", "--suffix", ""]).unwrap();
+    assert!(
+        validate_args(&cli)
+            .unwrap_err()
+            .to_string()
+            .contains("Invalid --suffix")
+    );
+
+    assert!(Cli::try_parse_from(["shinkansen", "a.txt", "--in-place", "-o", "out.txt"]).is_err());
+    assert!(Cli::try_parse_from(["shinkansen", "a.txt", "--suffix", ".bak"]).is_err());
+}
+
+#[test]
+fn test_process_directory_in_place_with_backups() {
+    let temp_dir = tempdir().unwrap();
+    let input_dir = temp_dir.path().join("conf");
+    fs::create_dir_all(input_dir.join("sub")).unwrap();
+    fs::write(input_dir.join("app.conf"), "port={{ port }}").unwrap();
+    fs::write(input_dir.join("sub").join("db.conf"), "n={{ 6 * 7 }}").unwrap();
+
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        input_dir.to_str().unwrap(),
+        "-r",
+        "--in-place",
+        "--suffix",
+        ".bak",
+    ])
+    .unwrap();
+    validate_args(&cli).unwrap();
+    let variables = HashMap::from([("port".to_string(), minijinja::Value::from(8080))]);
+    process_inputs(&cli, &variables, &Default::default()).unwrap();
+
+    assert_eq!(
+        fs::read_to_string(input_dir.join("app.conf")).unwrap(),
+        "port=8080"
+    );
+    assert_eq!(
+        fs::read_to_string(input_dir.join("app.conf.bak")).unwrap(),
+        "port={{ port }}"
+    );
+    assert_eq!(
+        fs::read_to_string(input_dir.join("sub").join("db.conf")).unwrap(),
+        "n=42"
+    );
+    assert_eq!(
+        fs::read_to_string(input_dir.join("sub").join("db.conf.bak")).unwrap(),
+        "n={{ 6 * 7 }}"
+    );
+}
+
+#[test]
+fn test_dry_run_writes_and_renders_nothing() {
+    let temp_dir = tempdir().unwrap();
+    let input_dir = temp_dir.path().join("templates");
+    let output_dir = temp_dir.path().join("output");
+    fs::create_dir_all(&input_dir).unwrap();
+    fs::write(input_dir.join("app.conf"), "port={{ port }}").unwrap();
+    // Not rendered, so a template error goes unnoticed
+    fs::write(input_dir.join("broken.conf"), "{% if %}").unwrap();
+    fs::write(input_dir.join("logo.png"), [0u8, 1, 2]).unwrap();
+
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        input_dir.to_str().unwrap(),
+        "-o",
+        output_dir.to_str().unwrap(),
+        "--dry-run",
+    ])
+    .unwrap();
+    validate_args(&cli).unwrap();
+    process_inputs(&cli, &HashMap::new(), &Default::default()).unwrap();
+
+    assert!(!output_dir.exists());
+    assert!(Cli::try_parse_from(["shinkansen", "a.txt", "--dry-run", "--emit-patch"]).is_err());
+}
+
+#[test]
+fn test_check_reports_stale_outputs_without_writing() {
+    let temp_dir = tempdir().unwrap();
+    let input_dir = temp_dir.path().join("templates");
+    let output_dir = temp_dir.path().join("output");
+    fs::create_dir_all(&input_dir).unwrap();
+    fs::create_dir_all(&output_dir).unwrap();
+    fs::write(input_dir.join("fresh.txt"), "n={{ 6 * 7 }}").unwrap();
+    fs::write(input_dir.join("changed.txt"), "new").unwrap();
+    fs::write(input_dir.join("missing.txt"), "m").unwrap();
+    fs::write(output_dir.join("fresh.txt"), "n=42").unwrap();
+    fs::write(output_dir.join("changed.txt"), "old").unwrap();
+
+    let check = |input: &std::path::Path| {
+        let cli = Cli::try_parse_from([
+            "shinkansen",
+            input.to_str().unwrap(),
+            "-o",
+            output_dir.to_str().unwrap(),
+            "--check",
+        ])
+        .unwrap();
+        validate_args(&cli).unwrap();
+        process_inputs(&cli, &HashMap::new(), &Default::default())
+    };
+
+    let message = check(&input_dir).unwrap_err().to_string();
+    assert!(
+        message.contains("2 generated file(s) out of date"),
+        "{}",
+        message
+    );
+    assert!(message.contains("changed.txt (changed)"), "{}", message);
+    assert!(message.contains("missing.txt (missing)"), "{}", message);
+    assert!(!message.contains("fresh.txt"), "{}", message);
+    assert_eq!(
+        fs::read_to_string(output_dir.join("changed.txt")).unwrap(),
+        "old"
+    );
+    assert!(!output_dir.join("missing.txt").exists());
+
+    check(&input_dir.join("fresh.txt")).unwrap();
+}
+
+#[test]
+fn test_concat_joins_fragments_in_input_order() {
+    let temp_dir = tempdir().unwrap();
+    let fragments = temp_dir.path().join("fragments");
+    let output = temp_dir.path().join("haproxy.cfg");
+    fs::create_dir_all(&fragments).unwrap();
+    fs::write(fragments.join("00-global.cfg"), "global\n").unwrap();
+    fs::write(fragments.join("10-web.cfg"), "backend {{ name }}\n").unwrap();
+    fs::write(fragments.join("20-api.cfg"), "backend api\n").unwrap();
+
+    let pattern = fragments.join("*.cfg");
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        pattern.to_str().unwrap(),
+        "--concat",
+        "--separator",
+        "# {{ template|split('/')|last }}",
+        "-o",
+        output.to_str().unwrap(),
+    ])
+    .unwrap();
+    validate_args(&cli).unwrap();
+    let variables = HashMap::from([("name".to_string(), minijinja::Value::from("web"))]);
+    process_inputs(&cli, &variables, &Default::default()).unwrap();
+
+    assert_eq!(
+        fs::read_to_string(&output).unwrap(),
+        "global\n# 10-web.cfg\nbackend web\n# 20-api.cfg\nbackend api"
+    );
+}
+
+#[test]
+fn test_validate_concat() {
+    let cli = Cli::try_parse_from(["shinkansen", "a.cfg", "b.cfg", "--concat"]).unwrap();
+    assert!(validate_args(&cli).is_ok());
+    let cli =
+        Cli::try_parse_from(["shinkansen", "a.cfg", "b.cfg", "--concat", "-o", "out.cfg"]).unwrap();
+    assert!(validate_args(&cli).is_ok());
+
+    let temp_dir = tempdir().unwrap();
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        "a.cfg",
+        "b.cfg",
+        "--concat",
+        "-o",
+        temp_dir.path().to_str().unwrap(),
+    ])
+    .unwrap();
+    let err = validate_args(&cli).unwrap_err();
+    assert!(err.to_string().contains("--concat writes a single file"));
+
+    assert!(Cli::try_parse_from(["shinkansen", "a.cfg", "--separator", "#"]).is_err());
+}
+
+#[test]
+fn test_concat_orders_directory_fragments_by_numeric_prefix() {
+    let temp_dir = tempdir().unwrap();
+    let conf_d = temp_dir.path().join("conf.d");
+    let output = temp_dir.path().join("app.cfg");
+    fs::create_dir_all(&conf_d).unwrap();
+    fs::write(conf_d.join("10-tls.cfg.j2"), "{{ fragment.prefix }} tls").unwrap();
+    fs::write(conf_d.join("9-base.cfg.j2"), "{{ fragment.prefix }} base").unwrap();
+    fs::write(conf_d.join("zz-extra.cfg.j2"), "{{ fragment.name }}").unwrap();
+    fs::write(conf_d.join("100-end.cfg.j2"), "{{ fragment.prefix }} end").unwrap();
+
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        conf_d.to_str().unwrap(),
+        "--concat",
+        "-o",
+        output.to_str().unwrap(),
+    ])
+    .unwrap();
+    validate_args(&cli).unwrap();
+    process_inputs(&cli, &HashMap::new(), &Default::default()).unwrap();
+
+    assert_eq!(
+        fs::read_to_string(&output).unwrap(),
+        "9 base\n10 tls\n100 end\nzz-extra.cfg.j2"
+    );
+}
+
+#[test]
+fn test_no_clobber_refuses_before_writing() {
+    let temp_dir = tempdir().unwrap();
+    let input_dir = temp_dir.path().join("templates");
+    let output_dir = temp_dir.path().join("output");
+    fs::create_dir_all(&input_dir).unwrap();
+    fs::create_dir_all(&output_dir).unwrap();
+    fs::write(input_dir.join("a.txt"), "new a").unwrap();
+    fs::write(input_dir.join("b.txt"), "new b").unwrap();
+    fs::write(output_dir.join("b.txt"), "old b").unwrap();
+
+    let run = |extra: &[&str]| {
+        let mut args = vec![
+            "shinkansen",
+            input_dir.to_str().unwrap(),
+            "-o",
+            output_dir.to_str().unwrap(),
+            "--no-clobber",
+        ];
+        args.extend(extra);
+        let cli = Cli::try_parse_from(args).unwrap();
+        process_inputs(&cli, &HashMap::new(), &Default::default())
+    };
+
+    let err = run(&[]).unwrap_err().to_string();
+    assert!(
+        err.contains("Refusing to overwrite existing files"),
+        "{}",
+        err
+    );
+    assert!(err.contains("b.txt") && !err.contains("a.txt"), "{}", err);
+    assert!(!output_dir.join("a.txt").exists());
+
+    run(&["--force"]).unwrap();
+    assert_eq!(
+        fs::read_to_string(output_dir.join("b.txt")).unwrap(),
+        "new b"
+    );
+
+    let cli = Cli::try_parse_from(["shinkansen", "--interactive", "-o", "out.txt"]).unwrap();
+    assert!(validate_args(&cli).is_err());
+}
+
+#[test]
+fn test_vars_stdin_needs_a_template_file() {
+    for args in [
+        vec!["shinkansen", "--vars-stdin"],
+        vec!["shinkansen", "-", "--vars-stdin", "-o", "out.txt"],
+    ] {
+        let cli = Cli::try_parse_from(args).unwrap();
+        let err = validate_args(&cli).unwrap_err();
+        assert!(err.to_string().contains("--vars-stdin"), "{}", err);
+    }
+    let cli = Cli::try_parse_from(["shinkansen", "app.conf", "--vars-stdin"]).unwrap();
+    assert!(validate_args(&cli).is_ok());
+    assert!(Cli::try_parse_from(["shinkansen", "a.conf", "--vars-stdin", "-i"]).is_err());
+}
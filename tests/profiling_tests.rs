@@ -0,0 +1,87 @@
+use clap::Parser;
+use shinkansen_lib::cli::Cli;
+use shinkansen_lib::profiling::{Profiler, TemplateProfile};
+use shinkansen_lib::rendering::{RenderOptions, render_template_with_options};
+use std::collections::HashMap;
+use std::time::Duration;
+
+fn profile(name: &str, render_ms: u64) -> TemplateProfile {
+    TemplateProfile {
+        name: name.to_string(),
+        parse: Duration::from_millis(1),
+        render: Duration::from_millis(render_ms),
+        postprocess: Duration::ZERO,
+        instructions: 10,
+    }
+}
+
+#[test]
+fn test_render_records_profile() {
+    let profiler = Profiler::default();
+    let options = RenderOptions {
+        profiler: Some(profiler.clone()),
+        ..Default::default()
+    };
+
+    let small =
+        render_template_with_options("{{ 1 }}", &HashMap::new(), "small", &options).unwrap();
+    let large = render_template_with_options(
+        "{% for i in range(200) %}{{ i|string|upper }}{% endfor %}",
+        &HashMap::new(),
+        "large",
+        &options,
+    )
+    .unwrap();
+    assert_eq!(small, "1");
+    assert!(large.starts_with("0123"));
+
+    let profiles = profiler.profiles();
+    assert_eq!(profiles.len(), 2);
+    let instructions = |name: &str| {
+        profiles
+            .iter()
+            .find(|profile| profile.name == name)
+            .unwrap()
+            .instructions
+    };
+    assert!(instructions("small") > 0);
+    assert!(instructions("large") > 200 * instructions("small"));
+}
+
+#[test]
+fn test_rendering_without_profiler_is_unchanged() {
+    let options = RenderOptions::default();
+    let result = render_template_with_options(
+        "{% for i in range(3) %}{{ i }}{% endfor %}",
+        &HashMap::new(),
+        "t",
+        &options,
+    )
+    .unwrap();
+    assert_eq!(result, "012");
+}
+
+#[test]
+fn test_report_lists_slowest_first() {
+    let profiler = Profiler::default();
+    profiler.record(profile("fast.yaml", 2));
+    profiler.record(profile("slow.yaml", 40));
+
+    let report = profiler.report();
+    let lines: Vec<&str> = report.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].ends_with("10 instructions  slow.yaml"));
+    assert!(lines[1].ends_with("fast.yaml"));
+    assert!(lines[2].contains("all templates (2)"));
+
+    assert_eq!(Profiler::default().report(), "");
+}
+
+#[test]
+fn test_profile_template_flag() {
+    let cli = Cli::try_parse_from(["shinkansen", "--profile-template"]).unwrap();
+    assert!(RenderOptions::from_cli(&cli).profiler.is_some());
+
+    let cli = Cli::try_parse_from(["shinkansen"]).unwrap();
+    assert!(RenderOptions::from_cli(&cli).profiler.is_none());
+}
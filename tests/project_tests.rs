@@ -0,0 +1,580 @@
+use clap::Parser;
+use shinkansen_lib::cli::Cli;
+use shinkansen_lib::project::{PROJECT_CONFIG_FILE, ProjectConfig, find_project_config};
+use shinkansen_lib::rendering::{RenderOptions, render_template_with_options};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+
+const CONFIG: &str = r#"
+[[render]]
+match = "*.ps1"
+delimiters = { block = ["<%", "%>"], variable = ["<<", ">>"] }
+
+[[render]]
+match = ["*.yaml", "*.yml"]
+trim_blocks = true
+lstrip_blocks = true
+
+[[render]]
+match = "web/**/*.html"
+autoescape = "html"
+"#;
+
+fn variables() -> HashMap<String, minijinja::Value> {
+    let mut variables = HashMap::new();
+    variables.insert("name".to_string(), minijinja::Value::from("<b>World</b>"));
+    variables
+}
+
+#[test]
+fn test_profile_matches_by_extension() {
+    let project = ProjectConfig::parse(CONFIG, Path::new(".")).unwrap();
+    let options = project.render_options_for(Path::new("scripts/setup.ps1"), &Default::default());
+
+    let result = render_template_with_options(
+        "Write-Host '<< name >>' {{ untouched }}",
+        &variables(),
+        "setup.ps1",
+        &options,
+    )
+    .unwrap();
+    assert_eq!(result, "Write-Host '<b>World</b>' {{ untouched }}");
+}
+
+#[test]
+fn test_profile_whitespace_options() {
+    let project = ProjectConfig::parse(CONFIG, Path::new(".")).unwrap();
+    let options = project.render_options_for(Path::new("deploy.yml"), &Default::default());
+    assert!(options.trim_blocks);
+    assert!(options.lstrip_blocks);
+    assert!(options.delimiters.is_none());
+
+    let result = render_template_with_options(
+        "items:\n  {% for i in [1, 2] %}\n  - {{ i }}\n  {% endfor %}\n",
+        &HashMap::new(),
+        "deploy.yml",
+        &options,
+    )
+    .unwrap();
+    assert_eq!(result, "items:\n  - 1\n  - 2\n");
+}
+
+#[test]
+fn test_path_patterns_are_relative_to_root() {
+    let project = ProjectConfig::parse(CONFIG, Path::new("site")).unwrap();
+    let base = RenderOptions::default();
+
+    let options = project.render_options_for(Path::new("site/web/pages/index.html"), &base);
+    assert!(options.autoescape_html);
+
+    let options = project.render_options_for(Path::new("other/web/pages/index.html"), &base);
+    assert!(!options.autoescape_html);
+
+    let result = render_template_with_options(
+        "<p>{{ name }}</p>",
+        &variables(),
+        "index.html",
+        &project.render_options_for(Path::new("site/web/index.html"), &base),
+    )
+    .unwrap();
+    assert_eq!(result, "<p>&lt;b&gt;World&lt;&#x2f;b&gt;</p>");
+}
+
+#[test]
+fn test_unmatched_file_keeps_base_options() {
+    let project = ProjectConfig::parse(CONFIG, Path::new(".")).unwrap();
+    let base = RenderOptions {
+        k8s: true,
+        ..Default::default()
+    };
+    let options = project.render_options_for(Path::new("README.md"), &base);
+    assert!(options.k8s);
+    assert!(!options.trim_blocks);
+    assert!(options.delimiters.is_none());
+}
+
+#[test]
+fn test_later_rules_override_earlier_ones() {
+    let config = r#"
+[[render]]
+match = "*"
+trim_blocks = true
+postprocess = "sort"
+
+[[render]]
+match = "*.txt"
+trim_blocks = false
+postprocess = ""
+"#;
+    let project = ProjectConfig::parse(config, Path::new(".")).unwrap();
+
+    let options = project.render_options_for(Path::new("notes.txt"), &Default::default());
+    assert!(!options.trim_blocks);
+    assert!(options.postprocess.is_none());
+
+    let options = project.render_options_for(Path::new("notes.md"), &Default::default());
+    assert!(options.trim_blocks);
+    assert_eq!(options.postprocess.as_deref(), Some("sort"));
+}
+
+#[test]
+fn test_invalid_config_is_rejected() {
+    let unknown_key = "[[render]]\nmatch = \"*\"\nindent = 2\n";
+    assert!(ProjectConfig::parse(unknown_key, Path::new(".")).is_err());
+
+    let bad_autoescape = "[[render]]\nmatch = \"*\"\nautoescape = \"xml\"\n";
+    assert!(ProjectConfig::parse(bad_autoescape, Path::new(".")).is_err());
+
+    let bad_glob = "[[render]]\nmatch = \"[*\"\n";
+    let err = ProjectConfig::parse(bad_glob, Path::new(".")).unwrap_err();
+    assert!(err.to_string().contains("Invalid render pattern"));
+}
+
+#[test]
+fn test_max_render_time_per_profile() {
+    use std::time::Duration;
+
+    let config = "[[render]]\nmatch = \"reports/*\"\nmax_render_time = \"500ms\"\n";
+    let project = ProjectConfig::parse(config, Path::new(".")).unwrap();
+    let base = RenderOptions {
+        max_render_time: Some(Duration::from_secs(5)),
+        ..Default::default()
+    };
+    let report = project.render_options_for(Path::new("reports/q1.html"), &base);
+    assert_eq!(report.max_render_time, Some(Duration::from_millis(500)));
+    let other = project.render_options_for(Path::new("app.conf"), &base);
+    assert_eq!(other.max_render_time, Some(Duration::from_secs(5)));
+
+    let invalid = "[[render]]\nmatch = \"*\"\nmax_render_time = \"soon\"\n";
+    let err = ProjectConfig::parse(invalid, Path::new(".")).unwrap_err();
+    assert!(
+        err.to_string().contains("Invalid max_render_time"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_invalid_delimiters_fail_rendering() {
+    let config = "[[render]]\nmatch = \"*\"\ndelimiters = { variable = [\"{%\", \"%}\"] }\n";
+    let project = ProjectConfig::parse(config, Path::new(".")).unwrap();
+    let options = project.render_options_for(Path::new("a.txt"), &Default::default());
+
+    let result = render_template_with_options("x", &HashMap::new(), "a.txt", &options);
+    assert!(result.is_err());
+    assert!(
+        result
+            .unwrap_err()
+            .to_string()
+            .contains("Invalid template delimiters")
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_postprocess_pipes_output() {
+    let options = RenderOptions {
+        postprocess: Some("tr a-z A-Z".to_string()),
+        ..Default::default()
+    };
+    let result =
+        render_template_with_options("hello {{ 1 + 1 }}", &HashMap::new(), "t", &options).unwrap();
+    assert_eq!(result, "HELLO 2");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_postprocess_failure_is_reported() {
+    let options = RenderOptions {
+        postprocess: Some("echo broken >&2; exit 3".to_string()),
+        ..Default::default()
+    };
+    let err = render_template_with_options("x", &HashMap::new(), "t", &options).unwrap_err();
+    let message = err.to_string();
+    assert!(message.contains("Postprocess command"));
+    assert!(message.contains("broken"));
+}
+
+#[test]
+fn test_find_project_config_walks_up() {
+    let temp_dir = tempdir().unwrap();
+    let nested = temp_dir.path().join("a").join("b");
+    fs::create_dir_all(&nested).unwrap();
+    assert!(find_project_config(&nested).is_none_or(|path| !path.starts_with(temp_dir.path())));
+
+    fs::write(temp_dir.path().join(PROJECT_CONFIG_FILE), "").unwrap();
+    assert_eq!(
+        find_project_config(&nested).unwrap(),
+        temp_dir.path().join(PROJECT_CONFIG_FILE)
+    );
+}
+
+#[test]
+fn test_project_config_flag_applies_per_file() {
+    let temp_dir = tempdir().unwrap();
+    let config_path = temp_dir.path().join("profiles.toml");
+    fs::write(&config_path, CONFIG).unwrap();
+
+    let input_dir = temp_dir.path().join("templates");
+    let output_dir = temp_dir.path().join("out");
+    fs::create_dir_all(&input_dir).unwrap();
+    fs::write(input_dir.join("run.ps1"), "echo << value >> {{ value }}").unwrap();
+    fs::write(input_dir.join("notes.txt"), "echo {{ value }}").unwrap();
+
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        input_dir.to_str().unwrap(),
+        "-o",
+        output_dir.to_str().unwrap(),
+        "-D",
+        "value=42",
+        "--project-config",
+        config_path.to_str().unwrap(),
+    ])
+    .unwrap();
+    let variables = shinkansen_lib::variables::collect_variables(&cli).unwrap();
+    shinkansen_lib::processing::process_inputs(&cli, &variables, &Default::default()).unwrap();
+
+    assert_eq!(
+        fs::read_to_string(output_dir.join("run.ps1")).unwrap(),
+        "echo 42 {{ value }}"
+    );
+    assert_eq!(
+        fs::read_to_string(output_dir.join("notes.txt")).unwrap(),
+        "echo 42"
+    );
+}
+
+#[test]
+fn test_no_project_config_flag() {
+    let cli = Cli::try_parse_from(["shinkansen", "--no-project-config"]).unwrap();
+    let project = ProjectConfig::from_cli(&cli).unwrap();
+    assert!(project.render.is_empty());
+
+    let result = Cli::try_parse_from([
+        "shinkansen",
+        "--no-project-config",
+        "--project-config",
+        "x.toml",
+    ]);
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_hooks_match_outputs_in_order() {
+    let config = r#"
+[[hook]]
+match = "*.service"
+run = "systemd-analyze verify {}"
+
+[[hook]]
+match = ["*.service", "*.timer"]
+run = "chmod 0644"
+"#;
+    let project = ProjectConfig::parse(config, Path::new(".")).unwrap();
+    assert_eq!(
+        project.hooks_for(Path::new("out/app.service")),
+        vec!["systemd-analyze verify {}", "chmod 0644"]
+    );
+    assert_eq!(
+        project.hooks_for(Path::new("out/app.timer")),
+        vec!["chmod 0644"]
+    );
+    assert!(project.hooks_for(Path::new("out/app.conf")).is_empty());
+}
+
+#[test]
+fn test_invalid_hooks_are_rejected() {
+    let missing_run = "[[hook]]\nmatch = \"*\"\n";
+    assert!(ProjectConfig::parse(missing_run, Path::new(".")).is_err());
+
+    let empty_run = "[[hook]]\nmatch = \"*\"\nrun = \" \"\n";
+    let err = ProjectConfig::parse(empty_run, Path::new(".")).unwrap_err();
+    assert!(err.to_string().contains("Hook commands must not be empty"));
+
+    let bad_glob = "[[hook]]\nmatch = \"[*\"\nrun = \"true\"\n";
+    let err = ProjectConfig::parse(bad_glob, Path::new(".")).unwrap_err();
+    assert!(err.to_string().contains("Invalid hook pattern"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_hooks_run_after_writing_and_failures_are_aggregated() {
+    let temp_dir = tempdir().unwrap();
+    let input_dir = temp_dir.path().join("templates");
+    let output_dir = temp_dir.path().join("out");
+    fs::create_dir_all(&input_dir).unwrap();
+    for name in ["a.service", "b.service", "c.service", "notes.txt"] {
+        fs::write(input_dir.join(name), format!("{{{{ '{}' }}}}", name)).unwrap();
+    }
+    let config_path = temp_dir.path().join("hooks.toml");
+    fs::write(
+        &config_path,
+        r#"
+[[hook]]
+match = "*.service"
+run = "grep -q 'a.service' {} || { echo rejected; exit 3; }"
+
+[[hook]]
+match = "*.service"
+run = "cp {} {}.checked"
+"#,
+    )
+    .unwrap();
+
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        input_dir.to_str().unwrap(),
+        "-o",
+        output_dir.to_str().unwrap(),
+        "--project-config",
+        config_path.to_str().unwrap(),
+        "-j",
+        "2",
+    ])
+    .unwrap();
+    let err =
+        shinkansen_lib::processing::process_inputs(&cli, &HashMap::new(), &Default::default())
+            .unwrap_err()
+            .to_string();
+
+    // Every output is written; the hooks of a file stop at its first failure
+    assert!(output_dir.join("b.service").exists());
+    assert!(output_dir.join("notes.txt").exists());
+    assert!(output_dir.join("a.service.checked").exists());
+    assert!(!output_dir.join("b.service.checked").exists());
+    assert!(err.starts_with("2 post-write hook(s) failed:"), "{}", err);
+    assert!(err.contains("b.service: 'grep -q"));
+    assert!(err.contains("c.service: 'grep -q"));
+    assert!(err.contains("(exit status: 3)\nrejected"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_hooks_receive_output_paths_unexpanded() {
+    let temp_dir = tempdir().unwrap();
+    let marker = temp_dir.path().join("pwned");
+    let input = temp_dir.path().join("app.conf");
+    fs::write(&input, "hello").unwrap();
+    let output_dir = temp_dir
+        .path()
+        .join(format!("out dir $(touch {})", marker.display()));
+    fs::create_dir_all(&output_dir).unwrap();
+    let output = output_dir.join("app.conf");
+    let config_path = temp_dir.path().join("hooks.toml");
+    fs::write(
+        &config_path,
+        "[[hook]]\nmatch = \"*\"\nrun = \"cp {} {}.checked\"\n",
+    )
+    .unwrap();
+
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        input.to_str().unwrap(),
+        "-o",
+        output.to_str().unwrap(),
+        "--project-config",
+        config_path.to_str().unwrap(),
+    ])
+    .unwrap();
+    shinkansen_lib::processing::process_inputs(&cli, &HashMap::new(), &Default::default()).unwrap();
+
+    assert!(output_dir.join("app.conf.checked").exists());
+    assert!(!marker.exists());
+}
+
+#[test]
+fn test_project_requirements() {
+    let project = ProjectConfig::parse(
+        "requires_shinkansen = \">=0.1\"\nrequires_filter_packs = [\"json\", \"k8s\"]\n",
+        Path::new("."),
+    )
+    .unwrap();
+    let err = project
+        .check_requirements(&RenderOptions::default())
+        .unwrap_err();
+    assert!(err.to_string().contains("'k8s' filter pack"), "{}", err);
+    assert!(err.to_string().contains("--k8s"), "{}", err);
+    let k8s = RenderOptions {
+        k8s: true,
+        ..Default::default()
+    };
+    project.check_requirements(&k8s).unwrap();
+
+    let project =
+        ProjectConfig::parse("requires_filter_packs = [\"yaml\"]\n", Path::new(".")).unwrap();
+    let err = project.check_requirements(&k8s).unwrap_err();
+    assert!(err.to_string().contains("does not have"), "{}", err);
+
+    let err =
+        ProjectConfig::parse("requires_shinkansen = \">= soon\"\n", Path::new(".")).unwrap_err();
+    assert!(
+        err.to_string().contains("Invalid requires_shinkansen"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_unsatisfied_version_requirement_fails_before_rendering() {
+    let temp_dir = tempdir().unwrap();
+    let input = temp_dir.path().join("app.conf");
+    let output = temp_dir.path().join("out.conf");
+    fs::write(&input, "hello").unwrap();
+    let config_path = temp_dir.path().join(PROJECT_CONFIG_FILE);
+    fs::write(&config_path, "requires_shinkansen = \">=999\"\n").unwrap();
+
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        input.to_str().unwrap(),
+        "-o",
+        output.to_str().unwrap(),
+        "--project-config",
+        config_path.to_str().unwrap(),
+    ])
+    .unwrap();
+    let err =
+        shinkansen_lib::processing::process_inputs(&cli, &HashMap::new(), &Default::default())
+            .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "This project requires shinkansen >=999, but this is shinkansen {}",
+            env!("CARGO_PKG_VERSION")
+        )
+    );
+    assert!(!output.exists());
+}
+
+#[test]
+fn test_lifecycle_hooks_are_parsed() {
+    let config = r#"
+[hooks]
+before = ["git pull --ff-only"]
+on_failure = ["notify-send failed"]
+"#;
+    let project = ProjectConfig::parse(config, Path::new(".")).unwrap();
+    assert_eq!(project.lifecycle.before, vec!["git pull --ff-only"]);
+    assert!(project.lifecycle.after.is_empty());
+    assert_eq!(project.lifecycle.on_failure, vec!["notify-send failed"]);
+
+    let unknown_stage = "[hooks]\nduring = [\"true\"]\n";
+    assert!(ProjectConfig::parse(unknown_stage, Path::new(".")).is_err());
+}
+
+#[test]
+fn test_hook_environment() {
+    let mut variables = HashMap::new();
+    variables.insert("app-name".to_string(), minijinja::Value::from("web"));
+    variables.insert("replicas".to_string(), minijinja::Value::from(3));
+    variables.insert(
+        "db".to_string(),
+        minijinja::Value::from_serialize(serde_json::json!({"host": "db.local", "ports": [5432]})),
+    );
+
+    let env = shinkansen_lib::hooks::hook_environment(&variables);
+    let get = |name: &str| {
+        env.iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    };
+    assert_eq!(get("SHINKANSEN_VAR_APP_NAME"), Some("web"));
+    assert_eq!(get("SHINKANSEN_VAR_REPLICAS"), Some("3"));
+    assert_eq!(get("SHINKANSEN_VAR_DB_HOST"), Some("db.local"));
+    assert_eq!(get("SHINKANSEN_VAR_DB_PORTS"), Some("[5432]"));
+    assert_eq!(
+        get("SHINKANSEN_VAR_DB"),
+        Some(r#"{"host":"db.local","ports":[5432]}"#)
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_lifecycle_hooks_run_around_processing() {
+    let temp_dir = tempdir().unwrap();
+    let log = temp_dir.path().join("log.txt");
+    let template = temp_dir.path().join("app.txt");
+    let output = temp_dir.path().join("app.out");
+    let config_path = temp_dir.path().join("hooks.toml");
+    fs::write(
+        &config_path,
+        format!(
+            r#"
+[hooks]
+before = ["echo before $SHINKANSEN_VAR_ENV >> {log}"]
+after = ["test -f {out} && echo after >> {log}"]
+on_failure = ["echo \"failed: $SHINKANSEN_ERROR\" >> {log}"]
+"#,
+            log = log.display(),
+            out = output.display()
+        ),
+    )
+    .unwrap();
+
+    let run = |content: &str| {
+        fs::write(&template, content).unwrap();
+        let cli = Cli::try_parse_from([
+            "shinkansen",
+            template.to_str().unwrap(),
+            "-o",
+            output.to_str().unwrap(),
+            "-D",
+            "env=prod",
+            "--project-config",
+            config_path.to_str().unwrap(),
+        ])
+        .unwrap();
+        let variables = shinkansen_lib::variables::collect_variables(&cli).unwrap();
+        shinkansen_lib::processing::process_inputs(&cli, &variables, &Default::default())
+    };
+
+    run("{{ env }}").unwrap();
+    assert_eq!(fs::read_to_string(&log).unwrap(), "before prod\nafter\n");
+
+    fs::remove_file(&log).unwrap();
+    let err = run("{{ broken(").unwrap_err();
+    let log = fs::read_to_string(&log).unwrap();
+    assert!(log.starts_with("before prod\nfailed: "), "{}", log);
+    assert!(log.contains("app.txt"));
+    assert!(!log.contains("after"));
+    assert!(err.to_string().contains("app.txt"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_failing_before_hook_stops_the_run() {
+    let temp_dir = tempdir().unwrap();
+    let template = temp_dir.path().join("app.txt");
+    let output = temp_dir.path().join("app.out");
+    fs::write(&template, "rendered").unwrap();
+    let config_path = temp_dir.path().join("hooks.toml");
+    fs::write(
+        &config_path,
+        "[hooks]\nbefore = [\"exit 4\"]\non_failure = [\"exit 5\"]\n",
+    )
+    .unwrap();
+
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        template.to_str().unwrap(),
+        "-o",
+        output.to_str().unwrap(),
+        "--project-config",
+        config_path.to_str().unwrap(),
+    ])
+    .unwrap();
+    let diagnostics = shinkansen_lib::diagnostics::Diagnostics::default();
+    let err = shinkansen_lib::processing::process_inputs(&cli, &HashMap::new(), &diagnostics)
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "before hook 'exit 4' failed (exit status: 4)"
+    );
+    assert!(!output.exists());
+    assert_eq!(
+        diagnostics.warnings(),
+        vec!["on_failure hook 'exit 5' failed (exit status: 5)"]
+    );
+}
@@ -168,3 +168,39 @@ fn test_template_with_comments() {
     assert!(!output.contains("This is a comment"));
     assert!(!output.contains("Another comment"));
 }
+
+#[test]
+fn test_max_render_time_cancels_the_render() {
+    use shinkansen_lib::rendering::{RenderOptions, render_template_with_options};
+    use std::time::{Duration, Instant};
+
+    let options = RenderOptions {
+        max_render_time: Some(Duration::from_millis(100)),
+        ..Default::default()
+    };
+    let variables = HashMap::from([("name".to_string(), "web".into())]);
+
+    // Ten billion iterations: only the time limit ends this render
+    let endless =
+        "{% for i in range(100000) %}{% for j in range(100000) %}{{ j }}{% endfor %}{% endfor %}";
+    let start = Instant::now();
+    let err = render_template_with_options(endless, &variables, "endless.conf", &options)
+        .unwrap_err()
+        .to_string();
+    assert!(start.elapsed() < Duration::from_secs(10));
+    assert!(
+        err.contains("Rendering endless.conf took longer than 100ms and was cancelled after"),
+        "{}",
+        err
+    );
+
+    // Loops that write nothing still stop at the next variable lookup
+    let silent = "{% for i in range(100000) %}{% for j in range(100000) %}{% endfor %}{{ name }}{% endfor %}";
+    let err = render_template_with_options(silent, &variables, "silent.conf", &options)
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("took longer than 100ms"), "{}", err);
+
+    let quick = render_template_with_options("app={{ name }}", &variables, "app.conf", &options);
+    assert_eq!(quick.unwrap(), "app=web");
+}
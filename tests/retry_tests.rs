@@ -0,0 +1,122 @@
+use clap::Parser;
+use shinkansen_lib::cli::Cli;
+use shinkansen_lib::error::ShinkansenError;
+use shinkansen_lib::retry::{DEFAULT_RETRY_DELAY, Failure, RetryPolicy, retry};
+use std::cell::Cell;
+use std::time::Duration;
+
+fn fast_policy(retries: u32) -> RetryPolicy {
+    RetryPolicy {
+        retries,
+        delay: Duration::from_millis(1),
+    }
+}
+
+fn failure(message: &str) -> ShinkansenError {
+    ShinkansenError::DataSourceError(message.to_string())
+}
+
+#[test]
+fn test_retry_policy_from_cli() {
+    let cli =
+        Cli::try_parse_from(["shinkansen", "--retries", "3", "--retry-delay", "250ms"]).unwrap();
+    let policy = RetryPolicy::from_cli(&cli);
+    assert_eq!(policy.retries, 3);
+    assert_eq!(policy.delay, Duration::from_millis(250));
+
+    let policy = RetryPolicy::from_cli(&Cli::default());
+    assert_eq!(policy.retries, 0);
+    assert_eq!(policy.delay, DEFAULT_RETRY_DELAY);
+}
+
+#[test]
+fn test_transient_failures_are_retried() {
+    let attempts = Cell::new(0);
+    let result = retry(&fast_policy(2), "source", || {
+        attempts.set(attempts.get() + 1);
+        if attempts.get() < 3 {
+            Err(failure("timed out"))
+        } else {
+            Ok("data")
+        }
+    });
+
+    assert_eq!(result.unwrap(), "data");
+    assert_eq!(attempts.get(), 3);
+}
+
+#[test]
+fn test_permanent_failure_is_not_retried() {
+    let attempts = Cell::new(0);
+    let result: Result<(), _> = fast_policy(5).run("source", || {
+        attempts.set(attempts.get() + 1);
+        Err(Failure::Permanent(failure("404 Not Found")))
+    });
+
+    assert_eq!(attempts.get(), 1);
+    assert_eq!(result.unwrap_err().to_string(), "404 Not Found");
+}
+
+#[test]
+fn test_exhausted_retries_aggregate_errors() {
+    let attempts = Cell::new(0);
+    let result: Result<(), _> = retry(&fast_policy(2), "GET https://cmdb", || {
+        attempts.set(attempts.get() + 1);
+        Err(failure(&format!("failure {}", attempts.get())))
+    });
+
+    let message = result.unwrap_err().to_string();
+    assert!(message.contains("GET https://cmdb failed after 3 attempts"));
+    assert!(message.contains("attempt 1: failure 1"));
+    assert!(message.contains("attempt 3: failure 3"));
+}
+
+#[test]
+fn test_backoff_grows_exponentially_with_jitter() {
+    let policy = RetryPolicy {
+        retries: 10,
+        delay: Duration::from_millis(100),
+    };
+
+    for _ in 0..20 {
+        let first = policy.backoff(1);
+        assert!(first >= Duration::from_millis(50) && first <= Duration::from_millis(100));
+
+        let third = policy.backoff(3);
+        assert!(third >= Duration::from_millis(200) && third <= Duration::from_millis(400));
+    }
+
+    // Capped at 30 seconds
+    assert!(policy.backoff(20) <= Duration::from_secs(30));
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn test_http_retries_server_errors() {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        let responses = [
+            "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: 11\r\nConnection: close\r\n\r\n{\"ok\":true}",
+        ];
+        for response in responses {
+            if let Ok((mut stream, _)) = listener.accept() {
+                let mut buffer = [0u8; 4096];
+                let _ = stream.read(&mut buffer);
+                let _ = stream.write_all(response.as_bytes());
+            }
+        }
+    });
+
+    let network = shinkansen_lib::network::NetworkOptions {
+        retry: fast_policy(1),
+        ..Default::default()
+    };
+    let client = shinkansen_lib::http::Client::new(&network).unwrap();
+    let response = client.get(&format!("http://{}/status", address)).unwrap();
+    assert_eq!(response.body, "{\"ok\":true}");
+}
@@ -0,0 +1,50 @@
+use clap::Parser;
+use shinkansen_lib::cli::Cli;
+use shinkansen_lib::rendering::render_template;
+use shinkansen_lib::run::{RunContext, format_timestamp};
+use shinkansen_lib::variables::collect_variables;
+use std::collections::HashMap;
+
+#[test]
+fn test_format_timestamp() {
+    assert_eq!(format_timestamp(0), "1970-01-01T00:00:00Z");
+    assert_eq!(format_timestamp(951_782_400), "2000-02-29T00:00:00Z");
+    assert_eq!(format_timestamp(1_700_000_000), "2023-11-14T22:13:20Z");
+    assert_eq!(format_timestamp(4_102_444_799), "2099-12-31T23:59:59Z");
+}
+
+#[test]
+fn test_run_context_is_stable_within_a_run() {
+    let template = "{{ run.id }} {{ run.started_at }} {{ run.user }} {{ run.hostname }}";
+    let first = render_template(template, &HashMap::new(), "a").unwrap();
+    let second = render_template(template, &HashMap::new(), "b").unwrap();
+    assert_eq!(first, second);
+
+    let run = RunContext::current();
+    assert_eq!(run.id.len(), 16);
+    assert!(run.id.chars().all(|c| c.is_ascii_hexdigit()));
+    assert!(run.started_at.ends_with('Z'));
+    assert_eq!(
+        first,
+        format!(
+            "{} {} {} {}",
+            run.id, run.started_at, run.user, run.hostname
+        )
+    );
+}
+
+#[test]
+fn test_run_fields_can_be_pinned() {
+    let cli = Cli::try_parse_from(["shinkansen", "-D", "run.id=build-42"]).unwrap();
+    let variables = collect_variables(&cli).unwrap();
+
+    let result = render_template("{{ run.id }} {{ run.user }}", &variables, "t").unwrap();
+    assert_eq!(result, format!("build-42 {}", RunContext::current().user));
+}
+
+#[test]
+fn test_run_is_not_added_to_variables() {
+    let cli = Cli::try_parse_from(["shinkansen", "-D", "a=1"]).unwrap();
+    let variables = collect_variables(&cli).unwrap();
+    assert!(!variables.contains_key("run"));
+}
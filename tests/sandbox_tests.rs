@@ -0,0 +1,332 @@
+use shinkansen_lib::rendering::{RenderOptions, render_template, render_template_with_options};
+use shinkansen_lib::sandbox::{FileAccess, template_root};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::tempdir;
+
+const HELLO_SHA256: &str = "5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03";
+
+fn render_with(template: &str, access: FileAccess) -> shinkansen_lib::error::Result<String> {
+    let options = RenderOptions {
+        files: Some(access),
+        ..Default::default()
+    };
+    render_template_with_options(template, &HashMap::new(), "test", &options)
+}
+
+#[test]
+fn test_filehash_in_template_root() {
+    let dir = tempdir().unwrap();
+    fs::write(dir.path().join("app.conf"), "hello\n").unwrap();
+
+    let result = render_with(
+        "{{ filehash('app.conf') }}",
+        FileAccess::new(dir.path(), &[]),
+    )
+    .unwrap();
+    assert_eq!(result, HELLO_SHA256);
+}
+
+#[test]
+fn test_filehash_outside_root_is_rejected() {
+    let dir = tempdir().unwrap();
+    fs::create_dir(dir.path().join("templates")).unwrap();
+    fs::create_dir(dir.path().join("secrets")).unwrap();
+    fs::write(dir.path().join("secrets/key"), "hello\n").unwrap();
+
+    let access = FileAccess::new(dir.path().join("templates"), &[]);
+    let err = render_with("{{ filehash('../secrets/key') }}", access.clone()).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("'../secrets/key' is outside the template root and --allow-dir directories")
+    );
+
+    let access = FileAccess::new(access.root, &[dir.path().join("secrets")]);
+    assert_eq!(
+        render_with("{{ filehash('../secrets/key') }}", access).unwrap(),
+        HELLO_SHA256
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_filehash_symlink_cannot_escape() {
+    let dir = tempdir().unwrap();
+    fs::create_dir(dir.path().join("templates")).unwrap();
+    fs::write(dir.path().join("secret"), "hello\n").unwrap();
+    std::os::unix::fs::symlink(dir.path().join("secret"), dir.path().join("templates/link"))
+        .unwrap();
+
+    let err = render_with(
+        "{{ filehash('link') }}",
+        FileAccess::new(dir.path().join("templates"), &[]),
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("is outside the template root"));
+}
+
+#[test]
+fn test_filehash_missing_file() {
+    let dir = tempdir().unwrap();
+    let err = render_with(
+        "{{ filehash('missing.conf') }}",
+        FileAccess::new(dir.path(), &[]),
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("cannot read 'missing.conf'"));
+}
+
+#[test]
+fn test_file_functions_need_file_access() {
+    assert!(render_template("{{ filehash('Cargo.toml') }}", &HashMap::new(), "test").is_err());
+}
+
+#[test]
+fn test_template_root() {
+    let dir = tempdir().unwrap();
+    let templates = dir.path().join("templates");
+    fs::create_dir_all(templates.join("nested")).unwrap();
+    let inputs = vec![templates.to_str().unwrap().to_string()];
+
+    assert_eq!(
+        template_root(&inputs, &templates.join("nested/app.yaml")),
+        templates
+    );
+    assert_eq!(
+        template_root(&[], &dir.path().join("single.yaml")),
+        dir.path()
+    );
+    assert_eq!(
+        template_root(&[], Path::new("app.yaml")),
+        PathBuf::from(".")
+    );
+
+    // A glob input roots its files at the literal directory before the wildcard
+    let glob = vec![format!("{}/**/*.yaml", templates.to_str().unwrap())];
+    assert_eq!(
+        template_root(&glob, &templates.join("nested/app.yaml")),
+        templates
+    );
+}
+
+fn asset_tree() -> tempfile::TempDir {
+    let dir = tempdir().unwrap();
+    fs::create_dir_all(dir.path().join("static/css/vendor")).unwrap();
+    for file in [
+        "static/site.css",
+        "static/css/app.css",
+        "static/css/vendor/reset.css",
+        "static/css/app.js",
+        "index.html",
+    ] {
+        fs::write(dir.path().join(file), "").unwrap();
+    }
+    dir
+}
+
+#[test]
+fn test_glob_matches_recursively_and_sorts() {
+    let dir = asset_tree();
+    let access = FileAccess::new(dir.path(), &[]);
+    assert_eq!(
+        render_with("{{ glob('static/**/*.css') | join(',') }}", access.clone()).unwrap(),
+        "static/css/app.css,static/css/vendor/reset.css,static/site.css"
+    );
+    assert_eq!(
+        render_with("{{ glob('static/*.css') | join(',') }}", access.clone()).unwrap(),
+        "static/site.css"
+    );
+    assert_eq!(
+        render_with("{{ glob('*.html') | join(',') }}", access).unwrap(),
+        "index.html"
+    );
+}
+
+#[test]
+fn test_glob_outside_root_is_rejected() {
+    let dir = asset_tree();
+    let access = FileAccess::new(dir.path().join("static/css"), &[]);
+    let err = render_with("{{ glob('../*.css') }}", access).unwrap_err();
+    assert!(err.to_string().contains("is outside the template root"));
+}
+
+#[test]
+fn test_glob_invalid_pattern() {
+    let dir = asset_tree();
+    let err = render_with("{{ glob('static/[') }}", FileAccess::new(dir.path(), &[])).unwrap_err();
+    assert!(err.to_string().contains("invalid glob 'static/['"));
+}
+
+#[test]
+fn test_listdir() {
+    let dir = asset_tree();
+    let access = FileAccess::new(dir.path(), &[]);
+    assert_eq!(
+        render_with("{{ listdir('static') | join(',') }}", access.clone()).unwrap(),
+        "css,site.css"
+    );
+    assert_eq!(
+        render_with("{{ listdir() | join(',') }}", access.clone()).unwrap(),
+        "index.html,static"
+    );
+    let err = render_with("{{ listdir('..') }}", access).unwrap_err();
+    assert!(err.to_string().contains("is outside the template root"));
+}
+
+fn render_in(template: &str, template_dirs: Vec<PathBuf>) -> shinkansen_lib::error::Result<String> {
+    let options = RenderOptions {
+        template_dirs,
+        ..Default::default()
+    };
+    let mut variables = HashMap::new();
+    variables.insert("title".to_string(), minijinja::Value::from("Hi"));
+    render_template_with_options(template, &variables, "page.html", &options)
+}
+
+fn layout_tree() -> tempfile::TempDir {
+    let dir = tempdir().unwrap();
+    fs::create_dir_all(dir.path().join("layouts")).unwrap();
+    fs::create_dir_all(dir.path().join("partials/nav")).unwrap();
+    fs::write(
+        dir.path().join("layouts/base.html"),
+        "<main>{% block body %}{% endblock %}</main>",
+    )
+    .unwrap();
+    fs::write(
+        dir.path().join("partials/nav/header.html"),
+        "<h1>{{ title }}</h1>",
+    )
+    .unwrap();
+    fs::write(dir.path().join("partials/base.html"), "shadowed").unwrap();
+    fs::write(dir.path().join("secret.html"), "secret").unwrap();
+    dir
+}
+
+#[test]
+fn test_extends_and_include_from_template_dirs() {
+    let dir = layout_tree();
+    let dirs = vec![dir.path().join("layouts"), dir.path().join("partials")];
+    let result = render_in(
+        "{% extends 'base.html' %}{% block body %}{% include 'nav/header.html' %}{% endblock %}",
+        dirs,
+    )
+    .unwrap();
+    assert_eq!(result, "<main><h1>Hi</h1></main>");
+}
+
+#[test]
+fn test_render_function_overrides_context() {
+    let dir = layout_tree();
+    let dirs = vec![dir.path().join("partials")];
+    let result = render_in(
+        "{{ render('nav/header.html') }}{{ render('nav/header.html', {'title': 'Bye'}) }}{{ title }}",
+        dirs,
+    )
+    .unwrap();
+    assert_eq!(result, "<h1>Hi</h1><h1>Bye</h1>Hi");
+}
+
+#[test]
+fn test_render_function_keyword_overrides() {
+    let dir = layout_tree();
+    let dirs = vec![dir.path().join("partials")];
+    let result = render_in(
+        "{{ render('nav/header.html', title='Kw') }}{{ render('nav/header.html', {'title': 'Map'}, title='Both') }}{{ title }}",
+        dirs,
+    )
+    .unwrap();
+    assert_eq!(result, "<h1>Kw</h1><h1>Both</h1>Hi");
+}
+
+#[test]
+fn test_with_block_scopes_includes() {
+    let dir = layout_tree();
+    fs::write(
+        dir.path().join("partials/counter.txt"),
+        "{% set title = 'changed' %}[{{ title }}]",
+    )
+    .unwrap();
+    let dirs = vec![dir.path().join("partials")];
+    let result = render_in(
+        "{% with title = 'Scoped' %}{% include 'nav/header.html' %}{% endwith %}{% with %}{% include 'counter.txt' %}{% endwith %}{{ render('counter.txt') }}{{ title }}",
+        dirs,
+    )
+    .unwrap();
+    assert_eq!(result, "<h1>Scoped</h1>[changed][changed]Hi");
+}
+
+#[test]
+fn test_render_function_sees_locals_and_stops_recursion() {
+    let dir = layout_tree();
+    fs::write(
+        dir.path().join("partials/item.txt"),
+        "{{ item }}={{ title }};",
+    )
+    .unwrap();
+    fs::write(
+        dir.path().join("partials/loop.txt"),
+        "{{ render('loop.txt') }}",
+    )
+    .unwrap();
+    let dirs = vec![dir.path().join("partials")];
+
+    let result = render_in(
+        "{% for item in [1, 2] %}{{ render('item.txt') }}{% endfor %}",
+        dirs.clone(),
+    )
+    .unwrap();
+    assert_eq!(result, "1=Hi;2=Hi;");
+
+    let err = render_in("{{ render('loop.txt') }}", dirs.clone()).unwrap_err();
+    assert!(
+        err.to_string().contains("render() nested more than"),
+        "{}",
+        err
+    );
+    let err = render_in("{{ render('item.txt', [1]) }}", dirs).unwrap_err();
+    assert!(err.to_string().contains("expects a mapping"), "{}", err);
+}
+
+#[test]
+fn test_include_without_template_dir_fails_with_hint() {
+    let err = render_in("{% include 'header.html' %}", Vec::new()).unwrap_err();
+    assert!(err.to_string().contains("template not found"));
+    assert!(err.hint().unwrap().contains("--template-dir"));
+}
+
+#[test]
+fn test_include_cannot_leave_template_dir() {
+    let dir = layout_tree();
+    let dirs = vec![dir.path().join("partials")];
+    let err = render_in("{% include '../secret.html' %}", dirs.clone()).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("must be a relative path inside --template-dir")
+    );
+    let err = render_in("{% include '/etc/hostname' %}", dirs).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("must be a relative path inside --template-dir")
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_include_symlink_cannot_escape() {
+    let dir = layout_tree();
+    std::os::unix::fs::symlink(
+        dir.path().join("secret.html"),
+        dir.path().join("partials/link.html"),
+    )
+    .unwrap();
+    let err = render_in(
+        "{% include 'link.html' %}",
+        vec![dir.path().join("partials")],
+    )
+    .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("links outside the template directory")
+    );
+}
@@ -0,0 +1,269 @@
+use std::fs;
+use std::path::Path;
+
+use clap::Parser;
+use tempfile::tempdir;
+
+use shinkansen_lib::cli::Cli;
+use shinkansen_lib::fileio::IoOptions;
+use shinkansen_lib::input::{ArchiveSource, InputSource};
+use shinkansen_lib::session::{REDACTED, ReplayOutcome, Session, record, replay};
+use shinkansen_lib::variables::collect_variable_context;
+
+fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+}
+
+/// Run `args` with `--record bundle`, returning the run's result
+fn record_run(args: &[String], bundle: &Path) -> shinkansen_lib::error::Result<()> {
+    let cli = Cli::try_parse_from(args).unwrap();
+    let context = collect_variable_context(&cli).unwrap();
+    record(&cli, &context, args, bundle)
+}
+
+fn member(bundle: &Path, name: &str) -> Vec<u8> {
+    let archive = ArchiveSource::open(bundle, &IoOptions::default()).unwrap();
+    archive.read(&archive.path().join(name)).unwrap()
+}
+
+fn replay_cli(extra: &[&str]) -> Cli {
+    let mut args = vec!["shinkansen", "--replay", "bundle.tar"];
+    args.extend(extra);
+    Cli::try_parse_from(args).unwrap()
+}
+
+#[test]
+fn test_record_and_replay() {
+    let temp_dir = tempdir().unwrap();
+    let templates = temp_dir.path().join("templates");
+    fs::create_dir_all(templates.join("nginx")).unwrap();
+    fs::write(templates.join("app.conf"), "app={{ name }}").unwrap();
+    fs::write(templates.join("nginx/site.conf"), "site={{ name }}").unwrap();
+    let output_dir = temp_dir.path().join("out");
+    let bundle = temp_dir.path().join("session.tar.gz");
+
+    let run = args(&[
+        "shinkansen",
+        templates.to_str().unwrap(),
+        "-r",
+        "-o",
+        output_dir.to_str().unwrap(),
+        "-D",
+        "name=web",
+        "--no-project-config",
+        "--record",
+        bundle.to_str().unwrap(),
+    ]);
+    record_run(&run, &bundle).unwrap();
+    assert_eq!(
+        fs::read_to_string(output_dir.join("nginx/site.conf")).unwrap(),
+        "site=web"
+    );
+
+    let session: Session = serde_json::from_slice(&member(&bundle, "session.json")).unwrap();
+    assert_eq!(session.args, run[..run.len() - 2]);
+    assert_eq!(session.version, env!("CARGO_PKG_VERSION"));
+    assert_eq!(session.inputs.len(), 2);
+    assert_eq!(session.outputs.len(), 2);
+    assert_eq!(session.error, None);
+    let context: serde_json::Value =
+        serde_json::from_slice(&member(&bundle, "context.json")).unwrap();
+    assert_eq!(context["name"], "web");
+    assert!(context["run"]["started_at"].is_string(), "{}", context);
+
+    // Replaying renders from the bundle, not from the files on disk
+    fs::write(templates.join("app.conf"), "edited").unwrap();
+    fs::remove_dir_all(&output_dir).unwrap();
+    let report = replay(&bundle, &replay_cli(&[])).unwrap();
+    assert!(!report.has_differences(), "{}", report.report());
+    assert!(
+        report
+            .outputs
+            .iter()
+            .all(|(_, outcome)| *outcome == ReplayOutcome::Same)
+    );
+    assert!(!output_dir.exists());
+    assert!(
+        report.report().ends_with(&format!(
+            "2 of 2 output(s) match the recording of shinkansen {} {}\n",
+            env!("CARGO_PKG_VERSION"),
+            run[1..run.len() - 2].join(" ")
+        )),
+        "{}",
+        report.report()
+    );
+}
+
+#[test]
+fn test_recorded_secrets_are_redacted() {
+    let temp_dir = tempdir().unwrap();
+    let config = temp_dir.path().join("config.toml");
+    fs::write(
+        &config,
+        r#"
+signing = "abc"
+
+[db]
+host = "db1"
+password = "hunter2"
+
+[_meta.signing]
+secret = true
+"#,
+    )
+    .unwrap();
+    let template = temp_dir.path().join("app.conf");
+    fs::write(
+        &template,
+        "{{ db.host }} {{ db.password }} {{ api_token }} {{ smtp.password }}",
+    )
+    .unwrap();
+    let per_file = temp_dir.path().join("per-file.yaml");
+    fs::write(
+        &per_file,
+        format!(
+            "{}:\n  vars: {{smtp: {{host: mx1, password: m41l}}}}\n",
+            template.display()
+        ),
+    )
+    .unwrap();
+    let project = temp_dir.path().join("shinkansen.toml");
+    fs::write(
+        &project,
+        "[[hook]]\nmatch = \"*.service\"\nrun = \"notify --token t0k3n {}\"\n",
+    )
+    .unwrap();
+    let output = temp_dir.path().join("app.out");
+    let bundle = temp_dir.path().join("session.tar");
+
+    let run = args(&[
+        "shinkansen",
+        template.to_str().unwrap(),
+        "-o",
+        output.to_str().unwrap(),
+        "-c",
+        config.to_str().unwrap(),
+        "-D",
+        "api_token=t0k3n,region=eu",
+        "-Dsigning=s1gn",
+        "--define=db.password=hunter2",
+        "--set-string",
+        "client_secret=k3y",
+        "--set-json=private_key=\"p3m\"",
+        "--per-file-vars",
+        per_file.to_str().unwrap(),
+        "--project-config",
+        project.to_str().unwrap(),
+        &format!("--record={}", bundle.display()),
+    ]);
+    record_run(&run, &bundle).unwrap();
+    assert_eq!(
+        fs::read_to_string(&output).unwrap(),
+        "db1 hunter2 t0k3n m41l"
+    );
+
+    let context: serde_json::Value =
+        serde_json::from_slice(&member(&bundle, "context.json")).unwrap();
+    assert_eq!(context["db"]["host"], "db1");
+    assert_eq!(context["db"]["password"], REDACTED);
+    assert_eq!(context["signing"], REDACTED);
+    assert_eq!(context["api_token"], REDACTED);
+    let session: Session = serde_json::from_slice(&member(&bundle, "session.json")).unwrap();
+    assert_eq!(
+        session.redacted,
+        [
+            "api_token",
+            "client_secret",
+            "db.password",
+            "private_key",
+            "signing",
+            &format!("{}:smtp.password", template.display()),
+        ]
+    );
+    assert_eq!(
+        session.args[6..13],
+        [
+            "-D",
+            "api_token=<redacted>,region=eu",
+            "-Dsigning=<redacted>",
+            "--define=db.password=<redacted>",
+            "--set-string",
+            "client_secret=<redacted>",
+            "--set-json=private_key=<redacted>",
+        ]
+    );
+    assert_eq!(session.args.len(), run.len() - 1);
+
+    // No member of the bundle holds a secret, outputs included
+    let archive = ArchiveSource::open(&bundle, &IoOptions::default()).unwrap();
+    let members: Vec<_> = archive.files().unwrap().map(Result::unwrap).collect();
+    assert_eq!(members.len(), 6, "{:?}", members);
+    for path in members {
+        let content = String::from_utf8(archive.read(&path).unwrap()).unwrap();
+        for secret in ["t0k3n", "s1gn", "hunter2", "k3y", "p3m", "m41l"] {
+            assert!(!content.contains(secret), "{:?}: {}", path, content);
+        }
+    }
+    let recorded = &session.outputs[0].member;
+    assert_eq!(
+        member(&bundle, recorded),
+        b"db1 <redacted> <redacted> <redacted>"
+    );
+
+    // The replay renders the redacted values where the recording scrubbed them
+    let report = replay(&bundle, &replay_cli(&["--diff"])).unwrap();
+    assert!(!report.has_differences(), "{}", report.report());
+    assert_eq!(report.outputs[0].1, ReplayOutcome::Same);
+    let text = report.report();
+    assert!(
+        text.contains("redacted: api_token, client_secret, db.password, private_key, signing, "),
+        "{}",
+        text
+    );
+}
+
+#[test]
+fn test_failing_runs_are_recorded() {
+    let temp_dir = tempdir().unwrap();
+    let templates = temp_dir.path().join("templates");
+    fs::create_dir_all(&templates).unwrap();
+    fs::write(templates.join("a.conf"), "a={{ name }}").unwrap();
+    fs::write(templates.join("b.conf"), "b={{ name | nosuchfilter }}").unwrap();
+    let output_dir = temp_dir.path().join("out");
+    let bundle = temp_dir.path().join("session.tar");
+
+    let run = args(&[
+        "shinkansen",
+        templates.to_str().unwrap(),
+        "-o",
+        output_dir.to_str().unwrap(),
+        "-D",
+        "name=web",
+        "--no-project-config",
+        "--record",
+        bundle.to_str().unwrap(),
+    ]);
+    assert!(record_run(&run, &bundle).is_err());
+
+    let session: Session = serde_json::from_slice(&member(&bundle, "session.json")).unwrap();
+    let error = session.error.clone().unwrap();
+    assert!(error.contains("nosuchfilter"), "{}", error);
+
+    // The replay fails the same way
+    let report = replay(&bundle, &replay_cli(&[])).unwrap();
+    assert_eq!(report.error, session.error);
+    assert!(!report.has_differences(), "{}", report.report());
+    assert!(report.report().contains("recorded error: "));
+}
+
+#[test]
+fn test_replay_rejects_missing_bundles() {
+    let temp_dir = tempdir().unwrap();
+    let bundle = temp_dir.path().join("missing.tar");
+    assert!(replay(&bundle, &replay_cli(&[])).is_err());
+
+    let cli = Cli::try_parse_from(["shinkansen", "--replay", "a.tar", "--record", "b.tar"]);
+    assert!(cli.is_err());
+    let cli = Cli::try_parse_from(["shinkansen", "--replay", "a.tar", "app.conf"]);
+    assert!(cli.is_err());
+}
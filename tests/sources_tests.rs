@@ -0,0 +1,71 @@
+use serde_json::json;
+use shinkansen_lib::error::ShinkansenError;
+use shinkansen_lib::sources::{SourceTask, collect_source_variables};
+use std::collections::HashMap;
+use std::time::Duration;
+
+fn delayed(label: &str, delay_ms: u64, name: &str, value: serde_json::Value) -> SourceTask {
+    let name = name.to_string();
+    SourceTask::new(label, move || {
+        std::thread::sleep(Duration::from_millis(delay_ms));
+        Ok(vec![(name, value)])
+    })
+}
+
+#[test]
+fn test_sources_applied_in_declaration_order() {
+    // The slower first source must not override the second one
+    let tasks = vec![
+        delayed("first", 50, "region", json!("us-east-1")),
+        delayed("second", 0, "region", json!("eu-west-1")),
+    ];
+    let mut variables = HashMap::new();
+    let timings = collect_source_variables(&mut variables, tasks).unwrap();
+
+    assert_eq!(variables.get("region").unwrap().as_str(), Some("eu-west-1"));
+    let labels: Vec<&str> = timings.iter().map(|t| t.label.as_str()).collect();
+    assert_eq!(labels, vec!["first", "second"]);
+    assert!(timings[0].elapsed >= Duration::from_millis(50));
+}
+
+#[test]
+fn test_first_failing_source_is_reported() {
+    let tasks = vec![
+        delayed("ok", 0, "a", json!(1)),
+        SourceTask::new("broken", || {
+            Err(ShinkansenError::DataSourceError(
+                "first failure".to_string(),
+            ))
+        }),
+        SourceTask::new("also broken", || {
+            Err(ShinkansenError::DataSourceError(
+                "second failure".to_string(),
+            ))
+        }),
+    ];
+    let mut variables = HashMap::new();
+    let result = collect_source_variables(&mut variables, tasks);
+
+    assert_eq!(result.unwrap_err().to_string(), "first failure");
+}
+
+#[test]
+fn test_timing_flag() {
+    use clap::Parser;
+    let cli = shinkansen_lib::cli::Cli::try_parse_from(["shinkansen", "--timing"]).unwrap();
+    assert!(cli.timing);
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn test_sources_fetched_concurrently() {
+    let tasks = (0..4)
+        .map(|i| delayed(&format!("source {}", i), 200, &format!("v{}", i), json!(i)))
+        .collect();
+    let mut variables = HashMap::new();
+    let start = std::time::Instant::now();
+    collect_source_variables(&mut variables, tasks).unwrap();
+
+    assert_eq!(variables.len(), 4);
+    assert!(start.elapsed() < Duration::from_millis(700));
+}
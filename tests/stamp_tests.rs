@@ -0,0 +1,179 @@
+use clap::Parser;
+use shinkansen_lib::cli::Cli;
+use shinkansen_lib::processing::{process_inputs, validate_args};
+use shinkansen_lib::stamp::{DEFAULT_STAMP_TEXT, StampOptions, comment_syntax};
+use shinkansen_lib::variables::collect_variables;
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+
+fn stamp() -> StampOptions {
+    StampOptions {
+        text: DEFAULT_STAMP_TEXT.to_string(),
+    }
+}
+
+#[test]
+fn test_comment_syntax_detection() {
+    assert_eq!(comment_syntax(Path::new("deploy.yaml")), Some(("#", "")));
+    assert_eq!(comment_syntax(Path::new("src/main.RS")), Some(("//", "")));
+    assert_eq!(
+        comment_syntax(Path::new("index.html")),
+        Some(("<!--", "-->"))
+    );
+    assert_eq!(comment_syntax(Path::new("site.css")), Some(("/*", "*/")));
+    assert_eq!(comment_syntax(Path::new("Dockerfile")), Some(("#", "")));
+    assert_eq!(comment_syntax(Path::new(".env")), Some(("#", "")));
+    assert_eq!(comment_syntax(Path::new("data.json")), None);
+    assert_eq!(comment_syntax(Path::new("README")), None);
+}
+
+#[test]
+fn test_stamp_header_text() {
+    let header = stamp().header(Path::new("templates/app.yaml"), Path::new("out/app.yaml"));
+    assert_eq!(
+        header.as_deref(),
+        Some("# GENERATED BY shinkansen from templates/app.yaml — do not edit")
+    );
+
+    let custom = StampOptions {
+        text: "Managed by CI ({template})".to_string(),
+    };
+    let header = custom.header(Path::new("page.tmpl"), Path::new("page.html"));
+    assert_eq!(
+        header.as_deref(),
+        Some("<!-- Managed by CI (page.tmpl) -->")
+    );
+}
+
+#[test]
+fn test_stamp_keeps_interpreter_and_xml_declaration_first() {
+    let stamped = stamp().apply(
+        Path::new("run.tmpl"),
+        Path::new("run.sh"),
+        "#!/bin/sh\necho hi\n",
+    );
+    assert_eq!(
+        stamped,
+        "#!/bin/sh\n# GENERATED BY shinkansen from run.tmpl — do not edit\necho hi\n"
+    );
+
+    let stamped = stamp().apply(
+        Path::new("pom.tmpl"),
+        Path::new("pom.xml"),
+        "<?xml version=\"1.0\"?>\r\n<project/>\r\n",
+    );
+    assert_eq!(
+        stamped,
+        "<?xml version=\"1.0\"?>\r\n<!-- GENERATED BY shinkansen from pom.tmpl — do not edit -->\r\n<project/>\r\n"
+    );
+}
+
+#[test]
+fn test_stamp_skips_formats_without_comments() {
+    let content = "{\"a\": 1}\n";
+    assert_eq!(
+        stamp().apply(Path::new("a.tmpl"), Path::new("a.json"), content),
+        content
+    );
+    assert!(stamp().is_stamped(Path::new("a.tmpl"), Path::new("a.json"), content));
+}
+
+#[test]
+fn test_stamp_and_check_stamps_flags() {
+    let temp_dir = tempdir().unwrap();
+    let input_dir = temp_dir.path().join("templates");
+    let output_dir = temp_dir.path().join("out");
+    fs::create_dir_all(&input_dir).unwrap();
+    fs::write(input_dir.join("app.yaml"), "name: {{ name }}\n").unwrap();
+    fs::write(input_dir.join("run.sh"), "#!/bin/sh\necho {{ name }}\n").unwrap();
+
+    let args = |extra: &str| {
+        Cli::try_parse_from([
+            "shinkansen",
+            input_dir.to_str().unwrap(),
+            "-o",
+            output_dir.to_str().unwrap(),
+            "-D",
+            "name=web",
+            extra,
+        ])
+        .unwrap()
+    };
+
+    // Nothing has been generated yet
+    let cli = args("--check-stamps");
+    validate_args(&cli).unwrap();
+    let err =
+        process_inputs(&cli, &collect_variables(&cli).unwrap(), &Default::default()).unwrap_err();
+    assert!(err.to_string().contains("(missing)"));
+
+    let cli = args("--stamp");
+    process_inputs(&cli, &collect_variables(&cli).unwrap(), &Default::default()).unwrap();
+    let app = fs::read_to_string(output_dir.join("app.yaml")).unwrap();
+    assert!(app.starts_with("# GENERATED BY shinkansen from "));
+    assert!(app.ends_with("\nname: web"));
+
+    let cli = args("--check-stamps");
+    process_inputs(&cli, &collect_variables(&cli).unwrap(), &Default::default()).unwrap();
+
+    // A hand edit that drops the header is reported
+    fs::write(output_dir.join("run.sh"), "#!/bin/sh\necho edited\n").unwrap();
+    let err =
+        process_inputs(&cli, &collect_variables(&cli).unwrap(), &Default::default()).unwrap_err();
+    let message = err.to_string();
+    assert!(message.contains("run.sh"));
+    assert!(!message.contains("app.yaml"));
+}
+
+#[test]
+fn test_check_stamps_conflicts() {
+    assert!(Cli::try_parse_from(["shinkansen", "a", "--stamp", "--check-stamps"]).is_err());
+
+    let cli = Cli::try_parse_from(["shinkansen", "a.txt", "--check-stamps"]).unwrap();
+    assert!(validate_args(&cli).is_err());
+}
+
+#[test]
+fn test_stamp_refuses_to_overwrite_hand_maintained_files() {
+    let temp_dir = tempdir().unwrap();
+    let template = temp_dir.path().join("nginx.tmpl");
+    let output = temp_dir.path().join("nginx.conf");
+    fs::write(&template, "worker_processes {{ workers }};").unwrap();
+    fs::write(&output, "# tuned by hand\nworker_processes 16;\n").unwrap();
+
+    let args = |extra: &[&str]| {
+        let mut args = vec![
+            "shinkansen",
+            template.to_str().unwrap(),
+            "-o",
+            output.to_str().unwrap(),
+            "-D",
+            "workers=4",
+            "--stamp",
+        ];
+        args.extend(extra);
+        Cli::try_parse_from(args).unwrap()
+    };
+
+    let cli = args(&[]);
+    let err =
+        process_inputs(&cli, &collect_variables(&cli).unwrap(), &Default::default()).unwrap_err();
+    assert!(err.to_string().contains("Refusing to overwrite"));
+    assert!(err.to_string().contains("nginx.conf"));
+    assert_eq!(
+        fs::read_to_string(&output).unwrap(),
+        "# tuned by hand\nworker_processes 16;\n"
+    );
+
+    // --force takes the file over; from then on it is stamped and regenerates freely
+    let cli = args(&["--force"]);
+    process_inputs(&cli, &collect_variables(&cli).unwrap(), &Default::default()).unwrap();
+    let cli = args(&[]);
+    process_inputs(&cli, &collect_variables(&cli).unwrap(), &Default::default()).unwrap();
+    assert!(
+        fs::read_to_string(&output)
+            .unwrap()
+            .ends_with("\nworker_processes 4;")
+    );
+}
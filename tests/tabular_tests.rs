@@ -0,0 +1,124 @@
+use std::fs;
+use std::path::Path;
+
+use clap::Parser;
+use serde_json::json;
+
+use shinkansen_lib::cli::Cli;
+use shinkansen_lib::fileio::Compression;
+use shinkansen_lib::rendering::render_template;
+use shinkansen_lib::tabular::{delimiter_for, detect_delimiter, parse_table};
+use shinkansen_lib::variables::collect_variables;
+
+#[test]
+fn test_parse_table_rows_as_objects() {
+    let table = parse_table(
+        "name,email\nada,ada@example.com\r\n\nbob,bob@example.com\n",
+        ',',
+    );
+    assert_eq!(
+        table.unwrap(),
+        json!([
+            {"name": "ada", "email": "ada@example.com"},
+            {"name": "bob", "email": "bob@example.com"},
+        ])
+    );
+
+    assert_eq!(parse_table("", ',').unwrap(), json!([]));
+    assert_eq!(parse_table("\u{feff}name\n", ',').unwrap(), json!([]));
+}
+
+#[test]
+fn test_parse_table_quoted_fields() {
+    let content = "name,note\n\"Lovelace, Ada\",\"says \"\"hi\"\"\nand bye\"\nbob,\"\"\n";
+    assert_eq!(
+        parse_table(content, ',').unwrap(),
+        json!([
+            {"name": "Lovelace, Ada", "note": "says \"hi\"\nand bye"},
+            {"name": "bob", "note": ""},
+        ])
+    );
+
+    let err = parse_table("a,b\n\"open,1\n", ',').unwrap_err();
+    assert!(err.contains("line 2 is not closed"), "{}", err);
+    let err = parse_table("a,b\n\"x\"y,1\n", ',').unwrap_err();
+    assert!(err.contains("unexpected 'y'"), "{}", err);
+}
+
+#[test]
+fn test_parse_table_rejects_malformed_tables() {
+    let err = parse_table("a,b\n1,2\n1,2,3\n", ',').unwrap_err();
+    assert!(
+        err.contains("line 3 has 3 field(s), but the header row has 2"),
+        "{}",
+        err
+    );
+    let err = parse_table("a,,c\n1,2,3\n", ',').unwrap_err();
+    assert!(err.contains("column 2"), "{}", err);
+    let err = parse_table("a,b,a\n1,2,3\n", ',').unwrap_err();
+    assert!(err.contains("'a' twice"), "{}", err);
+}
+
+#[test]
+fn test_delimiter_detection() {
+    assert_eq!(delimiter_for(Path::new("users.tsv")), Some('\t'));
+    assert_eq!(delimiter_for(Path::new("users.TAB")), Some('\t'));
+    assert_eq!(delimiter_for(Path::new("users.csv.gz")), Some(','));
+    assert_eq!(delimiter_for(Path::new("users.txt")), None);
+    assert_eq!(delimiter_for(Path::new("users")), None);
+
+    assert_eq!(detect_delimiter("name\temail\nada\tada@example.com"), '\t');
+    assert_eq!(detect_delimiter("name;email;note\na;b;c,d"), ';');
+    assert_eq!(detect_delimiter("\"a;b\"|c|d\n"), '|');
+    assert_eq!(detect_delimiter("name\nada"), ',');
+}
+
+#[test]
+fn test_data_files_as_variables() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let users = temp_dir.path().join("users.txt");
+    fs::write(&users, "name;role\nada;admin\nbob;dev\n").unwrap();
+    let hosts = temp_dir.path().join("hosts.tsv.gz");
+    let content = Compression::Gzip
+        .compress(b"host\tip\nweb1\t10.0.0.1\n")
+        .unwrap();
+    fs::write(&hosts, content).unwrap();
+
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        "--data",
+        &format!("users={}", users.display()),
+        "--data",
+        &format!("hosts={}", hosts.display()),
+    ])
+    .unwrap();
+    let variables = collect_variables(&cli).unwrap();
+    let rendered = render_template(
+        "{% for user in users %}{{ user.name }}={{ user.role }} {% endfor %}{{ hosts[0].ip }}",
+        &variables,
+        "users.conf",
+    )
+    .unwrap();
+    assert_eq!(rendered, "ada=admin bob=dev 10.0.0.1");
+
+    let missing = temp_dir.path().join("missing.csv");
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        "--data",
+        &format!("users={}", missing.display()),
+    ])
+    .unwrap();
+    let err = collect_variables(&cli).unwrap_err();
+    assert!(
+        err.to_string().contains("Failed to read data file"),
+        "{}",
+        err
+    );
+
+    let ragged = temp_dir.path().join("ragged.csv");
+    fs::write(&ragged, "a,b\n1\n").unwrap();
+    let cli =
+        Cli::try_parse_from(["shinkansen", "--data", &format!("r={}", ragged.display())]).unwrap();
+    let err = collect_variables(&cli).unwrap_err();
+    assert!(err.to_string().contains("line 2 has 1 field(s)"), "{}", err);
+}
@@ -0,0 +1,200 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use clap::Parser;
+use tempfile::tempdir;
+
+use shinkansen_lib::cli::Cli;
+use shinkansen_lib::processing::process_inputs;
+use shinkansen_lib::transaction::StagedDirectory;
+
+fn render_transactionally(input_dir: &Path, output: &Path) -> shinkansen_lib::error::Result<()> {
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        input_dir.to_str().unwrap(),
+        "-o",
+        output.to_str().unwrap(),
+        "--transactional",
+    ])
+    .unwrap();
+    process_inputs(&cli, &HashMap::new(), &Default::default())
+}
+
+fn names(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn test_transactional_output_replaces_directory() {
+    let temp_dir = tempdir().unwrap();
+    let input_dir = temp_dir.path().join("templates");
+    let output_dir = temp_dir.path().join("output");
+    fs::create_dir_all(&input_dir).unwrap();
+    fs::create_dir_all(&output_dir).unwrap();
+    fs::write(input_dir.join("a.txt"), "a={{ 1 + 1 }}").unwrap();
+    fs::write(input_dir.join("b.txt"), "b").unwrap();
+    fs::write(output_dir.join("a.txt"), "old").unwrap();
+    fs::write(output_dir.join("keep.txt"), "kept").unwrap();
+
+    render_transactionally(&input_dir, &output_dir).unwrap();
+
+    assert_eq!(fs::read_to_string(output_dir.join("a.txt")).unwrap(), "a=2");
+    assert_eq!(fs::read_to_string(output_dir.join("b.txt")).unwrap(), "b");
+    assert_eq!(
+        fs::read_to_string(output_dir.join("keep.txt")).unwrap(),
+        "kept"
+    );
+    assert_eq!(names(temp_dir.path()), ["output", "templates"]);
+}
+
+#[test]
+fn test_transactional_failure_leaves_output_untouched() {
+    let temp_dir = tempdir().unwrap();
+    let input_dir = temp_dir.path().join("templates");
+    let output_dir = temp_dir.path().join("output");
+    fs::create_dir_all(&input_dir).unwrap();
+    fs::create_dir_all(&output_dir).unwrap();
+    fs::write(input_dir.join("a.txt"), "new").unwrap();
+    fs::write(input_dir.join("b.txt"), "{% if %}").unwrap();
+    fs::write(output_dir.join("a.txt"), "old").unwrap();
+
+    assert!(render_transactionally(&input_dir, &output_dir).is_err());
+
+    assert_eq!(fs::read_to_string(output_dir.join("a.txt")).unwrap(), "old");
+    assert_eq!(names(&output_dir), ["a.txt"]);
+    assert_eq!(names(temp_dir.path()), ["output", "templates"]);
+}
+
+#[test]
+fn test_uncommitted_stage_is_removed() {
+    let temp_dir = tempdir().unwrap();
+    let output_dir = temp_dir.path().join("output");
+
+    let staged = StagedDirectory::begin(&output_dir).unwrap();
+    fs::write(staged.path().join("a.txt"), "a").unwrap();
+    drop(staged);
+
+    assert!(names(temp_dir.path()).is_empty());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_transactional_output_flips_symlink() {
+    let temp_dir = tempdir().unwrap();
+    let input_dir = temp_dir.path().join("templates");
+    let release = temp_dir.path().join("v1");
+    let current = temp_dir.path().join("current");
+    fs::create_dir_all(&input_dir).unwrap();
+    fs::create_dir_all(&release).unwrap();
+    fs::write(input_dir.join("a.txt"), "new").unwrap();
+    fs::write(release.join("a.txt"), "old").unwrap();
+    std::os::unix::fs::symlink("v1", &current).unwrap();
+
+    render_transactionally(&input_dir, &current).unwrap();
+
+    assert_eq!(fs::read_to_string(current.join("a.txt")).unwrap(), "new");
+    assert_eq!(fs::read_to_string(release.join("a.txt")).unwrap(), "old");
+    let target = fs::read_link(&current).unwrap();
+    assert!(
+        target.to_string_lossy().starts_with("current."),
+        "{:?}",
+        target
+    );
+}
+
+#[cfg(unix)]
+fn render_release(input_dir: &Path, releases: &Path, link: &Path, keep: &str) {
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        input_dir.to_str().unwrap(),
+        "-o",
+        releases.to_str().unwrap(),
+        "--link",
+        link.to_str().unwrap(),
+        "--keep",
+        keep,
+    ])
+    .unwrap();
+    shinkansen_lib::processing::validate_args(&cli).unwrap();
+    process_inputs(&cli, &HashMap::new(), &Default::default()).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_releases_switch_link_and_keep_newest() {
+    let temp_dir = tempdir().unwrap();
+    let input_dir = temp_dir.path().join("templates");
+    let releases = temp_dir.path().join("releases");
+    let current = temp_dir.path().join("current");
+    fs::create_dir_all(&input_dir).unwrap();
+
+    for round in 1..=3 {
+        fs::write(input_dir.join("app.conf"), format!("round={}", round)).unwrap();
+        render_release(&input_dir, &releases, &current, "2");
+        assert_eq!(
+            fs::read_to_string(current.join("app.conf")).unwrap(),
+            format!("round={}", round)
+        );
+    }
+
+    // Runs within one second share a timestamp and are numbered
+    let kept = names(&releases);
+    assert_eq!(kept.len(), 2, "{:?}", kept);
+    let target = fs::read_link(&current).unwrap();
+    assert!(target.starts_with("releases"), "{:?}", target);
+    assert_eq!(target.file_name().unwrap().to_string_lossy(), kept[1]);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_keep_only_prunes_releases() {
+    let temp_dir = tempdir().unwrap();
+    let input_dir = temp_dir.path().join("templates");
+    let releases = temp_dir.path().join("releases");
+    let current = temp_dir.path().join("current");
+    let elsewhere = temp_dir.path().join("elsewhere");
+    fs::create_dir_all(&input_dir).unwrap();
+    fs::create_dir_all(&elsewhere).unwrap();
+    fs::write(input_dir.join("app.conf"), "new").unwrap();
+    fs::create_dir_all(releases.join("20000101T000000Z")).unwrap();
+    fs::create_dir_all(releases.join("shared")).unwrap();
+    fs::create_dir_all(releases.join("zzz")).unwrap();
+    std::os::unix::fs::symlink(&elsewhere, releases.join("19990101T000000Z")).unwrap();
+
+    // Only the older release goes; other directories and symlinks stay
+    render_release(&input_dir, &releases, &current, "1");
+    let kept = names(&releases);
+    assert_eq!(kept.len(), 4, "{:?}", kept);
+    assert_eq!(kept[0], "19990101T000000Z");
+    assert_eq!(kept[2..], ["shared", "zzz"]);
+    assert!(!releases.join("20000101T000000Z").exists());
+    assert!(elsewhere.exists());
+    let target = fs::read_link(&current).unwrap();
+    assert_eq!(target.file_name().unwrap().to_string_lossy(), kept[1]);
+}
+
+#[test]
+fn test_link_must_not_replace_a_regular_file() {
+    let temp_dir = tempdir().unwrap();
+    let current = temp_dir.path().join("current");
+    fs::write(&current, "not a link").unwrap();
+
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        "app.conf",
+        "-o",
+        temp_dir.path().join("releases").to_str().unwrap(),
+        "--link",
+        current.to_str().unwrap(),
+    ])
+    .unwrap();
+    let err = shinkansen_lib::processing::validate_args(&cli).unwrap_err();
+    assert!(err.to_string().contains("is not a symlink"));
+    assert!(Cli::try_parse_from(["shinkansen", "app.conf", "-o", "r/", "--keep", "2"]).is_err());
+}
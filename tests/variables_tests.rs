@@ -2,10 +2,12 @@ use clap::Parser;
 use minijinja::Value;
 use shinkansen_lib::cli::Cli;
 use shinkansen_lib::variables::{
-    collect_cli_variables, collect_config_variables, collect_env_variables, collect_variables,
+    DotenvSource, apply_derived_variables, collect_cli_variables, collect_config_variables,
+    collect_env_variables, collect_stdin_variables, collect_variable_context, collect_variables,
 };
 use std::collections::HashMap;
 use std::env;
+use std::path::Path;
 
 #[test]
 fn test_cli_variables_parsing() {
@@ -64,6 +66,7 @@ fn test_env_variables() {
 }
 
 #[test]
+#[allow(clippy::approx_constant)]
 fn test_env_variables_numeric_types() {
     // Set test environment variables with numeric values
     // TODO: Audit that the environment access only happens in single-threaded code.
@@ -90,10 +93,7 @@ fn test_env_variables_numeric_types() {
         f64::try_from(variables.get("TEST_FLOAT").unwrap().clone()).unwrap(),
         3.14
     );
-    assert_eq!(
-        bool::try_from(variables.get("TEST_BOOL").unwrap().clone()).unwrap(),
-        true
-    );
+    assert!(bool::try_from(variables.get("TEST_BOOL").unwrap().clone()).unwrap());
     assert_eq!(
         variables.get("TEST_STRING").unwrap().as_str().unwrap(),
         "hello"
@@ -121,7 +121,7 @@ fn test_env_variables_nonexistent() {
     let variables = collect_variables(&cli).unwrap();
 
     // Should not contain the nonexistent variable
-    assert!(variables.get("NONEXISTENT_VAR").is_none());
+    assert!(!variables.contains_key("NONEXISTENT_VAR"));
 }
 
 #[test]
@@ -206,10 +206,7 @@ fn test_config_file_json() {
         "test"
     );
     assert_eq!(variables.get("number_var").unwrap().as_i64().unwrap(), 42);
-    assert_eq!(
-        bool::try_from(variables.get("bool_var").unwrap().clone()).unwrap(),
-        true
-    );
+    assert!(bool::try_from(variables.get("bool_var").unwrap().clone()).unwrap());
 
     // Clean up
     std::fs::remove_file(config_file).ok();
@@ -313,10 +310,7 @@ fn test_collect_config_variables_directly() {
         "test"
     );
     assert_eq!(variables.get("number_var").unwrap().as_i64().unwrap(), 42);
-    assert_eq!(
-        bool::try_from(variables.get("bool_var").unwrap().clone()).unwrap(),
-        true
-    );
+    assert!(bool::try_from(variables.get("bool_var").unwrap().clone()).unwrap());
 
     // Clean up
     std::fs::remove_file(config_file).ok();
@@ -404,6 +398,7 @@ fn test_cli_variables_with_escaped_characters() {
 }
 
 #[test]
+#[allow(clippy::approx_constant)]
 fn test_cli_variables_numeric_types() {
     // Test integer
     let mut variables: HashMap<String, minijinja::Value> = HashMap::new();
@@ -445,19 +440,13 @@ fn test_cli_variables_numeric_types() {
     let mut variables: HashMap<String, minijinja::Value> = HashMap::new();
     let cli_vars = vec!["bool_true=true".to_string()];
     collect_cli_variables(&mut variables, &cli_vars).unwrap();
-    assert_eq!(
-        bool::try_from(variables.get("bool_true").unwrap().clone()).unwrap(),
-        true
-    );
+    assert!(bool::try_from(variables.get("bool_true").unwrap().clone()).unwrap());
 
     // Test boolean false
     let mut variables: HashMap<String, minijinja::Value> = HashMap::new();
     let cli_vars = vec!["bool_false=false".to_string()];
     collect_cli_variables(&mut variables, &cli_vars).unwrap();
-    assert_eq!(
-        bool::try_from(variables.get("bool_false").unwrap().clone()).unwrap(),
-        false
-    );
+    assert!(!bool::try_from(variables.get("bool_false").unwrap().clone()).unwrap());
 
     // Test string (non-numeric)
     let mut variables: HashMap<String, minijinja::Value> = HashMap::new();
@@ -477,10 +466,7 @@ fn test_cli_variables_numeric_types() {
         f64::try_from(variables2.get("float").unwrap().clone()).unwrap(),
         1.5
     );
-    assert_eq!(
-        bool::try_from(variables2.get("bool").unwrap().clone()).unwrap(),
-        true
-    );
+    assert!(bool::try_from(variables2.get("bool").unwrap().clone()).unwrap());
     assert_eq!(variables2.get("text").unwrap().as_str().unwrap(), "test");
 }
 
@@ -499,10 +485,10 @@ fn test_cli_variables_json_arrays_and_objects() {
             assert_eq!(vec[1].as_i64().unwrap(), 2);
             assert_eq!(vec[2].as_i64().unwrap(), 3);
         } else {
-            assert!(false, "items is not an array");
+            panic!("items is not an array");
         }
     } else {
-        assert!(false, "items key not found");
+        panic!("items key not found");
     }
 
     // Test JSON array with strings
@@ -517,10 +503,10 @@ fn test_cli_variables_json_arrays_and_objects() {
             assert_eq!(vec[0].as_str().unwrap(), "hello");
             assert_eq!(vec[1].as_str().unwrap(), "world");
         } else {
-            assert!(false, "items is not an array");
+            panic!("items is not an array");
         }
     } else {
-        assert!(false, "items key not found");
+        panic!("items key not found");
     }
 
     // Test JSON object parsing
@@ -538,10 +524,10 @@ fn test_cli_variables_json_arrays_and_objects() {
                 "value"
             );
         } else {
-            assert!(false, "obj is not an object");
+            panic!("obj is not an object");
         }
     } else {
-        assert!(false, "obj key not found");
+        panic!("obj key not found");
     }
 
     // Test nested JSON structures
@@ -559,22 +545,22 @@ fn test_cli_variables_json_arrays_and_objects() {
                             assert_eq!(vec.len(), 3);
                             assert_eq!(vec[0].as_i64().unwrap(), 1);
                         } else {
-                            assert!(false, "nested.array is not an array");
+                            panic!("nested.array is not an array");
                         }
                     } else {
-                        assert!(false, "nested.array key not found");
+                        panic!("nested.array key not found");
                     }
                 } else {
-                    assert!(false, "data.nested is not an object");
+                    panic!("data.nested is not an object");
                 }
             } else {
-                assert!(false, "data.nested key not found");
+                panic!("data.nested key not found");
             }
         } else {
-            assert!(false, "data is not an object");
+            panic!("data is not an object");
         }
     } else {
-        assert!(false, "data key not found");
+        panic!("data key not found");
     }
 
     // Test mixed JSON and regular values in one command
@@ -589,10 +575,10 @@ fn test_cli_variables_json_arrays_and_objects() {
             let vec: Vec<minijinja::Value> = arr.collect();
             assert_eq!(vec.len(), 2);
         } else {
-            assert!(false, "arr is not an array");
+            panic!("arr is not an array");
         }
     } else {
-        assert!(false, "arr key not found");
+        panic!("arr key not found");
     }
 
     if let Some(obj_value) = variables5.get("obj") {
@@ -602,10 +588,10 @@ fn test_cli_variables_json_arrays_and_objects() {
                 "v"
             );
         } else {
-            assert!(false, "obj is not an object");
+            panic!("obj is not an object");
         }
     } else {
-        assert!(false, "obj key not found");
+        panic!("obj key not found");
     }
 
     // Test that commas inside JSON arrays don't split variables
@@ -621,10 +607,10 @@ fn test_cli_variables_json_arrays_and_objects() {
             let vec: Vec<minijinja::Value> = arr.collect();
             assert_eq!(vec.len(), 3);
         } else {
-            assert!(false, "items is not an array");
+            panic!("items is not an array");
         }
     } else {
-        assert!(false, "items key not found");
+        panic!("items key not found");
     }
 }
 
@@ -670,10 +656,10 @@ fn test_cli_variables_escaped_brackets() {
             let vec: Vec<minijinja::Value> = arr.collect();
             assert_eq!(vec.len(), 3);
         } else {
-            assert!(false, "arr is not an array");
+            panic!("arr is not an array");
         }
     } else {
-        assert!(false, "arr key not found");
+        panic!("arr key not found");
     }
 
     assert_eq!(
@@ -732,13 +718,13 @@ fn test_cli_variables_nested_objects() {
             if let Some(bar_value) = obj.get_value(&Value::from("bar")) {
                 assert_eq!(bar_value.as_i64().unwrap(), 42);
             } else {
-                assert!(false, "bar key not found in foo object");
+                panic!("bar key not found in foo object");
             }
         } else {
-            assert!(false, "foo is not an object");
+            panic!("foo is not an object");
         }
     } else {
-        assert!(false, "foo key not found");
+        panic!("foo key not found");
     }
 
     // Test multiple nested properties in one command
@@ -769,10 +755,10 @@ fn test_cli_variables_nested_objects() {
                 42
             );
         } else {
-            assert!(false, "foo is not an object");
+            panic!("foo is not an object");
         }
     } else {
-        assert!(false, "foo key not found");
+        panic!("foo key not found");
     }
 
     // Test second nested variable separately
@@ -786,13 +772,13 @@ fn test_cli_variables_nested_objects() {
             if let Some(baz_value) = obj.get_value(&Value::from("baz")) {
                 assert!(baz_value.is_true());
             } else {
-                assert!(false, "baz value not found");
+                panic!("baz value not found");
             }
         } else {
-            assert!(false, "foo is not an object");
+            panic!("foo is not an object");
         }
     } else {
-        assert!(false, "foo key not found");
+        panic!("foo key not found");
     }
 
     // Test deeply nested objects
@@ -815,28 +801,25 @@ fn test_cli_variables_nested_objects() {
                                 "deep_value"
                             );
                         } else {
-                            assert!(false, "baz is not an object");
+                            panic!("baz is not an object");
                         }
                     } else {
-                        assert!(false, "baz key not found");
+                        panic!("baz key not found");
                     }
                 } else {
-                    assert!(false, "bar is not an object");
+                    panic!("bar is not an object");
                 }
             } else {
-                assert!(false, "bar key not found");
+                panic!("bar key not found");
             }
         } else {
-            assert!(false, "foo is not an object");
+            panic!("foo is not an object");
         }
     } else {
-        assert!(false, "foo key not found");
+        panic!("foo key not found");
     }
 
     // Test merging with existing nested structures
-    // Note: The current implementation doesn't merge nested objects when processing
-    // multiple variables with the same root key. Each variable overwrites the previous one.
-    // This is a known limitation that could be improved in the future.
     let mut variables4 = HashMap::new();
     let cli_vars1 = vec!["foo.bar=first".to_string()];
     collect_cli_variables(&mut variables4, &cli_vars1).unwrap();
@@ -844,10 +827,9 @@ fn test_cli_variables_nested_objects() {
     let cli_vars2 = vec!["foo.baz=second".to_string()];
     collect_cli_variables(&mut variables4, &cli_vars2).unwrap();
 
-    // Due to the current limitation, only the last nested object will be present
+    // Both keys are present since nested objects are merged
     if let Some(foo_value) = variables4.get("foo") {
         if let Some(obj) = foo_value.as_object() {
-            // Only "baz" should be present since it was the last one processed
             assert_eq!(
                 obj.get_value(&Value::from("baz"))
                     .unwrap()
@@ -855,13 +837,18 @@ fn test_cli_variables_nested_objects() {
                     .unwrap(),
                 "second"
             );
-            // "bar" should not be present due to the overwriting behavior
-            assert!(obj.get_value(&Value::from("bar")).is_none());
+            assert_eq!(
+                obj.get_value(&Value::from("bar"))
+                    .unwrap()
+                    .as_str()
+                    .unwrap(),
+                "first"
+            );
         } else {
-            assert!(false, "foo is not an object");
+            panic!("foo is not an object");
         }
     } else {
-        assert!(false, "foo key not found");
+        panic!("foo key not found");
     }
 
     // Test mixed nested and non-nested variables
@@ -881,9 +868,926 @@ fn test_cli_variables_nested_objects() {
                 "world"
             );
         } else {
-            assert!(false, "foo is not an object");
+            panic!("foo is not an object");
         }
     } else {
-        assert!(false, "foo key not found");
+        panic!("foo key not found");
     }
 }
+
+#[test]
+fn test_profile_flag_selects_conditional_config_section() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let config_path = temp_dir.path().join("config.toml");
+    std::fs::write(
+        &config_path,
+        "replicas = 1\n\n[vars.'profile:prod']\nreplicas = 3\n",
+    )
+    .unwrap();
+    let config = config_path.to_str().unwrap();
+
+    let cli = Cli::try_parse_from(["shinkansen", "-c", config]).unwrap();
+    let variables = collect_variables(&cli).unwrap();
+    assert_eq!(variables.get("replicas").unwrap().as_i64(), Some(1));
+
+    let cli = Cli::try_parse_from(["shinkansen", "-c", config, "--profile", "prod"]).unwrap();
+    let variables = collect_variables(&cli).unwrap();
+    assert_eq!(variables.get("replicas").unwrap().as_i64(), Some(3));
+    assert!(!variables.contains_key("vars"));
+}
+
+#[test]
+fn test_profile_flag_merges_profiles_section() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let config_path = temp_dir.path().join("config.yaml");
+    std::fs::write(
+        &config_path,
+        "log_level: info
+db: {host: localhost, port: 5432}
+profiles:
+  prod:
+    log_level: warn
+    db: {host: db.prod}
+  dev:
+    debug: true
+",
+    )
+    .unwrap();
+    let config = config_path.to_str().unwrap();
+
+    let cli = Cli::try_parse_from(["shinkansen", "-c", config]).unwrap();
+    let variables = collect_variables(&cli).unwrap();
+    assert_eq!(variables.get("log_level").unwrap().as_str(), Some("info"));
+    assert!(!variables.contains_key("profiles"));
+    assert!(!variables.contains_key("debug"));
+
+    let cli = Cli::try_parse_from(["shinkansen", "-c", config, "--profile", "prod"]).unwrap();
+    let variables = collect_variables(&cli).unwrap();
+    assert_eq!(variables.get("log_level").unwrap().as_str(), Some("warn"));
+    let db = variables.get("db").unwrap();
+    assert_eq!(nested(db, &["host"]).as_str(), Some("db.prod"));
+    assert_eq!(nested(db, &["port"]).as_i64(), Some(5432));
+}
+
+fn derived(entries: &[(&str, &str)]) -> Vec<(String, String)> {
+    entries
+        .iter()
+        .map(|(name, template)| (name.to_string(), template.to_string()))
+        .collect()
+}
+
+#[test]
+fn test_derived_variables_resolve_in_any_order() {
+    let mut variables = HashMap::new();
+    variables.insert("registry".to_string(), Value::from("ghcr.io"));
+    variables.insert("app".to_string(), Value::from("web"));
+    variables.insert("tag".to_string(), Value::from("1.2"));
+
+    let entries = derived(&[
+        ("deployment", "{{ image | replace(':', '-') }}"),
+        ("image", "{{ registry }}/{{ app }}:{{ tag }}"),
+    ]);
+    apply_derived_variables(&mut variables, &entries).unwrap();
+
+    assert_eq!(
+        variables.get("image").unwrap().as_str(),
+        Some("ghcr.io/web:1.2")
+    );
+    assert_eq!(
+        variables.get("deployment").unwrap().as_str(),
+        Some("ghcr.io/web-1.2")
+    );
+}
+
+#[test]
+fn test_derived_variables_do_not_override_defined_names() {
+    let mut variables = HashMap::new();
+    variables.insert("image".to_string(), Value::from("custom"));
+    apply_derived_variables(&mut variables, &derived(&[("image", "{{ missing }}")])).unwrap();
+    assert_eq!(variables.get("image").unwrap().as_str(), Some("custom"));
+}
+
+#[test]
+fn test_derived_variables_report_unresolvable_templates() {
+    let mut variables = HashMap::new();
+    let entries = derived(&[("a", "{{ b }}"), ("b", "{{ a }}")]);
+    let err = apply_derived_variables(&mut variables, &entries).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("Failed to compute derived variables")
+    );
+}
+
+#[test]
+fn test_derived_section_from_config_uses_cli_values() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let config_path = temp_dir.path().join("config.toml");
+    std::fs::write(
+        &config_path,
+        "registry = \"ghcr.io\"\napp = \"web\"\n\n[derived]\nimage = \"{{ registry }}/{{ app }}:{{ tag }}\"\n",
+    )
+    .unwrap();
+    let config = config_path.to_str().unwrap();
+
+    let cli = Cli::try_parse_from(["shinkansen", "-c", config, "-D", "tag=v2"]).unwrap();
+    let variables = collect_variables(&cli).unwrap();
+    assert_eq!(
+        variables.get("image").unwrap().as_str(),
+        Some("ghcr.io/web:v2")
+    );
+    assert!(!variables.contains_key("derived"));
+
+    let cli =
+        Cli::try_parse_from(["shinkansen", "-c", config, "-D", "tag=v2,image=local"]).unwrap();
+    let variables = collect_variables(&cli).unwrap();
+    assert_eq!(variables.get("image").unwrap().as_str(), Some("local"));
+}
+
+fn nested(value: &Value, path: &[&str]) -> Value {
+    path.iter()
+        .fold(value.clone(), |value, key| value.get_attr(key).unwrap())
+}
+
+#[test]
+fn test_nested_cli_keys_are_merged() {
+    let mut variables = HashMap::new();
+    collect_cli_variables(
+        &mut variables,
+        &[
+            "app.image.tag=v1".to_string(),
+            "app.image.repo=web,app.replicas=3".to_string(),
+        ],
+    )
+    .unwrap();
+
+    let app = variables.get("app").unwrap();
+    assert_eq!(nested(app, &["image", "tag"]).as_str(), Some("v1"));
+    assert_eq!(nested(app, &["image", "repo"]).as_str(), Some("web"));
+    assert_eq!(nested(app, &["replicas"]).as_i64(), Some(3));
+}
+
+#[test]
+fn test_nested_cli_key_merges_into_config_object() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let config_path = temp_dir.path().join("config.json");
+    std::fs::write(
+        &config_path,
+        r#"{"db": {"host": "localhost", "port": 5432}}"#,
+    )
+    .unwrap();
+
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        "-c",
+        config_path.to_str().unwrap(),
+        "-D",
+        "db.port=6432",
+    ])
+    .unwrap();
+    let variables = collect_variables(&cli).unwrap();
+    let db = variables.get("db").unwrap();
+    assert_eq!(nested(db, &["host"]).as_str(), Some("localhost"));
+    assert_eq!(nested(db, &["port"]).as_i64(), Some(6432));
+}
+
+#[test]
+fn test_repeated_config_files_are_deep_merged_in_order() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let base = temp_dir.path().join("base.yaml");
+    let prod = temp_dir.path().join("prod.yaml");
+    std::fs::write(
+        &base,
+        "app: web\ndb:\n  host: localhost\n  port: 5432\n  pool: {min: 1, max: 5}\n\
+         _meta:\n  region: {default: eu-west-1}\n",
+    )
+    .unwrap();
+    std::fs::write(
+        &prod,
+        "db:\n  host: db.prod\n  pool: {max: 50}\n_meta:\n  region: {default: us-east-1}\n",
+    )
+    .unwrap();
+
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        "-c",
+        base.to_str().unwrap(),
+        "-c",
+        prod.to_str().unwrap(),
+    ])
+    .unwrap();
+    let context = collect_variable_context(&cli).unwrap();
+    let db = context.values.get("db").unwrap();
+    assert_eq!(nested(db, &["host"]).as_str(), Some("db.prod"));
+    assert_eq!(nested(db, &["port"]).as_i64(), Some(5432));
+    assert_eq!(nested(db, &["pool", "min"]).as_i64(), Some(1));
+    assert_eq!(nested(db, &["pool", "max"]).as_i64(), Some(50));
+    assert_eq!(context.values.get("app").unwrap().as_str(), Some("web"));
+    assert_eq!(
+        context.values.get("region").unwrap().as_str(),
+        Some("us-east-1")
+    );
+    assert_eq!(
+        context.provenance.winner("app").unwrap().to_string(),
+        format!("config {}", base.display())
+    );
+    assert_eq!(
+        context.provenance.winner("db").unwrap().to_string(),
+        format!("config {}", prod.display())
+    );
+}
+
+#[test]
+fn test_config_object_merges_into_env_json_object() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let config_path = temp_dir.path().join("config.yaml");
+    std::fs::write(&config_path, "DEEP_MERGE_APP:\n  image:\n    repo: web\n").unwrap();
+
+    unsafe {
+        env::set_var(
+            "DEEP_MERGE_APP",
+            r#"{"env": "prod", "image": {"repo": "old", "pull": "Always"}}"#,
+        )
+    };
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        "--env",
+        "DEEP_MERGE_APP:json",
+        "-c",
+        config_path.to_str().unwrap(),
+    ])
+    .unwrap();
+    let variables = collect_variables(&cli).unwrap();
+    unsafe { env::remove_var("DEEP_MERGE_APP") };
+
+    let app = variables.get("DEEP_MERGE_APP").unwrap();
+    assert_eq!(nested(app, &["env"]).as_str(), Some("prod"));
+    assert_eq!(nested(app, &["image", "repo"]).as_str(), Some("web"));
+    assert_eq!(nested(app, &["image", "pull"]).as_str(), Some("Always"));
+}
+
+#[test]
+fn test_object_values_merge_and_other_values_replace() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let config_path = temp_dir.path().join("config.json");
+    std::fs::write(
+        &config_path,
+        r#"{"app": {"image": {"repo": "web", "tag": "1"}, "ports": [80, 443]}}"#,
+    )
+    .unwrap();
+
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        "-c",
+        config_path.to_str().unwrap(),
+        "--set-json",
+        r#"app.image={"tag": "2"}"#,
+        "--set-json",
+        "app.ports=[8080]",
+    ])
+    .unwrap();
+    let variables = collect_variables(&cli).unwrap();
+    let app = variables.get("app").unwrap();
+    assert_eq!(nested(app, &["image", "repo"]).as_str(), Some("web"));
+    assert_eq!(nested(app, &["image", "tag"]).as_str(), Some("2"));
+    assert_eq!(nested(app, &["ports"]).len(), Some(1));
+}
+
+#[test]
+fn test_cli_object_merges_into_dotted_keys() {
+    let mut variables = HashMap::new();
+    collect_cli_variables(
+        &mut variables,
+        &[
+            "foo.bar=1".to_string(),
+            "foo.baz=2".to_string(),
+            r#"foo={"qux": 3}"#.to_string(),
+        ],
+    )
+    .unwrap();
+
+    let foo = variables.get("foo").unwrap();
+    assert_eq!(nested(foo, &["bar"]).as_i64(), Some(1));
+    assert_eq!(nested(foo, &["baz"]).as_i64(), Some(2));
+    assert_eq!(nested(foo, &["qux"]).as_i64(), Some(3));
+}
+
+#[test]
+fn test_set_string_skips_type_detection() {
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        "--set-string",
+        "version=1.10,enabled=true",
+        "--set-string",
+        "app.zip=01234",
+    ])
+    .unwrap();
+    let variables = collect_variables(&cli).unwrap();
+    assert_eq!(variables.get("version").unwrap().as_str(), Some("1.10"));
+    assert_eq!(variables.get("enabled").unwrap().as_str(), Some("true"));
+    assert_eq!(
+        nested(variables.get("app").unwrap(), &["zip"]).as_str(),
+        Some("01234")
+    );
+}
+
+#[test]
+fn test_set_json_parses_values() {
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        "-D",
+        "app.name=web",
+        "--set-json",
+        r#"app.ports=[80, 443]"#,
+        "--set-json",
+        r#"labels={"tier": "frontend", "note": "a,b"}"#,
+    ])
+    .unwrap();
+    let variables = collect_variables(&cli).unwrap();
+    let app = variables.get("app").unwrap();
+    assert_eq!(nested(app, &["name"]).as_str(), Some("web"));
+    assert_eq!(nested(app, &["ports"]).len(), Some(2));
+    assert_eq!(
+        nested(variables.get("labels").unwrap(), &["note"]).as_str(),
+        Some("a,b")
+    );
+
+    let cli = Cli::try_parse_from(["shinkansen", "--set-json", "bad={nope"]).unwrap();
+    let err = collect_variables(&cli).unwrap_err();
+    assert!(err.to_string().contains("Invalid JSON for --set-json bad"));
+}
+
+#[test]
+fn test_set_file_reads_contents() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let cert_path = temp_dir.path().join("server.pem");
+    std::fs::write(&cert_path, "-----BEGIN CERTIFICATE-----\nabc\n").unwrap();
+
+    let assignment = format!("tls.cert={}", cert_path.display());
+    let cli = Cli::try_parse_from(["shinkansen", "--set-file", &assignment]).unwrap();
+    let variables = collect_variables(&cli).unwrap();
+    assert_eq!(
+        nested(variables.get("tls").unwrap(), &["cert"]).as_str(),
+        Some("-----BEGIN CERTIFICATE-----\nabc\n")
+    );
+
+    let missing = format!("x={}", temp_dir.path().join("missing").display());
+    let cli = Cli::try_parse_from(["shinkansen", "--set-file", &missing]).unwrap();
+    assert!(collect_variables(&cli).is_err());
+}
+
+#[test]
+fn test_set_flags_override_define() {
+    let cli =
+        Cli::try_parse_from(["shinkansen", "--set-string", "port=80", "-D", "port=8080"]).unwrap();
+    let variables = collect_variables(&cli).unwrap();
+    assert_eq!(variables.get("port").unwrap().as_str(), Some("80"));
+}
+
+#[test]
+fn test_array_index_keys() {
+    let mut variables = HashMap::new();
+    collect_cli_variables(
+        &mut variables,
+        &[
+            "servers[0].port=80".to_string(),
+            "servers[0].host=web".to_string(),
+            "servers[1].port=443".to_string(),
+        ],
+    )
+    .unwrap();
+
+    let servers = variables.get("servers").unwrap();
+    assert_eq!(servers.len(), Some(2));
+    let first = servers.get_item(&Value::from(0)).unwrap();
+    assert_eq!(nested(&first, &["port"]).as_i64(), Some(80));
+    assert_eq!(nested(&first, &["host"]).as_str(), Some("web"));
+    let second = servers.get_item(&Value::from(1)).unwrap();
+    assert_eq!(nested(&second, &["port"]).as_i64(), Some(443));
+}
+
+#[test]
+fn test_array_index_updates_configured_list() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let config_path = temp_dir.path().join("config.yaml");
+    std::fs::write(
+        &config_path,
+        "servers:\n  - host: a\n    port: 80\n  - host: b\n    port: 81\n",
+    )
+    .unwrap();
+
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        "-c",
+        config_path.to_str().unwrap(),
+        "-D",
+        "servers[1].port=8081",
+        "-D",
+        "servers[+].host=c",
+    ])
+    .unwrap();
+    let variables = collect_variables(&cli).unwrap();
+    let servers = variables.get("servers").unwrap();
+    assert_eq!(servers.len(), Some(3));
+
+    let second = servers.get_item(&Value::from(1)).unwrap();
+    assert_eq!(nested(&second, &["host"]).as_str(), Some("b"));
+    assert_eq!(nested(&second, &["port"]).as_i64(), Some(8081));
+    let third = servers.get_item(&Value::from(2)).unwrap();
+    assert_eq!(nested(&third, &["host"]).as_str(), Some("c"));
+}
+
+#[test]
+fn test_array_append_and_padding() {
+    let mut variables = HashMap::new();
+    collect_cli_variables(
+        &mut variables,
+        &[
+            "hosts[+]=a".to_string(),
+            "hosts[+]=b".to_string(),
+            "matrix[0][2]=x".to_string(),
+        ],
+    )
+    .unwrap();
+
+    let hosts = variables.get("hosts").unwrap();
+    assert_eq!(hosts.to_string(), r#"["a", "b"]"#);
+    let row = variables
+        .get("matrix")
+        .unwrap()
+        .get_item(&Value::from(0))
+        .unwrap();
+    assert_eq!(row.len(), Some(3));
+    assert!(row.get_item(&Value::from(0)).unwrap().is_none());
+    assert_eq!(row.get_item(&Value::from(2)).unwrap().as_str(), Some("x"));
+}
+
+#[test]
+fn test_invalid_array_keys() {
+    for key in [
+        "servers[x]=1",
+        "servers[0]port=1",
+        "[0]=1",
+        "a..b=1",
+        "a[=1",
+    ] {
+        let mut variables = HashMap::new();
+        let result = collect_cli_variables(&mut variables, &[key.to_string()]);
+        assert!(result.is_err(), "{} should be rejected", key);
+    }
+}
+
+#[test]
+fn test_quoted_key_segments() {
+    let mut variables = HashMap::new();
+    collect_cli_variables(
+        &mut variables,
+        &[
+            r#"a."dotted.key"=1"#.to_string(),
+            "'weird key'=v,'x=y'=2".to_string(),
+            r#"servers[0]."host.name"=web"#.to_string(),
+        ],
+    )
+    .unwrap();
+
+    let a = variables.get("a").unwrap();
+    assert_eq!(a.get_attr("dotted.key").unwrap().as_i64(), Some(1));
+    assert_eq!(variables.get("weird key").unwrap().as_str(), Some("v"));
+    assert_eq!(variables.get("x=y").unwrap().as_i64(), Some(2));
+    let server = variables
+        .get("servers")
+        .unwrap()
+        .get_item(&Value::from(0))
+        .unwrap();
+    assert_eq!(server.get_attr("host.name").unwrap().as_str(), Some("web"));
+}
+
+#[test]
+fn test_quotes_inside_key_names_are_literal() {
+    let mut variables = HashMap::new();
+    collect_cli_variables(
+        &mut variables,
+        &["o'brien=1,x=2".to_string(), r#"a\"b=3"#.to_string()],
+    )
+    .unwrap();
+
+    assert_eq!(variables.get("o'brien").unwrap().as_i64(), Some(1));
+    assert_eq!(variables.get("x").unwrap().as_i64(), Some(2));
+    assert_eq!(variables.get(r#"a\"b"#).unwrap().as_i64(), Some(3));
+}
+
+#[test]
+fn test_quotes_in_values_are_not_special() {
+    let mut variables = HashMap::new();
+    collect_cli_variables(&mut variables, &["msg=it's,other=x".to_string()]).unwrap();
+    assert_eq!(variables.get("msg").unwrap().as_str(), Some("it's"));
+    assert_eq!(variables.get("other").unwrap().as_str(), Some("x"));
+}
+
+#[test]
+fn test_unicode_keys_and_values() {
+    let mut variables = HashMap::new();
+    collect_cli_variables(
+        &mut variables,
+        &["grüße.straße=Ünïcödé,naïve=a\\,b,emoji=🚄".to_string()],
+    )
+    .unwrap();
+
+    let grusse = variables.get("grüße").unwrap();
+    assert_eq!(grusse.get_attr("straße").unwrap().as_str(), Some("Ünïcödé"));
+    assert_eq!(variables.get("naïve").unwrap().as_str(), Some("a,b"));
+    assert_eq!(variables.get("emoji").unwrap().as_str(), Some("🚄"));
+}
+
+#[test]
+fn test_invalid_quoted_keys() {
+    for key in [r#""unterminated=1"#, r#""a"b=1"#, "a[0=1"] {
+        let mut variables = HashMap::new();
+        let result = collect_cli_variables(&mut variables, &[key.to_string()]);
+        assert!(result.is_err(), "{} should be rejected", key);
+    }
+}
+
+#[test]
+fn test_require_env_reports_all_missing_variables() {
+    // TODO: Audit that the environment access only happens in single-threaded code.
+    unsafe { env::set_var("TEST_REQUIRED_PRESENT", "here") };
+
+    let args = vec![
+        "shinkansen",
+        "--env",
+        "TEST_REQUIRED_PRESENT,TEST_REQUIRED_MISSING1,TEST_REQUIRED_MISSING2",
+        "--require-env",
+    ];
+    let cli = Cli::try_parse_from(args).unwrap();
+    let err = collect_variables(&cli).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Missing required environment variables: TEST_REQUIRED_MISSING1, TEST_REQUIRED_MISSING2"
+    );
+
+    // Without --require-env missing variables are still ignored
+    let args = vec![
+        "shinkansen",
+        "--env",
+        "TEST_REQUIRED_PRESENT,TEST_REQUIRED_MISSING1",
+    ];
+    let cli = Cli::try_parse_from(args).unwrap();
+    let variables = collect_variables(&cli).unwrap();
+    assert_eq!(
+        variables.get("TEST_REQUIRED_PRESENT").unwrap().as_str(),
+        Some("here")
+    );
+    assert!(!variables.contains_key("TEST_REQUIRED_MISSING1"));
+
+    // TODO: Audit that the environment access only happens in single-threaded code.
+    unsafe { env::remove_var("TEST_REQUIRED_PRESENT") };
+}
+
+#[test]
+fn test_require_env_needs_env() {
+    assert!(Cli::try_parse_from(["shinkansen", "--require-env"]).is_err());
+}
+
+#[test]
+fn test_env_json_suffix() {
+    // TODO: Audit that the environment access only happens in single-threaded code.
+    unsafe {
+        env::set_var(
+            "TEST_ENV_JSON_CONFIG",
+            r#"{"paths": ["C:\\temp"], "replicas": 3}"#,
+        )
+    };
+    // TODO: Audit that the environment access only happens in single-threaded code.
+    unsafe { env::set_var("TEST_ENV_JSON_BROKEN", "{not json") };
+
+    let args = vec!["shinkansen", "--env", "TEST_ENV_JSON_CONFIG:json"];
+    let cli = Cli::try_parse_from(args).unwrap();
+    let variables = collect_variables(&cli).unwrap();
+    let config = variables.get("TEST_ENV_JSON_CONFIG").unwrap();
+    assert_eq!(config.get_attr("replicas").unwrap().as_i64(), Some(3));
+    let first_path = config
+        .get_attr("paths")
+        .unwrap()
+        .get_item(&Value::from(0))
+        .unwrap();
+    assert_eq!(first_path.as_str(), Some("C:\\temp"));
+
+    let args = vec!["shinkansen", "--env", "TEST_ENV_JSON_BROKEN:json"];
+    let cli = Cli::try_parse_from(args).unwrap();
+    let err = collect_variables(&cli).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("Environment variable TEST_ENV_JSON_BROKEN is not valid JSON")
+    );
+
+    // Missing variables are reported by name, without the suffix
+    let args = vec![
+        "shinkansen",
+        "--env",
+        "TEST_ENV_JSON_UNSET:json",
+        "--require-env",
+    ];
+    let cli = Cli::try_parse_from(args).unwrap();
+    let err = collect_variables(&cli).unwrap_err();
+    assert!(err.to_string().ends_with(": TEST_ENV_JSON_UNSET"));
+
+    // TODO: Audit that the environment access only happens in single-threaded code.
+    unsafe { env::remove_var("TEST_ENV_JSON_CONFIG") };
+    // TODO: Audit that the environment access only happens in single-threaded code.
+    unsafe { env::remove_var("TEST_ENV_JSON_BROKEN") };
+}
+
+#[test]
+fn test_config_template_renders_config_before_parsing() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let config_path = temp_dir.path().join("config.yaml");
+    std::fs::write(
+        &config_path,
+        "replicas: {{ 3 if env == \"prod\" else 1 }}\nurl: https://{{ env }}.example.com\n",
+    )
+    .unwrap();
+
+    let args = vec![
+        "shinkansen",
+        "-c",
+        config_path.to_str().unwrap(),
+        "--config-template",
+        "-D",
+        "env=prod",
+    ];
+    let cli = Cli::try_parse_from(args).unwrap();
+    let variables = collect_variables(&cli).unwrap();
+    assert_eq!(variables.get("replicas").unwrap().as_i64(), Some(3));
+    assert_eq!(
+        variables.get("url").unwrap().as_str(),
+        Some("https://prod.example.com")
+    );
+    // -D still takes precedence over the rendered config
+    assert_eq!(variables.get("env").unwrap().as_str(), Some("prod"));
+}
+
+#[test]
+fn test_config_template_is_opt_in() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let config_path = temp_dir.path().join("config.yaml");
+    std::fs::write(&config_path, "greeting: \"{{ name }}\"\n").unwrap();
+
+    let args = vec!["shinkansen", "-c", config_path.to_str().unwrap()];
+    let cli = Cli::try_parse_from(args).unwrap();
+    let variables = collect_variables(&cli).unwrap();
+    assert_eq!(
+        variables.get("greeting").unwrap().as_str(),
+        Some("{{ name }}")
+    );
+
+    assert!(Cli::try_parse_from(["shinkansen", "--config-template"]).is_err());
+}
+
+#[test]
+fn test_dotenv_parsing() {
+    let content = r#"
+# database settings
+export DB_HOST=db.internal  # primary
+DB_PORT = 5432
+GREETING="Hello\n\"world\""
+LITERAL='no \n escapes'
+COMMAND=`echo $HOME`
+CERT="-----BEGIN-----
+abc
+-----END-----"
+EMPTY=
+URL=http://example.com/#anchor
+"#;
+    let source = DotenvSource::parse(Path::new(".env"), content).unwrap();
+    let entries: Vec<(&str, &str)> = source
+        .entries
+        .iter()
+        .map(|(name, value)| (name.as_str(), value.as_str()))
+        .collect();
+    assert_eq!(
+        entries,
+        vec![
+            ("DB_HOST", "db.internal"),
+            ("DB_PORT", "5432"),
+            ("GREETING", "Hello\n\"world\""),
+            ("LITERAL", "no \\n escapes"),
+            ("COMMAND", "echo $HOME"),
+            ("CERT", "-----BEGIN-----\nabc\n-----END-----"),
+            ("EMPTY", ""),
+            ("URL", "http://example.com/#anchor"),
+        ]
+    );
+}
+
+#[test]
+fn test_dotenv_errors_name_the_line() {
+    for (content, message) in [
+        ("A=1\nnot an assignment\n", ".env:2: expected KEY=value"),
+        ("1X=1\n", ".env:1: invalid variable name '1X'"),
+        ("A=\"open\nstill open\n", ".env:1: unterminated \" quote"),
+        (
+            "A='x' y\n",
+            ".env:1: unexpected text after the quoted value of A",
+        ),
+    ] {
+        let err = DotenvSource::parse(Path::new(".env"), content).unwrap_err();
+        assert!(err.to_string().contains(message), "{}: {}", content, err);
+    }
+}
+
+#[test]
+fn test_env_file_precedence_and_types() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let base = temp_dir.path().join(".env");
+    let local = temp_dir.path().join(".env.local");
+    std::fs::write(
+        &base,
+        "REPLICAS=2\nDEBUG=false\nREGION=eu-west-1\nTAG=base\n",
+    )
+    .unwrap();
+    std::fs::write(&local, "TAG=local\n").unwrap();
+
+    let cli = Cli::try_parse_from([
+        "shinkansen".as_ref(),
+        "--env-file".as_ref(),
+        base.as_os_str(),
+        "--env-file".as_ref(),
+        local.as_os_str(),
+        "-D".as_ref(),
+        "REGION=us-east-1".as_ref(),
+    ])
+    .unwrap();
+    let context = collect_variable_context(&cli).unwrap();
+    let variables = &context.values;
+    assert_eq!(variables.get("REPLICAS"), Some(&Value::from(2)));
+    assert_eq!(variables.get("DEBUG"), Some(&Value::from(false)));
+    assert_eq!(variables.get("TAG").unwrap().as_str(), Some("local"));
+    assert_eq!(variables.get("REGION").unwrap().as_str(), Some("us-east-1"));
+    assert_eq!(
+        context.provenance.winner("TAG").unwrap().to_string(),
+        format!("env file {}", local.display())
+    );
+
+    let missing = Cli::try_parse_from(["shinkansen", "--env-file", "/nonexistent/.env"]).unwrap();
+    assert!(collect_variables(&missing).is_err());
+}
+
+#[test]
+fn test_env_all_exposes_environment_as_object() {
+    // TODO: Audit that the environment access only happens in single-threaded code.
+    unsafe { env::set_var("TEST_ENV_ALL_PORT", "8080") };
+
+    let cli = Cli::try_parse_from(["shinkansen", "--env-all"]).unwrap();
+    let context = collect_variable_context(&cli).unwrap();
+    let environment = context.values.get("env").unwrap();
+    // Values stay strings and nothing is added at the top level
+    assert_eq!(
+        environment.get_attr("TEST_ENV_ALL_PORT").unwrap(),
+        Value::from("8080")
+    );
+    assert!(!context.values.contains_key("TEST_ENV_ALL_PORT"));
+    assert_eq!(
+        context.provenance.winner("env").unwrap().to_string(),
+        "--env-all"
+    );
+
+    // A config file or -D can still define `env` itself
+    let cli = Cli::try_parse_from(["shinkansen", "--env-all", "-D", "env=prod"]).unwrap();
+    let variables = collect_variables(&cli).unwrap();
+    assert_eq!(variables.get("env").unwrap().as_str(), Some("prod"));
+
+    // TODO: Audit that the environment access only happens in single-threaded code.
+    unsafe { env::remove_var("TEST_ENV_ALL_PORT") };
+}
+
+#[test]
+fn test_env_prefix_imports_matching_variables() {
+    // TODO: Audit that the environment access only happens in single-threaded code.
+    unsafe {
+        env::set_var("TESTPFX_DB_HOST", "db.internal");
+        env::set_var("TESTPFX_DB_PORT", "5432");
+        env::set_var("TESTPFX_", "ignored when stripped");
+    };
+
+    let cli = Cli::try_parse_from(["shinkansen", "--env-prefix", "TESTPFX_"]).unwrap();
+    let variables = collect_variables(&cli).unwrap();
+    assert_eq!(
+        variables.get("TESTPFX_DB_HOST").unwrap().as_str(),
+        Some("db.internal")
+    );
+    assert_eq!(variables.get("TESTPFX_DB_PORT"), Some(&Value::from(5432)));
+
+    let cli = Cli::try_parse_from([
+        "shinkansen",
+        "--env-prefix",
+        "TESTPFX_",
+        "--strip-env-prefix",
+    ])
+    .unwrap();
+    let context = collect_variable_context(&cli).unwrap();
+    assert_eq!(
+        context.values.get("db_host").unwrap().as_str(),
+        Some("db.internal")
+    );
+    assert_eq!(context.values.get("db_port"), Some(&Value::from(5432)));
+    assert!(!context.values.contains_key("TESTPFX_DB_HOST"));
+    assert!(!context.values.contains_key(""));
+    assert_eq!(
+        context.provenance.winner("db_host").unwrap().to_string(),
+        "env $TESTPFX_DB_HOST"
+    );
+
+    assert!(Cli::try_parse_from(["shinkansen", "--strip-env-prefix"]).is_err());
+    assert!(Cli::try_parse_from(["shinkansen", "--env-prefix", ""]).is_err());
+
+    // TODO: Audit that the environment access only happens in single-threaded code.
+    unsafe {
+        env::remove_var("TESTPFX_DB_HOST");
+        env::remove_var("TESTPFX_DB_PORT");
+        env::remove_var("TESTPFX_");
+    };
+}
+
+#[test]
+fn test_stdin_variables_accept_json_and_yaml() {
+    let mut variables = HashMap::new();
+    collect_stdin_variables(&mut variables, r#"{"data": {"host": "db"}, "replicas": 3}"#).unwrap();
+    assert_eq!(
+        variables.get("data").unwrap().get_attr("host").unwrap(),
+        Value::from("db")
+    );
+    assert_eq!(variables.get("replicas"), Some(&Value::from(3)));
+
+    collect_stdin_variables(&mut variables, "data:\n  port: 5432\nname: web\n").unwrap();
+    let data = variables.get("data").unwrap();
+    assert_eq!(data.get_attr("host").unwrap(), Value::from("db"));
+    assert_eq!(data.get_attr("port").unwrap(), Value::from(5432));
+    assert_eq!(variables.get("name").unwrap().as_str(), Some("web"));
+
+    for content in ["[1, 2]", "just text", "a: [unclosed"] {
+        assert!(
+            collect_stdin_variables(&mut HashMap::new(), content).is_err(),
+            "{}",
+            content
+        );
+    }
+}
+
+#[test]
+fn test_define_reads_values_from_files() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let json = temp_dir.path().join("payload.json");
+    let yaml = temp_dir.path().join("values.yml");
+    let notes = temp_dir.path().join("notes.txt");
+    let data = temp_dir.path().join("data.txt");
+    std::fs::write(&json, r#"{"replicas": 3, "tags": ["a", "b"]}"#).unwrap();
+    std::fs::write(&yaml, "region: eu-west-1\nzones: [a, b]\n").unwrap();
+    std::fs::write(&notes, "key: value, with \"quotes\"\n").unwrap();
+    std::fs::write(&data, "[1, 2]").unwrap();
+
+    let mut variables = HashMap::new();
+    collect_cli_variables(
+        &mut variables,
+        &[
+            format!("config=@{}", json.display()),
+            format!("values=@{}", yaml.display()),
+            format!("notes=@{}", notes.display()),
+            format!("list=@{}", data.display()),
+            "email=\\@example.com".to_string(),
+        ],
+    )
+    .unwrap();
+    let config = variables.get("config").unwrap();
+    assert_eq!(config.get_attr("replicas").unwrap(), Value::from(3));
+    assert_eq!(config.get_attr("tags").unwrap().len(), Some(2));
+    let values = variables.get("values").unwrap();
+    assert_eq!(values.get_attr("region").unwrap(), Value::from("eu-west-1"));
+    assert_eq!(
+        variables.get("notes").unwrap().as_str(),
+        Some("key: value, with \"quotes\"\n")
+    );
+    assert_eq!(variables.get("list").unwrap().len(), Some(2));
+    assert_eq!(
+        variables.get("email").unwrap().as_str(),
+        Some("@example.com")
+    );
+
+    std::fs::write(&json, "{not json").unwrap();
+    let err = collect_cli_variables(
+        &mut HashMap::new(),
+        &[format!("config=@{}", json.display())],
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("Invalid JSON"), "{}", err);
+    let missing = temp_dir.path().join("missing.json");
+    let err = collect_cli_variables(
+        &mut HashMap::new(),
+        &[format!("config=@{}", missing.display())],
+    )
+    .unwrap_err();
+    assert!(
+        err.to_string().contains("Failed to read -D config"),
+        "{}",
+        err
+    );
+}
@@ -0,0 +1,105 @@
+use clap::Parser;
+use shinkansen_lib::cli::Cli;
+use shinkansen_lib::verify::{VerifyCommand, parse_verify_commands, verify_rendered};
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+
+#[test]
+fn test_parse_verify_command_without_extension() {
+    let cmd = VerifyCommand::parse("nginx -t -c {}").unwrap();
+    assert_eq!(cmd.extension, None);
+    assert_eq!(cmd.command, "nginx -t -c {}");
+}
+
+#[test]
+fn test_parse_verify_command_with_extension() {
+    let cmd = VerifyCommand::parse(".service=systemd-analyze verify {}").unwrap();
+    assert_eq!(cmd.extension, Some("service".to_string()));
+    assert_eq!(cmd.command, "systemd-analyze verify {}");
+}
+
+#[test]
+fn test_parse_verify_command_keeps_equals_in_arguments() {
+    let cmd = VerifyCommand::parse("check --mode=strict {}").unwrap();
+    assert_eq!(cmd.extension, None);
+    assert_eq!(cmd.command, "check --mode=strict {}");
+}
+
+#[test]
+fn test_parse_verify_command_empty() {
+    assert!(VerifyCommand::parse(".conf=").is_err());
+    assert!(parse_verify_commands(&["".to_string()]).is_err());
+}
+
+#[test]
+fn test_verify_command_applies_to_extension() {
+    let cmd = VerifyCommand::parse(".conf=true").unwrap();
+    assert!(cmd.applies_to(Path::new("out/nginx.conf")));
+    assert!(!cmd.applies_to(Path::new("out/app.yaml")));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_verify_rendered_success_and_failure() {
+    let ok = parse_verify_commands(&["grep -q hello {}".to_string()]).unwrap();
+    assert!(verify_rendered(&ok, Path::new("app.conf"), "hello world").is_ok());
+
+    let result = verify_rendered(&ok, Path::new("app.conf"), "goodbye");
+    assert!(result.is_err());
+    assert!(
+        result
+            .unwrap_err()
+            .to_string()
+            .contains("Verification failed for app.conf")
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_verify_rendered_skips_other_extensions() {
+    let commands = parse_verify_commands(&[".conf=false".to_string()]).unwrap();
+    assert!(verify_rendered(&commands, Path::new("app.yaml"), "content").is_ok());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_failed_verification_does_not_write_output() {
+    let temp_dir = tempdir().unwrap();
+    let input = temp_dir.path().join("app.conf");
+    let output = temp_dir.path().join("rendered.conf");
+    fs::write(&input, "listen {{ port }};").unwrap();
+
+    let args = vec![
+        "shinkansen",
+        input.to_str().unwrap(),
+        "-D",
+        "port=80",
+        "-o",
+        output.to_str().unwrap(),
+        "--verify-cmd",
+        "grep -q 443 {}",
+    ];
+    let cli = Cli::try_parse_from(args).unwrap();
+    let variables = shinkansen_lib::variables::collect_variables(&cli).unwrap();
+    let result = shinkansen_lib::processing::process_inputs(&cli, &variables, &Default::default());
+
+    assert!(result.is_err());
+    assert!(!output.exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_verify_rendered_does_not_pass_output_path_to_the_shell() {
+    let temp_dir = tempdir().unwrap();
+    let marker = temp_dir.path().join("pwned");
+    let output = temp_dir
+        .path()
+        .join(format!("my app $(touch {}).conf", marker.display()));
+
+    let commands = parse_verify_commands(&["grep -q hello".to_string()]).unwrap();
+    assert!(verify_rendered(&commands, &output, "hello").is_ok());
+    let commands = parse_verify_commands(&["test -f {} && grep -q hello {}".to_string()]).unwrap();
+    assert!(verify_rendered(&commands, &output, "hello").is_ok());
+    assert!(!marker.exists());
+}
@@ -0,0 +1,91 @@
+use clap::Parser;
+use shinkansen_lib::cli::Cli;
+use shinkansen_lib::filters::FILTER_PACKS;
+use shinkansen_lib::version::{VersionInfo, VersionReq};
+
+#[test]
+fn test_version_json_fields() {
+    let info: serde_json::Value = serde_json::from_str(&VersionInfo::current().to_json()).unwrap();
+    assert_eq!(info["version"], env!("CARGO_PKG_VERSION"));
+    assert!(info["git_sha"].is_string() || info["git_sha"].is_null());
+    assert!(info["features"].is_array());
+
+    let formats = info["config_formats"].as_array().unwrap();
+    assert!(formats.contains(&serde_json::json!("yaml")));
+    assert!(formats.contains(&serde_json::json!("toml")));
+
+    let k8s = info["filter_packs"]["k8s"].as_array().unwrap();
+    assert!(k8s.contains(&serde_json::json!("toYaml")));
+}
+
+#[test]
+fn test_version_features_match_build() {
+    let features = VersionInfo::current().features;
+    assert_eq!(features.contains(&"http"), cfg!(feature = "http"));
+    assert_eq!(features.contains(&"sqlite"), cfg!(feature = "sqlite"));
+}
+
+#[test]
+fn test_filter_packs_are_registered() {
+    let mut env = minijinja::Environment::new();
+    shinkansen_lib::filters::register_crypto(&mut env);
+    shinkansen_lib::filters::register_json(&mut env);
+    shinkansen_lib::filters::register_k8s(&mut env);
+    shinkansen_lib::filters::register_net(&mut env);
+    shinkansen_lib::filters::register_sorting(&mut env);
+    shinkansen_lib::filters::register_text(&mut env);
+    for (_, filters) in FILTER_PACKS {
+        for filter in *filters {
+            let template = format!("{{{{ 'a' | {} }}}}", filter);
+            let result = env.render_str(&template, ());
+            assert!(
+                !result
+                    .as_ref()
+                    .is_err_and(|err| err.kind() == minijinja::ErrorKind::UnknownFilter),
+                "{} is not registered",
+                filter
+            );
+        }
+    }
+}
+
+#[test]
+fn test_version_text() {
+    let info = VersionInfo::current();
+    assert!(
+        info.text()
+            .starts_with(&format!("shinkansen {}", env!("CARGO_PKG_VERSION")))
+    );
+}
+
+#[test]
+fn test_json_requires_version() {
+    assert!(Cli::try_parse_from(["shinkansen", "--json"]).is_err());
+    let cli = Cli::try_parse_from(["shinkansen", "--version", "--json"]).unwrap();
+    assert!(cli.version && cli.json);
+    assert!(Cli::try_parse_from(["shinkansen", "-V"]).unwrap().version);
+}
+
+#[test]
+fn test_version_requirements() {
+    let matches =
+        |requirement: &str, version: &str| VersionReq::parse(requirement).unwrap().matches(version);
+    assert!(matches(">=0.2", "0.2.0"));
+    assert!(matches(">=0.2", "1.0.0"));
+    assert!(!matches(">=0.5", "0.2.0"));
+    assert!(matches(">=0.2, <0.4", "0.3.9"));
+    assert!(!matches(">=0.2, <0.4", "0.4.0"));
+    assert!(matches("0.2", "0.2.7"));
+    assert!(!matches("^0.2", "0.3.0"));
+    assert!(matches("^1.2", "1.9.0"));
+    assert!(!matches("^1.2", "2.0.0"));
+    assert!(matches("~1.2", "1.2.5"));
+    assert!(!matches("~1.2", "1.3.0"));
+    assert!(matches("=0.2", "0.2.3"));
+    assert!(!matches("=0.2.1", "0.2.3"));
+    assert!(matches(">0.1.9", "0.2.0-beta"));
+
+    for invalid in ["", ">=", "=>0.2", ">=0.x", "1.2.3.4", ">=0.2,"] {
+        assert!(VersionReq::parse(invalid).is_err(), "{}", invalid);
+    }
+}